@@ -1,4 +1,127 @@
+use std::ffi::OsString;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+/// Environment variable that replaces ~/.opencodex/ as the data directory
+pub const HOME_ENV: &str = "OPENCODEX_HOME";
+
+/// Environment variable that confirms --madmax without an interactive prompt
+pub const CONFIRM_MADMAX_ENV: &str = "OPENCODEX_CONFIRM_MADMAX";
+
+/// Environment variable that pre-registers the owner's Telegram user ID (same as --owner)
+pub const OWNER_ID_ENV: &str = "OPENCODEX_OWNER_ID";
+
+/// Environment variable pointing the bot at a self-hosted Bot API server (same as --api-url)
+pub const API_URL_ENV: &str = "OPENCODEX_API_URL";
+
 /// Returns the dot-prefixed config directory name (~/.opencodex/).
 pub fn dir_name() -> &'static str {
     ".opencodex"
 }
+
+fn data_dir_from(override_dir: Option<OsString>, home: Option<PathBuf>) -> Option<PathBuf> {
+    match override_dir {
+        Some(dir) if !dir.is_empty() => Some(PathBuf::from(dir)),
+        _ => home.map(|h| h.join(dir_name())),
+    }
+}
+
+/// Directory holding settings, sessions and logs: `$OPENCODEX_HOME` if set, otherwise
+/// ~/.opencodex/. None when neither is available, in which case nothing is persisted.
+pub fn data_dir() -> Option<PathBuf> {
+    data_dir_from(std::env::var_os(HOME_ENV), dirs::home_dir())
+}
+
+/// 16 hex characters from /dev/urandom, falling back to the clock, pid and a counter
+fn random_suffix() -> String {
+    use std::sync::atomic::{AtomicU64, Ordering};
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let mut bytes = [0u8; 8];
+    if File::open("/dev/urandom")
+        .and_then(|mut f| f.read_exact(&mut bytes))
+        .is_err()
+    {
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(0);
+        let mixed = nanos
+            ^ (u64::from(std::process::id()) << 32)
+            ^ COUNTER.fetch_add(1, Ordering::Relaxed).rotate_left(17);
+        bytes = mixed.to_le_bytes();
+    }
+    hex::encode(bytes)
+}
+
+/// Create a new file `<prefix><random>.<extension>` in `dir`, readable and writable by the
+/// owner only (0o600). The file must not exist yet and a symlink in its place is never
+/// followed, so a pre-planted name cannot redirect the write.
+pub fn create_private_file(
+    dir: &Path,
+    prefix: &str,
+    extension: &str,
+) -> io::Result<(PathBuf, File)> {
+    let mut last_err = io::Error::from(io::ErrorKind::AlreadyExists);
+    for _ in 0..8 {
+        let path = dir.join(format!("{prefix}{}.{extension}", random_suffix()));
+        let mut options = OpenOptions::new();
+        options.read(true).write(true).create_new(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600).custom_flags(libc::O_NOFOLLOW);
+        }
+        match options.open(&path) {
+            Ok(file) => return Ok((path, file)),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => last_err = e,
+            Err(e) => return Err(e),
+        }
+    }
+    Err(last_err)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_data_dir_override_and_fallback() {
+        let home = Some(PathBuf::from("/home/u"));
+        assert_eq!(
+            data_dir_from(None, home.clone()),
+            Some(PathBuf::from("/home/u/.opencodex"))
+        );
+        assert_eq!(
+            data_dir_from(Some("/srv/oc".into()), None),
+            Some(PathBuf::from("/srv/oc"))
+        );
+        assert_eq!(
+            data_dir_from(Some("".into()), home),
+            Some(PathBuf::from("/home/u/.opencodex"))
+        );
+        assert_eq!(data_dir_from(None, None), None);
+    }
+
+    #[test]
+    fn test_create_private_file() {
+        let dir = std::env::temp_dir();
+        let (first, second) = match (
+            create_private_file(&dir, "opencodex_test_private_", "txt"),
+            create_private_file(&dir, "opencodex_test_private_", "txt"),
+        ) {
+            (Ok((first, _)), Ok((second, _))) => (first, second),
+            other => panic!("failed to create private files: {other:?}"),
+        };
+        assert!(first.exists());
+        assert_ne!(first, second);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&first).map(|m| m.permissions().mode());
+            assert_eq!(mode.map(|m| m & 0o777).ok(), Some(0o600));
+        }
+        let _ = std::fs::remove_file(&first);
+        let _ = std::fs::remove_file(&second);
+    }
+}
@@ -1,10 +1,47 @@
-use std::path::Path;
+use std::path::{Component, Path, PathBuf};
+use std::sync::OnceLock;
+
+/// Chats the bot responds in (`--allow-chat`). Empty means every chat.
+static CHAT_ALLOWLIST: OnceLock<Vec<i64>> = OnceLock::new();
+
+pub fn configure_chat_allowlist(chat_ids: Vec<i64>) {
+    let _ = CHAT_ALLOWLIST.set(chat_ids);
+}
+
+/// Owner set at startup (`--owner` / `OPENCODEX_OWNER_ID`); disables imprinting
+static PRECONFIGURED_OWNER: OnceLock<u64> = OnceLock::new();
+
+pub fn configure_owner(user_id: Option<u64>) {
+    if let Some(user_id) = user_id {
+        let _ = PRECONFIGURED_OWNER.set(user_id);
+    }
+}
+
+/// The owner given at startup, which replaces any owner saved in the settings
+pub fn preconfigured_owner() -> Option<u64> {
+    PRECONFIGURED_OWNER.get().copied()
+}
+
+/// Whether updates from this chat are handled at all.
+/// Checked before imprinting and permission checks, so unknown chats are ignored entirely.
+pub fn is_chat_allowed(chat_id: i64) -> bool {
+    chat_in_allowlist(
+        CHAT_ALLOWLIST.get().map(Vec::as_slice).unwrap_or(&[]),
+        chat_id,
+    )
+}
+
+fn chat_in_allowlist(allowlist: &[i64], chat_id: i64) -> bool {
+    allowlist.is_empty() || allowlist.contains(&chat_id)
+}
 
 /// Permission levels for bot users.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum PermissionLevel {
     /// Bot owner (first user to DM — imprinting auth)
     Owner,
+    /// Non-owner temporarily allowed AI prompts and read-only commands in one chat (/elevate)
+    Elevated,
     /// Public-mode user (non-owner in a group chat with public mode enabled)
     Public,
     /// Denied (non-owner in a private or non-public group)
@@ -33,17 +70,36 @@ pub fn classify_command(command_text: &str) -> CommandRisk {
     let cmd = lower.split_whitespace().next().unwrap_or("");
 
     match cmd {
+        // Re-running the backend's model listing starts a process
+        "/models" if lower.split_whitespace().nth(1) == Some("refresh") => CommandRisk::High,
+
         // Low risk: read-only
-        "/help" | "/pwd" | "/availabletools" => CommandRisk::Low,
+        "/help" | "/pwd" | "/availabletools" | "/ping" | "/version" | "/uptime" | "/models" => {
+            CommandRisk::Low
+        }
 
         // Medium risk: may expose data
-        "/down" | "/allowedtools" => CommandRisk::Medium,
+        "/down" | "/cat" | "/allowedtools" | "/agents" | "/diskusage" | "/raw" | "/lastprompt"
+        | "/output" | "/backup" | "/lasttools" | "/search" | "/errors" => CommandRisk::Medium,
 
         // Critical: admin operations
-        "/stop" | "/clear" | "/start" | "/public" => CommandRisk::Critical,
+        "/stop" | "/clear" | "/start" | "/public" | "/defaultpublic" | "/approval" | "/madmax"
+        | "/reload" | "/restart" | "/restore" | "/elevate" | "/backendargs" | "/shell"
+        | "/uploads" | "/render" | "/recheck" | "/cooldown" | "/cmdcooldown"
+        | "/backupsessions" | "/rescan" | "/mute" | "/mirror" | "/unmute" | "/restoresessions"
+        | "/chats" | "/log" | "/reloadsession" | "/selftest" | "/rawstream" | "/setdefault"
+        | "/config" | "/footer" => CommandRisk::Critical,
 
         // High risk: modifies state
-        "/cd" | "/allowed" => CommandRisk::High,
+        "/cd" | "/alias" | "/cmdalias" | "/downid" | "/startpreview" | "/tooloutput"
+        | "/maxresponse" | "/worktree" | "/allowed" | "/preset" | "/continue" | "/summary"
+        | "/cron" | "/label" | "/pin" | "/unpin" | "/run" | "/prefix" | "/suffix" | "/extract"
+        | "/filter" | "/compact" | "/shellconfirm" | "/replystyle" | "/context" | "/pending"
+        | "/clearconfirm" | "/auditlog" | "/progressfile" | "/verbose" | "/parsemode"
+        | "/promptlimit" | "/format" | "/respondlang" | "/notify" | "/greeting"
+        | "/forwardwait" | "/mediahint" | "/shortpaths" | "/stream" | "/recap" | "/stderr"
+        | "/uploaddir" | "/toolscheck" | "/autodownload" | "/strictcode" | "/typingpause"
+        | "/projecthint" => CommandRisk::High,
 
         _ => {
             // Shell commands (!) are high risk
@@ -60,24 +116,45 @@ pub fn classify_command(command_text: &str) -> CommandRisk {
 /// Check whether a user with the given context can execute a command of the given risk.
 ///
 /// - Owners can execute anything.
+/// - Elevated users can execute Low and Medium-risk commands (plus AI prompts, see
+///   `can_execute_message`).
 /// - Public users can only execute Low-risk commands.
 /// - Denied users cannot execute anything.
 pub fn can_execute(permission: PermissionLevel, risk: CommandRisk) -> bool {
     match permission {
         PermissionLevel::Owner => true,
+        PermissionLevel::Elevated => matches!(risk, CommandRisk::Low | CommandRisk::Medium),
         PermissionLevel::Public => matches!(risk, CommandRisk::Low),
         PermissionLevel::Denied => false,
     }
 }
 
+/// Whether a message is a prompt for the AI rather than a `/` command or `!` shell command
+pub fn is_ai_prompt(command_text: &str) -> bool {
+    !command_text.trim().starts_with(['/', '!'])
+}
+
+/// Check whether a user can send this message. Elevated users may send AI prompts, but
+/// High-risk commands (`!` shell, /cron, /downid, /run …) that run code directly or outlive
+/// the grant stay owner-only.
+pub fn can_execute_message(permission: PermissionLevel, command_text: &str) -> bool {
+    match permission {
+        PermissionLevel::Elevated if is_ai_prompt(command_text) => true,
+        _ => can_execute(permission, classify_command(command_text)),
+    }
+}
+
 /// Determine the permission level for a user in a given context.
+/// `elevated` is true while the user holds an unexpired /elevate grant for the chat.
 pub fn get_permission_level(
     user_id: u64,
     owner_user_id: Option<u64>,
     is_public_chat: bool,
+    elevated: bool,
 ) -> PermissionLevel {
     match owner_user_id {
         Some(owner) if user_id == owner => PermissionLevel::Owner,
+        Some(_) if elevated => PermissionLevel::Elevated,
         Some(_) if is_public_chat => PermissionLevel::Public,
         Some(_) => PermissionLevel::Denied,
         // No owner yet — first user gets owner (imprinting handled elsewhere)
@@ -89,7 +166,6 @@ pub fn get_permission_level(
 ///
 /// Both paths are canonicalized before comparison to prevent traversal attacks
 /// (e.g. `../../etc/passwd`).
-#[allow(dead_code)]
 pub fn is_path_within_sandbox(target: &Path, sandbox_root: &Path) -> bool {
     let Ok(canonical_target) = target.canonicalize() else {
         // If the path doesn't exist yet, resolve the parent
@@ -110,6 +186,35 @@ pub fn is_path_within_sandbox(target: &Path, sandbox_root: &Path) -> bool {
     canonical_target.starts_with(&canonical_root)
 }
 
+/// Resolve `.` and `..` components without touching the filesystem (symlinks are kept).
+fn normalize_lexically(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
+/// Check whether a path names a location inside the sandbox root but resolves outside it,
+/// i.e. a symlink under the root points elsewhere (e.g. `project/logs -> /etc`).
+///
+/// Paths that are outside the root by name are not escapes.
+pub fn is_symlink_escape(requested: &Path, sandbox_root: &Path) -> bool {
+    let lexical = normalize_lexically(requested);
+    let root_lexical = normalize_lexically(sandbox_root);
+    let named_inside = lexical.starts_with(&root_lexical)
+        || sandbox_root
+            .canonicalize()
+            .is_ok_and(|root| lexical.starts_with(root));
+    named_inside && !is_path_within_sandbox(requested, sandbox_root)
+}
+
 /// Maximum file upload size in bytes (50 MB).
 pub const DEFAULT_UPLOAD_LIMIT: u64 = 50 * 1024 * 1024;
 
@@ -123,18 +228,77 @@ mod tests {
         assert_eq!(classify_command("/help"), CommandRisk::Low);
         assert_eq!(classify_command("/pwd"), CommandRisk::Low);
         assert_eq!(classify_command("/availabletools"), CommandRisk::Low);
+        assert_eq!(classify_command("/ping"), CommandRisk::Low);
+        assert_eq!(classify_command("/version"), CommandRisk::Low);
+        assert_eq!(classify_command("/uptime"), CommandRisk::Low);
+        assert_eq!(classify_command("/pending clear"), CommandRisk::High);
+        assert_eq!(classify_command("/autodownload on"), CommandRisk::High);
+        assert_eq!(classify_command("/strictcode on"), CommandRisk::High);
+        assert_eq!(classify_command("/recheck"), CommandRisk::Critical);
+        assert_eq!(classify_command("/config"), CommandRisk::Critical);
+        assert_eq!(
+            classify_command("/setdefault ~/work"),
+            CommandRisk::Critical
+        );
+        assert_eq!(classify_command("/models"), CommandRisk::Low);
+        assert_eq!(classify_command("/models refresh"), CommandRisk::High);
     }
 
     #[test]
     fn test_classify_down_is_medium() {
         assert_eq!(classify_command("/down somefile.txt"), CommandRisk::Medium);
+        assert_eq!(classify_command("/cat app.log tail"), CommandRisk::Medium);
         assert_eq!(classify_command("/allowedtools"), CommandRisk::Medium);
+        assert_eq!(classify_command("/output"), CommandRisk::Medium);
+        assert_eq!(classify_command("/lasttools"), CommandRisk::Medium);
+        assert_eq!(classify_command("/search all schema"), CommandRisk::Medium);
+        assert_eq!(classify_command("/errors 10"), CommandRisk::Medium);
+        assert_eq!(classify_command("/log 100"), CommandRisk::Critical);
+        assert_eq!(classify_command("/reloadsession"), CommandRisk::Critical);
     }
 
     #[test]
     fn test_classify_cd_is_high() {
         assert_eq!(classify_command("/cd /tmp"), CommandRisk::High);
         assert_eq!(classify_command("/allowed add Bash"), CommandRisk::High);
+        assert_eq!(classify_command("/cmdalias add ls /pwd"), CommandRisk::High);
+        assert_eq!(classify_command("/forwardwait on"), CommandRisk::High);
+        assert_eq!(classify_command("/mediahint off"), CommandRisk::High);
+        assert_eq!(classify_command("/shortpaths on"), CommandRisk::High);
+        assert_eq!(classify_command("/stream off"), CommandRisk::High);
+        assert_eq!(classify_command("/typingpause on"), CommandRisk::High);
+        assert_eq!(classify_command("/projecthint on"), CommandRisk::High);
+        assert_eq!(classify_command("/recap on"), CommandRisk::High);
+        assert_eq!(classify_command("/stderr on"), CommandRisk::High);
+        assert_eq!(classify_command("/maxresponse 4000"), CommandRisk::High);
+        assert_eq!(classify_command("/run"), CommandRisk::High);
+        assert_eq!(classify_command("/shell on"), CommandRisk::Critical);
+        assert_eq!(classify_command("/uploads off"), CommandRisk::Critical);
+        assert_eq!(classify_command("/restart"), CommandRisk::Critical);
+        assert_eq!(classify_command("/render **x**"), CommandRisk::Critical);
+        assert_eq!(classify_command("/cooldown 30"), CommandRisk::Critical);
+        assert_eq!(
+            classify_command("/cmdcooldown summary 60"),
+            CommandRisk::Critical
+        );
+        assert_eq!(classify_command("/backupsessions"), CommandRisk::Critical);
+        assert_eq!(classify_command("/rescan"), CommandRisk::Critical);
+        assert_eq!(classify_command("/mute"), CommandRisk::Critical);
+        assert_eq!(classify_command("/mirror -100123"), CommandRisk::Critical);
+        assert_eq!(classify_command("/unmute"), CommandRisk::Critical);
+        assert_eq!(classify_command("/restoresessions"), CommandRisk::Critical);
+        assert_eq!(classify_command("/chats"), CommandRisk::Critical);
+        assert_eq!(classify_command("/selftest"), CommandRisk::Critical);
+        assert_eq!(classify_command("/rawstream on"), CommandRisk::Critical);
+        assert_eq!(classify_command("/footer clear"), CommandRisk::Critical);
+        assert_eq!(classify_command("/shellconfirm on"), CommandRisk::High);
+        assert_eq!(classify_command("/replystyle new"), CommandRisk::High);
+        assert_eq!(classify_command("/context src/main.rs"), CommandRisk::High);
+        assert_eq!(classify_command("/uploaddir uploads"), CommandRisk::High);
+        assert_eq!(classify_command("/format off"), CommandRisk::High);
+        assert_eq!(classify_command("/promptlimit reject"), CommandRisk::High);
+        assert_eq!(classify_command("/respondlang English"), CommandRisk::High);
+        assert_eq!(classify_command("/toolscheck prune"), CommandRisk::High);
     }
 
     #[test]
@@ -147,8 +311,15 @@ mod tests {
     fn test_classify_stop_is_critical() {
         assert_eq!(classify_command("/stop"), CommandRisk::Critical);
         assert_eq!(classify_command("/clear"), CommandRisk::Critical);
+        assert_eq!(classify_command("/elevate 42 30"), CommandRisk::Critical);
+        assert_eq!(
+            classify_command("/backendargs -c model_reasoning_effort=high"),
+            CommandRisk::Critical
+        );
         assert_eq!(classify_command("/start"), CommandRisk::Critical);
         assert_eq!(classify_command("/public"), CommandRisk::Critical);
+        assert_eq!(classify_command("/defaultpublic on"), CommandRisk::Critical);
+        assert_eq!(classify_command("/approval on"), CommandRisk::Critical);
     }
 
     #[test]
@@ -181,7 +352,7 @@ mod tests {
     #[test]
     fn test_get_permission_owner() {
         assert_eq!(
-            get_permission_level(123, Some(123), false),
+            get_permission_level(123, Some(123), false, false),
             PermissionLevel::Owner
         );
     }
@@ -189,7 +360,7 @@ mod tests {
     #[test]
     fn test_get_permission_public() {
         assert_eq!(
-            get_permission_level(456, Some(123), true),
+            get_permission_level(456, Some(123), true, false),
             PermissionLevel::Public
         );
     }
@@ -197,15 +368,42 @@ mod tests {
     #[test]
     fn test_get_permission_denied() {
         assert_eq!(
-            get_permission_level(456, Some(123), false),
+            get_permission_level(456, Some(123), false, false),
             PermissionLevel::Denied
         );
     }
 
+    #[test]
+    fn test_get_permission_elevated() {
+        assert_eq!(
+            get_permission_level(456, Some(123), false, true),
+            PermissionLevel::Elevated
+        );
+        assert_eq!(
+            get_permission_level(123, Some(123), false, true),
+            PermissionLevel::Owner
+        );
+        assert!(can_execute(PermissionLevel::Elevated, CommandRisk::Medium));
+        assert!(!can_execute(PermissionLevel::Elevated, CommandRisk::High));
+        assert!(!can_execute(
+            PermissionLevel::Elevated,
+            CommandRisk::Critical
+        ));
+        let elevated = PermissionLevel::Elevated;
+        assert!(can_execute_message(elevated, "explain this module"));
+        assert!(can_execute_message(elevated, ";fix the test"));
+        assert!(can_execute_message(elevated, "/pwd"));
+        assert!(!can_execute_message(elevated, "!rm -rf target"));
+        assert!(!can_execute_message(elevated, "/cron add 0 * * * * report"));
+        assert!(!can_execute_message(elevated, "/downid abc"));
+        assert!(!can_execute_message(elevated, "/run"));
+        assert!(!can_execute_message(PermissionLevel::Public, "hello"));
+    }
+
     #[test]
     fn test_get_permission_no_owner_imprints() {
         assert_eq!(
-            get_permission_level(789, None, false),
+            get_permission_level(789, None, false, false),
             PermissionLevel::Owner
         );
     }
@@ -247,6 +445,36 @@ mod tests {
         let _ = fs::remove_dir_all(&sandbox);
     }
 
+    #[cfg(unix)]
+    #[test]
+    fn test_symlink_escape_detected() {
+        let tmp = std::env::temp_dir();
+        let sandbox = tmp.join("opencodex_test_sandbox4");
+        let outside = tmp.join("opencodex_test_sandbox4_outside");
+        let _ = fs::create_dir_all(sandbox.join("inner"));
+        let _ = fs::create_dir_all(&outside);
+        let _ = std::os::unix::fs::symlink(&outside, sandbox.join("link"));
+
+        assert!(is_symlink_escape(&sandbox.join("link"), &sandbox));
+        assert!(is_symlink_escape(
+            &sandbox.join("inner").join("..").join("link"),
+            &sandbox
+        ));
+        assert!(!is_symlink_escape(&sandbox.join("inner"), &sandbox));
+        // Outside the root by name: an explicit move, not an escape
+        assert!(!is_symlink_escape(&outside, &sandbox));
+
+        let _ = fs::remove_dir_all(&sandbox);
+        let _ = fs::remove_dir_all(&outside);
+    }
+
+    #[test]
+    fn test_chat_allowlist() {
+        assert!(chat_in_allowlist(&[], 42));
+        assert!(chat_in_allowlist(&[42, -100123], -100123));
+        assert!(!chat_in_allowlist(&[42], 43));
+    }
+
     #[test]
     fn test_upload_limit_is_50mb() {
         assert_eq!(DEFAULT_UPLOAD_LIMIT, 50 * 1024 * 1024);
@@ -1,14 +1,21 @@
-use std::fs::OpenOptions;
+use std::fs::{File, OpenOptions};
 use std::io::{BufRead, BufReader, Read, Write};
+use std::path::PathBuf;
 use std::process::{Command, Stdio};
-use std::sync::mpsc::{self, Sender};
-use std::sync::OnceLock;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex, OnceLock, PoisonError, RwLock};
+use std::thread::JoinHandle;
+use std::time::Duration;
 
 use regex::Regex;
 use serde_json::Value;
 
-/// Cached path to selected AI binary.
-static AI_BINARY_PATH: OnceLock<Option<String>> = OnceLock::new();
+/// Cached path to selected AI binary, resolved on first use and replaced by /rescan.
+static AI_BINARY_PATH: OnceLock<RwLock<Option<String>>> = OnceLock::new();
+
+/// Cached `--version` output of the AI binary (None until it succeeds; cleared by /rescan).
+static AI_BINARY_VERSION: RwLock<Option<String>> = RwLock::new(None);
 
 #[derive(Debug, Clone, Copy, Default)]
 struct ExecutionOptions {
@@ -22,10 +29,48 @@ pub fn configure_execution(use_omx: bool, madmax: bool) {
     let _ = EXECUTION_OPTIONS.set(ExecutionOptions { use_omx, madmax });
 }
 
+/// Retries after a stale `--resume` error (default 1; 0 surfaces the error instead)
+static RESUME_RETRIES: OnceLock<u32> = OnceLock::new();
+
+const DEFAULT_RESUME_RETRIES: u32 = 1;
+
+pub fn configure_resume_retries(retries: u32) {
+    let _ = RESUME_RETRIES.set(retries);
+}
+
+fn resume_retries() -> u32 {
+    *RESUME_RETRIES.get_or_init(|| DEFAULT_RESUME_RETRIES)
+}
+
+/// Stop a backend that sends no output line for this long (None = wait indefinitely)
+static STALL_TIMEOUT: OnceLock<Option<Duration>> = OnceLock::new();
+
+/// Set the stall timeout in seconds (0 disables it)
+pub fn configure_stall_timeout(secs: u64) {
+    let _ = STALL_TIMEOUT.set((secs > 0).then(|| Duration::from_secs(secs)));
+}
+
+pub fn stall_timeout() -> Option<Duration> {
+    *STALL_TIMEOUT.get_or_init(|| None)
+}
+
+/// How often the stdout loop wakes up to check for cancellation and stalls
+const STDOUT_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
 fn execution_options() -> &'static ExecutionOptions {
     EXECUTION_OPTIONS.get_or_init(ExecutionOptions::default)
 }
 
+/// Whether the bot runs with --madmax (all restrictions lifted)
+pub fn is_madmax() -> bool {
+    execution_options().madmax
+}
+
+/// Whether the bot runs with --omx (omx binary instead of codex)
+pub fn uses_omx() -> bool {
+    execution_options().use_omx
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum BackendKind {
     Codex,
@@ -40,7 +85,7 @@ fn backend_kind() -> BackendKind {
     }
 }
 
-fn ai_binary_name() -> &'static str {
+pub(crate) fn ai_binary_name() -> &'static str {
     match backend_kind() {
         BackendKind::Codex => "codex",
         BackendKind::Omx => "omx",
@@ -77,10 +122,219 @@ fn resolve_ai_binary_path() -> Option<String> {
     None
 }
 
-pub(crate) fn get_ai_binary_path() -> Option<&'static str> {
-    AI_BINARY_PATH
-        .get_or_init(resolve_ai_binary_path)
-        .as_deref()
+fn ai_binary_path_cell() -> &'static RwLock<Option<String>> {
+    AI_BINARY_PATH.get_or_init(|| RwLock::new(resolve_ai_binary_path()))
+}
+
+pub(crate) fn get_ai_binary_path() -> Option<String> {
+    ai_binary_path_cell()
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone()
+}
+
+/// Resolve the AI binary again (e.g. after installing it while the bot runs) and replace the
+/// cached path and version. Returns the (previous, new) paths.
+pub(crate) fn rescan_ai_binary_path() -> (Option<String>, Option<String>) {
+    let resolved = resolve_ai_binary_path();
+    let previous = std::mem::replace(
+        &mut *ai_binary_path_cell()
+            .write()
+            .unwrap_or_else(PoisonError::into_inner),
+        resolved.clone(),
+    );
+    *AI_BINARY_VERSION
+        .write()
+        .unwrap_or_else(PoisonError::into_inner) = None;
+    (previous, resolved)
+}
+
+/// Run `<binary> --version` (stdout, or stderr if stdout is empty)
+pub(crate) fn resolve_ai_binary_version() -> Option<String> {
+    let output = Command::new(get_ai_binary_path()?)
+        .arg("--version")
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if !stdout.is_empty() {
+        return Some(stdout);
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+    (!stderr.is_empty()).then_some(stderr)
+}
+
+/// Run `<binary> --version` without the cache to check that the backend still starts
+pub(crate) fn probe_ai_binary() -> bool {
+    resolve_ai_binary_version().is_some()
+}
+
+/// Version of the AI binary, queried once and cached until the next /rescan
+pub(crate) fn get_ai_binary_version() -> Option<String> {
+    if let Some(version) = AI_BINARY_VERSION
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone()
+    {
+        return Some(version);
+    }
+    let version = resolve_ai_binary_version()?;
+    *AI_BINARY_VERSION
+        .write()
+        .unwrap_or_else(PoisonError::into_inner) = Some(version.clone());
+    Some(version)
+}
+
+/// How `/models` finds valid model names (config.json `model_list_command` / `models`)
+#[derive(Debug, Clone, Default)]
+struct ModelListing {
+    /// Arguments for the AI binary that print the available models (empty = not supported)
+    command: Vec<String>,
+    /// Static list used when there is no command or it fails
+    fallback: Vec<String>,
+}
+
+static MODEL_LISTING: OnceLock<ModelListing> = OnceLock::new();
+
+/// Models parsed from the last successful listing command (cleared by `/models refresh`)
+static MODEL_LIST_CACHE: Mutex<Option<Vec<String>>> = Mutex::new(None);
+
+/// The model-listing command is killed after this long
+const MODEL_LIST_TIMEOUT: Duration = Duration::from_secs(15);
+
+pub fn configure_model_listing(command: Vec<String>, fallback: Vec<String>) {
+    let _ = MODEL_LISTING.set(ModelListing { command, fallback });
+}
+
+fn model_listing() -> &'static ModelListing {
+    MODEL_LISTING.get_or_init(ModelListing::default)
+}
+
+/// Where a model list came from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum ModelSource {
+    /// Output of the listing command (the command line is included)
+    Backend(String),
+    /// Same, served from the cache
+    Cached(String),
+    /// The static `models` list from config.json; carries why the command was not used
+    Configured(String),
+}
+
+/// Parse the output of a model-listing command.
+/// Accepts JSON (an array of names or of objects with `id`/`slug`/`name`/`model`, optionally
+/// wrapped in `data`/`models`) or plain text with one model per line (first column; blank
+/// lines, `#` comments and all-caps header lines are skipped).
+fn parse_model_list(output: &str) -> Vec<String> {
+    fn entry_name(value: &Value) -> Option<String> {
+        match value {
+            Value::String(name) => Some(name.clone()),
+            Value::Object(obj) => ["id", "slug", "name", "model"]
+                .iter()
+                .find_map(|key| obj.get(*key).and_then(Value::as_str))
+                .map(str::to_string),
+            _ => None,
+        }
+    }
+
+    let mut models: Vec<String> = match serde_json::from_str::<Value>(output.trim()) {
+        Ok(json) => {
+            let items = json
+                .get("data")
+                .or_else(|| json.get("models"))
+                .unwrap_or(&json);
+            items
+                .as_array()
+                .map(|items| items.iter().filter_map(entry_name).collect())
+                .unwrap_or_default()
+        }
+        Err(_) => output
+            .lines()
+            .map(|line| line.trim().trim_start_matches(['-', '*']).trim())
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .filter_map(|line| line.split_whitespace().next())
+            .filter(|name| name.chars().any(|c| !c.is_ascii_uppercase() && c != '_'))
+            .map(str::to_string)
+            .collect(),
+    };
+    let mut seen = std::collections::HashSet::new();
+    models.retain(|name| !name.trim().is_empty() && seen.insert(name.clone()));
+    models
+}
+
+/// Run `<binary> <command>` with a timeout and parse its stdout
+fn run_model_list_command(command: &[String]) -> Result<Vec<String>, String> {
+    let binary = get_ai_binary_path().ok_or("backend binary not found")?;
+    let mut child = Command::new(binary)
+        .args(command)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| format!("failed to start: {e}"))?;
+
+    let mut stdout = child.stdout.take().ok_or("no stdout")?;
+    let (tx, rx) = mpsc::channel();
+    std::thread::spawn(move || {
+        let mut buf = Vec::new();
+        let _ = stdout.read_to_end(&mut buf);
+        let _ = tx.send(buf);
+    });
+    let output = match rx.recv_timeout(MODEL_LIST_TIMEOUT) {
+        Ok(output) => output,
+        Err(_) => {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(format!("timed out after {}s", MODEL_LIST_TIMEOUT.as_secs()));
+        }
+    };
+    let status = child.wait().map_err(|e| e.to_string())?;
+    if !status.success() {
+        return Err(format!("exited with {status}"));
+    }
+    let models = parse_model_list(&String::from_utf8_lossy(&output));
+    if models.is_empty() {
+        return Err("no models in output".to_string());
+    }
+    Ok(models)
+}
+
+/// Available model names for `/models`: the listing command's output (cached until
+/// `refresh`), falling back to the configured static list. Blocking; call from
+/// `spawn_blocking`.
+pub(crate) fn list_models(refresh: bool) -> Result<(Vec<String>, ModelSource), String> {
+    let listing = model_listing();
+    let command_line = format!("{} {}", ai_binary_name(), listing.command.join(" "));
+    let mut cache = MODEL_LIST_CACHE
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    if refresh {
+        *cache = None;
+    }
+    if let Some(models) = cache.as_ref() {
+        return Ok((models.clone(), ModelSource::Cached(command_line)));
+    }
+
+    let failure = if listing.command.is_empty() {
+        "no model_list_command configured".to_string()
+    } else {
+        match run_model_list_command(&listing.command) {
+            Ok(models) => {
+                *cache = Some(models.clone());
+                return Ok((models, ModelSource::Backend(command_line)));
+            }
+            Err(e) => {
+                debug_log(&format!("model list command failed: {e}"));
+                format!("`{command_line}` failed: {e}")
+            }
+        }
+    };
+    if listing.fallback.is_empty() {
+        return Err(failure);
+    }
+    Ok((listing.fallback.clone(), ModelSource::Configured(failure)))
 }
 
 fn debug_enabled_from_values(primary: Option<&str>, legacy: Option<&str>) -> bool {
@@ -94,19 +348,18 @@ fn debug_enabled_from_env() -> bool {
 }
 
 /// Debug logging helper (active when OPENCLAUDE_DEBUG=1, or legacy COKACDIR_DEBUG=1)
-fn debug_log(msg: &str) {
+pub(crate) fn debug_log(msg: &str) {
     static ENABLED: OnceLock<bool> = OnceLock::new();
     let enabled = ENABLED.get_or_init(debug_enabled_from_env);
     if !*enabled {
         return;
     }
 
-    let Some(home) = dirs::home_dir() else {
+    let Some(data_dir) = crate::app::data_dir() else {
         return;
     };
 
-    let log_path = home
-        .join(crate::app::dir_name())
+    let log_path = data_dir
         .join("debug")
         .join(format!("{}.log", ai_binary_name()));
     if let Some(parent) = log_path.parent() {
@@ -138,8 +391,12 @@ pub enum StreamMessage {
     ToolUse { name: String, input: String },
     /// Tool execution result
     ToolResult { content: String, is_error: bool },
-    /// Background task notification
-    #[allow(dead_code)]
+    /// Line appended by the backend to its progress file (opt-in, see `PROGRESS_FILE_ENV`)
+    Progress { line: String },
+    /// The previous session could not be resumed; the request continues in a new session
+    /// without the earlier context (sent just before the new session's `Init`)
+    SessionReset { previous_session_id: String },
+    /// Background task / team agent notification (OMX multi-agent runs)
     TaskNotification {
         task_id: String,
         status: String,
@@ -150,6 +407,10 @@ pub enum StreamMessage {
         result: String,
         session_id: Option<String>,
     },
+    /// Backend warning that does not end the request (see `BackendNotice::Warning`)
+    Warning { message: String },
+    /// Non-empty stderr of a run that succeeded; may arrive after `Done` (/stderr)
+    Stderr { output: String },
     /// Error
     Error { message: String },
 }
@@ -225,7 +486,8 @@ BASH EXECUTION RULES (MUST FOLLOW):
 - NEVER use interactive flags like -i"#
 }
 
-fn build_full_prompt(
+/// Assemble the prompt sent to the backend: system prompt, tool constraint, then user prompt
+pub fn build_full_prompt(
     prompt: &str,
     system_prompt: Option<&str>,
     allowed_tools: Option<&[String]>,
@@ -255,10 +517,14 @@ fn build_full_prompt(
     sections.join("\n\n")
 }
 
-fn codex_args(session_id: Option<&str>, working_dir: &str) -> Result<Vec<String>, String> {
+fn codex_args(
+    session_id: Option<&str>,
+    working_dir: &str,
+    madmax: bool,
+) -> Result<Vec<String>, String> {
     let mut args = vec!["-C".to_string(), working_dir.to_string()];
 
-    if execution_options().madmax {
+    if madmax {
         args.push("--dangerously-bypass-approvals-and-sandbox".to_string());
     } else {
         args.push("--sandbox".to_string());
@@ -286,12 +552,16 @@ fn codex_args(session_id: Option<&str>, working_dir: &str) -> Result<Vec<String>
     Ok(args)
 }
 
-fn omx_args(session_id: Option<&str>, working_dir: &str) -> Result<Vec<String>, String> {
+fn omx_args(
+    session_id: Option<&str>,
+    working_dir: &str,
+    madmax: bool,
+) -> Result<Vec<String>, String> {
     // Keep OMX invocation direct (`omx ...`) but pass Codex-compatible exec flags.
     // OMX forwards these to Codex while preserving OMX behaviors (team/HUD modes).
     let mut args = vec!["--cd".to_string(), working_dir.to_string()];
 
-    if execution_options().madmax {
+    if madmax {
         // OMX-native madmax alias.
         args.push("--madmax".to_string());
     } else {
@@ -324,13 +594,347 @@ fn backend_args(
     backend: BackendKind,
     session_id: Option<&str>,
     working_dir: &str,
+    madmax: bool,
 ) -> Result<Vec<String>, String> {
     match backend {
-        BackendKind::Codex => codex_args(session_id, working_dir),
-        BackendKind::Omx => omx_args(session_id, working_dir),
+        BackendKind::Codex => codex_args(session_id, working_dir, madmax),
+        BackendKind::Omx => omx_args(session_id, working_dir, madmax),
+    }
+}
+
+/// Per-request backend settings layered on top of the global execution options
+/// (e.g. from a project's `.opencodex.toml`).
+#[derive(Debug, Clone, Default)]
+pub struct BackendOverrides {
+    pub model: Option<String>,
+    /// Point the backend at a progress file via `PROGRESS_FILE_ENV` and tail it (/progressfile)
+    pub progress_file: bool,
+    /// Bypass approvals and the sandbox for this request (/madmax); None follows `--madmax`
+    pub madmax: Option<bool>,
+    /// Extra arguments from /backendargs, already checked by `validate_backend_args`
+    pub extra_args: Vec<String>,
+    /// Copy every raw stdout line here (/rawstream)
+    pub raw_stream: Option<std::sync::Arc<RawStreamLog>>,
+    /// Prepended to the prompt whenever it runs without `resume`, including the fresh-session
+    /// retry after a stale `--resume` (/recap)
+    pub fresh_session_context: Option<String>,
+}
+
+/// Flags /backendargs accepts; each takes exactly one value (`--flag value` or `--flag=value`)
+const ALLOWED_BACKEND_FLAGS: &[&str] =
+    &["-c", "--config", "-p", "--profile", "--enable", "--disable"];
+
+/// Config keys that would undo the sandbox/approval settings the bot chose (see /madmax)
+const FORBIDDEN_CONFIG_KEYS: &[&str] = &["sandbox", "approval", "dangerously"];
+
+/// Config keys `-c`/`--config` may set: model tuning and output only. Anything else (MCP
+/// servers, shell environment, providers, ...) could run or reach things the sandbox does not
+/// cover.
+const ALLOWED_CONFIG_KEYS: &[&str] = &[
+    "model",
+    "model_reasoning_effort",
+    "model_reasoning_summary",
+    "model_verbosity",
+    "model_context_window",
+    "model_max_output_tokens",
+    "model_auto_compact_token_limit",
+    "hide_agent_reasoning",
+    "show_raw_agent_reasoning",
+    "project_doc_max_bytes",
+    "tools.web_search",
+];
+
+/// Most arguments /backendargs stores per chat
+pub(crate) const MAX_BACKEND_ARGS: usize = 16;
+
+/// Longest single /backendargs argument (bytes)
+const MAX_BACKEND_ARG_LEN: usize = 200;
+
+/// Check extra backend arguments (/backendargs): only whitelisted flags with one value each,
+/// no shell metacharacters, no mention of the sandbox or approvals, and `-c` only for the keys
+/// in ALLOWED_CONFIG_KEYS.
+pub(crate) fn validate_backend_args(args: &[String]) -> Result<(), String> {
+    if args.len() > MAX_BACKEND_ARGS {
+        return Err(format!("too many arguments (max {MAX_BACKEND_ARGS})"));
+    }
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let (flag, inline_value) = match arg.split_once('=') {
+            Some((flag, value)) if flag.starts_with("--") => (flag, Some(value)),
+            _ => (arg.as_str(), None),
+        };
+        if !ALLOWED_BACKEND_FLAGS.contains(&flag) {
+            return Err(format!("flag not allowed: {arg}"));
+        }
+        let value = match inline_value {
+            Some(value) => value,
+            None => iter
+                .next()
+                .map(String::as_str)
+                .ok_or_else(|| format!("missing value for {flag}"))?,
+        };
+        if value.is_empty() || value.starts_with('-') || value.len() > MAX_BACKEND_ARG_LEN {
+            return Err(format!("invalid value for {flag}"));
+        }
+        if value
+            .chars()
+            .any(|c| c.is_control() || "`$;&|<>(){}[]\\'\"*?!~#".contains(c))
+        {
+            return Err(format!("shell metacharacters are not allowed: {value}"));
+        }
+        let lower = value.to_lowercase();
+        if FORBIDDEN_CONFIG_KEYS.iter().any(|key| lower.contains(key)) {
+            return Err(format!(
+                "sandbox/approval settings cannot be changed: {value}"
+            ));
+        }
+        if matches!(flag, "-c" | "--config") {
+            let Some((key, _)) = value.split_once('=') else {
+                return Err(format!("{flag} expects key=value: {value}"));
+            };
+            if !ALLOWED_CONFIG_KEYS.contains(&key.trim()) {
+                return Err(format!(
+                    "config key not allowed: {key} (allowed: {})",
+                    ALLOWED_CONFIG_KEYS.join(", ")
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Insert override flags before the `exec` subcommand so both Codex and OMX accept them.
+fn apply_backend_overrides(args: &mut Vec<String>, overrides: &BackendOverrides) {
+    let mut extra = Vec::new();
+    if let Some(model) = overrides.model.as_deref() {
+        extra.push("--model".to_string());
+        extra.push(model.to_string());
+    }
+    extra.extend(overrides.extra_args.iter().cloned());
+    if extra.is_empty() {
+        return;
+    }
+    let exec_pos = args.iter().position(|a| a == "exec").unwrap_or(args.len());
+    args.splice(exec_pos..exec_pos, extra);
+}
+
+/// Capacity of the bounded channel between the backend reader and the Telegram poller
+pub const STREAM_CHANNEL_CAPACITY: usize = 256;
+
+/// Producer side of the stream channel with backpressure handling.
+/// `Text` chunks that don't fit are coalesced into a single pending chunk instead of
+/// queueing; every other message blocks until there is room, so it is never dropped.
+struct StreamSender {
+    inner: SyncSender<StreamMessage>,
+    pending_text: String,
+    /// Session dropped after a stale `--resume`; reported once a different session starts
+    reset_from: Option<String>,
+}
+
+impl StreamSender {
+    fn new(inner: SyncSender<StreamMessage>) -> Self {
+        Self {
+            inner,
+            pending_text: String::new(),
+            reset_from: None,
+        }
+    }
+
+    /// Send a message. Returns Err if the receiver has been dropped.
+    fn send(&mut self, msg: StreamMessage) -> Result<(), ()> {
+        match msg {
+            StreamMessage::Text { content } => {
+                // Keep the line break readers put between separate text messages
+                if !self.pending_text.is_empty() {
+                    self.pending_text.push('\n');
+                }
+                self.pending_text.push_str(&content);
+                self.try_flush()
+            }
+            StreamMessage::Init { session_id } if self.reset_from.is_some() => {
+                if let Some(previous) = self.reset_from.take().filter(|p| *p != session_id) {
+                    self.flush()?;
+                    self.inner
+                        .send(StreamMessage::SessionReset {
+                            previous_session_id: previous,
+                        })
+                        .map_err(|_| ())?;
+                }
+                self.send(StreamMessage::Init { session_id })
+            }
+            other => {
+                self.flush()?;
+                self.inner.send(other).map_err(|_| ())
+            }
+        }
+    }
+
+    /// Hand coalesced text to the channel if there is room, otherwise keep it pending
+    fn try_flush(&mut self) -> Result<(), ()> {
+        if self.pending_text.is_empty() {
+            return Ok(());
+        }
+        let content = std::mem::take(&mut self.pending_text);
+        match self.inner.try_send(StreamMessage::Text { content }) {
+            Ok(()) => Ok(()),
+            Err(TrySendError::Full(StreamMessage::Text { content })) => {
+                self.pending_text = content;
+                Ok(())
+            }
+            Err(TrySendError::Full(_)) => Ok(()),
+            Err(TrySendError::Disconnected(_)) => Err(()),
+        }
+    }
+
+    /// Send coalesced text, waiting for room (keeps ordering before non-text messages)
+    fn flush(&mut self) -> Result<(), ()> {
+        if self.pending_text.is_empty() {
+            return Ok(());
+        }
+        let content = std::mem::take(&mut self.pending_text);
+        self.inner
+            .send(StreamMessage::Text { content })
+            .map_err(|_| ())
+    }
+}
+
+/// Environment variable naming a file the backend may append progress lines to.
+/// Useful for backends that buffer stdout but can report status out-of-band.
+pub const PROGRESS_FILE_ENV: &str = "OPENCODEX_PROGRESS_FILE";
+
+/// Raw stdout lines kept per chat by `RawStreamLog`
+const RAW_STREAM_MAX_LINES: usize = 200;
+
+/// Longest raw line kept; the rest is cut off with a marker
+const RAW_STREAM_MAX_LINE_BYTES: usize = 4000;
+
+/// Ring buffer of the raw JSONL lines the backend printed for the latest request (/rawstream),
+/// including lines the parser ignores
+#[derive(Debug, Default)]
+pub struct RawStreamLog {
+    inner: std::sync::Mutex<RawStreamLines>,
+}
+
+#[derive(Debug, Default)]
+struct RawStreamLines {
+    lines: std::collections::VecDeque<String>,
+    /// Older lines of the current request pushed out of the buffer
+    dropped: usize,
+}
+
+impl RawStreamLog {
+    /// Forget the previous request's lines
+    pub fn start_request(&self) {
+        if let Ok(mut inner) = self.inner.lock() {
+            inner.lines.clear();
+            inner.dropped = 0;
+        }
+    }
+
+    fn push(&self, line: &str) {
+        let Ok(mut inner) = self.inner.lock() else {
+            return;
+        };
+        if inner.lines.len() >= RAW_STREAM_MAX_LINES {
+            inner.lines.pop_front();
+            inner.dropped += 1;
+        }
+        let line = if line.len() > RAW_STREAM_MAX_LINE_BYTES {
+            let mut cut = RAW_STREAM_MAX_LINE_BYTES;
+            while !line.is_char_boundary(cut) {
+                cut -= 1;
+            }
+            format!("{} …[{} bytes cut]", &line[..cut], line.len() - cut)
+        } else {
+            line.to_string()
+        };
+        inner.lines.push_back(line);
+    }
+
+    /// (kept lines, older lines dropped)
+    pub fn snapshot(&self) -> (Vec<String>, usize) {
+        self.inner
+            .lock()
+            .map(|inner| (inner.lines.iter().cloned().collect(), inner.dropped))
+            .unwrap_or_default()
+    }
+}
+
+/// How often the progress file is checked for new lines
+const PROGRESS_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Tails a per-run progress file on a background thread, forwarding each complete line
+/// as `StreamMessage::Progress`. Dropping it reads the remaining lines and deletes the file.
+struct ProgressTail {
+    path: PathBuf,
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl ProgressTail {
+    fn start(sender: SyncSender<StreamMessage>) -> Result<Self, String> {
+        // Random name, created exclusively: a symlink planted in the shared temp dir is not followed
+        let (path, mut file) =
+            crate::app::create_private_file(&std::env::temp_dir(), "opencodex_progress_", "log")
+                .map_err(|e| format!("Failed to create progress file: {}", e))?;
+        let stop = Arc::new(AtomicBool::new(false));
+        let stop_flag = stop.clone();
+
+        let handle = std::thread::spawn(move || {
+            let mut partial = Vec::new();
+            loop {
+                let stopping = stop_flag.load(Ordering::Relaxed);
+                for line in read_progress_lines(&mut file, &mut partial) {
+                    // Best effort: progress lines are dropped rather than blocking the reader
+                    if let Err(TrySendError::Disconnected(_)) =
+                        sender.try_send(StreamMessage::Progress { line })
+                    {
+                        return;
+                    }
+                }
+                if stopping {
+                    return;
+                }
+                std::thread::sleep(PROGRESS_POLL_INTERVAL);
+            }
+        });
+
+        Ok(Self {
+            path,
+            stop,
+            handle: Some(handle),
+        })
     }
 }
 
+impl Drop for ProgressTail {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Read bytes appended since the last call and return the complete, non-empty lines.
+/// An unterminated trailing line is kept in `partial` until its newline arrives.
+fn read_progress_lines(file: &mut File, partial: &mut Vec<u8>) -> Vec<String> {
+    if file.read_to_end(partial).is_err() {
+        return Vec::new();
+    }
+    let Some(last_newline) = partial.iter().rposition(|&b| b == b'\n') else {
+        return Vec::new();
+    };
+    let complete: Vec<u8> = partial.drain(..=last_newline).collect();
+    String::from_utf8_lossy(&complete)
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .map(String::from)
+        .collect()
+}
+
 #[derive(Debug)]
 struct StreamingAttemptOutcome {
     done_sent: bool,
@@ -339,6 +943,8 @@ struct StreamingAttemptOutcome {
     status_code: Option<i32>,
     stderr_output: String,
     emitted_message_count: usize,
+    /// Error items classified as warnings; folded back into the error if the run fails
+    warnings: Vec<String>,
 }
 
 #[derive(Debug)]
@@ -347,29 +953,115 @@ enum StreamingAttemptState {
     Cancelled,
 }
 
-fn is_retryable_resume_error(stderr_output: &str) -> bool {
+/// Built-in stderr phrases that mean a `--resume` session cannot be continued
+const RESUME_ERROR_PATTERNS: &[&str] = &[
+    "failed to resume",
+    "could not resume",
+    "cannot resume",
+    "can't resume",
+    "unable to resume",
+    "invalid value for '--resume'",
+    "invalid value for \"--resume\"",
+    "thread not found",
+    "session not found",
+    "conversation not found",
+    "unknown thread",
+    "unknown session",
+    "no such thread",
+    "no such session",
+    "expired thread",
+    "expired session",
+];
+
+/// How an `error` item from the Codex stream is treated
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BackendNotice {
+    /// Known informational noise; dropped
+    Noise,
+    /// Worth showing, but the request continues
+    Warning,
+    /// Ends the request as failed
+    Error,
+}
+
+/// Built-in starts of error-item messages that are informational noise (lowercase prefixes)
+const NOISE_NOTICE_PREFIXES: &[&str] = &["under-development features enabled"];
+
+/// Built-in starts of error-item messages that are warnings rather than failures (lowercase
+/// prefixes). Only the notice's own opening counts: a real error that merely mentions a
+/// deprecated option must still fail the request.
+const WARNING_NOTICE_PREFIXES: &[&str] = &[
+    "deprecated:",
+    "deprecation warning",
+    "warning:",
+    "reasoning token budget",
+];
+
+/// Extra message prefixes from `suppressed_notices` / `warning_notices` in config.json
+#[derive(Debug, Default)]
+struct NoticePatterns {
+    noise: Vec<String>,
+    warning: Vec<String>,
+}
+
+static EXTRA_NOTICE_PATTERNS: OnceLock<NoticePatterns> = OnceLock::new();
+
+pub fn configure_notice_patterns(noise: Vec<String>, warning: Vec<String>) {
+    let normalize = |patterns: Vec<String>| {
+        patterns
+            .into_iter()
+            .map(|p| p.trim().to_lowercase())
+            .filter(|p| !p.is_empty())
+            .collect()
+    };
+    let _ = EXTRA_NOTICE_PATTERNS.set(NoticePatterns {
+        noise: normalize(noise),
+        warning: normalize(warning),
+    });
+}
+
+/// Classify an error item's message by how it starts; noise prefixes win over warning prefixes
+fn classify_backend_notice(message: &str, extra: &NoticePatterns) -> BackendNotice {
+    let lower = message.trim_start().to_lowercase();
+    let matches = |builtin: &[&str], extra: &[String]| {
+        builtin
+            .iter()
+            .copied()
+            .chain(extra.iter().map(String::as_str))
+            .any(|prefix| lower.starts_with(prefix))
+    };
+    if matches(NOISE_NOTICE_PREFIXES, &extra.noise) {
+        BackendNotice::Noise
+    } else if matches(WARNING_NOTICE_PREFIXES, &extra.warning) {
+        BackendNotice::Warning
+    } else {
+        BackendNotice::Error
+    }
+}
+
+/// Extra phrases from `resume_error_patterns` in config.json (added to the built-ins)
+static EXTRA_RESUME_ERROR_PATTERNS: OnceLock<Vec<String>> = OnceLock::new();
+
+pub fn configure_resume_error_patterns(patterns: Vec<String>) {
+    let patterns = patterns
+        .into_iter()
+        .map(|p| p.trim().to_lowercase())
+        .filter(|p| !p.is_empty())
+        .collect();
+    let _ = EXTRA_RESUME_ERROR_PATTERNS.set(patterns);
+}
+
+/// Why stderr was classified as a stale-resume error: the matching phrase, or
+/// "heuristic" when only the resume-context fallback matched
+fn resume_error_match(stderr_output: &str, extra_patterns: &[String]) -> Option<String> {
     let lower = stderr_output.to_lowercase();
-    let known_patterns = [
-        "failed to resume",
-        "could not resume",
-        "cannot resume",
-        "can't resume",
-        "unable to resume",
-        "invalid value for '--resume'",
-        "invalid value for \"--resume\"",
-        "thread not found",
-        "session not found",
-        "conversation not found",
-        "unknown thread",
-        "unknown session",
-        "no such thread",
-        "no such session",
-        "expired thread",
-        "expired session",
-    ];
-
-    if known_patterns.iter().any(|pattern| lower.contains(pattern)) {
-        return true;
+    if let Some(pattern) = RESUME_ERROR_PATTERNS
+        .iter()
+        .copied()
+        .chain(extra_patterns.iter().map(String::as_str))
+        .find(|pattern| lower.contains(pattern))
+    {
+        return Some(format!("pattern \"{pattern}\""));
     }
 
     let has_resume_context = lower.contains("--resume") || lower.contains("resume ");
@@ -380,22 +1072,90 @@ fn is_retryable_resume_error(stderr_output: &str) -> bool {
         || lower.contains("expired")
         || lower.contains("invalid");
 
-    has_resume_context && has_missing_or_invalid_hint
+    (has_resume_context && has_missing_or_invalid_hint).then(|| "heuristic".to_string())
 }
 
+fn is_retryable_resume_error(stderr_output: &str) -> bool {
+    let extra = EXTRA_RESUME_ERROR_PATTERNS
+        .get()
+        .map_or(&[][..], Vec::as_slice);
+    let matched = resume_error_match(stderr_output, extra);
+    // Logged either way so new backend phrasings can be added to resume_error_patterns
+    debug_log(&format!(
+        "Resume error check: {} | stderr: {}",
+        matched.as_deref().unwrap_or("no match"),
+        stderr_output.trim()
+    ));
+    matched.is_some()
+}
+
+/// Event type fragments that mean the backend is waiting for an answer on stdin
+const INTERACTIVE_PROMPT_MARKERS: &[&str] = &[
+    "approval_request",
+    "elicitation",
+    "request_user_input",
+    "user_input_request",
+];
+
+/// Detect a JSONL event asking the user for input (approval, elicitation, question).
+/// Backends run with `-a never` should not emit these, but some occasionally do.
+/// Heuristic: the event's `type` (or the `type` of its `msg`/`item` payload) contains
+/// one of `INTERACTIVE_PROMPT_MARKERS`. Returns the matching type.
+fn interactive_prompt_kind(json: &Value) -> Option<String> {
+    [Some(json), json.get("msg"), json.get("item")]
+        .into_iter()
+        .flatten()
+        .filter_map(|scope| scope.get("type").and_then(|v| v.as_str()))
+        .find(|t| {
+            let lower = t.to_lowercase();
+            INTERACTIVE_PROMPT_MARKERS.iter().any(|m| lower.contains(m))
+        })
+        .map(String::from)
+}
+
+/// Read one line as raw bytes and decode it lossily, so stray non-UTF-8 bytes from the
+/// backend only spoil that line (it then fails JSON parsing and is skipped) instead of
+/// failing the read. Returns None at EOF.
+fn read_lossy_line(
+    reader: &mut impl BufRead,
+    buf: &mut Vec<u8>,
+) -> std::io::Result<Option<String>> {
+    buf.clear();
+    if reader.read_until(b'\n', buf)? == 0 {
+        return Ok(None);
+    }
+    Ok(Some(String::from_utf8_lossy(buf).into_owned()))
+}
+
+#[allow(clippy::too_many_arguments)]
 fn execute_command_streaming_once(
     ai_bin: &str,
     binary_name: &str,
     args: &[String],
     full_prompt: &str,
     working_dir: &str,
-    sender: &Sender<StreamMessage>,
+    sender: &mut StreamSender,
     cancel_token: Option<std::sync::Arc<CancelToken>>,
+    progress_file: bool,
+    raw_stream: Option<&RawStreamLog>,
 ) -> Result<StreamingAttemptState, String> {
-    let mut child = Command::new(ai_bin)
+    let mut command = Command::new(ai_bin);
+    command
         .args(args)
         .current_dir(working_dir)
-        .env_remove("CLAUDECODE")
+        .env_remove("CLAUDECODE");
+
+    // Stopped (after a final read) and removed when this attempt returns
+    let progress_tail = if progress_file {
+        let tail = ProgressTail::start(sender.inner.clone())?;
+        command.env(PROGRESS_FILE_ENV, &tail.path);
+        debug_log(&format!("Progress file: {}", tail.path.display()));
+        Some(tail)
+    } else {
+        None
+    };
+
+    let mut child = command
         .stdin(Stdio::piped())
         .stdout(Stdio::piped())
         .stderr(Stdio::piped())
@@ -425,17 +1185,33 @@ fn execute_command_streaming_once(
         .ok_or_else(|| "Failed to capture stderr".to_string())?;
 
     let stderr_handle = std::thread::spawn(move || {
-        let mut buf = String::new();
+        let mut buf = Vec::new();
         let mut reader = BufReader::new(stderr);
-        let _ = reader.read_to_string(&mut buf);
-        buf
+        let _ = reader.read_to_end(&mut buf);
+        String::from_utf8_lossy(&buf).into_owned()
+    });
+
+    // Lines are read on a separate thread so a backend that stops writing without
+    // closing stdout can be detected (stall timeout) and cancelled promptly
+    let (line_tx, line_rx) = mpsc::sync_channel(STREAM_CHANNEL_CAPACITY);
+    std::thread::spawn(move || {
+        let mut reader = BufReader::new(stdout);
+        let mut line_buf = Vec::new();
+        loop {
+            let line = read_lossy_line(&mut reader, &mut line_buf).map_err(|e| e.to_string());
+            let eof = matches!(line, Ok(None) | Err(_));
+            if line_tx.send(line).is_err() || eof {
+                break;
+            }
+        }
     });
 
-    let mut reader = BufReader::new(stdout);
-    let mut line_buf = String::new();
+    let stall_limit = stall_timeout();
+    let mut last_line_at = std::time::Instant::now();
     let mut last_session_id: Option<String> = None;
     let mut done_sent = false;
     let mut emitted_message_count: usize = 0;
+    let mut warnings: Vec<String> = Vec::new();
 
     loop {
         if let Some(ref token) = cancel_token {
@@ -447,26 +1223,58 @@ fn execute_command_streaming_once(
             }
         }
 
-        line_buf.clear();
-        let read = reader
-            .read_line(&mut line_buf)
-            .map_err(|e| format!("Failed to read {} output: {}", binary_name, e))?;
-
-        if read == 0 {
-            break;
-        }
+        let line = match line_rx.recv_timeout(STDOUT_POLL_INTERVAL) {
+            Ok(Ok(Some(line))) => line,
+            Ok(Ok(None)) | Err(mpsc::RecvTimeoutError::Disconnected) => break,
+            Ok(Err(e)) => return Err(format!("Failed to read {} output: {}", binary_name, e)),
+            Err(mpsc::RecvTimeoutError::Timeout) => {
+                match stall_limit.filter(|limit| last_line_at.elapsed() >= *limit) {
+                    Some(limit) => {
+                        debug_log("No output within the stall timeout — killing AI process");
+                        let _ = child.kill();
+                        let _ = child.wait();
+                        return Err(format!(
+                            "{} sent no output for {}s and was stopped as unresponsive \
+                             (--stall-timeout)",
+                            binary_name,
+                            limit.as_secs()
+                        ));
+                    }
+                    None => continue,
+                }
+            }
+        };
+        last_line_at = std::time::Instant::now();
 
-        let line = line_buf.trim();
+        let line = line.trim();
         if line.is_empty() {
             continue;
         }
 
         debug_log(&format!("line: {}", line));
+        if let Some(raw_stream) = raw_stream {
+            raw_stream.push(line);
+        }
 
         let Ok(json) = serde_json::from_str::<Value>(line) else {
             continue;
         };
 
+        // stdin is closed after the prompt (the backend reads it until EOF), so a request
+        // for input can never be answered: stop now instead of waiting forever
+        if let Some(kind) = interactive_prompt_kind(&json) {
+            debug_log(&format!(
+                "Interactive prompt event ({kind}) — killing AI process"
+            ));
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(format!(
+                "{} asked for interactive input ({}), which cannot be answered from Telegram. \
+                 The request was stopped.",
+                binary_name, kind
+            ));
+        }
+
         let parsed = parse_codex_stream_line(&json);
         for mut msg in parsed {
             match &mut msg {
@@ -485,8 +1293,12 @@ fn execute_command_streaming_once(
                 StreamMessage::Text { .. }
                 | StreamMessage::ToolUse { .. }
                 | StreamMessage::ToolResult { .. }
+                | StreamMessage::Progress { .. }
+                | StreamMessage::SessionReset { .. }
                 | StreamMessage::TaskNotification { .. }
+                | StreamMessage::Stderr { .. }
                 | StreamMessage::Error { .. } => {}
+                StreamMessage::Warning { message } => warnings.push(message.clone()),
             }
 
             if sender.send(msg).is_err() {
@@ -511,6 +1323,8 @@ fn execute_command_streaming_once(
         .wait()
         .map_err(|e| format!("{} process wait failed: {}", binary_name, e))?;
     let stderr_output = stderr_handle.join().unwrap_or_else(|_| "".to_string());
+    // Forward lines written just before exit ahead of the Done message
+    drop(progress_tail);
 
     Ok(StreamingAttemptState::Completed(StreamingAttemptOutcome {
         done_sent,
@@ -519,68 +1333,83 @@ fn execute_command_streaming_once(
         status_code: status.code(),
         stderr_output,
         emitted_message_count,
+        warnings,
     }))
 }
 
 /// Execute a command using the selected AI backend (Codex by default, OMX with --omx)
-#[allow(dead_code)]
 pub fn execute_command(
     prompt: &str,
     session_id: Option<&str>,
     working_dir: &str,
     allowed_tools: Option<&[String]>,
+    overrides: &BackendOverrides,
 ) -> CodexResponse {
-    let (tx, rx) = mpsc::channel();
-
-    let run_result = execute_command_streaming(
-        prompt,
-        session_id,
-        working_dir,
-        tx,
-        None,
-        allowed_tools,
-        None,
-    );
-
-    if let Err(e) = run_result {
-        return CodexResponse {
-            success: false,
-            response: None,
-            session_id: None,
-            error: Some(e),
-        };
-    }
+    let (tx, rx) = mpsc::sync_channel(STREAM_CHANNEL_CAPACITY);
 
     let mut response = String::new();
     let mut final_session_id = session_id.map(String::from);
     let mut saw_error: Option<String> = None;
 
-    for msg in rx {
-        match msg {
-            StreamMessage::Init { session_id } => {
-                final_session_id = Some(session_id);
-            }
-            StreamMessage::Text { content } => {
-                if !response.is_empty() {
-                    response.push('\n');
+    // The channel is bounded, so messages must be drained while the backend runs
+    let run_result = std::thread::scope(|scope| {
+        let producer = scope.spawn(move || {
+            execute_command_streaming(
+                prompt,
+                session_id,
+                working_dir,
+                tx,
+                None,
+                allowed_tools,
+                overrides,
+                None,
+            )
+        });
+
+        for msg in rx {
+            match msg {
+                StreamMessage::Init { session_id } => {
+                    final_session_id = Some(session_id);
                 }
-                response.push_str(&content);
-            }
-            StreamMessage::Done { result, session_id } => {
-                if response.trim().is_empty() && !result.trim().is_empty() {
-                    response = result;
+                StreamMessage::Text { content } => {
+                    if !response.is_empty() {
+                        response.push('\n');
+                    }
+                    response.push_str(&content);
                 }
-                if session_id.is_some() {
-                    final_session_id = session_id;
+                StreamMessage::Done { result, session_id } => {
+                    if response.trim().is_empty() && !result.trim().is_empty() {
+                        response = result;
+                    }
+                    if session_id.is_some() {
+                        final_session_id = session_id;
+                    }
                 }
+                StreamMessage::Error { message } => {
+                    saw_error = Some(message);
+                }
+                StreamMessage::ToolUse { .. }
+                | StreamMessage::ToolResult { .. }
+                | StreamMessage::Progress { .. }
+                | StreamMessage::SessionReset { .. }
+                | StreamMessage::TaskNotification { .. }
+                | StreamMessage::Warning { .. }
+                | StreamMessage::Stderr { .. } => {}
             }
-            StreamMessage::Error { message } => {
-                saw_error = Some(message);
-            }
-            StreamMessage::ToolUse { .. }
-            | StreamMessage::ToolResult { .. }
-            | StreamMessage::TaskNotification { .. } => {}
         }
+
+        producer
+            .join()
+            .unwrap_or_else(|_| Err("Streaming thread panicked".to_string()))
+    });
+
+    if let Err(e) = run_result {
+        return CodexResponse {
+            success: false,
+            response: None,
+            session_id: None,
+            error: Some(e),
+        };
     }
 
     if let Some(error) = saw_error {
@@ -633,13 +1462,15 @@ pub fn is_ai_supported() -> bool {
 /// Execute a command using the selected AI backend with streaming JSON output.
 /// If `system_prompt` is None, uses the default system prompt.
 /// If `system_prompt` is Some(""), no system prompt is prepended.
+#[allow(clippy::too_many_arguments)]
 pub fn execute_command_streaming(
     prompt: &str,
     session_id: Option<&str>,
     working_dir: &str,
-    sender: Sender<StreamMessage>,
+    sender: SyncSender<StreamMessage>,
     system_prompt: Option<&str>,
     allowed_tools: Option<&[String]>,
+    overrides: &BackendOverrides,
     cancel_token: Option<std::sync::Arc<CancelToken>>,
 ) -> Result<(), String> {
     debug_log("========================================");
@@ -658,23 +1489,38 @@ pub fn execute_command_streaming(
     let full_prompt = build_full_prompt(prompt, system_prompt, allowed_tools);
     debug_log(&format!("Prompt length: {}", full_prompt.len()));
     let mut attempt_session_id = session_id.map(String::from);
-    let mut retried_without_resume = false;
+    let mut resume_failures = 0;
+    let mut sender = StreamSender::new(sender);
 
     loop {
-        let args = backend_args(backend, attempt_session_id.as_deref(), working_dir)?;
+        let madmax = overrides.madmax.unwrap_or(execution_options().madmax);
+        let mut args = backend_args(backend, attempt_session_id.as_deref(), working_dir, madmax)?;
+        apply_backend_overrides(&mut args, overrides);
 
         debug_log(&format!("Command: {}", ai_bin));
         debug_log(&format!("Backend: {:?}", backend));
         debug_log(&format!("Args: {:?}", args));
 
+        // A new session knows nothing of the conversation so far; give it the recap
+        let fresh_prompt = match (&attempt_session_id, &overrides.fresh_session_context) {
+            (None, Some(context)) => Some(build_full_prompt(
+                &format!("{context}\n\n{prompt}"),
+                system_prompt,
+                allowed_tools,
+            )),
+            _ => None,
+        };
+
         let attempt = execute_command_streaming_once(
-            ai_bin,
+            &ai_bin,
             binary_name,
             &args,
-            &full_prompt,
+            fresh_prompt.as_deref().unwrap_or(&full_prompt),
             working_dir,
-            &sender,
+            &mut sender,
             cancel_token.clone(),
+            overrides.progress_file,
+            overrides.raw_stream.as_deref(),
         )?;
 
         let StreamingAttemptState::Completed(outcome) = attempt else {
@@ -683,16 +1529,26 @@ pub fn execute_command_streaming(
 
         if !outcome.status_success
             && attempt_session_id.is_some()
-            && !retried_without_resume
+            && resume_failures < resume_retries()
             && outcome.emitted_message_count == 0
             && is_retryable_resume_error(&outcome.stderr_output)
         {
-            let stale = attempt_session_id.as_deref().unwrap_or_default();
+            resume_failures += 1;
+            // Earlier retries keep --resume in case the failure was transient;
+            // the last one gives up on the session and starts fresh
+            if resume_failures < resume_retries() {
+                debug_log(&format!(
+                    "Resume failed (attempt {resume_failures}/{}). Retrying with resume.",
+                    resume_retries()
+                ));
+                std::thread::sleep(Duration::from_secs(1));
+                continue;
+            }
+            let stale = attempt_session_id.take().unwrap_or_default();
             debug_log(&format!(
                 "Detected stale --resume session ({stale}). Retrying without resume."
             ));
-            attempt_session_id = None;
-            retried_without_resume = true;
+            sender.reset_from = Some(stale);
             continue;
         }
 
@@ -702,7 +1558,19 @@ pub fn execute_command_streaming(
             } else {
                 format!("{} exited with code {:?}", binary_name, outcome.status_code)
             };
+            // Notices taken for warnings may be what made the run fail: report them with it
+            let message = outcome
+                .warnings
+                .iter()
+                .map(String::as_str)
+                .chain([message.as_str()])
+                .collect::<Vec<_>>()
+                .join("\n");
             let _ = sender.send(StreamMessage::Error { message });
+        } else if !outcome.stderr_output.trim().is_empty() {
+            let _ = sender.send(StreamMessage::Stderr {
+                output: outcome.stderr_output.trim().to_string(),
+            });
         }
 
         if !outcome.done_sent {
@@ -722,6 +1590,44 @@ pub fn execute_command_streaming(
     Ok(())
 }
 
+/// First non-empty string among `keys`, looked up on `json` and then on its nested
+/// `task`/`agent` object (OMX team events carry details in either place).
+fn first_str_field(json: &Value, keys: &[&str]) -> Option<String> {
+    let scopes = [Some(json), json.get("task"), json.get("agent")];
+    scopes.into_iter().flatten().find_map(|scope| {
+        keys.iter().find_map(|key| {
+            scope
+                .get(key)
+                .and_then(|v| v.as_str())
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(String::from)
+        })
+    })
+}
+
+/// Parse an OMX team/HUD agent status event into a task notification.
+/// Returns None when the event carries neither a status nor a summary.
+fn parse_task_notification(json: &Value) -> Option<StreamMessage> {
+    let task_id = first_str_field(json, &["task_id", "agent_id", "worker_id", "id", "name"])
+        .unwrap_or_default();
+    let status = first_str_field(json, &["status", "state"]).unwrap_or_default();
+    let summary = first_str_field(json, &["summary", "message", "description", "title"]);
+
+    let summary = match summary {
+        Some(summary) => summary,
+        None if status.is_empty() => return None,
+        None if task_id.is_empty() => status.clone(),
+        None => format!("{task_id}: {status}"),
+    };
+
+    Some(StreamMessage::TaskNotification {
+        task_id,
+        status,
+        summary,
+    })
+}
+
 /// Parse one Codex/OMX JSONL event line into zero or more StreamMessage values.
 fn parse_codex_stream_line(json: &Value) -> Vec<StreamMessage> {
     let mut messages = Vec::new();
@@ -732,15 +1638,21 @@ fn parse_codex_stream_line(json: &Value) -> Vec<StreamMessage> {
 
     match event_type {
         // OMX (Claude-compatible) stream-json init event
-        "system" => {
-            if json.get("subtype").and_then(|v| v.as_str()) == Some("init") {
-                if let Some(session_id) = json.get("session_id").and_then(|v| v.as_str()) {
-                    messages.push(StreamMessage::Init {
-                        session_id: session_id.to_string(),
-                    });
-                }
+        "system" if json.get("subtype").and_then(|v| v.as_str()) == Some("init") => {
+            if let Some(session_id) = json.get("session_id").and_then(|v| v.as_str()) {
+                messages.push(StreamMessage::Init {
+                    session_id: session_id.to_string(),
+                });
             }
         }
+        // OMX (Claude-compatible) stream-json background task notification
+        "system" if json.get("subtype").and_then(|v| v.as_str()) == Some("task_notification") => {
+            messages.extend(parse_task_notification(json));
+        }
+        // OMX team/HUD multi-agent status events (e.g. "team.task.updated", "hud.agent.status")
+        t if t.starts_with("team.") || t.starts_with("hud.") => {
+            messages.extend(parse_task_notification(json));
+        }
         // OMX (Claude-compatible) stream-json assistant event
         "assistant" => {
             if let Some(content) = json
@@ -886,6 +1798,18 @@ fn parse_codex_stream_line(json: &Value) -> Vec<StreamMessage> {
                             messages.push(StreamMessage::ToolResult { content, is_error });
                         }
                     }
+                    // Files written by the agent's patch tool: reported as an `apply_patch`
+                    // tool use carrying the changed paths (/autodownload)
+                    Some("file_change")
+                        if item.get("status").and_then(|v| v.as_str()) != Some("failed") =>
+                    {
+                        if let Some(changes) = item.get("changes").filter(|v| v.is_array()) {
+                            messages.push(StreamMessage::ToolUse {
+                                name: "apply_patch".to_string(),
+                                input: serde_json::json!({ "changes": changes }).to_string(),
+                            });
+                        }
+                    }
                     Some("error") => {
                         let message = item
                             .get("message")
@@ -894,11 +1818,19 @@ fn parse_codex_stream_line(json: &Value) -> Vec<StreamMessage> {
                             .trim()
                             .to_string();
 
-                        // Ignore local unstable-feature warning noise.
-                        if !message.is_empty()
-                            && !message.contains("Under-development features enabled")
-                        {
-                            messages.push(StreamMessage::Error { message });
+                        if !message.is_empty() {
+                            let extra = EXTRA_NOTICE_PATTERNS.get_or_init(Default::default);
+                            match classify_backend_notice(&message, extra) {
+                                BackendNotice::Noise => {
+                                    debug_log(&format!("Suppressed backend notice: {message}"));
+                                }
+                                BackendNotice::Warning => {
+                                    messages.push(StreamMessage::Warning { message });
+                                }
+                                BackendNotice::Error => {
+                                    messages.push(StreamMessage::Error { message });
+                                }
+                            }
                         }
                     }
                     _ => {}
@@ -966,6 +1898,21 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn test_resume_error_match_extra_patterns() {
+        let extra = vec!["rollout file missing".to_string()];
+        let stderr = "Error: Rollout file missing for id 42";
+        assert_eq!(resume_error_match(stderr, &[]), None);
+        assert_eq!(
+            resume_error_match(stderr, &extra).as_deref(),
+            Some("pattern \"rollout file missing\"")
+        );
+        assert_eq!(
+            resume_error_match("resume id abc does not exist", &extra).as_deref(),
+            Some("heuristic")
+        );
+    }
+
     #[test]
     fn test_retryable_resume_error_negative_patterns() {
         assert!(!is_retryable_resume_error(
@@ -976,6 +1923,127 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn test_stream_sender_coalesces_text_under_pressure() {
+        let (tx, rx) = mpsc::sync_channel(2);
+        let mut sender = StreamSender::new(tx);
+        for chunk in ["a", "b", "c", "d"] {
+            assert!(sender
+                .send(StreamMessage::Text {
+                    content: chunk.to_string(),
+                })
+                .is_ok());
+        }
+        // Channel holds "a" and "b"; the overflow is merged instead of queued
+        assert_eq!(sender.pending_text, "c\nd");
+
+        let receiver = std::thread::spawn(move || rx.into_iter().collect::<Vec<_>>());
+        assert!(sender
+            .send(StreamMessage::Done {
+                result: String::new(),
+                session_id: None,
+            })
+            .is_ok());
+        drop(sender);
+
+        let received = receiver.join().expect("receiver thread should finish");
+        let text: String = received
+            .iter()
+            .filter_map(|m| match m {
+                StreamMessage::Text { content } => Some(content.as_str()),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        // Same text a reader joining the messages line by line gets without backpressure
+        assert_eq!(text, "a\nb\nc\nd");
+        assert_eq!(received.len(), 4);
+        assert!(matches!(received.last(), Some(StreamMessage::Done { .. })));
+    }
+
+    #[test]
+    fn test_interactive_prompt_kind() {
+        let approval = parse_json(r#"{"type":"exec_approval_request","command":["rm","-rf","x"]}"#);
+        assert_eq!(
+            interactive_prompt_kind(&approval).as_deref(),
+            Some("exec_approval_request")
+        );
+        let nested = parse_json(r#"{"id":"1","msg":{"type":"elicitation_request"}}"#);
+        assert_eq!(
+            interactive_prompt_kind(&nested).as_deref(),
+            Some("elicitation_request")
+        );
+        let normal = parse_json(r#"{"type":"item.completed","item":{"type":"agent_message"}}"#);
+        assert_eq!(interactive_prompt_kind(&normal), None);
+    }
+
+    #[test]
+    fn test_parse_model_list() {
+        assert_eq!(
+            parse_model_list(
+                "NAME        CONTEXT\ngpt-5-codex  400k\n\n# legacy\n- o4-mini\ngpt-5-codex\n"
+            ),
+            vec!["gpt-5-codex", "o4-mini"]
+        );
+        assert_eq!(
+            parse_model_list(r#"{"data":[{"id":"gpt-5"},{"slug":"o3"},{"other":1}]}"#),
+            vec!["gpt-5", "o3"]
+        );
+        assert_eq!(parse_model_list(r#"["a","b"]"#), vec!["a", "b"]);
+        assert!(parse_model_list("").is_empty());
+    }
+
+    #[test]
+    fn test_read_lossy_line_survives_invalid_utf8() {
+        let input: &[u8] = b"{\"type\":\"a\"}\n\xff\xfe{\"bad\n{\"type\":\"b\"}";
+        let mut reader = BufReader::new(input);
+        let mut buf = Vec::new();
+        let mut lines = Vec::new();
+        while let Some(line) = read_lossy_line(&mut reader, &mut buf).expect("read") {
+            lines.push(line);
+        }
+        assert_eq!(lines.len(), 3);
+        assert!(serde_json::from_str::<Value>(lines[0].trim()).is_ok());
+        assert!(lines[1].contains('\u{FFFD}'));
+        assert!(serde_json::from_str::<Value>(lines[1].trim()).is_err());
+        assert_eq!(lines[2], "{\"type\":\"b\"}");
+    }
+
+    #[test]
+    fn test_stream_sender_reports_session_reset() {
+        let (tx, rx) = mpsc::sync_channel(8);
+        let mut sender = StreamSender::new(tx);
+        sender.reset_from = Some("old".to_string());
+        for sid in ["new", "new"] {
+            assert!(sender
+                .send(StreamMessage::Init {
+                    session_id: sid.to_string(),
+                })
+                .is_ok());
+        }
+        drop(sender);
+
+        let received: Vec<_> = rx.into_iter().collect();
+        assert_eq!(received.len(), 3);
+        assert!(matches!(
+            &received[0],
+            StreamMessage::SessionReset { previous_session_id } if previous_session_id == "old"
+        ));
+
+        // Resuming the same session again is not a reset
+        let (tx, rx) = mpsc::sync_channel(8);
+        let mut sender = StreamSender::new(tx);
+        sender.reset_from = Some("same".to_string());
+        let _ = sender.send(StreamMessage::Init {
+            session_id: "same".to_string(),
+        });
+        drop(sender);
+        assert!(matches!(
+            rx.into_iter().collect::<Vec<_>>().as_slice(),
+            [StreamMessage::Init { .. }]
+        ));
+    }
+
     #[test]
     fn test_parse_thread_started() {
         let json = parse_json(r#"{"type":"thread.started","thread_id":"thread-123"}"#);
@@ -1002,6 +2070,67 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_parse_omx_task_notification() {
+        let json = parse_json(
+            r#"{"type":"system","subtype":"task_notification","task_id":"task-7","status":"completed","summary":"Refactored parser"}"#,
+        );
+        let msgs = parse_codex_stream_line(&json);
+        assert_eq!(msgs.len(), 1);
+        match &msgs[0] {
+            StreamMessage::TaskNotification {
+                task_id,
+                status,
+                summary,
+            } => {
+                assert_eq!(task_id, "task-7");
+                assert_eq!(status, "completed");
+                assert_eq!(summary, "Refactored parser");
+            }
+            _ => panic!("expected task notification"),
+        }
+    }
+
+    #[test]
+    fn test_parse_omx_team_event_nested() {
+        let json = parse_json(
+            r#"{"type":"team.task.updated","team":"alpha","task":{"id":"2","state":"in_progress","title":"Write tests"}}"#,
+        );
+        let msgs = parse_codex_stream_line(&json);
+        assert_eq!(msgs.len(), 1);
+        match &msgs[0] {
+            StreamMessage::TaskNotification {
+                task_id,
+                status,
+                summary,
+            } => {
+                assert_eq!(task_id, "2");
+                assert_eq!(status, "in_progress");
+                assert_eq!(summary, "Write tests");
+            }
+            _ => panic!("expected task notification"),
+        }
+    }
+
+    #[test]
+    fn test_parse_omx_hud_agent_status_without_summary() {
+        let json = parse_json(
+            r#"{"type":"hud.agent.status","agent":{"name":"worker-1","status":"idle"}}"#,
+        );
+        let msgs = parse_codex_stream_line(&json);
+        assert_eq!(msgs.len(), 1);
+        match &msgs[0] {
+            StreamMessage::TaskNotification { summary, .. } => {
+                assert_eq!(summary, "worker-1: idle")
+            }
+            _ => panic!("expected task notification"),
+        }
+
+        // Heartbeats without status or summary are dropped
+        let json = parse_json(r#"{"type":"hud.tick","team":"alpha"}"#);
+        assert!(parse_codex_stream_line(&json).is_empty());
+    }
+
     #[test]
     fn test_parse_omx_assistant_text() {
         let json = parse_json(
@@ -1096,6 +2225,27 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_parse_file_change() {
+        let json = parse_json(
+            r#"{"type":"item.completed","item":{"type":"file_change","changes":[{"path":"/tmp/a.rs","kind":"add"}],"status":"completed"}}"#,
+        );
+        let msgs = parse_codex_stream_line(&json);
+        assert_eq!(msgs.len(), 1);
+        match &msgs[0] {
+            StreamMessage::ToolUse { name, input } => {
+                assert_eq!(name, "apply_patch");
+                assert!(input.contains("/tmp/a.rs"));
+            }
+            _ => panic!("expected tool use message"),
+        }
+
+        let failed = parse_json(
+            r#"{"type":"item.completed","item":{"type":"file_change","changes":[{"path":"/tmp/a.rs","kind":"add"}],"status":"failed"}}"#,
+        );
+        assert!(parse_codex_stream_line(&failed).is_empty());
+    }
+
     #[test]
     fn test_parse_command_completed_error() {
         let json = parse_json(
@@ -1121,6 +2271,58 @@ mod tests {
         assert!(msgs.is_empty());
     }
 
+    #[test]
+    fn test_classify_backend_notice() {
+        let none = NoticePatterns::default();
+        assert_eq!(
+            classify_backend_notice("Under-development features enabled: x", &none),
+            BackendNotice::Noise
+        );
+        assert_eq!(
+            classify_backend_notice("Deprecated: flag --foo, use --bar", &none),
+            BackendNotice::Warning
+        );
+        assert_eq!(
+            classify_backend_notice("Reasoning token budget exceeded, answer shortened", &none),
+            BackendNotice::Warning
+        );
+        // A real error that only mentions deprecation stays an error
+        assert_eq!(
+            classify_backend_notice("Model gpt-x is deprecated and was removed", &none),
+            BackendNotice::Error
+        );
+        assert_eq!(
+            classify_backend_notice("failed to run", &none),
+            BackendNotice::Error
+        );
+
+        let extra = NoticePatterns {
+            noise: vec!["telemetry".to_string()],
+            warning: vec!["rate limit".to_string(), "deprecated".to_string()],
+        };
+        assert_eq!(
+            classify_backend_notice("Telemetry upload failed", &extra),
+            BackendNotice::Noise
+        );
+        assert_eq!(
+            classify_backend_notice("Rate limit approaching", &extra),
+            BackendNotice::Warning
+        );
+        assert_eq!(
+            classify_backend_notice("Upload failed: telemetry endpoint down", &extra),
+            BackendNotice::Error
+        );
+        // Noise wins when both match
+        let both = NoticePatterns {
+            noise: vec!["telemetry".to_string()],
+            warning: vec!["telemetry".to_string()],
+        };
+        assert_eq!(
+            classify_backend_notice("telemetry setting ignored", &both),
+            BackendNotice::Noise
+        );
+    }
+
     #[test]
     fn test_parse_real_error_forwarded() {
         let json = parse_json(
@@ -1196,7 +2398,7 @@ mod tests {
 
     #[test]
     fn test_codex_args_default_session() {
-        let args = codex_args(None, "/tmp/project").expect("args should build");
+        let args = codex_args(None, "/tmp/project", false).expect("args should build");
         assert_eq!(
             args,
             vec![
@@ -1216,7 +2418,7 @@ mod tests {
 
     #[test]
     fn test_codex_args_resume_session() {
-        let args = codex_args(Some("session-1"), "/tmp/project").expect("args should build");
+        let args = codex_args(Some("session-1"), "/tmp/project", false).expect("args should build");
         assert_eq!(
             args,
             vec![
@@ -1237,7 +2439,7 @@ mod tests {
 
     #[test]
     fn test_omx_args_default_session() {
-        let args = omx_args(None, "/tmp/project").expect("args should build");
+        let args = omx_args(None, "/tmp/project", false).expect("args should build");
         assert_eq!(
             args,
             vec![
@@ -1257,7 +2459,7 @@ mod tests {
 
     #[test]
     fn test_omx_args_resume_session() {
-        let args = omx_args(Some("session-1"), "/tmp/project").expect("args should build");
+        let args = omx_args(Some("session-1"), "/tmp/project", false).expect("args should build");
         assert_eq!(
             args,
             vec![
@@ -1276,18 +2478,129 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_backend_args_madmax() {
+        let codex = codex_args(None, "/tmp/project", true).expect("args should build");
+        assert_eq!(codex[2], "--dangerously-bypass-approvals-and-sandbox");
+        assert!(!codex.contains(&"--sandbox".to_string()));
+
+        let omx = omx_args(Some("session-1"), "/tmp/project", true).expect("args should build");
+        assert_eq!(omx[2], "--madmax");
+        assert!(!omx.contains(&"never".to_string()));
+    }
+
     #[test]
     fn test_backend_args_dispatch() {
-        let codex = backend_args(BackendKind::Codex, None, "/tmp/project")
+        let codex = backend_args(BackendKind::Codex, None, "/tmp/project", false)
             .expect("codex args should build");
         assert!(codex.contains(&"exec".to_string()));
 
-        let omx = backend_args(BackendKind::Omx, Some("session-1"), "/tmp/project")
+        let omx = backend_args(BackendKind::Omx, Some("session-1"), "/tmp/project", false)
             .expect("omx args should build");
         assert!(omx.contains(&"exec".to_string()));
         assert!(omx.contains(&"resume".to_string()));
     }
 
+    #[test]
+    fn test_raw_stream_log_is_bounded() {
+        let log = RawStreamLog::default();
+        for i in 0..RAW_STREAM_MAX_LINES + 3 {
+            log.push(&format!("{{\"n\":{i}}}"));
+        }
+        let (lines, dropped) = log.snapshot();
+        assert_eq!(lines.len(), RAW_STREAM_MAX_LINES);
+        assert_eq!(dropped, 3);
+        assert_eq!(lines[0], "{\"n\":3}");
+
+        log.start_request();
+        log.push(&"é".repeat(RAW_STREAM_MAX_LINE_BYTES));
+        let (lines, dropped) = log.snapshot();
+        assert_eq!(dropped, 0);
+        assert!(lines[0].ends_with(&format!("…[{} bytes cut]", RAW_STREAM_MAX_LINE_BYTES)));
+    }
+
+    #[test]
+    fn test_apply_backend_overrides_inserts_model_before_exec() {
+        let mut args = vec!["-C".to_string(), "/tmp".to_string(), "exec".to_string()];
+        apply_backend_overrides(&mut args, &BackendOverrides::default());
+        assert_eq!(args, vec!["-C", "/tmp", "exec"]);
+
+        let overrides = BackendOverrides {
+            model: Some("o3".to_string()),
+            ..Default::default()
+        };
+        apply_backend_overrides(&mut args, &overrides);
+        assert_eq!(args, vec!["-C", "/tmp", "--model", "o3", "exec"]);
+
+        let mut args = vec!["-C".to_string(), "/tmp".to_string(), "exec".to_string()];
+        let overrides = BackendOverrides {
+            model: Some("o3".to_string()),
+            extra_args: vec!["-c".to_string(), "model_reasoning_effort=high".to_string()],
+            ..Default::default()
+        };
+        apply_backend_overrides(&mut args, &overrides);
+        assert_eq!(
+            args,
+            vec![
+                "-C",
+                "/tmp",
+                "--model",
+                "o3",
+                "-c",
+                "model_reasoning_effort=high",
+                "exec"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_backend_args() {
+        let args = |s: &str| s.split_whitespace().map(String::from).collect::<Vec<_>>();
+        assert!(validate_backend_args(&args("-c model_reasoning_effort=high")).is_ok());
+        assert!(validate_backend_args(&args("--profile fast --enable web_search")).is_ok());
+        assert!(validate_backend_args(&args("--config=hide_agent_reasoning=true")).is_ok());
+        assert!(validate_backend_args(&[]).is_ok());
+
+        assert!(
+            validate_backend_args(&args("--dangerously-bypass-approvals-and-sandbox")).is_err()
+        );
+        assert!(validate_backend_args(&args("-c sandbox_mode=danger-full-access")).is_err());
+        assert!(validate_backend_args(&args("-c approval_policy=never")).is_err());
+        assert!(validate_backend_args(&args("-c mcp_servers.x.command=sh")).is_err());
+        assert!(
+            validate_backend_args(&args("--config=shell_environment_policy.inherit=all")).is_err()
+        );
+        assert!(validate_backend_args(&args("-c model")).is_err());
+        assert!(validate_backend_args(&args("-c x=$(rm)")).is_err());
+        assert!(validate_backend_args(&args("-c a=1;ls")).is_err());
+        assert!(validate_backend_args(&args("-c")).is_err());
+        assert!(validate_backend_args(&args("-c --profile")).is_err());
+        assert!(validate_backend_args(&args("exec")).is_err());
+    }
+
+    #[test]
+    fn test_read_progress_lines_keeps_partial_line() {
+        let path = std::env::temp_dir().join("opencodex_test_progress_lines.log");
+        let mut writer = File::create(&path).expect("create progress file");
+        let mut reader = File::open(&path).expect("open progress file");
+        let mut partial = Vec::new();
+
+        writer.write_all(b"step 1\n\nstep 2\nstep").expect("write");
+        assert_eq!(
+            read_progress_lines(&mut reader, &mut partial),
+            vec!["step 1", "step 2"]
+        );
+        assert!(read_progress_lines(&mut reader, &mut partial).is_empty());
+
+        writer.write_all(b" 3\n").expect("write");
+        assert_eq!(
+            read_progress_lines(&mut reader, &mut partial),
+            vec!["step 3"]
+        );
+
+        let _ = std::fs::remove_file(&path);
+    }
+
     #[test]
     fn test_resolve_ai_binary_path_uses_codex() {
         let has_codex = std::process::Command::new("which")
@@ -0,0 +1,136 @@
+use std::borrow::Cow;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, PoisonError};
+
+/// Marker symbols used in console log lines and their `--ascii-logs` replacements
+const ASCII_MARKERS: &[(char, &str)] = &[
+    ('◀', "<"),
+    ('▶', ">"),
+    ('⚙', "*"),
+    ('✗', "x"),
+    ('★', "+"),
+    ('○', "o"),
+    ('↻', "~"),
+    ('■', "#"),
+    ('⚠', "!"),
+    ('✓', "ok"),
+    ('⏰', "@"),
+    ('⧉', "&"),
+    ('⬇', "v"),
+    ('🌿', "%"),
+    ('🗜', "="),
+    ('→', "->"),
+    ('—', "-"),
+];
+
+/// Swap marker symbols for ASCII in console output (--ascii-logs)
+static ASCII_LOGS: AtomicBool = AtomicBool::new(false);
+
+pub fn configure_ascii_logs(enabled: bool) {
+    ASCII_LOGS.store(enabled, Ordering::Relaxed);
+}
+
+/// `line` with its marker symbols replaced by ASCII equivalents
+fn to_ascii(line: &str) -> Cow<'_, str> {
+    if !line
+        .chars()
+        .any(|c| ASCII_MARKERS.iter().any(|(m, _)| *m == c))
+    {
+        return Cow::Borrowed(line);
+    }
+    let mut out = String::with_capacity(line.len());
+    for c in line.chars() {
+        match ASCII_MARKERS.iter().find(|(m, _)| *m == c) {
+            Some((_, ascii)) => out.push_str(ascii),
+            None => out.push(c),
+        }
+    }
+    Cow::Owned(out)
+}
+
+/// Console line as printed: unchanged, or with ASCII markers under --ascii-logs
+pub fn render(line: &str) -> Cow<'_, str> {
+    if ASCII_LOGS.load(Ordering::Relaxed) {
+        to_ascii(line)
+    } else {
+        Cow::Borrowed(line)
+    }
+}
+
+/// Console lines kept in memory for /log
+pub const MAX_RECENT_LINES: usize = 300;
+
+static RECENT_LINES: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+
+/// Print a console log line (to stderr if `error`) and keep it for /log
+pub fn log_line(line: &str, error: bool) {
+    let line = render(line);
+    if error {
+        eprintln!("{line}");
+    } else {
+        println!("{line}");
+    }
+    let mut recent = RECENT_LINES.lock().unwrap_or_else(PoisonError::into_inner);
+    push_recent(&mut recent, line.into_owned());
+}
+
+fn push_recent(recent: &mut VecDeque<String>, line: String) {
+    if recent.len() == MAX_RECENT_LINES {
+        recent.pop_front();
+    }
+    recent.push_back(line);
+}
+
+/// The last `n` console log lines, oldest first
+pub fn recent_lines(n: usize) -> Vec<String> {
+    let recent = RECENT_LINES.lock().unwrap_or_else(PoisonError::into_inner);
+    recent
+        .iter()
+        .skip(recent.len().saturating_sub(n))
+        .cloned()
+        .collect()
+}
+
+/// `println!` for console log lines; markers follow --ascii-logs (see `ASCII_MARKERS`) and the
+/// line is kept for /log
+macro_rules! logln {
+    ($($arg:tt)*) => {
+        $crate::console::log_line(&format!($($arg)*), false)
+    };
+}
+
+/// `eprintln!` counterpart of `logln!`
+macro_rules! elogln {
+    ($($arg:tt)*) => {
+        $crate::console::log_line(&format!($($arg)*), true)
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_ascii() {
+        assert_eq!(
+            to_ascii("  [12:00:00] ◀ [민수] /start"),
+            "  [12:00:00] < [민수] /start"
+        );
+        assert_eq!(
+            to_ascii("  ✓ Bot connected — ok"),
+            "  ok Bot connected - ok"
+        );
+        assert!(matches!(to_ascii("plain line"), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn test_push_recent_drops_oldest() {
+        let mut recent = VecDeque::new();
+        for i in 0..MAX_RECENT_LINES + 5 {
+            push_recent(&mut recent, i.to_string());
+        }
+        assert_eq!(recent.len(), MAX_RECENT_LINES);
+        assert_eq!(recent.front().map(String::as_str), Some("5"));
+    }
+}
@@ -3,13 +3,37 @@ pub const MSG_OWNER_REGISTERED: &str =
 pub const MSG_PRIVATE_BOT: &str = "이 봇은 비공개입니다. 봇 소유자에게 문의하세요.";
 pub const MSG_NO_SESSION: &str =
     "세션이 없습니다. /start <폴더경로> 로 시작하세요.\n예: /start ~/my-project";
+pub const MSG_WORKDIR_MISSING: &str =
+    "작업 폴더가 더 이상 존재하지 않습니다: {path}\n/cd <폴더경로> 또는 /start <폴더경로> 로 다른 폴더를 지정하세요.";
 pub const MSG_AI_BUSY: &str = "AI가 작업 중입니다. /stop 으로 중단할 수 있습니다.";
 pub const MSG_SESSION_CLEARED: &str = "세션이 초기화되었습니다.";
+pub const MSG_REQUEST_CANCELLED_BY_START: &str =
+    "진행 중이던 AI 요청을 중단하고 새 세션을 시작합니다.";
 pub const MSG_NO_ACTIVE_REQUEST: &str = "진행 중인 AI 요청이 없습니다.";
 pub const MSG_FILTER_NOTICE: &str = "⚠ 일부 내용이 보안 필터에 의해 수정되었습니다.";
 pub const MSG_NO_RESPONSE: &str = "(응답 없음)";
-pub const MSG_SHELL_TIMEOUT: &str = "명령 실행 시간 초과 (60초 제한)";
+pub const MSG_NO_RESPONSE_AFTER_TOOLS: &str =
+    "(응답 없음) 작업은 완료되었지만 AI가 답변 없이 도구만 {count}번 실행했습니다.\n/lasttools 로 실행 내역을 확인하거나, 결과를 설명해 달라고 다시 요청해 보세요.";
+pub const MSG_NO_RESPONSE_SILENT: &str =
+    "(응답 없음) 요청은 정상적으로 완료되었지만 AI가 아무것도 출력하지 않았습니다.\n질문을 바꿔서 다시 보내 보세요.";
+pub const MSG_SHELL_TIMEOUT: &str = "명령 실행 시간 초과 ({secs}초 제한)";
 pub const MSG_STOPPING: &str = "중단 중...";
+pub const MSG_ALREADY_STOPPING: &str = "이미 중단하는 중입니다.";
+pub const MSG_PROMPT_TOO_LONG: &str =
+    "메시지가 너무 깁니다 ({len}바이트, 최대 {max}바이트). 내용을 줄이거나 파일로 업로드해 주세요.";
+pub const MSG_UNSUPPORTED_MEDIA: &str =
+    "{kind}은(는) 처리할 수 없습니다. 텍스트로 보내거나, 파일이 필요하면 파일(문서)로 첨부해 주세요.\n(/mediahint off: 이 안내 끄기)";
+
+/// How the AI sends files, part of the system prompt. Placeholders: {binary}, {chat_id}, {key},
+/// {api_url_arg} (replaceable with `sendfile_instructions` in config.json)
+pub const SENDFILE_INSTRUCTIONS_TEMPLATE: &str = "\
+When your work produces a file the user would want (generated code, reports, images, archives, etc.),
+send it by running this bash command:
+
+{binary} --sendfile <filepath> --chat {chat_id} --key {key}{api_url_arg}
+
+This delivers the file directly to the user's Telegram chat.
+Do NOT tell the user to use /down — use the command above instead.";
 
 pub const HELP_TEXT_TEMPLATE: &str = "\
 <b>{app} 텔레그램 봇</b>
@@ -17,35 +41,132 @@ pub const HELP_TEXT_TEMPLATE: &str = "\
 
 <b>세션</b>
 <code>/start &lt;path&gt;</code> — 지정 경로에서 세션 시작
-<code>/start</code> — 시작 시 전달된 기본 프로젝트 경로로 세션 시작
+<code>/start</code> — 기본 프로젝트 경로(<code>/setdefault</code>, 없으면 시작 시 전달된 경로)로 세션 시작
+  (폴더에 <code>.opencodex.toml</code>이 있으면 model, allowed_tools, system_prompt, shell_timeout, shell_output_limit 기본값 적용)
+<code>/startpreview &lt;count&gt; [chars]</code> — <code>/start</code> 복원 시 보여줄 최근 대화 개수와 항목당 글자 수 (<code>reset</code>)
+<code>/tooloutput &lt;결과&gt; [오류]</code> — 응답에 보여줄 도구 실행 결과/오류 글자 수 (기본 300/500, <code>reset</code>)
+<code>/maxresponse &lt;글자수&gt;</code> — 이 채팅에서 긴 AI 응답을 잘라서 표시 (전체는 <code>/raw</code>, <code>off</code>: 제한 없음)
 <code>/pwd</code> — 현재 작업 경로 확인
 <code>/cd &lt;path&gt;</code> — 작업 경로 변경
+<code>/worktree &lt;branch&gt;</code> — git worktree를 만들어 해당 브랜치에서 작업 (<code>remove</code>: 삭제 후 원래 경로로)
 <code>/status</code> — 런타임 상태 확인
+<code>/config</code> — 적용 중인 설정(백엔드, madmax, 모델, 허용 도구 수, 쉘 제한 시간 등)과 각 값의 출처 (토큰은 가림, Owner 전용)
+<code>/selftest</code> — 백엔드 실행, 세션/설정 파일 쓰기, Telegram 전송을 한 번에 점검 (소유자 전용)
+<code>/version</code> — 앱 버전, 백엔드 버전, 빌드 타깃 확인
+<code>/uptime</code> — 봇 프로세스가 실행된 시간 (일/시간/분)
+<code>/models</code> — 사용 가능한 모델 목록 (<code>refresh</code>로 다시 조회)
+<code>/rescan</code> — PATH에서 백엔드 실행 파일을 다시 찾기 (봇 실행 중 설치한 경우, 재시작 불필요)
+<code>/diskusage</code> — 세션 파일과 설정 디렉터리가 차지하는 용량, 가장 큰 세션 파일
+<code>/ping</code> — Telegram API 응답 시간 측정 (네트워크 지연 확인)
+<code>/agents</code> — 실행 중인 OMX 백그라운드 작업/에이전트 목록
+<code>/label &lt;text&gt;</code> — 현재 세션에 라벨 지정 (<code>clear</code>로 제거)
 <code>/clear</code> — AI 대화 히스토리 초기화
+<code>/pin</code> — 최근 AI 응답을 채팅에 고정
+<code>/unpin</code> — <code>/pin</code>으로 고정한 메시지 해제
+<code>/raw</code> — 최근 AI 응답을 서식 없이 다시 받기 (<code>file</code>: .txt 파일로)
+<code>/run</code> — 최근 AI 응답의 첫 코드 블록(bash/python/node)을 확인 후 세션 폴더에서 실행 (Owner 전용)
+<code>/rawstream on|off</code> — 백엔드가 출력한 JSONL 원본을 기록, 인자 없이 실행하면 마지막 요청분을 파일로 받기 (Owner 전용)
+<code>/lastprompt</code> — 마지막 AI 요청에 실제로 보낸 전체 프롬프트 보기
+<code>/output</code> — 마지막 AI 요청의 마지막 도구 실행 결과를 자르지 않고 보기
+<code>/lasttools</code> — 마지막 AI 요청에서 사용한 도구와 횟수
+<code>/errors [n]</code> — 이 세션에서 실패한 최근 요청 n개 (기본 5, 최대 20, 시각 포함)
+<code>/log [n]</code> — 실행 중인 봇의 콘솔 로그 최근 n줄 (기본 50, 최대 300, Owner 전용)
+<code>/search 검색어</code> — 현재 세션 기록에서 검색 (<code>/search all 검색어</code>: 이 폴더의 이전 세션 포함)
 <code>/stop</code> — 진행 중인 AI/쉘 작업 중단
+<code>/continue</code> — 끊긴 AI 응답을 같은 세션에서 이어서 받기
+<code>/summary</code> — 대화 히스토리를 AI로 요약 (확인 후 히스토리를 요약으로 교체)
+
+<b>반복 실행</b>
+<code>/cron \"0 9 * * *\" &lt;prompt&gt;</code> — cron 표현식 일정마다 프롬프트 실행 (서버 로컬 시간)
+<code>/cron list</code> — 이 채팅의 반복 작업 목록
+<code>/cron remove &lt;id&gt;</code> — 반복 작업 삭제
 
 <b>파일 전송</b>
 <code>/down &lt;file&gt;</code> — 서버 파일 다운로드
+<code>/cat &lt;file&gt; [tail] [줄 수]</code> — 파일 앞부분(또는 끝부분)만 읽어서 보기 (기본 50줄)
+<code>/context &lt;file&gt;</code> — 파일 내용을 다음 프롬프트 앞에 붙이기 (여러 번 가능, 인자 없으면 목록, <code>clear</code>로 비우기)
+<code>/pending [clear]</code> — 다음 프롬프트에 함께 보낼 업로드 기록·전달 메시지·첨부 파일 목록 (<code>clear</code>로 보내지 않고 비우기)
+메시지 안의 <code>@경로</code> — 세션 폴더의 해당 파일 내용을 함께 전송 (예: <code>@src/main.rs</code>)
+<code>/downid &lt;file_id&gt; [name]</code> — Telegram file_id로 파일을 받아 작업 폴더에 저장 (최대 20MB)
 파일/사진 전송 — 현재 세션 경로로 업로드
+<code>/uploaddir &lt;folder&gt;</code> — 업로드 파일을 작업 폴더 안의 하위 폴더에 저장 (<code>off</code>: 작업 폴더에 바로 저장)
+<code>/setdefault &lt;path&gt;</code> — 경로 없이 <code>/start</code>할 때 열 폴더 지정 (<code>off</code>: 실행할 때 지정한 폴더)
 
 <b>쉘</b>
-<code>!&lt;command&gt;</code> — 쉘 명령 직접 실행 (최대 60초)
+<code>!&lt;command&gt;</code> — 쉘 명령 직접 실행 (최대 60초, 프로젝트 <code>shell_timeout</code>으로 변경 가능)
 예: <code>!ls -la</code>, <code>!git status</code>
 
 <b>AI 대화</b>
 일반 메시지는 설정된 AI 백엔드로 전달됩니다.
 AI는 세션 경로 내에서 파일 읽기/수정/명령 실행을 수행할 수 있습니다.
 
+<b>프롬프트 설정</b>
+<code>/prefix &lt;text&gt;</code> — 모든 메시지 앞에 붙일 지시문 (<code>show</code>/<code>clear</code>)
+<code>/suffix &lt;text&gt;</code> — 모든 메시지 뒤에 붙일 지시문 (<code>show</code>/<code>clear</code>)
+<code>/extract on|off</code> — 경로가 지정된 코드 블록을 파일로 저장 제안
+<code>/reload</code> — <code>bot_settings.json</code>을 다시 읽어 적용 (세션 유지)
+<code>/reloadsession</code> — 메모리의 세션을 디스크 파일과 비교해 다르면 불러오기/저장 선택 (Owner 전용)
+<code>/render [--raw] &lt;마크다운&gt;</code> — 응답 변환기로 마크다운을 변환해 보내기 (<code>--raw</code>는 생성된 태그를 글자로 표시, 렌더링 버그 재현용)
+<code>/recheck</code> — 마지막 응답 원문을 HTML 변환과 일반 텍스트로 다시 보내고 입력/출력 파일 첨부 (렌더링 버그 신고용, Owner 전용)
+<code>/restart</code> — 세션을 저장하고 실행 중인 요청을 취소한 뒤 봇 프로세스를 같은 인자로 재시작 (버튼으로 확인)
+<code>/greeting on|off|set &lt;text&gt;|reset</code> — 그룹에 초대됐을 때 올리는 소개 메시지 (기본값 켜짐)
+<code>/footer [text|clear]</code> — 모든 채팅의 AI 응답 끝에 붙일 문구 (면책 문구 등, 잘린 응답에도 유지)
+<code>/backup</code> — 이 봇의 설정을 JSON 파일로 받기 (토큰 제외)
+<code>/restore</code> — 백업 파일을 캡션 <code>/restore</code>로 보내 설정 복원 (확인 후 병합)
+<code>/backupsessions</code> — 저장된 모든 세션 파일을 .tar.gz로 받기 (서버 이전용, 크면 여러 개로 분할)
+<code>/restoresessions</code> — 세션 백업 파일을 캡션 <code>/restoresessions</code>로 보내 세션 폴더에 복원 (확인 후 적용)
+<code>/notify on|off</code> — 다른 채팅에서 AI 요청/쉘 명령이 실패하면 Owner에게 개인 메시지로 알림
+<code>/verbose on|off</code> — 도구 실행 과정 표시 여부 (<code>off</code>면 답변만 표시)
+<code>/filter on|off</code> — 프롬프트 인젝션 필터 사용 여부 (기본값 <code>on</code>, 공개 그룹에서는 항상 <code>on</code>)
+<code>/alias add &lt;이름&gt; &lt;프롬프트&gt;</code> — 자주 쓰는 프롬프트를 <code>/이름</code>으로 등록 (<code>{args}</code> 자리에 뒤에 붙인 글 삽입, <code>list</code>/<code>remove</code>/<code>run</code>)
+<code>/cmdalias add &lt;이름&gt; /&lt;명령&gt;</code> — 이 채팅에서 기본 명령을 다른 이름으로 호출 (예: <code>/cmdalias add 경로 /pwd</code>, <code>list</code>/<code>remove</code>)
+<code>/madmax on|off</code> — 이 채팅의 AI 요청에서 승인/샌드박스 해제 (on은 버튼으로 한 번 더 확인, 위험)
+<code>/backendargs &lt;인자&gt;</code> — 이 채팅의 AI 요청에 백엔드 인자 추가 (<code>-c</code>, <code>--profile</code>, <code>--enable</code>, <code>--disable</code>만 허용, <code>clear</code>)
+<code>/auditlog on|off</code> — 모든 프롬프트와 응답을 <code>~/.opencodex/logs/&lt;chat_id&gt;.log</code>에 기록 (/clear 후에도 유지)
+<code>/clearconfirm on|off</code> — <code>/clear</code> 실행 전 버튼으로 확인 (실행 중인 요청이 있으면 경고)
+<code>/shell on|off</code> — 이 채팅에서 <code>!</code> 쉘 명령 허용 여부 (기본값 <code>on</code>, Owner 전용)
+<code>/uploads on|off</code> — 이 채팅에서 파일/사진 업로드 허용 여부 (기본값 <code>on</code>, Owner 전용)
+<code>/autodownload on|off</code> — 응답이 끝나면 AI가 만들거나 수정한 파일을 채팅으로 전송 (64KB 이하 바로, 큰 파일은 버튼, 기본값 <code>off</code>)
+<code>/strictcode on|off</code> — 완료된 응답의 코드 블록을 서식 없는 별도 메시지로 보내 그대로 표시 (기본값 <code>off</code>)
+<code>/shellconfirm on|off</code> — <code>rm -r</code>, <code>dd</code>, <code>mkfs</code> 등 위험해 보이는 <code>!</code> 명령은 버튼으로 확인 후 실행
+<code>/stderr on|off</code> — 백엔드가 성공했는데도 stderr에 남긴 경고를 응답 아래 접힌 메모로 표시 (<code>/verbose on</code>이어도 표시, 기본값 <code>off</code>)
+<code>/recap on|off</code> — 세션을 이어갈 수 없어 새 세션이 시작될 때 최근 대화를 프롬프트에 함께 전달 (추가 토큰 사용, 기본값 <code>off</code>)
+<code>/stream on|off</code> — 응답을 작성되는 대로 보여주기 / 완료될 때까지 스피너와 경과 시간만 표시 (기본값 <code>on</code>)
+<code>/projecthint on|off</code> — 작업 폴더의 <code>Cargo.toml</code>, <code>package.json</code>, <code>pyproject.toml</code> 등을 보고 Rust/Node.js/Python 같은 프로젝트 종류를 시스템 프롬프트에 알려주기 (기본값 <code>off</code>)
+<code>/typingpause on|off</code> — 채팅에 메시지가 이어지는 동안 응답 실시간 갱신을 멈추고 10초 조용해지면 다시 갱신 (기본값 <code>off</code>)
+<code>/shortpaths on|off</code> — <code>/pwd</code>, <code>/status</code>, <code>/cd</code>에서 경로를 <code>~</code> / <code>$PROJECT</code>로 줄여 표시 (표시만 변경)
+<code>/mediahint on|off</code> — 스티커, GIF, 동영상, 음성 메시지처럼 처리할 수 없는 메시지에 안내 답장 (기본값 <code>on</code>, 1:1 채팅)
+<code>/forwardwait on|off</code> — 전달(forward)된 메시지를 바로 보내지 않고 다음 지시와 함께 AI에 전달
+<code>/progressfile on|off</code> — 백엔드가 <code>OPENCODEX_PROGRESS_FILE</code> 파일에 쓴 진행 상황을 응답 중에 표시
+<code>/compact on|off</code> — 히스토리가 한도에 가까워지면 오래된 항목을 AI로 요약해 보존 (요청 1회 추가)
+<code>/parsemode html|markdownv2|off</code> — AI 응답 렌더링 형식 선택 (봇 전체, <code>off</code>: 변환 없이 일반 텍스트)
+<code>/promptlimit 바이트|truncate|reject</code> — 프롬프트 최대 길이와 초과 시 처리 (기본 16000, <code>truncate</code>: 잘라서 전송, <code>reject</code>: 거부, 봇 전체)
+<code>/format html|markdownv2|off</code> — 이 채팅에서만 응답 렌더링 형식 지정 (<code>reset</code>: 봇 전체 설정 따르기)
+<code>/respondlang 언어</code> — 사용자가 어떤 언어로 쓰든 이 언어로 응답 (<code>clear</code>: 메시지 언어 따르기)
+<code>/replystyle edit|new</code> — 진행 메시지를 답변으로 바꾸기 / 기록으로 남기고 답변은 새 메시지로 (이 채팅)
+
 <b>도구 관리</b>
-<code>/availabletools</code> — 사용 가능한 전체 도구 목록
+<code>/availabletools</code> — 사용 가능한 전체 도구 목록 (◀/▶ 버튼으로 페이지 이동, <code>all</code>: 한 번에 보기)
 <code>/allowedtools</code> — 현재 허용된 도구 목록
+<code>/toolscheck</code> — 허용 도구 점검: 알 수 없는 도구와 켜진 위험 도구 표시 (<code>prune</code>: 알 수 없는 도구 제거)
 <code>/allowed +name</code> — 도구 추가 (예: <code>/allowed +Bash</code>)
 <code>/allowed -name</code> — 도구 제거
+<code>/preset &lt;name&gt;</code> — 도구 프리셋 적용 (<code>readonly</code>, <code>dev</code>, <code>safe</code>, <code>list</code>)
+<code>/preset export</code> / <code>/preset import &lt;preset&gt;</code> — 이 채팅의 도구 목록, 프롬프트 앞/뒤 문구, 응답 길이 제한을 공유용 문자열로 내보내기 / 가져오기
 
 <b>그룹 채팅</b>
 <code>;</code><i>메시지</i> — AI에게 메시지 전송
 <code>;</code><i>caption</i> — 파일 업로드와 함께 AI 프롬프트 전달
 <code>/public on</code> — 그룹 멤버 전체 사용 허용
 <code>/public off</code> — 소유자만 사용 (기본값)
+<code>/public default</code> — 그룹 설정을 지우고 <code>/defaultpublic</code> 기본값 따르기
+<code>/approval on|off</code> — 공개 그룹에서 다른 멤버의 <code>;</code> 프롬프트를 소유자가 승인/거절 버튼으로 확인한 뒤 실행 (기본값 <code>off</code>, 소유자 전용)
+<code>/defaultpublic on|off</code> — <code>/public</code> 설정이 없는 그룹의 기본 공개 여부 (봇 전체, 소유자 전용)
+<code>/mirror &lt;채팅 ID&gt;</code> — 이 채팅의 AI 응답을 다른 채팅에 실시간으로 복사 (시연용, 대상 채팅은 읽기 전용, <code>off</code>로 해제)
+<code>/mute</code> / <code>/unmute</code> — 이 채팅에서 봇 응답 일시 중지 / 다시 시작 (중지 중에도 소유자의 명령어는 동작)
+<code>/elevate &lt;user_id&gt; &lt;분&gt;</code> — 특정 사용자에게 잠시 AI 프롬프트 권한 부여 (<code>revoke</code>로 해제)
+<code>/chats</code> — 봇이 상태를 가진 모든 채팅 (경로, 공개 여부, 실행 중 여부)
+<code>/cooldown &lt;초&gt;</code> — 소유자를 뺀 사용자별 AI 요청 최소 간격 (<code>off</code>로 해제)
+<code>/cmdcooldown &lt;명령어&gt; &lt;초&gt;</code> — 무거운 명령어(<code>/summary</code>, <code>/backup</code> 등)의 채팅별 최소 실행 간격 (<code>off</code>로 해제, <code>owner on</code>이면 소유자도 제한)
 
 <code>/help</code> — 도움말 표시";
@@ -1,6 +1,8 @@
 mod app;
 mod auth;
 mod codex;
+#[macro_use]
+mod console;
 mod i18n;
 mod session;
 mod telegram;
@@ -25,6 +27,10 @@ struct Cli {
     #[arg(long)]
     token: Option<String>,
 
+    /// Read the Telegram Bot token from a file (not saved to the config directory)
+    #[arg(long, value_name = "PATH", conflicts_with = "token")]
+    token_file: Option<PathBuf>,
+
     /// Enable full permission bypass mode
     #[arg(long)]
     madmax: bool,
@@ -33,6 +39,69 @@ struct Cli {
     #[arg(long)]
     omx: bool,
 
+    /// Store session files gzip-compressed (.json.gz); existing files convert on next save
+    #[arg(long)]
+    gzip_sessions: bool,
+
+    /// History item types written to session files, comma-separated: user, assistant, error,
+    /// system, tool_use, tool_result, tools or all (default: everything but system)
+    #[arg(long, value_name = "TYPES")]
+    persist_history: Option<String>,
+
+    /// Only respond in this chat ID (repeatable); updates from other chats are ignored
+    #[arg(
+        long = "allow-chat",
+        value_name = "CHAT_ID",
+        allow_negative_numbers = true
+    )]
+    allow_chat: Vec<i64>,
+
+    /// Telegram user ID of the owner; registered at startup instead of the first user to
+    /// message the bot (also OPENCODEX_OWNER_ID)
+    #[arg(long, value_name = "USER_ID")]
+    owner: Option<u64>,
+
+    /// Retries when a saved session cannot be resumed; the last retry starts a new
+    /// session (0 = report the error and keep the session)
+    #[arg(long, value_name = "N", default_value_t = 1)]
+    resume_retries: u32,
+
+    /// Stop the backend if it sends no output for this many seconds (0 = never)
+    #[arg(long, value_name = "SECS", default_value_t = 0)]
+    stall_timeout: u64,
+
+    /// Files of an album (media group) downloaded at once, across all chats
+    #[arg(long, value_name = "N", default_value_t = 4)]
+    upload_concurrency: usize,
+
+    /// Attempts to download an uploaded file from Telegram's servers; network errors and
+    /// server failures are retried with backoff, a 404 (expired file) fails immediately
+    #[arg(long, value_name = "N", default_value_t = 3, value_parser = clap::value_parser!(u32).range(1..))]
+    download_attempts: u32,
+
+    /// Base URL of a self-hosted Telegram Bot API server, e.g. http://localhost:8081
+    /// (also OPENCODEX_API_URL); raises the file size limit to 2000 MB
+    #[arg(long, value_name = "URL")]
+    api_url: Option<String>,
+
+    /// Receive updates via a webhook at this public HTTPS URL instead of long polling
+    #[arg(long, value_name = "URL")]
+    webhook_url: Option<String>,
+
+    /// Local port the webhook server listens on (used with --webhook-url)
+    #[arg(long, value_name = "PORT", default_value_t = 8443)]
+    webhook_port: u16,
+
+    /// Attempts to reach Telegram's getMe at startup before giving up; network errors are
+    /// retried with backoff, a rejected token fails immediately
+    #[arg(long, value_name = "N", default_value_t = 5, value_parser = clap::value_parser!(u32).range(1..))]
+    startup_attempts: u32,
+
+    /// Print ASCII markers (`<`, `>`, `!`, ...) in console log lines instead of symbols,
+    /// for terminals and log viewers that do not render them
+    #[arg(long)]
+    ascii_logs: bool,
+
     /// Internal: send file to Telegram (used by AI output automation)
     #[arg(long, value_name = "FILE_PATH")]
     sendfile: Option<String>,
@@ -49,10 +118,30 @@ struct Cli {
 #[derive(Debug, Default, Clone, Serialize, Deserialize)]
 struct AppConfig {
     token: Option<String>,
+    /// Extra stderr phrases meaning a saved session cannot be resumed (case-insensitive)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    resume_error_patterns: Vec<String>,
+    /// Arguments for the AI binary that print the available models (used by /models)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    model_list_command: Vec<String>,
+    /// Static model list shown by /models when the backend cannot list its models
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    models: Vec<String>,
+    /// Starts of extra backend error messages that are informational noise and dropped
+    /// (case-insensitive)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    suppressed_notices: Vec<String>,
+    /// Starts of extra backend error messages shown as warnings without failing the request
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    warning_notices: Vec<String>,
+    /// System prompt block explaining how the AI sends files; placeholders {binary},
+    /// {chat_id}, {key} and {api_url_arg} (default: i18n::SENDFILE_INSTRUCTIONS_TEMPLATE)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    sendfile_instructions: Option<String>,
 }
 
 fn config_path() -> Option<PathBuf> {
-    dirs::home_dir().map(|h| h.join(app::dir_name()).join("config.json"))
+    app::data_dir().map(|d| d.join("config.json"))
 }
 
 fn load_config() -> AppConfig {
@@ -87,23 +176,55 @@ fn save_config(config: &AppConfig) {
     }
 }
 
-fn resolve_token(cli_token: Option<String>) -> Result<String> {
+/// Read a token file (e.g. a Docker/systemd secret), trimming surrounding whitespace
+fn read_token_file(path: &Path) -> Result<String> {
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read token file {}", path.display()))?;
+    let token = content.trim();
+    if token.is_empty() {
+        anyhow::bail!("Token file {} is empty", path.display());
+    }
+    Ok(token.to_string())
+}
+
+/// Find the Telegram token, first match wins: --token, --token-file,
+/// OPENCODEX_TELEGRAM_TOKEN, OPENCODEX_TELEGRAM_TOKEN_FILE, TELEGRAM_BOT_TOKEN, config.json.
+/// Tokens given directly are saved to config.json; tokens read from a file are not, so the
+/// secret stays only in the file.
+fn resolve_token(
+    cli_token: Option<String>,
+    cli_token_file: Option<PathBuf>,
+) -> Result<(String, &'static str)> {
     if let Some(token) = cli_token {
         let mut cfg = load_config();
         cfg.token = Some(token.clone());
         save_config(&cfg);
-        return Ok(token);
+        return Ok((token, "--token"));
     }
 
-    // Binary-specific env var
+    if let Some(path) = cli_token_file {
+        return Ok((read_token_file(&path)?, "--token-file"));
+    }
+
+    // Binary-specific env vars
     let bin_env_var = "OPENCODEX_TELEGRAM_TOKEN";
+    let file_env_var = "OPENCODEX_TELEGRAM_TOKEN_FILE";
 
     if let Ok(token) = env::var(bin_env_var) {
         if !token.trim().is_empty() {
             let mut cfg = load_config();
             cfg.token = Some(token.clone());
             save_config(&cfg);
-            return Ok(token);
+            return Ok((token, "OPENCODEX_TELEGRAM_TOKEN"));
+        }
+    }
+
+    if let Ok(path) = env::var(file_env_var) {
+        if !path.trim().is_empty() {
+            return Ok((
+                read_token_file(Path::new(path.trim()))?,
+                "OPENCODEX_TELEGRAM_TOKEN_FILE",
+            ));
         }
     }
 
@@ -112,49 +233,159 @@ fn resolve_token(cli_token: Option<String>) -> Result<String> {
             let mut cfg = load_config();
             cfg.token = Some(token.clone());
             save_config(&cfg);
-            return Ok(token);
+            return Ok((token, "TELEGRAM_BOT_TOKEN"));
         }
     }
 
     let cfg = load_config();
     if let Some(token) = cfg.token {
         if !token.trim().is_empty() {
-            return Ok(token);
+            return Ok((token, "config.json"));
         }
     }
 
     anyhow::bail!(
-        "Telegram token not found. Use one of:\n  1) {} <project_dir> --token <TOKEN>\n  2) export {}=<TOKEN>\n  3) export TELEGRAM_BOT_TOKEN=<TOKEN>\n  4) save token in ~/{}/config.json",
+        "Telegram token not found. Use one of:\n  1) {} <project_dir> --token <TOKEN>\n  2) {} <project_dir> --token-file <PATH>\n  3) export {}=<TOKEN>\n  4) export {}=<PATH>\n  5) export TELEGRAM_BOT_TOKEN=<TOKEN>\n  6) save token in {}",
+        env!("CARGO_BIN_NAME"),
         env!("CARGO_BIN_NAME"),
         bin_env_var,
-        app::dir_name(),
+        file_env_var,
+        config_path().map_or_else(
+            || format!("~/{}/config.json", app::dir_name()),
+            |p| p.display().to_string()
+        ),
     );
 }
 
-async fn validate_telegram_token(token: &str) -> Result<()> {
-    let url = format!("https://api.telegram.org/bot{}/getMe", token);
-    let resp = reqwest::get(&url)
-        .await
-        .context("Failed to call Telegram getMe API")?;
+/// Owner to pre-register: `--owner` first, then `OPENCODEX_OWNER_ID`.
+/// Returns the user ID and where it came from.
+fn resolve_owner(cli_owner: Option<u64>) -> Result<Option<(u64, &'static str)>> {
+    if let Some(owner) = cli_owner {
+        return Ok(Some((owner, "--owner")));
+    }
+    match env::var(app::OWNER_ID_ENV) {
+        Ok(value) if !value.trim().is_empty() => {
+            let owner = value.trim().parse::<u64>().with_context(|| {
+                format!(
+                    "invalid {}: {value} (expected a Telegram user ID)",
+                    app::OWNER_ID_ENV
+                )
+            })?;
+            Ok(Some((owner, app::OWNER_ID_ENV)))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Self-hosted Bot API server: `--api-url` first, then `OPENCODEX_API_URL`.
+/// Returns the validated URL and where it came from.
+fn resolve_api_url(cli_url: Option<String>) -> Result<Option<(reqwest::Url, &'static str)>> {
+    let (value, source) = match cli_url {
+        Some(url) => (url, "--api-url"),
+        None => match env::var(app::API_URL_ENV) {
+            Ok(url) if !url.trim().is_empty() => (url, app::API_URL_ENV),
+            _ => return Ok(None),
+        },
+    };
+    let url = telegram::parse_api_url(value.trim())
+        .map_err(|e| anyhow::anyhow!("invalid {source}: {e}"))?;
+    Ok(Some((url, source)))
+}
+
+/// Longest wait between getMe attempts
+const MAX_STARTUP_BACKOFF: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// Why a getMe call failed
+enum TokenCheckError {
+    /// Telegram answered and refused the token; retrying cannot help
+    Rejected(anyhow::Error),
+    /// Network error or server-side failure; worth another try
+    Transient(anyhow::Error),
+}
+
+/// Call getMe once
+async fn check_telegram_token(url: &reqwest::Url) -> Result<(), TokenCheckError> {
+    let resp = reqwest::get(url.clone()).await.map_err(|e| {
+        TokenCheckError::Transient(
+            anyhow::Error::new(e.without_url()).context("Failed to call Telegram getMe API"),
+        )
+    })?;
     let status = resp.status();
     let body = resp.text().await.unwrap_or_default();
 
     if !status.is_success() {
-        anyhow::bail!(
+        let error = anyhow::anyhow!(
             "Telegram token validation failed (HTTP {}): {}",
             status,
             body
         );
+        // 5xx and flood control are the server's problem, not the token's
+        return Err(
+            if status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+                TokenCheckError::Transient(error)
+            } else {
+                TokenCheckError::Rejected(error)
+            },
+        );
     }
 
     let parsed: serde_json::Value = serde_json::from_str(&body).unwrap_or_default();
     let ok = parsed.get("ok").and_then(|v| v.as_bool()).unwrap_or(false);
     if !ok {
-        anyhow::bail!("Telegram token validation failed: {}", body);
+        return Err(TokenCheckError::Rejected(anyhow::anyhow!(
+            "Telegram token validation failed: {}",
+            body
+        )));
     }
     Ok(())
 }
 
+/// Delay before attempt `attempt + 1`: 1s, 2s, 4s, ... up to MAX_STARTUP_BACKOFF
+fn startup_backoff(attempt: u32) -> std::time::Duration {
+    std::time::Duration::from_secs(1u64 << attempt.saturating_sub(1).min(5))
+        .min(MAX_STARTUP_BACKOFF)
+}
+
+/// Validate the token with getMe, retrying network failures up to `attempts` times in total
+/// (the network may not be up yet when started as a service)
+async fn validate_telegram_token(token: &str, attempts: u32) -> Result<()> {
+    let url = telegram::new_bot(token)
+        .api_url()
+        .join(&format!("/bot{}/getMe", token))
+        .context("Failed to build the getMe URL")?;
+
+    let mut attempt = 1;
+    loop {
+        match check_telegram_token(&url).await {
+            Ok(()) => return Ok(()),
+            Err(TokenCheckError::Rejected(e)) => return Err(e),
+            Err(TokenCheckError::Transient(e)) if attempt >= attempts => {
+                return Err(e.context(format!(
+                    "Telegram unreachable after {attempts} attempt(s) (--startup-attempts)"
+                )));
+            }
+            Err(TokenCheckError::Transient(e)) => {
+                let delay = startup_backoff(attempt);
+                elogln!(
+                    "⚠ Telegram getMe failed (attempt {attempt}/{attempts}): {e:#}; retrying in {}s",
+                    delay.as_secs()
+                );
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+        }
+    }
+}
+
+/// Validate `--webhook-url`: Telegram only delivers webhooks to HTTPS URLs
+fn parse_webhook(url: &str, port: u16) -> Result<telegram::WebhookConfig> {
+    let url = reqwest::Url::parse(url).with_context(|| format!("invalid --webhook-url: {url}"))?;
+    if url.scheme() != "https" {
+        anyhow::bail!("--webhook-url must use https (got {})", url.scheme());
+    }
+    Ok(telegram::WebhookConfig { url, port })
+}
+
 async fn handle_sendfile(path: &str, chat_id: i64, hash_key: &str) -> Result<()> {
     let token = telegram::resolve_token_by_hash(hash_key)
         .with_context(|| format!("No bot token found for hash key: {}", hash_key))?;
@@ -164,27 +395,127 @@ async fn handle_sendfile(path: &str, chat_id: i64, hash_key: &str) -> Result<()>
         anyhow::bail!("file not found: {}", path);
     }
 
-    let bot = Bot::new(token);
-    bot.send_document(ChatId(chat_id), teloxide::types::InputFile::file(file_path))
+    let bot = telegram::new_bot(&token);
+    if let Err(e) = bot
+        .send_document(ChatId(chat_id), teloxide::types::InputFile::file(file_path))
         .await
-        .context("failed to send file")?;
+    {
+        if let Some(reason) = undeliverable_reason(&e) {
+            elogln!("✗ File not sent: chat {chat_id} {reason}");
+            std::process::exit(SENDFILE_UNDELIVERABLE_EXIT);
+        }
+        return Err(e).context("failed to send file");
+    }
 
     println!("File sent: {}", path);
     Ok(())
 }
 
+/// Exit status of --sendfile when the target chat cannot receive messages at all
+/// (distinct from 1, used for other failures that may succeed on a retry)
+const SENDFILE_UNDELIVERABLE_EXIT: i32 = 2;
+
+/// Readable reason when a send failed because the chat is gone or closed to the bot
+fn undeliverable_reason(err: &teloxide::RequestError) -> Option<String> {
+    use teloxide::{ApiError, RequestError};
+
+    let reason = match err {
+        RequestError::MigrateToChatId(new_id) => {
+            return Some(format!(
+                "was upgraded to a supergroup; its new id is {}",
+                new_id.0
+            ))
+        }
+        RequestError::Api(api) => match api {
+            ApiError::ChatNotFound => "was not found (deleted, or the bot was never in it)",
+            ApiError::BotBlocked => "blocked the bot",
+            ApiError::BotKicked | ApiError::BotKickedFromSupergroup => "removed the bot",
+            ApiError::UserDeactivated => "belongs to a deleted account",
+            ApiError::GroupDeactivated => "is a deactivated group",
+            ApiError::CantInitiateConversation => "has never started a conversation with the bot",
+            ApiError::NotEnoughRightsToPostMessages => "does not let the bot post",
+            _ => return None,
+        },
+        _ => return None,
+    };
+    Some(reason.to_string())
+}
+
+/// Require explicit confirmation before running with --madmax: `OPENCODEX_CONFIRM_MADMAX=1`,
+/// or answering y/yes when stdin is a terminal
+fn confirm_madmax() -> Result<()> {
+    use app::CONFIRM_MADMAX_ENV;
+
+    elogln!("⚠⚠⚠ WARNING: --madmax enabled ⚠⚠⚠");
+    eprintln!("  All Codex/OMX permission checks are DISABLED.");
+    eprintln!("  AI can execute commands without confirmation.");
+    eprintln!("  Anyone allowed to prompt the bot (e.g. a /public group) gets the same power.");
+    eprintln!("  Use only in trusted environments.");
+
+    if env::var(CONFIRM_MADMAX_ENV).is_ok_and(|v| v.trim() == "1") {
+        eprintln!("  Confirmed via {CONFIRM_MADMAX_ENV}=1.");
+        return Ok(());
+    }
+
+    use std::io::{BufRead, IsTerminal, Write};
+    if !std::io::stdin().is_terminal() {
+        anyhow::bail!(
+            "--madmax requires confirmation: set {CONFIRM_MADMAX_ENV}=1 when running without a terminal"
+        );
+    }
+    eprint!("  Start in madmax mode? [y/N] ");
+    let _ = std::io::stderr().flush();
+    let mut answer = String::new();
+    std::io::stdin()
+        .lock()
+        .read_line(&mut answer)
+        .context("failed to read confirmation")?;
+    match answer.trim().to_lowercase().as_str() {
+        "y" | "yes" => Ok(()),
+        _ => anyhow::bail!("madmax mode not confirmed; exiting"),
+    }
+}
+
+/// Warn when there is nowhere to persist settings and sessions
+fn check_data_dir() {
+    if app::data_dir().is_some() {
+        return;
+    }
+    elogln!("⚠⚠⚠ WARNING: home directory could not be resolved ⚠⚠⚠");
+    eprintln!("  Settings, sessions, cron jobs and logs will NOT be saved.");
+    eprintln!("  The token cannot be read from or saved to config.json.");
+    eprintln!(
+        "  Set {}=<dir> to choose where data is stored.",
+        app::HOME_ENV
+    );
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     let _ = dotenvy::dotenv();
     let cli = Cli::parse();
+    console::configure_ascii_logs(cli.ascii_logs);
     codex::configure_execution(cli.omx, cli.madmax);
-
-    if cli.madmax {
-        eprintln!("⚠⚠⚠ WARNING: --madmax enabled ⚠⚠⚠");
-        eprintln!("  All Codex/OMX permission checks are DISABLED.");
-        eprintln!("  AI can execute commands without confirmation.");
-        eprintln!("  Use only in trusted environments.");
-    }
+    telegram::configure_session_compression(cli.gzip_sessions);
+    let persisted_history = cli
+        .persist_history
+        .as_deref()
+        .map(telegram::parse_history_types)
+        .transpose()
+        .map_err(|e| anyhow::anyhow!("invalid --persist-history: {e}"))?;
+    telegram::configure_persisted_history(persisted_history);
+    auth::configure_chat_allowlist(cli.allow_chat.clone());
+    codex::configure_resume_retries(cli.resume_retries);
+    codex::configure_stall_timeout(cli.stall_timeout);
+    telegram::configure_upload_concurrency(cli.upload_concurrency);
+    telegram::configure_download_attempts(cli.download_attempts);
+    let api_url = resolve_api_url(cli.api_url.clone())?;
+    telegram::configure_api_url(api_url.as_ref().map(|(url, _)| url.clone()));
+    let config = load_config();
+    codex::configure_resume_error_patterns(config.resume_error_patterns);
+    codex::configure_model_listing(config.model_list_command, config.models);
+    codex::configure_notice_patterns(config.suppressed_notices, config.warning_notices);
+    telegram::configure_sendfile_instructions(config.sendfile_instructions);
 
     if let Some(path) = cli.sendfile.as_deref() {
         let chat_id = cli
@@ -198,6 +529,12 @@ async fn main() -> Result<()> {
         return Ok(());
     }
 
+    check_data_dir();
+
+    if cli.madmax {
+        confirm_madmax()?;
+    }
+
     let project_dir = cli.project_dir.as_deref().context(format!(
         "Usage: {} <project_dir> [--token <TOKEN>] [--madmax] [--omx]",
         env!("CARGO_BIN_NAME"),
@@ -213,8 +550,18 @@ async fn main() -> Result<()> {
         .map(|p| p.display().to_string())
         .unwrap_or_else(|_| project_dir.to_string());
 
-    let token = resolve_token(cli.token)?;
-    validate_telegram_token(&token).await?;
+    let webhook = cli
+        .webhook_url
+        .as_deref()
+        .map(|url| parse_webhook(url, cli.webhook_port))
+        .transpose()?;
+
+    let owner = resolve_owner(cli.owner)?;
+    auth::configure_owner(owner.map(|(id, _)| id));
+
+    let (token, token_source) = resolve_token(cli.token, cli.token_file)?;
+    telegram::configure_token_source(token_source);
+    validate_telegram_token(&token, cli.startup_attempts).await?;
     telegram::cleanup_stale_sessions(30);
 
     if codex::get_ai_binary_path().is_none() {
@@ -223,14 +570,17 @@ async fn main() -> Result<()> {
         } else {
             ("codex", "npm install -g @openai/codex")
         };
-        eprintln!("⚠ Warning: {} CLI not found in PATH.", name);
-        eprintln!("  AI features will not work until {} is installed.", name);
+        elogln!("⚠ Warning: {} CLI not found in PATH.", name);
+        eprintln!(
+            "  AI features will not work until {} is installed (then send /rescan).",
+            name
+        );
         eprintln!("  Install: {}", install);
     }
 
-    println!("{} {}", env!("CARGO_BIN_NAME"), env!("CARGO_PKG_VERSION"));
-    println!("project_dir: {}", canonical_project);
-    println!(
+    logln!("{} {}", env!("CARGO_BIN_NAME"), env!("CARGO_PKG_VERSION"));
+    logln!("project_dir: {}", canonical_project);
+    logln!(
         "ai_backend: {}",
         if cli.omx {
             "omx (--omx)"
@@ -238,9 +588,25 @@ async fn main() -> Result<()> {
             "codex (default)"
         }
     );
-    println!("status: connecting Telegram bot...");
+    if cli.madmax {
+        logln!("mode: ⚠ MADMAX MODE ACTIVE (approvals and sandbox disabled)");
+    }
+    if !cli.allow_chat.is_empty() {
+        let ids: Vec<String> = cli.allow_chat.iter().map(i64::to_string).collect();
+        logln!("allowed_chats: {}", ids.join(", "));
+    }
+    if let Some((id, source)) = owner {
+        logln!("owner: {id} (from {source})");
+    }
+    if let Some((url, source)) = &api_url {
+        logln!("api_url: {url} (from {source})");
+    }
+    if let Some(webhook) = &webhook {
+        logln!("updates: webhook {} (port {})", webhook.url, webhook.port);
+    }
+    logln!("status: connecting Telegram bot...");
 
-    telegram::run_bot(&token, &canonical_project).await;
+    telegram::run_bot(&token, &canonical_project, webhook).await;
 
     Ok(())
 }
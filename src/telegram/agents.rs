@@ -0,0 +1,153 @@
+use teloxide::prelude::*;
+use teloxide::types::ParseMode;
+
+use super::bot::SharedState;
+use super::streaming::{html_escape, shared_rate_limit_wait, truncate_str};
+
+/// Maximum number of background tasks tracked per chat
+const MAX_TRACKED_AGENTS: usize = 20;
+
+/// Statuses after which a background task is no longer considered running
+const TERMINAL_STATUSES: &[&str] = &[
+    "completed",
+    "complete",
+    "done",
+    "finished",
+    "succeeded",
+    "success",
+    "failed",
+    "error",
+    "cancelled",
+    "canceled",
+    "stopped",
+    "killed",
+];
+
+/// A background task/agent reported through OMX task notifications
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct AgentTask {
+    pub task_id: String,
+    pub status: String,
+    pub summary: String,
+    /// Local time of the last update (HH:MM:SS)
+    pub updated_at: String,
+}
+
+fn is_terminal_status(status: &str) -> bool {
+    let status = status.to_lowercase();
+    TERMINAL_STATUSES.contains(&status.as_str())
+}
+
+/// Apply a task notification to the chat's active task list.
+/// Finished tasks are removed; running ones are inserted or updated in place.
+pub(super) fn update_agent_tasks(
+    tasks: &mut Vec<AgentTask>,
+    task_id: &str,
+    status: &str,
+    summary: &str,
+    updated_at: &str,
+) {
+    // Notifications without an ID are keyed by their summary so repeats update in place
+    let key = if task_id.is_empty() { summary } else { task_id };
+    let existing = tasks.iter().position(|t| t.task_id == key);
+
+    if is_terminal_status(status) {
+        if let Some(idx) = existing {
+            tasks.remove(idx);
+        }
+        return;
+    }
+
+    let task = AgentTask {
+        task_id: key.to_string(),
+        status: status.to_string(),
+        summary: summary.to_string(),
+        updated_at: updated_at.to_string(),
+    };
+    match existing {
+        Some(idx) => tasks[idx] = task,
+        None => {
+            tasks.push(task);
+            if tasks.len() > MAX_TRACKED_AGENTS {
+                tasks.remove(0);
+            }
+        }
+    }
+}
+
+/// Handle /agents command - list background tasks/agents running in this chat
+pub(super) async fn handle_agents_command(
+    bot: &Bot,
+    chat_id: ChatId,
+    state: &SharedState,
+) -> ResponseResult<()> {
+    let (tasks, busy) = {
+        let data = state.lock().await;
+        (
+            data.agent_tasks.get(&chat_id).cloned().unwrap_or_default(),
+            data.cancel_tokens.contains_key(&chat_id),
+        )
+    };
+
+    let response_msg = if tasks.is_empty() {
+        if busy {
+            "No background agents reported for the current request.".to_string()
+        } else {
+            "No background agents running.".to_string()
+        }
+    } else {
+        let mut msg = format!("<b>Background agents</b> ({})\n", tasks.len());
+        for task in &tasks {
+            let status = if task.status.is_empty() {
+                "running"
+            } else {
+                task.status.as_str()
+            };
+            msg.push_str(&format!(
+                "\n• <code>{}</code> — <b>{}</b> ({})\n  {}",
+                html_escape(&truncate_str(&task.task_id, 40)),
+                html_escape(status),
+                task.updated_at,
+                html_escape(&truncate_str(&task.summary, 200))
+            ));
+        }
+        msg
+    };
+
+    shared_rate_limit_wait(state, chat_id).await;
+    bot.send_message(chat_id, &response_msg)
+        .parse_mode(ParseMode::Html)
+        .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_update_agent_tasks_tracks_running_only() {
+        let mut tasks = Vec::new();
+        update_agent_tasks(&mut tasks, "1", "running", "Write tests", "10:00:00");
+        update_agent_tasks(&mut tasks, "2", "queued", "Review", "10:00:01");
+        update_agent_tasks(
+            &mut tasks,
+            "1",
+            "in_progress",
+            "Write more tests",
+            "10:00:02",
+        );
+        assert_eq!(tasks.len(), 2);
+        assert_eq!(tasks[0].status, "in_progress");
+        assert_eq!(tasks[0].summary, "Write more tests");
+
+        update_agent_tasks(&mut tasks, "1", "Completed", "Done", "10:00:03");
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(tasks[0].task_id, "2");
+
+        // Terminal status for an unknown task is ignored
+        update_agent_tasks(&mut tasks, "9", "failed", "x", "10:00:04");
+        assert_eq!(tasks.len(), 1);
+    }
+}
@@ -0,0 +1,196 @@
+use teloxide::prelude::*;
+use teloxide::types::ParseMode;
+
+use super::bot::{BotSettings, SharedState};
+use super::commands::bot_commands;
+use super::message::handle_text_message;
+use super::storage::save_bot_settings;
+use super::streaming::{html_escape, send_long_message, shared_rate_limit_wait};
+
+/// Maximum number of aliases
+const MAX_ALIASES: usize = 50;
+
+/// Maximum length (bytes) of an alias template
+const MAX_TEMPLATE_LEN: usize = 2000;
+
+/// Maximum length of an alias name (Telegram commands allow up to 32 characters)
+const MAX_NAME_LEN: usize = 32;
+
+/// Placeholder replaced by the text after the alias name
+const ARGS_PLACEHOLDER: &str = "{args}";
+
+/// Why an alias name cannot be used, if it cannot
+fn alias_name_error(name: &str) -> Option<String> {
+    if name.is_empty()
+        || name.len() > MAX_NAME_LEN
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+    {
+        return Some(format!(
+            "Alias names use a-z, 0-9 and _ (up to {MAX_NAME_LEN} characters)."
+        ));
+    }
+    // Commands are routed by prefix, so `/stopall` would run /stop
+    bot_commands()
+        .into_iter()
+        .find(|c| name.starts_with(&c.command))
+        .map(|c| {
+            format!(
+                "<code>{name}</code> collides with the built-in /{}.",
+                c.command
+            )
+        })
+}
+
+/// Fill a template with the text after the alias name.
+/// Without a `{args}` placeholder, the arguments are appended as a separate paragraph.
+fn expand_alias(template: &str, args: &str) -> String {
+    let args = args.trim();
+    if template.contains(ARGS_PLACEHOLDER) {
+        template.replace(ARGS_PLACEHOLDER, args)
+    } else if args.is_empty() {
+        template.to_string()
+    } else {
+        format!("{template}\n\n{args}")
+    }
+}
+
+/// Expand `/<name> [args]` if `name` is a defined alias
+pub(super) fn resolve_alias(settings: &BotSettings, text: &str) -> Option<String> {
+    let rest = text.strip_prefix('/')?;
+    let (command, args) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+    // Group chats may address the command to the bot: /name@my_bot
+    let name = command.split('@').next().unwrap_or(command);
+    settings
+        .prompt_aliases
+        .get(name)
+        .map(|template| expand_alias(template, args))
+}
+
+/// Handle /alias command - manage prompt shortcuts (bot-wide)
+/// Usage: /alias add <name> <template>  (`{args}` is replaced by the text after the name)
+///        /alias run <name> [args]      (or just /<name> [args])
+///        /alias remove <name>
+///        /alias list                   (also with no argument)
+pub(super) async fn handle_alias_command(
+    bot: &Bot,
+    chat_id: ChatId,
+    text: &str,
+    state: &SharedState,
+    token: &str,
+) -> ResponseResult<()> {
+    let arg = text.strip_prefix("/alias").unwrap_or("").trim();
+    let (sub, rest) = arg.split_once(char::is_whitespace).unwrap_or((arg, ""));
+    let (name, body) = rest
+        .trim()
+        .split_once(char::is_whitespace)
+        .unwrap_or((rest.trim(), ""));
+    let name = name.trim_start_matches('/').to_lowercase();
+    let body = body.trim();
+
+    if sub == "run" {
+        let prompt = {
+            let data = state.lock().await;
+            data.settings
+                .prompt_aliases
+                .get(&name)
+                .map(|template| expand_alias(template, body))
+        };
+        let Some(prompt) = prompt else {
+            shared_rate_limit_wait(state, chat_id).await;
+            bot.send_message(chat_id, format!("Unknown alias: {name}"))
+                .await?;
+            return Ok(());
+        };
+        return handle_text_message(bot, chat_id, &prompt, state).await;
+    }
+
+    let response_msg = {
+        let mut data = state.lock().await;
+        let aliases = &mut data.settings.prompt_aliases;
+        match sub {
+            "add" if name.is_empty() || body.is_empty() => {
+                "Usage: <code>/alias add &lt;name&gt; &lt;prompt template&gt;</code>".to_string()
+            }
+            "add" => {
+                if let Some(error) = alias_name_error(&name) {
+                    error
+                } else if body.len() > MAX_TEMPLATE_LEN {
+                    format!("Template too long (max {MAX_TEMPLATE_LEN} bytes).")
+                } else if !aliases.contains_key(&name) && aliases.len() >= MAX_ALIASES {
+                    format!("Too many aliases (max {MAX_ALIASES}). Remove one first.")
+                } else {
+                    aliases.insert(name.clone(), body.to_string());
+                    save_bot_settings(token, &data.settings);
+                    format!(
+                        "Alias <code>/{name}</code> saved:\n<pre>{}</pre>",
+                        html_escape(body)
+                    )
+                }
+            }
+            "remove" | "rm" => {
+                if aliases.remove(&name).is_some() {
+                    save_bot_settings(token, &data.settings);
+                    format!("Alias <code>/{name}</code> removed.")
+                } else {
+                    format!("Unknown alias: <code>{}</code>", html_escape(&name))
+                }
+            }
+            "" | "list" if aliases.is_empty() => "No aliases defined.\n\n\
+                 <code>/alias add &lt;name&gt; &lt;prompt&gt;</code> — Define (use <code>{args}</code> for the text after the name)\n\
+                 <code>/&lt;name&gt; [args]</code> or <code>/alias run &lt;name&gt; [args]</code> — Run\n\
+                 <code>/alias remove &lt;name&gt;</code> — Remove"
+                .to_string(),
+            "" | "list" => {
+                let mut names: Vec<_> = aliases.iter().collect();
+                names.sort();
+                let lines: Vec<String> = names
+                    .into_iter()
+                    .map(|(name, template)| {
+                        format!("<code>/{name}</code> — {}", html_escape(template))
+                    })
+                    .collect();
+                format!("<b>Aliases</b>\n{}", lines.join("\n"))
+            }
+            _ => "Usage: <code>/alias add|run|remove|list</code>".to_string(),
+        }
+    };
+
+    send_long_message(bot, chat_id, &response_msg, Some(ParseMode::Html), state).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_alias_expand_and_resolve() {
+        assert_eq!(
+            expand_alias("fix {args} in src", " clippy warnings "),
+            "fix clippy warnings in src"
+        );
+        assert_eq!(expand_alias("run tests", ""), "run tests");
+        assert_eq!(
+            expand_alias("run tests", "only unit"),
+            "run tests\n\nonly unit"
+        );
+
+        let mut settings = BotSettings::default();
+        settings
+            .prompt_aliases
+            .insert("lint".to_string(), "run clippy on {args}".to_string());
+        assert_eq!(
+            resolve_alias(&settings, "/lint@my_bot the parser").as_deref(),
+            Some("run clippy on the parser")
+        );
+        assert_eq!(resolve_alias(&settings, "/other"), None);
+        assert_eq!(resolve_alias(&settings, "lint"), None);
+
+        assert!(alias_name_error("lint").is_none());
+        assert!(alias_name_error("Lint!").is_some());
+        assert!(alias_name_error("stopall").is_some());
+        assert!(alias_name_error("alias").is_some());
+    }
+}
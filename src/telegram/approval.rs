@@ -0,0 +1,182 @@
+use teloxide::prelude::*;
+use teloxide::types::{CallbackQuery, InlineKeyboardButton, InlineKeyboardMarkup, MessageId};
+
+use crate::i18n;
+
+use super::bot::{is_owner, SharedState};
+use super::message::handle_text_message;
+use super::streaming::shared_rate_limit_wait;
+
+/// Callback data prefix routed to this module
+pub(super) const APPROVAL_CALLBACK_PREFIX: &str = "approve:";
+
+/// Prompts that may wait for approval in one chat at a time (keeps members from flooding it)
+const MAX_PENDING_APPROVALS: usize = 10;
+
+/// Longest prompt (bytes) that can wait for approval; the card shows it in full
+const MAX_PROMPT_PREVIEW: usize = 3000;
+
+/// A member's prompt held until the owner approves or rejects it (/approval)
+pub(super) struct PendingApproval {
+    /// Display name of the member who sent it
+    pub requester: String,
+    pub prompt: String,
+}
+
+/// The approval card. A prompt too long to show in full is refused, so the owner never
+/// approves text they have not seen.
+fn approval_text(requester: &str, prompt: &str) -> Result<String, String> {
+    if prompt.len() > MAX_PROMPT_PREVIEW {
+        return Err(format!(
+            "The prompt is too long to show for approval ({} bytes, limit {MAX_PROMPT_PREVIEW}).",
+            prompt.len()
+        ));
+    }
+    Ok(format!(
+        "🔒 {requester} asks the AI:\n\n{prompt}\n\nThe owner must approve this prompt before it runs."
+    ))
+}
+
+/// Post a member's prompt with owner-only Approve/Reject buttons instead of running it
+pub(super) async fn hold_for_approval(
+    bot: &Bot,
+    chat_id: ChatId,
+    requester: &str,
+    prompt: String,
+    state: &SharedState,
+) -> ResponseResult<()> {
+    let card = match approval_text(requester, &prompt) {
+        Ok(card) => card,
+        Err(reason) => {
+            shared_rate_limit_wait(state, chat_id).await;
+            bot.send_message(chat_id, reason).await?;
+            return Ok(());
+        }
+    };
+    let waiting = state
+        .lock()
+        .await
+        .pending_approvals
+        .keys()
+        .filter(|(chat, _)| *chat == chat_id)
+        .count();
+    if waiting >= MAX_PENDING_APPROVALS {
+        shared_rate_limit_wait(state, chat_id).await;
+        bot.send_message(
+            chat_id,
+            "Too many prompts are waiting for the owner's approval. Try again later.",
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let keyboard = InlineKeyboardMarkup::new(vec![vec![
+        InlineKeyboardButton::callback("✅ Approve", format!("{APPROVAL_CALLBACK_PREFIX}yes")),
+        InlineKeyboardButton::callback("✖ Reject", format!("{APPROVAL_CALLBACK_PREFIX}no")),
+    ]]);
+    shared_rate_limit_wait(state, chat_id).await;
+    let sent = bot
+        .send_message(chat_id, card)
+        .reply_markup(keyboard)
+        .await?;
+    state.lock().await.pending_approvals.insert(
+        (chat_id, sent.id),
+        PendingApproval {
+            requester: requester.to_string(),
+            prompt,
+        },
+    );
+    Ok(())
+}
+
+/// Handle an Approve/Reject press (owner-only). `data` is `yes` or `no`.
+pub(super) async fn handle_approval_callback(
+    bot: &Bot,
+    query: &CallbackQuery,
+    data: &str,
+    state: &SharedState,
+) -> ResponseResult<()> {
+    let Some(message) = query.message.as_ref() else {
+        bot.answer_callback_query(&query.id).await?;
+        return Ok(());
+    };
+    let chat_id = message.chat().id;
+    let key: (ChatId, MessageId) = (chat_id, message.id());
+    let approve = data == "yes";
+
+    let outcome = {
+        let mut shared = state.lock().await;
+        if !is_owner(&shared.settings, query.from.id.0) {
+            Err("Only the bot owner can approve prompts.")
+        } else if !shared.pending_approvals.contains_key(&key) {
+            Err("This request has expired.")
+        } else if approve && shared.cancel_tokens.contains_key(&chat_id) {
+            // Kept pending so the owner can approve once the running request ends
+            Err(i18n::MSG_AI_BUSY)
+        } else {
+            shared
+                .pending_approvals
+                .remove(&key)
+                .ok_or("This request has expired.")
+        }
+    };
+    let pending = match outcome {
+        Ok(pending) => pending,
+        Err(reason) => {
+            bot.answer_callback_query(&query.id).text(reason).await?;
+            return Ok(());
+        }
+    };
+    bot.answer_callback_query(&query.id).await?;
+
+    let ts = chrono::Local::now().format("%H:%M:%S");
+    logln!(
+        "  [{ts}] ◀ [button] /approval {} ({})",
+        if approve { "approved" } else { "rejected" },
+        pending.requester
+    );
+    let status = if approve {
+        "✅ Approved by the owner."
+    } else {
+        "✖ Rejected by the owner."
+    };
+    shared_rate_limit_wait(state, chat_id).await;
+    if let Err(e) = bot
+        .edit_message_text(
+            chat_id,
+            message.id(),
+            format!(
+                "{status}\n{} asked:\n\n{}",
+                pending.requester, pending.prompt
+            ),
+        )
+        .await
+    {
+        logln!("  [{ts}]   ⚠ edit_message failed (approval): {e}");
+    }
+
+    if approve {
+        handle_text_message(bot, chat_id, &pending.prompt, state).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_approval_text() {
+        let text = approval_text("alice(42)", "delete the build folder").unwrap_or_default();
+        assert!(text.starts_with("🔒 alice(42) asks the AI:\n\ndelete the build folder\n\n"));
+        let fits = "x".repeat(MAX_PROMPT_PREVIEW);
+        assert!(approval_text("bob", &fits).is_ok_and(|card| card.contains(&fits)));
+    }
+
+    #[test]
+    fn test_overlong_prompt_is_not_queued() {
+        // hold_for_approval only queues a prompt once its card is built
+        let long = "x".repeat(MAX_PROMPT_PREVIEW + 1);
+        assert!(approval_text("bob", &long).is_err());
+    }
+}
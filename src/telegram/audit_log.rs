@@ -0,0 +1,82 @@
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use teloxide::prelude::*;
+
+/// A chat's log is rotated once it grows past this size
+const MAX_LOG_SIZE: u64 = 5 * 1024 * 1024;
+
+/// Number of rotated files kept (<chat_id>.log.1 is the newest)
+const ROTATED_LOGS: usize = 3;
+
+/// Directory holding per-chat audit logs: ~/<app_dir>/logs
+fn logs_dir() -> Option<PathBuf> {
+    crate::app::data_dir().map(|d| d.join("logs"))
+}
+
+/// Shift `<log>.1` → `<log>.2` … and move the current log to `<log>.1` once it is too big
+fn rotate_if_needed(path: &Path, max_size: u64) {
+    if fs::metadata(path).map_or(true, |meta| meta.len() < max_size) {
+        return;
+    }
+    let rotated = |n: usize| PathBuf::from(format!("{}.{n}", path.display()));
+    let _ = fs::remove_file(rotated(ROTATED_LOGS));
+    for n in (1..ROTATED_LOGS).rev() {
+        let _ = fs::rename(rotated(n), rotated(n + 1));
+    }
+    let _ = fs::rename(path, rotated(1));
+}
+
+fn write_entry(path: &Path, prompt: &str, response: &str) {
+    if let Some(parent) = path.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+    rotate_if_needed(path, MAX_LOG_SIZE);
+    if let Ok(mut file) = OpenOptions::new().create(true).append(true).open(path) {
+        let timestamp = chrono::Local::now().format("%Y-%m-%d %H:%M:%S");
+        let _ = writeln!(
+            file,
+            "[{timestamp}] USER\n{prompt}\n[{timestamp}] ASSISTANT\n{response}\n"
+        );
+    }
+}
+
+/// Append a prompt/response pair to the chat's audit log (/auditlog on).
+/// Unlike session files, the log is never trimmed by the history cap or /clear.
+/// The file is written on the blocking pool, so callers may hold the state lock.
+pub(super) fn append_audit_log(chat_id: ChatId, prompt: String, response: String) {
+    let Some(dir) = logs_dir() else {
+        return;
+    };
+    tokio::task::spawn_blocking(move || {
+        write_entry(&dir.join(format!("{}.log", chat_id.0)), &prompt, &response);
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_audit_log_rotation() {
+        let dir = std::env::temp_dir().join("opencodex_test_audit_log");
+        let _ = fs::remove_dir_all(&dir);
+        let path = dir.join("42.log");
+
+        write_entry(&path, "hello", "hi there");
+        let content = fs::read_to_string(&path).unwrap_or_default();
+        assert!(content.contains("USER\nhello\n"));
+        assert!(content.contains("ASSISTANT\nhi there\n"));
+
+        for _ in 0..ROTATED_LOGS + 2 {
+            rotate_if_needed(&path, 1);
+            write_entry(&path, "again", "ok");
+        }
+        assert!(path.exists());
+        assert!(dir.join(format!("42.log.{ROTATED_LOGS}")).exists());
+        assert!(!dir.join(format!("42.log.{}", ROTATED_LOGS + 1)).exists());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
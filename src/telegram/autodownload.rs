@@ -0,0 +1,306 @@
+use std::path::{Path, PathBuf};
+
+use teloxide::prelude::*;
+use teloxide::types::{
+    CallbackQuery, ChatAction, InlineKeyboardButton, InlineKeyboardMarkup, InputFile, MessageId,
+};
+
+use super::bot::{is_owner, SharedState};
+use super::bot_api::upload_limit;
+use super::diskusage::format_bytes;
+use super::streaming::shared_rate_limit_wait;
+
+/// Callback data prefix routed to this module
+pub(super) const AUTODOWNLOAD_CALLBACK_PREFIX: &str = "autodl:";
+
+/// Files up to this size are sent right away; larger ones get a download button
+const AUTO_SEND_MAX_BYTES: u64 = 64 * 1024;
+
+/// Maximum number of files sent or offered per turn (keeps the chat and keyboard usable)
+const MAX_CHANGED_FILES: usize = 8;
+
+/// Files a turn wrote that were too large to send unasked, awaiting a button press
+pub(super) struct PendingDownloads {
+    /// Message carrying the buttons (older buttons are rejected)
+    pub message_id: MessageId,
+    /// (path, sent) per offered file
+    pub files: Vec<(PathBuf, bool)>,
+}
+
+/// Paths a tool call created or modified, from its name and JSON input: Write/Edit/MultiEdit
+/// (`file_path`), NotebookEdit (`notebook_path`) and apply_patch (Codex `changes`, or the
+/// patch text's `*** Add File:` / `*** Update File:` / `*** Move to:` headers)
+pub(super) fn changed_file_paths(name: &str, input: &str) -> Vec<String> {
+    let Ok(v) = serde_json::from_str::<serde_json::Value>(input) else {
+        return Vec::new();
+    };
+    let field = |key: &str| {
+        v.get(key)
+            .and_then(|p| p.as_str())
+            .map(String::from)
+            .into_iter()
+            .collect()
+    };
+    match name {
+        "Write" | "Edit" | "MultiEdit" => field("file_path"),
+        "NotebookEdit" => field("notebook_path"),
+        "apply_patch" => {
+            if let Some(changes) = v.get("changes").and_then(|c| c.as_array()) {
+                return changes
+                    .iter()
+                    .filter(|c| c.get("kind").and_then(|k| k.as_str()) != Some("delete"))
+                    .filter_map(|c| c.get("path").and_then(|p| p.as_str()))
+                    .map(String::from)
+                    .collect();
+            }
+            let patch = v
+                .get("input")
+                .or_else(|| v.get("patch"))
+                .and_then(|p| p.as_str())
+                .unwrap_or("");
+            patch
+                .lines()
+                .filter_map(|line| {
+                    ["*** Add File: ", "*** Update File: ", "*** Move to: "]
+                        .iter()
+                        .find_map(|header| line.strip_prefix(header))
+                })
+                .map(|path| path.trim().to_string())
+                .collect()
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// Resolve paths against the session directory and keep existing regular files inside it,
+/// once each (a turn may also touch files elsewhere, which are not sent to the chat)
+fn existing_files(paths: &[String], root: &str) -> Vec<(PathBuf, u64)> {
+    let Ok(root) = Path::new(root).canonicalize() else {
+        return Vec::new();
+    };
+    let mut files: Vec<(PathBuf, u64)> = Vec::new();
+    for path in paths {
+        let Ok(path) = root.join(path).canonicalize() else {
+            continue;
+        };
+        if !path.starts_with(&root) || files.iter().any(|(seen, _)| *seen == path) {
+            continue;
+        }
+        if let Ok(meta) = std::fs::metadata(&path) {
+            if meta.is_file() {
+                files.push((path, meta.len()));
+            }
+        }
+    }
+    files
+}
+
+/// Path relative to the (canonical) session directory, for buttons and lists
+fn display_name(path: &Path, root: &str) -> String {
+    let root = Path::new(root)
+        .canonicalize()
+        .unwrap_or_else(|_| PathBuf::from(root));
+    path.strip_prefix(&root)
+        .unwrap_or(path)
+        .display()
+        .to_string()
+}
+
+/// Keyboard with one button per file not sent yet, plus Dismiss; None once all were sent
+fn render_buttons(files: &[(PathBuf, bool)], root: &str) -> Option<InlineKeyboardMarkup> {
+    let mut rows: Vec<Vec<InlineKeyboardButton>> = files
+        .iter()
+        .enumerate()
+        .filter(|(_, (_, sent))| !sent)
+        .map(|(idx, (path, _))| {
+            vec![InlineKeyboardButton::callback(
+                format!("⬇ {}", display_name(path, root)),
+                format!("{AUTODOWNLOAD_CALLBACK_PREFIX}{idx}"),
+            )]
+        })
+        .collect();
+    if rows.is_empty() {
+        return None;
+    }
+    rows.push(vec![InlineKeyboardButton::callback(
+        "✖ Dismiss",
+        format!("{AUTODOWNLOAD_CALLBACK_PREFIX}skip"),
+    )]);
+    Some(InlineKeyboardMarkup::new(rows))
+}
+
+/// After a turn, send the files it created or modified (/autodownload on): small files are
+/// sent right away, larger ones are offered with a download button each
+pub(super) async fn offer_changed_files(
+    bot: &Bot,
+    chat_id: ChatId,
+    paths: &[String],
+    root: &str,
+    state: &SharedState,
+) -> ResponseResult<()> {
+    let files: Vec<(PathBuf, u64)> = existing_files(paths, root)
+        .into_iter()
+        .filter(|(_, size)| *size <= upload_limit())
+        .take(MAX_CHANGED_FILES)
+        .collect();
+    let (small, large): (Vec<_>, Vec<_>) = files
+        .into_iter()
+        .partition(|(_, size)| *size <= AUTO_SEND_MAX_BYTES);
+
+    for (path, _) in &small {
+        shared_rate_limit_wait(state, chat_id).await;
+        let _ = bot
+            .send_chat_action(chat_id, ChatAction::UploadDocument)
+            .await;
+        shared_rate_limit_wait(state, chat_id).await;
+        bot.send_document(chat_id, InputFile::file(path)).await?;
+    }
+    if large.is_empty() {
+        return Ok(());
+    }
+
+    let lines: Vec<String> = large
+        .iter()
+        .map(|(path, size)| format!("• {} ({})", display_name(path, root), format_bytes(*size)))
+        .collect();
+    let files: Vec<(PathBuf, bool)> = large.into_iter().map(|(path, _)| (path, false)).collect();
+    let Some(keyboard) = render_buttons(&files, root) else {
+        return Ok(());
+    };
+
+    shared_rate_limit_wait(state, chat_id).await;
+    let sent = bot
+        .send_message(
+            chat_id,
+            format!("Files changed in this turn:\n{}", lines.join("\n")),
+        )
+        .reply_markup(keyboard)
+        .await?;
+    state.lock().await.pending_downloads.insert(
+        chat_id,
+        PendingDownloads {
+            message_id: sent.id,
+            files,
+        },
+    );
+    Ok(())
+}
+
+/// Handle a download button press (owner-only). `data` is a file index or `skip`.
+pub(super) async fn handle_autodownload_callback(
+    bot: &Bot,
+    query: &CallbackQuery,
+    data: &str,
+    state: &SharedState,
+) -> ResponseResult<()> {
+    let Some(message) = query.message.as_ref() else {
+        bot.answer_callback_query(&query.id).await?;
+        return Ok(());
+    };
+    let chat_id = message.chat().id;
+
+    let outcome = {
+        let mut shared = state.lock().await;
+        let root = shared
+            .sessions
+            .get(&chat_id)
+            .and_then(|s| s.current_path.clone())
+            .unwrap_or_default();
+        if !is_owner(&shared.settings, query.from.id.0) {
+            Err("Only the bot owner can download files.")
+        } else {
+            match shared.pending_downloads.get_mut(&chat_id) {
+                Some(pending) if pending.message_id == message.id() => {
+                    let target = data
+                        .parse::<usize>()
+                        .ok()
+                        .filter(|idx| pending.files.get(*idx).is_some_and(|(_, sent)| !sent));
+                    if let Some(idx) = target {
+                        pending.files[idx].1 = true;
+                    }
+                    let path = target.map(|idx| pending.files[idx].0.clone());
+                    let keyboard = if data == "skip" {
+                        None
+                    } else {
+                        render_buttons(&pending.files, &root)
+                    };
+                    if keyboard.is_none() {
+                        shared.pending_downloads.remove(&chat_id);
+                    }
+                    Ok((path, keyboard))
+                }
+                _ => Err("This download offer has expired."),
+            }
+        }
+    };
+
+    let (path, keyboard) = match outcome {
+        Ok(result) => result,
+        Err(reason) => {
+            bot.answer_callback_query(&query.id).text(reason).await?;
+            return Ok(());
+        }
+    };
+    bot.answer_callback_query(&query.id).await?;
+
+    shared_rate_limit_wait(state, chat_id).await;
+    let edit = match keyboard {
+        Some(keyboard) => {
+            bot.edit_message_reply_markup(chat_id, message.id())
+                .reply_markup(keyboard)
+                .await
+        }
+        None => bot.edit_message_reply_markup(chat_id, message.id()).await,
+    };
+    if let Err(e) = edit {
+        let ts = chrono::Local::now().format("%H:%M:%S");
+        logln!("  [{ts}]   ⚠ edit_message failed (autodownload): {e}");
+    }
+
+    let Some(path) = path else {
+        return Ok(());
+    };
+    let ts = chrono::Local::now().format("%H:%M:%S");
+    logln!("  [{ts}] ◀ [button] download {}", path.display());
+    shared_rate_limit_wait(state, chat_id).await;
+    if path.is_file() {
+        bot.send_document(chat_id, InputFile::file(&path)).await?;
+    } else {
+        bot.send_message(chat_id, format!("File not found: {}", path.display()))
+            .await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_changed_file_paths() {
+        assert_eq!(
+            changed_file_paths("Write", r#"{"file_path":"src/a.rs","content":"x"}"#),
+            vec!["src/a.rs"]
+        );
+        assert_eq!(
+            changed_file_paths("NotebookEdit", r#"{"notebook_path":"n.ipynb"}"#),
+            vec!["n.ipynb"]
+        );
+        assert_eq!(
+            changed_file_paths(
+                "apply_patch",
+                r#"{"changes":[{"path":"/p/a.rs","kind":"add"},{"path":"/p/b.rs","kind":"delete"},{"path":"/p/c.rs","kind":"update"}]}"#
+            ),
+            vec!["/p/a.rs", "/p/c.rs"]
+        );
+        assert_eq!(
+            changed_file_paths(
+                "apply_patch",
+                r#"{"input":"*** Begin Patch\n*** Add File: new.txt\n+hi\n*** Update File: old.rs\n*** Move to: moved.rs\n*** Delete File: gone.rs\n*** End Patch"}"#
+            ),
+            vec!["new.txt", "old.rs", "moved.rs"]
+        );
+        assert!(changed_file_paths("Read", r#"{"file_path":"src/a.rs"}"#).is_empty());
+        assert!(changed_file_paths("Bash", "ls -la").is_empty());
+    }
+}
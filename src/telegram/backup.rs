@@ -0,0 +1,366 @@
+use teloxide::prelude::*;
+use teloxide::types::{
+    CallbackQuery, InlineKeyboardButton, InlineKeyboardMarkup, InputFile, MessageId, ParseMode,
+};
+
+use super::bot::{is_owner, BotSettings, SharedState};
+use super::file_ops::download_telegram_file;
+use super::settings::describe_settings_changes;
+use super::storage::{
+    bot_settings_entry, parse_bot_settings_entry, save_bot_settings, validate_bot_settings_entry,
+};
+use super::streaming::{html_escape, shared_rate_limit_wait};
+
+/// Callback data prefix routed to this module
+pub(super) const RESTORE_CALLBACK_PREFIX: &str = "restore:";
+
+/// File name of the document sent by /backup
+const BACKUP_FILE_NAME: &str = "opencodex_settings_backup.json";
+
+/// Uploaded backups larger than this are rejected without parsing
+const MAX_BACKUP_SIZE: usize = 1024 * 1024;
+
+/// An uploaded backup awaiting confirmation (/restore)
+pub(super) struct PendingRestore {
+    /// Message carrying the Apply/Cancel buttons (older buttons are rejected)
+    pub message_id: MessageId,
+    /// Validated settings entry from the backup file
+    pub entry: serde_json::Value,
+}
+
+/// Merge a backup entry into the current settings: per-chat maps are combined (backup wins
+/// for chats present in both), and bot-wide values are taken from the backup when present.
+/// The owner is never changed, so a restore cannot lock out the current owner, and madmax is
+/// never switched on.
+fn merge_settings(current: &BotSettings, entry: &serde_json::Value) -> BotSettings {
+    let backup = parse_bot_settings_entry(entry);
+    let mut merged = current.clone();
+    merged.allowed_tools.extend(backup.allowed_tools);
+    merged.last_sessions.extend(backup.last_sessions);
+    merged
+        .as_public_for_group_chat
+        .extend(backup.as_public_for_group_chat);
+    merged.prompt_prefixes.extend(backup.prompt_prefixes);
+    merged.prompt_suffixes.extend(backup.prompt_suffixes);
+    merged.prompt_aliases.extend(backup.prompt_aliases);
+    merged.command_aliases.extend(backup.command_aliases);
+    merged
+        .extract_code_blocks
+        .extend(backup.extract_code_blocks);
+    merged.verbose_output.extend(backup.verbose_output);
+    merged.input_filter.extend(backup.input_filter);
+    merged.auto_compact.extend(backup.auto_compact);
+    merged.shell_confirm.extend(backup.shell_confirm);
+    merged.progress_file.extend(backup.progress_file);
+    merged.clear_confirm.extend(backup.clear_confirm);
+    merged.audit_log.extend(backup.audit_log);
+    // Turning madmax on needs the /madmax confirmation, so a backup can only turn it off
+    merged
+        .madmax
+        .extend(backup.madmax.into_iter().filter(|(_, enabled)| !enabled));
+    merged.forward_wait.extend(backup.forward_wait);
+    merged.shell_enabled.extend(backup.shell_enabled);
+    merged.media_hint.extend(backup.media_hint);
+    merged.short_paths.extend(backup.short_paths);
+    merged.stream_output.extend(backup.stream_output);
+    merged.history_recap.extend(backup.history_recap);
+    merged.stderr_notes.extend(backup.stderr_notes);
+    merged.uploads_enabled.extend(backup.uploads_enabled);
+    merged.auto_download.extend(backup.auto_download);
+    merged.strict_code.extend(backup.strict_code);
+    merged.prompt_approval.extend(backup.prompt_approval);
+    merged.typing_pause.extend(backup.typing_pause);
+    merged.project_hint.extend(backup.project_hint);
+    merged.muted_chats.extend(backup.muted_chats);
+    merged.mirror_chats.extend(backup.mirror_chats);
+    merged.start_previews.extend(backup.start_previews);
+    merged.tool_output_limits.extend(backup.tool_output_limits);
+    merged.backend_args.extend(backup.backend_args);
+    merged.reply_styles.extend(backup.reply_styles);
+    merged.max_response_chars.extend(backup.max_response_chars);
+    merged.request_cooldowns.extend(backup.request_cooldowns);
+    merged.command_cooldowns.extend(backup.command_cooldowns);
+    if entry.get("command_cooldowns_include_owner").is_some() {
+        merged.command_cooldowns_include_owner = backup.command_cooldowns_include_owner;
+    }
+    merged.upload_subdirs.extend(backup.upload_subdirs);
+    merged.start_dirs.extend(backup.start_dirs);
+    merged.response_formats.extend(backup.response_formats);
+    merged.response_languages.extend(backup.response_languages);
+    if entry.get("error_notify").is_some() {
+        merged.error_notify = backup.error_notify;
+    }
+    if entry.get("default_public").is_some() {
+        merged.default_public = backup.default_public;
+    }
+    if entry.get("greeting_disabled").is_some() {
+        merged.greeting_disabled = backup.greeting_disabled;
+    }
+    if entry.get("greeting_text").is_some() {
+        merged.greeting_text = backup.greeting_text;
+    }
+    if entry.get("footer").is_some() {
+        merged.footer = backup.footer;
+    }
+    if entry.get("response_format").is_some() {
+        merged.response_format = backup.response_format;
+    }
+    if entry.get("prompt_limit").is_some() {
+        merged.prompt_limit = backup.prompt_limit;
+    }
+    if merged.owner_dm_chat_id.is_none() && merged.owner_user_id == backup.owner_user_id {
+        merged.owner_dm_chat_id = backup.owner_dm_chat_id;
+    }
+    if merged.owner_user_id.is_none() {
+        merged.owner_user_id = backup.owner_user_id;
+    }
+    merged
+}
+
+/// Handle /backup command - send this bot's settings (token redacted) as a JSON document
+pub(super) async fn handle_backup_command(
+    bot: &Bot,
+    chat_id: ChatId,
+    state: &SharedState,
+) -> ResponseResult<()> {
+    let mut entry = {
+        let data = state.lock().await;
+        bot_settings_entry(&data.settings)
+    };
+    entry["token"] = serde_json::json!("[redacted]");
+    let json = serde_json::to_string_pretty(&entry).unwrap_or_default();
+
+    shared_rate_limit_wait(state, chat_id).await;
+    bot.send_document(
+        chat_id,
+        InputFile::memory(json.into_bytes()).file_name(BACKUP_FILE_NAME),
+    )
+    .caption("Settings backup (token redacted). Send this file back with the caption /restore to restore it.")
+    .await?;
+
+    Ok(())
+}
+
+/// Handle /restore typed as text: explain how to restore
+pub(super) async fn handle_restore_command(
+    bot: &Bot,
+    chat_id: ChatId,
+    state: &SharedState,
+) -> ResponseResult<()> {
+    shared_rate_limit_wait(state, chat_id).await;
+    bot.send_message(
+        chat_id,
+        "Send a settings backup file (from /backup) with the caption <code>/restore</code>.\n\
+         The changes are shown for confirmation before anything is applied.",
+    )
+    .parse_mode(ParseMode::Html)
+    .await?;
+    Ok(())
+}
+
+/// Handle an uploaded backup file (caption /restore): validate it and ask for confirmation
+pub(super) async fn handle_restore_upload(
+    bot: &Bot,
+    chat_id: ChatId,
+    msg: &Message,
+    state: &SharedState,
+) -> ResponseResult<()> {
+    let Some(doc) = msg.document() else {
+        return handle_restore_command(bot, chat_id, state).await;
+    };
+
+    let parsed = if doc.file.size as usize > MAX_BACKUP_SIZE {
+        Err(format!(
+            "file too large (limit {} KB)",
+            MAX_BACKUP_SIZE / 1024
+        ))
+    } else {
+        shared_rate_limit_wait(state, chat_id).await;
+        download_telegram_file(bot, &doc.file.id, MAX_BACKUP_SIZE as u64)
+            .await?
+            .and_then(|bytes| serde_json::from_slice(&bytes).map_err(|e| e.to_string()))
+            .and_then(|entry: serde_json::Value| validate_bot_settings_entry(&entry).map(|_| entry))
+    };
+    let entry = match parsed {
+        Ok(entry) => entry,
+        Err(e) => {
+            shared_rate_limit_wait(state, chat_id).await;
+            bot.send_message(chat_id, format!("Invalid backup: {e}"))
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let changes = {
+        let data = state.lock().await;
+        describe_settings_changes(&data.settings, &merge_settings(&data.settings, &entry))
+    };
+    if changes.is_empty() {
+        shared_rate_limit_wait(state, chat_id).await;
+        bot.send_message(
+            chat_id,
+            "Backup matches the current settings. Nothing to restore.",
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let lines: Vec<String> = changes
+        .iter()
+        .map(|c| format!("• {}", html_escape(c)))
+        .collect();
+    let keyboard = InlineKeyboardMarkup::new(vec![vec![
+        InlineKeyboardButton::callback("Apply", format!("{RESTORE_CALLBACK_PREFIX}apply")),
+        InlineKeyboardButton::callback("Cancel", format!("{RESTORE_CALLBACK_PREFIX}cancel")),
+    ]]);
+    shared_rate_limit_wait(state, chat_id).await;
+    let sent = bot
+        .send_message(
+            chat_id,
+            format!(
+                "<b>Restore settings?</b>\n{}\n\nThe backup is merged into the current settings; \
+                 the owner is kept.",
+                lines.join("\n")
+            ),
+        )
+        .parse_mode(ParseMode::Html)
+        .reply_markup(keyboard)
+        .await?;
+
+    let mut data = state.lock().await;
+    data.pending_restores.insert(
+        chat_id,
+        PendingRestore {
+            message_id: sent.id,
+            entry,
+        },
+    );
+    Ok(())
+}
+
+/// Handle an Apply/Cancel press (owner-only). `data` is `apply` or `cancel`.
+pub(super) async fn handle_restore_callback(
+    bot: &Bot,
+    query: &CallbackQuery,
+    data: &str,
+    state: &SharedState,
+    token: &str,
+) -> ResponseResult<()> {
+    let Some(message) = query.message.as_ref() else {
+        bot.answer_callback_query(&query.id).await?;
+        return Ok(());
+    };
+    let chat_id = message.chat().id;
+
+    let outcome = {
+        let mut shared = state.lock().await;
+        if !is_owner(&shared.settings, query.from.id.0) {
+            Err("Only the bot owner can restore settings.")
+        } else {
+            match shared.pending_restores.remove(&chat_id) {
+                Some(pending) if pending.message_id == message.id() => {
+                    if data == "apply" {
+                        // Merge against the settings as they are now, not at upload time
+                        let merged = merge_settings(&shared.settings, &pending.entry);
+                        shared.settings = merged;
+                        save_bot_settings(token, &shared.settings);
+                        Ok("Settings restored.")
+                    } else {
+                        Ok("Restore cancelled.")
+                    }
+                }
+                Some(pending) => {
+                    shared.pending_restores.insert(chat_id, pending);
+                    Err("This restore request has expired.")
+                }
+                None => Err("This restore request has expired."),
+            }
+        }
+    };
+
+    match outcome {
+        Ok(result) => {
+            bot.answer_callback_query(&query.id).await?;
+            let ts = chrono::Local::now().format("%H:%M:%S");
+            logln!("  [{ts}] ◀ [button] /restore {data}");
+            shared_rate_limit_wait(state, chat_id).await;
+            if let Err(e) = bot.edit_message_text(chat_id, message.id(), result).await {
+                logln!("  [{ts}]   ⚠ edit_message failed (restore): {e}");
+            }
+        }
+        Err(reason) => {
+            bot.answer_callback_query(&query.id).text(reason).await?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::footer::MAX_FOOTER_LEN;
+    use super::*;
+
+    #[test]
+    fn test_backup_roundtrip_and_merge() {
+        let mut current = BotSettings {
+            owner_user_id: Some(1),
+            ..Default::default()
+        };
+        current.verbose_output.insert("10".to_string(), false);
+
+        let mut backed_up = BotSettings {
+            owner_user_id: Some(2),
+            error_notify: true,
+            ..Default::default()
+        };
+        backed_up
+            .as_public_for_group_chat
+            .insert("-100".to_string(), true);
+        backed_up.madmax.insert("-100".to_string(), true);
+        backed_up.madmax.insert("10".to_string(), false);
+        let mut entry = bot_settings_entry(&backed_up);
+        entry["token"] = serde_json::json!("[redacted]");
+        assert_eq!(validate_bot_settings_entry(&entry), Ok(()));
+
+        let merged = merge_settings(&current, &entry);
+        assert_eq!(merged.owner_user_id, Some(1));
+        assert!(merged.error_notify);
+        assert_eq!(merged.as_public_for_group_chat.get("-100"), Some(&true));
+        assert_eq!(merged.verbose_output.get("10"), Some(&false));
+        assert_eq!(merged.madmax.get("-100"), None);
+        assert_eq!(merged.madmax.get("10"), Some(&false));
+
+        assert!(validate_bot_settings_entry(&serde_json::json!([])).is_err());
+        assert!(validate_bot_settings_entry(&serde_json::json!({"owner_user_id": "x"})).is_err());
+        assert!(validate_bot_settings_entry(&serde_json::json!({"unknown": 1})).is_err());
+        let footer = "x".repeat(MAX_FOOTER_LEN + 1);
+        assert!(validate_bot_settings_entry(&serde_json::json!({ "footer": footer })).is_err());
+        assert_eq!(
+            parse_bot_settings_entry(&serde_json::json!({ "footer": footer })).footer,
+            None
+        );
+        assert!(
+            validate_bot_settings_entry(&serde_json::json!({"allowed_tools": {"1": [1]}})).is_err()
+        );
+        assert_eq!(
+            validate_bot_settings_entry(&serde_json::json!({"allowed_tools": ["Bash"]})),
+            Ok(())
+        );
+        // Settings saved under former key names still load
+        let legacy = serde_json::json!({
+            "start_dir": {"10": "/srv"},
+            "reply_style": {"10": "new"},
+            "chat_response_format": {"10": "plain"},
+        });
+        assert_eq!(validate_bot_settings_entry(&legacy), Ok(()));
+        let parsed = parse_bot_settings_entry(&legacy);
+        assert_eq!(parsed.start_dirs.get("10"), Some(&"/srv".to_string()));
+        assert_eq!(parsed.reply_styles.len(), 1);
+        assert_eq!(parsed.response_formats.len(), 1);
+        // ...and are written back under the current names
+        let saved = bot_settings_entry(&parsed);
+        assert!(saved.get("start_dirs").is_some() && saved.get("start_dir").is_none());
+        assert!(saved.get("response_formats").is_some());
+    }
+}
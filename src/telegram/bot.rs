@@ -1,12 +1,33 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 use std::sync::Arc;
 
+use serde::{Deserialize, Serialize};
 use teloxide::prelude::*;
 use tokio::sync::Mutex;
 
-use crate::codex::{CancelToken, DEFAULT_ALLOWED_TOOLS};
+use crate::codex::{CancelToken, RawStreamLog, DEFAULT_ALLOWED_TOOLS};
+use crate::session::PromptLimit;
+
+use super::agents::AgentTask;
+use super::approval::PendingApproval;
+use super::autodownload::PendingDownloads;
+use super::backup::PendingRestore;
+use super::breaker::BackendBreaker;
+use super::cron::CronJob;
+use super::extract::PendingExtraction;
+use super::media_group::PendingMediaGroup;
+use super::notify::OwnerNotifyState;
+use super::paste::PendingPaste;
+use super::project_config::ProjectConfig;
+use super::project_kind::ProjectKind;
+use super::run::PendingRun;
+use super::session_backup::PendingSessionRestore;
+use super::shell_guard::PendingShellCommand;
+use super::streaming::ResponseFormat;
+use super::summary::PendingSummary;
 
 /// Per-chat session state
+#[derive(Default)]
 pub(super) struct ChatSession {
     pub session_id: Option<String>,
     pub current_path: Option<String>,
@@ -14,8 +35,98 @@ pub(super) struct ChatSession {
     /// File upload records not yet sent to Claude Code AI.
     /// Drained and prepended to the next user prompt so Claude Code knows about uploaded files.
     pub pending_uploads: Vec<String>,
+    /// Forwarded messages held until the next prompt (/forwardwait on), already wrapped
+    pub pending_forwards: Vec<String>,
+    /// (path, labelled contents) of files staged for the next prompt (/context)
+    pub pending_contexts: Vec<(String, String)>,
     /// Set to true by /clear to prevent a racing polling loop from re-populating history.
     pub cleared: bool,
+    /// User-assigned label for the current session (set via /label)
+    pub label: Option<String>,
+    /// Message ID of the bot's most recent AI response (target of /pin)
+    pub last_response_msg_id: Option<teloxide::types::MessageId>,
+    /// Message ID pinned via /pin (target of /unpin)
+    pub pinned_msg_id: Option<teloxide::types::MessageId>,
+    /// Recent AI response message IDs with the prompt that produced them (for reaction feedback)
+    pub recent_responses: Vec<(teloxide::types::MessageId, String)>,
+    /// Recent owner prompt message IDs with the prompt they sent (for reaction commands)
+    pub recent_prompts: Vec<(teloxide::types::MessageId, String)>,
+    /// Defaults loaded from the session directory's `.opencodex.toml`
+    pub project_config: Option<ProjectConfig>,
+    /// Project type detected in the working directory, for /projecthint
+    pub project_kind: Option<ProjectKind>,
+}
+
+/// How much history /start shows when it restores a session (/startpreview)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub(super) struct StartPreview {
+    /// Number of most recent history items listed
+    pub items: usize,
+    /// Characters shown per item before it is cut off with "..."
+    pub chars: usize,
+}
+
+impl Default for StartPreview {
+    fn default() -> Self {
+        Self {
+            items: 5,
+            chars: 200,
+        }
+    }
+}
+
+/// How much of each tool result is shown in verbose responses (/tooloutput)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub(super) struct ToolOutputLimits {
+    /// Characters kept from a successful tool result
+    pub result: usize,
+    /// Characters kept from a failed tool result
+    pub error: usize,
+}
+
+impl Default for ToolOutputLimits {
+    fn default() -> Self {
+        Self {
+            result: 300,
+            error: 500,
+        }
+    }
+}
+
+/// How the finished answer is delivered (/replystyle)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(super) enum ReplyStyle {
+    /// The streaming placeholder is edited into the answer (default)
+    #[default]
+    Edit,
+    /// The placeholder is kept as a progress log and the answer is sent as a new message
+    New,
+}
+
+impl ReplyStyle {
+    pub fn name(self) -> &'static str {
+        match self {
+            ReplyStyle::Edit => "edit",
+            ReplyStyle::New => "new",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "edit" => Some(ReplyStyle::Edit),
+            "new" => Some(ReplyStyle::New),
+            _ => None,
+        }
+    }
+}
+
+/// Untruncated result of the last tool call in a chat's most recent request (/output)
+pub(super) struct LastToolOutput {
+    /// Summary of the tool call that produced it, if one was seen
+    pub tool: Option<String>,
+    pub content: String,
+    pub is_error: bool,
 }
 
 /// Bot-level settings persisted to disk
@@ -26,18 +137,131 @@ pub(super) struct BotSettings {
     pub last_sessions: HashMap<String, String>,
     /// Telegram user ID of the registered owner (imprinting auth)
     pub owner_user_id: Option<u64>,
-    /// chat_id (string) -> true if group chat is public (non-owner users allowed)
+    /// Owner's private chat with the bot (captured at imprinting or the owner's next DM)
+    pub owner_dm_chat_id: Option<i64>,
+    /// DM the owner when AI requests or shell commands fail in other chats (/notify)
+    pub error_notify: bool,
+    /// chat_id (string) -> true if group chat is public (non-owner users allowed); an entry
+    /// overrides `default_public` either way
     pub as_public_for_group_chat: HashMap<String, bool>,
+    /// Groups without a /public setting of their own are public (/defaultpublic)
+    pub default_public: bool,
+    /// chat_id (string) -> standing instruction prepended to every prompt (/prefix)
+    pub prompt_prefixes: HashMap<String, String>,
+    /// chat_id (string) -> standing instruction appended to every prompt (/suffix)
+    pub prompt_suffixes: HashMap<String, String>,
+    /// alias name -> prompt template run by /<name> (/alias)
+    pub prompt_aliases: HashMap<String, String>,
+    /// chat_id (string) -> alias name -> built-in command it runs (/cmdalias)
+    pub command_aliases: HashMap<String, HashMap<String, String>>,
+    /// chat_id (string) -> true if file-tagged code blocks are offered for writing (/extract)
+    pub extract_code_blocks: HashMap<String, bool>,
+    /// chat_id (string) -> false if tool use/result lines are hidden from responses (/verbose off)
+    pub verbose_output: HashMap<String, bool>,
+    /// chat_id (string) -> false if the prompt-injection filter is disabled (/filter off)
+    pub input_filter: HashMap<String, bool>,
+    /// chat_id (string) -> true if old history is summarized instead of dropped (/compact)
+    pub auto_compact: HashMap<String, bool>,
+    /// chat_id (string) -> true if destructive `!` commands need confirmation (/shellconfirm)
+    pub shell_confirm: HashMap<String, bool>,
+    /// chat_id (string) -> true if the backend's progress file is tailed (/progressfile)
+    pub progress_file: HashMap<String, bool>,
+    /// chat_id (string) -> true if /clear asks for confirmation first (/clearconfirm)
+    pub clear_confirm: HashMap<String, bool>,
+    /// chat_id (string) -> true if prompts/responses are appended to logs/<chat_id>.log (/auditlog)
+    pub audit_log: HashMap<String, bool>,
+    /// chat_id (string) -> approvals and sandbox bypassed (/madmax); absent follows --madmax
+    pub madmax: HashMap<String, bool>,
+    /// chat_id (string) -> true if forwarded messages wait for a follow-up prompt (/forwardwait)
+    pub forward_wait: HashMap<String, bool>,
+    /// chat_id (string) -> false if `!` shell commands are disabled (/shell off)
+    pub shell_enabled: HashMap<String, bool>,
+    /// chat_id (string) -> false if unsupported media (stickers, GIFs, ...) is ignored
+    /// silently (/mediahint off)
+    pub media_hint: HashMap<String, bool>,
+    /// chat_id (string) -> true if paths are shown shortened with `~` / `$PROJECT` (/shortpaths)
+    pub short_paths: HashMap<String, bool>,
+    /// chat_id (string) -> false if responses appear only when complete (/stream off)
+    pub stream_output: HashMap<String, bool>,
+    /// chat_id (string) -> true if new backend sessions get a recap of recent history (/recap)
+    pub history_recap: HashMap<String, bool>,
+    /// chat_id (string) -> true if stderr of successful runs is shown under responses (/stderr)
+    pub stderr_notes: HashMap<String, bool>,
+    /// chat_id (string) -> false if file and photo uploads are refused (/uploads)
+    pub uploads_enabled: HashMap<String, bool>,
+    /// chat_id (string) -> true if files a turn writes are sent after it (/autodownload)
+    pub auto_download: HashMap<String, bool>,
+    /// chat_id (string) -> true if code blocks are sent as separate plain-text messages
+    /// (/strictcode)
+    pub strict_code: HashMap<String, bool>,
+    /// chat_id (string) -> true if member prompts in a public group wait for the owner's
+    /// approval (/approval)
+    pub prompt_approval: HashMap<String, bool>,
+    /// chat_id (string) -> true if streaming edits wait while the chat is active
+    /// (/typingpause)
+    pub typing_pause: HashMap<String, bool>,
+    /// chat_id (string) -> true if the system prompt names the detected project type
+    /// (/projecthint)
+    pub project_hint: HashMap<String, bool>,
+    /// chat_id (string) -> history preview shown by /start on restore (/startpreview)
+    pub start_previews: HashMap<String, StartPreview>,
+    /// chat_id (string) -> truncation of tool results in responses (/tooloutput)
+    pub tool_output_limits: HashMap<String, ToolOutputLimits>,
+    /// chat_id (string) -> extra backend arguments placed before `exec` (/backendargs)
+    pub backend_args: HashMap<String, Vec<String>>,
+    /// chat_id (string) -> how the final answer is delivered (/replystyle); absent means edit
+    pub reply_styles: HashMap<String, ReplyStyle>,
+    /// chat_id (string) -> characters after which AI responses are cut in the chat (/maxresponse)
+    pub max_response_chars: HashMap<String, usize>,
+    /// chat_id (string) -> seconds each non-owner user waits between AI requests (/cooldown)
+    pub request_cooldowns: HashMap<String, u64>,
+    /// command (e.g. "/summary") -> seconds between its uses in each chat (/cmdcooldown)
+    pub command_cooldowns: HashMap<String, u64>,
+    /// Apply /cmdcooldown to the owner as well (`/cmdcooldown owner on`)
+    pub command_cooldowns_include_owner: bool,
+    /// chat_id (string) -> language every response must use (/respondlang); absent answers in
+    /// the user's language
+    pub response_languages: HashMap<String, String>,
+    /// chat_id (string) -> rendering of AI responses in this chat (/format); absent follows
+    /// the bot-wide `response_format`
+    pub response_formats: HashMap<String, ResponseFormat>,
+    /// chat_id (string) -> folder under the working directory that uploads go to (/uploaddir);
+    /// absent saves to the working directory itself
+    pub upload_subdirs: HashMap<String, String>,
+    /// chat_id (string) -> directory `/start` without a path binds to (/setdefault); absent
+    /// uses the directory the bot was launched with
+    pub start_dirs: HashMap<String, String>,
+    /// chat_id (string) -> chat receiving a live copy of its AI responses (/mirror)
+    pub mirror_chats: HashMap<String, i64>,
+    /// chat_id (string) -> true while the bot ignores the chat (/mute)
+    pub muted_chats: HashMap<String, bool>,
+    /// Stay silent when added to a group (/greeting off)
+    pub greeting_disabled: bool,
+    /// Custom intro posted when added to a group (/greeting set)
+    pub greeting_text: Option<String>,
+    /// Line appended to every AI response in all chats (/footer)
+    pub footer: Option<String>,
+    /// Parse mode used to render AI responses (/parsemode)
+    pub response_format: ResponseFormat,
+    /// Longest prompt accepted and whether longer ones are cut or refused (/promptlimit)
+    pub prompt_limit: PromptLimit,
 }
 
 /// Get allowed tools for a specific chat_id.
-/// Returns the chat-specific list if configured, otherwise DEFAULT_ALLOWED_TOOLS.
-pub(super) fn get_allowed_tools(settings: &BotSettings, chat_id: ChatId) -> Vec<String> {
+/// Returns the chat-specific list if configured, then the project config's list,
+/// otherwise DEFAULT_ALLOWED_TOOLS.
+pub(super) fn get_allowed_tools(data: &SharedData, chat_id: ChatId) -> Vec<String> {
     let key = chat_id.0.to_string();
-    settings
+    data.settings
         .allowed_tools
         .get(&key)
         .cloned()
+        .or_else(|| {
+            data.sessions
+                .get(&chat_id)
+                .and_then(|s| s.project_config.as_ref())
+                .and_then(|c| c.allowed_tools.clone())
+        })
         .unwrap_or_else(|| {
             DEFAULT_ALLOWED_TOOLS
                 .iter()
@@ -46,6 +270,40 @@ pub(super) fn get_allowed_tools(settings: &BotSettings, chat_id: ChatId) -> Vec<
         })
 }
 
+/// The bot's owner: the pre-registered owner (--owner / OPENCODEX_OWNER_ID) if any, otherwise
+/// the imprinted owner from the settings
+pub(super) fn owner_of(settings: &BotSettings) -> Option<u64> {
+    crate::auth::preconfigured_owner().or(settings.owner_user_id)
+}
+
+/// Whether `user_id` is the bot's owner (see owner_of)
+pub(super) fn is_owner(settings: &BotSettings, user_id: u64) -> bool {
+    owner_of(settings) == Some(user_id)
+}
+
+/// Whether `token` still belongs to the chat's current request. /start and /clear drop the
+/// token of the request they cancel, so by the time that task cleans up, a newer request may
+/// own the chat's cancel token, Stop button and "Stopping..." message; it must leave them alone.
+pub(super) fn is_current_request(
+    data: &SharedData,
+    chat_id: ChatId,
+    token: &Arc<CancelToken>,
+) -> bool {
+    data.cancel_tokens
+        .get(&chat_id)
+        .is_none_or(|current| Arc::ptr_eq(current, token))
+}
+
+/// Whether non-owner members may use the bot in this chat: the group's /public setting, or
+/// /defaultpublic for groups without one. Private chats (positive ids) are never public.
+pub(super) fn is_public_chat(settings: &BotSettings, chat_id: ChatId) -> bool {
+    settings
+        .as_public_for_group_chat
+        .get(&chat_id.0.to_string())
+        .copied()
+        .unwrap_or(settings.default_public && chat_id.0 < 0)
+}
+
 /// Shared state: per-chat sessions + bot settings
 pub(super) struct SharedData {
     pub sessions: HashMap<ChatId, ChatSession>,
@@ -54,13 +312,146 @@ pub(super) struct SharedData {
     pub cancel_tokens: HashMap<ChatId, Arc<CancelToken>>,
     /// Per-chat shell command PID for stopping in-progress `!` commands
     pub shell_pids: HashMap<ChatId, u32>,
+    /// Chats where /stop has signalled the running request or shell command and it has not
+    /// exited yet; repeated /stop presses are answered once and otherwise ignored
+    pub stopping: HashSet<ChatId>,
     /// Message ID of the "Stopping..." message sent by /stop, so the polling loop can update it
     pub stop_message_ids: HashMap<ChatId, teloxide::types::MessageId>,
+    /// Per-chat processing message carrying the Stop button of the running request
+    pub stop_buttons: HashMap<ChatId, teloxide::types::MessageId>,
     /// Per-chat timestamp of the last Telegram API call (for rate limiting)
     pub api_timestamps: HashMap<ChatId, tokio::time::Instant>,
+    /// Per-chat time the last message arrived from a user (/typingpause)
+    pub inbound_timestamps: HashMap<ChatId, tokio::time::Instant>,
+    /// Per-chat code blocks awaiting write confirmation (/extract)
+    pub pending_extractions: HashMap<ChatId, PendingExtraction>,
+    /// Counter used to tag extraction offers so stale buttons are rejected
+    pub next_extraction_id: u64,
+    /// Per-chat files written by the last turn, offered for download (/autodownload)
+    pub pending_downloads: HashMap<ChatId, PendingDownloads>,
+    /// Per-chat background tasks/agents reported by the running request (/agents)
+    pub agent_tasks: HashMap<ChatId, Vec<AgentTask>>,
+    /// Per-chat full prompt of the most recent AI request, as sent to the backend (/lastprompt)
+    pub last_prompts: HashMap<ChatId, String>,
+    /// Per-chat full output of the last tool call in the most recent AI request (/output)
+    pub last_tool_outputs: HashMap<ChatId, LastToolOutput>,
+    /// Parts of a long paste split by the Telegram client, keyed by (chat, user)
+    pub pending_pastes: HashMap<(ChatId, u64), PendingPaste>,
+    /// Per-chat tool names and call counts of the most recent AI request (/lasttools)
+    pub last_turn_tools: HashMap<ChatId, BTreeMap<String, usize>>,
+    /// Chats capturing the backend's raw JSONL output (/rawstream on); not persisted
+    pub raw_streams: HashMap<ChatId, Arc<RawStreamLog>>,
+    /// Album items collected before their batch download, keyed by (chat, media_group_id)
+    pub media_groups: HashMap<(ChatId, String), PendingMediaGroup>,
+    /// Recurring prompts registered via /cron (all chats, persisted in cron_jobs.json)
+    pub cron_jobs: Vec<CronJob>,
+    /// Rate limiting for owner failure DMs (/notify)
+    pub owner_notify: OwnerNotifyState,
+    /// Per-chat summaries awaiting confirmation before replacing the history (/summary)
+    pub pending_summaries: HashMap<ChatId, PendingSummary>,
+    /// Chats with an automatic history compaction in progress (/compact)
+    pub compacting: HashSet<ChatId>,
+    /// Per-chat destructive `!` command awaiting confirmation (/shellconfirm)
+    pub pending_shell_commands: HashMap<ChatId, PendingShellCommand>,
+    /// Per-chat code block of the last response awaiting confirmation before it runs (/run)
+    pub pending_runs: HashMap<ChatId, PendingRun>,
+    /// Member prompts awaiting the owner's approval, by their approval message (/approval)
+    pub pending_approvals: HashMap<(ChatId, teloxide::types::MessageId), PendingApproval>,
+    /// Per-chat uploaded settings backup awaiting confirmation (/restore)
+    pub pending_restores: HashMap<ChatId, PendingRestore>,
+    /// Per-chat uploaded session archive awaiting confirmation (/restoresessions)
+    pub pending_session_restores: HashMap<ChatId, PendingSessionRestore>,
+    /// Per-chat message carrying the /reloadsession Load/Save/Cancel buttons
+    pub pending_session_reloads: HashMap<ChatId, teloxide::types::MessageId>,
+    /// Per-chat message carrying the /clear Clear/Cancel buttons (/clearconfirm)
+    pub pending_clears: HashMap<ChatId, teloxide::types::MessageId>,
+    /// Per-chat message carrying the /madmax on confirmation buttons
+    pub pending_madmax: HashMap<ChatId, teloxide::types::MessageId>,
+    /// Per-chat /restart confirmation message (older buttons are rejected)
+    pub pending_restarts: HashMap<ChatId, teloxide::types::MessageId>,
+    /// (chat, user) -> expiry of a temporary High-risk grant (/elevate)
+    pub elevations: HashMap<(ChatId, u64), std::time::Instant>,
+    /// (chat, user) -> when the user's last AI request there started, for /cooldown
+    pub user_last_requests: HashMap<(ChatId, u64), std::time::Instant>,
+    /// (chat, command) -> when the command last ran there, for /cmdcooldown
+    pub command_last_used: HashMap<(ChatId, String), std::time::Instant>,
+    /// Per-chat IDs of recently handled messages, so redelivered updates are skipped
+    pub recent_messages: HashMap<ChatId, RecentMessages>,
+    /// Per-chat consecutive backend failures; refuses the chat's prompts for a while once
+    /// tripped, so one failing project does not pause the others
+    pub backend_breakers: HashMap<ChatId, BackendBreaker>,
+    /// When the bot process started serving, for /uptime
+    pub started_at: std::time::Instant,
+}
+
+/// Number of message IDs remembered per chat for duplicate detection
+const RECENT_MESSAGES_PER_CHAT: usize = 64;
+
+/// Bounded record of recently handled message IDs (oldest evicted first)
+#[derive(Default)]
+pub(super) struct RecentMessages {
+    ids: VecDeque<i32>,
+}
+
+impl RecentMessages {
+    /// Record `id`; returns false if it was already handled
+    pub fn first_seen(&mut self, id: teloxide::types::MessageId) -> bool {
+        if self.ids.contains(&id.0) {
+            return false;
+        }
+        if self.ids.len() >= RECENT_MESSAGES_PER_CHAT {
+            self.ids.pop_front();
+        }
+        self.ids.push_back(id.0);
+        true
+    }
 }
 
 pub(super) type SharedState = Arc<Mutex<SharedData>>;
 
+/// Maximum number of AI responses tracked per chat for reaction feedback
+pub(super) const MAX_TRACKED_RESPONSES: usize = 50;
+
+/// Bytes of a tool result kept for /output
+pub(super) const MAX_KEPT_TOOL_OUTPUT: usize = 1024 * 1024;
+
 /// Telegram message length limit
 pub(super) const TELEGRAM_MSG_LIMIT: usize = 4096;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use teloxide::types::MessageId;
+
+    #[test]
+    fn test_recent_messages_dedup_and_eviction() {
+        let mut recent = RecentMessages::default();
+        assert!(recent.first_seen(MessageId(1)));
+        assert!(!recent.first_seen(MessageId(1)));
+        for id in 2..=RECENT_MESSAGES_PER_CHAT as i32 + 1 {
+            assert!(recent.first_seen(MessageId(id)));
+        }
+        assert_eq!(recent.ids.len(), RECENT_MESSAGES_PER_CHAT);
+        // 1 was evicted, so it counts as new again
+        assert!(recent.first_seen(MessageId(1)));
+    }
+
+    #[test]
+    fn test_is_public_chat() {
+        let mut settings = BotSettings::default();
+        settings
+            .as_public_for_group_chat
+            .insert("-100".to_string(), true);
+        settings
+            .as_public_for_group_chat
+            .insert("-200".to_string(), false);
+        assert!(is_public_chat(&settings, ChatId(-100)));
+        assert!(!is_public_chat(&settings, ChatId(-300)));
+
+        settings.default_public = true;
+        assert!(is_public_chat(&settings, ChatId(-300)));
+        // An explicit /public off still wins, and private chats are never public
+        assert!(!is_public_chat(&settings, ChatId(-200)));
+        assert!(!is_public_chat(&settings, ChatId(42)));
+    }
+}
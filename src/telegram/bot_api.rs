@@ -0,0 +1,123 @@
+use std::sync::OnceLock;
+
+use teloxide::prelude::*;
+
+use crate::auth;
+
+/// Largest file the public Bot API lets bots download
+const CLOUD_DOWNLOAD_LIMIT: u64 = 20 * 1024 * 1024;
+
+/// Largest file a self-hosted Bot API server (`--local`) transfers
+const LOCAL_FILE_LIMIT: u64 = 2000 * 1024 * 1024;
+
+/// Self-hosted Bot API server set by `--api-url`; None uses api.telegram.org
+static API_URL: OnceLock<Option<reqwest::Url>> = OnceLock::new();
+
+pub fn configure_api_url(url: Option<reqwest::Url>) {
+    let _ = API_URL.set(url);
+}
+
+fn custom_api_url() -> Option<&'static reqwest::Url> {
+    API_URL.get().and_then(Option::as_ref)
+}
+
+/// Parse and check a Bot API server base URL. Method paths are joined onto the root,
+/// so a path prefix would be silently dropped and is rejected instead.
+pub fn parse_api_url(url: &str) -> Result<reqwest::Url, String> {
+    let parsed = reqwest::Url::parse(url).map_err(|e| format!("invalid URL {url}: {e}"))?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err(format!("must use http or https (got {})", parsed.scheme()));
+    }
+    if parsed.host_str().is_none() {
+        return Err(format!("missing host: {url}"));
+    }
+    if parsed.path() != "/" || parsed.query().is_some() || parsed.fragment().is_some() {
+        return Err(format!(
+            "must be the server root without a path or query (e.g. http://localhost:8081), got {url}"
+        ));
+    }
+    Ok(parsed)
+}
+
+/// Bot client for `token`, talking to the configured Bot API server
+pub fn new_bot(token: &str) -> Bot {
+    let bot = Bot::new(token);
+    match custom_api_url() {
+        Some(url) => bot.set_api_url(url.clone()),
+        None => bot,
+    }
+}
+
+/// The bot's own user id: the number before the colon in its token. Known without asking
+/// Telegram, so hot paths need no `get_me` round trip.
+pub(super) fn bot_user_id(bot: &Bot) -> Option<UserId> {
+    let (id, _) = bot.token().split_once(':')?;
+    id.parse().ok().map(UserId)
+}
+
+/// Extra `--sendfile` arguments so the backend's file sends use the same server
+pub(super) fn sendfile_api_arg() -> String {
+    custom_api_url()
+        .map(|url| format!(" --api-url {url}"))
+        .unwrap_or_default()
+}
+
+/// True if the bot talks to a self-hosted Bot API server (`--api-url`)
+pub(super) fn is_self_hosted() -> bool {
+    custom_api_url().is_some()
+}
+
+/// (download, upload) size limits for the public API or a self-hosted server
+fn file_limits(self_hosted: bool) -> (u64, u64) {
+    if self_hosted {
+        (LOCAL_FILE_LIMIT, LOCAL_FILE_LIMIT)
+    } else {
+        (CLOUD_DOWNLOAD_LIMIT, auth::DEFAULT_UPLOAD_LIMIT)
+    }
+}
+
+/// Largest file /downid can fetch by file_id
+pub(super) fn download_limit() -> u64 {
+    file_limits(is_self_hosted()).0
+}
+
+/// Largest upload saved to the working directory
+pub(super) fn upload_limit() -> u64 {
+    file_limits(is_self_hosted()).1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_api_url() {
+        assert_eq!(
+            parse_api_url("http://localhost:8081").map(|u| u.to_string()),
+            Ok("http://localhost:8081/".to_string())
+        );
+        assert!(parse_api_url("https://bots.example.com/").is_ok());
+        assert!(parse_api_url("ftp://localhost").is_err());
+        assert!(parse_api_url("localhost:8081").is_err());
+        assert!(parse_api_url("http://localhost:8081/api").is_err());
+        assert!(parse_api_url("not a url").is_err());
+    }
+
+    #[test]
+    fn test_bot_user_id() {
+        assert_eq!(
+            bot_user_id(&Bot::new("123456:ABC-DEF")),
+            Some(UserId(123456))
+        );
+        assert_eq!(bot_user_id(&Bot::new("not-a-token")), None);
+    }
+
+    #[test]
+    fn test_file_limits() {
+        assert_eq!(
+            file_limits(false),
+            (20 * 1024 * 1024, auth::DEFAULT_UPLOAD_LIMIT)
+        );
+        assert_eq!(file_limits(true), (LOCAL_FILE_LIMIT, LOCAL_FILE_LIMIT));
+    }
+}
@@ -0,0 +1,155 @@
+use std::time::{Duration, Instant};
+
+use teloxide::prelude::*;
+
+use crate::codex;
+
+use super::bot::SharedState;
+use super::streaming::shared_rate_limit_wait;
+
+/// Consecutive backend failures that open the breaker
+const FAILURE_THRESHOLD: u32 = 3;
+
+/// How long prompts are refused once the breaker opens
+const COOLDOWN: Duration = Duration::from_secs(5 * 60);
+
+/// Stops spawning the backend for a chat after repeated failures there (e.g. an expired API key
+/// or a broken project). After the cooldown one request is let through; if it fails too, the breaker reopens.
+#[derive(Debug, Default)]
+pub(super) struct BackendBreaker {
+    consecutive_failures: u32,
+    open_until: Option<Instant>,
+}
+
+impl BackendBreaker {
+    /// Record a failed request; returns true if this failure opened the breaker
+    pub fn record_failure(&mut self, now: Instant) -> bool {
+        self.consecutive_failures += 1;
+        if self.consecutive_failures >= FAILURE_THRESHOLD && self.blocked_for(now).is_none() {
+            self.open_until = Some(now + COOLDOWN);
+            return true;
+        }
+        false
+    }
+
+    /// Remaining cooldown while the breaker is open
+    pub fn blocked_for(&self, now: Instant) -> Option<Duration> {
+        self.open_until
+            .filter(|until| *until > now)
+            .map(|until| until - now)
+    }
+
+    /// One-line state for /status
+    pub fn describe(&self, now: Instant) -> String {
+        match self.blocked_for(now) {
+            Some(left) => format!(
+                "open ({} consecutive failures, retry in {}s)",
+                self.consecutive_failures,
+                left.as_secs()
+            ),
+            None if self.consecutive_failures > 0 => {
+                format!("closed ({} recent failures)", self.consecutive_failures)
+            }
+            None => "closed".to_string(),
+        }
+    }
+}
+
+/// Reply instead of running the prompt while the chat's breaker is open.
+/// Returns true if the prompt was refused.
+pub(super) async fn refuse_if_backend_failing(
+    bot: &Bot,
+    chat_id: ChatId,
+    state: &SharedState,
+) -> ResponseResult<bool> {
+    let blocked = {
+        let data = state.lock().await;
+        data.backend_breakers
+            .get(&chat_id)
+            .and_then(|breaker| breaker.blocked_for(Instant::now()))
+    };
+    let Some(left) = blocked else {
+        return Ok(false);
+    };
+    shared_rate_limit_wait(state, chat_id).await;
+    bot.send_message(
+        chat_id,
+        format!(
+            "⚠ The backend is currently failing ({FAILURE_THRESHOLD}+ errors in a row), so this \
+             prompt was not sent. Try again in {} min, or run /status to check the backend.",
+            left.as_secs().div_ceil(60)
+        ),
+    )
+    .await?;
+    Ok(true)
+}
+
+/// Record how a chat's request ended; logs when the chat's breaker opens
+pub(super) async fn record_backend_result(state: &SharedState, chat_id: ChatId, failed: bool) {
+    let mut data = state.lock().await;
+    if !failed {
+        // A closed breaker with no failures is the default; keep no entry for it
+        data.backend_breakers.remove(&chat_id);
+    } else if data
+        .backend_breakers
+        .entry(chat_id)
+        .or_default()
+        .record_failure(Instant::now())
+    {
+        let ts = chrono::Local::now().format("%H:%M:%S");
+        logln!(
+            "  [{ts}] ⚠ Backend failed {FAILURE_THRESHOLD} times in a row in chat {}; pausing its prompts for {} min",
+            chat_id.0,
+            COOLDOWN.as_secs() / 60
+        );
+    }
+}
+
+/// /status backend check: while the chat's breaker is open, probe the backend binary and close
+/// the breaker if it responds. Returns the breaker state for display.
+pub(super) async fn check_backend_breaker(state: &SharedState, chat_id: ChatId) -> String {
+    let open = {
+        let data = state.lock().await;
+        data.backend_breakers
+            .get(&chat_id)
+            .is_some_and(|breaker| breaker.blocked_for(Instant::now()).is_some())
+    };
+    if open
+        && tokio::task::spawn_blocking(codex::probe_ai_binary)
+            .await
+            .unwrap_or(false)
+    {
+        let mut data = state.lock().await;
+        data.backend_breakers.remove(&chat_id);
+        return "closed (reset after a successful backend check)".to_string();
+    }
+    let data = state.lock().await;
+    data.backend_breakers.get(&chat_id).map_or_else(
+        || "closed".to_string(),
+        |breaker| breaker.describe(Instant::now()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backend_breaker_opens_and_recovers() {
+        let mut breaker = BackendBreaker::default();
+        let start = Instant::now();
+        for _ in 1..FAILURE_THRESHOLD {
+            assert!(!breaker.record_failure(start));
+        }
+        assert!(breaker.blocked_for(start).is_none());
+        assert!(breaker.record_failure(start));
+        assert!(breaker.blocked_for(start).is_some());
+
+        // After the cooldown one more failure reopens it immediately
+        let later = start + COOLDOWN + Duration::from_secs(1);
+        assert!(breaker.blocked_for(later).is_none());
+        assert!(breaker.record_failure(later));
+
+        assert_eq!(BackendBreaker::default().describe(later), "closed");
+    }
+}
@@ -0,0 +1,243 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+use teloxide::prelude::*;
+use teloxide::types::ParseMode;
+
+use crate::i18n;
+
+use super::bot::SharedState;
+use super::diskusage::format_bytes;
+use super::file_ops::{looks_binary, resolve_chat_path};
+use super::streaming::{html_escape, send_long_message, shared_rate_limit_wait};
+
+/// Lines shown when no count is given
+const DEFAULT_LINES: usize = 50;
+
+/// Upper bound for the requested line count
+const MAX_LINES: usize = 1000;
+
+/// At most this many bytes of file content are read and sent, whatever the line count
+const MAX_CAT_BYTES: usize = 64 * 1024;
+
+/// Block size used when scanning backwards for `tail`
+const TAIL_CHUNK: u64 = 8 * 1024;
+
+/// Parsed `/cat <file> [head|tail] [lines]`
+#[derive(Debug, PartialEq, Eq)]
+struct CatRequest<'a> {
+    path: &'a str,
+    tail: bool,
+    lines: usize,
+}
+
+/// Options are taken from the end so paths may contain spaces
+fn parse_cat_args(args: &str) -> Option<CatRequest<'_>> {
+    let mut rest = args.trim();
+    let mut lines = DEFAULT_LINES;
+    let mut tail = false;
+    if let Some((head, last)) = rest.rsplit_once(char::is_whitespace) {
+        if let Ok(n) = last.parse::<usize>() {
+            lines = n.clamp(1, MAX_LINES);
+            rest = head.trim_end();
+        }
+    }
+    if let Some((head, last)) = rest.rsplit_once(char::is_whitespace) {
+        if last == "tail" || last == "head" {
+            tail = last == "tail";
+            rest = head.trim_end();
+        }
+    }
+    (!rest.is_empty()).then_some(CatRequest {
+        path: rest,
+        tail,
+        lines,
+    })
+}
+
+/// Read up to `lines` lines from the start, stopping early (never reads past `byte_cap`).
+/// Returns the text and whether the file continues.
+fn read_head(
+    mut reader: impl BufRead,
+    lines: usize,
+    byte_cap: usize,
+) -> io::Result<(Vec<u8>, bool)> {
+    let mut out = Vec::new();
+    for _ in 0..lines {
+        let remaining = byte_cap.saturating_sub(out.len());
+        if remaining == 0 {
+            break;
+        }
+        // `take` keeps a single enormous line from being read whole
+        if (&mut reader)
+            .take(remaining as u64)
+            .read_until(b'\n', &mut out)?
+            == 0
+        {
+            return Ok((out, false));
+        }
+    }
+    let more = !reader.fill_buf()?.is_empty();
+    Ok((out, more))
+}
+
+/// Read the last `lines` lines by scanning backwards from the end in `TAIL_CHUNK` blocks
+/// (at most `byte_cap` bytes are kept). Returns the text and whether earlier content exists.
+fn read_tail<R: Read + Seek>(
+    reader: &mut R,
+    lines: usize,
+    byte_cap: usize,
+) -> io::Result<(Vec<u8>, bool)> {
+    let mut pos = reader.seek(SeekFrom::End(0))?;
+    let mut buf: Vec<u8> = Vec::new();
+    while pos > 0 && buf.len() < byte_cap && buf.iter().filter(|&&b| b == b'\n').count() <= lines {
+        let step = TAIL_CHUNK.min(pos);
+        pos -= step;
+        reader.seek(SeekFrom::Start(pos))?;
+        let mut chunk = vec![0; step as usize];
+        reader.read_exact(&mut chunk)?;
+        chunk.extend_from_slice(&buf);
+        buf = chunk;
+    }
+
+    let body = buf.strip_suffix(b"\n").unwrap_or(&buf);
+    let mut start = body
+        .iter()
+        .enumerate()
+        .rev()
+        .filter(|(_, &b)| b == b'\n')
+        .nth(lines - 1)
+        .map_or(0, |(i, _)| i + 1);
+    start = start.max(buf.len().saturating_sub(byte_cap));
+    let more = pos > 0 || start > 0;
+    Ok((buf[start..].to_vec(), more))
+}
+
+/// Handle /cat command - show the first (or last) lines of a text file without loading it whole
+/// Usage: /cat <file> [tail] [lines]
+pub(super) async fn handle_cat_command(
+    bot: &Bot,
+    chat_id: ChatId,
+    text: &str,
+    state: &SharedState,
+) -> ResponseResult<()> {
+    let args = text.strip_prefix("/cat").unwrap_or("");
+    let Some(request) = parse_cat_args(args) else {
+        shared_rate_limit_wait(state, chat_id).await;
+        bot.send_message(
+            chat_id,
+            format!(
+                "Usage: /cat <file> [tail] [lines]\n\
+                 Shows the first (or last) {DEFAULT_LINES} lines by default."
+            ),
+        )
+        .await?;
+        return Ok(());
+    };
+    let Some(resolved_path) = resolve_chat_path(state, chat_id, request.path).await else {
+        shared_rate_limit_wait(state, chat_id).await;
+        bot.send_message(chat_id, i18n::MSG_NO_SESSION).await?;
+        return Ok(());
+    };
+
+    let (tail, lines) = (request.tail, request.lines);
+    let path = resolved_path.clone();
+    let result = tokio::task::spawn_blocking(move || -> Result<_, String> {
+        let path = Path::new(&path);
+        if !path.is_file() {
+            return Err(if path.exists() {
+                "Not a file".to_string()
+            } else {
+                "File not found".to_string()
+            });
+        }
+        let mut file = File::open(path).map_err(|e| e.to_string())?;
+        let size = file.metadata().map_err(|e| e.to_string())?.len();
+        let (content, more) = if tail {
+            read_tail(&mut file, lines, MAX_CAT_BYTES)
+        } else {
+            read_head(BufReader::new(file), lines, MAX_CAT_BYTES)
+        }
+        .map_err(|e| e.to_string())?;
+        Ok((content, more, size))
+    })
+    .await
+    .unwrap_or_else(|e| Err(e.to_string()));
+
+    let response_msg = match result {
+        Ok((content, _, _)) if looks_binary(&content) => format!(
+            "Binary file: {} (use /down to download it)",
+            html_escape(&resolved_path)
+        ),
+        Ok((content, more, size)) => {
+            let text = String::from_utf8_lossy(&content);
+            let shown = text.lines().count();
+            let range = match (tail, more) {
+                (_, false) => "whole file".to_string(),
+                (false, true) => format!("first {shown} lines"),
+                (true, true) => format!("last {shown} lines"),
+            };
+            format!(
+                "<b>{}</b> ({}, {range})\n<pre>{}</pre>",
+                html_escape(&resolved_path),
+                format_bytes(size),
+                html_escape(text.trim_end())
+            )
+        }
+        Err(e) => format!("{}: {}", html_escape(&e), html_escape(&resolved_path)),
+    };
+
+    send_long_message(bot, chat_id, &response_msg, Some(ParseMode::Html), state).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+#[allow(clippy::expect_used)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_cat_head_tail_and_args() {
+        let text: String = (1..=2000).map(|i| format!("line {i}\n")).collect();
+
+        let (head, more) = read_head(Cursor::new(text.as_bytes()), 3, MAX_CAT_BYTES).expect("head");
+        assert_eq!(head, b"line 1\nline 2\nline 3\n");
+        assert!(more);
+        let (all, more) =
+            read_head(Cursor::new(b"a\nb".as_slice()), 10, MAX_CAT_BYTES).expect("head");
+        assert_eq!(all, b"a\nb");
+        assert!(!more);
+        let (capped, more) = read_head(Cursor::new(text.as_bytes()), 10, 10).expect("head");
+        assert_eq!(capped.len(), 10);
+        assert!(more);
+
+        let (tail, more) =
+            read_tail(&mut Cursor::new(text.as_bytes()), 2, MAX_CAT_BYTES).expect("tail");
+        assert_eq!(tail, b"line 1999\nline 2000\n");
+        assert!(more);
+        let (tail, more) =
+            read_tail(&mut Cursor::new(b"x\ny".as_slice()), 5, MAX_CAT_BYTES).expect("tail");
+        assert_eq!(tail, b"x\ny");
+        assert!(!more);
+
+        assert_eq!(
+            parse_cat_args(" logs/my app.log tail 20"),
+            Some(CatRequest {
+                path: "logs/my app.log",
+                tail: true,
+                lines: 20
+            })
+        );
+        assert_eq!(
+            parse_cat_args("a.txt"),
+            Some(CatRequest {
+                path: "a.txt",
+                tail: false,
+                lines: DEFAULT_LINES
+            })
+        );
+        assert_eq!(parse_cat_args("  "), None);
+    }
+}
@@ -0,0 +1,95 @@
+use std::collections::BTreeMap;
+
+use teloxide::prelude::*;
+
+use super::bot::{is_public_chat, SharedData, SharedState};
+use super::paginate::{send_paged_list, ListContent, PagedList};
+use super::streaming::html_escape;
+
+/// What the bot knows about one chat, for /chats
+#[derive(Debug, Default, PartialEq)]
+struct ChatEntry {
+    path: Option<String>,
+    public: bool,
+    active: bool,
+}
+
+/// Every chat with a saved session path or in-memory session, by chat id
+fn collect_chats(data: &SharedData) -> BTreeMap<i64, ChatEntry> {
+    let mut chats: BTreeMap<i64, ChatEntry> = BTreeMap::new();
+    for (chat, path) in &data.settings.last_sessions {
+        if let Ok(id) = chat.parse::<i64>() {
+            chats.entry(id).or_default().path = Some(path.clone());
+        }
+    }
+    for (chat_id, session) in &data.sessions {
+        let entry = chats.entry(chat_id.0).or_default();
+        if let Some(path) = &session.current_path {
+            entry.path = Some(path.clone());
+        }
+    }
+    for (id, entry) in chats.iter_mut() {
+        let chat_id = ChatId(*id);
+        entry.public = is_public_chat(&data.settings, ChatId(*id));
+        entry.active =
+            data.cancel_tokens.contains_key(&chat_id) || data.shell_pids.contains_key(&chat_id);
+    }
+    chats
+}
+
+fn chat_line(id: i64, entry: &ChatEntry) -> String {
+    let mut line = format!("<code>{id}</code>");
+    if entry.active {
+        line.push_str(" ● running");
+    }
+    if entry.public {
+        line.push_str(" · public");
+    }
+    match &entry.path {
+        Some(path) => line.push_str(&format!("\n  {}", html_escape(path))),
+        None => line.push_str("\n  (no session)"),
+    }
+    line
+}
+
+/// Content of the /chats list
+pub(super) fn chats_content(data: &SharedData) -> ListContent {
+    let chats = collect_chats(data);
+    let active = chats.values().filter(|c| c.active).count();
+    ListContent {
+        title: "Chats".to_string(),
+        items: chats.iter().map(|(id, c)| chat_line(*id, c)).collect(),
+        footer: format!("{} chat(s), {active} running", chats.len()),
+    }
+}
+
+/// Handle /chats command - list every chat the bot has state for (owner-only)
+pub(super) async fn handle_chats_command(
+    bot: &Bot,
+    chat_id: ChatId,
+    state: &SharedState,
+) -> ResponseResult<()> {
+    send_paged_list(bot, chat_id, PagedList::Chats, state).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chat_line() {
+        let entry = ChatEntry {
+            path: Some("/srv/<app>".to_string()),
+            public: true,
+            active: true,
+        };
+        assert_eq!(
+            chat_line(-100, &entry),
+            "<code>-100</code> ● running · public\n  /srv/&lt;app&gt;"
+        );
+        assert_eq!(
+            chat_line(42, &ChatEntry::default()),
+            "<code>42</code>\n  (no session)"
+        );
+    }
+}
@@ -0,0 +1,209 @@
+use teloxide::prelude::*;
+use teloxide::types::ParseMode;
+
+use super::bot::{BotSettings, SharedState};
+use super::commands::bot_commands;
+use super::storage::save_bot_settings;
+use super::streaming::{html_escape, send_long_message};
+
+/// Maximum number of command aliases per chat
+const MAX_COMMAND_ALIASES: usize = 30;
+
+/// Maximum length of an alias name (in characters; non-ASCII names such as /경로 are allowed)
+const MAX_NAME_LEN: usize = 32;
+
+/// Maximum length (bytes) of an alias target including its fixed arguments
+const MAX_TARGET_LEN: usize = 500;
+
+/// Why a command alias name cannot be used, if it cannot
+fn command_alias_name_error(name: &str) -> Option<String> {
+    if name.is_empty()
+        || name.chars().count() > MAX_NAME_LEN
+        || name
+            .chars()
+            .any(|c| c.is_whitespace() || c == '/' || c == '@')
+    {
+        return Some(format!(
+            "Alias names are a single word without / or @ (up to {MAX_NAME_LEN} characters)."
+        ));
+    }
+    // Aliases are expanded before routing, and /stop is routed by prefix
+    if name.starts_with("stop") {
+        return Some("Aliases cannot shadow /stop.".to_string());
+    }
+    if name == "cmdalias" {
+        return Some("/cmdalias itself cannot be aliased.".to_string());
+    }
+    None
+}
+
+/// Why a target cannot be used, if it cannot: it must be a built-in command
+fn command_alias_target_error(target: &str) -> Option<String> {
+    let command = target
+        .strip_prefix('/')
+        .and_then(|t| t.split_whitespace().next())
+        .unwrap_or("");
+    if target.len() > MAX_TARGET_LEN {
+        return Some(format!("Target too long (max {MAX_TARGET_LEN} bytes)."));
+    }
+    if !bot_commands().iter().any(|c| c.command == command) {
+        return Some(format!(
+            "<code>{}</code> is not a built-in command.",
+            html_escape(target)
+        ));
+    }
+    None
+}
+
+/// Rewrite `/<name> [args]` to the aliased built-in command if this chat defines `name`.
+/// The target's fixed arguments come first, followed by the message's arguments.
+pub(super) fn resolve_command_alias(
+    settings: &BotSettings,
+    chat_id: ChatId,
+    text: &str,
+) -> Option<String> {
+    let rest = text.strip_prefix('/')?;
+    let (name, args) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+    let target = settings
+        .command_aliases
+        .get(&chat_id.0.to_string())?
+        .get(&name.to_lowercase())?;
+    let args = args.trim();
+    Some(if args.is_empty() {
+        target.clone()
+    } else {
+        format!("{target} {args}")
+    })
+}
+
+/// Handle /cmdalias command - map extra names to built-in commands in this chat
+/// Usage: /cmdalias add <name> </command> [fixed args]
+///        /cmdalias remove <name>
+///        /cmdalias list               (also with no argument)
+pub(super) async fn handle_cmdalias_command(
+    bot: &Bot,
+    chat_id: ChatId,
+    text: &str,
+    state: &SharedState,
+    token: &str,
+) -> ResponseResult<()> {
+    let arg = text.strip_prefix("/cmdalias").unwrap_or("").trim();
+    let (sub, rest) = arg.split_once(char::is_whitespace).unwrap_or((arg, ""));
+    let (name, target) = rest
+        .trim()
+        .split_once(char::is_whitespace)
+        .unwrap_or((rest.trim(), ""));
+    let name = name.trim_start_matches('/').to_lowercase();
+    let target = target.trim();
+
+    let response_msg = {
+        let mut data = state.lock().await;
+        let chat_key = chat_id.0.to_string();
+        let aliases = data
+            .settings
+            .command_aliases
+            .entry(chat_key.clone())
+            .or_default();
+        let response = match sub {
+            "add" if name.is_empty() || target.is_empty() => {
+                "Usage: <code>/cmdalias add &lt;name&gt; /&lt;command&gt; [args]</code>".to_string()
+            }
+            "add" => {
+                if let Some(error) =
+                    command_alias_name_error(&name).or_else(|| command_alias_target_error(target))
+                {
+                    error
+                } else if !aliases.contains_key(&name) && aliases.len() >= MAX_COMMAND_ALIASES {
+                    format!("Too many command aliases (max {MAX_COMMAND_ALIASES}). Remove one first.")
+                } else {
+                    aliases.insert(name.clone(), target.to_string());
+                    format!(
+                        "<code>/{}</code> now runs <code>{}</code> in this chat.",
+                        html_escape(&name),
+                        html_escape(target)
+                    )
+                }
+            }
+            "remove" | "rm" => {
+                if aliases.remove(&name).is_some() {
+                    format!("Command alias <code>/{}</code> removed.", html_escape(&name))
+                } else {
+                    format!("Unknown command alias: <code>{}</code>", html_escape(&name))
+                }
+            }
+            "" | "list" if aliases.is_empty() => "No command aliases in this chat.\n\n\
+                 <code>/cmdalias add &lt;name&gt; /&lt;command&gt; [args]</code> — Add (e.g. <code>/cmdalias add 경로 /pwd</code>)\n\
+                 <code>/cmdalias remove &lt;name&gt;</code> — Remove"
+                .to_string(),
+            "" | "list" => {
+                let mut entries: Vec<_> = aliases.iter().collect();
+                entries.sort();
+                let lines: Vec<String> = entries
+                    .into_iter()
+                    .map(|(name, target)| {
+                        format!(
+                            "<code>/{}</code> → <code>{}</code>",
+                            html_escape(name),
+                            html_escape(target)
+                        )
+                    })
+                    .collect();
+                format!("<b>Command aliases</b>\n{}", lines.join("\n"))
+            }
+            _ => "Usage: <code>/cmdalias add|remove|list</code>".to_string(),
+        };
+        if data
+            .settings
+            .command_aliases
+            .get(&chat_key)
+            .is_some_and(|m| m.is_empty())
+        {
+            data.settings.command_aliases.remove(&chat_key);
+        }
+        if matches!(sub, "add" | "remove" | "rm") {
+            save_bot_settings(token, &data.settings);
+        }
+        response
+    };
+
+    send_long_message(bot, chat_id, &response_msg, Some(ParseMode::Html), state).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_command_alias_resolve_and_validation() {
+        let mut settings = BotSettings::default();
+        let chat = ChatId(7);
+        settings.command_aliases.insert(
+            "7".to_string(),
+            [
+                ("경로".to_string(), "/pwd".to_string()),
+                ("log".to_string(), "/cat app.log tail".to_string()),
+            ]
+            .into_iter()
+            .collect(),
+        );
+        assert_eq!(
+            resolve_command_alias(&settings, chat, "/경로").as_deref(),
+            Some("/pwd")
+        );
+        assert_eq!(
+            resolve_command_alias(&settings, chat, "/log 20").as_deref(),
+            Some("/cat app.log tail 20")
+        );
+        assert_eq!(resolve_command_alias(&settings, ChatId(8), "/log"), None);
+        assert_eq!(resolve_command_alias(&settings, chat, "/pwd"), None);
+
+        assert!(command_alias_name_error("경로").is_none());
+        assert!(command_alias_name_error("stop").is_some());
+        assert!(command_alias_name_error("stopall").is_some());
+        assert!(command_alias_name_error("a b").is_some());
+        assert!(command_alias_target_error("/cat x.log").is_none());
+        assert!(command_alias_target_error("/nonexistent").is_some());
+        assert!(command_alias_target_error("pwd").is_some());
+    }
+}
@@ -1,54 +1,281 @@
 use std::collections::HashMap;
 use std::path::Path;
-use std::process::Command;
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
+use std::time::Instant;
 
 use teloxide::prelude::*;
-use teloxide::types::ParseMode;
+use teloxide::types::{
+    AllowedUpdate, CallbackQuery, ChatMemberUpdated, InlineKeyboardButton, InlineKeyboardMarkup,
+    InlineQuery, MessageReactionUpdated, ParseMode,
+};
+use teloxide::update_listeners::{webhooks, Polling};
 
 use crate::auth;
-use crate::codex;
+use crate::codex::{self, CancelToken};
 use crate::i18n;
 use crate::session::HistoryType;
 
-use super::bot::{ChatSession, SharedData, SharedState};
-use super::file_ops::{handle_down_command, handle_file_upload, handle_shell_command};
-use super::message::handle_text_message;
-use super::storage::{load_bot_settings, load_existing_session, save_bot_settings};
-use super::streaming::{send_long_message, shared_rate_limit_wait, truncate_str};
+use super::agents::handle_agents_command;
+use super::alias::{handle_alias_command, resolve_alias};
+use super::approval::{handle_approval_callback, hold_for_approval, APPROVAL_CALLBACK_PREFIX};
+use super::autodownload::{handle_autodownload_callback, AUTODOWNLOAD_CALLBACK_PREFIX};
+use super::backup::{
+    handle_backup_command, handle_restore_callback, handle_restore_command, handle_restore_upload,
+    RESTORE_CALLBACK_PREFIX,
+};
+use super::bot::{is_owner, is_public_chat, owner_of, SharedData, SharedState, TELEGRAM_MSG_LIMIT};
+use super::bot_api::new_bot;
+use super::breaker::check_backend_breaker;
+use super::cat::handle_cat_command;
+use super::chats::handle_chats_command;
+use super::command_alias::{handle_cmdalias_command, resolve_command_alias};
+use super::console_log::handle_log_command;
+use super::context::handle_context_command;
+use super::continuation::{
+    handle_continue_callback, handle_continue_command, CONTINUE_CALLBACK_PREFIX,
+};
+use super::cooldown::{
+    handle_cmdcooldown_command, handle_cooldown_command, refuse_if_command_cooling_down,
+    refuse_if_cooling_down,
+};
+use super::cron::{handle_cron_command, spawn_cron_scheduler};
+use super::diskusage::handle_diskusage_command;
+use super::effective_config::handle_config_command;
+use super::elevate::{handle_elevate_command, is_elevated};
+use super::errors::{handle_errors_command, is_error};
+use super::extract::{handle_extract_callback, EXTRACT_CALLBACK_PREFIX};
+use super::feedback::handle_message_reaction;
+use super::file_ops::{
+    display_path, expand_home, handle_down_command, handle_downid_command, handle_file_upload,
+    handle_shell_command,
+};
+use super::footer::handle_footer_command;
+use super::forward::{forwarded_prompt, queue_forwarded, with_reply_context};
+use super::greeting::{handle_bot_added, handle_greeting_command};
+use super::inline::handle_inline_query;
+use super::madmax::{handle_madmax_callback, handle_madmax_command, MADMAX_CALLBACK_PREFIX};
+use super::media_group::queue_media_group_item;
+use super::membership::handle_my_chat_member;
+use super::message::{handle_text_message, STOP_CALLBACK_PREFIX};
+use super::mirror::{handle_mirror_command, is_mirror_target};
+use super::mute::{handle_mute_command, is_muted, passes_mute};
+use super::notify::handle_notify_command;
+use super::paginate::{handle_page_callback, PAGE_CALLBACK_PREFIX};
+use super::paste::{continue_paste, start_paste_if_split};
+use super::pending::handle_pending_command;
+use super::project_config::{load_project_config_with_note, PROJECT_CONFIG_FILE};
+use super::rawstream::handle_rawstream_command;
+use super::reaction_commands::{handle_reaction_command, track_prompt};
+use super::recheck::handle_recheck_command;
+use super::restart::{handle_restart_callback, handle_restart_command, RESTART_CALLBACK_PREFIX};
+use super::run::{handle_run_callback, handle_run_command, RUN_CALLBACK_PREFIX};
+use super::search::handle_search_command;
+use super::selftest::handle_selftest_command;
+use super::session_backup::{
+    handle_backupsessions_command, handle_restoresessions_command, handle_restoresessions_upload,
+    handle_session_restore_callback, SESSION_RESTORE_CALLBACK_PREFIX,
+};
+use super::session_reload::{
+    handle_reloadsession_command, handle_session_reload_callback, SESSION_RELOAD_CALLBACK_PREFIX,
+};
+use super::settings::{
+    chat_start_dir, handle_backendargs_command, handle_format_command, handle_maxresponse_command,
+    handle_parsemode_command, handle_prompt_affix_command, handle_promptlimit_command,
+    handle_reload_command, handle_replystyle_command, handle_respondlang_command,
+    handle_setdefault_command, handle_startpreview_command, handle_toggle_command,
+    handle_tooloutput_command, handle_uploaddir_command, ChatToggle, PromptAffix,
+};
+use super::shell_guard::{handle_shell_callback, SHELL_CALLBACK_PREFIX};
+use super::storage::{
+    load_bot_settings, load_cron_jobs, load_existing_session, save_bot_settings,
+    save_session_to_file,
+};
+use super::streaming::{html_escape, send_long_message, shared_rate_limit_wait, truncate_str};
+use super::summary::{handle_summary_callback, handle_summary_command, SUMMARY_CALLBACK_PREFIX};
 use super::tools::{
     handle_allowed_command, handle_allowedtools_command, handle_availabletools_command,
+    handle_preset_command, handle_toolscheck_command,
 };
-
-/// Entry point: start the Telegram bot with long polling.
-/// `default_project_dir` is the working directory bound by the CLI binary.
-pub async fn run_bot(token: &str, default_project_dir: &str) {
-    let bot = Bot::new(token);
-    let bot_settings = load_bot_settings(token);
-
-    // Register bot commands for autocomplete
-    let commands = vec![
+use super::uptime::handle_uptime_command;
+use super::worktree::handle_worktree_command;
+
+/// Callback data prefix for the /clear confirmation buttons (/clearconfirm)
+const CLEAR_CALLBACK_PREFIX: &str = "clear:";
+
+/// Maximum length (bytes) of a session label set via /label
+const MAX_LABEL_LEN: usize = 64;
+
+/// Hint shown when pinning fails (usually missing admin rights in groups)
+const PIN_PERMISSION_HINT: &str = "In groups, the bot needs the \"Pin messages\" admin permission.";
+
+/// Update types requested from Telegram. Reactions are not delivered unless listed explicitly.
+const ALLOWED_UPDATES: &[AllowedUpdate] = &[
+    AllowedUpdate::Message,
+    AllowedUpdate::InlineQuery,
+    AllowedUpdate::MessageReaction,
+    AllowedUpdate::CallbackQuery,
+    AllowedUpdate::MyChatMember,
+];
+
+/// Commands registered for autocomplete (also the names aliases may not shadow)
+pub(super) fn bot_commands() -> Vec<teloxide::types::BotCommand> {
+    vec![
         teloxide::types::BotCommand::new("help", "도움말"),
         teloxide::types::BotCommand::new("start", "세션 시작"),
+        teloxide::types::BotCommand::new("startpreview", "/start 시 보여줄 히스토리 개수/길이"),
+        teloxide::types::BotCommand::new("tooloutput", "도구 실행 결과 표시 길이"),
+        teloxide::types::BotCommand::new("maxresponse", "AI 응답 최대 길이"),
         teloxide::types::BotCommand::new("pwd", "현재 경로 확인"),
         teloxide::types::BotCommand::new("cd", "작업 경로 변경"),
+        teloxide::types::BotCommand::new("worktree", "git worktree 브랜치에서 작업"),
         teloxide::types::BotCommand::new("clear", "대화 히스토리 초기화"),
         teloxide::types::BotCommand::new("stop", "진행 중 작업 중단"),
+        teloxide::types::BotCommand::new("continue", "끊긴 AI 응답 이어서 받기"),
+        teloxide::types::BotCommand::new("summary", "대화 히스토리 요약으로 압축"),
         teloxide::types::BotCommand::new("status", "런타임 상태 확인"),
+        teloxide::types::BotCommand::new("config", "적용 중인 설정과 출처 확인"),
+        teloxide::types::BotCommand::new("selftest", "백엔드/저장소/Telegram 동작 점검"),
+        teloxide::types::BotCommand::new("version", "앱/백엔드 버전 확인"),
+        teloxide::types::BotCommand::new("uptime", "봇 실행 시간 확인"),
+        teloxide::types::BotCommand::new("rescan", "백엔드 실행 파일 경로 다시 찾기"),
+        teloxide::types::BotCommand::new("models", "사용 가능한 모델 목록"),
+        teloxide::types::BotCommand::new("diskusage", "세션/설정 디렉터리 용량"),
+        teloxide::types::BotCommand::new("ping", "Telegram API 응답 시간 측정"),
+        teloxide::types::BotCommand::new("agents", "실행 중인 백그라운드 에이전트"),
+        teloxide::types::BotCommand::new("cron", "반복 실행 프롬프트 예약/목록/삭제"),
+        teloxide::types::BotCommand::new("label", "세션 라벨 지정"),
+        teloxide::types::BotCommand::new("pin", "최근 AI 응답 고정"),
+        teloxide::types::BotCommand::new("unpin", "고정 해제"),
+        teloxide::types::BotCommand::new("raw", "최근 AI 응답 원문 보기"),
+        teloxide::types::BotCommand::new("run", "최근 AI 응답의 첫 코드 블록 실행"),
+        teloxide::types::BotCommand::new("rawstream", "백엔드 JSONL 원본 출력 기록/보기 (Owner)"),
+        teloxide::types::BotCommand::new("lastprompt", "마지막으로 보낸 전체 프롬프트"),
+        teloxide::types::BotCommand::new("output", "마지막 도구 실행 결과 전체 보기"),
+        teloxide::types::BotCommand::new("lasttools", "마지막 요청에서 사용한 도구와 횟수"),
+        teloxide::types::BotCommand::new("errors", "이 세션의 최근 오류 보기"),
+        teloxide::types::BotCommand::new("log", "봇 콘솔 로그 최근 줄 보기"),
+        teloxide::types::BotCommand::new("search", "세션 기록에서 검색 (all: 이전 세션 포함)"),
+        teloxide::types::BotCommand::new("prefix", "프롬프트 앞 고정 지시문"),
+        teloxide::types::BotCommand::new("suffix", "프롬프트 뒤 고정 지시문"),
+        teloxide::types::BotCommand::new("extract", "코드 블록 파일 추출 on/off"),
+        teloxide::types::BotCommand::new("verbose", "도구 실행 과정 표시 on/off"),
+        teloxide::types::BotCommand::new("filter", "프롬프트 인젝션 필터 on/off"),
+        teloxide::types::BotCommand::new("compact", "오래된 히스토리 자동 요약 on/off"),
+        teloxide::types::BotCommand::new("shellconfirm", "위험한 쉘 명령 실행 전 확인 on/off"),
+        teloxide::types::BotCommand::new("progressfile", "백엔드 진행 상황 파일 표시 on/off"),
+        teloxide::types::BotCommand::new("clearconfirm", "/clear 실행 전 확인 on/off"),
+        teloxide::types::BotCommand::new("auditlog", "프롬프트/응답 감사 로그 on/off"),
+        teloxide::types::BotCommand::new("madmax", "이 채팅의 샌드박스 해제 on/off (위험)"),
+        teloxide::types::BotCommand::new("shell", "이 채팅에서 ! 쉘 명령 허용 on/off"),
+        teloxide::types::BotCommand::new("mediahint", "처리할 수 없는 미디어 안내 on/off"),
+        teloxide::types::BotCommand::new("uploads", "파일/사진 업로드 허용 on/off"),
+        teloxide::types::BotCommand::new("autodownload", "AI가 만든/수정한 파일 자동 전송 on/off"),
+        teloxide::types::BotCommand::new(
+            "strictcode",
+            "코드 블록을 별도 일반 텍스트로 전송 on/off",
+        ),
+        teloxide::types::BotCommand::new("stderr", "백엔드 stderr 경고를 응답 아래에 표시 on/off"),
+        teloxide::types::BotCommand::new("recap", "새 세션에 최근 대화 요약 전달 on/off"),
+        teloxide::types::BotCommand::new("stream", "응답 작성 중 실시간 표시 on/off"),
+        teloxide::types::BotCommand::new(
+            "typingpause",
+            "채팅에 메시지가 이어지는 동안 실시간 표시 멈춤 on/off",
+        ),
+        teloxide::types::BotCommand::new("projecthint", "프로젝트 종류를 AI에게 알려주기 on/off"),
+        teloxide::types::BotCommand::new("shortpaths", "경로를 ~ / $PROJECT로 줄여 표시 on/off"),
+        teloxide::types::BotCommand::new(
+            "forwardwait",
+            "전달된 메시지를 다음 지시와 함께 보내기 on/off",
+        ),
+        teloxide::types::BotCommand::new("backendargs", "이 채팅의 추가 백엔드 인자"),
+        teloxide::types::BotCommand::new("parsemode", "응답 형식 (html/markdownv2/off)"),
+        teloxide::types::BotCommand::new("promptlimit", "프롬프트 최대 길이와 초과 시 처리"),
+        teloxide::types::BotCommand::new("format", "이 채팅의 응답 형식 (html/markdownv2/off)"),
+        teloxide::types::BotCommand::new("respondlang", "이 채팅의 응답 언어 고정 (clear: 자동)"),
+        teloxide::types::BotCommand::new("replystyle", "최종 답변 전달 방식 (edit/new)"),
+        teloxide::types::BotCommand::new("uploaddir", "업로드 파일을 저장할 하위 폴더"),
+        teloxide::types::BotCommand::new("setdefault", "/start 기본 폴더 지정"),
+        teloxide::types::BotCommand::new("reload", "설정 파일 다시 읽기"),
+        teloxide::types::BotCommand::new(
+            "reloadsession",
+            "세션을 디스크 파일과 비교해 다시 불러오기",
+        ),
+        teloxide::types::BotCommand::new("render", "마크다운 변환 결과 미리보기 (디버깅)"),
+        teloxide::types::BotCommand::new("recheck", "마지막 응답 렌더링 재검사 (버그 신고용)"),
+        teloxide::types::BotCommand::new("restart", "세션 저장 후 봇 프로세스 재시작"),
+        teloxide::types::BotCommand::new("greeting", "그룹 초대 시 인사말 설정"),
+        teloxide::types::BotCommand::new("footer", "모든 AI 응답 끝에 붙일 문구 설정"),
+        teloxide::types::BotCommand::new("backup", "봇 설정을 JSON 파일로 내보내기"),
+        teloxide::types::BotCommand::new("backupsessions", "저장된 모든 세션 파일을 압축해 받기"),
+        teloxide::types::BotCommand::new("restoresessions", "세션 백업 파일로 세션 복원"),
+        teloxide::types::BotCommand::new("restore", "백업 파일로 봇 설정 복원"),
+        teloxide::types::BotCommand::new("notify", "다른 채팅의 실패를 개인 메시지로 알림 on/off"),
         teloxide::types::BotCommand::new("down", "서버 파일 다운로드"),
+        teloxide::types::BotCommand::new("context", "파일 내용을 다음 프롬프트에 첨부"),
+        teloxide::types::BotCommand::new("pending", "다음 프롬프트에 붙을 업로드/첨부 확인·비우기"),
+        teloxide::types::BotCommand::new("cat", "텍스트 파일 앞/뒤 일부 보기"),
+        teloxide::types::BotCommand::new("downid", "file_id로 Telegram 파일 저장"),
+        teloxide::types::BotCommand::new("elevate", "다른 사용자에게 임시 권한 부여"),
+        teloxide::types::BotCommand::new("chats", "봇이 상태를 가진 모든 채팅 목록"),
+        teloxide::types::BotCommand::new("cooldown", "사용자별 AI 요청 최소 간격 (초)"),
+        teloxide::types::BotCommand::new("mirror", "AI 응답을 다른 채팅에 실시간 복사"),
+        teloxide::types::BotCommand::new("mute", "이 채팅에서 봇 응답 일시 중지"),
+        teloxide::types::BotCommand::new("unmute", "봇 응답 다시 시작"),
+        teloxide::types::BotCommand::new("cmdcooldown", "무거운 명령어의 채팅별 최소 실행 간격"),
         teloxide::types::BotCommand::new("public", "그룹 공개 모드 전환"),
+        teloxide::types::BotCommand::new("defaultpublic", "새 그룹의 기본 공개 여부 설정"),
+        teloxide::types::BotCommand::new(
+            "approval",
+            "공개 그룹 멤버 프롬프트를 Owner 승인 후 실행 on/off",
+        ),
         teloxide::types::BotCommand::new("availabletools", "전체 도구 목록"),
         teloxide::types::BotCommand::new("allowedtools", "허용 도구 목록"),
+        teloxide::types::BotCommand::new("toolscheck", "허용 도구 점검 (알 수 없는/위험 도구)"),
         teloxide::types::BotCommand::new("allowed", "도구 허용/해제"),
-    ];
-    if let Err(e) = bot.set_my_commands(commands).await {
-        println!("  ⚠ Failed to set bot commands: {e}");
+        teloxide::types::BotCommand::new("preset", "도구 프리셋 적용 (readonly/dev/safe)"),
+        teloxide::types::BotCommand::new("alias", "프롬프트 단축 명령 관리"),
+        teloxide::types::BotCommand::new("cmdalias", "이 채팅에서 쓸 명령어 별칭 관리"),
+    ]
+}
+
+/// Receive updates through a webhook instead of long polling (`--webhook-url`)
+pub struct WebhookConfig {
+    /// Public HTTPS URL Telegram posts updates to; its path is served locally
+    pub url: reqwest::Url,
+    /// Local port the webhook server listens on (all interfaces)
+    pub port: u16,
+}
+
+/// Entry point: start the Telegram bot with long polling.
+/// Handles chat messages, owner-only inline queries, reaction feedback, inline keyboard buttons,
+/// and the bot being removed from chats.
+/// `default_project_dir` is the working directory bound by the CLI binary.
+pub async fn run_bot(token: &str, default_project_dir: &str, webhook: Option<WebhookConfig>) {
+    let bot = new_bot(token);
+    let mut bot_settings = load_bot_settings(token);
+    if let Some(owner_id) = auth::preconfigured_owner() {
+        if bot_settings.owner_user_id != Some(owner_id) {
+            if let Some(previous) = bot_settings.owner_user_id {
+                logln!("  ⚠ Replacing saved owner {previous} with the pre-registered owner");
+            }
+            bot_settings.owner_user_id = Some(owner_id);
+            bot_settings.owner_dm_chat_id = None;
+            save_bot_settings(token, &bot_settings);
+        }
+    }
+
+    // Register bot commands for autocomplete
+    if let Err(e) = bot.set_my_commands(bot_commands()).await {
+        logln!("  ⚠ Failed to set bot commands: {e}");
     }
 
     match bot_settings.owner_user_id {
-        Some(owner_id) => println!("  ✓ Owner: {owner_id}"),
-        None => println!("  ⚠ No owner registered — first user will be registered as owner"),
+        Some(owner_id) if auth::preconfigured_owner().is_some() => {
+            logln!("  ✓ Owner: {owner_id} (pre-registered, imprinting disabled)")
+        }
+        Some(owner_id) => logln!("  ✓ Owner: {owner_id}"),
+        None => logln!("  ⚠ No owner registered — first user will be registered as owner"),
     }
 
     let state: SharedState = Arc::new(tokio::sync::Mutex::new(SharedData {
@@ -56,22 +283,312 @@ pub async fn run_bot(token: &str, default_project_dir: &str) {
         settings: bot_settings,
         cancel_tokens: HashMap::new(),
         shell_pids: HashMap::new(),
+        stopping: Default::default(),
         stop_message_ids: HashMap::new(),
+        stop_buttons: HashMap::new(),
         api_timestamps: HashMap::new(),
+        inbound_timestamps: HashMap::new(),
+        pending_extractions: HashMap::new(),
+        pending_downloads: HashMap::new(),
+        next_extraction_id: 0,
+        agent_tasks: HashMap::new(),
+        last_prompts: HashMap::new(),
+        last_tool_outputs: HashMap::new(),
+        last_turn_tools: HashMap::new(),
+        raw_streams: HashMap::new(),
+        media_groups: HashMap::new(),
+        pending_pastes: HashMap::new(),
+        cron_jobs: load_cron_jobs(token),
+        owner_notify: Default::default(),
+        pending_summaries: HashMap::new(),
+        compacting: Default::default(),
+        pending_shell_commands: HashMap::new(),
+        pending_runs: HashMap::new(),
+        pending_approvals: HashMap::new(),
+        pending_restores: HashMap::new(),
+        pending_session_restores: HashMap::new(),
+        pending_session_reloads: HashMap::new(),
+        pending_clears: HashMap::new(),
+        pending_madmax: HashMap::new(),
+        pending_restarts: HashMap::new(),
+        elevations: HashMap::new(),
+        user_last_requests: HashMap::new(),
+        command_last_used: HashMap::new(),
+        recent_messages: HashMap::new(),
+        backend_breakers: HashMap::new(),
+        started_at: Instant::now(),
     }));
 
-    println!("  ✓ Bot connected — Listening for messages");
+    logln!("  ✓ Bot connected — Listening for messages");
+
+    spawn_cron_scheduler(
+        bot.clone(),
+        state.clone(),
+        token.to_string(),
+        default_project_dir.to_string(),
+    );
 
-    let shared_state = state.clone();
+    let message_state = state.clone();
+    let inline_state = state.clone();
+    let reaction_state = state.clone();
+    let callback_state = state.clone();
     let token_owned = token.to_string();
+    let callback_token = token.to_string();
+    let member_state = state.clone();
+    let member_token = token.to_string();
     let default_project_dir_owned = default_project_dir.to_string();
-    teloxide::repl(bot, move |bot: Bot, msg: Message| {
-        let state = shared_state.clone();
-        let token = token_owned.clone();
-        let default_project_dir = default_project_dir_owned.clone();
-        async move { handle_message(bot, msg, state, &token, &default_project_dir).await }
+    let inline_project_dir = default_project_dir.to_string();
+
+    let handler = dptree::entry()
+        .branch(
+            Update::filter_message().endpoint(move |bot: Bot, msg: Message| {
+                let state = message_state.clone();
+                let token = token_owned.clone();
+                let default_project_dir = default_project_dir_owned.clone();
+                async move { handle_message(bot, msg, state, &token, &default_project_dir).await }
+            }),
+        )
+        .branch(
+            Update::filter_inline_query().endpoint(move |bot: Bot, query: InlineQuery| {
+                let state = inline_state.clone();
+                let default_project_dir = inline_project_dir.clone();
+                async move { handle_inline_query(bot, query, state, &default_project_dir).await }
+            }),
+        )
+        .branch(Update::filter_message_reaction_updated().endpoint(
+            move |bot: Bot, reaction: MessageReactionUpdated| {
+                let state = reaction_state.clone();
+                async move { handle_reaction_update(bot, reaction, state).await }
+            },
+        ))
+        .branch(
+            Update::filter_callback_query().endpoint(move |bot: Bot, query: CallbackQuery| {
+                let state = callback_state.clone();
+                let token = callback_token.clone();
+                async move { handle_callback_query(bot, query, state, &token).await }
+            }),
+        )
+        .branch(
+            Update::filter_my_chat_member().endpoint(move |update: ChatMemberUpdated| {
+                let state = member_state.clone();
+                let token = member_token.clone();
+                async move { handle_my_chat_member(update, state, &token).await }
+            }),
+        );
+
+    let mut dispatcher = Dispatcher::builder(bot.clone(), handler)
+        .default_handler(|_upd| Box::pin(async {}))
+        .enable_ctrlc_handler()
+        .build();
+    let error_handler = LoggingErrorHandler::with_custom_text("An error from the update listener");
+
+    match webhook {
+        Some(webhook) => {
+            // The webhook listener's setWebhook call does not pass allowed_updates, and
+            // Telegram keeps the previous value when it is omitted, so register them first
+            if let Err(e) = bot
+                .set_webhook(webhook.url.clone())
+                .allowed_updates(ALLOWED_UPDATES.to_vec())
+                .await
+            {
+                logln!("  ✗ Failed to register webhook: {e}");
+                return;
+            }
+            let address = std::net::SocketAddr::from(([0, 0, 0, 0], webhook.port));
+            let options = webhooks::Options::new(address, webhook.url.clone());
+            let listener = match webhooks::axum(bot, options).await {
+                Ok(listener) => listener,
+                Err(e) => {
+                    logln!("  ✗ Failed to start webhook listener: {e}");
+                    return;
+                }
+            };
+            logln!(
+                "  ✓ Webhook: {} (listening on port {})",
+                webhook.url,
+                webhook.port
+            );
+            dispatcher
+                .dispatch_with_listener(listener, error_handler)
+                .await;
+        }
+        None => {
+            let listener = Polling::builder(bot)
+                .timeout(std::time::Duration::from_secs(10))
+                .allowed_updates(ALLOWED_UPDATES.to_vec())
+                .delete_webhook()
+                .await
+                .build();
+            dispatcher
+                .dispatch_with_listener(listener, error_handler)
+                .await;
+        }
+    }
+}
+
+/// A session brought back by `restore_session_if_missing`
+pub(super) struct RestoredSession {
+    pub path: String,
+    /// Remembered folder that no longer exists, replaced by `path`
+    pub missing: Option<String>,
+}
+
+/// Restore a chat's session from bot_settings.json if it is not in memory.
+/// Falls back to the startup project dir when there is no previous path or it no longer
+/// exists, so a first prompt never needs an explicit /start; the caller tells the user when
+/// the remembered folder was gone.
+/// Returns None if the session already existed or no usable path was found.
+pub(super) fn restore_session_if_missing(
+    data: &mut SharedData,
+    chat_id: ChatId,
+    default_project_dir: &str,
+) -> Option<RestoredSession> {
+    if data.sessions.contains_key(&chat_id) {
+        return None;
+    }
+    let remembered = data
+        .settings
+        .last_sessions
+        .get(&chat_id.0.to_string())
+        .cloned();
+    let missing = remembered.clone().filter(|path| !Path::new(path).is_dir());
+    let candidate_path = remembered
+        .filter(|path| Path::new(path).is_dir())
+        .unwrap_or_else(|| chat_start_dir(&data.settings, chat_id, default_project_dir));
+    if !Path::new(&candidate_path).is_dir() {
+        return None;
+    }
+    let existing = load_existing_session(&candidate_path);
+    let (project_config, _) = load_project_config_with_note(&candidate_path);
+    let session = data.sessions.entry(chat_id).or_default();
+    session.current_path = Some(candidate_path.clone());
+    session.project_config = project_config;
+    if let Some((session_data, _)) = existing {
+        session.session_id = Some(session_data.session_id.clone());
+        session.history = session_data.history.clone();
+        session.label = session_data.label.clone();
+    }
+    Some(RestoredSession {
+        path: candidate_path,
+        missing,
     })
-    .await;
+}
+
+/// Route a reaction to the reaction commands or, failing that, to feedback. Reactions pass
+/// the same --allow-chat and /mute gates as messages; they are never commands, so a muted
+/// chat ignores them all.
+async fn handle_reaction_update(
+    bot: Bot,
+    reaction: MessageReactionUpdated,
+    state: SharedState,
+) -> ResponseResult<()> {
+    let chat_id = reaction.chat.id;
+    if !auth::is_chat_allowed(chat_id.0) {
+        codex::debug_log(&format!(
+            "Ignored reaction from chat {} (--allow-chat)",
+            chat_id
+        ));
+        return Ok(());
+    }
+    {
+        let data = state.lock().await;
+        let is_owner = reaction
+            .user()
+            .is_some_and(|user| is_owner(&data.settings, user.id.0));
+        let silenced =
+            is_muted(&data.settings, chat_id) || is_mirror_target(&data.settings, chat_id);
+        if silenced && !passes_mute(is_owner, None) {
+            codex::debug_log(&format!("Ignored reaction in muted chat {}", chat_id));
+            return Ok(());
+        }
+    }
+    if handle_reaction_command(&bot, &reaction, &state).await? {
+        return Ok(());
+    }
+    handle_message_reaction(reaction, state).await
+}
+
+/// Route inline keyboard button presses by their callback data prefix
+async fn handle_callback_query(
+    bot: Bot,
+    query: CallbackQuery,
+    state: SharedState,
+    token: &str,
+) -> ResponseResult<()> {
+    if let Some(message) = &query.message {
+        if !auth::is_chat_allowed(message.chat().id.0) {
+            bot.answer_callback_query(&query.id).await?;
+            return Ok(());
+        }
+    }
+    let data = query.data.clone().unwrap_or_default();
+    if let Some(rest) = data.strip_prefix(EXTRACT_CALLBACK_PREFIX) {
+        return handle_extract_callback(&bot, &query, rest, &state).await;
+    }
+    if let Some(rest) = data.strip_prefix(AUTODOWNLOAD_CALLBACK_PREFIX) {
+        return handle_autodownload_callback(&bot, &query, rest, &state).await;
+    }
+    if let Some(rest) = data.strip_prefix(PAGE_CALLBACK_PREFIX) {
+        return handle_page_callback(&bot, &query, rest, &state).await;
+    }
+    if let Some(rest) = data.strip_prefix(CONTINUE_CALLBACK_PREFIX) {
+        return handle_continue_callback(&bot, &query, rest, &state).await;
+    }
+    if let Some(rest) = data.strip_prefix(SUMMARY_CALLBACK_PREFIX) {
+        return handle_summary_callback(&bot, &query, rest, &state).await;
+    }
+    if let Some(rest) = data.strip_prefix(SHELL_CALLBACK_PREFIX) {
+        return handle_shell_callback(&bot, &query, rest, &state).await;
+    }
+    if let Some(rest) = data.strip_prefix(APPROVAL_CALLBACK_PREFIX) {
+        return handle_approval_callback(&bot, &query, rest, &state).await;
+    }
+    if let Some(rest) = data.strip_prefix(RUN_CALLBACK_PREFIX) {
+        return handle_run_callback(&bot, &query, rest, &state).await;
+    }
+    if let Some(rest) = data.strip_prefix(SESSION_RELOAD_CALLBACK_PREFIX) {
+        return handle_session_reload_callback(&bot, &query, rest, &state).await;
+    }
+    if let Some(rest) = data.strip_prefix(SESSION_RESTORE_CALLBACK_PREFIX) {
+        return handle_session_restore_callback(&bot, &query, rest, &state).await;
+    }
+    if let Some(rest) = data.strip_prefix(RESTORE_CALLBACK_PREFIX) {
+        return handle_restore_callback(&bot, &query, rest, &state, token).await;
+    }
+    if let Some(rest) = data.strip_prefix(MADMAX_CALLBACK_PREFIX) {
+        return handle_madmax_callback(&bot, &query, rest, &state, token).await;
+    }
+    if let Some(rest) = data.strip_prefix(RESTART_CALLBACK_PREFIX) {
+        return handle_restart_callback(&bot, &query, rest, &state).await;
+    }
+    if let Some(rest) = data.strip_prefix(CLEAR_CALLBACK_PREFIX) {
+        return handle_clear_callback(&bot, &query, rest, &state).await;
+    }
+    if data.starts_with(STOP_CALLBACK_PREFIX) {
+        return handle_stop_callback(&bot, &query, &state).await;
+    }
+    // Unknown button: acknowledge so the client stops showing a spinner
+    bot.answer_callback_query(&query.id).await?;
+    Ok(())
+}
+
+/// Name of a media type the bot receives but cannot pass to the AI (/mediahint)
+fn unsupported_media_kind(msg: &Message) -> Option<&'static str> {
+    if msg.sticker().is_some() {
+        Some("스티커")
+    } else if msg.animation().is_some() {
+        Some("GIF")
+    } else if msg.video().is_some() {
+        Some("동영상")
+    } else if msg.video_note().is_some() {
+        Some("동영상 메시지")
+    } else if msg.voice().is_some() {
+        Some("음성 메시지")
+    } else if msg.audio().is_some() {
+        Some("오디오")
+    } else {
+        None
+    }
 }
 
 /// Route incoming messages to appropriate handlers
@@ -83,6 +600,41 @@ async fn handle_message(
     default_project_dir: &str,
 ) -> ResponseResult<()> {
     let chat_id = msg.chat.id;
+    // Lockdown (--allow-chat): ignore other chats before imprinting or auth
+    if !auth::is_chat_allowed(chat_id.0) {
+        codex::debug_log(&format!(
+            "Ignored update from chat {} (--allow-chat)",
+            chat_id
+        ));
+        return Ok(());
+    }
+    // Telegram may redeliver an update after a network hiccup; never handle a message twice
+    {
+        let mut data = state.lock().await;
+        if !data
+            .recent_messages
+            .entry(chat_id)
+            .or_default()
+            .first_seen(msg.id)
+        {
+            codex::debug_log(&format!(
+                "Ignored duplicate message {} in chat {}",
+                msg.id.0, chat_id
+            ));
+            return Ok(());
+        }
+        data.inbound_timestamps
+            .insert(chat_id, tokio::time::Instant::now());
+    }
+    // Bot added to a group: post the intro (/greeting) instead of processing the join event
+    if handle_bot_added(&bot, &msg, &state).await? {
+        return Ok(());
+    }
+    // Never process other bots' messages (two bridges in one group would loop forever)
+    if msg.from.as_ref().is_some_and(|u| u.is_bot) {
+        codex::debug_log(&format!("Ignored bot message in chat {}", chat_id));
+        return Ok(());
+    }
     let raw_user_name = msg
         .from
         .as_ref()
@@ -96,40 +648,55 @@ async fn handle_message(
         // No user info (e.g. channel post) -> reject
         return Ok(());
     };
+    // Muted chat (/mute) or read-only mirror audience (/mirror): ignore everything but the
+    // owner's commands, without replying
+    {
+        let data = state.lock().await;
+        let is_owner = is_owner(&data.settings, uid);
+        let silenced =
+            is_muted(&data.settings, chat_id) || is_mirror_target(&data.settings, chat_id);
+        if silenced && !passes_mute(is_owner, msg.text()) {
+            codex::debug_log(&format!("Ignored message in muted chat {}", chat_id));
+            return Ok(());
+        }
+    }
     let is_group_chat = matches!(msg.chat.kind, teloxide::types::ChatKind::Public(_));
     let (imprinted, rejected_private) = {
         let mut data = state.lock().await;
-        match data.settings.owner_user_id {
+        // A pre-registered owner (--owner) is never replaced by imprinting
+        match owner_of(&data.settings) {
             None => {
                 // Imprint: register first user as owner
                 data.settings.owner_user_id = Some(uid);
+                if msg.chat.is_private() {
+                    data.settings.owner_dm_chat_id = Some(chat_id.0);
+                }
                 save_bot_settings(token, &data.settings);
-                println!("  [{timestamp}] ★ Owner registered: {raw_user_name} (id:{uid})");
+                logln!("  [{timestamp}] ★ Owner registered: {raw_user_name} (id:{uid})");
                 (true, false)
             }
             Some(owner_id) => {
                 if uid != owner_id {
                     // Check if this is a public group chat
-                    let chat_key = chat_id.0.to_string();
-                    let is_public = is_group_chat
-                        && data
-                            .settings
-                            .as_public_for_group_chat
-                            .get(&chat_key)
-                            .copied()
-                            .unwrap_or(false);
-                    if !is_public {
+                    let is_public = is_group_chat && is_public_chat(&data.settings, chat_id);
+                    if is_elevated(&data, chat_id, uid) {
+                        logln!("  [{timestamp}] ○ [{raw_user_name}(id:{uid})] Elevated access");
+                        (false, false)
+                    } else if !is_public {
                         // Unregistered user -> reject with guidance
-                        println!("  [{timestamp}] ✗ Rejected: {raw_user_name} (id:{uid})");
+                        logln!("  [{timestamp}] ✗ Rejected: {raw_user_name} (id:{uid})");
                         (false, true)
                     } else {
                         // Public group chat: allow non-owner user
-                        println!(
-                            "  [{timestamp}] ○ [{raw_user_name}(id:{uid})] Public group access"
-                        );
+                        logln!("  [{timestamp}] ○ [{raw_user_name}(id:{uid})] Public group access");
                         (false, false)
                     }
                 } else {
+                    // Remember the owner's private chat for failure notices (/notify)
+                    if msg.chat.is_private() && data.settings.owner_dm_chat_id != Some(chat_id.0) {
+                        data.settings.owner_dm_chat_id = Some(chat_id.0);
+                        save_bot_settings(token, &data.settings);
+                    }
                     (false, false)
                 }
             }
@@ -148,13 +715,23 @@ async fn handle_message(
 
     let is_owner = {
         let data = state.lock().await;
-        data.settings.owner_user_id == Some(uid)
+        is_owner(&data.settings, uid)
     };
 
     let user_name = format!("{}({uid})", raw_user_name);
 
     // Handle file/photo uploads
     if msg.document().is_some() || msg.photo().is_some() {
+        let uploads_enabled = {
+            let data = state.lock().await;
+            ChatToggle::Uploads.is_enabled(&data.settings, chat_id)
+        };
+        if !uploads_enabled {
+            shared_rate_limit_wait(&state, chat_id).await;
+            bot.send_message(chat_id, "File uploads are disabled in this chat.")
+                .await?;
+            return Ok(());
+        }
         // Auth: file uploads are High risk (modifies filesystem)
         if !is_owner {
             shared_rate_limit_wait(&state, chat_id).await;
@@ -162,6 +739,23 @@ async fn handle_message(
                 .await?;
             return Ok(());
         }
+        // A session archive sent back with the caption /restoresessions
+        if msg
+            .caption()
+            .unwrap_or("")
+            .trim()
+            .starts_with("/restoresessions")
+        {
+            logln!("  [{timestamp}] ◀ [{user_name}] /restoresessions (upload)");
+            handle_restoresessions_upload(&bot, chat_id, &msg, &state).await?;
+            return Ok(());
+        }
+        // A settings backup sent back with the caption /restore
+        if msg.caption().unwrap_or("").trim().starts_with("/restore") {
+            logln!("  [{timestamp}] ◀ [{user_name}] /restore (upload)");
+            handle_restore_upload(&bot, chat_id, &msg, &state).await?;
+            return Ok(());
+        }
         // In group chats, only process uploads whose caption starts with ';'
         if is_group_chat {
             let caption = msg.caption().unwrap_or("");
@@ -174,599 +768,2009 @@ async fn handle_message(
         } else {
             "photo"
         };
-        println!("  [{timestamp}] ◀ [{user_name}] Upload: {file_hint}");
-        handle_file_upload(&bot, chat_id, &msg, &state).await?;
-        println!("  [{timestamp}] ▶ [{user_name}] Upload complete");
         // If caption contains text after ';', send it to AI as a follow-up message
-        if let Some(caption) = msg.caption() {
-            let text_part = if is_group_chat {
+        let caption_prompt = msg.caption().and_then(|caption| {
+            let text = if is_group_chat {
                 // Group chat: extract text after ';'
-                caption.find(';').map(|pos| caption[pos + 1..].trim())
+                caption.find(';').map(|pos| caption[pos + 1..].trim())?
             } else {
                 // DM: use entire caption as-is
-                let trimmed = caption.trim();
-                if trimmed.is_empty() {
-                    None
-                } else {
-                    Some(trimmed)
-                }
+                caption.trim()
             };
-            if let Some(text) = text_part {
-                if !text.is_empty() {
-                    // Block if an AI request is already in progress
-                    let ai_busy = {
-                        let data = state.lock().await;
-                        data.cancel_tokens.contains_key(&chat_id)
-                    };
-                    if ai_busy {
-                        shared_rate_limit_wait(&state, chat_id).await;
-                        bot.send_message(chat_id, i18n::MSG_AI_BUSY).await?;
-                    } else {
-                        handle_text_message(&bot, chat_id, text, &state).await?;
-                    }
-                }
+            (!text.is_empty()).then(|| text.to_string())
+        });
+        // Albums arrive as one message per file; collect them and download concurrently
+        if let Some(group_id) = msg.media_group_id() {
+            logln!("  [{timestamp}] ◀ [{user_name}] Upload: {file_hint} (album)");
+            queue_media_group_item(&bot, chat_id, &msg, group_id, caption_prompt, &state).await;
+            return Ok(());
+        }
+        logln!("  [{timestamp}] ◀ [{user_name}] Upload: {file_hint}");
+        handle_file_upload(&bot, chat_id, &msg, &state).await?;
+        logln!("  [{timestamp}] ▶ [{user_name}] Upload complete");
+        if let Some(text) = caption_prompt {
+            // Block if an AI request is already in progress
+            let ai_busy = {
+                let data = state.lock().await;
+                data.cancel_tokens.contains_key(&chat_id)
+            };
+            if ai_busy {
+                shared_rate_limit_wait(&state, chat_id).await;
+                bot.send_message(chat_id, i18n::MSG_AI_BUSY).await?;
+            } else {
+                handle_text_message(&bot, chat_id, &text, &state).await?;
             }
         }
         return Ok(());
     }
 
     let Some(raw_text) = msg.text() else {
+        // Group members share stickers and GIFs with each other; only answer them in DMs
+        if let Some(kind) = unsupported_media_kind(&msg).filter(|_| msg.chat.is_private()) {
+            logln!("  [{timestamp}] ◀ [{user_name}] Unsupported media: {kind}");
+            let notify = {
+                let data = state.lock().await;
+                ChatToggle::MediaHint.is_enabled(&data.settings, chat_id)
+            };
+            if notify {
+                shared_rate_limit_wait(&state, chat_id).await;
+                bot.send_message(chat_id, i18n::MSG_UNSUPPORTED_MEDIA.replace("{kind}", kind))
+                    .await?;
+            }
+        }
         return Ok(());
     };
 
-    // Strip @botname suffix from commands (e.g. "/pwd@mybot" -> "/pwd")
-    let text = if raw_text.starts_with('/') {
-        if let Some(space_pos) = raw_text.find(' ') {
-            // "/cmd@bot args" -> "/cmd args"
-            let cmd_part = &raw_text[..space_pos];
-            let args_part = &raw_text[space_pos..];
-            if let Some(at_pos) = cmd_part.find('@') {
-                format!("{}{}", &cmd_part[..at_pos], args_part)
-            } else {
-                raw_text.to_string()
-            }
-        } else {
-            // "/cmd@bot" (no args) -> "/cmd"
-            if let Some(at_pos) = raw_text.find('@') {
-                raw_text[..at_pos].to_string()
-            } else {
-                raw_text.to_string()
-            }
-        }
-    } else {
-        raw_text.to_string()
+    let text = strip_bot_mention(raw_text);
+    // Per-chat command aliases (/cmdalias) are expanded before auth and routing
+    let text = {
+        let data = state.lock().await;
+        resolve_command_alias(&data.settings, chat_id, &text)
+    }
+    .unwrap_or(text);
+    // Forwarded messages are quoted context, never commands (a forwarded "!rm ..." must not run)
+    let forwarded = msg.forward_origin().is_some();
+    let text = match msg.forward_origin() {
+        Some(origin) => forwarded_prompt(origin, raw_text),
+        None => text,
     };
     let preview = truncate_str(&text, 60);
 
     // Auto-restore session from bot_settings.json if not in memory.
     // If there is no previous path, fall back to startup project dir.
     if !text.starts_with("/start") {
-        let mut data = state.lock().await;
-        if !data.sessions.contains_key(&chat_id) {
-            let candidate_path = data
-                .settings
-                .last_sessions
-                .get(&chat_id.0.to_string())
-                .cloned()
-                .unwrap_or_else(|| default_project_dir.to_string());
-            if Path::new(&candidate_path).is_dir() {
-                let existing = load_existing_session(&candidate_path);
-                let session = data.sessions.entry(chat_id).or_insert_with(|| ChatSession {
-                    session_id: None,
-                    current_path: None,
-                    history: Vec::new(),
-                    pending_uploads: Vec::new(),
-                    cleared: false,
-                });
-                session.current_path = Some(candidate_path.clone());
-                if let Some((session_data, _)) = existing {
-                    session.session_id = Some(session_data.session_id.clone());
-                    session.history = session_data.history.clone();
-                }
-                let ts = chrono::Local::now().format("%H:%M:%S");
-                println!("  [{ts}] ↻ [{user_name}] Auto-restored session: {candidate_path}");
+        let restored = {
+            let mut data = state.lock().await;
+            restore_session_if_missing(&mut data, chat_id, default_project_dir)
+        };
+        if let Some(restored) = restored {
+            let ts = chrono::Local::now().format("%H:%M:%S");
+            logln!(
+                "  [{ts}] ↻ [{user_name}] Auto-restored session: {}",
+                restored.path
+            );
+            // Say so before the prompt runs somewhere the user did not expect
+            if let Some(missing) = restored.missing {
+                shared_rate_limit_wait(&state, chat_id).await;
+                bot.send_message(
+                    chat_id,
+                    format!(
+                        "The last working folder {missing} no longer exists. This chat now \
+                         works in {}. Use /start <path> to choose another folder.",
+                        restored.path
+                    ),
+                )
+                .await?;
             }
         }
     }
 
-    // In group chats, ignore plain text (only /, !, ; prefixed messages are processed)
-    if is_group_chat && !text.starts_with('/') && !text.starts_with('!') && !text.starts_with(';') {
+    // The rest of a long paste the client split into several messages
+    if !forwarded
+        && !text.starts_with(['/', '!'])
+        && continue_paste(chat_id, uid, &text, &state).await
+    {
         return Ok(());
     }
 
-    // Auth: check command risk vs user permission level
-    {
+    // Group filter, auth, busy guard and command name, decided without touching Telegram
+    let action = {
         let data = state.lock().await;
-        let is_public_chat = is_group_chat
-            && data
-                .settings
-                .as_public_for_group_chat
-                .get(&chat_id.0.to_string())
-                .copied()
-                .unwrap_or(false);
-        let permission =
-            auth::get_permission_level(uid, data.settings.owner_user_id, is_public_chat);
-        let risk = auth::classify_command(&text);
-        if !auth::can_execute(permission, risk) {
-            drop(data);
+        let is_public = is_group_chat && is_public_chat(&data.settings, chat_id);
+        let context = RouteContext {
+            is_group: is_group_chat,
+            is_public,
+            permission: auth::get_permission_level(
+                uid,
+                owner_of(&data.settings),
+                is_public,
+                is_elevated(&data, chat_id, uid),
+            ),
+            forwarded,
+            busy: data.cancel_tokens.contains_key(&chat_id),
+            approval: ChatToggle::Approval.is_enabled(&data.settings, chat_id),
+            shell: ChatToggle::Shell.is_enabled(&data.settings, chat_id),
+            forward_wait: ChatToggle::ForwardWait.is_enabled(&data.settings, chat_id),
+        };
+        route_message(&text, &context)
+    };
+    match action {
+        RoutedAction::Ignore => return Ok(()),
+        RoutedAction::Denied => {
             shared_rate_limit_wait(&state, chat_id).await;
             bot.send_message(chat_id, "Permission denied. This command is owner-only.")
                 .await?;
             return Ok(());
         }
-    }
-
-    // Block all messages except /stop while an AI request is in progress
-    if !text.starts_with("/stop") {
-        let data = state.lock().await;
-        if data.cancel_tokens.contains_key(&chat_id) {
-            drop(data);
+        RoutedAction::Busy => {
             shared_rate_limit_wait(&state, chat_id).await;
             bot.send_message(chat_id, i18n::MSG_AI_BUSY).await?;
             return Ok(());
         }
+        _ => {}
     }
 
-    if text.starts_with("/stop") {
-        println!("  [{timestamp}] ◀ [{user_name}] /stop");
-        handle_stop_command(&bot, chat_id, &state).await?;
-    } else if text.starts_with("/help") {
-        println!("  [{timestamp}] ◀ [{user_name}] /help");
-        handle_help_command(&bot, chat_id, &state).await?;
-    } else if text.starts_with("/start") {
-        println!("  [{timestamp}] ◀ [{user_name}] /start");
-        handle_start_command(&bot, chat_id, &text, &state, token, default_project_dir).await?;
-    } else if text.starts_with("/clear") {
-        println!("  [{timestamp}] ◀ [{user_name}] /clear");
-        handle_clear_command(&bot, chat_id, &state).await?;
-        println!("  [{timestamp}] ▶ [{user_name}] Session cleared");
-    } else if text.starts_with("/pwd") {
-        println!("  [{timestamp}] ◀ [{user_name}] /pwd");
-        handle_pwd_command(&bot, chat_id, &state).await?;
-    } else if text.starts_with("/status") {
-        println!("  [{timestamp}] ◀ [{user_name}] /status");
-        handle_status_command(&bot, chat_id, &state).await?;
-    } else if text.starts_with("/cd") {
-        println!(
-            "  [{timestamp}] ◀ [{user_name}] /cd {}",
-            text.strip_prefix("/cd").unwrap_or("").trim()
-        );
-        handle_cd_command(&bot, chat_id, &text, &state, token).await?;
-    } else if text.starts_with("/down") {
-        println!(
-            "  [{timestamp}] ◀ [{user_name}] /down {}",
-            text.strip_prefix("/down").unwrap_or("").trim()
-        );
-        handle_down_command(&bot, chat_id, &text, &state).await?;
-    } else if text.starts_with("/public") {
-        println!(
-            "  [{timestamp}] ◀ [{user_name}] /public {}",
-            text.strip_prefix("/public").unwrap_or("").trim()
-        );
-        handle_public_command(&bot, chat_id, &text, &state, token, is_group_chat, is_owner).await?;
-    } else if text.starts_with("/availabletools") {
-        println!("  [{timestamp}] ◀ [{user_name}] /availabletools");
-        handle_availabletools_command(&bot, chat_id, &state).await?;
-    } else if text.starts_with("/allowedtools") {
-        println!("  [{timestamp}] ◀ [{user_name}] /allowedtools");
-        handle_allowedtools_command(&bot, chat_id, &state).await?;
-    } else if text.starts_with("/allowed") {
-        println!(
-            "  [{timestamp}] ◀ [{user_name}] /allowed {}",
-            text.strip_prefix("/allowed").unwrap_or("").trim()
-        );
-        handle_allowed_command(&bot, chat_id, &text, &state, token).await?;
-    } else if text.starts_with('!') {
-        println!("  [{timestamp}] ◀ [{user_name}] Shell: {preview}");
-        handle_shell_command(&bot, chat_id, &text, &state).await?;
-        println!("  [{timestamp}] ▶ [{user_name}] Shell done");
-    } else if text.starts_with(';') {
-        let stripped = text.strip_prefix(';').unwrap_or(&text).trim().to_string();
-        if stripped.is_empty() {
-            return Ok(());
-        }
-        let preview = truncate_str(&stripped, 60);
-        println!("  [{timestamp}] ◀ [{user_name}] {preview}");
-        handle_text_message(&bot, chat_id, &stripped, &state).await?;
-    } else {
-        println!("  [{timestamp}] ◀ [{user_name}] {preview}");
-        handle_text_message(&bot, chat_id, &text, &state).await?;
+    // Heavy commands the owner put on a cooldown (/cmdcooldown)
+    if refuse_if_command_cooling_down(&bot, chat_id, uid, &text, &state).await? {
+        return Ok(());
     }
 
-    Ok(())
-}
-
-/// Handle /help command
-async fn handle_help_command(
-    bot: &Bot,
-    chat_id: ChatId,
-    state: &SharedState,
-) -> ResponseResult<()> {
-    let help = i18n::HELP_TEXT_TEMPLATE.replace("{app}", env!("CARGO_BIN_NAME"));
-
-    shared_rate_limit_wait(state, chat_id).await;
-    bot.send_message(chat_id, help)
-        .parse_mode(ParseMode::Html)
-        .await?;
-
-    Ok(())
-}
-
-/// Handle /status command - show current runtime state
-async fn handle_status_command(
-    bot: &Bot,
-    chat_id: ChatId,
-    state: &SharedState,
-) -> ResponseResult<()> {
-    let (path, session_id, history_len, ai_active) = {
-        let data = state.lock().await;
-        let session = data.sessions.get(&chat_id);
-        (
-            session
-                .and_then(|s| s.current_path.clone())
-                .unwrap_or_else(|| "-".to_string()),
-            session
-                .and_then(|s| s.session_id.clone())
-                .unwrap_or_else(|| "-".to_string()),
-            session.map(|s| s.history.len()).unwrap_or(0),
-            data.cancel_tokens.contains_key(&chat_id),
-        )
-    };
-
-    let backend_path = codex::get_ai_binary_path();
-    let backend_name = backend_path
-        .and_then(|p| {
-            Path::new(p)
-                .file_name()
-                .and_then(|name| name.to_str())
-                .map(|s| s.to_string())
-        })
-        .unwrap_or_else(|| "unavailable".to_string());
-    let backend_version = backend_path
-        .and_then(|path| {
-            Command::new(path)
-                .arg("--version")
-                .output()
-                .ok()
-                .and_then(|output| {
-                    if output.status.success() {
-                        let stdout = String::from_utf8_lossy(&output.stdout).trim().to_string();
-                        if !stdout.is_empty() {
-                            Some(stdout)
-                        } else {
-                            let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
-                            (!stderr.is_empty()).then_some(stderr)
-                        }
-                    } else {
-                        None
-                    }
-                })
-        })
-        .unwrap_or_else(|| "unknown".to_string());
-    let ai_state = if ai_active { "running" } else { "idle" };
-
-    let message = format!(
-        "Status\n\
-path: {path}\n\
-session_id: {session_id}\n\
-history_len: {history_len}\n\
-active_ai: {ai_state}\n\
-backend: {backend_name}\n\
-backend_version: {backend_version}\n\
-app_version: {} {}",
-        env!("CARGO_BIN_NAME"),
-        env!("CARGO_PKG_VERSION")
-    );
-
-    shared_rate_limit_wait(state, chat_id).await;
-    bot.send_message(chat_id, message).await?;
-
-    Ok(())
-}
-
-/// Handle /start <path> command
-async fn handle_start_command(
-    bot: &Bot,
-    chat_id: ChatId,
-    text: &str,
-    state: &SharedState,
-    token: &str,
-    default_project_dir: &str,
-) -> ResponseResult<()> {
-    // Extract path from "/start <path>"
-    let path_str = text.strip_prefix("/start").unwrap_or("").trim();
+    match action {
+        RoutedAction::Command(command) => match command {
+            "/stop" => {
+                logln!("  [{timestamp}] ◀ [{user_name}] /stop");
+                handle_stop_command(&bot, chat_id, &state).await?;
+            }
+            "/help" => {
+                logln!("  [{timestamp}] ◀ [{user_name}] /help");
+                handle_help_command(&bot, chat_id, &state).await?;
+            }
+            "/backendargs" => {
+                logln!("  [{timestamp}] ◀ [{user_name}] /backendargs");
+                handle_backendargs_command(&bot, chat_id, &text, &state, token).await?;
+            }
+            "/maxresponse" => {
+                logln!(
+                    "  [{timestamp}] ◀ [{user_name}] /maxresponse {}",
+                    text.strip_prefix("/maxresponse").unwrap_or("").trim()
+                );
+                handle_maxresponse_command(&bot, chat_id, &text, &state, token).await?;
+            }
+            "/tooloutput" => {
+                logln!("  [{timestamp}] ◀ [{user_name}] /tooloutput");
+                handle_tooloutput_command(&bot, chat_id, &text, &state, token).await?;
+            }
+            "/startpreview" => {
+                logln!("  [{timestamp}] ◀ [{user_name}] /startpreview");
+                handle_startpreview_command(&bot, chat_id, &text, &state, token).await?;
+            }
+            "/start" => {
+                logln!("  [{timestamp}] ◀ [{user_name}] /start");
+                if cancel_active_request(&state, chat_id).await {
+                    logln!("  [{timestamp}] ■ [{user_name}] Running request cancelled by /start");
+                    shared_rate_limit_wait(&state, chat_id).await;
+                    bot.send_message(chat_id, i18n::MSG_REQUEST_CANCELLED_BY_START)
+                        .await?;
+                }
+                handle_start_command(&bot, chat_id, &text, &state, token, default_project_dir)
+                    .await?;
+            }
+            "/chats" => {
+                logln!("  [{timestamp}] ◀ [{user_name}] /chats");
+                handle_chats_command(&bot, chat_id, &state).await?;
+            }
+            "/selftest" => {
+                logln!("  [{timestamp}] ◀ [{user_name}] /selftest");
+                handle_selftest_command(&bot, chat_id, &state).await?;
+            }
+            "/context" => {
+                logln!("  [{timestamp}] ◀ [{user_name}] /context");
+                handle_context_command(&bot, chat_id, &text, &state).await?;
+            }
+            "/pending" => {
+                logln!("  [{timestamp}] ◀ [{user_name}] /pending");
+                handle_pending_command(&bot, chat_id, &text, &state).await?;
+            }
+            "/mirror" => {
+                logln!("  [{timestamp}] ◀ [{user_name}] /mirror");
+                handle_mirror_command(&bot, chat_id, &text, &state, token).await?;
+            }
+            "/mute" => {
+                logln!("  [{timestamp}] ◀ [{user_name}] /mute");
+                handle_mute_command(&bot, chat_id, true, &state, token).await?;
+            }
+            "/unmute" => {
+                logln!("  [{timestamp}] ◀ [{user_name}] /unmute");
+                handle_mute_command(&bot, chat_id, false, &state, token).await?;
+            }
+            "/cmdcooldown" => {
+                logln!("  [{timestamp}] ◀ [{user_name}] /cmdcooldown");
+                handle_cmdcooldown_command(&bot, chat_id, &text, &state, token).await?;
+            }
+            "/cooldown" => {
+                logln!("  [{timestamp}] ◀ [{user_name}] /cooldown");
+                handle_cooldown_command(&bot, chat_id, &text, &state, token).await?;
+            }
+            "/clearconfirm" => {
+                logln!("  [{timestamp}] ◀ [{user_name}] /clearconfirm");
+                handle_toggle_command(
+                    &bot,
+                    chat_id,
+                    &text,
+                    &state,
+                    token,
+                    ChatToggle::ClearConfirm,
+                )
+                .await?;
+            }
+            "/clear" => {
+                logln!("  [{timestamp}] ◀ [{user_name}] /clear");
+                if handle_clear_command(&bot, chat_id, &state).await? {
+                    logln!("  [{timestamp}] ▶ [{user_name}] Session cleared");
+                }
+            }
+            "/pwd" => {
+                logln!("  [{timestamp}] ◀ [{user_name}] /pwd");
+                handle_pwd_command(&bot, chat_id, &state, default_project_dir).await?;
+            }
+            "/ping" => {
+                logln!("  [{timestamp}] ◀ [{user_name}] /ping");
+                handle_ping_command(&bot, chat_id, &state).await?;
+            }
+            "/version" => {
+                logln!("  [{timestamp}] ◀ [{user_name}] /version");
+                handle_version_command(&bot, chat_id, &state).await?;
+            }
+            "/uptime" => {
+                logln!("  [{timestamp}] ◀ [{user_name}] /uptime");
+                handle_uptime_command(&bot, chat_id, &state).await?;
+            }
+            "/rescan" => {
+                logln!("  [{timestamp}] ◀ [{user_name}] /rescan");
+                handle_rescan_command(&bot, chat_id, &state).await?;
+            }
+            "/models" => {
+                logln!("  [{timestamp}] ◀ [{user_name}] /models");
+                handle_models_command(&bot, chat_id, &text, &state).await?;
+            }
+            "/status" => {
+                logln!("  [{timestamp}] ◀ [{user_name}] /status");
+                handle_status_command(&bot, chat_id, &msg, &state, default_project_dir).await?;
+            }
+            "/config" => {
+                logln!("  [{timestamp}] ◀ [{user_name}] /config");
+                handle_config_command(&bot, chat_id, &state, token).await?;
+            }
+            "/diskusage" => {
+                logln!("  [{timestamp}] ◀ [{user_name}] /diskusage");
+                handle_diskusage_command(&bot, chat_id, &state).await?;
+            }
+            "/agents" => {
+                logln!("  [{timestamp}] ◀ [{user_name}] /agents");
+                handle_agents_command(&bot, chat_id, &state).await?;
+            }
+            "/continue" => {
+                logln!("  [{timestamp}] ◀ [{user_name}] /continue");
+                handle_continue_command(&bot, chat_id, &state).await?;
+            }
+            "/summary" => {
+                logln!("  [{timestamp}] ◀ [{user_name}] /summary");
+                handle_summary_command(&bot, chat_id, &state).await?;
+            }
+            "/cron" => {
+                logln!(
+                    "  [{timestamp}] ◀ [{user_name}] /cron {}",
+                    truncate_str(text.strip_prefix("/cron").unwrap_or("").trim(), 60)
+                );
+                handle_cron_command(&bot, chat_id, &text, &state, token).await?;
+            }
+            "/cd" => {
+                logln!(
+                    "  [{timestamp}] ◀ [{user_name}] /cd {}",
+                    text.strip_prefix("/cd").unwrap_or("").trim()
+                );
+                handle_cd_command(&bot, chat_id, &text, &state, token, default_project_dir).await?;
+            }
+            "/worktree" => {
+                logln!(
+                    "  [{timestamp}] ◀ [{user_name}] /worktree {}",
+                    text.strip_prefix("/worktree").unwrap_or("").trim()
+                );
+                handle_worktree_command(&bot, chat_id, &text, &state, token).await?;
+            }
+            "/label" => {
+                logln!(
+                    "  [{timestamp}] ◀ [{user_name}] /label {}",
+                    text.strip_prefix("/label").unwrap_or("").trim()
+                );
+                handle_label_command(&bot, chat_id, &text, &state).await?;
+            }
+            "/pin" => {
+                logln!("  [{timestamp}] ◀ [{user_name}] /pin");
+                handle_pin_command(&bot, chat_id, &state).await?;
+            }
+            "/unpin" => {
+                logln!("  [{timestamp}] ◀ [{user_name}] /unpin");
+                handle_unpin_command(&bot, chat_id, &state).await?;
+            }
+            "/recheck" => {
+                logln!("  [{timestamp}] ◀ [{user_name}] /recheck");
+                handle_recheck_command(&bot, chat_id, &state).await?;
+            }
+            "/render" => {
+                logln!("  [{timestamp}] ◀ [{user_name}] /render");
+                handle_render_command(&bot, chat_id, &text, &state).await?;
+            }
+            "/rawstream" => {
+                logln!(
+                    "  [{timestamp}] ◀ [{user_name}] /rawstream {}",
+                    text.strip_prefix("/rawstream").unwrap_or("").trim()
+                );
+                handle_rawstream_command(&bot, chat_id, &text, &state).await?;
+            }
+            "/raw" => {
+                logln!("  [{timestamp}] ◀ [{user_name}] /raw");
+                handle_raw_command(&bot, chat_id, &text, &state).await?;
+            }
+            "/run" => {
+                logln!("  [{timestamp}] ◀ [{user_name}] /run");
+                handle_run_command(&bot, chat_id, is_owner, &state).await?;
+            }
+            "/lasttools" => {
+                logln!("  [{timestamp}] ◀ [{user_name}] /lasttools");
+                handle_lasttools_command(&bot, chat_id, &state).await?;
+            }
+            "/log" => {
+                logln!("  [{timestamp}] ◀ [{user_name}] /log");
+                handle_log_command(&bot, chat_id, &text, &state).await?;
+            }
+            "/errors" => {
+                logln!("  [{timestamp}] ◀ [{user_name}] /errors");
+                handle_errors_command(&bot, chat_id, &text, &state).await?;
+            }
+            "/search" => {
+                logln!(
+                    "  [{timestamp}] ◀ [{user_name}] /search {}",
+                    text.strip_prefix("/search").unwrap_or("").trim()
+                );
+                handle_search_command(&bot, chat_id, &text, &state).await?;
+            }
+            "/output" => {
+                logln!("  [{timestamp}] ◀ [{user_name}] /output");
+                handle_output_command(&bot, chat_id, &state).await?;
+            }
+            "/lastprompt" => {
+                logln!("  [{timestamp}] ◀ [{user_name}] /lastprompt");
+                handle_lastprompt_command(&bot, chat_id, &state).await?;
+            }
+            "/prefix" => {
+                logln!("  [{timestamp}] ◀ [{user_name}] /prefix");
+                handle_prompt_affix_command(
+                    &bot,
+                    chat_id,
+                    &text,
+                    &state,
+                    token,
+                    PromptAffix::Prefix,
+                )
+                .await?;
+            }
+            "/suffix" => {
+                logln!("  [{timestamp}] ◀ [{user_name}] /suffix");
+                handle_prompt_affix_command(
+                    &bot,
+                    chat_id,
+                    &text,
+                    &state,
+                    token,
+                    PromptAffix::Suffix,
+                )
+                .await?;
+            }
+            "/restart" => {
+                logln!("  [{timestamp}] ◀ [{user_name}] /restart");
+                handle_restart_command(&bot, chat_id, &state).await?;
+            }
+            "/madmax" => {
+                logln!("  [{timestamp}] ◀ [{user_name}] /madmax");
+                handle_madmax_command(&bot, chat_id, &text, &state, token).await?;
+            }
+            "/replystyle" => {
+                logln!(
+                    "  [{timestamp}] ◀ [{user_name}] /replystyle {}",
+                    text.strip_prefix("/replystyle").unwrap_or("").trim()
+                );
+                handle_replystyle_command(&bot, chat_id, &text, &state, token).await?;
+            }
+            "/uploaddir" => {
+                logln!(
+                    "  [{timestamp}] ◀ [{user_name}] /uploaddir {}",
+                    text.strip_prefix("/uploaddir").unwrap_or("").trim()
+                );
+                handle_uploaddir_command(&bot, chat_id, &text, &state, token).await?;
+            }
+            "/setdefault" => {
+                logln!("  [{timestamp}] ◀ [{user_name}] /setdefault");
+                handle_setdefault_command(&bot, chat_id, &text, &state, token, default_project_dir)
+                    .await?;
+            }
+            "/parsemode" => {
+                logln!(
+                    "  [{timestamp}] ◀ [{user_name}] /parsemode {}",
+                    text.strip_prefix("/parsemode").unwrap_or("").trim()
+                );
+                handle_parsemode_command(&bot, chat_id, &text, &state, token).await?;
+            }
+            "/promptlimit" => {
+                logln!(
+                    "  [{timestamp}] ◀ [{user_name}] /promptlimit {}",
+                    text.strip_prefix("/promptlimit").unwrap_or("").trim()
+                );
+                handle_promptlimit_command(&bot, chat_id, &text, &state, token).await?;
+            }
+            "/format" => {
+                logln!(
+                    "  [{timestamp}] ◀ [{user_name}] /format {}",
+                    text.strip_prefix("/format").unwrap_or("").trim()
+                );
+                handle_format_command(&bot, chat_id, &text, &state, token).await?;
+            }
+            "/respondlang" => {
+                logln!(
+                    "  [{timestamp}] ◀ [{user_name}] /respondlang {}",
+                    text.strip_prefix("/respondlang").unwrap_or("").trim()
+                );
+                handle_respondlang_command(&bot, chat_id, &text, &state, token).await?;
+            }
+            "/reloadsession" => {
+                logln!("  [{timestamp}] ◀ [{user_name}] /reloadsession");
+                handle_reloadsession_command(&bot, chat_id, &state).await?;
+            }
+            "/reload" => {
+                logln!("  [{timestamp}] ◀ [{user_name}] /reload");
+                handle_reload_command(&bot, chat_id, &state, token).await?;
+            }
+            "/greeting" => {
+                logln!("  [{timestamp}] ◀ [{user_name}] /greeting");
+                handle_greeting_command(&bot, chat_id, &text, &state, token).await?;
+            }
+            "/footer" => {
+                logln!(
+                    "  [{timestamp}] ◀ [{user_name}] /footer {}",
+                    truncate_str(text.strip_prefix("/footer").unwrap_or("").trim(), 60)
+                );
+                handle_footer_command(&bot, chat_id, &text, &state, token).await?;
+            }
+            "/backupsessions" => {
+                logln!("  [{timestamp}] ◀ [{user_name}] /backupsessions");
+                handle_backupsessions_command(&bot, chat_id, &state).await?;
+            }
+            "/restoresessions" => {
+                logln!("  [{timestamp}] ◀ [{user_name}] /restoresessions");
+                handle_restoresessions_command(&bot, chat_id, &state).await?;
+            }
+            "/backup" => {
+                logln!("  [{timestamp}] ◀ [{user_name}] /backup");
+                handle_backup_command(&bot, chat_id, &state).await?;
+            }
+            "/restore" => {
+                logln!("  [{timestamp}] ◀ [{user_name}] /restore");
+                handle_restore_command(&bot, chat_id, &state).await?;
+            }
+            "/notify" => {
+                logln!(
+                    "  [{timestamp}] ◀ [{user_name}] /notify {}",
+                    text.strip_prefix("/notify").unwrap_or("").trim()
+                );
+                handle_notify_command(&bot, chat_id, &text, &state, token).await?;
+            }
+            "/downid" => {
+                logln!("  [{timestamp}] ◀ [{user_name}] /downid");
+                handle_downid_command(&bot, chat_id, &text, &state).await?;
+            }
+            "/down" => {
+                logln!(
+                    "  [{timestamp}] ◀ [{user_name}] /down {}",
+                    text.strip_prefix("/down").unwrap_or("").trim()
+                );
+                handle_down_command(&bot, chat_id, &text, &state).await?;
+            }
+            "/cat" => {
+                logln!(
+                    "  [{timestamp}] ◀ [{user_name}] /cat {}",
+                    text.strip_prefix("/cat").unwrap_or("").trim()
+                );
+                handle_cat_command(&bot, chat_id, &text, &state).await?;
+            }
+            "/elevate" => {
+                logln!(
+                    "  [{timestamp}] ◀ [{user_name}] /elevate {}",
+                    text.strip_prefix("/elevate").unwrap_or("").trim()
+                );
+                handle_elevate_command(&bot, chat_id, &text, &state).await?;
+            }
+            "/defaultpublic" => {
+                logln!(
+                    "  [{timestamp}] ◀ [{user_name}] /defaultpublic {}",
+                    text.strip_prefix("/defaultpublic").unwrap_or("").trim()
+                );
+                handle_defaultpublic_command(&bot, chat_id, &text, &state, token, is_owner).await?;
+            }
+            "/public" => {
+                logln!(
+                    "  [{timestamp}] ◀ [{user_name}] /public {}",
+                    text.strip_prefix("/public").unwrap_or("").trim()
+                );
+                handle_public_command(&bot, chat_id, &text, &state, token, is_group_chat, is_owner)
+                    .await?;
+            }
+            "/availabletools" => {
+                logln!("  [{timestamp}] ◀ [{user_name}] /availabletools");
+                handle_availabletools_command(&bot, chat_id, &text, &state).await?;
+            }
+            "/alias" => {
+                logln!("  [{timestamp}] ◀ [{user_name}] /alias");
+                handle_alias_command(&bot, chat_id, &text, &state, token).await?;
+            }
+            "/cmdalias" => {
+                logln!("  [{timestamp}] ◀ [{user_name}] /cmdalias");
+                handle_cmdalias_command(&bot, chat_id, &text, &state, token).await?;
+            }
+            "/preset" => {
+                logln!("  [{timestamp}] ◀ [{user_name}] /preset");
+                handle_preset_command(&bot, chat_id, &text, &state, token).await?;
+            }
+            "/toolscheck" => {
+                logln!(
+                    "  [{timestamp}] ◀ [{user_name}] /toolscheck {}",
+                    text.strip_prefix("/toolscheck").unwrap_or("").trim()
+                );
+                handle_toolscheck_command(&bot, chat_id, &text, &state, token).await?;
+            }
+            "/allowedtools" => {
+                logln!("  [{timestamp}] ◀ [{user_name}] /allowedtools");
+                handle_allowedtools_command(&bot, chat_id, &state).await?;
+            }
+            "/allowed" => {
+                logln!(
+                    "  [{timestamp}] ◀ [{user_name}] /allowed {}",
+                    text.strip_prefix("/allowed").unwrap_or("").trim()
+                );
+                handle_allowed_command(&bot, chat_id, &text, &state, token).await?;
+            }
+            // Every name in ROUTED_COMMANDS needs an arm above (test_routed_commands_have_arms)
+            unhandled => {
+                logln!("  [{timestamp}]   ⚠ {unhandled} is routed but has no handler");
+                shared_rate_limit_wait(&state, chat_id).await;
+                bot.send_message(chat_id, format!("{unhandled} is not available."))
+                    .await?;
+            }
+        },
+        RoutedAction::Toggle(toggle) => {
+            logln!(
+                "  [{timestamp}] ◀ [{user_name}] {} {}",
+                toggle.command(),
+                text.strip_prefix(toggle.command()).unwrap_or("").trim()
+            );
+            handle_toggle_command(&bot, chat_id, &text, &state, token, toggle).await?;
+        }
+        RoutedAction::Shell { enabled: false } => {
+            logln!("  [{timestamp}] ◀ [{user_name}] Shell (disabled): {preview}");
+            shared_rate_limit_wait(&state, chat_id).await;
+            bot.send_message(
+                chat_id,
+                "Direct shell (!) is disabled in this chat. Ask the AI instead, or use /shell on.",
+            )
+            .await?;
+        }
+        RoutedAction::Shell { enabled: true } => {
+            logln!("  [{timestamp}] ◀ [{user_name}] Shell: {preview}");
+            handle_shell_command(&bot, chat_id, &text, &state).await?;
+            logln!("  [{timestamp}] ▶ [{user_name}] Shell done");
+        }
+        RoutedAction::PrefixedPrompt { approval } => {
+            let stripped = text.strip_prefix(';').unwrap_or(&text).trim().to_string();
+            if stripped.is_empty() {
+                return Ok(());
+            }
+            let preview = truncate_str(&stripped, 60);
+            logln!("  [{timestamp}] ◀ [{user_name}] {preview}");
+            if refuse_if_cooling_down(&bot, chat_id, uid, &state).await? {
+                return Ok(());
+            }
+            let prompt = with_reply_context(&bot, &msg, &stripped);
+            if approval {
+                logln!("  [{timestamp}] ○ [{user_name}] Held for owner approval");
+                hold_for_approval(&bot, chat_id, &user_name, prompt, &state).await?;
+            } else if !start_paste_if_split(&bot, chat_id, uid, &stripped, &prompt, &state).await {
+                if is_owner {
+                    remember_owner_prompt(&state, chat_id, msg.id, &prompt).await;
+                }
+                handle_text_message(&bot, chat_id, &prompt, &state).await?;
+            }
+        }
+        RoutedAction::HeldForward => {
+            logln!("  [{timestamp}] ◀ [{user_name}] Forward (held): {preview}");
+            queue_forwarded(&bot, chat_id, text, &state).await?;
+        }
+        RoutedAction::Prompt => {
+            logln!("  [{timestamp}] ◀ [{user_name}] {preview}");
+            if refuse_if_cooling_down(&bot, chat_id, uid, &state).await? {
+                return Ok(());
+            }
+            let alias_prompt = {
+                let data = state.lock().await;
+                resolve_alias(&data.settings, &text)
+            };
+            let prompt = alias_prompt.as_deref().unwrap_or(&text);
+            let prompt = with_reply_context(&bot, &msg, prompt);
+            if forwarded || !start_paste_if_split(&bot, chat_id, uid, &text, &prompt, &state).await
+            {
+                if is_owner {
+                    remember_owner_prompt(&state, chat_id, msg.id, &prompt).await;
+                }
+                handle_text_message(&bot, chat_id, &prompt, &state).await?;
+            }
+        }
+        // Answered before the cooldown check above
+        RoutedAction::Ignore | RoutedAction::Denied | RoutedAction::Busy => {}
+    }
+
+    Ok(())
+}
+
+/// Slash commands in routing order. A message is routed to the first name it starts with, so
+/// a command must come before any shorter command that is its prefix (/allowedtools before
+/// /allowed). Per-chat toggles (/shell, /stream, ...) are matched by exact name afterwards.
+const ROUTED_COMMANDS: &[&str] = &[
+    "/stop",
+    "/help",
+    "/backendargs",
+    "/maxresponse",
+    "/tooloutput",
+    "/startpreview",
+    "/start",
+    "/chats",
+    "/selftest",
+    "/context",
+    "/pending",
+    "/mirror",
+    "/mute",
+    "/unmute",
+    "/cmdcooldown",
+    "/cooldown",
+    "/clearconfirm",
+    "/clear",
+    "/pwd",
+    "/ping",
+    "/version",
+    "/uptime",
+    "/rescan",
+    "/models",
+    "/status",
+    "/config",
+    "/diskusage",
+    "/agents",
+    "/continue",
+    "/summary",
+    "/cron",
+    "/cd",
+    "/worktree",
+    "/label",
+    "/pin",
+    "/unpin",
+    "/recheck",
+    "/render",
+    "/rawstream",
+    "/raw",
+    "/run",
+    "/lasttools",
+    "/log",
+    "/errors",
+    "/search",
+    "/output",
+    "/lastprompt",
+    "/prefix",
+    "/suffix",
+    "/restart",
+    "/madmax",
+    "/replystyle",
+    "/uploaddir",
+    "/setdefault",
+    "/parsemode",
+    "/promptlimit",
+    "/format",
+    "/respondlang",
+    "/reloadsession",
+    "/reload",
+    "/greeting",
+    "/footer",
+    "/backupsessions",
+    "/restoresessions",
+    "/backup",
+    "/restore",
+    "/notify",
+    "/downid",
+    "/down",
+    "/cat",
+    "/elevate",
+    "/defaultpublic",
+    "/public",
+    "/availabletools",
+    "/alias",
+    "/cmdalias",
+    "/preset",
+    "/toolscheck",
+    "/allowedtools",
+    "/allowed",
+];
+
+/// Who sent a message and where, as far as routing it is concerned
+struct RouteContext {
+    is_group: bool,
+    /// Group chat that non-owners may use (/public, /defaultpublic)
+    is_public: bool,
+    permission: auth::PermissionLevel,
+    /// The message was forwarded from elsewhere
+    forwarded: bool,
+    /// An AI request is running in the chat
+    busy: bool,
+    /// /approval: members' `;` prompts in a public group wait for the owner
+    approval: bool,
+    /// /shell: direct `!` commands are allowed
+    shell: bool,
+    /// /forwardwait: forwarded messages are collected before reaching the AI
+    forward_wait: bool,
+}
+
+/// What handle_message does with a message, decided from its text and context alone
+#[derive(Debug, PartialEq, Eq)]
+enum RoutedAction {
+    /// Plain text in a group chat (only `/`, `!` and `;` messages are for the bot)
+    Ignore,
+    /// The sender's permission level does not cover the command's risk
+    Denied,
+    /// An AI request is running and the message neither stops nor cancels it
+    Busy,
+    /// Slash command, by its name in ROUTED_COMMANDS
+    Command(&'static str),
+    Toggle(ChatToggle),
+    /// `!` shell command; refused when /shell is off
+    Shell {
+        enabled: bool,
+    },
+    /// `;` prompt; with `approval` it is held for the owner instead of run
+    PrefixedPrompt {
+        approval: bool,
+    },
+    /// Forwarded message collected by /forwardwait
+    HeldForward,
+    /// Anything else is a prompt for the AI
+    Prompt,
+}
+
+/// Strip the @botname suffix from a command ("/pwd@mybot" -> "/pwd", "/cd@mybot ~" -> "/cd ~")
+fn strip_bot_mention(raw_text: &str) -> String {
+    if !raw_text.starts_with('/') {
+        return raw_text.to_string();
+    }
+    let command_end = raw_text.find(' ').unwrap_or(raw_text.len());
+    match raw_text[..command_end].find('@') {
+        Some(at_pos) => format!("{}{}", &raw_text[..at_pos], &raw_text[command_end..]),
+        None => raw_text.to_string(),
+    }
+}
+
+/// Decide how to handle a message: the group-chat prefix filter, the permission check, the
+/// busy guard, then the command it names
+fn route_message(text: &str, context: &RouteContext) -> RoutedAction {
+    // In group chats, ignore plain text (only /, !, ; prefixed messages are processed)
+    if context.is_group && !text.starts_with(['/', '!', ';']) {
+        return RoutedAction::Ignore;
+    }
+
+    // With /approval on, `;` prompts from other members of a public group are held for the
+    // owner instead of being refused
+    let approval = context.approval
+        && context.is_public
+        && context.permission != auth::PermissionLevel::Owner
+        && !context.forwarded
+        && text.starts_with(';');
+    if !approval && !auth::can_execute_message(context.permission, text) {
+        return RoutedAction::Denied;
+    }
+
+    // Block all messages except /stop while an AI request is in progress; /start and /clear
+    // cancel the request themselves
+    if context.busy && !text.starts_with("/stop") && !interrupts_request(text) {
+        return RoutedAction::Busy;
+    }
+
+    if let Some(command) = ROUTED_COMMANDS
+        .iter()
+        .copied()
+        .find(|c| text.starts_with(c))
+    {
+        RoutedAction::Command(command)
+    } else if let Some(toggle) = ChatToggle::from_text(text) {
+        RoutedAction::Toggle(toggle)
+    } else if text.starts_with('!') {
+        RoutedAction::Shell {
+            enabled: context.shell,
+        }
+    } else if text.starts_with(';') {
+        RoutedAction::PrefixedPrompt { approval }
+    } else if context.forwarded && context.forward_wait {
+        RoutedAction::HeldForward
+    } else {
+        RoutedAction::Prompt
+    }
+}
+
+/// Handle /help command
+async fn handle_help_command(
+    bot: &Bot,
+    chat_id: ChatId,
+    state: &SharedState,
+) -> ResponseResult<()> {
+    let help = i18n::HELP_TEXT_TEMPLATE.replace("{app}", env!("CARGO_BIN_NAME"));
+
+    shared_rate_limit_wait(state, chat_id).await;
+    bot.send_message(chat_id, help)
+        .parse_mode(ParseMode::Html)
+        .await?;
+
+    Ok(())
+}
+
+/// Handle /ping command - measure Telegram Bot API round-trip latency
+/// (sendMessage, then getMe), to tell network slowness apart from backend slowness
+async fn handle_ping_command(
+    bot: &Bot,
+    chat_id: ChatId,
+    state: &SharedState,
+) -> ResponseResult<()> {
+    shared_rate_limit_wait(state, chat_id).await;
+    let started = Instant::now();
+    let sent = bot.send_message(chat_id, "🏓 Pong...").await?;
+    let send_ms = started.elapsed().as_millis();
+
+    let started = Instant::now();
+    let get_me = match bot.get_me().await {
+        Ok(_) => format!("{} ms", started.elapsed().as_millis()),
+        Err(e) => format!("failed ({e})"),
+    };
+
+    let report = format!("🏓 Pong\nsendMessage: {send_ms} ms\ngetMe: {get_me}");
+    shared_rate_limit_wait(state, chat_id).await;
+    bot.edit_message_text(chat_id, sent.id, report).await?;
+
+    Ok(())
+}
+
+/// Name and (cached) version of the AI backend binary
+async fn backend_info() -> (String, String) {
+    let backend_name = codex::get_ai_binary_path()
+        .and_then(|p| {
+            Path::new(&p)
+                .file_name()
+                .and_then(|name| name.to_str())
+                .map(String::from)
+        })
+        .unwrap_or_else(|| "unavailable".to_string());
+    // The first call runs `--version`; later calls hit the cache
+    let backend_version = tokio::task::spawn_blocking(codex::get_ai_binary_version)
+        .await
+        .ok()
+        .flatten()
+        .unwrap_or_else(|| "unknown".to_string());
+    (backend_name, backend_version)
+}
+
+/// Handle /version command - report app, backend and build target versions
+async fn handle_version_command(
+    bot: &Bot,
+    chat_id: ChatId,
+    state: &SharedState,
+) -> ResponseResult<()> {
+    let (backend_name, backend_version) = backend_info().await;
+    let message = format!(
+        "{} {}\nbackend: {backend_name} ({backend_version})\ntarget: {}-{}",
+        env!("CARGO_BIN_NAME"),
+        env!("CARGO_PKG_VERSION"),
+        std::env::consts::ARCH,
+        std::env::consts::OS
+    );
+
+    shared_rate_limit_wait(state, chat_id).await;
+    bot.send_message(chat_id, message).await?;
+
+    Ok(())
+}
+
+/// Handle /rescan command - look up the backend binary in PATH again, e.g. after installing
+/// it while the bot was running (owner-only)
+async fn handle_rescan_command(
+    bot: &Bot,
+    chat_id: ChatId,
+    state: &SharedState,
+) -> ResponseResult<()> {
+    let message = match tokio::task::spawn_blocking(codex::rescan_ai_binary_path).await {
+        Ok((_, None)) => format!(
+            "{} CLI still not found in PATH. Install it, then run /rescan again.",
+            codex::ai_binary_name()
+        ),
+        Ok((previous, Some(path))) if previous.as_deref() == Some(path.as_str()) => {
+            format!("Backend unchanged: {path}")
+        }
+        Ok((None, Some(path))) => format!("Backend found: {path}"),
+        Ok((Some(previous), Some(path))) => format!("Backend changed: {previous} → {path}"),
+        Err(e) => format!("Rescan failed: {e}"),
+    };
+
+    shared_rate_limit_wait(state, chat_id).await;
+    bot.send_message(chat_id, message).await?;
+    Ok(())
+}
+
+/// Handle /models command - list model names accepted by the backend
+/// Usage: /models [refresh]  (`refresh` re-runs the listing command instead of using the cache)
+async fn handle_models_command(
+    bot: &Bot,
+    chat_id: ChatId,
+    text: &str,
+    state: &SharedState,
+) -> ResponseResult<()> {
+    let refresh = text.strip_prefix("/models").unwrap_or("").trim() == "refresh";
+    let current_model = {
+        let data = state.lock().await;
+        data.sessions
+            .get(&chat_id)
+            .and_then(|s| s.project_config.as_ref())
+            .and_then(|c| c.model.clone())
+    };
+
+    let listed = tokio::task::spawn_blocking(move || codex::list_models(refresh))
+        .await
+        .unwrap_or_else(|e| Err(e.to_string()));
+    let current_line = format!(
+        "Current model: <code>{}</code> (set <code>model</code> in {PROJECT_CONFIG_FILE})",
+        html_escape(current_model.as_deref().unwrap_or("backend default"))
+    );
+    let response_msg = match listed {
+        Ok((models, source)) => {
+            let source = match source {
+                codex::ModelSource::Backend(command) => {
+                    format!("from <code>{}</code>", html_escape(&command))
+                }
+                codex::ModelSource::Cached(command) => format!(
+                    "from <code>{}</code>, cached — <code>/models refresh</code> to re-run",
+                    html_escape(&command)
+                ),
+                codex::ModelSource::Configured(reason) => {
+                    format!("configured list; {}", html_escape(&reason))
+                }
+            };
+            let lines: Vec<String> = models
+                .iter()
+                .map(|model| {
+                    let marker = if current_model.as_deref() == Some(model.as_str()) {
+                        " ◀"
+                    } else {
+                        ""
+                    };
+                    format!("• <code>{}</code>{marker}", html_escape(model))
+                })
+                .collect();
+            format!(
+                "<b>Models</b> ({source})\n{}\n\n{current_line}",
+                lines.join("\n")
+            )
+        }
+        Err(reason) => format!(
+            "Could not list models: {}\n\n\
+             Set <code>model_list_command</code> (arguments for the backend binary) or a static \
+             <code>models</code> list in config.json.\n\n{current_line}",
+            html_escape(&reason)
+        ),
+    };
+
+    send_long_message(bot, chat_id, &response_msg, Some(ParseMode::Html), state).await?;
+    Ok(())
+}
+
+/// `/status` line for the forum topic a message was sent in. Telegram names the topic only
+/// through its opening message, which topic messages carry as their reply target.
+fn topic_status(msg: &Message) -> String {
+    let Some(thread) = msg.thread_id.filter(|_| msg.is_topic_message) else {
+        return "-".to_string();
+    };
+    let name = msg
+        .reply_to_message()
+        .and_then(|reply| reply.forum_topic_created())
+        .map(|topic| topic.name.as_str());
+    format_topic_status(thread.0 .0, name)
+}
+
+fn format_topic_status(thread_id: i32, name: Option<&str>) -> String {
+    let topic = match name {
+        Some(name) => format!("{name} (thread {thread_id})"),
+        None => format!("thread {thread_id}"),
+    };
+    format!("{topic}, session shared by all topics of this chat")
+}
+
+/// Handle /status command - show current runtime state
+async fn handle_status_command(
+    bot: &Bot,
+    chat_id: ChatId,
+    msg: &Message,
+    state: &SharedState,
+    default_project_dir: &str,
+) -> ResponseResult<()> {
+    let (
+        path,
+        session_id,
+        label,
+        history_len,
+        error_count,
+        ai_active,
+        madmax,
+        muted,
+        uploads,
+        backend_args,
+    ) = {
+        let data = state.lock().await;
+        let session = data.sessions.get(&chat_id);
+        (
+            session
+                .and_then(|s| s.current_path.as_deref())
+                .map(|path| display_path(&data, chat_id, path, default_project_dir))
+                .unwrap_or_else(|| "-".to_string()),
+            session
+                .and_then(|s| s.session_id.clone())
+                .unwrap_or_else(|| "-".to_string()),
+            session
+                .and_then(|s| s.label.clone())
+                .unwrap_or_else(|| "-".to_string()),
+            session.map(|s| s.history.len()).unwrap_or(0),
+            session
+                .map(|s| s.history.iter().filter(|item| is_error(item)).count())
+                .unwrap_or(0),
+            data.cancel_tokens.contains_key(&chat_id),
+            ChatToggle::Madmax.is_enabled(&data.settings, chat_id),
+            is_muted(&data.settings, chat_id),
+            ChatToggle::Uploads.is_enabled(&data.settings, chat_id),
+            data.settings
+                .backend_args
+                .get(&chat_id.0.to_string())
+                .map(|args| args.join(" "))
+                .unwrap_or_else(|| "-".to_string()),
+        )
+    };
+    let madmax = if madmax { "on" } else { "off" };
+    let muted = if muted { "yes (/unmute)" } else { "no" };
+    let uploads = if uploads { "on" } else { "off" };
+    let topic = topic_status(msg);
+
+    let (backend_name, backend_version) = backend_info().await;
+    let breaker = check_backend_breaker(state, chat_id).await;
+    let ai_state = if ai_active { "running" } else { "idle" };
+
+    let message = format!(
+        "Status\n\
+path: {path}\n\
+session_id: {session_id}\n\
+label: {label}\n\
+topic: {topic}\n\
+history_len: {history_len}\n\
+errors: {error_count}\n\
+active_ai: {ai_state}\n\
+madmax: {madmax}\n\
+muted: {muted}\n\
+uploads: {uploads}\n\
+backend: {backend_name}\n\
+backend_args: {backend_args}\n\
+backend_version: {backend_version}\n\
+backend_breaker: {breaker}\n\
+app_version: {} {}",
+        env!("CARGO_BIN_NAME"),
+        env!("CARGO_PKG_VERSION")
+    );
+
+    shared_rate_limit_wait(state, chat_id).await;
+    bot.send_message(chat_id, message).await?;
+
+    Ok(())
+}
+
+/// Handle /start <path> command
+async fn handle_start_command(
+    bot: &Bot,
+    chat_id: ChatId,
+    text: &str,
+    state: &SharedState,
+    token: &str,
+    default_project_dir: &str,
+) -> ResponseResult<()> {
+    // Extract path from "/start <path>"
+    let path_str = text.strip_prefix("/start").unwrap_or("").trim();
+
+    let canonical_path = if path_str.is_empty() {
+        // Bind to the chat's /setdefault directory, or the startup project directory
+        let start_dir = {
+            let data = state.lock().await;
+            chat_start_dir(&data.settings, chat_id, default_project_dir)
+        };
+        let path = Path::new(&start_dir);
+        if !path.exists() || !path.is_dir() {
+            shared_rate_limit_wait(state, chat_id).await;
+            bot.send_message(
+                chat_id,
+                format!("Error: default project dir is invalid: {}", start_dir),
+            )
+            .await?;
+            return Ok(());
+        }
+        path.canonicalize()
+            .map(|p| p.display().to_string())
+            .unwrap_or_else(|_| start_dir.clone())
+    } else {
+        let expanded = expand_home(path_str);
+        // Validate path exists
+        let path = Path::new(&expanded);
+        if !path.exists() || !path.is_dir() {
+            shared_rate_limit_wait(state, chat_id).await;
+            bot.send_message(
+                chat_id,
+                format!("Error: '{}' is not a valid directory.", expanded),
+            )
+            .await?;
+            return Ok(());
+        }
+        path.canonicalize()
+            .map(|p| p.display().to_string())
+            .unwrap_or_else(|_| expanded)
+    };
+
+    // Try to load existing session for this path
+    let existing = load_existing_session(&canonical_path);
+    let (project_config, project_note) = load_project_config_with_note(&canonical_path);
+
+    let mut response_lines = Vec::new();
+
+    {
+        let mut data = state.lock().await;
+        let preview = data
+            .settings
+            .start_previews
+            .get(&chat_id.0.to_string())
+            .copied()
+            .unwrap_or_default();
+        let session = data.sessions.entry(chat_id).or_default();
+        session.project_config = project_config;
+
+        if let Some((session_data, _)) = &existing {
+            session.session_id = Some(session_data.session_id.clone());
+            session.current_path = Some(canonical_path.clone());
+            session.history = session_data.history.clone();
+            session.label = session_data.label.clone();
+
+            let ts = chrono::Local::now().format("%H:%M:%S");
+            logln!("  [{ts}] ▶ Session restored: {canonical_path}");
+            response_lines.push(format!("Session restored at `{}`.", canonical_path));
+            if let Some(label) = &session_data.label {
+                response_lines.push(format!("Label: {}", label));
+            }
+            if session_data.in_progress {
+                response_lines
+                    .push("(The last turn was interrupted before it finished.)".to_string());
+            }
+            response_lines.extend(project_note);
+            response_lines.push(String::new());
+
+            // Show the last few conversation items (/startpreview)
+            let history_len = session_data.history.len();
+            let start_idx = history_len.saturating_sub(preview.items);
+            for item in &session_data.history[start_idx..] {
+                let prefix = match item.item_type {
+                    HistoryType::User => "You",
+                    HistoryType::Assistant => "AI",
+                    HistoryType::Error => "Error",
+                    HistoryType::System => "System",
+                    HistoryType::ToolUse => "Tool",
+                    HistoryType::ToolResult => "Result",
+                };
+                // Truncate long items for display
+                let content: String = item.content.chars().take(preview.chars).collect();
+                let truncated = if item.content.chars().count() > preview.chars {
+                    "..."
+                } else {
+                    ""
+                };
+                response_lines.push(format!("[{}] {}{}", prefix, content, truncated));
+            }
+        } else {
+            session.session_id = None;
+            session.current_path = Some(canonical_path.clone());
+            session.history.clear();
+            session.label = None;
+
+            let ts = chrono::Local::now().format("%H:%M:%S");
+            logln!("  [{ts}] ▶ Session started: {canonical_path}");
+            response_lines.push(format!("Session started at `{}`.", canonical_path));
+            response_lines.extend(project_note);
+        }
+    }
+
+    // Persist chat_id -> path mapping for auto-restore after restart
+    {
+        let mut data = state.lock().await;
+        data.settings
+            .last_sessions
+            .insert(chat_id.0.to_string(), canonical_path);
+        save_bot_settings(token, &data.settings);
+    }
+
+    let response_text = response_lines.join("\n");
+    send_long_message(bot, chat_id, &response_text, None, state).await?;
+
+    Ok(())
+}
+
+/// Handle /clear command. With /clearconfirm on, only asks for confirmation.
+/// Returns true if the session was cleared right away.
+pub(super) async fn handle_clear_command(
+    bot: &Bot,
+    chat_id: ChatId,
+    state: &SharedState,
+) -> ResponseResult<bool> {
+    let (confirm, ai_active) = {
+        let data = state.lock().await;
+        (
+            ChatToggle::ClearConfirm.is_enabled(&data.settings, chat_id),
+            data.cancel_tokens.contains_key(&chat_id),
+        )
+    };
+    if !confirm {
+        clear_session(state, chat_id).await;
+        shared_rate_limit_wait(state, chat_id).await;
+        bot.send_message(chat_id, i18n::MSG_SESSION_CLEARED).await?;
+        return Ok(true);
+    }
+
+    let keyboard = InlineKeyboardMarkup::new(vec![vec![
+        InlineKeyboardButton::callback("Clear", format!("{CLEAR_CALLBACK_PREFIX}yes")),
+        InlineKeyboardButton::callback("Cancel", format!("{CLEAR_CALLBACK_PREFIX}no")),
+    ]]);
+    let warning = if ai_active {
+        "\n⚠ An AI request is running and will be cancelled."
+    } else {
+        ""
+    };
+    shared_rate_limit_wait(state, chat_id).await;
+    let sent = bot
+        .send_message(
+            chat_id,
+            format!("Clear this session's history and AI context?{warning}"),
+        )
+        .reply_markup(keyboard)
+        .await?;
+
+    let mut data = state.lock().await;
+    data.pending_clears.insert(chat_id, sent.id);
+    Ok(false)
+}
+
+/// Handle a Clear/Cancel press on the /clear confirmation (owner-only).
+/// `data` is `yes` or `no`.
+async fn handle_clear_callback(
+    bot: &Bot,
+    query: &CallbackQuery,
+    data: &str,
+    state: &SharedState,
+) -> ResponseResult<()> {
+    let Some(message) = query.message.as_ref() else {
+        bot.answer_callback_query(&query.id).await?;
+        return Ok(());
+    };
+    let chat_id = message.chat().id;
+
+    let outcome = {
+        let mut shared = state.lock().await;
+        if !is_owner(&shared.settings, query.from.id.0) {
+            Err("Only the bot owner can clear the session.")
+        } else if shared.pending_clears.get(&chat_id) == Some(&message.id()) {
+            shared.pending_clears.remove(&chat_id);
+            Ok(data == "yes")
+        } else {
+            Err("This confirmation has expired.")
+        }
+    };
+
+    let clear = match outcome {
+        Ok(clear) => clear,
+        Err(reason) => {
+            bot.answer_callback_query(&query.id).text(reason).await?;
+            return Ok(());
+        }
+    };
+
+    bot.answer_callback_query(&query.id).await?;
+    if clear {
+        clear_session(state, chat_id).await;
+    }
+    let ts = chrono::Local::now().format("%H:%M:%S");
+    logln!(
+        "  [{ts}] ◀ [button] /clear ({})",
+        if clear { "confirmed" } else { "cancelled" }
+    );
+    let result = if clear {
+        i18n::MSG_SESSION_CLEARED
+    } else {
+        "Clear cancelled."
+    };
+    shared_rate_limit_wait(state, chat_id).await;
+    if let Err(e) = bot.edit_message_text(chat_id, message.id(), result).await {
+        logln!("  [{ts}]   ⚠ edit_message failed (clear confirm): {e}");
+    }
+
+    Ok(())
+}
+
+/// Cancel the running AI request (if any) and wipe the chat's session state
+async fn clear_session(state: &SharedState, chat_id: ChatId) {
+    // Cancel in-progress AI request if any
+    cancel_active_request(state, chat_id).await;
+
+    {
+        let mut data = state.lock().await;
+        if let Some(session) = data.sessions.get_mut(&chat_id) {
+            session.session_id = None;
+            session.history.clear();
+            session.pending_uploads.clear();
+            session.pending_forwards.clear();
+            session.pending_contexts.clear();
+            session.label = None;
+            session.cleared = true;
+        }
+        data.cancel_tokens.remove(&chat_id);
+        data.stop_message_ids.remove(&chat_id);
+        if !data.shell_pids.contains_key(&chat_id) {
+            data.stopping.remove(&chat_id);
+        }
+    }
+}
+
+/// Commands that are not refused while an AI request runs because they cancel it first:
+/// /start binds a new session and /clear wipes the current one
+fn interrupts_request(text: &str) -> bool {
+    matches!(text.split_whitespace().next(), Some("/start" | "/clear"))
+}
+
+/// Remember an owner prompt so reacting to its message can rerun it (reaction commands)
+async fn remember_owner_prompt(
+    state: &SharedState,
+    chat_id: ChatId,
+    msg_id: teloxide::types::MessageId,
+    prompt: &str,
+) {
+    let mut data = state.lock().await;
+    if let Some(session) = data.sessions.get_mut(&chat_id) {
+        track_prompt(session, msg_id, prompt);
+    }
+}
+
+/// Flag a request as cancelled and terminate its backend process
+fn cancel_request(token: &CancelToken) {
+    token.cancelled.store(true, Ordering::Relaxed);
+    if let Ok(guard) = token.child_pid.lock() {
+        if let Some(pid) = *guard {
+            #[cfg(unix)]
+            // SAFETY: sending SIGTERM to cancel the child AI process
+            #[allow(unsafe_code)]
+            unsafe {
+                libc::kill(pid as libc::pid_t, libc::SIGTERM);
+            }
+        }
+    }
+}
+
+/// Cancel the chat's running AI request so /start or /clear can proceed right away. The
+/// session is marked cleared so the request does not write its partial result into the
+/// session that replaces it. Returns true if a request was running.
+async fn cancel_active_request(state: &SharedState, chat_id: ChatId) -> bool {
+    let mut data = state.lock().await;
+    let Some(token) = data.cancel_tokens.remove(&chat_id) else {
+        return false;
+    };
+    cancel_request(&token);
+    if let Some(session) = data.sessions.get_mut(&chat_id) {
+        session.cleared = true;
+    }
+    data.stop_message_ids.remove(&chat_id);
+    if !data.shell_pids.contains_key(&chat_id) {
+        data.stopping.remove(&chat_id);
+    }
+    true
+}
+
+/// Handle /pwd command - show current session path
+async fn handle_pwd_command(
+    bot: &Bot,
+    chat_id: ChatId,
+    state: &SharedState,
+    default_project_dir: &str,
+) -> ResponseResult<()> {
+    let current_path = {
+        let data = state.lock().await;
+        data.sessions
+            .get(&chat_id)
+            .and_then(|s| s.current_path.as_deref())
+            .map(|path| display_path(&data, chat_id, path, default_project_dir))
+    };
+
+    shared_rate_limit_wait(state, chat_id).await;
+    match current_path {
+        Some(path) => bot.send_message(chat_id, &path).await?,
+        None => bot.send_message(chat_id, i18n::MSG_NO_SESSION).await?,
+    };
+
+    Ok(())
+}
+
+/// Handle /cd command - change working directory without resetting session
+async fn handle_cd_command(
+    bot: &Bot,
+    chat_id: ChatId,
+    text: &str,
+    state: &SharedState,
+    token: &str,
+    default_project_dir: &str,
+) -> ResponseResult<()> {
+    let path_str = text.strip_prefix("/cd").unwrap_or("").trim();
+
+    // No argument: show current path (like /pwd)
+    if path_str.is_empty() {
+        let current_path = {
+            let data = state.lock().await;
+            data.sessions
+                .get(&chat_id)
+                .and_then(|s| s.current_path.as_deref())
+                .map(|path| display_path(&data, chat_id, path, default_project_dir))
+        };
+        shared_rate_limit_wait(state, chat_id).await;
+        match current_path {
+            Some(path) => {
+                bot.send_message(chat_id, format!("Current: {path}"))
+                    .await?
+            }
+            None => bot.send_message(chat_id, i18n::MSG_NO_SESSION).await?,
+        };
+        return Ok(());
+    }
 
-    let canonical_path = if path_str.is_empty() {
-        // Bind to startup project directory by default.
-        let path = Path::new(default_project_dir);
-        if !path.exists() || !path.is_dir() {
+    // Expand ~ to home directory
+    let expanded = if path_str.starts_with("~/") || path_str == "~" {
+        expand_home(path_str)
+    } else if path_str.starts_with('/') {
+        path_str.to_string()
+    } else {
+        // Relative path: resolve against current_path
+        let base = {
+            let data = state.lock().await;
+            data.sessions
+                .get(&chat_id)
+                .and_then(|s| s.current_path.clone())
+        };
+        match base {
+            Some(b) => Path::new(&b).join(path_str).display().to_string(),
+            None => {
+                shared_rate_limit_wait(state, chat_id).await;
+                bot.send_message(chat_id, i18n::MSG_NO_SESSION).await?;
+                return Ok(());
+            }
+        }
+    };
+
+    // Validate path
+    let path = Path::new(&expanded);
+    if !path.exists() || !path.is_dir() {
+        shared_rate_limit_wait(state, chat_id).await;
+        bot.send_message(chat_id, format!("Error: not a valid directory: {expanded}"))
+            .await?;
+        return Ok(());
+    }
+
+    let canonical = path
+        .canonicalize()
+        .map(|p| p.display().to_string())
+        .unwrap_or_else(|_| expanded.clone());
+
+    // A symlink inside the project must not silently move the session elsewhere.
+    // Blocked unless madmax is on for this chat, where it is only flagged.
+    let mut escape_warning = None;
+    if auth::is_symlink_escape(path, Path::new(default_project_dir)) {
+        let ts = chrono::Local::now().format("%H:%M:%S");
+        logln!("  [{ts}]   ⚠ /cd symlink escape: {expanded} → {canonical}");
+        let notice = format!(
+            "{expanded} resolves to {canonical}, outside the project root {default_project_dir} (symlink)."
+        );
+        let madmax = {
+            let data = state.lock().await;
+            ChatToggle::Madmax.is_enabled(&data.settings, chat_id)
+        };
+        if !madmax {
             shared_rate_limit_wait(state, chat_id).await;
             bot.send_message(
                 chat_id,
-                format!(
-                    "Error: default project dir is invalid: {}",
-                    default_project_dir
-                ),
+                format!("Blocked: {notice}\nUse /cd {canonical} if you really mean to go there."),
             )
             .await?;
             return Ok(());
         }
-        path.canonicalize()
-            .map(|p| p.display().to_string())
-            .unwrap_or_else(|_| default_project_dir.to_string())
-    } else {
-        // Expand ~ to home directory
-        let expanded = if path_str.starts_with("~/") || path_str == "~" {
-            if let Some(home) = dirs::home_dir() {
-                home.join(path_str.strip_prefix("~/").unwrap_or(""))
-                    .display()
-                    .to_string()
-            } else {
-                path_str.to_string()
-            }
+        escape_warning = Some(format!("⚠ {notice}"));
+    }
+
+    let (project_config, project_note) = load_project_config_with_note(&canonical);
+
+    // Update current_path and project defaults, preserve session and history
+    let shown_path = {
+        let mut data = state.lock().await;
+        if let Some(session) = data.sessions.get_mut(&chat_id) {
+            session.current_path = Some(canonical.clone());
+            session.project_config = project_config;
         } else {
-            path_str.to_string()
-        };
-        // Validate path exists
-        let path = Path::new(&expanded);
-        if !path.exists() || !path.is_dir() {
             shared_rate_limit_wait(state, chat_id).await;
-            bot.send_message(
-                chat_id,
-                format!("Error: '{}' is not a valid directory.", expanded),
-            )
-            .await?;
+            bot.send_message(chat_id, i18n::MSG_NO_SESSION).await?;
             return Ok(());
         }
-        path.canonicalize()
-            .map(|p| p.display().to_string())
-            .unwrap_or_else(|_| expanded)
+
+        // Persist path so it survives session restarts
+        data.settings
+            .last_sessions
+            .insert(chat_id.0.to_string(), canonical.clone());
+        save_bot_settings(token, &data.settings);
+        display_path(&data, chat_id, &canonical, default_project_dir)
     };
 
-    // Try to load existing session for this path
-    let existing = load_existing_session(&canonical_path);
+    let mut response_msg = format!("Changed to: {shown_path}");
+    if let Some(warning) = escape_warning {
+        response_msg.push('\n');
+        response_msg.push_str(&warning);
+    }
+    if let Some(note) = project_note {
+        response_msg.push('\n');
+        response_msg.push_str(&note);
+    }
+    shared_rate_limit_wait(state, chat_id).await;
+    bot.send_message(chat_id, response_msg).await?;
 
-    let mut response_lines = Vec::new();
+    Ok(())
+}
 
-    {
+/// Handle /label command - set or show a human-readable label for the current session
+async fn handle_label_command(
+    bot: &Bot,
+    chat_id: ChatId,
+    text: &str,
+    state: &SharedState,
+) -> ResponseResult<()> {
+    let arg = text.strip_prefix("/label").unwrap_or("").trim();
+
+    let response_msg = {
         let mut data = state.lock().await;
-        let session = data.sessions.entry(chat_id).or_insert_with(|| ChatSession {
-            session_id: None,
-            current_path: None,
-            history: Vec::new(),
-            pending_uploads: Vec::new(),
-            cleared: false,
-        });
+        match data.sessions.get_mut(&chat_id) {
+            None => i18n::MSG_NO_SESSION.to_string(),
+            Some(session) if arg.is_empty() => match &session.label {
+                Some(label) => format!(
+                    "Label: {label}\n\n/label <text> — Change label\n/label clear — Remove label"
+                ),
+                None => "No label set.\nUsage: /label <text>".to_string(),
+            },
+            Some(session) => {
+                if arg == "clear" {
+                    session.label = None;
+                } else {
+                    session.label = Some(truncate_str(arg, MAX_LABEL_LEN));
+                }
+                if let Some(path) = session.current_path.clone() {
+                    save_session_to_file(session, &path);
+                }
+                match &session.label {
+                    Some(label) => format!("Label set: {label}"),
+                    None => "Label cleared.".to_string(),
+                }
+            }
+        }
+    };
 
-        if let Some((session_data, _)) = &existing {
-            session.session_id = Some(session_data.session_id.clone());
-            session.current_path = Some(canonical_path.clone());
-            session.history = session_data.history.clone();
+    shared_rate_limit_wait(state, chat_id).await;
+    bot.send_message(chat_id, response_msg).await?;
 
-            let ts = chrono::Local::now().format("%H:%M:%S");
-            println!("  [{ts}] ▶ Session restored: {canonical_path}");
-            response_lines.push(format!("Session restored at `{}`.", canonical_path));
-            response_lines.push(String::new());
+    Ok(())
+}
 
-            // Show last 5 conversation items
-            let history_len = session_data.history.len();
-            let start_idx = history_len.saturating_sub(5);
-            for item in &session_data.history[start_idx..] {
-                let prefix = match item.item_type {
-                    HistoryType::User => "You",
-                    HistoryType::Assistant => "AI",
-                    HistoryType::Error => "Error",
-                    HistoryType::System => "System",
-                    HistoryType::ToolUse => "Tool",
-                    HistoryType::ToolResult => "Result",
-                };
-                // Truncate long items for display
-                let content: String = item.content.chars().take(200).collect();
-                let truncated = if item.content.chars().count() > 200 {
-                    "..."
-                } else {
-                    ""
-                };
-                response_lines.push(format!("[{}] {}{}", prefix, content, truncated));
-            }
-        } else {
-            session.session_id = None;
-            session.current_path = Some(canonical_path.clone());
-            session.history.clear();
+/// Handle /pin command - pin the bot's most recent AI response in this chat
+async fn handle_pin_command(bot: &Bot, chat_id: ChatId, state: &SharedState) -> ResponseResult<()> {
+    let last_response = {
+        let data = state.lock().await;
+        data.sessions
+            .get(&chat_id)
+            .and_then(|s| s.last_response_msg_id)
+    };
+
+    let Some(msg_id) = last_response else {
+        shared_rate_limit_wait(state, chat_id).await;
+        bot.send_message(chat_id, "No AI response to pin yet.")
+            .await?;
+        return Ok(());
+    };
 
+    shared_rate_limit_wait(state, chat_id).await;
+    match bot
+        .pin_chat_message(chat_id, msg_id)
+        .disable_notification(true)
+        .await
+    {
+        Ok(_) => {
+            let mut data = state.lock().await;
+            if let Some(session) = data.sessions.get_mut(&chat_id) {
+                session.pinned_msg_id = Some(msg_id);
+            }
+        }
+        Err(e) => {
             let ts = chrono::Local::now().format("%H:%M:%S");
-            println!("  [{ts}] ▶ Session started: {canonical_path}");
-            response_lines.push(format!("Session started at `{}`.", canonical_path));
+            logln!("  [{ts}]   ⚠ pin_chat_message failed: {e}");
+            shared_rate_limit_wait(state, chat_id).await;
+            bot.send_message(
+                chat_id,
+                format!("Failed to pin message: {e}\n{PIN_PERMISSION_HINT}"),
+            )
+            .await?;
         }
     }
 
-    // Persist chat_id -> path mapping for auto-restore after restart
-    {
-        let mut data = state.lock().await;
-        data.settings
-            .last_sessions
-            .insert(chat_id.0.to_string(), canonical_path);
-        save_bot_settings(token, &data.settings);
+    Ok(())
+}
+
+/// Handle /raw command - resend the last AI response without any formatting
+/// Usage: /raw       (plain text message; long responses are sent as a .txt file)
+///        /raw file  (always send as a .txt file)
+async fn handle_raw_command(
+    bot: &Bot,
+    chat_id: ChatId,
+    text: &str,
+    state: &SharedState,
+) -> ResponseResult<()> {
+    let as_file = text.strip_prefix("/raw").unwrap_or("").trim() == "file";
+    let last_response = {
+        let data = state.lock().await;
+        data.sessions.get(&chat_id).and_then(|s| {
+            s.history
+                .iter()
+                .rev()
+                .find(|item| matches!(item.item_type, HistoryType::Assistant))
+                .map(|item| item.content.clone())
+        })
+    };
+
+    let Some(content) = last_response else {
+        shared_rate_limit_wait(state, chat_id).await;
+        bot.send_message(chat_id, "No AI response in this session yet.")
+            .await?;
+        return Ok(());
+    };
+
+    shared_rate_limit_wait(state, chat_id).await;
+    if as_file || content.len() > TELEGRAM_MSG_LIMIT {
+        let file =
+            teloxide::types::InputFile::memory(content.into_bytes()).file_name("response.txt");
+        bot.send_document(chat_id, file).await?;
+    } else {
+        bot.send_message(chat_id, content).await?;
     }
 
-    let response_text = response_lines.join("\n");
-    send_long_message(bot, chat_id, &response_text, None, state).await?;
+    Ok(())
+}
+
+/// Handle /render command - run markdown through the response converter and send the result,
+/// to reproduce rendering bugs. Uses the chat's /parsemode.
+/// Usage: /render <markdown>        (send rendered)
+///        /render --raw <markdown>  (show the generated markup as plain text)
+async fn handle_render_command(
+    bot: &Bot,
+    chat_id: ChatId,
+    text: &str,
+    state: &SharedState,
+) -> ResponseResult<()> {
+    let arg = text.strip_prefix("/render").unwrap_or("").trim_start();
+    let (raw, markdown) = match arg.split_once(char::is_whitespace) {
+        Some(("--raw", rest)) => (true, rest.trim_start()),
+        _ if arg == "--raw" => (true, ""),
+        _ => (false, arg),
+    };
+    if markdown.trim().is_empty() {
+        shared_rate_limit_wait(state, chat_id).await;
+        bot.send_message(
+            chat_id,
+            "Usage: /render <markdown>\n/render --raw <markdown> — Show the generated markup",
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let format = {
+        let data = state.lock().await;
+        data.settings
+            .response_formats
+            .get(&chat_id.0.to_string())
+            .copied()
+            .unwrap_or(data.settings.response_format)
+    };
+    let rendered = format.render(markdown);
+    if raw {
+        send_long_message(bot, chat_id, &rendered, None, state).await?;
+        return Ok(());
+    }
+    if let Err(e) = send_long_message(bot, chat_id, &rendered, format.parse_mode(), state).await {
+        // The rejection is what the caller is debugging: show it with the markup
+        shared_rate_limit_wait(state, chat_id).await;
+        bot.send_message(
+            chat_id,
+            format!(
+                "Telegram rejected the rendered {} text: {e}\n\n{}",
+                format.name(),
+                truncate_str(&rendered, TELEGRAM_MSG_LIMIT - 200)
+            ),
+        )
+        .await?;
+    }
 
     Ok(())
 }
 
-/// Handle /clear command
-async fn handle_clear_command(
+/// Handle /lastprompt command - show the exact prompt sent for the most recent AI request
+async fn handle_lastprompt_command(
     bot: &Bot,
     chat_id: ChatId,
     state: &SharedState,
 ) -> ResponseResult<()> {
-    // Cancel in-progress AI request if any
-    let cancel_token = {
+    let last_prompt = {
         let data = state.lock().await;
-        data.cancel_tokens.get(&chat_id).cloned()
+        data.last_prompts.get(&chat_id).cloned()
     };
-    if let Some(token) = cancel_token {
-        token.cancelled.store(true, Ordering::Relaxed);
-        if let Ok(guard) = token.child_pid.lock() {
-            if let Some(pid) = *guard {
-                #[cfg(unix)]
-                // SAFETY: sending SIGTERM to cancel the child AI process
-                #[allow(unsafe_code)]
-                unsafe {
-                    libc::kill(pid as libc::pid_t, libc::SIGTERM);
-                }
-            }
-        }
+
+    let Some(prompt) = last_prompt else {
+        shared_rate_limit_wait(state, chat_id).await;
+        bot.send_message(chat_id, "No AI request has been sent in this chat yet.")
+            .await?;
+        return Ok(());
+    };
+
+    shared_rate_limit_wait(state, chat_id).await;
+    let escaped = html_escape(&prompt);
+    // "<pre></pre>" adds 11 bytes
+    if escaped.len() + 11 <= TELEGRAM_MSG_LIMIT {
+        bot.send_message(chat_id, format!("<pre>{escaped}</pre>"))
+            .parse_mode(ParseMode::Html)
+            .await?;
+    } else {
+        let file =
+            teloxide::types::InputFile::memory(prompt.into_bytes()).file_name("last_prompt.txt");
+        bot.send_document(chat_id, file).await?;
     }
 
-    {
-        let mut data = state.lock().await;
-        if let Some(session) = data.sessions.get_mut(&chat_id) {
-            session.session_id = None;
-            session.history.clear();
-            session.pending_uploads.clear();
-            session.cleared = true;
-        }
-        data.cancel_tokens.remove(&chat_id);
-        data.stop_message_ids.remove(&chat_id);
+    Ok(())
+}
+
+/// Handle /output command - resend the full output of the last tool call (read-only)
+async fn handle_output_command(
+    bot: &Bot,
+    chat_id: ChatId,
+    state: &SharedState,
+) -> ResponseResult<()> {
+    let output = {
+        let data = state.lock().await;
+        data.last_tool_outputs
+            .get(&chat_id)
+            .map(|o| (o.tool.clone(), o.content.clone(), o.is_error))
+    };
+
+    let Some((tool, content, is_error)) = output else {
+        shared_rate_limit_wait(state, chat_id).await;
+        bot.send_message(chat_id, "No tool output recorded in this chat yet.")
+            .await?;
+        return Ok(());
+    };
+
+    let mut header = if is_error {
+        "❌ Last tool error".to_string()
+    } else {
+        "Last tool output".to_string()
+    };
+    if let Some(tool) = &tool {
+        header.push_str(&format!(" — ⚙ {tool}"));
     }
+    let content = if content.is_empty() {
+        "(empty)".to_string()
+    } else {
+        content
+    };
 
     shared_rate_limit_wait(state, chat_id).await;
-    bot.send_message(chat_id, i18n::MSG_SESSION_CLEARED).await?;
+    let message = format!(
+        "<b>{}</b>\n<pre>{}</pre>",
+        html_escape(&header),
+        html_escape(&content)
+    );
+    if message.len() <= TELEGRAM_MSG_LIMIT {
+        bot.send_message(chat_id, message)
+            .parse_mode(ParseMode::Html)
+            .await?;
+    } else {
+        let file =
+            teloxide::types::InputFile::memory(content.into_bytes()).file_name("tool_output.txt");
+        bot.send_document(chat_id, file)
+            .caption(truncate_str(&header, 1000))
+            .await?;
+    }
 
     Ok(())
 }
 
-/// Handle /pwd command - show current session path
-async fn handle_pwd_command(bot: &Bot, chat_id: ChatId, state: &SharedState) -> ResponseResult<()> {
-    let current_path = {
+/// Handle /lasttools command - which tools the most recent AI request called, with counts
+async fn handle_lasttools_command(
+    bot: &Bot,
+    chat_id: ChatId,
+    state: &SharedState,
+) -> ResponseResult<()> {
+    let tools = {
         let data = state.lock().await;
-        data.sessions
-            .get(&chat_id)
-            .and_then(|s| s.current_path.clone())
+        data.last_turn_tools.get(&chat_id).cloned()
     };
 
-    shared_rate_limit_wait(state, chat_id).await;
-    match current_path {
-        Some(path) => bot.send_message(chat_id, &path).await?,
-        None => bot.send_message(chat_id, i18n::MSG_NO_SESSION).await?,
+    let response_msg = match tools {
+        None => "No AI request recorded in this chat yet.".to_string(),
+        Some(tools) if tools.is_empty() => "The last request did not use any tools.".to_string(),
+        Some(tools) => {
+            let mut counts: Vec<(String, usize)> = tools.into_iter().collect();
+            counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+            let total: usize = counts.iter().map(|(_, n)| n).sum();
+            let lines: Vec<String> = counts
+                .iter()
+                .map(|(name, n)| format!("• <code>{}</code> × {n}", html_escape(name)))
+                .collect();
+            format!(
+                "<b>Tools used in the last request</b> ({total} call(s))\n{}",
+                lines.join("\n")
+            )
+        }
     };
 
+    shared_rate_limit_wait(state, chat_id).await;
+    bot.send_message(chat_id, response_msg)
+        .parse_mode(ParseMode::Html)
+        .await?;
     Ok(())
 }
 
-/// Handle /cd command - change working directory without resetting session
-async fn handle_cd_command(
+/// Handle /unpin command - unpin the message pinned via /pin
+async fn handle_unpin_command(
     bot: &Bot,
     chat_id: ChatId,
-    text: &str,
     state: &SharedState,
-    token: &str,
 ) -> ResponseResult<()> {
-    let path_str = text.strip_prefix("/cd").unwrap_or("").trim();
+    let pinned = {
+        let data = state.lock().await;
+        data.sessions.get(&chat_id).and_then(|s| s.pinned_msg_id)
+    };
 
-    // No argument: show current path (like /pwd)
-    if path_str.is_empty() {
-        let current_path = {
-            let data = state.lock().await;
-            data.sessions
-                .get(&chat_id)
-                .and_then(|s| s.current_path.clone())
-        };
+    let Some(msg_id) = pinned else {
         shared_rate_limit_wait(state, chat_id).await;
-        match current_path {
-            Some(path) => {
-                bot.send_message(chat_id, format!("Current: {path}"))
-                    .await?
-            }
-            None => bot.send_message(chat_id, i18n::MSG_NO_SESSION).await?,
-        };
+        bot.send_message(chat_id, "No message pinned via /pin.")
+            .await?;
         return Ok(());
-    }
+    };
 
-    // Expand ~ to home directory
-    let expanded = if path_str.starts_with("~/") || path_str == "~" {
-        if let Some(home) = dirs::home_dir() {
-            home.join(path_str.strip_prefix("~/").unwrap_or(""))
-                .display()
-                .to_string()
-        } else {
-            path_str.to_string()
-        }
-    } else if path_str.starts_with('/') {
-        path_str.to_string()
-    } else {
-        // Relative path: resolve against current_path
-        let base = {
-            let data = state.lock().await;
-            data.sessions
-                .get(&chat_id)
-                .and_then(|s| s.current_path.clone())
-        };
-        match base {
-            Some(b) => Path::new(&b).join(path_str).display().to_string(),
-            None => {
-                shared_rate_limit_wait(state, chat_id).await;
-                bot.send_message(chat_id, i18n::MSG_NO_SESSION).await?;
-                return Ok(());
+    shared_rate_limit_wait(state, chat_id).await;
+    match bot.unpin_chat_message(chat_id).message_id(msg_id).await {
+        Ok(_) => {
+            {
+                let mut data = state.lock().await;
+                if let Some(session) = data.sessions.get_mut(&chat_id) {
+                    session.pinned_msg_id = None;
+                }
             }
+            shared_rate_limit_wait(state, chat_id).await;
+            bot.send_message(chat_id, "Unpinned.").await?;
         }
-    };
-
-    // Validate path
-    let path = Path::new(&expanded);
-    if !path.exists() || !path.is_dir() {
-        shared_rate_limit_wait(state, chat_id).await;
-        bot.send_message(chat_id, format!("Error: not a valid directory: {expanded}"))
+        Err(e) => {
+            let ts = chrono::Local::now().format("%H:%M:%S");
+            logln!("  [{ts}]   ⚠ unpin_chat_message failed: {e}");
+            shared_rate_limit_wait(state, chat_id).await;
+            bot.send_message(
+                chat_id,
+                format!("Failed to unpin message: {e}\n{PIN_PERMISSION_HINT}"),
+            )
             .await?;
-        return Ok(());
+        }
     }
 
-    let canonical = path
-        .canonicalize()
-        .map(|p| p.display().to_string())
-        .unwrap_or(expanded);
+    Ok(())
+}
 
-    // Update only current_path, preserve session and history
-    {
-        let mut data = state.lock().await;
-        if let Some(session) = data.sessions.get_mut(&chat_id) {
-            session.current_path = Some(canonical.clone());
+/// Handle a press on the ⏹ Stop button of a processing message (owner-only, like /stop)
+async fn handle_stop_callback(
+    bot: &Bot,
+    query: &CallbackQuery,
+    state: &SharedState,
+) -> ResponseResult<()> {
+    let Some(message) = query.message.as_ref() else {
+        bot.answer_callback_query(&query.id).await?;
+        return Ok(());
+    };
+    let chat_id = message.chat().id;
+
+    let rejection = {
+        let data = state.lock().await;
+        if !is_owner(&data.settings, query.from.id.0) {
+            Some("Only the bot owner can stop requests.")
+        } else if data.stop_buttons.get(&chat_id) != Some(&message.id()) {
+            Some("This request has already finished.")
         } else {
-            shared_rate_limit_wait(state, chat_id).await;
-            bot.send_message(chat_id, i18n::MSG_NO_SESSION).await?;
-            return Ok(());
+            None
         }
-
-        // Persist path so it survives session restarts
-        data.settings
-            .last_sessions
-            .insert(chat_id.0.to_string(), canonical.clone());
-        save_bot_settings(token, &data.settings);
+    };
+    if let Some(reason) = rejection {
+        bot.answer_callback_query(&query.id).text(reason).await?;
+        return Ok(());
     }
 
-    shared_rate_limit_wait(state, chat_id).await;
-    bot.send_message(chat_id, format!("Changed to: {canonical}"))
-        .await?;
-
-    Ok(())
+    bot.answer_callback_query(&query.id).await?;
+    let ts = chrono::Local::now().format("%H:%M:%S");
+    logln!("  [{ts}] ◀ [button] /stop");
+    handle_stop_command(bot, chat_id, state).await
 }
 
 /// Handle /stop command - cancel in-progress AI request
-async fn handle_stop_command(
+pub(super) async fn handle_stop_command(
     bot: &Bot,
     chat_id: ChatId,
     state: &SharedState,
 ) -> ResponseResult<()> {
-    let (token, shell_pid) = {
+    // Decide under one lock, so of several quick /stop presses only the first acts.
+    // The request and shell loops clear `stopping` once their process has exited.
+    let (token, shell_pid, already_stopping) = {
         let mut data = state.lock().await;
-        let token = data.cancel_tokens.get(&chat_id).cloned();
-        let shell_pid = data.shell_pids.remove(&chat_id);
-        (token, shell_pid)
+        let running =
+            data.cancel_tokens.contains_key(&chat_id) || data.shell_pids.contains_key(&chat_id);
+        if running && data.stopping.contains(&chat_id) {
+            (None, None, true)
+        } else {
+            // Skip a request that is already cancelled (e.g. by /clear)
+            let token = data
+                .cancel_tokens
+                .get(&chat_id)
+                .filter(|t| !t.cancelled.load(Ordering::Relaxed))
+                .cloned();
+            let shell_pid = data.shell_pids.get(&chat_id).copied();
+            if token.is_some() || shell_pid.is_some() {
+                data.stopping.insert(chat_id);
+            } else {
+                data.stopping.remove(&chat_id);
+            }
+            (token, shell_pid, false)
+        }
     };
     let has_ai_token = token.is_some();
 
+    if already_stopping {
+        shared_rate_limit_wait(state, chat_id).await;
+        bot.send_message(chat_id, i18n::MSG_ALREADY_STOPPING)
+            .await?;
+        return Ok(());
+    }
     if token.is_none() && shell_pid.is_none() {
         shared_rate_limit_wait(state, chat_id).await;
         bot.send_message(chat_id, i18n::MSG_NO_ACTIVE_REQUEST)
@@ -776,37 +2780,34 @@ async fn handle_stop_command(
 
     // Cancel AI request if present.
     if let Some(token) = token {
-        // Ignore duplicate /stop for AI, but still allow shell cancellation below.
-        if !token.cancelled.load(Ordering::Relaxed) {
-            // Send immediate feedback to user
-            shared_rate_limit_wait(state, chat_id).await;
-            let stop_msg = bot.send_message(chat_id, i18n::MSG_STOPPING).await?;
+        // Send immediate feedback to user
+        shared_rate_limit_wait(state, chat_id).await;
+        let stop_msg = bot.send_message(chat_id, i18n::MSG_STOPPING).await?;
 
-            // Store the stop message ID so the polling loop can update it later
-            {
-                let mut data = state.lock().await;
-                data.stop_message_ids.insert(chat_id, stop_msg.id);
-            }
+        // Store the stop message ID so the polling loop can update it later
+        {
+            let mut data = state.lock().await;
+            data.stop_message_ids.insert(chat_id, stop_msg.id);
+        }
 
-            // Set cancellation flag
-            token.cancelled.store(true, Ordering::Relaxed);
+        // Set cancellation flag
+        token.cancelled.store(true, Ordering::Relaxed);
 
-            // Kill child process directly to unblock reader.lines()
-            // When the child dies, its stdout pipe closes -> reader returns EOF -> blocking thread exits
-            if let Ok(guard) = token.child_pid.lock() {
-                if let Some(pid) = *guard {
-                    #[cfg(unix)]
-                    // SAFETY: sending SIGTERM to cancel the child AI process
-                    #[allow(unsafe_code)]
-                    unsafe {
-                        libc::kill(pid as libc::pid_t, libc::SIGTERM);
-                    }
+        // Kill child process directly to unblock reader.lines()
+        // When the child dies, its stdout pipe closes -> reader returns EOF -> blocking thread exits
+        if let Ok(guard) = token.child_pid.lock() {
+            if let Some(pid) = *guard {
+                #[cfg(unix)]
+                // SAFETY: sending SIGTERM to cancel the child AI process
+                #[allow(unsafe_code)]
+                unsafe {
+                    libc::kill(pid as libc::pid_t, libc::SIGTERM);
                 }
             }
-
-            let ts = chrono::Local::now().format("%H:%M:%S");
-            println!("  [{ts}] ■ Cancel signal sent");
         }
+
+        let ts = chrono::Local::now().format("%H:%M:%S");
+        logln!("  [{ts}] ■ Cancel signal sent");
     }
 
     // Stop running shell command if present.
@@ -825,7 +2826,7 @@ async fn handle_stop_command(
         }
 
         let ts = chrono::Local::now().format("%H:%M:%S");
-        println!("  [{ts}] ■ Shell stop signal sent (pid:{pid})");
+        logln!("  [{ts}] ■ Shell stop signal sent (pid:{pid})");
     }
 
     Ok(())
@@ -877,28 +2878,43 @@ async fn handle_public_command(
         }
         "off" => {
             let mut data = state.lock().await;
-            data.settings.as_public_for_group_chat.remove(&chat_key);
+            // Kept as an explicit entry so /defaultpublic on does not open this group
+            data.settings
+                .as_public_for_group_chat
+                .insert(chat_key, false);
             save_bot_settings(token, &data.settings);
             "Public access <b>disabled</b> for this group.\nOnly the owner can use the bot."
                 .to_string()
         }
+        "default" => {
+            let mut data = state.lock().await;
+            data.settings.as_public_for_group_chat.remove(&chat_key);
+            save_bot_settings(token, &data.settings);
+            let status = if data.settings.default_public {
+                "enabled"
+            } else {
+                "disabled"
+            };
+            format!("This group now follows the bot default (/defaultpublic): public access <b>{status}</b>.")
+        }
         "" => {
             let data = state.lock().await;
-            let is_public = data
-                .settings
-                .as_public_for_group_chat
-                .get(&chat_key)
-                .copied()
-                .unwrap_or(false);
+            let is_public = is_public_chat(&data.settings, chat_id);
             let status = if is_public { "enabled" } else { "disabled" };
+            let source = if data.settings.as_public_for_group_chat.contains_key(&chat_key) {
+                "set for this group"
+            } else {
+                "bot default, /defaultpublic"
+            };
             format!(
-                "Public access is currently <b>{}</b> for this group.\n\n\
+                "Public access is currently <b>{}</b> for this group ({source}).\n\n\
                  <code>/public on</code> — Allow all members\n\
-                 <code>/public off</code> — Owner only",
+                 <code>/public off</code> — Owner only\n\
+                 <code>/public default</code> — Follow /defaultpublic",
                 status
             )
         }
-        _ => "Usage:\n<code>/public on</code> — Allow all group members\n<code>/public off</code> — Owner only".to_string(),
+        _ => "Usage:\n<code>/public on</code> — Allow all group members\n<code>/public off</code> — Owner only\n<code>/public default</code> — Follow /defaultpublic".to_string(),
     };
 
     shared_rate_limit_wait(state, chat_id).await;
@@ -908,3 +2924,244 @@ async fn handle_public_command(
 
     Ok(())
 }
+
+/// Handle /defaultpublic command - whether groups without their own /public setting are
+/// public (owner-only, bot-wide)
+async fn handle_defaultpublic_command(
+    bot: &Bot,
+    chat_id: ChatId,
+    text: &str,
+    state: &SharedState,
+    token: &str,
+    is_owner: bool,
+) -> ResponseResult<()> {
+    if !is_owner {
+        shared_rate_limit_wait(state, chat_id).await;
+        bot.send_message(
+            chat_id,
+            "Only the bot owner can change public access settings.",
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let arg = text
+        .strip_prefix("/defaultpublic")
+        .unwrap_or("")
+        .trim()
+        .to_lowercase();
+    let response_msg = match arg.as_str() {
+        "on" | "off" => {
+            let mut data = state.lock().await;
+            data.settings.default_public = arg == "on";
+            save_bot_settings(token, &data.settings);
+            if data.settings.default_public {
+                "Groups are now <b>public</b> by default: all members can use the bot in groups \
+                 without their own <code>/public</code> setting."
+                    .to_string()
+            } else {
+                "Groups are now <b>owner-only</b> by default. Groups set with \
+                 <code>/public on</code> stay public."
+                    .to_string()
+            }
+        }
+        "" => {
+            let data = state.lock().await;
+            let overrides = data
+                .settings
+                .as_public_for_group_chat
+                .values()
+                .filter(|public| **public != data.settings.default_public)
+                .count();
+            format!(
+                "Default for groups: <b>{}</b> ({overrides} group(s) set otherwise with /public).\n\n\
+                 <code>/defaultpublic on</code> — Groups are public unless set with /public off\n\
+                 <code>/defaultpublic off</code> — Groups are owner-only unless set with /public on",
+                if data.settings.default_public {
+                    "public"
+                } else {
+                    "owner-only"
+                }
+            )
+        }
+        _ => "Usage:\n<code>/defaultpublic on</code> — Groups are public by default\n<code>/defaultpublic off</code> — Groups are owner-only by default".to_string(),
+    };
+
+    shared_rate_limit_wait(state, chat_id).await;
+    bot.send_message(chat_id, &response_msg)
+        .parse_mode(ParseMode::Html)
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_interrupts_request() {
+        assert!(interrupts_request("/start"));
+        assert!(interrupts_request("/start ~/work"));
+        assert!(interrupts_request("/clear"));
+        // Longer commands sharing the prefix still wait for the request
+        assert!(!interrupts_request("/startpreview 10"));
+        assert!(!interrupts_request("/clearconfirm on"));
+        assert!(!interrupts_request("/pwd"));
+        assert!(!interrupts_request("start over"));
+    }
+
+    fn owner_dm() -> RouteContext {
+        RouteContext {
+            is_group: false,
+            is_public: false,
+            permission: auth::PermissionLevel::Owner,
+            forwarded: false,
+            busy: false,
+            approval: false,
+            shell: true,
+            forward_wait: false,
+        }
+    }
+
+    #[test]
+    fn test_strip_bot_mention() {
+        assert_eq!(strip_bot_mention("/pwd@mybot"), "/pwd");
+        assert_eq!(strip_bot_mention("/cd@mybot ~/work"), "/cd ~/work");
+        assert_eq!(strip_bot_mention("/cd ~/mail@host"), "/cd ~/mail@host");
+        assert_eq!(strip_bot_mention("/pwd"), "/pwd");
+        assert_eq!(
+            strip_bot_mention(";ask user@example.com"),
+            ";ask user@example.com"
+        );
+    }
+
+    #[test]
+    fn test_route_command_order() {
+        let context = owner_dm();
+        assert_eq!(
+            route_message("/allowedtools", &context),
+            RoutedAction::Command("/allowedtools")
+        );
+        assert_eq!(
+            route_message("/allowed +Bash", &context),
+            RoutedAction::Command("/allowed")
+        );
+        assert_eq!(
+            route_message("/startpreview 10", &context),
+            RoutedAction::Command("/startpreview")
+        );
+        assert_eq!(
+            route_message("/shell off", &context),
+            RoutedAction::Toggle(ChatToggle::Shell)
+        );
+        assert_eq!(route_message("/unknown", &context), RoutedAction::Prompt);
+
+        // No command is swallowed by a shorter one routed before it
+        for (i, earlier) in ROUTED_COMMANDS.iter().enumerate() {
+            for later in &ROUTED_COMMANDS[i + 1..] {
+                assert!(!later.starts_with(earlier), "{earlier} shadows {later}");
+            }
+        }
+        for toggle in ChatToggle::ALL {
+            let action = route_message(toggle.command(), &context);
+            assert!(
+                action == RoutedAction::Toggle(toggle)
+                    || action == RoutedAction::Command(toggle.command()),
+                "{} routed as {action:?}",
+                toggle.command()
+            );
+        }
+    }
+
+    #[test]
+    fn test_routed_commands_have_arms() {
+        let source = include_str!("commands.rs");
+        let start = source
+            .find("RoutedAction::Command(command) => match command {")
+            .unwrap_or(0);
+        let end = source[start..]
+            .find("RoutedAction::Toggle(toggle) =>")
+            .map_or(source.len(), |len| start + len);
+        let arms = &source[start..end];
+        for command in ROUTED_COMMANDS {
+            assert!(
+                arms.contains(&format!("\"{command}\" =>"))
+                    || arms.contains(&format!("\"{command}\" |")),
+                "{command} has no arm in handle_message"
+            );
+        }
+    }
+
+    #[test]
+    fn test_route_group_prefix_filter() {
+        let group = RouteContext {
+            is_group: true,
+            ..owner_dm()
+        };
+        assert_eq!(
+            route_message("hello everyone", &group),
+            RoutedAction::Ignore
+        );
+        assert_eq!(
+            route_message(";fix the build", &group),
+            RoutedAction::PrefixedPrompt { approval: false }
+        );
+        assert_eq!(
+            route_message("!ls", &group),
+            RoutedAction::Shell { enabled: true }
+        );
+        assert_eq!(route_message("/pwd", &group), RoutedAction::Command("/pwd"));
+        // Direct messages need no prefix
+        assert_eq!(route_message("hello", &owner_dm()), RoutedAction::Prompt);
+    }
+
+    #[test]
+    fn test_route_permission_and_busy() {
+        let member = RouteContext {
+            is_group: true,
+            is_public: true,
+            permission: auth::get_permission_level(2, Some(1), true, false),
+            ..owner_dm()
+        };
+        assert_eq!(route_message("!rm -rf /", &member), RoutedAction::Denied);
+        let approval = RouteContext {
+            approval: true,
+            ..member
+        };
+        assert_eq!(
+            route_message(";deploy", &approval),
+            RoutedAction::PrefixedPrompt { approval: true }
+        );
+
+        let busy = RouteContext {
+            busy: true,
+            ..owner_dm()
+        };
+        assert_eq!(route_message("/pwd", &busy), RoutedAction::Busy);
+        assert_eq!(
+            route_message("/stop", &busy),
+            RoutedAction::Command("/stop")
+        );
+        assert_eq!(
+            route_message("/clear", &busy),
+            RoutedAction::Command("/clear")
+        );
+        assert_eq!(
+            route_message(
+                "!ls",
+                &RouteContext {
+                    shell: false,
+                    ..owner_dm()
+                }
+            ),
+            RoutedAction::Shell { enabled: false }
+        );
+    }
+
+    #[test]
+    fn test_cancel_request_without_process() {
+        let token = CancelToken::new();
+        cancel_request(&token);
+        assert!(token.cancelled.load(Ordering::Relaxed));
+    }
+}
@@ -0,0 +1,58 @@
+use teloxide::prelude::*;
+use teloxide::types::{InputFile, ParseMode};
+
+use crate::console::{recent_lines, MAX_RECENT_LINES};
+
+use super::bot::{SharedState, TELEGRAM_MSG_LIMIT};
+use super::streaming::{html_escape, shared_rate_limit_wait};
+
+/// Lines shown by /log without an argument
+const DEFAULT_LOG_LINES: usize = 50;
+
+/// Handle /log command - show the latest console log lines of the running bot (owner-only)
+/// Usage: /log [n]   (default 50, at most 300; long output is sent as a file)
+pub(super) async fn handle_log_command(
+    bot: &Bot,
+    chat_id: ChatId,
+    text: &str,
+    state: &SharedState,
+) -> ResponseResult<()> {
+    let arg = text.strip_prefix("/log").unwrap_or("").trim();
+    let count = if arg.is_empty() {
+        Some(DEFAULT_LOG_LINES)
+    } else {
+        arg.parse::<usize>()
+            .ok()
+            .filter(|n| (1..=MAX_RECENT_LINES).contains(n))
+    };
+    let Some(count) = count else {
+        shared_rate_limit_wait(state, chat_id).await;
+        bot.send_message(
+            chat_id,
+            format!("Usage: /log [n]  (1-{MAX_RECENT_LINES}, default {DEFAULT_LOG_LINES})"),
+        )
+        .await?;
+        return Ok(());
+    };
+
+    let lines = recent_lines(count);
+    shared_rate_limit_wait(state, chat_id).await;
+    if lines.is_empty() {
+        bot.send_message(chat_id, "No console log lines yet.")
+            .await?;
+        return Ok(());
+    }
+    let content = lines.join("\n");
+    let body = format!("<pre>{}</pre>", html_escape(&content));
+    if body.len() > TELEGRAM_MSG_LIMIT {
+        let file = InputFile::memory(format!("{content}\n").into_bytes()).file_name("console.log");
+        bot.send_document(chat_id, file)
+            .caption(format!("Last {} console log line(s)", lines.len()))
+            .await?;
+    } else {
+        bot.send_message(chat_id, body)
+            .parse_mode(ParseMode::Html)
+            .await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,329 @@
+use std::path::Path;
+
+use teloxide::prelude::*;
+
+use crate::auth;
+use crate::i18n;
+
+use super::bot::SharedState;
+use super::diskusage::format_bytes;
+use super::file_ops::{looks_binary, resolve_chat_path};
+use super::streaming::shared_rate_limit_wait;
+
+/// Total size of all file contents staged for the next prompt
+const MAX_STAGED_CONTEXT_BYTES: usize = 128 * 1024;
+
+/// File contents as prepended to the prompt, labelled with the path
+fn context_block(path: &str, content: &str) -> String {
+    format!(
+        "[Context file] {path}\n--- BEGIN {path} ---\n{}\n--- END {path} ---",
+        content.trim_end()
+    )
+}
+
+/// Size of the staged blocks, not counting `replacing` (staging a file again replaces it)
+fn staged_bytes(staged: &[(String, String)], replacing: &str) -> usize {
+    staged
+        .iter()
+        .filter(|(path, _)| path != replacing)
+        .map(|(_, block)| block.len())
+        .sum()
+}
+
+/// Read a text file for staging, refusing binaries and anything over `budget` bytes
+fn read_context_file(path: &Path, budget: usize) -> Result<String, String> {
+    if !path.is_file() {
+        return Err(if path.exists() {
+            "Not a file".to_string()
+        } else {
+            "File not found".to_string()
+        });
+    }
+    let size = path.metadata().map_err(|e| e.to_string())?.len();
+    if size > budget as u64 {
+        return Err(format!(
+            "Too large: {} (room left: {} of {})",
+            format_bytes(size),
+            format_bytes(budget as u64),
+            format_bytes(MAX_STAGED_CONTEXT_BYTES as u64)
+        ));
+    }
+    let bytes = std::fs::read(path).map_err(|e| e.to_string())?;
+    if looks_binary(&bytes) {
+        return Err("Binary file".to_string());
+    }
+    String::from_utf8(bytes).map_err(|_| "Not UTF-8 text".to_string())
+}
+
+/// Most `@path` references inlined from one prompt
+const MAX_FILE_REFERENCES: usize = 10;
+
+/// `@path` tokens in a prompt: the token's byte range and the path it names.
+/// A token starts with `@` at the beginning or after whitespace and must contain `/` or `.`,
+/// so mentions like `@alice` are left alone; trailing punctuation is not part of the path.
+fn file_reference_tokens(prompt: &str) -> Vec<(std::ops::Range<usize>, &str)> {
+    let mut tokens = Vec::new();
+    let mut prev_is_space = true;
+    for (start, c) in prompt.char_indices() {
+        if c == '@' && prev_is_space {
+            let rest = &prompt[start + 1..];
+            let len = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            let path = rest[..len].trim_end_matches(|c: char| ",;:!?)]}\"'`".contains(c));
+            let path = path.strip_suffix('.').unwrap_or(path);
+            if path.contains(['/', '.']) && !path.contains('@') {
+                tokens.push((start..start + 1 + path.len(), path));
+            }
+        }
+        prev_is_space = c.is_whitespace();
+    }
+    tokens
+}
+
+/// Inline files referenced as `@path` from the session directory: each token is replaced
+/// by the plain path and the file's labelled contents are returned as blocks to prepend.
+/// Tokens naming no file are left as typed; files outside the session directory, binaries
+/// and anything over the shared size budget are skipped with a note for the user.
+/// Returns (prompt, blocks, notes).
+fn expand_file_references(prompt: &str, session_dir: &Path) -> (String, Vec<String>, Vec<String>) {
+    let mut expanded = String::with_capacity(prompt.len());
+    let mut blocks: Vec<String> = Vec::new();
+    let mut notes = Vec::new();
+    let mut attached: Vec<&str> = Vec::new();
+    let mut budget = MAX_STAGED_CONTEXT_BYTES;
+    let mut pos = 0;
+
+    for (range, path) in file_reference_tokens(prompt) {
+        let full = session_dir.join(path);
+        if !full.is_file() {
+            continue;
+        }
+        if !auth::is_path_within_sandbox(&full, session_dir) {
+            notes.push(format!("@{path}: outside the session folder"));
+            continue;
+        }
+        if !attached.contains(&path) {
+            if attached.len() >= MAX_FILE_REFERENCES {
+                notes.push(format!(
+                    "@{path}: at most {MAX_FILE_REFERENCES} files per message"
+                ));
+                continue;
+            }
+            match read_context_file(&full, budget) {
+                Ok(content) => {
+                    let block = context_block(path, &content);
+                    budget = budget.saturating_sub(block.len());
+                    blocks.push(block);
+                    attached.push(path);
+                }
+                Err(e) => {
+                    notes.push(format!("@{path}: {e}"));
+                    continue;
+                }
+            }
+        }
+        expanded.push_str(&prompt[pos..range.start]);
+        expanded.push_str(path);
+        pos = range.end;
+    }
+    expanded.push_str(&prompt[pos..]);
+    (expanded, blocks, notes)
+}
+
+/// Expand `@path` references in a prompt (see `expand_file_references`) and tell the user
+/// about references that were skipped. Returns the prompt and the file blocks to prepend.
+pub(super) async fn inline_file_references(
+    bot: &Bot,
+    chat_id: ChatId,
+    prompt: &str,
+    session_dir: &str,
+    state: &SharedState,
+) -> (String, Vec<String>) {
+    if file_reference_tokens(prompt).is_empty() {
+        return (prompt.to_string(), Vec::new());
+    }
+    let (owned_prompt, dir) = (prompt.to_string(), session_dir.to_string());
+    let (expanded, blocks, notes) =
+        tokio::task::spawn_blocking(move || expand_file_references(&owned_prompt, Path::new(&dir)))
+            .await
+            .unwrap_or_else(|_| (prompt.to_string(), Vec::new(), Vec::new()));
+
+    if !blocks.is_empty() {
+        let ts = chrono::Local::now().format("%H:%M:%S");
+        logln!("  [{ts}]   ⧉ Inlined {} referenced file(s)", blocks.len());
+    }
+    if !notes.is_empty() {
+        shared_rate_limit_wait(state, chat_id).await;
+        let _ = bot
+            .send_message(
+                chat_id,
+                format!(
+                    "Some @file references were not attached:\n{}",
+                    notes.join("\n")
+                ),
+            )
+            .await;
+    }
+    (expanded, blocks)
+}
+
+/// Handle /context command - stage file contents to prepend to the next prompt
+/// Usage: /context <file>   (add; repeat to stage several files)
+///        /context          (list staged files)
+///        /context clear
+pub(super) async fn handle_context_command(
+    bot: &Bot,
+    chat_id: ChatId,
+    text: &str,
+    state: &SharedState,
+) -> ResponseResult<()> {
+    let arg = text.strip_prefix("/context").unwrap_or("").trim();
+
+    let response_msg = match arg {
+        "" => {
+            let data = state.lock().await;
+            let staged = data
+                .sessions
+                .get(&chat_id)
+                .map(|s| s.pending_contexts.as_slice())
+                .unwrap_or_default();
+            if staged.is_empty() {
+                "No files staged. /context <file> adds a file's contents to your next prompt."
+                    .to_string()
+            } else {
+                let total: usize = staged.iter().map(|(_, block)| block.len()).sum();
+                let lines: Vec<String> = staged
+                    .iter()
+                    .map(|(path, block)| format!("• {path} ({})", format_bytes(block.len() as u64)))
+                    .collect();
+                format!(
+                    "Staged for the next prompt ({} of {}):\n{}\n\n/context clear — Remove all",
+                    format_bytes(total as u64),
+                    format_bytes(MAX_STAGED_CONTEXT_BYTES as u64),
+                    lines.join("\n")
+                )
+            }
+        }
+        "clear" => {
+            let mut data = state.lock().await;
+            let count = data
+                .sessions
+                .get_mut(&chat_id)
+                .map(|s| std::mem::take(&mut s.pending_contexts).len())
+                .unwrap_or(0);
+            format!("Removed {count} staged file(s).")
+        }
+        file => stage_context_file(chat_id, file, state).await,
+    };
+
+    shared_rate_limit_wait(state, chat_id).await;
+    bot.send_message(chat_id, response_msg).await?;
+    Ok(())
+}
+
+async fn stage_context_file(chat_id: ChatId, file: &str, state: &SharedState) -> String {
+    let Some(path) = resolve_chat_path(state, chat_id, file).await else {
+        return i18n::MSG_NO_SESSION.to_string();
+    };
+    let used = {
+        let data = state.lock().await;
+        data.sessions
+            .get(&chat_id)
+            .map(|s| staged_bytes(&s.pending_contexts, &path))
+            .unwrap_or(0)
+    };
+    let budget = MAX_STAGED_CONTEXT_BYTES.saturating_sub(used);
+    let read_path = path.clone();
+    let content =
+        tokio::task::spawn_blocking(move || read_context_file(Path::new(&read_path), budget))
+            .await
+            .unwrap_or_else(|e| Err(e.to_string()));
+    let block = match content {
+        Ok(content) => context_block(&path, &content),
+        Err(e) => return format!("{e}: {path}"),
+    };
+
+    let mut data = state.lock().await;
+    let Some(session) = data.sessions.get_mut(&chat_id) else {
+        return i18n::MSG_NO_SESSION.to_string();
+    };
+    // The label adds a little to the file size; re-check against what is staged now
+    if staged_bytes(&session.pending_contexts, &path) + block.len() > MAX_STAGED_CONTEXT_BYTES {
+        return format!(
+            "Staged context is full ({} max). /context clear to start over.",
+            format_bytes(MAX_STAGED_CONTEXT_BYTES as u64)
+        );
+    }
+    session.pending_contexts.retain(|(p, _)| *p != path);
+    let size = block.len();
+    session.pending_contexts.push((path.clone(), block));
+    format!(
+        "Staged {path} ({}). It will be included with your next prompt ({} file(s) staged).",
+        format_bytes(size as u64),
+        session.pending_contexts.len()
+    )
+}
+
+#[cfg(test)]
+#[allow(clippy::expect_used)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_context_file_limits() {
+        let dir = std::env::temp_dir().join(format!("opencodex_context_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).expect("create dir");
+        let text = dir.join("notes.txt");
+        std::fs::write(&text, "hello\n").expect("write text");
+        let binary = dir.join("blob.bin");
+        std::fs::write(&binary, [1u8, 0, 2]).expect("write binary");
+
+        assert_eq!(read_context_file(&text, 100), Ok("hello\n".to_string()));
+        assert!(read_context_file(&text, 3).is_err_and(|e| e.starts_with("Too large")));
+        assert_eq!(
+            read_context_file(&binary, 100),
+            Err("Binary file".to_string())
+        );
+        assert_eq!(
+            read_context_file(&dir.join("missing"), 100),
+            Err("File not found".to_string())
+        );
+        assert_eq!(
+            context_block("a.rs", "fn main() {}\n"),
+            "[Context file] a.rs\n--- BEGIN a.rs ---\nfn main() {}\n--- END a.rs ---"
+        );
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_file_reference_tokens() {
+        let prompt = "Review @src/main.rs, then @README.md. Ask @alice or mail a@b.c";
+        let paths: Vec<&str> = file_reference_tokens(prompt)
+            .into_iter()
+            .map(|(_, path)| path)
+            .collect();
+        assert_eq!(paths, vec!["src/main.rs", "README.md"]);
+    }
+
+    #[test]
+    fn test_expand_file_references() {
+        let root = std::env::temp_dir().join(format!("opencodex_refs_{}", std::process::id()));
+        let dir = root.join("project");
+        std::fs::create_dir_all(dir.join("src")).expect("create dir");
+        std::fs::write(dir.join("src/lib.rs"), "pub fn f() {}\n").expect("write lib");
+        std::fs::write(root.join("secret.txt"), "hidden").expect("write secret");
+
+        let (prompt, blocks, notes) = expand_file_references(
+            "Explain @src/lib.rs and @../secret.txt, not @missing.rs",
+            &dir,
+        );
+        assert_eq!(
+            prompt,
+            "Explain src/lib.rs and @../secret.txt, not @missing.rs"
+        );
+        assert_eq!(blocks, vec![context_block("src/lib.rs", "pub fn f() {}")]);
+        assert_eq!(notes, vec!["@../secret.txt: outside the session folder"]);
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
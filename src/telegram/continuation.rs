@@ -0,0 +1,161 @@
+use teloxide::prelude::*;
+use teloxide::types::{CallbackQuery, InlineKeyboardButton, InlineKeyboardMarkup};
+
+use crate::i18n;
+
+use super::bot::{is_owner, SharedState};
+use super::message::handle_text_message;
+use super::streaming::{floor_char_boundary, shared_rate_limit_wait};
+
+/// Callback data prefix routed to this module
+pub(super) const CONTINUE_CALLBACK_PREFIX: &str = "continue:";
+
+/// Telegram limits callback data to 64 bytes; longer session ids are shortened to fit
+const MAX_CALLBACK_SESSION_ID: usize = 64 - CONTINUE_CALLBACK_PREFIX.len();
+
+/// Prompt sent by /continue and the "Continue" button
+const CONTINUE_PROMPT: &str = "Continue exactly where your previous response stopped. \
+     Do not repeat or summarize what you already wrote.";
+
+/// Responses shorter than this are not treated as cut off unless a code block is left open
+const MIN_TRUNCATION_LEN: usize = 1000;
+
+/// Heuristic: does the response look like it stopped mid-output?
+/// True for an unclosed code fence, or a long response ending mid-sentence.
+pub(super) fn looks_truncated(response: &str) -> bool {
+    let trimmed = response.trim_end();
+    let open_fences = trimmed
+        .lines()
+        .filter(|l| l.trim_start().starts_with("```"))
+        .count();
+    if open_fences % 2 == 1 {
+        return true;
+    }
+    if trimmed.chars().count() < MIN_TRUNCATION_LEN {
+        return false;
+    }
+    trimmed
+        .chars()
+        .last()
+        .is_some_and(|c| c.is_ascii_alphanumeric() || matches!(c, ',' | '-' | '('))
+}
+
+/// Ask the backend to continue its last response in the current session.
+/// Requires a resumable session (one that has already produced a response).
+pub(super) async fn handle_continue_command(
+    bot: &Bot,
+    chat_id: ChatId,
+    state: &SharedState,
+) -> ResponseResult<()> {
+    let has_backend_session = {
+        let data = state.lock().await;
+        data.sessions
+            .get(&chat_id)
+            .is_some_and(|s| s.current_path.is_some() && s.session_id.is_some())
+    };
+    if !has_backend_session {
+        shared_rate_limit_wait(state, chat_id).await;
+        bot.send_message(chat_id, "Nothing to continue in this session.")
+            .await?;
+        return Ok(());
+    }
+
+    handle_text_message(bot, chat_id, CONTINUE_PROMPT, state).await
+}
+
+/// Offer a "Continue" button under a response that looks cut off. The button carries the
+/// backend session it continues, so it stops working once the chat moves to another one.
+pub(super) async fn offer_continue(
+    bot: &Bot,
+    chat_id: ChatId,
+    state: &SharedState,
+) -> ResponseResult<()> {
+    let session_id = {
+        let data = state.lock().await;
+        data.sessions
+            .get(&chat_id)
+            .and_then(|s| s.session_id.clone())
+    };
+    let Some(mut session_id) = session_id else {
+        return Ok(());
+    };
+    session_id.truncate(floor_char_boundary(&session_id, MAX_CALLBACK_SESSION_ID));
+    let keyboard = InlineKeyboardMarkup::new(vec![vec![InlineKeyboardButton::callback(
+        "▶ Continue",
+        format!("{CONTINUE_CALLBACK_PREFIX}{session_id}"),
+    )]]);
+    shared_rate_limit_wait(state, chat_id).await;
+    bot.send_message(chat_id, "The response looks cut off.")
+        .reply_markup(keyboard)
+        .await?;
+    Ok(())
+}
+
+/// Handle a "Continue" button press (owner-only). `session_id` is the backend session the
+/// button was offered for.
+pub(super) async fn handle_continue_callback(
+    bot: &Bot,
+    query: &CallbackQuery,
+    session_id: &str,
+    state: &SharedState,
+) -> ResponseResult<()> {
+    let Some(message) = query.message.as_ref() else {
+        bot.answer_callback_query(&query.id).await?;
+        return Ok(());
+    };
+    let chat_id = message.chat().id;
+
+    let rejection = {
+        let data = state.lock().await;
+        if !is_owner(&data.settings, query.from.id.0) {
+            Some("Only the bot owner can continue.")
+        } else if data.cancel_tokens.contains_key(&chat_id) {
+            Some(i18n::MSG_AI_BUSY)
+        } else if data
+            .sessions
+            .get(&chat_id)
+            .and_then(|s| s.session_id.as_deref())
+            .is_none_or(|current| session_id.is_empty() || !current.starts_with(session_id))
+        {
+            Some("This response belongs to an earlier session.")
+        } else {
+            None
+        }
+    };
+    if let Some(reason) = rejection {
+        bot.answer_callback_query(&query.id).text(reason).await?;
+        return Ok(());
+    }
+
+    bot.answer_callback_query(&query.id).await?;
+    // Drop the button so the same continuation cannot be triggered twice
+    shared_rate_limit_wait(state, chat_id).await;
+    if let Err(e) = bot
+        .edit_message_text(chat_id, message.id(), "▶ Continuing...")
+        .await
+    {
+        let ts = chrono::Local::now().format("%H:%M:%S");
+        logln!("  [{ts}]   ⚠ edit_message failed (continue): {e}");
+    }
+
+    let ts = chrono::Local::now().format("%H:%M:%S");
+    logln!("  [{ts}] ◀ [button] /continue");
+    handle_continue_command(bot, chat_id, state).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_looks_truncated() {
+        assert!(!looks_truncated("Done."));
+        assert!(looks_truncated("Here:\n```rust\nfn main() {"));
+        assert!(!looks_truncated("Here:\n```rust\nfn main() {}\n```"));
+
+        let long = "word ".repeat(300);
+        assert!(looks_truncated(&format!("{long}and then the")));
+        assert!(!looks_truncated(&format!("{long}finished.")));
+        assert!(!looks_truncated(&format!("{long}완료했습니다")));
+    }
+}
@@ -0,0 +1,322 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::time::{Duration, Instant};
+
+use teloxide::prelude::*;
+
+use super::bot::{is_owner, SharedState};
+use super::storage::save_bot_settings;
+use super::streaming::shared_rate_limit_wait;
+
+/// Longest cooldown /cooldown and /cmdcooldown accept (one day)
+const MAX_COOLDOWN_SECS: u64 = 24 * 60 * 60;
+
+/// Commands that cannot be put on a cooldown: stopping a request must always work, and
+/// /cmdcooldown itself is needed to lift one
+const NO_COOLDOWN_COMMANDS: &[&str] = &["/stop", "/help", "/cmdcooldown"];
+
+/// Longest command name /cmdcooldown accepts, including the slash (Telegram allows 32)
+const MAX_COMMAND_NAME_LEN: usize = 33;
+
+/// The command a message invokes, without a `@botname` suffix
+fn command_name(text: &str) -> Option<&str> {
+    let first = text.split_whitespace().next()?;
+    let name = first.split('@').next().unwrap_or(first);
+    (name.len() > 1 && name.starts_with('/')).then_some(name)
+}
+
+/// Normalize a command given to /cmdcooldown (`summary` or `/summary`)
+fn parse_cooldown_command(arg: &str) -> Result<String, String> {
+    let name = format!("/{}", arg.trim_start_matches('/').to_lowercase());
+    if name.len() < 2
+        || name.len() > MAX_COMMAND_NAME_LEN
+        || !name[1..]
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_')
+    {
+        return Err(format!("Invalid command name: {arg}"));
+    }
+    if NO_COOLDOWN_COMMANDS.contains(&name.as_str()) {
+        return Err(format!("{name} cannot have a cooldown."));
+    }
+    Ok(name)
+}
+
+/// Time left before a user whose last AI request started at `last` may start another
+fn cooldown_remaining(last: Option<Instant>, cooldown: Duration, now: Instant) -> Option<Duration> {
+    let ready_at = last? + cooldown;
+    (ready_at > now).then(|| ready_at - now)
+}
+
+/// Forget uses older than the longest possible cooldown; they can no longer refuse anything
+fn prune_expired<K: Eq + Hash>(last_used: &mut HashMap<K, Instant>, now: Instant) {
+    let max = Duration::from_secs(MAX_COOLDOWN_SECS);
+    last_used.retain(|_, last| now.duration_since(*last) < max);
+}
+
+/// Enforce this chat's per-user cooldown (/cooldown) before a prompt starts an AI request.
+/// Records the request when allowed; the owner is exempt. Returns true if the prompt was refused.
+pub(super) async fn refuse_if_cooling_down(
+    bot: &Bot,
+    chat_id: ChatId,
+    user_id: u64,
+    state: &SharedState,
+) -> ResponseResult<bool> {
+    let remaining = {
+        let mut data = state.lock().await;
+        if is_owner(&data.settings, user_id) {
+            return Ok(false);
+        }
+        let Some(&secs) = data.settings.request_cooldowns.get(&chat_id.0.to_string()) else {
+            return Ok(false);
+        };
+        let key = (chat_id, user_id);
+        let now = Instant::now();
+        let last = data.user_last_requests.get(&key).copied();
+        let remaining = cooldown_remaining(last, Duration::from_secs(secs), now);
+        if remaining.is_none() {
+            prune_expired(&mut data.user_last_requests, now);
+            data.user_last_requests.insert(key, now);
+        }
+        remaining
+    };
+    let Some(left) = remaining else {
+        return Ok(false);
+    };
+    shared_rate_limit_wait(state, chat_id).await;
+    bot.send_message(
+        chat_id,
+        format!(
+            "Please wait {}s before sending another request.",
+            left.as_secs().max(1)
+        ),
+    )
+    .await?;
+    Ok(true)
+}
+
+/// Enforce the bot-wide per-command cooldown (/cmdcooldown) before a command runs.
+/// Tracked per chat and command; the owner is exempt unless `/cmdcooldown owner on`.
+/// Records the use when allowed. Returns true if the command was refused.
+pub(super) async fn refuse_if_command_cooling_down(
+    bot: &Bot,
+    chat_id: ChatId,
+    user_id: u64,
+    text: &str,
+    state: &SharedState,
+) -> ResponseResult<bool> {
+    let Some(command) = command_name(text) else {
+        return Ok(false);
+    };
+    let remaining = {
+        let mut data = state.lock().await;
+        let Some(&secs) = data.settings.command_cooldowns.get(command) else {
+            return Ok(false);
+        };
+        if is_owner(&data.settings, user_id) && !data.settings.command_cooldowns_include_owner {
+            return Ok(false);
+        }
+        let key = (chat_id, command.to_string());
+        let now = Instant::now();
+        let last = data.command_last_used.get(&key).copied();
+        let remaining = cooldown_remaining(last, Duration::from_secs(secs), now);
+        if remaining.is_none() {
+            prune_expired(&mut data.command_last_used, now);
+            data.command_last_used.insert(key, now);
+        }
+        remaining
+    };
+    let Some(left) = remaining else {
+        return Ok(false);
+    };
+    shared_rate_limit_wait(state, chat_id).await;
+    bot.send_message(
+        chat_id,
+        format!(
+            "{command} was used recently in this chat. Try again in {}s.",
+            left.as_secs().max(1)
+        ),
+    )
+    .await?;
+    Ok(true)
+}
+
+/// Handle /cmdcooldown command - minimum interval between uses of a command in each chat
+/// Usage: /cmdcooldown                      (list)
+///        /cmdcooldown <command> <seconds>
+///        /cmdcooldown <command> off
+///        /cmdcooldown owner on|off         (whether the owner is limited too)
+pub(super) async fn handle_cmdcooldown_command(
+    bot: &Bot,
+    chat_id: ChatId,
+    text: &str,
+    state: &SharedState,
+    token: &str,
+) -> ResponseResult<()> {
+    let arg = text.strip_prefix("/cmdcooldown").unwrap_or("").trim();
+    let usage = format!(
+        "/cmdcooldown <command> <seconds> — Each chat can run the command at most once per \
+         interval (max {MAX_COOLDOWN_SECS})\n/cmdcooldown <command> off — Remove\n\
+         /cmdcooldown owner on|off — Also limit the owner (default off)"
+    );
+    let parts: Vec<&str> = arg.split_whitespace().collect();
+
+    let response_msg = {
+        let mut data = state.lock().await;
+        match parts.as_slice() {
+            [] => {
+                let mut entries: Vec<(&String, &u64)> =
+                    data.settings.command_cooldowns.iter().collect();
+                entries.sort();
+                let owner = if data.settings.command_cooldowns_include_owner {
+                    "The owner is limited too."
+                } else {
+                    "The owner is exempt."
+                };
+                if entries.is_empty() {
+                    format!("No command cooldowns.\n\n{usage}")
+                } else {
+                    let lines: Vec<String> = entries
+                        .iter()
+                        .map(|(command, secs)| format!("{command} — {secs}s"))
+                        .collect();
+                    format!(
+                        "Command cooldowns (per chat):\n{}\n{owner}\n\n{usage}",
+                        lines.join("\n")
+                    )
+                }
+            }
+            ["owner", value @ ("on" | "off")] => {
+                data.settings.command_cooldowns_include_owner = *value == "on";
+                save_bot_settings(token, &data.settings);
+                if *value == "on" {
+                    "Command cooldowns now apply to the owner too.".to_string()
+                } else {
+                    "The owner is exempt from command cooldowns.".to_string()
+                }
+            }
+            [command, value] => match parse_cooldown_command(command) {
+                Err(e) => e,
+                Ok(command) if matches!(*value, "off" | "0") => {
+                    if data.settings.command_cooldowns.remove(&command).is_some() {
+                        save_bot_settings(token, &data.settings);
+                    }
+                    data.command_last_used
+                        .retain(|(_, used), _| *used != command);
+                    format!("Cooldown for {command} removed.")
+                }
+                Ok(command) => match value.parse::<u64>() {
+                    Ok(secs) if secs <= MAX_COOLDOWN_SECS => {
+                        data.settings
+                            .command_cooldowns
+                            .insert(command.clone(), secs);
+                        save_bot_settings(token, &data.settings);
+                        format!("{command} can now run once every {secs}s in each chat.")
+                    }
+                    _ => format!("Invalid cooldown: {value}\n\n{usage}"),
+                },
+            },
+            _ => usage,
+        }
+    };
+
+    shared_rate_limit_wait(state, chat_id).await;
+    bot.send_message(chat_id, response_msg).await?;
+    Ok(())
+}
+
+/// Handle /cooldown command - minimum interval between AI requests per user in this chat
+/// Usage: /cooldown           (show)
+///        /cooldown <seconds>
+///        /cooldown off
+pub(super) async fn handle_cooldown_command(
+    bot: &Bot,
+    chat_id: ChatId,
+    text: &str,
+    state: &SharedState,
+    token: &str,
+) -> ResponseResult<()> {
+    let arg = text.strip_prefix("/cooldown").unwrap_or("").trim();
+    let chat_key = chat_id.0.to_string();
+    let usage = format!(
+        "/cooldown <seconds> — Each user (except the owner) can start an AI request at most \
+         once per interval (max {MAX_COOLDOWN_SECS})\n/cooldown off — Remove"
+    );
+
+    let response_msg = {
+        let mut data = state.lock().await;
+        match arg {
+            "" => match data.settings.request_cooldowns.get(&chat_key) {
+                Some(secs) => format!("Request cooldown: {secs}s per user.\n\n{usage}"),
+                None => format!("No request cooldown in this chat.\n\n{usage}"),
+            },
+            "off" | "0" => {
+                if data.settings.request_cooldowns.remove(&chat_key).is_some() {
+                    save_bot_settings(token, &data.settings);
+                }
+                "Request cooldown removed.".to_string()
+            }
+            _ => match arg.parse::<u64>() {
+                Ok(secs) if secs <= MAX_COOLDOWN_SECS => {
+                    data.settings.request_cooldowns.insert(chat_key, secs);
+                    save_bot_settings(token, &data.settings);
+                    format!("Each user can now start an AI request once every {secs}s.")
+                }
+                _ => format!("Invalid cooldown: {arg}\n\n{usage}"),
+            },
+        }
+    };
+
+    shared_rate_limit_wait(state, chat_id).await;
+    bot.send_message(chat_id, response_msg).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cooldown_remaining() {
+        let start = Instant::now();
+        let cooldown = Duration::from_secs(30);
+        assert_eq!(cooldown_remaining(None, cooldown, start), None);
+        assert_eq!(
+            cooldown_remaining(Some(start), cooldown, start + Duration::from_secs(10)),
+            Some(Duration::from_secs(20))
+        );
+        assert_eq!(
+            cooldown_remaining(Some(start), cooldown, start + cooldown),
+            None
+        );
+    }
+
+    #[test]
+    fn test_prune_expired() {
+        let now = Instant::now() + Duration::from_secs(MAX_COOLDOWN_SECS + 10);
+        let mut last_used = HashMap::from([
+            ((ChatId(1), 7), now - Duration::from_secs(MAX_COOLDOWN_SECS)),
+            ((ChatId(2), 7), now - Duration::from_secs(60)),
+        ]);
+        prune_expired(&mut last_used, now);
+        assert_eq!(last_used.keys().collect::<Vec<_>>(), vec![&(ChatId(2), 7)]);
+    }
+
+    #[test]
+    fn test_command_names() {
+        assert_eq!(command_name("/summary@my_bot now"), Some("/summary"));
+        assert_eq!(command_name("/search all foo"), Some("/search"));
+        assert_eq!(command_name("hello"), None);
+        assert_eq!(command_name("/"), None);
+
+        assert_eq!(
+            parse_cooldown_command("Summary"),
+            Ok("/summary".to_string())
+        );
+        assert_eq!(parse_cooldown_command("/backup"), Ok("/backup".to_string()));
+        assert!(parse_cooldown_command("/stop").is_err());
+        assert!(parse_cooldown_command("/cmdcooldown").is_err());
+        assert!(parse_cooldown_command("/a-b").is_err());
+        assert!(parse_cooldown_command("/").is_err());
+    }
+}
@@ -0,0 +1,320 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Local};
+use croner::Cron;
+use serde::{Deserialize, Serialize};
+use teloxide::prelude::*;
+use teloxide::types::ParseMode;
+
+use super::bot::SharedState;
+use super::commands::restore_session_if_missing;
+use super::message::handle_text_message;
+use super::storage::save_cron_jobs;
+use super::streaming::{html_escape, shared_rate_limit_wait, truncate_str};
+
+/// How often the scheduler checks for due jobs
+const CRON_TICK: Duration = Duration::from_secs(30);
+
+/// Maximum number of recurring jobs per chat
+const MAX_CRON_JOBS_PER_CHAT: usize = 20;
+
+/// A recurring prompt registered via /cron, persisted in cron_jobs.json
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(super) struct CronJob {
+    pub id: u64,
+    pub chat_id: i64,
+    /// Standard 5-field cron expression (minute hour day month weekday), local time
+    pub schedule: String,
+    pub prompt: String,
+    pub created_at: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_run: Option<String>,
+}
+
+/// Parse and validate a cron expression
+fn parse_schedule(expr: &str) -> Result<Cron, String> {
+    if expr.split_whitespace().count() != 5 {
+        return Err("expected 5 fields: minute hour day month weekday".to_string());
+    }
+    Cron::new(expr).parse().map_err(|e| e.to_string())
+}
+
+/// Split `/cron` add arguments into (schedule, prompt).
+/// Accepts a quoted schedule (`"0 9 * * *" prompt`) or five bare fields (`0 9 * * * prompt`).
+pub(super) fn parse_cron_args(args: &str) -> Result<(String, String), String> {
+    let args = args.trim();
+    let (schedule, prompt) = if let Some(rest) = args.strip_prefix('"') {
+        let (schedule, prompt) = rest
+            .split_once('"')
+            .ok_or("missing closing quote around the schedule")?;
+        (schedule.trim().to_string(), prompt.trim().to_string())
+    } else {
+        let mut parts = args.splitn(6, char::is_whitespace);
+        let fields: Vec<&str> = parts.by_ref().take(5).collect();
+        (
+            fields.join(" "),
+            parts.next().unwrap_or("").trim().to_string(),
+        )
+    };
+
+    parse_schedule(&schedule)?;
+    if prompt.is_empty() {
+        return Err("prompt cannot be empty".to_string());
+    }
+    Ok((schedule, prompt))
+}
+
+/// True if `schedule` has an occurrence in `(after, now]`
+pub(super) fn is_due(schedule: &str, after: &DateTime<Local>, now: &DateTime<Local>) -> bool {
+    let Ok(cron) = parse_schedule(schedule) else {
+        return false;
+    };
+    cron.find_next_occurrence(after, false)
+        .map(|next| next <= *now)
+        .unwrap_or(false)
+}
+
+/// Next run time of a schedule, formatted for display
+fn next_run_display(schedule: &str) -> String {
+    parse_schedule(schedule)
+        .and_then(|cron| {
+            cron.find_next_occurrence(&Local::now(), false)
+                .map_err(|e| e.to_string())
+        })
+        .map(|next| next.format("%Y-%m-%d %H:%M").to_string())
+        .unwrap_or_else(|_| "-".to_string())
+}
+
+/// Handle /cron command - add, list or remove recurring prompts for this chat
+pub(super) async fn handle_cron_command(
+    bot: &Bot,
+    chat_id: ChatId,
+    text: &str,
+    state: &SharedState,
+    token: &str,
+) -> ResponseResult<()> {
+    let arg = text.strip_prefix("/cron").unwrap_or("").trim();
+    let (sub, rest) = arg.split_once(char::is_whitespace).unwrap_or((arg, ""));
+
+    let response_msg = match sub {
+        "" | "list" => {
+            let data = state.lock().await;
+            let jobs: Vec<&CronJob> = data
+                .cron_jobs
+                .iter()
+                .filter(|j| j.chat_id == chat_id.0)
+                .collect();
+            if jobs.is_empty() {
+                "No recurring jobs.\n\nUsage: <code>/cron \"0 9 * * *\" &lt;prompt&gt;</code>"
+                    .to_string()
+            } else {
+                let mut msg = format!("<b>Recurring jobs</b> ({})\n", jobs.len());
+                for job in jobs {
+                    msg.push_str(&format!(
+                        "\n<b>#{}</b> <code>{}</code> — next: {}\n  {}",
+                        job.id,
+                        html_escape(&job.schedule),
+                        next_run_display(&job.schedule),
+                        html_escape(&truncate_str(&job.prompt, 200))
+                    ));
+                    if let Some(last_run) = &job.last_run {
+                        msg.push_str(&format!("\n  last run: {}", html_escape(last_run)));
+                    }
+                }
+                msg.push_str("\n\nRemove with <code>/cron remove &lt;id&gt;</code>");
+                msg
+            }
+        }
+        "remove" | "rm" | "del" => match rest.trim().trim_start_matches('#').parse::<u64>() {
+            Err(_) => "Usage: <code>/cron remove &lt;id&gt;</code>".to_string(),
+            Ok(id) => {
+                let mut data = state.lock().await;
+                let before = data.cron_jobs.len();
+                data.cron_jobs
+                    .retain(|j| !(j.id == id && j.chat_id == chat_id.0));
+                if data.cron_jobs.len() < before {
+                    save_cron_jobs(token, &data.cron_jobs);
+                    format!("Removed job #{id}.")
+                } else {
+                    format!("No job #{id} in this chat.")
+                }
+            }
+        },
+        _ => match parse_cron_args(arg) {
+            Err(e) => format!(
+                "Invalid job: {}\n\nUsage: <code>/cron \"0 9 * * *\" &lt;prompt&gt;</code>",
+                html_escape(&e)
+            ),
+            Ok((schedule, prompt)) => {
+                let mut data = state.lock().await;
+                let count = data
+                    .cron_jobs
+                    .iter()
+                    .filter(|j| j.chat_id == chat_id.0)
+                    .count();
+                if count >= MAX_CRON_JOBS_PER_CHAT {
+                    format!("Too many jobs (max {MAX_CRON_JOBS_PER_CHAT} per chat).")
+                } else {
+                    let id = data.cron_jobs.iter().map(|j| j.id).max().unwrap_or(0) + 1;
+                    let next = next_run_display(&schedule);
+                    data.cron_jobs.push(CronJob {
+                        id,
+                        chat_id: chat_id.0,
+                        schedule: schedule.clone(),
+                        prompt,
+                        created_at: Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+                        last_run: None,
+                    });
+                    save_cron_jobs(token, &data.cron_jobs);
+                    format!(
+                        "Added job <b>#{id}</b> <code>{}</code>\nNext run: {next}",
+                        html_escape(&schedule)
+                    )
+                }
+            }
+        },
+    };
+
+    shared_rate_limit_wait(state, chat_id).await;
+    bot.send_message(chat_id, &response_msg)
+        .parse_mode(ParseMode::Html)
+        .await?;
+
+    Ok(())
+}
+
+/// Run one due job: restore the chat's session if needed, then send the prompt to the AI
+/// as if the owner had typed it. Skipped (with a notice) while the chat is busy.
+async fn run_cron_job(
+    bot: &Bot,
+    state: &SharedState,
+    token: &str,
+    default_project_dir: &str,
+    job: CronJob,
+) -> ResponseResult<()> {
+    let chat_id = ChatId(job.chat_id);
+    let ts = Local::now().format("%H:%M:%S");
+
+    let (busy, has_session, moved) = {
+        let mut data = state.lock().await;
+        let moved = restore_session_if_missing(&mut data, chat_id, default_project_dir)
+            .and_then(|restored| Some((restored.missing?, restored.path)));
+        let busy = data.cancel_tokens.contains_key(&chat_id);
+        let has_session = data
+            .sessions
+            .get(&chat_id)
+            .is_some_and(|s| s.current_path.is_some());
+        if !busy && has_session {
+            if let Some(stored) = data.cron_jobs.iter_mut().find(|j| j.id == job.id) {
+                stored.last_run = Some(Local::now().format("%Y-%m-%d %H:%M:%S").to_string());
+            }
+            save_cron_jobs(token, &data.cron_jobs);
+        }
+        (busy, has_session, moved)
+    };
+
+    if busy || !has_session {
+        let reason = if busy { "AI is busy" } else { "no session" };
+        logln!("  [{ts}] ⏰ Cron job #{} skipped ({reason})", job.id);
+        shared_rate_limit_wait(state, chat_id).await;
+        bot.send_message(
+            chat_id,
+            format!("⏰ Skipped job #{} ({reason}): {}", job.id, job.prompt),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    logln!(
+        "  [{ts}] ⏰ Cron job #{}: {}",
+        job.id,
+        truncate_str(&job.prompt, 60)
+    );
+    // The remembered folder is gone: say where the job runs instead
+    let moved_note = moved
+        .map(|(missing, path)| {
+            format!("\n(The last working folder {missing} no longer exists; running in {path}.)")
+        })
+        .unwrap_or_default();
+    shared_rate_limit_wait(state, chat_id).await;
+    bot.send_message(
+        chat_id,
+        format!("⏰ Job #{}: {}{moved_note}", job.id, job.prompt),
+    )
+    .await?;
+    handle_text_message(bot, chat_id, &job.prompt, state).await
+}
+
+/// Spawn the background scheduler that fires due /cron jobs.
+/// Occurrences missed while the bot was not running are not replayed.
+pub(super) fn spawn_cron_scheduler(
+    bot: Bot,
+    state: SharedState,
+    token: String,
+    default_project_dir: String,
+) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(CRON_TICK);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+        let mut last_tick = Local::now();
+
+        loop {
+            interval.tick().await;
+            let now = Local::now();
+            let due: Vec<CronJob> = {
+                let data = state.lock().await;
+                data.cron_jobs
+                    .iter()
+                    .filter(|j| is_due(&j.schedule, &last_tick, &now))
+                    .cloned()
+                    .collect()
+            };
+            last_tick = now;
+
+            for job in due {
+                let id = job.id;
+                if let Err(e) = run_cron_job(&bot, &state, &token, &default_project_dir, job).await
+                {
+                    let ts = Local::now().format("%H:%M:%S");
+                    logln!("  [{ts}]   ⚠ Cron job #{id} failed: {e}");
+                }
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_parse_cron_args() {
+        assert_eq!(
+            parse_cron_args("\"0 9 * * *\" check deps"),
+            Ok(("0 9 * * *".to_string(), "check deps".to_string()))
+        );
+        assert_eq!(
+            parse_cron_args("*/15 * * * 1-5 run report"),
+            Ok(("*/15 * * * 1-5".to_string(), "run report".to_string()))
+        );
+        assert!(parse_cron_args("\"0 9 * * *\"").is_err());
+        assert!(parse_cron_args("\"0 9 * *\" prompt").is_err());
+        assert!(parse_cron_args("\"61 9 * * *\" prompt").is_err());
+        assert!(parse_cron_args("\"0 9 * * * prompt").is_err());
+    }
+
+    #[test]
+    fn test_is_due_window() {
+        let at = |h, m, s| {
+            Local
+                .with_ymd_and_hms(2025, 3, 10, h, m, s)
+                .single()
+                .unwrap_or_else(Local::now)
+        };
+        assert!(is_due("0 9 * * *", &at(8, 59, 50), &at(9, 0, 20)));
+        assert!(!is_due("0 9 * * *", &at(9, 0, 20), &at(9, 0, 50)));
+        assert!(!is_due("0 9 * * *", &at(8, 0, 0), &at(8, 59, 59)));
+        assert!(!is_due("not a cron", &at(8, 0, 0), &at(10, 0, 0)));
+    }
+}
@@ -0,0 +1,180 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use teloxide::prelude::*;
+use teloxide::types::ParseMode;
+
+use crate::session::ai_sessions_dir;
+
+use super::bot::SharedState;
+use super::streaming::{html_escape, shared_rate_limit_wait};
+
+/// Stop scanning after this many directory entries
+const MAX_SCAN_ENTRIES: usize = 20_000;
+
+/// Directory levels below the scanned root that are visited
+const MAX_SCAN_DEPTH: usize = 4;
+
+/// Number of largest session files listed
+const TOP_FILES: usize = 5;
+
+/// Size totals of a directory tree (symlinks are not followed)
+#[derive(Debug, Default)]
+struct DirUsage {
+    bytes: u64,
+    files: usize,
+    /// True if the entry or depth limit cut the scan short
+    truncated: bool,
+    /// Largest files, biggest first
+    largest: Vec<(PathBuf, u64)>,
+}
+
+fn scan_usage(root: &Path, top: usize) -> DirUsage {
+    let mut usage = DirUsage::default();
+    let mut stack = vec![(root.to_path_buf(), 0)];
+    let mut seen = 0;
+
+    while let Some((dir, depth)) = stack.pop() {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            seen += 1;
+            if seen > MAX_SCAN_ENTRIES {
+                usage.truncated = true;
+                return usage;
+            }
+            let Ok(meta) = fs::symlink_metadata(entry.path()) else {
+                continue;
+            };
+            if meta.is_dir() {
+                if depth < MAX_SCAN_DEPTH {
+                    stack.push((entry.path(), depth + 1));
+                } else {
+                    usage.truncated = true;
+                }
+            } else if meta.is_file() {
+                usage.bytes += meta.len();
+                usage.files += 1;
+                usage.largest.push((entry.path(), meta.len()));
+                usage
+                    .largest
+                    .sort_by_key(|(_, size)| std::cmp::Reverse(*size));
+                usage.largest.truncate(top);
+            }
+        }
+    }
+    usage
+}
+
+pub(super) fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{value:.1} {}", UNITS[unit])
+}
+
+/// Handle /diskusage command - report space used by sessions and the app directory
+pub(super) async fn handle_diskusage_command(
+    bot: &Bot,
+    chat_id: ChatId,
+    state: &SharedState,
+) -> ResponseResult<()> {
+    let Some(sessions_dir) = ai_sessions_dir() else {
+        shared_rate_limit_wait(state, chat_id).await;
+        bot.send_message(chat_id, "Cannot determine the home directory.")
+            .await?;
+        return Ok(());
+    };
+    let app_dir = sessions_dir
+        .parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|| sessions_dir.clone());
+
+    let scan_dirs = (sessions_dir.clone(), app_dir.clone());
+    let (sessions, app) = tokio::task::spawn_blocking(move || {
+        (
+            scan_usage(&scan_dirs.0, TOP_FILES),
+            scan_usage(&scan_dirs.1, 0),
+        )
+    })
+    .await
+    .unwrap_or_default();
+
+    let partial = |usage: &DirUsage| {
+        if usage.truncated {
+            " (partial scan)"
+        } else {
+            ""
+        }
+    };
+    let mut msg = format!(
+        "<b>Disk usage</b>\n\n\
+         Sessions: <b>{}</b> in {} files{}\n<code>{}</code>\n\n\
+         App directory total: <b>{}</b> in {} files{}\n<code>{}</code>",
+        format_bytes(sessions.bytes),
+        sessions.files,
+        partial(&sessions),
+        html_escape(&sessions_dir.display().to_string()),
+        format_bytes(app.bytes),
+        app.files,
+        partial(&app),
+        html_escape(&app_dir.display().to_string()),
+    );
+    if !sessions.largest.is_empty() {
+        msg.push_str("\n\n<b>Largest session files</b>");
+        for (path, size) in &sessions.largest {
+            let name = path
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default();
+            msg.push_str(&format!(
+                "\n{} — <code>{}</code>",
+                format_bytes(*size),
+                html_escape(&name)
+            ));
+        }
+    }
+
+    shared_rate_limit_wait(state, chat_id).await;
+    bot.send_message(chat_id, msg)
+        .parse_mode(ParseMode::Html)
+        .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scan_usage_and_format() {
+        let root = std::env::temp_dir().join("opencodex_test_diskusage");
+        let _ = fs::remove_dir_all(&root);
+        let _ = fs::create_dir_all(root.join("sub"));
+        let _ = fs::write(root.join("a.json"), vec![b'a'; 300]);
+        let _ = fs::write(root.join("sub").join("b.json"), vec![b'b'; 2000]);
+        let _ = fs::write(root.join("c.json"), vec![b'c'; 10]);
+
+        let usage = scan_usage(&root, 2);
+        assert_eq!(usage.bytes, 2310);
+        assert_eq!(usage.files, 3);
+        assert!(!usage.truncated);
+        let sizes: Vec<u64> = usage.largest.iter().map(|(_, s)| *s).collect();
+        assert_eq!(sizes, vec![2000, 300]);
+
+        assert_eq!(format_bytes(512), "512 B");
+        assert_eq!(format_bytes(1536), "1.5 KB");
+        assert_eq!(format_bytes(5 * 1024 * 1024 * 1024), "5.0 GB");
+
+        let _ = fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,183 @@
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use teloxide::prelude::*;
+
+use crate::codex;
+
+use super::bot::{get_allowed_tools, SharedData, SharedState};
+use super::file_ops::{SHELL_OUTPUT_LIMIT, SHELL_TIMEOUT};
+use super::project_config::PROJECT_CONFIG_FILE;
+use super::settings::ChatToggle;
+use super::streaming::{shared_rate_limit_wait, MIN_SEND_GAP};
+
+/// Where the bot token was found at startup (flag, env var, file or config.json)
+static TOKEN_SOURCE: OnceLock<&'static str> = OnceLock::new();
+
+pub fn configure_token_source(source: &'static str) {
+    let _ = TOKEN_SOURCE.set(source);
+}
+
+/// One reported setting: its effective value and where that value came from
+struct Entry {
+    name: &'static str,
+    value: String,
+    source: String,
+}
+
+impl Entry {
+    fn new(name: &'static str, value: impl Into<String>, source: impl Into<String>) -> Self {
+        Entry {
+            name,
+            value: value.into(),
+            source: source.into(),
+        }
+    }
+}
+
+/// Bot id part of the token only; the secret after the colon is never shown
+fn redact_token(token: &str) -> String {
+    match token.split_once(':') {
+        Some((bot_id, _)) => format!("{bot_id}:***"),
+        None => "***".to_string(),
+    }
+}
+
+fn format_secs(duration: Duration) -> String {
+    format!("{}s", duration.as_secs_f64())
+}
+
+/// Effective settings for the chat, each with its source
+fn collect_entries(data: &SharedData, chat_id: ChatId, token: &str) -> Vec<Entry> {
+    let chat_key = chat_id.0.to_string();
+    let project = data
+        .sessions
+        .get(&chat_id)
+        .and_then(|s| s.project_config.as_ref());
+    let mut entries = Vec::new();
+
+    entries.push(Entry::new(
+        "token",
+        redact_token(token),
+        TOKEN_SOURCE.get().copied().unwrap_or("unknown"),
+    ));
+    entries.push(if codex::uses_omx() {
+        Entry::new("backend", "omx", "--omx")
+    } else {
+        Entry::new("backend", "codex", "default")
+    });
+
+    let madmax = ChatToggle::Madmax.is_enabled(&data.settings, chat_id);
+    let madmax_source = if data.settings.madmax.contains_key(&chat_key) {
+        "/madmax in this chat"
+    } else if codex::is_madmax() {
+        "--madmax"
+    } else {
+        "default"
+    };
+    entries.push(Entry::new(
+        "madmax",
+        if madmax { "on" } else { "off" },
+        madmax_source,
+    ));
+
+    entries.push(match project.and_then(|c| c.model.as_deref()) {
+        Some(model) => Entry::new("model", model, PROJECT_CONFIG_FILE),
+        None => Entry::new("model", "backend default", "default"),
+    });
+
+    let tools_source = if data.settings.allowed_tools.contains_key(&chat_key) {
+        "/allowed in this chat"
+    } else if project.is_some_and(|c| c.allowed_tools.is_some()) {
+        PROJECT_CONFIG_FILE
+    } else {
+        "default"
+    };
+    entries.push(Entry::new(
+        "allowed_tools",
+        format!("{} tools", get_allowed_tools(data, chat_id).len()),
+        tools_source,
+    ));
+
+    entries.push(match project.and_then(|c| c.shell_timeout) {
+        Some(secs) => Entry::new(
+            "shell_timeout",
+            format_secs(Duration::from_secs(secs)),
+            PROJECT_CONFIG_FILE,
+        ),
+        None => Entry::new("shell_timeout", format_secs(SHELL_TIMEOUT), "default"),
+    });
+    entries.push(match project.and_then(|c| c.shell_output_limit) {
+        Some(bytes) => Entry::new(
+            "shell_output_limit",
+            format!("{bytes} bytes"),
+            PROJECT_CONFIG_FILE,
+        ),
+        None => Entry::new(
+            "shell_output_limit",
+            format!("{SHELL_OUTPUT_LIMIT} bytes"),
+            "default",
+        ),
+    });
+
+    entries.push(match codex::stall_timeout() {
+        Some(timeout) => Entry::new("stall_timeout", format_secs(timeout), "--stall-timeout"),
+        None => Entry::new("stall_timeout", "off", "default"),
+    });
+    entries.push(Entry::new(
+        "rate_limit_gap",
+        format_secs(MIN_SEND_GAP),
+        "built-in",
+    ));
+    entries
+}
+
+fn render_entries(entries: &[Entry]) -> String {
+    let lines: Vec<String> = entries
+        .iter()
+        .map(|e| format!("{}: {} ({})", e.name, e.value, e.source))
+        .collect();
+    format!("Effective configuration\n{}", lines.join("\n"))
+}
+
+/// Handle /config command - show the effective settings for this chat and the bot, and
+/// which layer (flag, env var, config file, project file, chat setting or default) set each
+pub(super) async fn handle_config_command(
+    bot: &Bot,
+    chat_id: ChatId,
+    state: &SharedState,
+    token: &str,
+) -> ResponseResult<()> {
+    let message = {
+        let data = state.lock().await;
+        render_entries(&collect_entries(&data, chat_id, token))
+    };
+    shared_rate_limit_wait(state, chat_id).await;
+    bot.send_message(chat_id, message).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redact_token() {
+        assert_eq!(redact_token("123456:ABC-secret"), "123456:***");
+        assert_eq!(redact_token("garbage"), "***");
+    }
+
+    #[test]
+    fn test_render_entries() {
+        let entries = vec![
+            Entry::new("backend", "codex", "default"),
+            Entry::new("model", "o3", PROJECT_CONFIG_FILE),
+        ];
+        assert_eq!(
+            render_entries(&entries),
+            "Effective configuration\nbackend: codex (default)\nmodel: o3 (.opencodex.toml)"
+        );
+        assert_eq!(format_secs(Duration::from_millis(3000)), "3s");
+        assert_eq!(format_secs(Duration::from_millis(1500)), "1.5s");
+    }
+}
@@ -0,0 +1,97 @@
+use std::time::{Duration, Instant};
+
+use teloxide::prelude::*;
+use teloxide::types::ParseMode;
+
+use super::bot::{is_owner, SharedData, SharedState};
+use super::streaming::shared_rate_limit_wait;
+
+/// Longest grant /elevate accepts, in minutes
+const MAX_ELEVATE_MINUTES: u64 = 8 * 60;
+
+/// Whether `user_id` holds an unexpired /elevate grant in this chat
+pub(super) fn is_elevated(data: &SharedData, chat_id: ChatId, user_id: u64) -> bool {
+    data.elevations
+        .get(&(chat_id, user_id))
+        .is_some_and(|expires| *expires > Instant::now())
+}
+
+/// Handle /elevate command - let a non-owner send AI prompts and use read-only commands in
+/// this chat for a limited time. Commands that change state or run code stay owner-only.
+/// Usage: /elevate <user_id> <minutes> | revoke <user_id>  (no argument lists active grants)
+pub(super) async fn handle_elevate_command(
+    bot: &Bot,
+    chat_id: ChatId,
+    text: &str,
+    state: &SharedState,
+) -> ResponseResult<()> {
+    let arg = text.strip_prefix("/elevate").unwrap_or("").trim();
+    let parts: Vec<&str> = arg.split_whitespace().collect();
+
+    let response_msg = {
+        let mut data = state.lock().await;
+        let now = Instant::now();
+        data.elevations.retain(|_, expires| *expires > now);
+        match parts.as_slice() {
+            [] | ["list"] => {
+                let mut grants: Vec<String> = data
+                    .elevations
+                    .iter()
+                    .filter(|((chat, _), _)| *chat == chat_id)
+                    .map(|((_, user), expires)| {
+                        let minutes = expires.duration_since(now).as_secs().div_ceil(60);
+                        format!("<code>{user}</code> — {minutes} min left")
+                    })
+                    .collect();
+                grants.sort();
+                if grants.is_empty() {
+                    "No elevated users in this chat.\n\n\
+                     <code>/elevate &lt;user_id&gt; &lt;minutes&gt;</code> — Allow AI prompts temporarily\n\
+                     <code>/elevate revoke &lt;user_id&gt;</code> — End a grant early"
+                        .to_string()
+                } else {
+                    format!("<b>Elevated users</b>\n{}", grants.join("\n"))
+                }
+            }
+            ["revoke", user] => match user.parse::<u64>() {
+                Ok(user) if data.elevations.remove(&(chat_id, user)).is_some() => {
+                    format!("Elevation for <code>{user}</code> revoked.")
+                }
+                Ok(user) => format!("<code>{user}</code> is not elevated in this chat."),
+                Err(_) => "Usage: <code>/elevate revoke &lt;user_id&gt;</code>".to_string(),
+            },
+            [user, minutes] => match (user.parse::<u64>(), minutes.parse::<u64>()) {
+                (Ok(user), _) if is_owner(&data.settings, user) => {
+                    "The owner already has full access.".to_string()
+                }
+                (Ok(user), Ok(minutes)) if (1..=MAX_ELEVATE_MINUTES).contains(&minutes) => {
+                    data.elevations
+                        .insert((chat_id, user), now + Duration::from_secs(minutes * 60));
+                    let ts = chrono::Local::now().format("%H:%M:%S");
+                    logln!(
+                        "  [{ts}] ★ Elevated user {user} in chat {} for {minutes} min",
+                        chat_id.0
+                    );
+                    format!(
+                        "<code>{user}</code> can send AI prompts and use read-only commands \
+                         in this chat for {minutes} min. Other commands stay owner-only."
+                    )
+                }
+                (Ok(_), _) => format!("Minutes must be between 1 and {MAX_ELEVATE_MINUTES}."),
+                (Err(_), _) => {
+                    "Usage: <code>/elevate &lt;user_id&gt; &lt;minutes&gt;</code>".to_string()
+                }
+            },
+            _ => "Usage: <code>/elevate &lt;user_id&gt; &lt;minutes&gt;</code> or \
+                  <code>/elevate revoke &lt;user_id&gt;</code>"
+                .to_string(),
+        }
+    };
+
+    shared_rate_limit_wait(state, chat_id).await;
+    bot.send_message(chat_id, &response_msg)
+        .parse_mode(ParseMode::Html)
+        .await?;
+
+    Ok(())
+}
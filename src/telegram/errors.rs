@@ -0,0 +1,109 @@
+use teloxide::prelude::*;
+
+use crate::i18n;
+use crate::session::{HistoryItem, HistoryType};
+
+use super::bot::SharedState;
+use super::streaming::{shared_rate_limit_wait, truncate_str};
+
+/// Errors listed by /errors without an argument
+const DEFAULT_ERRORS_SHOWN: usize = 5;
+
+/// Most errors /errors lists
+const MAX_ERRORS_SHOWN: usize = 20;
+
+/// Bytes of each error message shown
+const MAX_ERROR_PREVIEW: usize = 500;
+
+/// Whether a history item records a failed request
+pub(super) fn is_error(item: &HistoryItem) -> bool {
+    matches!(item.item_type, HistoryType::Error)
+}
+
+/// The last `n` errors of `history`, oldest first
+fn recent_errors(history: &[HistoryItem], n: usize) -> Vec<&str> {
+    let errors: Vec<&str> = history
+        .iter()
+        .filter(|item| is_error(item))
+        .map(|item| item.content.as_str())
+        .collect();
+    errors[errors.len().saturating_sub(n)..].to_vec()
+}
+
+/// Handle /errors command - list the session's most recent failed requests
+/// Usage: /errors [n]   (default 5, at most 20)
+pub(super) async fn handle_errors_command(
+    bot: &Bot,
+    chat_id: ChatId,
+    text: &str,
+    state: &SharedState,
+) -> ResponseResult<()> {
+    let arg = text.strip_prefix("/errors").unwrap_or("").trim();
+    let count = if arg.is_empty() {
+        Ok(DEFAULT_ERRORS_SHOWN)
+    } else {
+        arg.parse::<usize>()
+            .ok()
+            .filter(|n| (1..=MAX_ERRORS_SHOWN).contains(n))
+            .ok_or(())
+    };
+
+    let response_msg = match count {
+        Err(()) => {
+            format!("Usage: /errors [n]  (1-{MAX_ERRORS_SHOWN}, default {DEFAULT_ERRORS_SHOWN})")
+        }
+        Ok(n) => {
+            let data = state.lock().await;
+            match data.sessions.get(&chat_id) {
+                None => i18n::MSG_NO_SESSION.to_string(),
+                Some(session) => {
+                    let total = session.history.iter().filter(|item| is_error(item)).count();
+                    let errors = recent_errors(&session.history, n);
+                    if errors.is_empty() {
+                        "No errors in this session.".to_string()
+                    } else {
+                        let lines: Vec<String> = errors
+                            .iter()
+                            .map(|error| format!("• {}", truncate_str(error, MAX_ERROR_PREVIEW)))
+                            .collect();
+                        format!(
+                            "Last {} of {total} error(s) in this session (oldest first):\n\n{}",
+                            errors.len(),
+                            lines.join("\n\n")
+                        )
+                    }
+                }
+            }
+        }
+    };
+
+    shared_rate_limit_wait(state, chat_id).await;
+    bot.send_message(chat_id, response_msg).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recent_errors() {
+        let item = |item_type, content: &str| HistoryItem {
+            item_type,
+            content: content.to_string(),
+        };
+        let history = vec![
+            item(HistoryType::Error, "[10:00:00] first"),
+            item(HistoryType::User, "hi"),
+            item(HistoryType::Error, "[10:05:00] second"),
+            item(HistoryType::Assistant, "hello"),
+            item(HistoryType::Error, "[10:09:00] third"),
+        ];
+        assert_eq!(
+            recent_errors(&history, 2),
+            vec!["[10:05:00] second", "[10:09:00] third"]
+        );
+        assert_eq!(recent_errors(&history, 10).len(), 3);
+        assert!(recent_errors(&history[1..2], 5).is_empty());
+    }
+}
@@ -0,0 +1,357 @@
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use teloxide::prelude::*;
+use teloxide::types::{CallbackQuery, InlineKeyboardButton, InlineKeyboardMarkup, ParseMode};
+
+use crate::auth;
+
+use super::bot::{is_owner, SharedState};
+use super::streaming::{html_escape, shared_rate_limit_wait};
+
+/// Callback data prefix routed to this module
+pub(super) const EXTRACT_CALLBACK_PREFIX: &str = "extract:";
+
+/// Maximum number of files offered per response (keeps the keyboard usable)
+const MAX_EXTRACT_FILES: usize = 8;
+
+/// A fenced code block whose info string names a target file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct CodeFile {
+    pub path: String,
+    pub content: String,
+}
+
+/// Code files offered for extraction, awaiting confirmation via inline keyboard
+pub(super) struct PendingExtraction {
+    pub id: u64,
+    /// Session directory the files are written into
+    pub root: String,
+    pub files: Vec<CodeFile>,
+    /// Per-file result line once handled (written or failed)
+    pub results: Vec<Option<String>>,
+}
+
+/// Parse a target path from a fence info string.
+/// Supports `title=path`, `file=path`, `filename=path`, `path=path` and `lang:path`.
+fn path_from_info(info: &str) -> Option<String> {
+    for token in info.split_whitespace() {
+        if let Some((key, value)) = token.split_once('=') {
+            if matches!(key, "title" | "file" | "filename" | "path") {
+                let value = value.trim_matches(|c| c == '"' || c == '\'');
+                if !value.is_empty() {
+                    return Some(value.to_string());
+                }
+            }
+        }
+    }
+
+    let first = info.split_whitespace().next()?;
+    let (_, path) = first.split_once(':')?;
+    (!path.is_empty()).then(|| path.to_string())
+}
+
+/// Extract fenced code blocks that carry a file path hint
+pub(super) fn extract_file_blocks(md: &str) -> Vec<CodeFile> {
+    let mut files = Vec::new();
+    let mut lines = md.lines();
+
+    while let Some(line) = lines.next() {
+        let Some(info) = line.trim_start().strip_prefix("```") else {
+            continue;
+        };
+        let path = path_from_info(info.trim());
+
+        let mut code_lines = Vec::new();
+        for inner in lines.by_ref() {
+            if inner.trim_start().starts_with("```") {
+                break;
+            }
+            code_lines.push(inner);
+        }
+
+        if let Some(path) = path {
+            let mut content = code_lines.join("\n");
+            content.push('\n');
+            files.push(CodeFile { path, content });
+        }
+    }
+
+    files
+}
+
+/// Validate a hinted path lexically: must be relative and must not contain `..` or a root.
+pub(super) fn validate_relative_path(path: &str) -> Option<PathBuf> {
+    let candidate = Path::new(path);
+    if path.is_empty() || candidate.is_absolute() {
+        return None;
+    }
+    let mut clean = PathBuf::new();
+    for component in candidate.components() {
+        match component {
+            Component::Normal(part) => clean.push(part),
+            Component::CurDir => {}
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+    (!clean.as_os_str().is_empty()).then_some(clean)
+}
+
+/// Write one extracted file under `root`, refusing paths that escape it (incl. via symlinks).
+/// The path is checked before any missing parent folder is created.
+fn write_extracted_file(root: &str, file: &CodeFile) -> Result<PathBuf, String> {
+    let relative = validate_relative_path(&file.path).ok_or("invalid path")?;
+    let root = Path::new(root);
+    let dest = root.join(relative);
+    let escapes = "path escapes the session directory".to_string();
+    let existing = dest
+        .ancestors()
+        .find(|ancestor| ancestor.exists())
+        .unwrap_or(root);
+    if !auth::is_path_within_sandbox(existing, root) {
+        return Err(escapes);
+    }
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    if !auth::is_path_within_sandbox(&dest, root) {
+        return Err(escapes);
+    }
+    fs::write(&dest, &file.content).map_err(|e| e.to_string())?;
+    Ok(dest)
+}
+
+/// Render the offer message text and keyboard for the current extraction state
+fn render_offer(pending: &PendingExtraction) -> (String, Option<InlineKeyboardMarkup>) {
+    let mut text = String::from("<b>Code blocks with file paths</b>\n");
+    for (idx, file) in pending.files.iter().enumerate() {
+        let lines = file.content.lines().count();
+        match &pending.results[idx] {
+            Some(result) => text.push_str(&format!("{}. {}\n", idx + 1, result)),
+            None => text.push_str(&format!(
+                "{}. <code>{}</code> ({} lines)\n",
+                idx + 1,
+                html_escape(&file.path),
+                lines
+            )),
+        }
+    }
+
+    let remaining: Vec<usize> = (0..pending.files.len())
+        .filter(|idx| pending.results[*idx].is_none())
+        .collect();
+    if remaining.is_empty() {
+        return (text, None);
+    }
+
+    text.push_str(&format!(
+        "\nWrite into <code>{}</code>?",
+        html_escape(&pending.root)
+    ));
+    let mut rows: Vec<Vec<InlineKeyboardButton>> = remaining
+        .iter()
+        .map(|idx| {
+            vec![InlineKeyboardButton::callback(
+                format!("💾 {}", pending.files[*idx].path),
+                format!("{EXTRACT_CALLBACK_PREFIX}{}:{idx}", pending.id),
+            )]
+        })
+        .collect();
+    rows.push(vec![
+        InlineKeyboardButton::callback(
+            "💾 All",
+            format!("{EXTRACT_CALLBACK_PREFIX}{}:all", pending.id),
+        ),
+        InlineKeyboardButton::callback(
+            "✖ Dismiss",
+            format!("{EXTRACT_CALLBACK_PREFIX}{}:skip", pending.id),
+        ),
+    ]);
+    (text, Some(InlineKeyboardMarkup::new(rows)))
+}
+
+/// Offer to write file-tagged code blocks from an AI response into the session directory.
+/// Does nothing when the response contains no such blocks.
+pub(super) async fn offer_code_extraction(
+    bot: &Bot,
+    chat_id: ChatId,
+    response: &str,
+    root: &str,
+    state: &SharedState,
+) -> ResponseResult<()> {
+    let files: Vec<CodeFile> = extract_file_blocks(response)
+        .into_iter()
+        .filter(|f| validate_relative_path(&f.path).is_some())
+        .take(MAX_EXTRACT_FILES)
+        .collect();
+    if files.is_empty() {
+        return Ok(());
+    }
+
+    let (text, keyboard) = {
+        let mut data = state.lock().await;
+        data.next_extraction_id += 1;
+        let pending = PendingExtraction {
+            id: data.next_extraction_id,
+            root: root.to_string(),
+            results: vec![None; files.len()],
+            files,
+        };
+        let rendered = render_offer(&pending);
+        data.pending_extractions.insert(chat_id, pending);
+        rendered
+    };
+
+    shared_rate_limit_wait(state, chat_id).await;
+    let mut req = bot.send_message(chat_id, text).parse_mode(ParseMode::Html);
+    if let Some(keyboard) = keyboard {
+        req = req.reply_markup(keyboard);
+    }
+    req.await?;
+
+    Ok(())
+}
+
+/// Handle an extraction button press. `data` is the callback data without the prefix:
+/// `<id>:<index>`, `<id>:all` or `<id>:skip`.
+pub(super) async fn handle_extract_callback(
+    bot: &Bot,
+    query: &CallbackQuery,
+    data: &str,
+    state: &SharedState,
+) -> ResponseResult<()> {
+    let Some(message) = query.message.as_ref() else {
+        bot.answer_callback_query(&query.id).await?;
+        return Ok(());
+    };
+    let chat_id = message.chat().id;
+    let message_id = message.id();
+
+    let (id_str, choice) = data.split_once(':').unwrap_or((data, ""));
+    let id: u64 = id_str.parse().unwrap_or(0);
+
+    let outcome = {
+        let mut shared = state.lock().await;
+        if !is_owner(&shared.settings, query.from.id.0) {
+            Err("Only the bot owner can write files.")
+        } else {
+            match shared.pending_extractions.get_mut(&chat_id) {
+                Some(pending) if pending.id == id => {
+                    let targets: Vec<usize> = match choice {
+                        "all" => (0..pending.files.len()).collect(),
+                        "skip" => Vec::new(),
+                        idx => idx.parse().ok().into_iter().collect(),
+                    };
+                    for idx in targets {
+                        if idx >= pending.files.len() || pending.results[idx].is_some() {
+                            continue;
+                        }
+                        let result = match write_extracted_file(&pending.root, &pending.files[idx])
+                        {
+                            Ok(dest) => format!("✅ {}", html_escape(&dest.display().to_string())),
+                            Err(e) => format!(
+                                "❌ {}: {}",
+                                html_escape(&pending.files[idx].path),
+                                html_escape(&e)
+                            ),
+                        };
+                        pending.results[idx] = Some(result);
+                    }
+                    let (mut text, keyboard) = render_offer(pending);
+                    let finished = choice == "skip" || keyboard.is_none();
+                    if finished {
+                        if choice == "skip" {
+                            text.push_str("\nDismissed.");
+                        }
+                        shared.pending_extractions.remove(&chat_id);
+                        Ok((text, None))
+                    } else {
+                        Ok((text, keyboard))
+                    }
+                }
+                _ => Err("This extraction offer has expired."),
+            }
+        }
+    };
+
+    match outcome {
+        Ok((text, keyboard)) => {
+            bot.answer_callback_query(&query.id).await?;
+            shared_rate_limit_wait(state, chat_id).await;
+            let mut req = bot
+                .edit_message_text(chat_id, message_id, text)
+                .parse_mode(ParseMode::Html);
+            if let Some(keyboard) = keyboard {
+                req = req.reply_markup(keyboard);
+            }
+            if let Err(e) = req.await {
+                let ts = chrono::Local::now().format("%H:%M:%S");
+                logln!("  [{ts}]   ⚠ edit_message failed (extract): {e}");
+            }
+        }
+        Err(reason) => {
+            bot.answer_callback_query(&query.id).text(reason).await?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_file_blocks_info_forms() {
+        let md = "intro\n```rust title=src/foo.rs\nfn a() {}\n```\n```python:scripts/run.py\nprint(1)\n```\n```bash\nls\n```";
+        let files = extract_file_blocks(md);
+        assert_eq!(
+            files,
+            vec![
+                CodeFile {
+                    path: "src/foo.rs".to_string(),
+                    content: "fn a() {}\n".to_string(),
+                },
+                CodeFile {
+                    path: "scripts/run.py".to_string(),
+                    content: "print(1)\n".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_relative_path_rejects_traversal() {
+        assert_eq!(
+            validate_relative_path("./src/foo.rs"),
+            Some(PathBuf::from("src/foo.rs"))
+        );
+        assert!(validate_relative_path("../etc/passwd").is_none());
+        assert!(validate_relative_path("src/../../x").is_none());
+        assert!(validate_relative_path("/etc/passwd").is_none());
+        assert!(validate_relative_path("").is_none());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    #[allow(clippy::expect_used)]
+    fn test_write_extracted_file_checks_before_creating() {
+        let base = std::env::temp_dir().join(format!("opencodex_extract_{}", std::process::id()));
+        let session = base.join("project");
+        let outside = base.join("outside");
+        fs::create_dir_all(&session).expect("create session dir");
+        fs::create_dir_all(&outside).expect("create outside dir");
+        std::os::unix::fs::symlink(&outside, session.join("link")).expect("create symlink");
+        let root = session.to_string_lossy().to_string();
+        let file = |path: &str| CodeFile {
+            path: path.to_string(),
+            content: "x\n".to_string(),
+        };
+
+        assert!(write_extracted_file(&root, &file("src/new/a.rs")).is_ok());
+        assert!(write_extracted_file(&root, &file("link/nested/a.rs")).is_err());
+        assert!(!outside.join("nested").exists());
+
+        let _ = fs::remove_dir_all(&base);
+    }
+}
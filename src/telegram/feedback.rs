@@ -0,0 +1,102 @@
+use teloxide::prelude::*;
+use teloxide::types::{MessageReactionUpdated, ReactionType};
+
+use super::bot::{is_owner, SharedState};
+use super::storage::append_feedback_record;
+
+/// Map a reaction this update added to a feedback rating ("up" / "down"). Other reactions,
+/// and ones that were already there, are ignored.
+pub(super) fn reaction_rating(old: &[ReactionType], new: &[ReactionType]) -> Option<&'static str> {
+    new.iter()
+        .filter(|r| !old.contains(r))
+        .filter_map(|r| r.emoji())
+        .find_map(|emoji| match emoji.as_str() {
+            "👍" => Some("up"),
+            "👎" => Some("down"),
+            _ => None,
+        })
+}
+
+/// Handle 👍/👎 reactions on the bot's AI responses.
+/// Only the owner's reactions are recorded; the rating is appended to the feedback log
+/// together with the session ID and the prompt that produced the response.
+pub(super) async fn handle_message_reaction(
+    reaction: MessageReactionUpdated,
+    state: SharedState,
+) -> ResponseResult<()> {
+    let Some(user) = reaction.user() else {
+        // Anonymous (channel/admin) reactions carry no user to authorize
+        return Ok(());
+    };
+    let uid = user.id.0;
+    let chat_id = reaction.chat.id;
+
+    let Some(rating) = reaction_rating(&reaction.old_reaction, &reaction.new_reaction) else {
+        return Ok(());
+    };
+
+    let record = {
+        let data = state.lock().await;
+        if !is_owner(&data.settings, uid) {
+            return Ok(());
+        }
+        let Some(session) = data.sessions.get(&chat_id) else {
+            return Ok(());
+        };
+        let Some((_, prompt)) = session
+            .recent_responses
+            .iter()
+            .find(|(id, _)| *id == reaction.message_id)
+        else {
+            // Not one of our tracked AI responses
+            return Ok(());
+        };
+        serde_json::json!({
+            "timestamp": chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+            "chat_id": chat_id.0,
+            "message_id": reaction.message_id.0,
+            "session_id": session.session_id,
+            "path": session.current_path,
+            "rating": rating,
+            "prompt": prompt,
+        })
+    };
+
+    append_feedback_record(&record);
+
+    let ts = chrono::Local::now().format("%H:%M:%S");
+    let icon = if rating == "up" { "👍" } else { "👎" };
+    logln!(
+        "  [{ts}] {icon} Feedback recorded (msg:{})",
+        reaction.message_id.0
+    );
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn emoji(e: &str) -> ReactionType {
+        ReactionType::Emoji {
+            emoji: e.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_reaction_rating() {
+        assert_eq!(reaction_rating(&[], &[emoji("👍")]), Some("up"));
+        assert_eq!(
+            reaction_rating(&[], &[emoji("🔥"), emoji("👎")]),
+            Some("down")
+        );
+        assert_eq!(reaction_rating(&[], &[emoji("🔥")]), None);
+        assert_eq!(reaction_rating(&[], &[]), None);
+        // Adding another emoji next to an existing 👍 is not new feedback
+        assert_eq!(
+            reaction_rating(&[emoji("👍")], &[emoji("👍"), emoji("🔥")]),
+            None
+        );
+    }
+}
@@ -1,18 +1,325 @@
 use std::fs;
-use std::path::Path;
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::OnceLock;
 use std::time::{Duration, Instant};
 
 use teloxide::prelude::*;
+use teloxide::types::ChatAction;
+use teloxide::{ApiError, RequestError};
 
-use crate::auth;
 use crate::i18n;
 use crate::session::{enforce_history_cap, HistoryItem, HistoryType};
 
-use super::bot::SharedState;
+use super::bot::{SharedData, SharedState};
+use super::bot_api::{download_limit, is_self_hosted, upload_limit};
+use super::diskusage::format_bytes;
+use super::notify::notify_owner_of_failure;
+use super::settings::ChatToggle;
+use super::shell_guard::{dangerous_shell_pattern, offer_shell_confirmation};
 use super::storage::save_session_to_file;
-use super::streaming::{html_escape, send_long_message, shared_rate_limit_wait};
+use super::streaming::{html_escape, send_long_message, shared_rate_limit_wait, strip_ansi};
 
-const SHELL_TIMEOUT: Duration = Duration::from_secs(60);
+/// Default timeout for `!` shell commands (a project's `shell_timeout` overrides it)
+pub(super) const SHELL_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Default cap on captured `!` command output, stdout and stderr combined
+/// (a project's `shell_output_limit` overrides it)
+pub(super) const SHELL_OUTPUT_LIMIT: usize = 1024 * 1024;
+
+/// Download attempts per file when `--download-attempts` is not given
+const DEFAULT_DOWNLOAD_ATTEMPTS: u32 = 3;
+
+/// Longest wait between download attempts
+const MAX_DOWNLOAD_BACKOFF: Duration = Duration::from_secs(8);
+
+/// Attempts to fetch an uploaded file from Telegram's servers (`--download-attempts`)
+static DOWNLOAD_ATTEMPTS: OnceLock<u32> = OnceLock::new();
+
+pub fn configure_download_attempts(attempts: u32) {
+    let _ = DOWNLOAD_ATTEMPTS.set(attempts.max(1));
+}
+
+/// Bytes from the start of a file inspected by `looks_binary`
+const BINARY_SNIFF_BYTES: usize = 8 * 1024;
+
+/// Share of invalid UTF-8 bytes (percent) above which content counts as binary
+const BINARY_INVALID_UTF8_PERCENT: usize = 10;
+
+/// Whether file content looks binary rather than text: the first few KB contain a NUL byte
+/// or too many bytes that are not valid UTF-8
+pub(super) fn looks_binary(content: &[u8]) -> bool {
+    let sample = &content[..content.len().min(BINARY_SNIFF_BYTES)];
+    if sample.contains(&0) {
+        return true;
+    }
+    let mut invalid = 0;
+    let mut rest = sample;
+    while let Err(e) = std::str::from_utf8(rest) {
+        // No error length: the sample ends inside a multi-byte character
+        let Some(len) = e.error_len() else {
+            break;
+        };
+        invalid += len;
+        rest = &rest[e.valid_up_to() + len..];
+    }
+    invalid * 100 > sample.len() * BINARY_INVALID_UTF8_PERCENT
+}
+
+/// Read a pipe into memory while the shared `budget` lasts. When output exceeds it,
+/// reading stops and `limit_hit` is set so the caller can kill the process.
+fn capture_limited(mut pipe: impl Read, budget: &AtomicUsize, limit_hit: &AtomicBool) -> Vec<u8> {
+    let mut captured = Vec::new();
+    let mut chunk = [0u8; 8192];
+    loop {
+        let n = match pipe.read(&mut chunk) {
+            Ok(0) | Err(_) => break,
+            Ok(n) => n,
+        };
+        let available = budget
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |left| {
+                Some(left.saturating_sub(n))
+            })
+            .unwrap_or_else(|left| left);
+        let granted = available.min(n);
+        captured.extend_from_slice(&chunk[..granted]);
+        if granted < n {
+            limit_hit.store(true, Ordering::Relaxed);
+            break;
+        }
+    }
+    captured
+}
+
+/// Download an uploaded file's contents from Telegram, refusing files over `max_bytes`.
+/// The size reported by `get_file` is checked before the transfer starts; when it is
+/// missing, the download is aborted as soon as more than `max_bytes` have arrived.
+/// The outer error is a Telegram API failure, the inner one an HTTP download failure.
+pub(super) async fn download_telegram_file(
+    bot: &Bot,
+    file_id: &str,
+    max_bytes: u64,
+) -> ResponseResult<Result<Vec<u8>, String>> {
+    let file = bot.get_file(file_id).await?;
+    if u64::from(file.size) > max_bytes {
+        return Ok(Err(DownloadError::TooLarge(max_bytes).describe(0)));
+    }
+    Ok(download_file_path(bot, &file.path, max_bytes).await)
+}
+
+/// Why one attempt to fetch a file over HTTP failed
+#[derive(Debug, PartialEq, Eq)]
+enum DownloadError {
+    /// HTTP 404: the file path expired (Telegram keeps it for about an hour) or never existed
+    Expired,
+    /// Any other HTTP error status the server will keep answering with
+    Rejected(u16),
+    /// Connection or read failure, timeout, 5xx or flood control; worth another try
+    Transient(String),
+    /// The file is larger than the caller's limit (in bytes); the transfer was not finished
+    TooLarge(u64),
+}
+
+impl DownloadError {
+    fn from_status(status: reqwest::StatusCode) -> Self {
+        if status == reqwest::StatusCode::NOT_FOUND {
+            Self::Expired
+        } else if status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+            Self::Transient(format!("HTTP {status}"))
+        } else {
+            Self::Rejected(status.as_u16())
+        }
+    }
+
+    /// Message for the chat, naming the kind of failure
+    fn describe(&self, attempts: u32) -> String {
+        match self {
+            Self::Expired => {
+                "file not found on Telegram's servers (HTTP 404); it may have expired, send it again"
+                    .to_string()
+            }
+            Self::Rejected(status) => format!("Telegram refused the download (HTTP {status})"),
+            Self::Transient(e) => format!("network error after {attempts} attempt(s): {e}"),
+            Self::TooLarge(limit) => format!("file too large (limit {})", format_bytes(*limit)),
+        }
+    }
+}
+
+/// Delay before attempt `attempt + 1`: 1s, 2s, 4s, ... up to MAX_DOWNLOAD_BACKOFF
+fn download_backoff(attempt: u32) -> Duration {
+    Duration::from_secs(1u64 << attempt.saturating_sub(1).min(5)).min(MAX_DOWNLOAD_BACKOFF)
+}
+
+/// Fetch `url` once, reading the body chunk by chunk and giving up once it exceeds
+/// `max_bytes` (the announced Content-Length is checked first)
+async fn fetch_once(url: &reqwest::Url, max_bytes: u64) -> Result<Vec<u8>, DownloadError> {
+    let mut resp = reqwest::get(url.clone())
+        .await
+        .map_err(|e| DownloadError::Transient(e.without_url().to_string()))?;
+    let status = resp.status();
+    if !status.is_success() {
+        return Err(DownloadError::from_status(status));
+    }
+    if resp.content_length().is_some_and(|len| len > max_bytes) {
+        return Err(DownloadError::TooLarge(max_bytes));
+    }
+    let mut body = Vec::new();
+    while let Some(chunk) = resp
+        .chunk()
+        .await
+        .map_err(|e| DownloadError::Transient(e.without_url().to_string()))?
+    {
+        if (body.len() + chunk.len()) as u64 > max_bytes {
+            return Err(DownloadError::TooLarge(max_bytes));
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Ok(body)
+}
+
+/// Download a file by the server path returned from `get_file`, retrying transient failures
+/// with backoff up to `--download-attempts` times in total. Files over `max_bytes` fail
+/// without retrying.
+/// A self-hosted server (`--api-url`) in `--local` mode returns absolute paths on its own disk
+/// instead; those are read directly when the bot runs on the same machine. The public API never
+/// does, so its paths are never looked up on the local disk.
+async fn download_file_path(bot: &Bot, path: &str, max_bytes: u64) -> Result<Vec<u8>, String> {
+    if is_self_hosted() && Path::new(path).is_absolute() {
+        if let Ok(meta) = tokio::fs::metadata(path).await {
+            if meta.len() > max_bytes {
+                return Err(DownloadError::TooLarge(max_bytes).describe(0));
+            }
+        }
+        if let Ok(bytes) = tokio::fs::read(path).await {
+            return Ok(bytes);
+        }
+    }
+    let url = bot
+        .api_url()
+        .join(&format!("file/bot{}/{}", bot.token(), path))
+        .map_err(|e| e.to_string())?;
+
+    let attempts = DOWNLOAD_ATTEMPTS
+        .get()
+        .copied()
+        .unwrap_or(DEFAULT_DOWNLOAD_ATTEMPTS);
+    let mut attempt = 1;
+    loop {
+        match fetch_once(&url, max_bytes).await {
+            Ok(bytes) => return Ok(bytes),
+            Err(DownloadError::Transient(e)) if attempt < attempts => {
+                let delay = download_backoff(attempt);
+                let ts = chrono::Local::now().format("%H:%M:%S");
+                logln!(
+                    "  [{ts}]   ⚠ file download failed (attempt {attempt}/{attempts}): {e}; retrying in {}s",
+                    delay.as_secs()
+                );
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+            Err(e) => return Err(e.describe(attempt)),
+        }
+    }
+}
+
+/// Telegram file_ids are URL-safe base64 strings
+fn is_valid_file_id(file_id: &str) -> bool {
+    (20..=256).contains(&file_id.len())
+        && file_id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Resolve a path relative to the chat's current session path.
+/// Returns None for a relative path when the chat has no session.
+pub(super) async fn resolve_chat_path(
+    state: &SharedState,
+    chat_id: ChatId,
+    file_path: &str,
+) -> Option<String> {
+    if Path::new(file_path).is_absolute() {
+        return Some(file_path.to_string());
+    }
+    let data = state.lock().await;
+    data.sessions
+        .get(&chat_id)
+        .and_then(|s| s.current_path.as_deref())
+        .map(|base| format!("{}/{}", base.trim_end_matches('/'), file_path))
+}
+
+/// Shorten a path for display: `$PROJECT/...` inside `project_root`, otherwise `~/...`
+/// inside `home`, otherwise unchanged
+fn shorten_path(path: &str, home: Option<&Path>, project_root: Option<&Path>) -> String {
+    let shortened = |root: Option<&Path>, name: &str| {
+        let rest = Path::new(path).strip_prefix(root?).ok()?;
+        Some(if rest.as_os_str().is_empty() {
+            name.to_string()
+        } else {
+            format!("{name}/{}", rest.display())
+        })
+    };
+    shortened(project_root, "$PROJECT")
+        .or_else(|| shortened(home, "~"))
+        .unwrap_or_else(|| path.to_string())
+}
+
+/// Expand a leading `~` or `~/` to `home`; other paths, and any path without a home, are
+/// returned unchanged
+fn expand_tilde(path: &str, home: Option<&Path>) -> String {
+    match (path.strip_prefix('~'), home) {
+        (Some(""), Some(home)) => home.display().to_string(),
+        (Some(rest), Some(home)) if rest.starts_with('/') => home
+            .join(rest.trim_start_matches('/'))
+            .display()
+            .to_string(),
+        _ => path.to_string(),
+    }
+}
+
+/// Expand `~` in a directory typed by the user (/start, /cd, /setdefault)
+pub(super) fn expand_home(path: &str) -> String {
+    expand_tilde(path, dirs::home_dir().as_deref())
+}
+
+/// How a chat's working directory is shown in /pwd, /status and /cd replies (/shortpaths).
+/// Only the display changes; the stored path stays absolute. The project root is only
+/// abbreviated while the chat is sandboxed (madmax off), since it cannot leave it then.
+pub(super) fn display_path(
+    data: &SharedData,
+    chat_id: ChatId,
+    path: &str,
+    project_root: &str,
+) -> String {
+    if !ChatToggle::ShortPaths.is_enabled(&data.settings, chat_id) {
+        return path.to_string();
+    }
+    let sandboxed = !ChatToggle::Madmax.is_enabled(&data.settings, chat_id);
+    shorten_path(
+        path,
+        dirs::home_dir().as_deref(),
+        sandboxed.then(|| Path::new(project_root)),
+    )
+}
+
+/// Check that the session directory still exists (it may have been deleted or unmounted).
+/// If not, tell the user how to recover and return false.
+pub(super) async fn ensure_working_dir(
+    bot: &Bot,
+    chat_id: ChatId,
+    state: &SharedState,
+    path: &str,
+) -> ResponseResult<bool> {
+    if Path::new(path).is_dir() {
+        return Ok(true);
+    }
+    let ts = chrono::Local::now().format("%H:%M:%S");
+    logln!("  [{ts}]   ⚠ Working directory missing: {path}");
+    shared_rate_limit_wait(state, chat_id).await;
+    bot.send_message(chat_id, i18n::MSG_WORKDIR_MISSING.replace("{path}", path))
+        .await?;
+    Ok(false)
+}
 
 /// Handle /down <filepath> - send file to user
 pub(super) async fn handle_down_command(
@@ -33,24 +340,10 @@ pub(super) async fn handle_down_command(
         return Ok(());
     }
 
-    // Resolve relative path using current session path
-    let resolved_path = if Path::new(file_path).is_absolute() {
-        file_path.to_string()
-    } else {
-        let current_path = {
-            let data = state.lock().await;
-            data.sessions
-                .get(&chat_id)
-                .and_then(|s| s.current_path.clone())
-        };
-        match current_path {
-            Some(base) => format!("{}/{}", base.trim_end_matches('/'), file_path),
-            None => {
-                shared_rate_limit_wait(state, chat_id).await;
-                bot.send_message(chat_id, i18n::MSG_NO_SESSION).await?;
-                return Ok(());
-            }
-        }
+    let Some(resolved_path) = resolve_chat_path(state, chat_id, file_path).await else {
+        shared_rate_limit_wait(state, chat_id).await;
+        bot.send_message(chat_id, i18n::MSG_NO_SESSION).await?;
+        return Ok(());
     };
 
     let path = Path::new(&resolved_path);
@@ -67,6 +360,11 @@ pub(super) async fn handle_down_command(
         return Ok(());
     }
 
+    // Large files take a while to upload; show that instead of nothing
+    shared_rate_limit_wait(state, chat_id).await;
+    let _ = bot
+        .send_chat_action(chat_id, ChatAction::UploadDocument)
+        .await;
     shared_rate_limit_wait(state, chat_id).await;
     bot.send_document(chat_id, teloxide::types::InputFile::file(path))
         .await?;
@@ -74,6 +372,98 @@ pub(super) async fn handle_down_command(
     Ok(())
 }
 
+/// file_id, file name and size of an uploaded document or photo (largest size)
+pub(super) fn uploaded_file(msg: &Message) -> Option<(String, String, u64)> {
+    if let Some(doc) = msg.document() {
+        let name = doc
+            .file_name
+            .clone()
+            .unwrap_or_else(|| "uploaded_file".to_string());
+        Some((doc.file.id.clone(), name, u64::from(doc.file.size)))
+    } else {
+        let photo = msg.photo()?.last()?;
+        Some((
+            photo.file.id.clone(),
+            format!("photo_{}.jpg", photo.file.unique_id),
+            u64::from(photo.file.size),
+        ))
+    }
+}
+
+/// Check an upload folder (/uploaddir) and return it in canonical `a/b` form.
+/// Only plain relative folders are accepted: no absolute paths and no `..`.
+pub(super) fn normalize_upload_subdir(input: &str) -> Result<String, String> {
+    let mut parts = Vec::new();
+    for component in Path::new(input.trim().trim_end_matches('/')).components() {
+        match component {
+            Component::Normal(part) => parts.push(part.to_string_lossy().to_string()),
+            Component::CurDir => {}
+            _ => return Err("must be a folder inside the working directory".to_string()),
+        }
+    }
+    if parts.is_empty() {
+        return Err("empty folder name".to_string());
+    }
+    Ok(parts.join("/"))
+}
+
+/// Err if `path` (which must exist) resolves outside `root` (canonical)
+fn check_resolves_inside(path: &Path, root: &Path) -> Result<(), String> {
+    let resolved = path.canonicalize().map_err(|e| e.to_string())?;
+    if !resolved.starts_with(root) {
+        return Err(format!(
+            "resolves outside the working directory ({})",
+            resolved.display()
+        ));
+    }
+    Ok(())
+}
+
+/// Create the upload folder under `session_dir`. Everything is checked before anything is
+/// created: the folder must be a plain relative path, and the part of it that already exists
+/// must not lead outside the session (e.g. through a symlink in the project).
+fn prepare_upload_subdir(session_dir: &str, subdir: &str) -> Result<PathBuf, String> {
+    let subdir = normalize_upload_subdir(subdir)?;
+    let root = Path::new(session_dir)
+        .canonicalize()
+        .map_err(|e| e.to_string())?;
+    let dir = Path::new(session_dir).join(subdir);
+    let existing = dir
+        .ancestors()
+        .find(|ancestor| ancestor.exists())
+        .unwrap_or(Path::new(session_dir));
+    check_resolves_inside(existing, &root)?;
+    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    // A symlink swapped in meanwhile still cannot hand out a folder elsewhere
+    check_resolves_inside(&dir, &root)?;
+    Ok(dir)
+}
+
+/// Session directory that received files are saved into.
+/// Replies with guidance and returns None if there is no usable one.
+pub(super) async fn upload_dir(
+    bot: &Bot,
+    chat_id: ChatId,
+    state: &SharedState,
+) -> ResponseResult<Option<String>> {
+    let current_path = {
+        let data = state.lock().await;
+        data.sessions
+            .get(&chat_id)
+            .and_then(|s| s.current_path.clone())
+    };
+
+    let Some(save_dir) = current_path else {
+        shared_rate_limit_wait(state, chat_id).await;
+        bot.send_message(chat_id, i18n::MSG_NO_SESSION).await?;
+        return Ok(None);
+    };
+    if !ensure_working_dir(bot, chat_id, state, &save_dir).await? {
+        return Ok(None);
+    }
+    Ok(Some(save_dir))
+}
+
 /// Handle file/photo upload - save to current session path
 pub(super) async fn handle_file_upload(
     bot: &Bot,
@@ -81,57 +471,109 @@ pub(super) async fn handle_file_upload(
     msg: &Message,
     state: &SharedState,
 ) -> ResponseResult<()> {
-    // Get current session path
+    let Some(save_dir) = upload_dir(bot, chat_id, state).await? else {
+        return Ok(());
+    };
+    let Some((file_id, file_name, size)) = uploaded_file(msg) else {
+        return Ok(());
+    };
+    // Reject before downloading when the message already tells the size
+    if size > upload_limit() {
+        shared_rate_limit_wait(state, chat_id).await;
+        bot.send_message(
+            chat_id,
+            format!(
+                "File too large ({:.1} MB). Limit is {} MB.",
+                size as f64 / (1024.0 * 1024.0),
+                upload_limit() / (1024 * 1024)
+            ),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    // Download file from Telegram via HTTP
+    shared_rate_limit_wait(state, chat_id).await;
+    let buf = match download_telegram_file(bot, &file_id, upload_limit()).await? {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            shared_rate_limit_wait(state, chat_id).await;
+            bot.send_message(chat_id, format!("Download failed: {}", e))
+                .await?;
+            return Ok(());
+        }
+    };
+
+    save_received_file(bot, chat_id, state, &save_dir, &file_name, &buf).await
+}
+
+/// Handle /downid command - fetch a Telegram file by its file_id into the session directory
+/// Usage: /downid <file_id> [file_name]
+pub(super) async fn handle_downid_command(
+    bot: &Bot,
+    chat_id: ChatId,
+    text: &str,
+    state: &SharedState,
+) -> ResponseResult<()> {
+    let arg = text.strip_prefix("/downid").unwrap_or("").trim();
+    let mut parts = arg.split_whitespace();
+    let (Some(file_id), name_arg) = (parts.next(), parts.next()) else {
+        shared_rate_limit_wait(state, chat_id).await;
+        bot.send_message(
+            chat_id,
+            "Usage: /downid <file_id> [file_name]\nSaves a Telegram file into the session directory.",
+        )
+        .await?;
+        return Ok(());
+    };
+    if !is_valid_file_id(file_id) {
+        shared_rate_limit_wait(state, chat_id).await;
+        bot.send_message(chat_id, "Invalid file_id format.").await?;
+        return Ok(());
+    }
+
     let current_path = {
         let data = state.lock().await;
         data.sessions
             .get(&chat_id)
             .and_then(|s| s.current_path.clone())
     };
-
     let Some(save_dir) = current_path else {
         shared_rate_limit_wait(state, chat_id).await;
         bot.send_message(chat_id, i18n::MSG_NO_SESSION).await?;
         return Ok(());
     };
+    if !ensure_working_dir(bot, chat_id, state, &save_dir).await? {
+        return Ok(());
+    }
 
-    // Get file_id and file_name
-    let (file_id, file_name) = if let Some(doc) = msg.document() {
-        let name = doc
-            .file_name
-            .clone()
-            .unwrap_or_else(|| "uploaded_file".to_string());
-        (doc.file.id.clone(), name)
-    } else if let Some(photos) = msg.photo() {
-        // Get the largest photo
-        if let Some(photo) = photos.last() {
-            let name = format!("photo_{}.jpg", photo.file.unique_id);
-            (photo.file.id.clone(), name)
-        } else {
+    let too_large = format!(
+        "File too large to download: bots can only fetch files up to {} MB.",
+        download_limit() / (1024 * 1024)
+    );
+    shared_rate_limit_wait(state, chat_id).await;
+    let file = match bot.get_file(file_id).await {
+        Ok(file) if u64::from(file.size) > download_limit() => Err(too_large),
+        Ok(file) => Ok(file),
+        Err(RequestError::Api(ApiError::WrongFileId)) => {
+            Err("Unknown file_id (it may belong to another bot).".to_string())
+        }
+        Err(RequestError::Api(ApiError::Unknown(e))) if e.contains("file is too big") => {
+            Err(too_large)
+        }
+        Err(e) => Err(format!("Download failed: {e}")),
+    };
+    let file = match file {
+        Ok(file) => file,
+        Err(e) => {
+            shared_rate_limit_wait(state, chat_id).await;
+            bot.send_message(chat_id, e).await?;
             return Ok(());
         }
-    } else {
-        return Ok(());
     };
 
-    // Download file from Telegram via HTTP
-    shared_rate_limit_wait(state, chat_id).await;
-    let file = bot.get_file(&file_id).await?;
-    let url = format!(
-        "https://api.telegram.org/file/bot{}/{}",
-        bot.token(),
-        file.path
-    );
-    let buf = match reqwest::get(&url).await {
-        Ok(resp) => match resp.bytes().await {
-            Ok(bytes) => bytes,
-            Err(e) => {
-                shared_rate_limit_wait(state, chat_id).await;
-                bot.send_message(chat_id, format!("Download failed: {}", e))
-                    .await?;
-                return Ok(());
-            }
-        },
+    let buf = match download_file_path(bot, &file.path, download_limit()).await {
+        Ok(bytes) => bytes,
         Err(e) => {
             shared_rate_limit_wait(state, chat_id).await;
             bot.send_message(chat_id, format!("Download failed: {}", e))
@@ -139,29 +581,76 @@ pub(super) async fn handle_file_upload(
             return Ok(());
         }
     };
+    let file_name = name_arg
+        .map(String::from)
+        .or_else(|| {
+            Path::new(&file.path)
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+        })
+        .unwrap_or_else(|| "downloaded_file".to_string());
 
+    save_received_file(bot, chat_id, state, &save_dir, &file_name, &buf).await
+}
+
+/// Save a file received from Telegram into the session directory and record it in the
+/// session history, so the next AI request is told about it
+pub(super) async fn save_received_file(
+    bot: &Bot,
+    chat_id: ChatId,
+    state: &SharedState,
+    save_dir: &str,
+    file_name: &str,
+    buf: &[u8],
+) -> ResponseResult<()> {
     // Enforce upload size limit
-    if buf.len() as u64 > auth::DEFAULT_UPLOAD_LIMIT {
+    if buf.len() as u64 > upload_limit() {
         shared_rate_limit_wait(state, chat_id).await;
         bot.send_message(
             chat_id,
             format!(
                 "File too large ({:.1} MB). Limit is {} MB.",
                 buf.len() as f64 / (1024.0 * 1024.0),
-                auth::DEFAULT_UPLOAD_LIMIT / (1024 * 1024)
+                upload_limit() / (1024 * 1024)
             ),
         )
         .await?;
         return Ok(());
     }
 
-    // Save to session path (sanitize file_name to prevent path traversal)
-    let safe_name = Path::new(&file_name)
+    // Save to the chat's upload folder under the session path (/uploaddir), if any
+    let subdir = {
+        let data = state.lock().await;
+        data.settings
+            .upload_subdirs
+            .get(&chat_id.0.to_string())
+            .cloned()
+    };
+    let dest_dir = match subdir {
+        Some(subdir) => match prepare_upload_subdir(save_dir, &subdir) {
+            Ok(dir) => dir,
+            Err(e) => {
+                shared_rate_limit_wait(state, chat_id).await;
+                bot.send_message(
+                    chat_id,
+                    format!(
+                        "Cannot use upload folder {subdir}: {e}\n/uploaddir off saves to the working directory."
+                    ),
+                )
+                .await?;
+                return Ok(());
+            }
+        },
+        None => PathBuf::from(save_dir),
+    };
+
+    // Sanitize file_name to prevent path traversal
+    let safe_name = Path::new(file_name)
         .file_name()
         .unwrap_or_else(|| std::ffi::OsStr::new("uploaded_file"));
-    let dest = Path::new(&save_dir).join(safe_name);
+    let dest = dest_dir.join(safe_name);
     let file_size = buf.len();
-    match fs::write(&dest, &buf) {
+    match fs::write(&dest, buf) {
         Ok(_) => {
             let msg_text = format!("Saved: {}\n({} bytes)", dest.display(), file_size);
             shared_rate_limit_wait(state, chat_id).await;
@@ -175,12 +664,14 @@ pub(super) async fn handle_file_upload(
         }
     }
 
-    // Record upload in session history and pending queue for Claude Code
+    // Record upload in session history and pending queue for Claude Code; binaries are
+    // marked so the AI does not try to read them as text
     let upload_record = format!(
-        "[File uploaded] {} → {} ({} bytes)",
+        "[File uploaded] {} → {} ({} bytes{})",
         file_name,
         dest.display(),
-        file_size
+        file_size,
+        if looks_binary(buf) { ", binary" } else { "" }
     );
     {
         let mut data = state.lock().await;
@@ -191,7 +682,7 @@ pub(super) async fn handle_file_upload(
             });
             enforce_history_cap(&mut session.history);
             session.pending_uploads.push(upload_record);
-            save_session_to_file(session, &save_dir);
+            save_session_to_file(session, save_dir);
         }
     }
 
@@ -217,17 +708,54 @@ pub(super) async fn handle_shell_command(
         return Ok(());
     }
 
-    // Get current_path for working directory (default to home directory)
-    let working_dir = {
+    // Hold destructive-looking commands until the owner confirms them (/shellconfirm on)
+    let confirm = {
         let data = state.lock().await;
-        data.sessions
-            .get(&chat_id)
-            .and_then(|s| s.current_path.clone())
-            .unwrap_or_else(|| {
-                dirs::home_dir()
-                    .map(|h| h.display().to_string())
-                    .unwrap_or_else(|| "/".to_string())
-            })
+        ChatToggle::ShellConfirm.is_enabled(&data.settings, chat_id)
+    };
+    if confirm {
+        if let Some(reason) = dangerous_shell_pattern(cmd_str) {
+            return offer_shell_confirmation(bot, chat_id, cmd_str, reason, state).await;
+        }
+    }
+
+    run_shell_command(bot, chat_id, cmd_str, state).await
+}
+
+/// Run a `!` command in the session directory and send its output
+pub(super) async fn run_shell_command(
+    bot: &Bot,
+    chat_id: ChatId,
+    cmd_str: &str,
+    state: &SharedState,
+) -> ResponseResult<()> {
+    // Get current_path for working directory (default to home directory), timeout and output cap
+    let (session_path, shell_timeout, output_limit) = {
+        let data = state.lock().await;
+        let session = data.sessions.get(&chat_id);
+        let session_path = session.and_then(|s| s.current_path.clone());
+        let shell_timeout = session
+            .and_then(|s| s.project_config.as_ref())
+            .and_then(|c| c.shell_timeout)
+            .map(Duration::from_secs)
+            .unwrap_or(SHELL_TIMEOUT);
+        let output_limit = session
+            .and_then(|s| s.project_config.as_ref())
+            .and_then(|c| c.shell_output_limit)
+            .map(|bytes| usize::try_from(bytes).unwrap_or(usize::MAX))
+            .unwrap_or(SHELL_OUTPUT_LIMIT);
+        (session_path, shell_timeout, output_limit)
+    };
+    let working_dir = match session_path {
+        Some(path) => {
+            if !ensure_working_dir(bot, chat_id, state, &path).await? {
+                return Ok(());
+            }
+            path
+        }
+        None => dirs::home_dir()
+            .map(|h| h.display().to_string())
+            .unwrap_or_else(|| "/".to_string()),
     };
 
     let cmd_owned = cmd_str.to_string();
@@ -249,54 +777,104 @@ pub(super) async fn handle_shell_command(
         {
             let mut data = state_for_blocking.blocking_lock();
             data.shell_pids.insert(chat_id, shell_pid);
+            // A new command is not covered by an earlier /stop
+            data.stopping.remove(&chat_id);
         }
 
         let execution_result = {
             let start = Instant::now();
             let mut timed_out = false;
+            let budget = AtomicUsize::new(output_limit);
+            let limit_hit = AtomicBool::new(false);
+            let stdout_pipe = child.stdout.take();
+            let stderr_pipe = child.stderr.take();
 
-            let mut output = loop {
-                match child.try_wait() {
-                    Ok(Some(_status)) => {
-                        break child.wait_with_output().map_err(|e| e.to_string())?
-                    }
-                    Ok(None) => {
-                        if start.elapsed() > SHELL_TIMEOUT {
-                            timed_out = true;
+            // Pipes are drained as the command runs, so only `output_limit` bytes are ever held
+            let (status, stdout, stderr) = std::thread::scope(|scope| {
+                let stdout_reader = scope.spawn(|| {
+                    stdout_pipe
+                        .map(|p| capture_limited(p, &budget, &limit_hit))
+                        .unwrap_or_default()
+                });
+                let stderr_reader = scope.spawn(|| {
+                    stderr_pipe
+                        .map(|p| capture_limited(p, &budget, &limit_hit))
+                        .unwrap_or_default()
+                });
+
+                let status = loop {
+                    match child.try_wait() {
+                        Ok(Some(status)) => break Ok(status),
+                        Ok(None) => {
+                            if limit_hit.load(Ordering::Relaxed) {
+                                let _ = child.kill();
+                                break child.wait();
+                            }
+                            if start.elapsed() > shell_timeout {
+                                timed_out = true;
+                                let _ = child.kill();
+                                break child.wait();
+                            }
+                            std::thread::sleep(Duration::from_millis(100));
+                        }
+                        Err(e) => {
                             let _ = child.kill();
-                            break child.wait_with_output().map_err(|e| e.to_string())?;
+                            break Err(e);
                         }
-                        std::thread::sleep(Duration::from_millis(100));
                     }
-                    Err(e) => return Err(e.to_string()),
+                };
+                (
+                    status,
+                    stdout_reader.join().unwrap_or_default(),
+                    stderr_reader.join().unwrap_or_default(),
+                )
+            });
+            status.map_err(|e| e.to_string()).map(|status| {
+                let mut output = std::process::Output {
+                    status,
+                    stdout,
+                    stderr,
+                };
+                if limit_hit.load(Ordering::Relaxed) {
+                    if !output.stderr.is_empty() {
+                        output.stderr.push(b'\n');
+                    }
+                    let notice = format!("[output truncated at {output_limit} bytes]");
+                    output.stderr.extend_from_slice(notice.as_bytes());
                 }
-            };
-
-            if timed_out {
-                if !output.stderr.is_empty() {
-                    output.stderr.push(b'\n');
+                if timed_out {
+                    if !output.stderr.is_empty() {
+                        output.stderr.push(b'\n');
+                    }
+                    let notice = i18n::MSG_SHELL_TIMEOUT
+                        .replace("{secs}", &shell_timeout.as_secs().to_string());
+                    output.stderr.extend_from_slice(notice.as_bytes());
                 }
                 output
-                    .stderr
-                    .extend_from_slice(i18n::MSG_SHELL_TIMEOUT.as_bytes());
-            }
-            Ok(output)
+            })
         };
 
         {
             let mut data = state_for_blocking.blocking_lock();
             data.shell_pids.remove(&chat_id);
+            if !data.cancel_tokens.contains_key(&chat_id) {
+                data.stopping.remove(&chat_id);
+            }
         }
 
         execution_result
     })
     .await;
 
+    let mut failure: Option<String> = None;
     let response = match result {
         Ok(Ok(output)) => {
-            let stdout = String::from_utf8_lossy(&output.stdout);
-            let stderr = String::from_utf8_lossy(&output.stderr);
+            let stdout = strip_ansi(&String::from_utf8_lossy(&output.stdout));
+            let stderr = strip_ansi(&String::from_utf8_lossy(&output.stderr));
             let exit_code = output.status.code().unwrap_or(-1);
+            if exit_code != 0 {
+                failure = Some(format!("exit code {exit_code}\n{}", stderr.trim_end()));
+            }
 
             let mut parts = Vec::new();
 
@@ -315,8 +893,14 @@ pub(super) async fn handle_shell_command(
 
             parts.join("\n")
         }
-        Ok(Err(e)) => format!("Failed to execute: {}", html_escape(&e)),
-        Err(e) => format!("Task error: {}", html_escape(&e.to_string())),
+        Ok(Err(e)) => {
+            failure = Some(format!("Failed to execute: {e}"));
+            format!("Failed to execute: {}", html_escape(&e))
+        }
+        Err(e) => {
+            failure = Some(format!("Task error: {e}"));
+            format!("Task error: {}", html_escape(&e.to_string()))
+        }
     };
 
     send_long_message(
@@ -328,15 +912,169 @@ pub(super) async fn handle_shell_command(
     )
     .await?;
 
+    if let Some(error) = failure {
+        notify_owner_of_failure(bot, state, chat_id, &format!("!{cmd_str}"), &error).await;
+    }
+
     Ok(())
 }
 
 #[cfg(test)]
 mod tests {
-    use super::SHELL_TIMEOUT;
+    use super::*;
+
+    #[test]
+    fn test_shorten_path() {
+        let home = Path::new("/home/alice");
+        let project = Path::new("/home/alice/work/app");
+        assert_eq!(
+            shorten_path("/home/alice/work/app/src", Some(home), Some(project)),
+            "$PROJECT/src"
+        );
+        assert_eq!(
+            shorten_path("/home/alice/work/app", Some(home), Some(project)),
+            "$PROJECT"
+        );
+        assert_eq!(
+            shorten_path("/home/alice/work/app/src", Some(home), None),
+            "~/work/app/src"
+        );
+        assert_eq!(shorten_path("/home/alice", Some(home), None), "~");
+        assert_eq!(
+            shorten_path("/home/alicex/notes", Some(home), None),
+            "/home/alicex/notes"
+        );
+        assert_eq!(
+            shorten_path("/srv/data", Some(home), Some(project)),
+            "/srv/data"
+        );
+    }
+
+    #[test]
+    fn test_expand_tilde() {
+        let home = Some(Path::new("/home/alice"));
+        assert_eq!(expand_tilde("~", home), "/home/alice");
+        assert_eq!(expand_tilde("~/work/app", home), "/home/alice/work/app");
+        assert_eq!(expand_tilde("~bob/notes", home), "~bob/notes");
+        assert_eq!(expand_tilde("/srv/data", home), "/srv/data");
+        assert_eq!(expand_tilde("~/work", None), "~/work");
+    }
 
     #[test]
     fn test_shell_timeout_constant_exists() {
         assert_eq!(SHELL_TIMEOUT.as_secs(), 60);
     }
+
+    #[test]
+    fn test_looks_binary() {
+        assert!(!looks_binary(b"fn main() {}\n"));
+        assert!(!looks_binary("한국어 텍스트".as_bytes()));
+        assert!(!looks_binary(b""));
+        assert!(looks_binary(b"PK\x03\x04\x00\x00"));
+        assert!(looks_binary(&[
+            0xff, 0xd8, 0xff, 0xe0, b'J', b'F', b'I', b'F'
+        ]));
+        // A character cut at the end of the sniffed range is not counted as invalid
+        let mut text = "a".repeat(BINARY_SNIFF_BYTES - 1).into_bytes();
+        text.extend_from_slice("é".as_bytes());
+        assert!(!looks_binary(&text));
+    }
+
+    #[test]
+    fn test_download_error_classification() {
+        use reqwest::StatusCode;
+        assert_eq!(
+            DownloadError::from_status(StatusCode::NOT_FOUND),
+            DownloadError::Expired
+        );
+        assert_eq!(
+            DownloadError::from_status(StatusCode::FORBIDDEN),
+            DownloadError::Rejected(403)
+        );
+        assert!(matches!(
+            DownloadError::from_status(StatusCode::BAD_GATEWAY),
+            DownloadError::Transient(_)
+        ));
+        assert!(matches!(
+            DownloadError::from_status(StatusCode::TOO_MANY_REQUESTS),
+            DownloadError::Transient(_)
+        ));
+        assert!(DownloadError::Transient("timed out".to_string())
+            .describe(3)
+            .starts_with("network error after 3 attempt(s)"));
+        assert_eq!(
+            DownloadError::TooLarge(20 * 1024 * 1024).describe(1),
+            "file too large (limit 20.0 MB)"
+        );
+    }
+
+    #[test]
+    fn test_download_backoff() {
+        assert_eq!(download_backoff(1), Duration::from_secs(1));
+        assert_eq!(download_backoff(2), Duration::from_secs(2));
+        assert_eq!(download_backoff(3), Duration::from_secs(4));
+        assert_eq!(download_backoff(10), MAX_DOWNLOAD_BACKOFF);
+    }
+
+    #[test]
+    fn test_is_valid_file_id() {
+        assert!(is_valid_file_id("BQACAgUAAxkBAAIBQ2Zx-y_8AAHdX9c3AAE"));
+        assert!(!is_valid_file_id("short"));
+        assert!(!is_valid_file_id("BQACAgUAAxkBAAIBQ2Zx/../etc/passwd"));
+        assert!(!is_valid_file_id(&"A".repeat(300)));
+    }
+
+    #[test]
+    fn test_capture_limited_shares_budget() {
+        let budget = AtomicUsize::new(10);
+        let limit_hit = AtomicBool::new(false);
+        let first = capture_limited(&b"123456"[..], &budget, &limit_hit);
+        assert_eq!(first, b"123456");
+        assert!(!limit_hit.load(Ordering::Relaxed));
+
+        let second = capture_limited(&b"abcdefgh"[..], &budget, &limit_hit);
+        assert_eq!(second, b"abcd");
+        assert!(limit_hit.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn test_normalize_upload_subdir() {
+        assert_eq!(
+            normalize_upload_subdir("uploads/"),
+            Ok("uploads".to_string())
+        );
+        assert_eq!(
+            normalize_upload_subdir("./inbox//2024"),
+            Ok("inbox/2024".to_string())
+        );
+        assert!(normalize_upload_subdir("../outside").is_err());
+        assert!(normalize_upload_subdir("a/../../b").is_err());
+        assert!(normalize_upload_subdir("/etc").is_err());
+        assert!(normalize_upload_subdir(" ").is_err());
+        assert!(normalize_upload_subdir(".").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    #[allow(clippy::expect_used)]
+    fn test_prepare_upload_subdir_rejects_symlink_escape() {
+        let base = std::env::temp_dir().join(format!("opencodex_uploaddir_{}", std::process::id()));
+        let session = base.join("project");
+        let outside = base.join("outside");
+        fs::create_dir_all(&session).expect("create session dir");
+        fs::create_dir_all(&outside).expect("create outside dir");
+        std::os::unix::fs::symlink(&outside, session.join("link")).expect("create symlink");
+        let session_dir = session.to_string_lossy().to_string();
+
+        let dir = prepare_upload_subdir(&session_dir, "uploads/new").expect("create subdir");
+        assert!(dir.is_dir());
+        assert!(prepare_upload_subdir(&session_dir, "link").is_err());
+        // Nothing is created before the checks pass
+        assert!(prepare_upload_subdir(&session_dir, "link/nested").is_err());
+        assert!(!outside.join("nested").exists());
+        assert!(prepare_upload_subdir(&session_dir, "../escaped").is_err());
+        assert!(!base.join("escaped").exists());
+
+        let _ = fs::remove_dir_all(&base);
+    }
 }
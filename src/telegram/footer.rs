@@ -0,0 +1,97 @@
+use teloxide::prelude::*;
+use teloxide::types::ParseMode;
+
+use super::bot::SharedState;
+use super::storage::save_bot_settings;
+use super::streaming::{html_escape, shared_rate_limit_wait, truncate_str};
+
+/// Maximum length (bytes) of the footer set via /footer
+pub(super) const MAX_FOOTER_LEN: usize = 300;
+
+/// Separates the response from the footer
+const FOOTER_SEPARATOR: &str = "\n\n― ";
+
+/// Append the bot-wide footer (/footer) to a response shown in the chat. With a `limit`, the
+/// response is cut instead of the footer so the whole text fits in that many bytes.
+pub(super) fn append_footer(response: &str, footer: Option<&str>, limit: Option<usize>) -> String {
+    let Some(footer) = footer else {
+        return match limit {
+            Some(limit) => truncate_str(response, limit),
+            None => response.to_string(),
+        };
+    };
+    let tail = format!("{FOOTER_SEPARATOR}{footer}");
+    let body = match limit {
+        Some(limit) => truncate_str(response, limit.saturating_sub(tail.len())),
+        None => response.to_string(),
+    };
+    format!("{body}{tail}")
+}
+
+/// Handle /footer command - set the text appended to every AI response (bot-wide)
+/// Usage: /footer <text> | clear  (no argument shows the current footer)
+pub(super) async fn handle_footer_command(
+    bot: &Bot,
+    chat_id: ChatId,
+    text: &str,
+    state: &SharedState,
+    token: &str,
+) -> ResponseResult<()> {
+    let arg = text.strip_prefix("/footer").unwrap_or("").trim();
+
+    let response_msg = {
+        let mut data = state.lock().await;
+        match arg {
+            "" => match &data.settings.footer {
+                Some(footer) => format!(
+                    "Response footer:\n\n{}\n\n<code>/footer clear</code> — Remove it",
+                    html_escape(footer)
+                ),
+                None => "No response footer.\n\n\
+                         <code>/footer &lt;text&gt;</code> — Append a line to every AI response"
+                    .to_string(),
+            },
+            "clear" => {
+                data.settings.footer = None;
+                save_bot_settings(token, &data.settings);
+                "Response footer removed.".to_string()
+            }
+            _ if arg.len() > MAX_FOOTER_LEN => format!(
+                "Footer too long ({} bytes, limit {MAX_FOOTER_LEN}).",
+                arg.len()
+            ),
+            _ => {
+                data.settings.footer = Some(arg.to_string());
+                save_bot_settings(token, &data.settings);
+                format!("Every AI response now ends with:\n\n{}", html_escape(arg))
+            }
+        }
+    };
+
+    shared_rate_limit_wait(state, chat_id).await;
+    bot.send_message(chat_id, &response_msg)
+        .parse_mode(ParseMode::Html)
+        .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_append_footer() {
+        assert_eq!(append_footer("answer", None, None), "answer");
+        assert_eq!(
+            append_footer("answer", Some("AI-generated"), None),
+            "answer\n\n― AI-generated"
+        );
+        // A cut response keeps the whole footer
+        let long = "line\n".repeat(100);
+        let cut = append_footer(&long, Some("AI-generated"), Some(100));
+        assert!(cut.len() <= 100);
+        assert!(cut.ends_with("\n\n― AI-generated"));
+        assert!(append_footer(&long, None, Some(100)).len() <= 100);
+    }
+}
@@ -0,0 +1,146 @@
+use teloxide::prelude::*;
+use teloxide::types::MessageOrigin;
+
+use crate::i18n;
+
+use super::bot::SharedState;
+use super::bot_api::bot_user_id;
+use super::streaming::shared_rate_limit_wait;
+
+/// Most forwarded messages held per chat while waiting for instructions (/forwardwait)
+const MAX_PENDING_FORWARDS: usize = 20;
+
+/// Who the forwarded message originally came from, if Telegram tells us
+fn forward_origin_label(origin: &MessageOrigin) -> Option<String> {
+    match origin {
+        MessageOrigin::User { sender_user, .. } => Some(sender_user.full_name()),
+        MessageOrigin::HiddenUser {
+            sender_user_name, ..
+        } => Some(sender_user_name.clone()),
+        MessageOrigin::Chat { sender_chat, .. } => sender_chat.title().map(String::from),
+        MessageOrigin::Channel { chat, .. } => chat.title().map(String::from),
+    }
+}
+
+/// Start of every prompt built by `forwarded_prompt`
+const FORWARDED_HEADER: &str = "The user forwarded the following content";
+
+/// Wrap forwarded text so the backend knows it is quoted context, not the user's own words.
+/// The result never starts with `/`, `!` or `;`, so a forwarded command is never run.
+pub(super) fn forwarded_prompt(origin: &MessageOrigin, text: &str) -> String {
+    let from = forward_origin_label(origin)
+        .map(|name| format!(" (originally from {name})"))
+        .unwrap_or_default();
+    format!("{FORWARDED_HEADER}{from}:\n\n{text}")
+}
+
+/// The forwarded text inside a prompt built by `forwarded_prompt`, without its header
+pub(super) fn forwarded_text(prompt: &str) -> &str {
+    match prompt.strip_prefix(FORWARDED_HEADER) {
+        Some(rest) => rest.split_once(":\n\n").map_or(rest, |(_, text)| text),
+        None => prompt,
+    }
+}
+
+/// Prompt for a message sent as a reply: the replied-to content first, clearly labelled,
+/// then the user's own text
+fn quoted_reply_prompt(author: &str, quoted: &str, text: &str) -> String {
+    format!(
+        "The user is replying to {author}:\n\n<quoted>\n{quoted}\n</quoted>\n\n\
+         Their message:\n\n{text}"
+    )
+}
+
+/// Add the replied-to message (or the part of it the user quoted) as context to a prompt.
+/// Returns the prompt unchanged when the message is not a reply with text.
+pub(super) fn with_reply_context(bot: &Bot, msg: &Message, prompt: &str) -> String {
+    let Some(reply) = msg.reply_to_message() else {
+        return prompt.to_string();
+    };
+    // In forum topics every message "replies" to the topic's opening message
+    if msg.is_topic_message && msg.thread_id.is_some_and(|thread| thread.0 == reply.id) {
+        return prompt.to_string();
+    }
+    let quoted = match msg.quote() {
+        Some(quote) => quote.text.as_str(),
+        None => match reply.text().or(reply.caption()) {
+            Some(text) => text,
+            None => return prompt.to_string(),
+        },
+    };
+    let author = match reply.from.as_ref() {
+        Some(user) if user.is_bot => {
+            if bot_user_id(bot) == Some(user.id) {
+                "your own earlier response".to_string()
+            } else {
+                format!("a message from the bot {}", user.full_name())
+            }
+        }
+        Some(user) => format!("a message from {}", user.full_name()),
+        None => "an earlier message".to_string(),
+    };
+    quoted_reply_prompt(&author, quoted, prompt)
+}
+
+/// Hold a forwarded message until the user sends a prompt (/forwardwait on)
+pub(super) async fn queue_forwarded(
+    bot: &Bot,
+    chat_id: ChatId,
+    prompt: String,
+    state: &SharedState,
+) -> ResponseResult<()> {
+    let queued = {
+        let mut data = state.lock().await;
+        data.sessions.get_mut(&chat_id).map(|session| {
+            if session.pending_forwards.len() >= MAX_PENDING_FORWARDS {
+                session.pending_forwards.remove(0);
+            }
+            session.pending_forwards.push(prompt);
+            session.pending_forwards.len()
+        })
+    };
+
+    shared_rate_limit_wait(state, chat_id).await;
+    match queued {
+        Some(queued) => {
+            bot.send_message(
+                chat_id,
+                format!(
+                    "Forwarded content saved ({queued} waiting). Send your instructions and it \
+                     will be included with them."
+                ),
+            )
+            .await?
+        }
+        None => bot.send_message(chat_id, i18n::MSG_NO_SESSION).await?,
+    };
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_forwarded_prompt_is_never_a_command() {
+        let origin = MessageOrigin::HiddenUser {
+            date: chrono::Utc::now(),
+            sender_user_name: "Alice".to_string(),
+        };
+        let prompt = forwarded_prompt(&origin, "!rm -rf /tmp/x");
+        assert_eq!(
+            prompt,
+            "The user forwarded the following content (originally from Alice):\n\n!rm -rf /tmp/x"
+        );
+        assert!(!prompt.starts_with(['/', '!', ';']));
+    }
+
+    #[test]
+    fn test_quoted_reply_prompt() {
+        assert_eq!(
+            quoted_reply_prompt("a message from Bob", "panic at line 3", "fix this"),
+            "The user is replying to a message from Bob:\n\n<quoted>\npanic at line 3\n</quoted>\n\n\
+             Their message:\n\nfix this"
+        );
+    }
+}
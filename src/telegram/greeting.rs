@@ -0,0 +1,117 @@
+use teloxide::prelude::*;
+use teloxide::types::ParseMode;
+
+use super::bot::{BotSettings, SharedState};
+use super::bot_api::bot_user_id;
+use super::storage::save_bot_settings;
+use super::streaming::{html_escape, shared_rate_limit_wait};
+
+/// Maximum length (bytes) of a custom greeting set via /greeting
+const MAX_GREETING_LEN: usize = 1000;
+
+/// Intro posted when the bot is added to a group (unless replaced via /greeting set)
+const DEFAULT_GREETING: &str =
+    "👋 Hi! I relay messages in this group to an AI coding assistant.\n\n\
+     By default only my owner can use me. The owner can run /public on to let group members \
+     use read-only commands such as /help and /pwd.\n\
+     In groups I only read messages starting with /, ! or ;.";
+
+/// Text of the group intro (custom text if set, otherwise the default)
+fn greeting_text(settings: &BotSettings) -> &str {
+    settings
+        .greeting_text
+        .as_deref()
+        .unwrap_or(DEFAULT_GREETING)
+}
+
+/// Post the intro if this message reports the bot itself joining a group.
+/// Returns true if the message was a join event for this bot (nothing else to process).
+pub(super) async fn handle_bot_added(
+    bot: &Bot,
+    msg: &Message,
+    state: &SharedState,
+) -> ResponseResult<bool> {
+    let Some(members) = msg.new_chat_members() else {
+        return Ok(false);
+    };
+    let Some(me) = bot_user_id(bot) else {
+        return Ok(false);
+    };
+    if !members.iter().any(|u| u.id == me) {
+        return Ok(false);
+    }
+
+    let chat_id = msg.chat.id;
+    let ts = chrono::Local::now().format("%H:%M:%S");
+    logln!("  [{ts}] ★ Added to group {}", chat_id.0);
+
+    let greeting = {
+        let data = state.lock().await;
+        (!data.settings.greeting_disabled).then(|| greeting_text(&data.settings).to_string())
+    };
+    if let Some(greeting) = greeting {
+        shared_rate_limit_wait(state, chat_id).await;
+        bot.send_message(chat_id, greeting).await?;
+    }
+    Ok(true)
+}
+
+/// Handle /greeting command - configure the intro posted when the bot joins a group (bot-wide)
+/// Usage: /greeting on | off | set <text> | reset  (no argument shows the current greeting)
+pub(super) async fn handle_greeting_command(
+    bot: &Bot,
+    chat_id: ChatId,
+    text: &str,
+    state: &SharedState,
+    token: &str,
+) -> ResponseResult<()> {
+    let arg = text.strip_prefix("/greeting").unwrap_or("").trim();
+    let (sub, rest) = arg.split_once(char::is_whitespace).unwrap_or((arg, ""));
+
+    let response_msg = {
+        let mut data = state.lock().await;
+        let status = |settings: &BotSettings| {
+            if settings.greeting_disabled {
+                "Group greeting <b>disabled</b>.".to_string()
+            } else {
+                format!(
+                    "Group greeting <b>enabled</b>:\n\n{}",
+                    html_escape(greeting_text(settings))
+                )
+            }
+        };
+        match sub {
+            "on" | "off" => {
+                data.settings.greeting_disabled = sub == "off";
+                save_bot_settings(token, &data.settings);
+                status(&data.settings)
+            }
+            "set" if rest.trim().is_empty() => {
+                "Usage: <code>/greeting set &lt;text&gt;</code>".to_string()
+            }
+            "set" if rest.trim().len() > MAX_GREETING_LEN => format!(
+                "Greeting too long ({} bytes, limit {MAX_GREETING_LEN}).",
+                rest.trim().len()
+            ),
+            "set" | "reset" => {
+                data.settings.greeting_text = (sub == "set").then(|| rest.trim().to_string());
+                save_bot_settings(token, &data.settings);
+                status(&data.settings)
+            }
+            _ => format!(
+                "{}\n\n\
+                 <code>/greeting on</code> / <code>/greeting off</code> — Post the greeting when added to a group\n\
+                 <code>/greeting set &lt;text&gt;</code> — Use a custom greeting\n\
+                 <code>/greeting reset</code> — Back to the default greeting",
+                status(&data.settings)
+            ),
+        }
+    };
+
+    shared_rate_limit_wait(state, chat_id).await;
+    bot.send_message(chat_id, &response_msg)
+        .parse_mode(ParseMode::Html)
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,234 @@
+use std::fs::{self, File};
+use std::io::{BufReader, Read};
+use std::path::{Path, PathBuf};
+
+use teloxide::prelude::*;
+use teloxide::types::{
+    InlineQuery, InlineQueryResult, InlineQueryResultArticle, InputMessageContent,
+    InputMessageContentText, ParseMode,
+};
+
+use super::bot::{is_owner, SharedState};
+use super::streaming::{html_escape, truncate_str};
+
+/// Maximum number of results returned for a single inline query
+const MAX_INLINE_RESULTS: usize = 20;
+
+/// Maximum number of directory entries visited per search (bounds latency on huge trees)
+const MAX_SCAN_ENTRIES: usize = 5000;
+
+/// Maximum number of lines included in a snippet
+const SNIPPET_MAX_LINES: usize = 40;
+
+/// Maximum snippet size in bytes (leaves headroom under Telegram's 4096 limit)
+const SNIPPET_MAX_BYTES: usize = 3500;
+
+/// Directory names skipped while searching (build output, dependencies, VCS metadata)
+const SKIPPED_DIRS: &[&str] = &["target", "node_modules", "dist", "build", "__pycache__"];
+
+/// Find files under `root` whose relative path contains `query` (case-insensitive).
+/// Hidden entries and common build/dependency directories are skipped.
+pub(super) fn search_project_files(root: &Path, query: &str, limit: usize) -> Vec<PathBuf> {
+    let needle = query.trim().to_lowercase();
+    let mut results = Vec::new();
+    if needle.is_empty() {
+        return results;
+    }
+
+    let mut stack = vec![root.to_path_buf()];
+    let mut visited: usize = 0;
+
+    while let Some(dir) = stack.pop() {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        let mut entries: Vec<_> = entries.filter_map(|e| e.ok()).collect();
+        entries.sort_by_key(|e| e.file_name());
+
+        for entry in entries {
+            visited += 1;
+            if visited > MAX_SCAN_ENTRIES || results.len() >= limit {
+                return results;
+            }
+
+            let name = entry.file_name().to_string_lossy().to_string();
+            if name.starts_with('.') {
+                continue;
+            }
+
+            let path = entry.path();
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+            if file_type.is_dir() {
+                if !SKIPPED_DIRS.contains(&name.as_str()) {
+                    stack.push(path);
+                }
+            } else if file_type.is_file() {
+                let relative = path.strip_prefix(root).unwrap_or(&path);
+                if relative.to_string_lossy().to_lowercase().contains(&needle) {
+                    results.push(path);
+                }
+            }
+        }
+    }
+
+    results
+}
+
+/// Read the first lines of a text file for preview. Returns None for unreadable or non-UTF-8 files.
+/// Only the start of the file is read, however large it is.
+fn read_snippet(path: &Path) -> Option<String> {
+    let mut content = Vec::new();
+    BufReader::new(File::open(path).ok()?)
+        .take(SNIPPET_MAX_BYTES as u64 + 1)
+        .read_to_end(&mut content)
+        .ok()?;
+    let text = match String::from_utf8(content) {
+        Ok(text) => text,
+        // The read limit may cut a multi-byte character; drop the partial tail
+        Err(e) if e.utf8_error().error_len().is_none() => {
+            let valid = e.utf8_error().valid_up_to();
+            let mut bytes = e.into_bytes();
+            bytes.truncate(valid);
+            String::from_utf8(bytes).ok()?
+        }
+        Err(_) => return None,
+    };
+    let head: Vec<&str> = text.lines().take(SNIPPET_MAX_LINES).collect();
+    Some(truncate_str(&head.join("\n"), SNIPPET_MAX_BYTES))
+}
+
+/// Handle inline queries (`@botname query`) - owner-only, read-only file search
+/// in the owner's current session directory (falls back to the startup project dir).
+pub(super) async fn handle_inline_query(
+    bot: Bot,
+    query: InlineQuery,
+    state: SharedState,
+    default_project_dir: &str,
+) -> ResponseResult<()> {
+    let uid = query.from.id.0;
+    let search_root = {
+        let data = state.lock().await;
+        if !is_owner(&data.settings, uid) {
+            None
+        } else {
+            // The owner's private chat shares the owner's user ID
+            let owner_chat = ChatId(uid as i64);
+            Some(
+                data.sessions
+                    .get(&owner_chat)
+                    .and_then(|s| s.current_path.clone())
+                    .or_else(|| data.settings.last_sessions.get(&uid.to_string()).cloned())
+                    .unwrap_or_else(|| default_project_dir.to_string()),
+            )
+        }
+    };
+
+    let Some(search_root) = search_root else {
+        // Not the owner: answer with nothing so the client stops waiting
+        bot.answer_inline_query(&query.id, Vec::<InlineQueryResult>::new())
+            .is_personal(true)
+            .cache_time(0)
+            .await?;
+        return Ok(());
+    };
+
+    let ts = chrono::Local::now().format("%H:%M:%S");
+    logln!(
+        "  [{ts}] ◀ [{}({uid})] Inline: {}",
+        query.from.first_name,
+        truncate_str(&query.query, 60)
+    );
+
+    let query_text = query.query.clone();
+    let results = tokio::task::spawn_blocking(move || {
+        let root_path = Path::new(&search_root);
+        search_project_files(root_path, &query_text, MAX_INLINE_RESULTS)
+            .into_iter()
+            .filter_map(|path| {
+                let snippet = read_snippet(&path)?;
+                let relative = path
+                    .strip_prefix(root_path)
+                    .unwrap_or(&path)
+                    .display()
+                    .to_string();
+                Some((relative, snippet))
+            })
+            .collect::<Vec<_>>()
+    })
+    .await
+    .unwrap_or_default();
+
+    let articles: Vec<InlineQueryResult> = results
+        .into_iter()
+        .enumerate()
+        .map(|(idx, (relative, snippet))| {
+            let body = if snippet.is_empty() {
+                format!("<b>{}</b>\n(empty file)", html_escape(&relative))
+            } else {
+                format!(
+                    "<b>{}</b>\n<pre>{}</pre>",
+                    html_escape(&relative),
+                    html_escape(&snippet)
+                )
+            };
+            let description = snippet.lines().next().unwrap_or("").to_string();
+            let content = InputMessageContent::Text(
+                InputMessageContentText::new(body).parse_mode(ParseMode::Html),
+            );
+            InlineQueryResult::Article(
+                InlineQueryResultArticle::new(idx.to_string(), relative, content)
+                    .description(truncate_str(&description, 100)),
+            )
+        })
+        .collect();
+
+    bot.answer_inline_query(&query.id, articles)
+        .is_personal(true)
+        .cache_time(0)
+        .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_search_project_files_matches_relative_path() {
+        let root = std::env::temp_dir().join("opencodex_test_inline_search");
+        let _ = fs::remove_dir_all(&root);
+        let _ = fs::create_dir_all(root.join("src"));
+        let _ = fs::create_dir_all(root.join("target"));
+        let _ = fs::create_dir_all(root.join(".git"));
+        let _ = fs::write(root.join("src").join("main.rs"), "fn main() {}");
+        let _ = fs::write(root.join("target").join("main.rs"), "build output");
+        let _ = fs::write(root.join(".git").join("main.rs"), "vcs");
+
+        let found = search_project_files(&root, "MAIN", 10);
+        assert_eq!(found, vec![root.join("src").join("main.rs")]);
+        assert!(search_project_files(&root, "  ", 10).is_empty());
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_read_snippet_reads_only_the_start() {
+        let root = std::env::temp_dir().join("opencodex_test_inline_snippet");
+        let _ = fs::remove_dir_all(&root);
+        let _ = fs::create_dir_all(&root);
+        // A character straddling the read limit, then a binary tail that is never read
+        let mut big = "é".repeat(SNIPPET_MAX_BYTES).into_bytes();
+        big.extend_from_slice(&[0xff; 16]);
+        let _ = fs::write(root.join("big.txt"), &big);
+        let _ = fs::write(root.join("bin.dat"), [0xff, 0xfe]);
+
+        let snippet = read_snippet(&root.join("big.txt")).unwrap_or_default();
+        assert!(!snippet.is_empty() && snippet.len() <= SNIPPET_MAX_BYTES);
+        assert_eq!(read_snippet(&root.join("bin.dat")), None);
+
+        let _ = fs::remove_dir_all(&root);
+    }
+}
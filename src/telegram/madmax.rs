@@ -0,0 +1,155 @@
+use teloxide::prelude::*;
+use teloxide::types::{CallbackQuery, InlineKeyboardButton, InlineKeyboardMarkup, ParseMode};
+
+use crate::codex;
+
+use super::bot::{is_owner, SharedState};
+use super::settings::ChatToggle;
+use super::storage::save_bot_settings;
+use super::streaming::shared_rate_limit_wait;
+
+/// Callback data prefix routed to this module
+pub(super) const MADMAX_CALLBACK_PREFIX: &str = "madmax:";
+
+fn global_note() -> &'static str {
+    if codex::is_madmax() {
+        "the bot was started with --madmax"
+    } else {
+        "the bot was started without --madmax"
+    }
+}
+
+/// Handle /madmax command - switch this chat between sandboxed and unrestricted mode
+/// Usage: /madmax on | off  (no argument shows the current mode).
+/// `on` only takes effect after confirming with a button.
+pub(super) async fn handle_madmax_command(
+    bot: &Bot,
+    chat_id: ChatId,
+    text: &str,
+    state: &SharedState,
+    token: &str,
+) -> ResponseResult<()> {
+    let arg = text
+        .strip_prefix("/madmax")
+        .unwrap_or("")
+        .trim()
+        .to_lowercase();
+
+    if arg == "on" {
+        let keyboard = InlineKeyboardMarkup::new(vec![vec![
+            InlineKeyboardButton::callback(
+                "⚠ Disable sandbox",
+                format!("{MADMAX_CALLBACK_PREFIX}on"),
+            ),
+            InlineKeyboardButton::callback("Cancel", format!("{MADMAX_CALLBACK_PREFIX}cancel")),
+        ]]);
+        shared_rate_limit_wait(state, chat_id).await;
+        let sent = bot
+            .send_message(
+                chat_id,
+                "⚠⚠⚠ <b>Enable madmax for this chat?</b>\n\n\
+                 The AI will run with <b>all approvals and the sandbox disabled</b>: it can \
+                 execute any command, modify or delete any file the bot's user can reach, and \
+                 use the network without asking.\n\nOnly continue in a trusted environment.",
+            )
+            .parse_mode(ParseMode::Html)
+            .reply_markup(keyboard)
+            .await?;
+        let mut data = state.lock().await;
+        data.pending_madmax.insert(chat_id, sent.id);
+        return Ok(());
+    }
+
+    let response_msg = {
+        let mut data = state.lock().await;
+        match arg.as_str() {
+            "off" => {
+                ChatToggle::Madmax.set(&mut data.settings, chat_id, false);
+                save_bot_settings(token, &data.settings);
+                "Madmax <b>disabled</b> for this chat. Requests run sandboxed \
+                 (<code>-a never</code>)."
+                    .to_string()
+            }
+            "" => {
+                let status = if ChatToggle::Madmax.is_enabled(&data.settings, chat_id) {
+                    "on"
+                } else {
+                    "off"
+                };
+                format!(
+                    "Madmax is <b>{status}</b> for this chat ({}).\n\n\
+                     <code>/madmax on</code> — Disable approvals and the sandbox (asks for confirmation)\n\
+                     <code>/madmax off</code> — Run sandboxed",
+                    global_note()
+                )
+            }
+            _ => "Usage: <code>/madmax on</code> or <code>/madmax off</code>".to_string(),
+        }
+    };
+
+    shared_rate_limit_wait(state, chat_id).await;
+    bot.send_message(chat_id, &response_msg)
+        .parse_mode(ParseMode::Html)
+        .await?;
+
+    Ok(())
+}
+
+/// Handle a confirm/cancel press on the /madmax on warning (owner-only).
+/// `data` is `on` or `cancel`.
+pub(super) async fn handle_madmax_callback(
+    bot: &Bot,
+    query: &CallbackQuery,
+    data: &str,
+    state: &SharedState,
+    token: &str,
+) -> ResponseResult<()> {
+    let Some(message) = query.message.as_ref() else {
+        bot.answer_callback_query(&query.id).await?;
+        return Ok(());
+    };
+    let chat_id = message.chat().id;
+
+    let outcome = {
+        let mut shared = state.lock().await;
+        if !is_owner(&shared.settings, query.from.id.0) {
+            Err("Only the bot owner can change madmax mode.")
+        } else if shared.pending_madmax.get(&chat_id) == Some(&message.id()) {
+            shared.pending_madmax.remove(&chat_id);
+            let enable = data == "on";
+            if enable {
+                ChatToggle::Madmax.set(&mut shared.settings, chat_id, true);
+                save_bot_settings(token, &shared.settings);
+            }
+            Ok(enable)
+        } else {
+            Err("This confirmation has expired.")
+        }
+    };
+
+    let enabled = match outcome {
+        Ok(enabled) => enabled,
+        Err(reason) => {
+            bot.answer_callback_query(&query.id).text(reason).await?;
+            return Ok(());
+        }
+    };
+
+    bot.answer_callback_query(&query.id).await?;
+    let ts = chrono::Local::now().format("%H:%M:%S");
+    logln!(
+        "  [{ts}] ◀ [button] /madmax on ({})",
+        if enabled { "confirmed" } else { "cancelled" }
+    );
+    let result = if enabled {
+        "⚠ Madmax enabled for this chat. Approvals and the sandbox are disabled for new requests."
+    } else {
+        "Madmax unchanged."
+    };
+    shared_rate_limit_wait(state, chat_id).await;
+    if let Err(e) = bot.edit_message_text(chat_id, message.id(), result).await {
+        logln!("  [{ts}]   ⚠ edit_message failed (madmax): {e}");
+    }
+
+    Ok(())
+}
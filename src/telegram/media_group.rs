@@ -0,0 +1,153 @@
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use teloxide::prelude::*;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+use super::bot::SharedState;
+use super::bot_api::upload_limit;
+use super::file_ops::{download_telegram_file, save_received_file, upload_dir, uploaded_file};
+use super::message::handle_text_message;
+use super::streaming::shared_rate_limit_wait;
+
+/// Telegram sends each item of an album as its own message; items arriving within this
+/// window after the first one are downloaded together
+const MEDIA_GROUP_WINDOW: Duration = Duration::from_millis(1500);
+
+/// Concurrent upload downloads when `--upload-concurrency` is not given
+const DEFAULT_UPLOAD_CONCURRENCY: usize = 4;
+
+/// Maximum concurrent downloads across all chats (`--upload-concurrency`)
+static UPLOAD_CONCURRENCY: OnceLock<usize> = OnceLock::new();
+
+pub fn configure_upload_concurrency(limit: usize) {
+    let _ = UPLOAD_CONCURRENCY.set(limit.max(1));
+}
+
+/// Bot-wide download slots, sized on first use
+fn upload_slots() -> &'static Semaphore {
+    static SLOTS: OnceLock<Semaphore> = OnceLock::new();
+    SLOTS.get_or_init(|| {
+        Semaphore::new(
+            UPLOAD_CONCURRENCY
+                .get()
+                .copied()
+                .unwrap_or(DEFAULT_UPLOAD_CONCURRENCY),
+        )
+    })
+}
+
+/// Files of one album collected so far
+#[derive(Default)]
+pub(super) struct PendingMediaGroup {
+    /// (file_id, file name, size)
+    files: Vec<(String, String, u64)>,
+    /// Caption text to send to the AI once every file is saved
+    prompt: Option<String>,
+}
+
+/// Add an album item; the first item schedules the batch download after `MEDIA_GROUP_WINDOW`
+pub(super) async fn queue_media_group_item(
+    bot: &Bot,
+    chat_id: ChatId,
+    msg: &Message,
+    group_id: &str,
+    prompt: Option<String>,
+    state: &SharedState,
+) {
+    let Some(file) = uploaded_file(msg) else {
+        return;
+    };
+    let key = (chat_id, group_id.to_string());
+    let first = {
+        let mut data = state.lock().await;
+        let first = !data.media_groups.contains_key(&key);
+        let group = data.media_groups.entry(key.clone()).or_default();
+        group.files.push(file);
+        if prompt.is_some() {
+            group.prompt = prompt;
+        }
+        first
+    };
+    if !first {
+        return;
+    }
+
+    let bot = bot.clone();
+    let state = state.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(MEDIA_GROUP_WINDOW).await;
+        let group = state.lock().await.media_groups.remove(&key);
+        if let Some(group) = group {
+            if let Err(e) = save_media_group(&bot, chat_id, group, &state).await {
+                let ts = chrono::Local::now().format("%H:%M:%S");
+                logln!("  [{ts}]   ⚠ Album upload failed: {e}");
+            }
+        }
+    });
+}
+
+/// Download an album's files concurrently, save them in file-name order, then send the caption
+async fn save_media_group(
+    bot: &Bot,
+    chat_id: ChatId,
+    group: PendingMediaGroup,
+    state: &SharedState,
+) -> ResponseResult<()> {
+    let Some(save_dir) = upload_dir(bot, chat_id, state).await? else {
+        return Ok(());
+    };
+    let ts = chrono::Local::now().format("%H:%M:%S");
+    logln!("  [{ts}]   ⬇ Album: {} file(s)", group.files.len());
+
+    let mut results = Vec::new();
+    let mut downloads = JoinSet::new();
+    for (file_id, file_name, size) in group.files {
+        if size > upload_limit() {
+            // Skip the transfer when the size is already known to exceed the limit
+            let limit_mb = upload_limit() / (1024 * 1024);
+            results.push((
+                file_name,
+                Ok(Err(format!("file too large (limit {limit_mb} MB)"))),
+            ));
+            continue;
+        }
+        let bot = bot.clone();
+        downloads.spawn(async move {
+            let _slot = upload_slots().acquire().await;
+            (
+                file_name,
+                download_telegram_file(&bot, &file_id, upload_limit()).await,
+            )
+        });
+    }
+    while let Some(joined) = downloads.join_next().await {
+        match joined {
+            Ok(result) => results.push(result),
+            Err(e) => logln!("  [{ts}]   ⚠ Album download task failed: {e}"),
+        }
+    }
+    // Completion order is arbitrary; keep `pending_uploads` deterministic
+    results.sort_by(|a, b| a.0.cmp(&b.0));
+
+    for (file_name, result) in results {
+        let error = match result {
+            Ok(Ok(buf)) => {
+                save_received_file(bot, chat_id, state, &save_dir, &file_name, &buf).await?;
+                continue;
+            }
+            Ok(Err(e)) => e,
+            Err(e) => e.to_string(),
+        };
+        shared_rate_limit_wait(state, chat_id).await;
+        bot.send_message(chat_id, format!("Download failed: {file_name}: {error}"))
+            .await?;
+    }
+
+    // Runs outside the per-chat worker; handle_text_message refuses it if a request is running
+    if let Some(prompt) = group.prompt {
+        handle_text_message(bot, chat_id, &prompt, state).await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,119 @@
+use std::sync::atomic::Ordering;
+
+use teloxide::prelude::*;
+use teloxide::types::ChatMemberUpdated;
+
+use super::bot::{SharedData, SharedState};
+use super::storage::{save_bot_settings, save_cron_jobs};
+
+/// Handle `my_chat_member` updates: when the bot is removed from a chat (kicked, or blocked
+/// in a private chat), stop its work there and forget the chat's state
+pub(super) async fn handle_my_chat_member(
+    update: ChatMemberUpdated,
+    state: SharedState,
+    token: &str,
+) -> ResponseResult<()> {
+    if !update.old_chat_member.is_present() || update.new_chat_member.is_present() {
+        return Ok(());
+    }
+    let chat_id = update.chat.id;
+
+    let mut data = state.lock().await;
+    let cron_jobs = data.cron_jobs.len();
+    let cancelled = evict_chat(&mut data, chat_id);
+    save_bot_settings(token, &data.settings);
+    if data.cron_jobs.len() < cron_jobs {
+        save_cron_jobs(token, &data.cron_jobs);
+    }
+
+    let ts = chrono::Local::now().format("%H:%M:%S");
+    logln!(
+        "  [{ts}] ✗ Removed from chat {} by {}: state cleared{}",
+        chat_id.0,
+        update.from.first_name,
+        if cancelled {
+            ", running work cancelled"
+        } else {
+            ""
+        }
+    );
+    Ok(())
+}
+
+/// Cancel the chat's running request and shell command, and drop its in-memory state, saved
+/// session path, public flag, mirrors and /cron jobs. Other per-chat preferences are kept in case the bot is
+/// added back. Returns true if something was running.
+fn evict_chat(data: &mut SharedData, chat_id: ChatId) -> bool {
+    let mut cancelled = false;
+    // The request and shell loops remove their own entries once the process exits
+    if let Some(token) = data.cancel_tokens.get(&chat_id) {
+        cancelled = true;
+        token.cancelled.store(true, Ordering::Relaxed);
+        if let Ok(guard) = token.child_pid.lock() {
+            if let Some(pid) = *guard {
+                #[cfg(unix)]
+                // SAFETY: sending SIGTERM to cancel the child AI process of a chat the bot left
+                #[allow(unsafe_code)]
+                unsafe {
+                    libc::kill(pid as libc::pid_t, libc::SIGTERM);
+                }
+            }
+        }
+    }
+    if let Some(pid) = data.shell_pids.get(&chat_id) {
+        cancelled = true;
+        #[cfg(unix)]
+        // SAFETY: sending SIGTERM to stop the shell command of a chat the bot left
+        #[allow(unsafe_code)]
+        unsafe {
+            libc::kill(*pid as libc::pid_t, libc::SIGTERM);
+        }
+    }
+
+    data.sessions.remove(&chat_id);
+    data.stop_message_ids.remove(&chat_id);
+    data.stopping.remove(&chat_id);
+    data.stop_buttons.remove(&chat_id);
+    data.api_timestamps.remove(&chat_id);
+    data.inbound_timestamps.remove(&chat_id);
+    data.pending_extractions.remove(&chat_id);
+    data.pending_downloads.remove(&chat_id);
+    data.agent_tasks.remove(&chat_id);
+    data.last_prompts.remove(&chat_id);
+    data.last_tool_outputs.remove(&chat_id);
+    data.last_turn_tools.remove(&chat_id);
+    data.raw_streams.remove(&chat_id);
+    data.media_groups.retain(|(chat, _), _| *chat != chat_id);
+    data.pending_pastes.retain(|(chat, _), _| *chat != chat_id);
+    data.pending_summaries.remove(&chat_id);
+    data.compacting.remove(&chat_id);
+    data.pending_shell_commands.remove(&chat_id);
+    data.pending_runs.remove(&chat_id);
+    data.pending_approvals
+        .retain(|(chat, _), _| *chat != chat_id);
+    data.pending_restores.remove(&chat_id);
+    data.pending_session_restores.remove(&chat_id);
+    data.pending_session_reloads.remove(&chat_id);
+    data.pending_clears.remove(&chat_id);
+    data.pending_madmax.remove(&chat_id);
+    data.pending_restarts.remove(&chat_id);
+    data.elevations.retain(|(chat, _), _| *chat != chat_id);
+    data.recent_messages.remove(&chat_id);
+    data.command_last_used
+        .retain(|(chat, _), _| *chat != chat_id);
+    data.backend_breakers.remove(&chat_id);
+    data.user_last_requests
+        .retain(|(chat, _), _| *chat != chat_id);
+
+    let key = chat_id.0.to_string();
+    data.settings.last_sessions.remove(&key);
+    data.settings.as_public_for_group_chat.remove(&key);
+    // A mirror cannot post to or from a chat the bot is no longer in
+    data.settings.mirror_chats.remove(&key);
+    data.settings
+        .mirror_chats
+        .retain(|_, target| *target != chat_id.0);
+    // A job would keep running prompts for a chat that can no longer see the answers
+    data.cron_jobs.retain(|job| job.chat_id != chat_id.0);
+    cancelled
+}
@@ -1,60 +1,452 @@
+use std::collections::BTreeMap;
+use std::path::Path;
 use std::sync::atomic::Ordering;
 use std::sync::mpsc;
-use std::sync::Arc;
+use std::sync::{Arc, OnceLock};
 
 use teloxide::prelude::*;
-use teloxide::types::ParseMode;
+use teloxide::types::{
+    ChatAction, InlineKeyboardButton, InlineKeyboardMarkup, MessageId, ParseMode,
+};
 
 use crate::codex::{self, CancelToken, StreamMessage, DEFAULT_ALLOWED_TOOLS};
 use crate::i18n;
 use crate::session::{enforce_history_cap, sanitize_user_input, HistoryItem, HistoryType};
 
-use super::bot::{SharedState, TELEGRAM_MSG_LIMIT};
-use super::storage::{save_session_to_file, token_hash};
+use super::agents::update_agent_tasks;
+use super::audit_log::append_audit_log;
+use super::autodownload::{changed_file_paths, offer_changed_files};
+use super::bot::{
+    is_public_chat, LastToolOutput, ReplyStyle, SharedState, MAX_KEPT_TOOL_OUTPUT,
+    MAX_TRACKED_RESPONSES, TELEGRAM_MSG_LIMIT,
+};
+use super::bot_api::sendfile_api_arg;
+use super::breaker::{record_backend_result, refuse_if_backend_failing};
+use super::context::inline_file_references;
+use super::continuation::{looks_truncated, offer_continue};
+use super::extract::offer_code_extraction;
+use super::file_ops::ensure_working_dir;
+use super::footer::append_footer;
+use super::mirror::Mirror;
+use super::notify::notify_owner_of_failure;
+use super::project_kind::detect_project_kind;
+use super::settings::{apply_prompt_affixes, cap_response, ChatToggle};
+use super::storage::{save_session_checkpoint, save_session_to_file, token_hash};
 use super::streaming::{
-    format_tool_input, markdown_to_telegram_html, normalize_empty_lines, send_long_message,
-    shared_rate_limit_wait, truncate_str,
+    chat_recently_active, format_tool_input, html_escape, normalize_empty_lines, progress_text,
+    send_code_segments, send_long_message, shared_rate_limit_wait, split_code_segments, strip_ansi,
+    truncate_str, ResponseFormat, ResponseSegment, PROGRESS_SPINNER,
 };
+use super::summary::maybe_auto_compact;
+
+/// Sendfile instructions from `sendfile_instructions` in config.json; None uses the built-in ones
+static SENDFILE_INSTRUCTIONS: OnceLock<Option<String>> = OnceLock::new();
+
+pub fn configure_sendfile_instructions(template: Option<String>) {
+    let _ = SENDFILE_INSTRUCTIONS.set(template.filter(|t| !t.trim().is_empty()));
+}
+
+/// Fill the sendfile template's placeholders
+fn render_sendfile_instructions(
+    template: &str,
+    binary: &str,
+    chat_id: ChatId,
+    key: &str,
+    api_url_arg: &str,
+) -> String {
+    template
+        .replace("{binary}", binary)
+        .replace("{chat_id}", &chat_id.0.to_string())
+        .replace("{key}", key)
+        .replace("{api_url_arg}", api_url_arg)
+}
+
+/// System prompt block telling the AI how to send a file to this chat
+fn sendfile_instructions(chat_id: ChatId, key: &str) -> String {
+    let template = SENDFILE_INSTRUCTIONS
+        .get()
+        .and_then(Option::as_deref)
+        .unwrap_or(i18n::SENDFILE_INSTRUCTIONS_TEMPLATE);
+    render_sendfile_instructions(
+        template,
+        env!("CARGO_BIN_NAME"),
+        chat_id,
+        key,
+        &sendfile_api_arg(),
+    )
+}
 
-/// Handle regular text messages - send to Claude Code AI
+/// Remember which prompt produced a response message, keeping only the most recent entries
+fn track_response(recent: &mut Vec<(MessageId, String)>, msg_id: MessageId, prompt: &str) {
+    recent.push((msg_id, prompt.to_string()));
+    if recent.len() > MAX_TRACKED_RESPONSES {
+        let drain_count = recent.len() - MAX_TRACKED_RESPONSES;
+        recent.drain(..drain_count);
+    }
+}
+
+/// Interval between mid-turn session checkpoints (crash protection for long turns)
+const CHECKPOINT_INTERVAL: tokio::time::Duration = tokio::time::Duration::from_secs(30);
+
+/// How long to wait after `Done` for the backend to exit and report its stderr (/stderr)
+const STDERR_GRACE: tokio::time::Duration = tokio::time::Duration::from_secs(5);
+
+/// Bytes of backend stderr shown in the note under a response
+const MAX_STDERR_NOTE: usize = 3000;
+
+/// Marker appended to a response whose backend output ended without a final result
+const INTERRUPTED_MARKER: &str = "[Interrupted: the backend stopped before finishing]";
+
+/// Partial response kept when the backend's output ends early, marked as interrupted
+fn interrupted_response(partial: &str) -> String {
+    if partial.trim().is_empty() {
+        INTERRUPTED_MARKER.to_string()
+    } else {
+        format!("{}\n\n{INTERRUPTED_MARKER}", partial.trim_end())
+    }
+}
+
+/// Collapsed (expandable quote) note carrying a successful run's stderr. The limit applies to
+/// the escaped text, which can be several times longer than stderr full of `<` and `&`.
+fn stderr_note_html(output: &str) -> String {
+    let mut escaped = truncate_str(&html_escape(output.trim()), MAX_STDERR_NOTE);
+    // Do not leave half an entity (`&am`) at the cut
+    if let Some(amp) = escaped.rfind('&') {
+        if !escaped[amp..].contains(';') {
+            escaped.truncate(amp);
+        }
+    }
+    format!("<b>Backend stderr</b>\n<blockquote expandable>{escaped}</blockquote>")
+}
+
+/// One tool call and its (successful) output
+struct ToolStep {
+    summary: String,
+    output: Option<String>,
+}
+
+/// Consecutive successful tool steps, collapsed into one block when more than one ran.
+/// Errors are never batched so they stay prominent.
+#[derive(Default)]
+struct ToolBatch {
+    steps: Vec<ToolStep>,
+}
+
+impl ToolBatch {
+    fn push_use(&mut self, summary: String) {
+        self.steps.push(ToolStep {
+            summary,
+            output: None,
+        });
+    }
+
+    fn push_result(&mut self, output: &str, limit: usize) {
+        let output = truncate_str(output, limit);
+        match self.steps.last_mut() {
+            Some(step) if step.output.is_none() => step.output = Some(output),
+            _ => self.steps.push(ToolStep {
+                summary: String::new(),
+                output: Some(output),
+            }),
+        }
+    }
+
+    /// Take the pending tool call that an error result belongs to, if any
+    fn take_pending_use(&mut self) -> Option<String> {
+        match self.steps.last() {
+            Some(step) if step.output.is_none() => self.steps.pop().map(|s| s.summary),
+            _ => None,
+        }
+    }
+
+    fn render(&self) -> String {
+        match self.steps.as_slice() {
+            [] => String::new(),
+            [step] => {
+                let mut out = String::new();
+                if !step.summary.is_empty() {
+                    out.push_str(&format!("\n\n⚙️ {}\n", step.summary));
+                }
+                match step.output.as_deref() {
+                    Some(o) if o.contains('\n') => out.push_str(&format!("\n```\n{}\n```\n", o)),
+                    Some(o) if !o.is_empty() => out.push_str(&format!("\n✅ `{}`\n\n", o)),
+                    _ => {}
+                }
+                out
+            }
+            steps => {
+                let mut out = format!("\n\n⚙️ {} commands run\n```\n", steps.len());
+                for step in steps {
+                    if !step.summary.is_empty() {
+                        out.push_str(&format!("▶ {}\n", step.summary.replace('`', "")));
+                    }
+                    if let Some(o) = step.output.as_deref().filter(|o| !o.is_empty()) {
+                        out.push_str(o);
+                        out.push('\n');
+                    }
+                }
+                out.push_str("```\n");
+                out
+            }
+        }
+    }
+
+    fn flush_into(&mut self, out: &mut String) {
+        out.push_str(&self.render());
+        self.steps.clear();
+    }
+}
+
+/// Callback data of the Stop button on the processing message
+pub(super) const STOP_CALLBACK_PREFIX: &str = "stop:";
+
+/// Marker appended to the kept progress message (/replystyle new)
+const PLACEHOLDER_DONE_MARKER: &str = "✓ Done — answer below";
+
+/// Turn the streaming placeholder into a finished log (/replystyle new): the streamed text
+/// without the spinner or Stop button, followed by a done marker
+async fn finalize_placeholder_log(
+    bot: &Bot,
+    chat_id: ChatId,
+    msg_id: MessageId,
+    response: &str,
+    response_format: ResponseFormat,
+    state: &SharedState,
+) {
+    let limit = TELEGRAM_MSG_LIMIT - PLACEHOLDER_DONE_MARKER.len() - 200;
+    let log = format!(
+        "{}\n\n{PLACEHOLDER_DONE_MARKER}",
+        truncate_str(response, limit)
+    );
+    shared_rate_limit_wait(state, chat_id).await;
+    let mut edit = bot.edit_message_text(chat_id, msg_id, response_format.render(&log));
+    edit.parse_mode = response_format.parse_mode();
+    if edit.await.is_err() {
+        // Cutting the text can leave unbalanced markup; the log is still useful as plain text
+        shared_rate_limit_wait(state, chat_id).await;
+        if let Err(e) = bot.edit_message_text(chat_id, msg_id, &log).await {
+            let ts = chrono::Local::now().format("%H:%M:%S");
+            logln!("  [{ts}]   ⚠ edit_message failed (placeholder log): {e}");
+        }
+    }
+}
+
+/// Stop button shown while a response streams. Edits without a keyboard (the final
+/// response) remove it.
+fn stop_keyboard() -> InlineKeyboardMarkup {
+    InlineKeyboardMarkup::new(vec![vec![InlineKeyboardButton::callback(
+        "⏹ Stop",
+        STOP_CALLBACK_PREFIX,
+    )]])
+}
+
+/// Handle regular text messages - send to Claude Code AI.
+/// The chat is claimed for the request (its cancel token registered) under the same lock that
+/// checks for a running one, so prompts started outside teloxide's per-chat worker (media
+/// groups, pastes, approvals, reaction reruns) cannot run side by side.
 pub(super) async fn handle_text_message(
     bot: &Bot,
     chat_id: ChatId,
     user_text: &str,
     state: &SharedState,
 ) -> ResponseResult<()> {
-    // Get session info, allowed tools, and pending uploads (drop lock before any await)
-    let (session_info, allowed_tools, pending_uploads) = {
+    let cancel_token = Arc::new(CancelToken::new());
+    let busy = {
+        let mut data = state.lock().await;
+        let busy = data.cancel_tokens.contains_key(&chat_id);
+        if !busy {
+            data.cancel_tokens.insert(chat_id, cancel_token.clone());
+            // A new request is not covered by an earlier /stop
+            data.stopping.remove(&chat_id);
+        }
+        busy
+    };
+    if busy {
+        shared_rate_limit_wait(state, chat_id).await;
+        bot.send_message(chat_id, i18n::MSG_AI_BUSY).await?;
+        return Ok(());
+    }
+
+    let started = start_text_request(bot, chat_id, user_text, state, cancel_token.clone()).await;
+    if !matches!(started, Ok(true)) {
+        // Nothing was started, so release the chat again
+        let mut data = state.lock().await;
+        if super::bot::is_current_request(&data, chat_id, &cancel_token) {
+            data.cancel_tokens.remove(&chat_id);
+        }
+    }
+    started.map(|_| ())
+}
+
+/// Start the backend request for a prompt in a chat claimed with `cancel_token`. Returns
+/// false if it was not started (the reason was already sent to the chat); once it returns
+/// true, the request's task owns the token and removes it when done.
+async fn start_text_request(
+    bot: &Bot,
+    chat_id: ChatId,
+    user_text: &str,
+    state: &SharedState,
+    cancel_token: Arc<CancelToken>,
+) -> ResponseResult<bool> {
+    // Bot-wide prompt length cap (/promptlimit), checked before pending uploads are drained
+    let prompt_limit = state.lock().await.settings.prompt_limit;
+    let limited_text = match prompt_limit.apply(user_text) {
+        Ok(text) => text,
+        Err(len) => {
+            shared_rate_limit_wait(state, chat_id).await;
+            bot.send_message(
+                chat_id,
+                i18n::MSG_PROMPT_TOO_LONG
+                    .replace("{len}", &len.to_string())
+                    .replace("{max}", &prompt_limit.max_bytes.to_string()),
+            )
+            .await?;
+            return Ok(false);
+        }
+    };
+    let user_text = limited_text.as_str();
+
+    // Get session info, allowed tools, prompt affixes, display settings, and pending uploads
+    // (drop lock before any await)
+    let (
+        session_info,
+        allowed_tools,
+        affixes,
+        verbose,
+        show_stderr,
+        tool_output_limits,
+        response_format,
+        response_language,
+        reply_style,
+        max_response_chars,
+        footer,
+        filter_enabled,
+        progress_file,
+        stream_enabled,
+        typing_pause,
+        project_kind,
+        madmax,
+        backend_args,
+        history_recap,
+        pending_uploads,
+    ) = {
         let mut data = state.lock().await;
         let info = data.sessions.get(&chat_id).and_then(|session| {
             session.current_path.as_ref().map(|_| {
                 (
                     session.session_id.clone(),
                     session.current_path.clone().unwrap_or_default(),
+                    session.project_config.clone().unwrap_or_default(),
                 )
             })
         });
-        let tools = super::bot::get_allowed_tools(&data.settings, chat_id);
-        // Drain pending uploads so they are sent to Claude exactly once
+        let tools = super::bot::get_allowed_tools(&data, chat_id);
+        let chat_key = chat_id.0.to_string();
+        let affixes = (
+            data.settings.prompt_prefixes.get(&chat_key).cloned(),
+            data.settings.prompt_suffixes.get(&chat_key).cloned(),
+        );
+        // Drain pending uploads and held forwards so they are sent to the AI exactly once
         let uploads = data
             .sessions
             .get_mut(&chat_id)
             .map(|s| {
                 s.cleared = false; // Reset cleared flag on new message
-                std::mem::take(&mut s.pending_uploads)
+                let mut pending = std::mem::take(&mut s.pending_uploads);
+                pending.append(&mut s.pending_forwards);
+                pending.extend(s.pending_contexts.drain(..).map(|(_, block)| block));
+                pending
             })
             .unwrap_or_default();
-        (info, tools, uploads)
+        let verbose = ChatToggle::Verbose.is_enabled(&data.settings, chat_id);
+        let show_stderr = verbose || ChatToggle::Stderr.is_enabled(&data.settings, chat_id);
+        let tool_output_limits = data
+            .settings
+            .tool_output_limits
+            .get(&chat_key)
+            .copied()
+            .unwrap_or_default();
+        let response_format = data
+            .settings
+            .response_formats
+            .get(&chat_key)
+            .copied()
+            .unwrap_or(data.settings.response_format);
+        let response_language = data.settings.response_languages.get(&chat_key).cloned();
+        let reply_style = data
+            .settings
+            .reply_styles
+            .get(&chat_key)
+            .copied()
+            .unwrap_or_default();
+        // The filter cannot be turned off for chats open to group members (/public on)
+        let is_public = is_public_chat(&data.settings, chat_id);
+        let filter_enabled = is_public || ChatToggle::Filter.is_enabled(&data.settings, chat_id);
+        let progress_file = ChatToggle::ProgressFile.is_enabled(&data.settings, chat_id);
+        let stream_enabled = ChatToggle::Stream.is_enabled(&data.settings, chat_id);
+        let typing_pause = ChatToggle::TypingPause.is_enabled(&data.settings, chat_id);
+        // Some(cached detection, if any) when /projecthint is on
+        let project_kind = ChatToggle::ProjectHint
+            .is_enabled(&data.settings, chat_id)
+            .then(|| {
+                data.sessions
+                    .get(&chat_id)
+                    .and_then(|s| s.project_kind.clone())
+            });
+        // Only used if the request ends up in a new backend session
+        let history_recap = if ChatToggle::Recap.is_enabled(&data.settings, chat_id) {
+            data.sessions
+                .get(&chat_id)
+                .and_then(|session| super::summary::history_recap(&session.history))
+        } else {
+            None
+        };
+        let madmax = ChatToggle::Madmax.is_enabled(&data.settings, chat_id);
+        let backend_args = data
+            .settings
+            .backend_args
+            .get(&chat_key)
+            .cloned()
+            .unwrap_or_default();
+        (
+            info,
+            tools,
+            affixes,
+            verbose,
+            show_stderr,
+            tool_output_limits,
+            response_format,
+            response_language,
+            reply_style,
+            data.settings.max_response_chars.get(&chat_key).copied(),
+            data.settings.footer.clone(),
+            filter_enabled,
+            progress_file,
+            stream_enabled,
+            typing_pause,
+            project_kind,
+            madmax,
+            backend_args,
+            history_recap,
+            uploads,
+        )
     };
 
-    let (session_id, current_path) = match session_info {
+    let (session_id, current_path, project_config) = match session_info {
         Some(info) => info,
         None => {
             shared_rate_limit_wait(state, chat_id).await;
             bot.send_message(chat_id, i18n::MSG_NO_SESSION).await?;
-            return Ok(());
+            return Ok(false);
         }
     };
+    if !ensure_working_dir(bot, chat_id, state, &current_path).await? {
+        return Ok(false);
+    }
+
+    if refuse_if_backend_failing(bot, chat_id, state).await? {
+        return Ok(false);
+    }
 
     // Note: user message is NOT added to history here.
     // It will be added together with the assistant response in the spawned task,
@@ -62,17 +454,44 @@ pub(super) async fn handle_text_message(
 
     // Send placeholder message (update shared timestamp so spawned task knows)
     shared_rate_limit_wait(state, chat_id).await;
-    let placeholder = bot.send_message(chat_id, "...").await?;
+    let placeholder = bot
+        .send_message(chat_id, "...")
+        .reply_markup(stop_keyboard())
+        .await?;
     let placeholder_msg_id = placeholder.id;
 
     // Sanitize input
-    let (sanitized_input, was_filtered) = sanitize_user_input(user_text);
-    if was_filtered {
-        shared_rate_limit_wait(state, chat_id).await;
-        let _ = bot.send_message(chat_id, i18n::MSG_FILTER_NOTICE).await;
-    }
+    let sanitized_input = if filter_enabled {
+        let (sanitized_input, was_filtered) = sanitize_user_input(user_text);
+        if was_filtered {
+            shared_rate_limit_wait(state, chat_id).await;
+            let _ = bot.send_message(chat_id, i18n::MSG_FILTER_NOTICE).await;
+        }
+        sanitized_input
+    } else {
+        let ts = chrono::Local::now().format("%H:%M:%S");
+        logln!("  [{ts}]   ⚠ Input filter disabled for this chat (/filter off)");
+        user_text.to_string()
+    };
 
-    // Prepend pending file upload records so Claude knows about recently uploaded files
+    // Inline files the user referenced as `@path`; their contents are prepended like /context
+    let (sanitized_input, referenced_files) =
+        inline_file_references(bot, chat_id, &sanitized_input, &current_path, state).await;
+    let pending_uploads: Vec<String> = pending_uploads
+        .into_iter()
+        .chain(referenced_files)
+        .collect();
+
+    // Wrap with the chat's standing /prefix and /suffix instructions
+    let (prompt_prefix, prompt_suffix) = affixes;
+    let sanitized_input = apply_prompt_affixes(
+        &sanitized_input,
+        prompt_prefix.as_deref(),
+        prompt_suffix.as_deref(),
+    );
+
+    // Prepend pending file upload records, held forwards (/forwardwait) and staged files
+    // (/context) as context
     let context_prompt = if pending_uploads.is_empty() {
         sanitized_input
     } else {
@@ -103,15 +522,46 @@ pub(super) async fn handle_text_message(
         )
     };
 
+    // Project type from marker files (/projecthint), detected once per working directory
+    let project_kind_notice = match project_kind {
+        Some(cached) => {
+            let kind = match cached.filter(|kind| kind.path == current_path) {
+                Some(kind) => kind,
+                None => {
+                    let kind = detect_project_kind(&current_path);
+                    if let Some(session) = state.lock().await.sessions.get_mut(&chat_id) {
+                        session.project_kind = Some(kind.clone());
+                    }
+                    kind
+                }
+            };
+            kind.notice()
+        }
+        None => String::new(),
+    };
+
+    // Project instructions from .opencodex.toml, appended after the bot's own rules
+    let project_notice = match project_config.system_prompt.as_deref().map(str::trim) {
+        Some(instructions) if !instructions.is_empty() => {
+            format!("\n\nPROJECT INSTRUCTIONS:\n{instructions}")
+        }
+        _ => String::new(),
+    };
+
+    // Fixed response language from /respondlang; last so it wins over project instructions
+    let language_notice = match &response_language {
+        Some(language) => format!(
+            "\n\nRESPONSE LANGUAGE: Always respond in {language}, \
+             regardless of the language the user writes in."
+        ),
+        None => String::new(),
+    };
+
     // Build system prompt with sendfile instructions
     let system_prompt_owned = format!(
         "You are chatting with a user through Telegram.\n\
          Current working directory: {}\n\n\
-         When your work produces a file the user would want (generated code, reports, images, archives, etc.),\n\
-         send it by running this bash command:\n\n\
-         {} --sendfile <filepath> --chat {} --key {}\n\n\
-         This delivers the file directly to the user's Telegram chat.\n\
-         Do NOT tell the user to use /down — use the command above instead.\n\n\
+         {}\n\n\
          Always keep the user informed about what you are doing. \
          Briefly explain each step as you work (e.g. \"Reading the file...\", \"Creating the script...\", \"Running tests...\"). \
          The user cannot see your tool calls, so narrate your progress so they know what is happening.\n\n\
@@ -119,19 +569,39 @@ pub(super) async fn handle_text_message(
          <code>omx team ...</code> directly (e.g. <code>omx team 3:executor \"task\"</code>).\n\n\
          IMPORTANT: The user is on Telegram and CANNOT interact with any interactive prompts, dialogs, or confirmation requests. \
          All tools that require user interaction (such as AskUserQuestion, EnterPlanMode, ExitPlanMode) will NOT work. \
-         Never use tools that expect user interaction. If you need clarification, just ask in plain text.{}",
-        current_path, env!("CARGO_BIN_NAME"), chat_id.0, token_hash(bot.token()), disabled_notice
+         Never use tools that expect user interaction. If you need clarification, just ask in plain text.{}{}{}{}",
+        current_path, sendfile_instructions(chat_id, &token_hash(bot.token())), disabled_notice, project_kind_notice, project_notice, language_notice
     );
+    let mut backend_overrides = codex::BackendOverrides {
+        model: project_config.model.clone(),
+        progress_file,
+        madmax: Some(madmax),
+        extra_args: backend_args,
+        raw_stream: None,
+        fresh_session_context: history_recap,
+    };
 
-    // Create cancel token for this request
-    let cancel_token = Arc::new(CancelToken::new());
+    // Remember the exact prompt sent to the backend (/lastprompt)
+    let full_prompt = codex::build_full_prompt(
+        &context_prompt,
+        Some(&system_prompt_owned),
+        Some(&allowed_tools),
+    );
+
+    // Request state shown by /stop, /lastprompt and /lasttools
     {
         let mut data = state.lock().await;
-        data.cancel_tokens.insert(chat_id, cancel_token.clone());
+        data.stop_buttons.insert(chat_id, placeholder_msg_id);
+        data.last_prompts.insert(chat_id, full_prompt);
+        data.last_turn_tools.insert(chat_id, BTreeMap::new());
+        if let Some(raw_stream) = data.raw_streams.get(&chat_id) {
+            raw_stream.start_request();
+            backend_overrides.raw_stream = Some(raw_stream.clone());
+        }
     }
 
     // Create channel for streaming
-    let (tx, rx) = mpsc::channel();
+    let (tx, rx) = mpsc::sync_channel(codex::STREAM_CHANNEL_CAPACITY);
 
     let session_id_clone = session_id.clone();
     let current_path_clone = current_path.clone();
@@ -146,6 +616,7 @@ pub(super) async fn handle_text_message(
             tx.clone(),
             Some(&system_prompt_owned),
             Some(&allowed_tools),
+            &backend_overrides,
             Some(cancel_token_clone),
         );
 
@@ -154,32 +625,37 @@ pub(super) async fn handle_text_message(
         }
     });
 
+    // Live copy for the chat's /mirror audience, if any
+    let mut mirror = Mirror::start(bot, chat_id, user_text, state).await;
+
     // Spawn the polling loop as a separate task so the handler returns immediately.
     // This allows teloxide's per-chat worker to process subsequent messages (e.g. /stop).
     let bot_owned = bot.clone();
     let state_owned = state.clone();
     let user_text_owned = user_text.to_string();
+    let prior_session_id = session_id.clone();
     tokio::spawn(async move {
-        const SPINNER: &[&str] = &[
-            "P",
-            "Pr",
-            "Pro",
-            "Proc",
-            "Proce",
-            "Proces",
-            "Process",
-            "Processi",
-            "Processin",
-            "Processing",
-            "Processing.",
-            "Processing..",
-        ];
         let mut full_response = String::new();
+        let mut tool_batch = ToolBatch::default();
         let mut last_edit_text = String::new();
         let mut done = false;
+        let mut completed = false;
         let mut cancelled = false;
         let mut new_session_id: Option<String> = None;
+        let mut backend_error: Option<String> = None;
+        let mut backend_stderr: Option<String> = None;
+        let mut disconnected = false;
+        let mut last_progress: Option<String> = None;
+        let mut last_tool_use: Option<String> = None;
+        let mut last_tool_output: Option<LastToolOutput> = None;
+        let mut tools_used: BTreeMap<String, usize> = BTreeMap::new();
+        // Files the turn's Write/Edit/apply_patch calls touched (/autodownload)
+        let mut changed_files: Vec<String> = Vec::new();
+        // Shown while nothing new is displayed; follows the tool being run
+        let mut chat_action = ChatAction::Typing;
         let mut spin_idx: usize = 0;
+        let started_at = tokio::time::Instant::now();
+        let mut last_checkpoint = started_at;
 
         while !done {
             // Check cancel token
@@ -205,19 +681,48 @@ pub(super) async fn handle_text_message(
                             new_session_id = Some(sid);
                         }
                         StreamMessage::Text { content } => {
+                            tool_batch.flush_into(&mut full_response);
+                            // Separate text messages go on separate lines, as they do when
+                            // the backend reader has merged them under backpressure
+                            if !full_response.is_empty() && !full_response.ends_with('\n') {
+                                full_response.push('\n');
+                            }
                             full_response.push_str(&content);
                         }
                         StreamMessage::ToolUse { name, input } => {
                             let summary = format_tool_input(&name, &input);
                             let ts = chrono::Local::now().format("%H:%M:%S");
-                            println!("  [{ts}]   ⚙ {name}: {}", truncate_str(&summary, 80));
-                            full_response.push_str(&format!("\n\n⚙️ {}\n", summary));
+                            logln!("  [{ts}]   ⚙ {name}: {}", truncate_str(&summary, 80));
+                            last_tool_use = Some(summary.clone());
+                            chat_action = chat_action_for_tool(&name, &input);
+                            changed_files.extend(changed_file_paths(&name, &input));
+                            *tools_used.entry(name).or_default() += 1;
+                            if verbose {
+                                tool_batch.push_use(summary);
+                            }
                         }
                         StreamMessage::ToolResult { content, is_error } => {
+                            chat_action = ChatAction::Typing;
+                            let content = strip_ansi(&content);
+                            last_tool_output = Some(LastToolOutput {
+                                tool: last_tool_use.take(),
+                                content: truncate_str(&content, MAX_KEPT_TOOL_OUTPUT),
+                                is_error,
+                            });
                             if is_error {
                                 let ts = chrono::Local::now().format("%H:%M:%S");
-                                println!("  [{ts}]   ✗ Error: {}", truncate_str(&content, 80));
-                                let truncated = truncate_str(&content, 500);
+                                logln!("  [{ts}]   ✗ Error: {}", truncate_str(&content, 80));
+                            }
+                            if !verbose {
+                                // Quiet mode: tool activity is only logged to the console
+                            } else if is_error {
+                                // Errors break the batch and are shown with their tool call
+                                let failed_use = tool_batch.take_pending_use();
+                                tool_batch.flush_into(&mut full_response);
+                                if let Some(summary) = failed_use {
+                                    full_response.push_str(&format!("\n\n⚙️ {}\n", summary));
+                                }
+                                let truncated = truncate_str(&content, tool_output_limits.error);
                                 if truncated.contains('\n') {
                                     full_response
                                         .push_str(&format!("\n❌\n```\n{}\n```\n", truncated));
@@ -225,15 +730,44 @@ pub(super) async fn handle_text_message(
                                     full_response.push_str(&format!("\n❌ `{}`\n\n", truncated));
                                 }
                             } else if !content.is_empty() {
-                                let truncated = truncate_str(&content, 300);
-                                if truncated.contains('\n') {
-                                    full_response.push_str(&format!("\n```\n{}\n```\n", truncated));
-                                } else {
-                                    full_response.push_str(&format!("\n✅ `{}`\n\n", truncated));
-                                }
+                                tool_batch.push_result(&content, tool_output_limits.result);
                             }
                         }
-                        StreamMessage::TaskNotification { summary, .. } => {
+                        StreamMessage::SessionReset {
+                            previous_session_id,
+                        } => {
+                            let ts = chrono::Local::now().format("%H:%M:%S");
+                            logln!(
+                                "  [{ts}]   ↻ Session {previous_session_id} could not be resumed; started a new one"
+                            );
+                            tool_batch.flush_into(&mut full_response);
+                            full_response.push_str(
+                                "↻ The previous session could not be resumed, so this reply \
+                                 starts a new session without the earlier context.\n\n",
+                            );
+                        }
+                        StreamMessage::Progress { line } => {
+                            // Shown while streaming only; not part of the saved response
+                            last_progress = Some(line);
+                        }
+                        StreamMessage::TaskNotification {
+                            task_id,
+                            status,
+                            summary,
+                        } => {
+                            tool_batch.flush_into(&mut full_response);
+                            {
+                                let updated_at =
+                                    chrono::Local::now().format("%H:%M:%S").to_string();
+                                let mut data = state_owned.lock().await;
+                                update_agent_tasks(
+                                    data.agent_tasks.entry(chat_id).or_default(),
+                                    &task_id,
+                                    &status,
+                                    &summary,
+                                    &updated_at,
+                                );
+                            }
                             if !summary.is_empty() {
                                 full_response.push_str(&format!("\n[Task: {}]\n", summary));
                             }
@@ -242,66 +776,178 @@ pub(super) async fn handle_text_message(
                             result,
                             session_id: sid,
                         } => {
+                            tool_batch.flush_into(&mut full_response);
                             if !result.is_empty() && full_response.is_empty() {
                                 full_response = result;
                             }
                             if let Some(s) = sid {
                                 new_session_id = Some(s);
                             }
+                            completed = true;
                             done = true;
                         }
+                        StreamMessage::Warning { message } => {
+                            let ts = chrono::Local::now().format("%H:%M:%S");
+                            logln!(
+                                "  [{ts}]   ⚠ Backend warning: {}",
+                                truncate_str(&message, 80)
+                            );
+                            // Like tool activity, shown in the chat only with /verbose on
+                            if verbose {
+                                tool_batch.flush_into(&mut full_response);
+                                full_response.push_str(&format!("\n⚠ {message}\n\n"));
+                            }
+                        }
+                        StreamMessage::Stderr { output } => {
+                            backend_stderr = Some(output);
+                        }
                         StreamMessage::Error { message } => {
+                            tool_batch = ToolBatch::default();
                             full_response = format!("Error: {}", message);
+                            backend_error = Some(message);
                             done = true;
                         }
                     },
                     Err(std::sync::mpsc::TryRecvError::Empty) => break,
                     Err(std::sync::mpsc::TryRecvError::Disconnected) => {
                         done = true;
+                        disconnected = true;
                         break;
                     }
                 }
             }
 
+            // Periodically persist the partial turn so a crash doesn't lose it
+            if !done && last_checkpoint.elapsed() >= CHECKPOINT_INTERVAL {
+                last_checkpoint = tokio::time::Instant::now();
+                let checkpoint_sid = new_session_id.clone().or_else(|| prior_session_id.clone());
+                let partial = format!("{}{}", full_response, tool_batch.render());
+                if let Some(sid) = checkpoint_sid.filter(|_| !partial.trim().is_empty()) {
+                    let data = state_owned.lock().await;
+                    if let Some(session) = data.sessions.get(&chat_id).filter(|s| !s.cleared) {
+                        save_session_checkpoint(
+                            session,
+                            &current_path,
+                            &sid,
+                            &user_text_owned,
+                            &partial,
+                        );
+                    }
+                }
+            }
+
             // Build display text with spinning clock+text indicator appended
-            let indicator = SPINNER[spin_idx % SPINNER.len()];
+            let spinner = PROGRESS_SPINNER[spin_idx % PROGRESS_SPINNER.len()];
             spin_idx += 1;
+            let indicator = match &last_progress {
+                Some(line) => format!("📝 {}\n{}", truncate_str(line, 200), spinner),
+                None => spinner.to_string(),
+            };
 
-            let display_text = if full_response.is_empty() {
-                indicator.to_string()
+            // Pending tool steps are shown as they would render once the batch ends
+            let current_response = format!("{}{}", full_response, tool_batch.render());
+            let display_text = if !stream_enabled {
+                // /stream off: only the spinner and elapsed time until the answer is complete
+                format!("{spinner} {}s", started_at.elapsed().as_secs())
             } else {
-                let normalized = normalize_empty_lines(&full_response);
-                let truncated = truncate_str(&normalized, TELEGRAM_MSG_LIMIT - 20);
-                format!("{}\n\n{}", truncated, indicator)
+                progress_text(&current_response, &indicator)
+            };
+
+            // /typingpause on: no edits or typing indicator while messages keep arriving; the
+            // next edit after the chat goes quiet shows everything written meanwhile
+            let chat_active = typing_pause && {
+                let data = state_owned.lock().await;
+                chat_recently_active(
+                    data.inbound_timestamps.get(&chat_id).copied(),
+                    tokio::time::Instant::now(),
+                )
             };
 
-            if display_text != last_edit_text && !done {
+            if chat_active {
+                continue;
+            } else if display_text != last_edit_text && !done {
                 // Rate limit: reserve slot right before the actual API call
                 shared_rate_limit_wait(&state_owned, chat_id).await;
-                let html_text = markdown_to_telegram_html(&display_text);
-                if let Err(e) = bot_owned
-                    .edit_message_text(chat_id, placeholder_msg_id, &html_text)
-                    .parse_mode(ParseMode::Html)
-                    .await
-                {
+                let rendered_text = response_format.render(&display_text);
+                let mut edit = bot_owned
+                    .edit_message_text(chat_id, placeholder_msg_id, &rendered_text)
+                    .reply_markup(stop_keyboard());
+                edit.parse_mode = response_format.parse_mode();
+                if let Err(e) = edit.await {
                     let ts = chrono::Local::now().format("%H:%M:%S");
-                    println!("  [{ts}]   ⚠ edit_message failed (streaming): {e}");
+                    logln!("  [{ts}]   ⚠ edit_message failed (streaming): {e}");
+                }
+                if let Some(mirror) = mirror.as_mut() {
+                    mirror.update(&bot_owned, &display_text, &state_owned).await;
                 }
                 last_edit_text = display_text;
             } else if !done {
                 // No new content to display, send typing indicator
                 shared_rate_limit_wait(&state_owned, chat_id).await;
-                let _ = bot_owned
-                    .send_chat_action(chat_id, teloxide::types::ChatAction::Typing)
-                    .await;
+                let _ = bot_owned.send_chat_action(chat_id, chat_action).await;
+            }
+        }
+
+        tool_batch.flush_into(&mut full_response);
+        let tool_runs: usize = tools_used.values().sum();
+
+        // The backend thread went away without `Done` or an error: keep what arrived so far
+        // (saved with the session id from `Init`) instead of an empty "no response"
+        if disconnected && !completed && backend_error.is_none() {
+            let ts = chrono::Local::now().format("%H:%M:%S");
+            logln!(
+                "  [{ts}]   ⚠ Backend output ended without a result; keeping {} bytes of partial response",
+                full_response.len()
+            );
+            full_response = interrupted_response(&full_response);
+        }
+
+        // stderr is only known once the backend exits, which may be shortly after `Done`
+        if show_stderr && !cancelled && backend_stderr.is_none() && !disconnected {
+            let deadline = tokio::time::Instant::now() + STDERR_GRACE;
+            while tokio::time::Instant::now() < deadline {
+                match rx.try_recv() {
+                    Ok(StreamMessage::Stderr { output }) => {
+                        backend_stderr = Some(output);
+                        break;
+                    }
+                    Ok(_) => {}
+                    Err(std::sync::mpsc::TryRecvError::Empty) => {
+                        tokio::time::sleep(tokio::time::Duration::from_millis(200)).await;
+                    }
+                    Err(std::sync::mpsc::TryRecvError::Disconnected) => break,
+                }
             }
         }
+        if let Some(output) = &backend_stderr {
+            let ts = chrono::Local::now().format("%H:%M:%S");
+            logln!(
+                "  [{ts}]   ⚠ Backend stderr: {}",
+                truncate_str(output.lines().next().unwrap_or(""), 80)
+            );
+        }
 
-        // Remove cancel token and take stop message ID (processing is done)
+        // Remove cancel token and take stop message ID (processing is done).
+        // Background agents end with the request, so drop the tracked set too.
+        // A newer request that started after /start or /clear cancelled this one keeps its own.
         let stop_msg_id = {
             let mut data = state_owned.lock().await;
-            data.cancel_tokens.remove(&chat_id);
-            data.stop_message_ids.remove(&chat_id)
+            if super::bot::is_current_request(&data, chat_id, &cancel_token) {
+                data.cancel_tokens.remove(&chat_id);
+                if !data.shell_pids.contains_key(&chat_id) {
+                    data.stopping.remove(&chat_id);
+                }
+                data.stop_buttons.remove(&chat_id);
+                data.agent_tasks.remove(&chat_id);
+                if let Some(output) = last_tool_output.take() {
+                    data.last_tool_outputs.insert(chat_id, output);
+                }
+                data.last_turn_tools
+                    .insert(chat_id, std::mem::take(&mut tools_used));
+                data.stop_message_ids.remove(&chat_id)
+            } else {
+                None
+            }
         };
 
         if cancelled {
@@ -328,57 +974,67 @@ pub(super) async fn handle_text_message(
                 format!("{}\n\n[Stopped]", normalized)
             };
 
+            let shown_stopped = append_footer(&stopped_response, footer.as_deref(), None);
+
             // Rate limit before final API call
             shared_rate_limit_wait(&state_owned, chat_id).await;
 
             // Update placeholder message with partial response instead of deleting
-            let html_stopped = markdown_to_telegram_html(&stopped_response);
-            if html_stopped.len() <= TELEGRAM_MSG_LIMIT {
-                if let Err(e) = bot_owned
-                    .edit_message_text(chat_id, placeholder_msg_id, &html_stopped)
-                    .parse_mode(ParseMode::Html)
-                    .await
-                {
+            let rendered_stopped = response_format.render(&shown_stopped);
+            let mut response_msg_id = Some(placeholder_msg_id);
+            if rendered_stopped.len() <= TELEGRAM_MSG_LIMIT {
+                let mut edit =
+                    bot_owned.edit_message_text(chat_id, placeholder_msg_id, &rendered_stopped);
+                edit.parse_mode = response_format.parse_mode();
+                if let Err(e) = edit.await {
                     let ts_err = chrono::Local::now().format("%H:%M:%S");
-                    println!("  [{ts_err}]   ⚠ edit_message failed (stopped/HTML): {e}");
+                    logln!("  [{ts_err}]   ⚠ edit_message failed (stopped/formatted): {e}");
                     shared_rate_limit_wait(&state_owned, chat_id).await;
                     let _ = bot_owned
-                        .edit_message_text(chat_id, placeholder_msg_id, &stopped_response)
+                        .edit_message_text(chat_id, placeholder_msg_id, &shown_stopped)
                         .await;
                 }
             } else {
                 let send_result = send_long_message(
                     &bot_owned,
                     chat_id,
-                    &html_stopped,
-                    Some(ParseMode::Html),
+                    &rendered_stopped,
+                    response_format.parse_mode(),
                     &state_owned,
                 )
                 .await;
                 match send_result {
-                    Ok(_) => {
+                    Ok(first_id) => {
+                        response_msg_id = first_id;
                         shared_rate_limit_wait(&state_owned, chat_id).await;
                         let _ = bot_owned.delete_message(chat_id, placeholder_msg_id).await;
                     }
                     Err(e) => {
                         let ts_err = chrono::Local::now().format("%H:%M:%S");
-                        println!("  [{ts_err}]   ⚠ send_long_message failed (stopped/HTML): {e}");
+                        logln!(
+                            "  [{ts_err}]   ⚠ send_long_message failed (stopped/formatted): {e}"
+                        );
                         let fallback = send_long_message(
                             &bot_owned,
                             chat_id,
-                            &stopped_response,
+                            &shown_stopped,
                             None,
                             &state_owned,
                         )
                         .await;
                         match fallback {
-                            Ok(_) => {
+                            Ok(first_id) => {
+                                response_msg_id = first_id;
                                 shared_rate_limit_wait(&state_owned, chat_id).await;
                                 let _ = bot_owned.delete_message(chat_id, placeholder_msg_id).await;
                             }
                             Err(_) => {
                                 shared_rate_limit_wait(&state_owned, chat_id).await;
-                                let truncated = truncate_str(&stopped_response, TELEGRAM_MSG_LIMIT);
+                                let truncated = append_footer(
+                                    &stopped_response,
+                                    footer.as_deref(),
+                                    Some(TELEGRAM_MSG_LIMIT),
+                                );
                                 let _ = bot_owned
                                     .edit_message_text(chat_id, placeholder_msg_id, &truncated)
                                     .await;
@@ -394,14 +1050,27 @@ pub(super) async fn handle_text_message(
                 let _ = bot_owned.delete_message(chat_id, msg_id).await;
             }
 
+            if let Some(mirror) = mirror.take() {
+                mirror
+                    .finish(&bot_owned, &shown_stopped, &state_owned)
+                    .await;
+            }
+
             let ts = chrono::Local::now().format("%H:%M:%S");
-            println!("  [{ts}] ■ Stopped");
+            logln!("  [{ts}] ■ Stopped");
 
             // Record user message + stopped response in history
             // (Claude session context already has this interaction)
             // Skip if session was cleared while we were running (race with /clear)
             let mut data = state_owned.lock().await;
+            if ChatToggle::AuditLog.is_enabled(&data.settings, chat_id) {
+                append_audit_log(chat_id, user_text_owned.clone(), stopped_response.clone());
+            }
             if let Some(session) = data.sessions.get_mut(&chat_id) {
+                session.last_response_msg_id = response_msg_id;
+                if let Some(msg_id) = response_msg_id {
+                    track_response(&mut session.recent_responses, msg_id, &user_text_owned);
+                }
                 if session.cleared {
                     // Session was cleared by /clear; do not re-populate
                 } else {
@@ -430,25 +1099,96 @@ pub(super) async fn handle_text_message(
 
         // Final response
         if full_response.is_empty() {
-            full_response = i18n::MSG_NO_RESPONSE.to_string();
+            full_response = if completed {
+                silent_completion_notice(tool_runs)
+            } else {
+                i18n::MSG_NO_RESPONSE.to_string()
+            };
         }
 
         let full_response = normalize_empty_lines(&full_response);
-        let html_response = markdown_to_telegram_html(&full_response);
+        // Long answers are cut in the chat (/maxresponse); the history keeps the full text
+        let capped_response = max_response_chars
+            .and_then(|max| cap_response(&full_response, max))
+            .unwrap_or_else(|| full_response.clone());
+        // The footer (/footer) is added after the cap so it is never cut off
+        let shown_response = append_footer(&capped_response, footer.as_deref(), None);
+        let rendered_response = response_format.render(&shown_response);
+        let mut response_msg_id = Some(placeholder_msg_id);
+        // /replystyle new: the placeholder stays as a log and the answer always arrives as
+        // new messages, so it is never deleted below
+        let keep_placeholder = reply_style == ReplyStyle::New;
+        if keep_placeholder {
+            finalize_placeholder_log(
+                &bot_owned,
+                chat_id,
+                placeholder_msg_id,
+                &shown_response,
+                response_format,
+                &state_owned,
+            )
+            .await;
+        }
 
-        if html_response.len() <= TELEGRAM_MSG_LIMIT {
-            // Try HTML first, fall back to plain text if it fails (e.g. parse error, rate limit)
-            if let Err(e) = bot_owned
-                .edit_message_text(chat_id, placeholder_msg_id, &html_response)
-                .parse_mode(ParseMode::Html)
-                .await
+        // /strictcode on: code blocks go out as separate plain-text messages
+        let strict_segments = {
+            let data = state_owned.lock().await;
+            let segments = if ChatToggle::StrictCode.is_enabled(&data.settings, chat_id)
+                && response_format != ResponseFormat::Plain
             {
+                split_code_segments(&shown_response)
+            } else {
+                Vec::new()
+            };
+            segments
+                .iter()
+                .any(|s| matches!(s, ResponseSegment::Code(_)))
+                .then_some(segments)
+        };
+
+        if let Some(segments) = strict_segments {
+            match send_code_segments(
+                &bot_owned,
+                chat_id,
+                &segments,
+                response_format,
+                &state_owned,
+            )
+            .await
+            {
+                Ok(first_id) => {
+                    response_msg_id = first_id;
+                    if !keep_placeholder {
+                        shared_rate_limit_wait(&state_owned, chat_id).await;
+                        let _ = bot_owned.delete_message(chat_id, placeholder_msg_id).await;
+                    }
+                }
+                Err(e) => {
+                    let ts = chrono::Local::now().format("%H:%M:%S");
+                    logln!("  [{ts}]   ⚠ send_code_segments failed: {e}");
+                    shared_rate_limit_wait(&state_owned, chat_id).await;
+                    let truncated = append_footer(
+                        &capped_response,
+                        footer.as_deref(),
+                        Some(TELEGRAM_MSG_LIMIT),
+                    );
+                    let _ = bot_owned
+                        .edit_message_text(chat_id, placeholder_msg_id, &truncated)
+                        .await;
+                }
+            }
+        } else if !keep_placeholder && rendered_response.len() <= TELEGRAM_MSG_LIMIT {
+            // Try the formatted text first, fall back to plain text if it fails (e.g. parse error, rate limit)
+            let mut edit =
+                bot_owned.edit_message_text(chat_id, placeholder_msg_id, &rendered_response);
+            edit.parse_mode = response_format.parse_mode();
+            if let Err(e) = edit.await {
                 let ts = chrono::Local::now().format("%H:%M:%S");
-                println!("  [{ts}]   ⚠ edit_message failed (HTML): {e}");
-                // Fallback: try plain text without HTML parse mode
+                logln!("  [{ts}]   ⚠ edit_message failed (formatted): {e}");
+                // Fallback: try plain text without a parse mode
                 shared_rate_limit_wait(&state_owned, chat_id).await;
                 let _ = bot_owned
-                    .edit_message_text(chat_id, placeholder_msg_id, &full_response)
+                    .edit_message_text(chat_id, placeholder_msg_id, &shown_response)
                     .await;
             }
         } else {
@@ -458,34 +1198,44 @@ pub(super) async fn handle_text_message(
             let send_result = send_long_message(
                 &bot_owned,
                 chat_id,
-                &html_response,
-                Some(ParseMode::Html),
+                &rendered_response,
+                response_format.parse_mode(),
                 &state_owned,
             )
             .await;
             match send_result {
-                Ok(_) => {
+                Ok(first_id) => {
+                    response_msg_id = first_id;
                     // New messages sent successfully, now safe to delete placeholder
-                    shared_rate_limit_wait(&state_owned, chat_id).await;
-                    let _ = bot_owned.delete_message(chat_id, placeholder_msg_id).await;
+                    if !keep_placeholder {
+                        shared_rate_limit_wait(&state_owned, chat_id).await;
+                        let _ = bot_owned.delete_message(chat_id, placeholder_msg_id).await;
+                    }
                 }
                 Err(e) => {
                     let ts = chrono::Local::now().format("%H:%M:%S");
-                    println!("  [{ts}]   ⚠ send_long_message failed (HTML): {e}");
+                    logln!("  [{ts}]   ⚠ send_long_message failed (formatted): {e}");
                     // Fallback: try plain text
                     let fallback_result =
-                        send_long_message(&bot_owned, chat_id, &full_response, None, &state_owned)
+                        send_long_message(&bot_owned, chat_id, &shown_response, None, &state_owned)
                             .await;
                     match fallback_result {
-                        Ok(_) => {
-                            shared_rate_limit_wait(&state_owned, chat_id).await;
-                            let _ = bot_owned.delete_message(chat_id, placeholder_msg_id).await;
+                        Ok(first_id) => {
+                            response_msg_id = first_id;
+                            if !keep_placeholder {
+                                shared_rate_limit_wait(&state_owned, chat_id).await;
+                                let _ = bot_owned.delete_message(chat_id, placeholder_msg_id).await;
+                            }
                         }
                         Err(e2) => {
-                            println!("  [{ts}]   ⚠ send_long_message failed (plain): {e2}");
+                            logln!("  [{ts}]   ⚠ send_long_message failed (plain): {e2}");
                             // Last resort: edit placeholder with truncated plain text
                             shared_rate_limit_wait(&state_owned, chat_id).await;
-                            let truncated = truncate_str(&full_response, TELEGRAM_MSG_LIMIT);
+                            let truncated = append_footer(
+                                &capped_response,
+                                footer.as_deref(),
+                                Some(TELEGRAM_MSG_LIMIT),
+                            );
                             let _ = bot_owned
                                 .edit_message_text(chat_id, placeholder_msg_id, &truncated)
                                 .await;
@@ -495,17 +1245,93 @@ pub(super) async fn handle_text_message(
             }
         }
 
+        if let Some(mirror) = mirror.take() {
+            mirror
+                .finish(&bot_owned, &shown_response, &state_owned)
+                .await;
+        }
+
+        // Warnings the backend printed to stderr despite succeeding (/stderr or /verbose)
+        if let Some(output) = backend_stderr.filter(|_| show_stderr) {
+            shared_rate_limit_wait(&state_owned, chat_id).await;
+            if let Err(e) = bot_owned
+                .send_message(chat_id, stderr_note_html(&output))
+                .parse_mode(ParseMode::Html)
+                .await
+            {
+                let ts = chrono::Local::now().format("%H:%M:%S");
+                logln!("  [{ts}]   ⚠ stderr note failed: {e}");
+            }
+        }
+
         // Clean up leftover "Stopping..." message if /stop raced with normal completion
         if let Some(msg_id) = stop_msg_id {
             shared_rate_limit_wait(&state_owned, chat_id).await;
             let _ = bot_owned.delete_message(chat_id, msg_id).await;
         }
 
+        // Offer file-tagged code blocks for writing (/extract on)
+        let (extract_enabled, autodownload_enabled) = {
+            let data = state_owned.lock().await;
+            (
+                ChatToggle::Extract.is_enabled(&data.settings, chat_id),
+                ChatToggle::AutoDownload.is_enabled(&data.settings, chat_id),
+            )
+        };
+        if extract_enabled {
+            if let Err(e) = offer_code_extraction(
+                &bot_owned,
+                chat_id,
+                &full_response,
+                &current_path,
+                &state_owned,
+            )
+            .await
+            {
+                let ts = chrono::Local::now().format("%H:%M:%S");
+                logln!("  [{ts}]   ⚠ extraction offer failed: {e}");
+            }
+        }
+
+        // Send the files the turn wrote (/autodownload on)
+        if autodownload_enabled && !changed_files.is_empty() {
+            if let Err(e) = offer_changed_files(
+                &bot_owned,
+                chat_id,
+                &changed_files,
+                &current_path,
+                &state_owned,
+            )
+            .await
+            {
+                let ts = chrono::Local::now().format("%H:%M:%S");
+                logln!("  [{ts}]   ⚠ changed file download failed: {e}");
+            }
+        }
+
+        record_backend_result(&state_owned, chat_id, backend_error.is_some()).await;
+        if let Some(error) = &backend_error {
+            notify_owner_of_failure(&bot_owned, &state_owned, chat_id, &user_text_owned, error)
+                .await;
+        }
+
+        let truncated = looks_truncated(&full_response);
+
         // Update session state: push user message + assistant response together
         // Skip if session was cleared while we were running (race with /clear)
         {
             let mut data = state_owned.lock().await;
+            if ChatToggle::AuditLog.is_enabled(&data.settings, chat_id) {
+                append_audit_log(chat_id, user_text_owned.clone(), full_response.clone());
+            }
+            // With /compact on, maybe_auto_compact caps the history once the oldest items are
+            // summarized, so capping here would drop the items it is about to fold
+            let compact = ChatToggle::Compact.is_enabled(&data.settings, chat_id);
             if let Some(session) = data.sessions.get_mut(&chat_id) {
+                session.last_response_msg_id = response_msg_id;
+                if let Some(msg_id) = response_msg_id {
+                    track_response(&mut session.recent_responses, msg_id, &user_text_owned);
+                }
                 if session.cleared {
                     // Session was cleared by /clear; do not re-populate
                 } else {
@@ -516,20 +1342,191 @@ pub(super) async fn handle_text_message(
                         item_type: HistoryType::User,
                         content: user_text_owned,
                     });
-                    session.history.push(HistoryItem {
-                        item_type: HistoryType::Assistant,
-                        content: full_response,
+                    // Failed requests are kept as errors, stamped for /errors
+                    session.history.push(match &backend_error {
+                        Some(error) => HistoryItem {
+                            item_type: HistoryType::Error,
+                            content: format!(
+                                "[{}] {error}",
+                                chrono::Local::now().format("%Y-%m-%d %H:%M:%S")
+                            ),
+                        },
+                        None => HistoryItem {
+                            item_type: HistoryType::Assistant,
+                            content: full_response,
+                        },
                     });
-                    enforce_history_cap(&mut session.history);
+                    if !compact {
+                        enforce_history_cap(&mut session.history);
+                    }
 
                     save_session_to_file(session, &current_path);
                 }
             }
         }
 
+        // Fold the oldest history into a summary, then apply the cap (/compact on)
+        maybe_auto_compact(&bot_owned, chat_id, &state_owned).await;
+
+        // Offer to continue a response that apparently stopped mid-output
+        if truncated {
+            if let Err(e) = offer_continue(&bot_owned, chat_id, &state_owned).await {
+                let ts = chrono::Local::now().format("%H:%M:%S");
+                logln!("  [{ts}]   ⚠ continue offer failed: {e}");
+            }
+        }
+
         let ts = chrono::Local::now().format("%H:%M:%S");
-        println!("  [{ts}] ▶ Response sent");
+        logln!("  [{ts}] ▶ Response sent");
     });
 
-    Ok(())
+    Ok(true)
+}
+
+/// Chat action matching a tool call: sending a file (`--sendfile`) or writing one shows an
+/// upload, everything else typing
+fn chat_action_for_tool(name: &str, input: &str) -> ChatAction {
+    if name == "Bash" {
+        let Some(rest) = input.split("--sendfile").nth(1) else {
+            return ChatAction::Typing;
+        };
+        let file = rest.split_whitespace().next().unwrap_or("");
+        let is_image = Path::new(file.trim_matches(|c| c == '"' || c == '\''))
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| {
+                matches!(
+                    ext.to_ascii_lowercase().as_str(),
+                    "png" | "jpg" | "jpeg" | "gif" | "webp"
+                )
+            });
+        return if is_image {
+            ChatAction::UploadPhoto
+        } else {
+            ChatAction::UploadDocument
+        };
+    }
+    match name {
+        "Write" | "NotebookEdit" => ChatAction::UploadDocument,
+        _ => ChatAction::Typing,
+    }
+}
+
+/// Shown when the backend finished the turn without error but produced no text
+fn silent_completion_notice(tool_runs: usize) -> String {
+    if tool_runs > 0 {
+        i18n::MSG_NO_RESPONSE_AFTER_TOOLS.replace("{count}", &tool_runs.to_string())
+    } else {
+        i18n::MSG_NO_RESPONSE_SILENT.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_sendfile_instructions() {
+        let default = render_sendfile_instructions(
+            i18n::SENDFILE_INSTRUCTIONS_TEMPLATE,
+            "opencodex",
+            ChatId(-100),
+            "abc",
+            " --api-url http://localhost:8081/",
+        );
+        assert!(default.contains(
+            "opencodex --sendfile <filepath> --chat -100 --key abc --api-url http://localhost:8081/\n"
+        ));
+        assert!(!default.contains('{'));
+        assert_eq!(
+            render_sendfile_instructions(
+                "파일은 `{binary} --sendfile <경로> --chat {chat_id} --key {key}{api_url_arg}`로 보내세요.",
+                "mybot",
+                ChatId(7),
+                "k",
+                "",
+            ),
+            "파일은 `mybot --sendfile <경로> --chat 7 --key k`로 보내세요."
+        );
+    }
+
+    #[test]
+    fn test_interrupted_response() {
+        assert_eq!(
+            interrupted_response("Half an answer\n\n"),
+            format!("Half an answer\n\n{INTERRUPTED_MARKER}")
+        );
+        assert_eq!(interrupted_response("  \n"), INTERRUPTED_MARKER);
+    }
+
+    #[test]
+    fn test_stderr_note_html() {
+        assert_eq!(
+            stderr_note_html("warning: <old> flag\n"),
+            "<b>Backend stderr</b>\n<blockquote expandable>warning: &lt;old&gt; flag</blockquote>"
+        );
+        assert!(stderr_note_html(&"x".repeat(5000)).len() < MAX_STDERR_NOTE + 100);
+        let escaped = stderr_note_html(&"<&".repeat(2000));
+        assert!(escaped.len() < MAX_STDERR_NOTE + 100);
+        assert!(escaped.ends_with(";</blockquote>"));
+    }
+
+    #[test]
+    fn test_chat_action_for_tool() {
+        let sendfile = |file: &str| format!("opencodex --sendfile {file} --chat 1 --key k");
+        assert_eq!(
+            chat_action_for_tool("Bash", &sendfile("out/report.pdf")),
+            ChatAction::UploadDocument
+        );
+        assert_eq!(
+            chat_action_for_tool("Bash", &sendfile("'chart.PNG'")),
+            ChatAction::UploadPhoto
+        );
+        assert_eq!(
+            chat_action_for_tool("Bash", "cargo test"),
+            ChatAction::Typing
+        );
+        assert_eq!(
+            chat_action_for_tool("Write", "{\"file_path\":\"a.rs\"}"),
+            ChatAction::UploadDocument
+        );
+        assert_eq!(chat_action_for_tool("Read", "a.rs"), ChatAction::Typing);
+    }
+
+    #[test]
+    fn test_silent_completion_notice() {
+        assert_eq!(silent_completion_notice(0), i18n::MSG_NO_RESPONSE_SILENT);
+        let notice = silent_completion_notice(3);
+        assert!(notice.contains("3번"));
+        assert!(notice.contains("/lasttools"));
+    }
+
+    #[test]
+    fn test_tool_batch_single_step_renders_inline() {
+        let mut batch = ToolBatch::default();
+        batch.push_use("Bash ls".to_string());
+        batch.push_result("file.txt", 300);
+        assert_eq!(batch.render(), "\n\n⚙️ Bash ls\n\n✅ `file.txt`\n\n");
+    }
+
+    #[test]
+    fn test_tool_batch_collapses_consecutive_steps() {
+        let mut batch = ToolBatch::default();
+        batch.push_use("Bash ls".to_string());
+        batch.push_result("a\nb", 300);
+        batch.push_use("Bash `pwd`".to_string());
+        batch.push_result("/tmp", 300);
+        batch.push_use("Bash make".to_string());
+
+        // The failing call is taken out so its error can be shown prominently
+        assert_eq!(batch.take_pending_use().as_deref(), Some("Bash make"));
+
+        let mut out = String::new();
+        batch.flush_into(&mut out);
+        assert_eq!(
+            out,
+            "\n\n⚙️ 2 commands run\n```\n▶ Bash ls\na\nb\n▶ Bash pwd\n/tmp\n```\n"
+        );
+        assert!(batch.render().is_empty());
+    }
 }
@@ -0,0 +1,190 @@
+use teloxide::prelude::*;
+use teloxide::types::MessageId;
+
+use super::bot::{BotSettings, SharedState, TELEGRAM_MSG_LIMIT};
+use super::storage::save_bot_settings;
+use super::streaming::{send_long_message, shared_rate_limit_wait, truncate_str};
+
+/// Bytes of the prompt repeated at the top of each mirrored response
+const MIRROR_PROMPT_PREVIEW: usize = 300;
+
+/// Chat receiving a copy of this chat's AI responses (/mirror)
+pub(super) fn mirror_target(settings: &BotSettings, chat_id: ChatId) -> Option<ChatId> {
+    settings
+        .mirror_chats
+        .get(&chat_id.0.to_string())
+        .copied()
+        .map(ChatId)
+}
+
+/// Whether this chat is the audience of some other chat's mirror (input is ignored there)
+pub(super) fn is_mirror_target(settings: &BotSettings, chat_id: ChatId) -> bool {
+    settings
+        .mirror_chats
+        .values()
+        .any(|&target| target == chat_id.0)
+}
+
+/// A response being copied to the mirror chat as it streams
+pub(super) struct Mirror {
+    chat_id: ChatId,
+    message_id: MessageId,
+    header: String,
+    last_text: String,
+}
+
+impl Mirror {
+    /// Post the mirror placeholder for a request of `source` if the chat has a mirror.
+    /// Failures are logged and the request simply runs without mirroring.
+    pub(super) async fn start(
+        bot: &Bot,
+        source: ChatId,
+        prompt: &str,
+        state: &SharedState,
+    ) -> Option<Mirror> {
+        let target = {
+            let data = state.lock().await;
+            mirror_target(&data.settings, source)?
+        };
+        let header = format!(
+            "[Mirror of chat {}]\nPrompt: {}",
+            source.0,
+            truncate_str(prompt, MIRROR_PROMPT_PREVIEW)
+        );
+        shared_rate_limit_wait(state, target).await;
+        match bot.send_message(target, format!("{header}\n\n…")).await {
+            Ok(sent) => Some(Mirror {
+                chat_id: target,
+                message_id: sent.id,
+                header,
+                last_text: String::new(),
+            }),
+            Err(e) => {
+                let ts = chrono::Local::now().format("%H:%M:%S");
+                logln!("  [{ts}]   ⚠ Mirror to chat {} failed: {e}", target.0);
+                None
+            }
+        }
+    }
+
+    fn compose(&self, text: &str) -> String {
+        let room = TELEGRAM_MSG_LIMIT.saturating_sub(self.header.len() + 2);
+        format!("{}\n\n{}", self.header, truncate_str(text, room))
+    }
+
+    /// Show the response so far (plain text, edited in place)
+    pub(super) async fn update(&mut self, bot: &Bot, text: &str, state: &SharedState) {
+        let composed = self.compose(text);
+        if composed == self.last_text {
+            return;
+        }
+        shared_rate_limit_wait(state, self.chat_id).await;
+        if let Err(e) = bot
+            .edit_message_text(self.chat_id, self.message_id, &composed)
+            .await
+        {
+            let ts = chrono::Local::now().format("%H:%M:%S");
+            logln!("  [{ts}]   ⚠ edit_message failed (mirror): {e}");
+        }
+        self.last_text = composed;
+    }
+
+    /// Show the final response; long ones are sent as new messages
+    pub(super) async fn finish(mut self, bot: &Bot, text: &str, state: &SharedState) {
+        if self.header.len() + 2 + text.len() <= TELEGRAM_MSG_LIMIT {
+            self.update(bot, text, state).await;
+            return;
+        }
+        shared_rate_limit_wait(state, self.chat_id).await;
+        let _ = bot
+            .edit_message_text(self.chat_id, self.message_id, &self.header)
+            .await;
+        if let Err(e) = send_long_message(bot, self.chat_id, text, None, state).await {
+            let ts = chrono::Local::now().format("%H:%M:%S");
+            logln!("  [{ts}]   ⚠ send_long_message failed (mirror): {e}");
+        }
+    }
+}
+
+/// Handle /mirror command - copy this chat's AI responses to another chat in real time
+/// (owner-only). The mirror chat is read-only: messages sent there are ignored.
+/// Usage: /mirror              (show)
+///        /mirror <chat_id>
+///        /mirror off
+pub(super) async fn handle_mirror_command(
+    bot: &Bot,
+    chat_id: ChatId,
+    text: &str,
+    state: &SharedState,
+    token: &str,
+) -> ResponseResult<()> {
+    let arg = text.strip_prefix("/mirror").unwrap_or("").trim();
+    let chat_key = chat_id.0.to_string();
+    let usage = "/mirror <chat_id> — Copy this chat's AI responses to another chat as they \
+                 stream (the bot must be a member there)\n/mirror off — Stop";
+
+    let response_msg = match arg {
+        "" => {
+            let data = state.lock().await;
+            match mirror_target(&data.settings, chat_id) {
+                Some(target) => format!("Mirroring AI responses to chat {}.\n\n{usage}", target.0),
+                None => format!("No mirror for this chat.\n\n{usage}"),
+            }
+        }
+        "off" => {
+            let mut data = state.lock().await;
+            if data.settings.mirror_chats.remove(&chat_key).is_some() {
+                save_bot_settings(token, &data.settings);
+                "Mirror stopped.".to_string()
+            } else {
+                "No mirror for this chat.".to_string()
+            }
+        }
+        _ => match arg.parse::<i64>() {
+            Ok(target) if target == chat_id.0 => "A chat cannot mirror itself.".to_string(),
+            Ok(target) => {
+                // Announcing the mirror also checks that the bot can post there
+                shared_rate_limit_wait(state, ChatId(target)).await;
+                let announced = bot
+                    .send_message(
+                        ChatId(target),
+                        format!(
+                            "This chat now mirrors the AI responses of chat {}. \
+                             It is read-only: messages here are ignored.",
+                            chat_id.0
+                        ),
+                    )
+                    .await;
+                match announced {
+                    Ok(_) => {
+                        let mut data = state.lock().await;
+                        data.settings.mirror_chats.insert(chat_key, target);
+                        save_bot_settings(token, &data.settings);
+                        format!("AI responses of this chat are now mirrored to chat {target}.")
+                    }
+                    Err(e) => format!("Cannot post to chat {target}: {e}"),
+                }
+            }
+            Err(_) => format!("Invalid chat ID: {arg}\n\n{usage}"),
+        },
+    };
+
+    shared_rate_limit_wait(state, chat_id).await;
+    bot.send_message(chat_id, response_msg).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mirror_lookup() {
+        let mut settings = BotSettings::default();
+        settings.mirror_chats.insert("100".to_string(), -200);
+        assert_eq!(mirror_target(&settings, ChatId(100)), Some(ChatId(-200)));
+        assert_eq!(mirror_target(&settings, ChatId(-200)), None);
+        assert!(is_mirror_target(&settings, ChatId(-200)));
+        assert!(!is_mirror_target(&settings, ChatId(100)));
+    }
+}
@@ -1,11 +1,70 @@
+mod agents;
+mod alias;
+mod approval;
+mod audit_log;
+mod autodownload;
+mod backup;
 mod bot;
+mod bot_api;
+mod breaker;
+mod cat;
+mod chats;
+mod command_alias;
 mod commands;
+mod console_log;
+mod context;
+mod continuation;
+mod cooldown;
+mod cron;
+mod diskusage;
+mod effective_config;
+mod elevate;
+mod errors;
+mod extract;
+mod feedback;
 mod file_ops;
+mod footer;
+mod forward;
+mod greeting;
+mod inline;
+mod madmax;
+mod media_group;
+mod membership;
 mod message;
+mod mirror;
+mod mute;
+mod notify;
+mod paginate;
+mod paste;
+mod pending;
+mod preset_share;
+mod project_config;
+mod project_kind;
+mod rawstream;
+mod reaction_commands;
+mod recheck;
+mod restart;
+mod run;
+mod search;
+mod selftest;
+mod session_backup;
+mod session_reload;
+mod settings;
+mod shell_guard;
 mod storage;
 mod streaming;
+mod summary;
 mod tools;
+mod uptime;
+mod worktree;
 
-pub use commands::run_bot;
+pub use bot_api::{configure_api_url, new_bot, parse_api_url};
+pub use commands::{run_bot, WebhookConfig};
+pub use effective_config::configure_token_source;
+pub use file_ops::configure_download_attempts;
+pub use media_group::configure_upload_concurrency;
+pub use message::configure_sendfile_instructions;
 pub use storage::cleanup_stale_sessions;
+pub use storage::configure_session_compression;
 pub use storage::resolve_token_by_hash;
+pub use storage::{configure_persisted_history, parse_history_types};
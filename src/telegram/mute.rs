@@ -0,0 +1,73 @@
+use teloxide::prelude::*;
+
+use super::bot::{BotSettings, SharedState};
+use super::storage::save_bot_settings;
+use super::streaming::shared_rate_limit_wait;
+
+/// Whether the bot is muted in this chat (/mute)
+pub(super) fn is_muted(settings: &BotSettings, chat_id: ChatId) -> bool {
+    settings
+        .muted_chats
+        .get(&chat_id.0.to_string())
+        .copied()
+        .unwrap_or(false)
+}
+
+/// Whether a message gets through while the chat is muted: only the owner's commands do
+pub(super) fn passes_mute(is_owner: bool, text: Option<&str>) -> bool {
+    is_owner && text.is_some_and(|t| t.trim_start().starts_with('/'))
+}
+
+/// Handle /mute and /unmute commands - stop or resume responding in this chat (owner-only).
+/// While muted, every message is ignored except the owner's commands.
+pub(super) async fn handle_mute_command(
+    bot: &Bot,
+    chat_id: ChatId,
+    mute: bool,
+    state: &SharedState,
+    token: &str,
+) -> ResponseResult<()> {
+    let response_msg = {
+        let mut data = state.lock().await;
+        if is_muted(&data.settings, chat_id) == mute {
+            if mute {
+                "The bot is already muted in this chat. /unmute — Resume"
+            } else {
+                "The bot is not muted in this chat."
+            }
+        } else {
+            let chat_key = chat_id.0.to_string();
+            if mute {
+                data.settings.muted_chats.insert(chat_key, true);
+            } else {
+                data.settings.muted_chats.remove(&chat_key);
+            }
+            save_bot_settings(token, &data.settings);
+            if mute {
+                "Muted. Messages in this chat are ignored until /unmute \
+                 (the owner's commands still work)."
+            } else {
+                "Unmuted. The bot responds in this chat again."
+            }
+        }
+    };
+
+    shared_rate_limit_wait(state, chat_id).await;
+    bot.send_message(chat_id, response_msg).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_passes_mute() {
+        assert!(passes_mute(true, Some("/unmute")));
+        assert!(passes_mute(true, Some("/status")));
+        assert!(!passes_mute(true, Some("hello")));
+        assert!(!passes_mute(true, None));
+        assert!(!passes_mute(false, Some("/unmute")));
+        assert!(!passes_mute(false, Some(";run tests")));
+    }
+}
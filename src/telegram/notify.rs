@@ -0,0 +1,158 @@
+use std::time::{Duration, Instant};
+
+use teloxide::prelude::*;
+use teloxide::types::ParseMode;
+
+use super::bot::SharedState;
+use super::storage::save_bot_settings;
+use super::streaming::{html_escape, shared_rate_limit_wait, truncate_str};
+
+/// Minimum time between failure DMs to the owner.
+/// Failures inside the window are counted and mentioned in the next DM.
+const NOTIFY_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Rate-limit bookkeeping for owner failure DMs (in memory only)
+#[derive(Debug, Default)]
+pub(super) struct OwnerNotifyState {
+    last_sent: Option<Instant>,
+    suppressed: u32,
+}
+
+impl OwnerNotifyState {
+    /// Decide whether a DM may be sent now. Returns the number of failures suppressed
+    /// since the last DM, or None if this failure must be suppressed too.
+    fn admit(&mut self, now: Instant) -> Option<u32> {
+        if let Some(last) = self.last_sent {
+            if now.duration_since(last) < NOTIFY_INTERVAL {
+                self.suppressed += 1;
+                return None;
+            }
+        }
+        self.last_sent = Some(now);
+        Some(std::mem::take(&mut self.suppressed))
+    }
+}
+
+/// DM the owner about a failed AI request or shell command in another chat.
+/// No-op unless enabled with /notify on and the owner's private chat is known.
+pub(super) async fn notify_owner_of_failure(
+    bot: &Bot,
+    state: &SharedState,
+    chat_id: ChatId,
+    command: &str,
+    error: &str,
+) {
+    let target = {
+        let mut data = state.lock().await;
+        match data.settings.owner_dm_chat_id {
+            Some(dm) if data.settings.error_notify && dm != chat_id.0 => data
+                .owner_notify
+                .admit(Instant::now())
+                .map(|suppressed| (ChatId(dm), suppressed)),
+            _ => None,
+        }
+    };
+    let Some((dm_chat, suppressed)) = target else {
+        return;
+    };
+
+    let mut msg = format!(
+        "⚠ <b>Failure in chat</b> <code>{}</code>\nCommand: <code>{}</code>\n<pre>{}</pre>",
+        chat_id.0,
+        html_escape(&truncate_str(command, 200)),
+        html_escape(&truncate_str(error, 1500))
+    );
+    if suppressed > 0 {
+        msg.push_str(&format!(
+            "\n(+{suppressed} more failure(s) since the last notice)"
+        ));
+    }
+
+    shared_rate_limit_wait(state, dm_chat).await;
+    if let Err(e) = bot
+        .send_message(dm_chat, msg)
+        .parse_mode(ParseMode::Html)
+        .await
+    {
+        let ts = chrono::Local::now().format("%H:%M:%S");
+        logln!("  [{ts}]   ⚠ owner notification failed: {e}");
+    }
+}
+
+/// Handle /notify command - DM the owner when requests fail in other chats (bot-wide)
+/// Usage: /notify on | off  (no argument shows the current state)
+pub(super) async fn handle_notify_command(
+    bot: &Bot,
+    chat_id: ChatId,
+    text: &str,
+    state: &SharedState,
+    token: &str,
+) -> ResponseResult<()> {
+    let arg = text
+        .strip_prefix("/notify")
+        .unwrap_or("")
+        .trim()
+        .to_lowercase();
+
+    let response_msg = {
+        let mut data = state.lock().await;
+        let dm_note = if data.settings.owner_dm_chat_id.is_some() {
+            ""
+        } else {
+            "\n\nThe owner's private chat is not known yet: \
+             send any message to the bot in a private chat so notices can be delivered."
+        };
+        match arg.as_str() {
+            "on" | "off" => {
+                data.settings.error_notify = arg == "on";
+                save_bot_settings(token, &data.settings);
+                if data.settings.error_notify {
+                    format!(
+                        "Failure notifications <b>enabled</b>.\n\
+                         Backend errors and failed shell commands in other chats are sent to \
+                         you privately (at most one notice per {} seconds).{dm_note}",
+                        NOTIFY_INTERVAL.as_secs()
+                    )
+                } else {
+                    "Failure notifications <b>disabled</b>.".to_string()
+                }
+            }
+            "" => {
+                let status = if data.settings.error_notify {
+                    "enabled"
+                } else {
+                    "disabled"
+                };
+                format!(
+                    "Failure notifications are currently <b>{status}</b>.\n\n\
+                     <code>/notify on</code> — DM the owner when requests fail in other chats\n\
+                     <code>/notify off</code> — Stop notifications{dm_note}"
+                )
+            }
+            _ => "Usage: <code>/notify on</code> or <code>/notify off</code>".to_string(),
+        }
+    };
+
+    shared_rate_limit_wait(state, chat_id).await;
+    bot.send_message(chat_id, &response_msg)
+        .parse_mode(ParseMode::Html)
+        .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_owner_notify_rate_limit() {
+        let mut notify = OwnerNotifyState::default();
+        let start = Instant::now();
+        assert_eq!(notify.admit(start), Some(0));
+        assert_eq!(notify.admit(start + Duration::from_secs(5)), None);
+        assert_eq!(notify.admit(start + Duration::from_secs(30)), None);
+        assert_eq!(notify.admit(start + NOTIFY_INTERVAL), Some(2));
+        assert_eq!(notify.admit(start + NOTIFY_INTERVAL * 2), Some(0));
+    }
+}
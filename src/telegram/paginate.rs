@@ -0,0 +1,243 @@
+use teloxide::prelude::*;
+use teloxide::types::{CallbackQuery, InlineKeyboardButton, InlineKeyboardMarkup, ParseMode};
+
+use crate::auth;
+
+use super::bot::{is_public_chat, owner_of, SharedState};
+use super::chats::chats_content;
+use super::elevate::is_elevated;
+use super::streaming::shared_rate_limit_wait;
+use super::tools::{allowed_tools_content, available_tools_content};
+
+/// Callback data prefix routed to this module
+pub(super) const PAGE_CALLBACK_PREFIX: &str = "page:";
+
+/// Items shown per page
+const PAGE_SIZE: usize = 10;
+
+/// A list that can be shown page by page. Pages are rebuilt from current state on every
+/// button press, so no per-message state is kept.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum PagedList {
+    AvailableTools,
+    AllowedTools,
+    Chats,
+}
+
+impl PagedList {
+    const ALL: [PagedList; 3] = [
+        PagedList::AvailableTools,
+        PagedList::AllowedTools,
+        PagedList::Chats,
+    ];
+
+    /// Identifier used in callback data
+    fn key(self) -> &'static str {
+        match self {
+            PagedList::AvailableTools => "tools",
+            PagedList::AllowedTools => "allowed",
+            PagedList::Chats => "chats",
+        }
+    }
+
+    fn from_key(key: &str) -> Option<PagedList> {
+        Self::ALL.into_iter().find(|l| l.key() == key)
+    }
+
+    /// Command that shows this list (its risk level gates the buttons)
+    fn command(self) -> &'static str {
+        match self {
+            PagedList::AvailableTools => "/availabletools",
+            PagedList::AllowedTools => "/allowedtools",
+            PagedList::Chats => "/chats",
+        }
+    }
+}
+
+/// Rendered (HTML) list items with a title and a footer shown on every page
+pub(super) struct ListContent {
+    pub title: String,
+    pub items: Vec<String>,
+    pub footer: String,
+}
+
+fn page_count(items: usize) -> usize {
+    items.div_ceil(PAGE_SIZE).max(1)
+}
+
+/// Render one page of a list. `page` is clamped to the last page.
+/// The keyboard is None when everything fits on a single page.
+pub(super) fn render_page(
+    list: PagedList,
+    content: &ListContent,
+    page: usize,
+) -> (String, Option<InlineKeyboardMarkup>) {
+    let pages = page_count(content.items.len());
+    let page = page.min(pages - 1);
+
+    let mut text = format!("<b>{}</b>", content.title);
+    if pages > 1 {
+        text.push_str(&format!(" ({}/{})", page + 1, pages));
+    }
+    text.push_str("\n\n");
+    for item in content.items.iter().skip(page * PAGE_SIZE).take(PAGE_SIZE) {
+        text.push_str(item);
+        text.push('\n');
+    }
+    if !content.footer.is_empty() {
+        text.push('\n');
+        text.push_str(&content.footer);
+    }
+
+    if pages == 1 {
+        return (text, None);
+    }
+    let mut row = Vec::new();
+    if page > 0 {
+        row.push(InlineKeyboardButton::callback(
+            "◀ Prev",
+            format!("{PAGE_CALLBACK_PREFIX}{}:{}", list.key(), page - 1),
+        ));
+    }
+    if page + 1 < pages {
+        row.push(InlineKeyboardButton::callback(
+            "Next ▶",
+            format!("{PAGE_CALLBACK_PREFIX}{}:{}", list.key(), page + 1),
+        ));
+    }
+    (text, Some(InlineKeyboardMarkup::new(vec![row])))
+}
+
+async fn list_content(list: PagedList, chat_id: ChatId, state: &SharedState) -> ListContent {
+    match list {
+        PagedList::AvailableTools => available_tools_content(),
+        PagedList::AllowedTools => {
+            let tools = {
+                let data = state.lock().await;
+                super::bot::get_allowed_tools(&data, chat_id)
+            };
+            allowed_tools_content(&tools)
+        }
+        PagedList::Chats => {
+            let data = state.lock().await;
+            chats_content(&data)
+        }
+    }
+}
+
+/// Send the first page of a list, with Prev/Next buttons if it spans several pages
+pub(super) async fn send_paged_list(
+    bot: &Bot,
+    chat_id: ChatId,
+    list: PagedList,
+    state: &SharedState,
+) -> ResponseResult<()> {
+    let content = list_content(list, chat_id, state).await;
+    let (text, keyboard) = render_page(list, &content, 0);
+
+    shared_rate_limit_wait(state, chat_id).await;
+    let mut req = bot.send_message(chat_id, text).parse_mode(ParseMode::Html);
+    if let Some(keyboard) = keyboard {
+        req = req.reply_markup(keyboard);
+    }
+    req.await?;
+
+    Ok(())
+}
+
+/// Handle a Prev/Next press. `data` is the callback data without the prefix: `<list>:<page>`.
+pub(super) async fn handle_page_callback(
+    bot: &Bot,
+    query: &CallbackQuery,
+    data: &str,
+    state: &SharedState,
+) -> ResponseResult<()> {
+    let Some(message) = query.message.as_ref() else {
+        bot.answer_callback_query(&query.id).await?;
+        return Ok(());
+    };
+    let chat_id = message.chat().id;
+
+    let (key, page) = data.split_once(':').unwrap_or((data, "0"));
+    let Some(list) = PagedList::from_key(key) else {
+        bot.answer_callback_query(&query.id).await?;
+        return Ok(());
+    };
+    let page: usize = page.parse().unwrap_or(0);
+
+    // Same rules as typing the list's command in this chat
+    let allowed = {
+        let data = state.lock().await;
+        let is_public_chat = is_public_chat(&data.settings, chat_id);
+        let permission = auth::get_permission_level(
+            query.from.id.0,
+            owner_of(&data.settings),
+            is_public_chat,
+            is_elevated(&data, chat_id, query.from.id.0),
+        );
+        auth::can_execute_message(permission, list.command())
+    };
+    if !allowed {
+        bot.answer_callback_query(&query.id)
+            .text("Permission denied.")
+            .await?;
+        return Ok(());
+    }
+
+    let content = list_content(list, chat_id, state).await;
+    let (text, keyboard) = render_page(list, &content, page);
+
+    bot.answer_callback_query(&query.id).await?;
+    shared_rate_limit_wait(state, chat_id).await;
+    let mut req = bot
+        .edit_message_text(chat_id, message.id(), text)
+        .parse_mode(ParseMode::Html);
+    if let Some(keyboard) = keyboard {
+        req = req.reply_markup(keyboard);
+    }
+    if let Err(e) = req.await {
+        let ts = chrono::Local::now().format("%H:%M:%S");
+        logln!("  [{ts}]   ⚠ edit_message failed (page): {e}");
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn content(n: usize) -> ListContent {
+        ListContent {
+            title: "Items".to_string(),
+            items: (1..=n).map(|i| format!("item {i}")).collect(),
+            footer: "Total".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_render_page_single_page_has_no_keyboard() {
+        let (text, keyboard) = render_page(PagedList::AvailableTools, &content(3), 0);
+        assert!(text.starts_with("<b>Items</b>\n\nitem 1\n"));
+        assert!(text.ends_with("\nTotal"));
+        assert!(keyboard.is_none());
+        assert_eq!(page_count(0), 1);
+    }
+
+    #[test]
+    fn test_render_page_navigation() {
+        let list = content(25);
+        let (text, keyboard) = render_page(PagedList::AllowedTools, &list, 1);
+        assert!(text.starts_with("<b>Items</b> (2/3)"));
+        assert!(text.contains("item 11\n") && text.contains("item 20\n"));
+        assert!(!text.contains("item 21\n"));
+        let buttons = keyboard.map(|k| k.inline_keyboard[0].len()).unwrap_or(0);
+        assert_eq!(buttons, 2);
+
+        // Out-of-range pages clamp to the last page, which only has "Prev"
+        let (text, keyboard) = render_page(PagedList::AllowedTools, &list, 9);
+        assert!(text.contains("(3/3)") && text.contains("item 25\n"));
+        let buttons = keyboard.map(|k| k.inline_keyboard[0].len()).unwrap_or(0);
+        assert_eq!(buttons, 1);
+    }
+}
@@ -0,0 +1,136 @@
+use std::time::Duration;
+
+use teloxide::prelude::*;
+use tokio::time::Instant;
+
+use super::bot::SharedState;
+use super::message::handle_text_message;
+use super::streaming::shared_rate_limit_wait;
+
+/// Telegram clients split text longer than 4096 UTF-16 units into several messages.
+/// A message this close to the limit is most likely the first part of such a paste.
+const SPLIT_PASTE_THRESHOLD: usize = 4000;
+
+/// Parts arriving within this long of the previous one are joined into the same prompt
+const PASTE_WINDOW: Duration = Duration::from_millis(1500);
+
+/// A long paste being collected from one user
+pub(super) struct PendingPaste {
+    parts: Vec<String>,
+    last_part: Instant,
+}
+
+/// Whether a prompt is long enough that Telegram probably split it
+fn looks_split(text: &str) -> bool {
+    text.encode_utf16().count() >= SPLIT_PASTE_THRESHOLD
+}
+
+/// Start collecting a paste if the user's `text` looks like the first part of a split
+/// message. `prompt` is what that text expands to (e.g. with reply context).
+/// Returns true if the prompt was held; it is sent once no more parts arrive.
+pub(super) async fn start_paste_if_split(
+    bot: &Bot,
+    chat_id: ChatId,
+    user_id: u64,
+    text: &str,
+    prompt: &str,
+    state: &SharedState,
+) -> bool {
+    if !looks_split(text) {
+        return false;
+    }
+    let key = (chat_id, user_id);
+    state.lock().await.pending_pastes.insert(
+        key,
+        PendingPaste {
+            parts: vec![prompt.to_string()],
+            last_part: Instant::now(),
+        },
+    );
+
+    let bot = bot.clone();
+    let state = state.clone();
+    tokio::spawn(async move {
+        // Wait until no part has arrived for a full window
+        let parts = loop {
+            let deadline = {
+                let data = state.lock().await;
+                match data.pending_pastes.get(&key) {
+                    Some(paste) => paste.last_part + PASTE_WINDOW,
+                    None => return,
+                }
+            };
+            tokio::time::sleep_until(deadline).await;
+            let mut data = state.lock().await;
+            if data
+                .pending_pastes
+                .get(&key)
+                .is_some_and(|paste| paste.last_part + PASTE_WINDOW <= Instant::now())
+            {
+                break data.pending_pastes.remove(&key).map(|paste| paste.parts);
+            }
+        };
+        if let Some(parts) = parts {
+            if let Err(e) = send_paste(&bot, chat_id, parts, &state).await {
+                let ts = chrono::Local::now().format("%H:%M:%S");
+                logln!("  [{ts}]   ⚠ Long paste failed: {e}");
+            }
+        }
+    });
+    true
+}
+
+/// Add a message to this user's paste in progress. Returns true if it was taken as a part.
+pub(super) async fn continue_paste(
+    chat_id: ChatId,
+    user_id: u64,
+    text: &str,
+    state: &SharedState,
+) -> bool {
+    let mut data = state.lock().await;
+    let Some(paste) = data.pending_pastes.get_mut(&(chat_id, user_id)) else {
+        return false;
+    };
+    paste.parts.push(text.to_string());
+    paste.last_part = Instant::now();
+    true
+}
+
+async fn send_paste(
+    bot: &Bot,
+    chat_id: ChatId,
+    parts: Vec<String>,
+    state: &SharedState,
+) -> ResponseResult<()> {
+    let ts = chrono::Local::now().format("%H:%M:%S");
+    logln!(
+        "  [{ts}]   ⧉ Joined a long paste from {} message(s)",
+        parts.len()
+    );
+    if parts.len() == 1 {
+        shared_rate_limit_wait(state, chat_id).await;
+        bot.send_message(
+            chat_id,
+            "This message is at Telegram's length limit and may have been cut off. \
+             Send long text as a file upload instead.",
+        )
+        .await?;
+    }
+    // Clients split at line breaks, which are dropped from the end of each part. This runs
+    // outside the per-chat worker; handle_text_message refuses it if a request is running.
+    handle_text_message(bot, chat_id, &parts.join("\n"), state).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_looks_split_counts_utf16_units() {
+        assert!(!looks_split("short prompt"));
+        assert!(looks_split(&"a".repeat(SPLIT_PASTE_THRESHOLD)));
+        // Each of these takes two UTF-16 units
+        assert!(looks_split(&"😀".repeat(SPLIT_PASTE_THRESHOLD / 2)));
+        assert!(!looks_split(&"가".repeat(SPLIT_PASTE_THRESHOLD - 1)));
+    }
+}
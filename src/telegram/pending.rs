@@ -0,0 +1,118 @@
+use teloxide::prelude::*;
+
+use crate::i18n;
+
+use super::bot::{ChatSession, SharedState};
+use super::diskusage::format_bytes;
+use super::forward::forwarded_text;
+use super::streaming::{shared_rate_limit_wait, truncate_str};
+
+/// Characters of a held forward shown by /pending
+const MAX_FORWARD_PREVIEW: usize = 80;
+
+/// One line per item the next prompt will carry: upload records, held forwards (/forwardwait)
+/// and staged files (/context)
+fn pending_lines(session: &ChatSession) -> Vec<String> {
+    let uploads = session.pending_uploads.iter().map(|record| {
+        format!(
+            "• upload: {}",
+            record.strip_prefix("[File uploaded] ").unwrap_or(record)
+        )
+    });
+    let forwards = session.pending_forwards.iter().map(|forward| {
+        let first_line = forwarded_text(forward)
+            .lines()
+            .find(|l| !l.trim().is_empty())
+            .unwrap_or("");
+        format!(
+            "• forward: {}",
+            truncate_str(first_line.trim(), MAX_FORWARD_PREVIEW)
+        )
+    });
+    let contexts = session
+        .pending_contexts
+        .iter()
+        .map(|(path, block)| format!("• context: {path} ({})", format_bytes(block.len() as u64)));
+    uploads.chain(forwards).chain(contexts).collect()
+}
+
+/// Handle /pending command - list what the next prompt will carry, or drop it
+/// Usage: /pending          (list)
+///        /pending clear
+pub(super) async fn handle_pending_command(
+    bot: &Bot,
+    chat_id: ChatId,
+    text: &str,
+    state: &SharedState,
+) -> ResponseResult<()> {
+    let arg = text.strip_prefix("/pending").unwrap_or("").trim();
+
+    let response_msg = {
+        let mut data = state.lock().await;
+        match (data.sessions.get_mut(&chat_id), arg) {
+            (None, _) => i18n::MSG_NO_SESSION.to_string(),
+            (Some(session), "") => {
+                let lines = pending_lines(session);
+                if lines.is_empty() {
+                    "Nothing is waiting for the next prompt.".to_string()
+                } else {
+                    format!(
+                        "Included with your next prompt ({}):\n{}\n\n/pending clear — Drop all",
+                        lines.len(),
+                        lines.join("\n")
+                    )
+                }
+            }
+            (Some(session), "clear") => {
+                let count = pending_lines(session).len();
+                session.pending_uploads.clear();
+                session.pending_forwards.clear();
+                session.pending_contexts.clear();
+                format!(
+                    "Dropped {count} pending item(s). Uploaded files stay in the session directory."
+                )
+            }
+            (Some(_), _) => "Usage: /pending [clear]".to_string(),
+        }
+    };
+
+    shared_rate_limit_wait(state, chat_id).await;
+    bot.send_message(chat_id, response_msg).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use teloxide::types::MessageOrigin;
+
+    use super::super::forward::forwarded_prompt;
+
+    #[test]
+    fn test_pending_lines() {
+        let mut session = ChatSession::default();
+        assert!(pending_lines(&session).is_empty());
+
+        session
+            .pending_uploads
+            .push("[File uploaded] a.png → /tmp/p/a.png (10 bytes, binary)".to_string());
+        let origin = MessageOrigin::HiddenUser {
+            date: chrono::Utc::now(),
+            sender_user_name: "Alice".to_string(),
+        };
+        session
+            .pending_forwards
+            .push(forwarded_prompt(&origin, "\nhello there\nsecond line"));
+        session
+            .pending_contexts
+            .push(("src/main.rs".to_string(), "x".repeat(2048)));
+        assert_eq!(
+            pending_lines(&session),
+            vec![
+                "• upload: a.png → /tmp/p/a.png (10 bytes, binary)".to_string(),
+                "• forward: hello there".to_string(),
+                "• context: src/main.rs (2.0 KB)".to_string(),
+            ]
+        );
+    }
+}
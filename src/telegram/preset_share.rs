@@ -0,0 +1,239 @@
+use serde::{Deserialize, Serialize};
+use teloxide::prelude::*;
+use teloxide::types::{InputFile, ParseMode};
+
+use super::bot::{get_allowed_tools, SharedData, SharedState, TELEGRAM_MSG_LIMIT};
+use super::settings::{MAX_AFFIX_LEN, MAX_RESPONSE_RANGE};
+use super::storage::save_bot_settings;
+use super::streaming::{html_escape, shared_rate_limit_wait};
+use super::tools::{normalize_tool_name, ALL_TOOLS};
+
+/// Format version written by /preset export; other versions are refused on import
+const PRESET_VERSION: u32 = 1;
+
+/// A chat's tool list and prompt settings as a portable JSON string (/preset export|import).
+/// Applying it makes the chat match: missing fields clear the chat's own setting.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+struct SharedPreset {
+    v: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tools: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    prefix: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    suffix: Option<String>,
+    /// Response length cap in characters (/maxresponse)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    max_response: Option<usize>,
+}
+
+/// The chat's effective settings as a preset
+fn export_preset(data: &SharedData, chat_id: ChatId) -> SharedPreset {
+    let chat_key = chat_id.0.to_string();
+    SharedPreset {
+        v: PRESET_VERSION,
+        tools: Some(get_allowed_tools(data, chat_id)),
+        prefix: data.settings.prompt_prefixes.get(&chat_key).cloned(),
+        suffix: data.settings.prompt_suffixes.get(&chat_key).cloned(),
+        max_response: data.settings.max_response_chars.get(&chat_key).copied(),
+    }
+}
+
+/// Parse and validate a preset: unknown tools are dropped and the response cap is clamped,
+/// each with a note for the reply. Over-long prefixes/suffixes reject the whole preset.
+fn parse_preset(json: &str) -> Result<(SharedPreset, Vec<String>), String> {
+    let mut preset: SharedPreset =
+        serde_json::from_str(json).map_err(|e| format!("Not a valid preset: {e}"))?;
+    if preset.v != PRESET_VERSION {
+        return Err(format!(
+            "Unsupported preset version {} (expected {PRESET_VERSION}).",
+            preset.v
+        ));
+    }
+    for (label, affix) in [("Prefix", &preset.prefix), ("Suffix", &preset.suffix)] {
+        if affix.as_ref().is_some_and(|a| a.len() > MAX_AFFIX_LEN) {
+            return Err(format!("{label} is too long (max {MAX_AFFIX_LEN} bytes)."));
+        }
+    }
+
+    let mut notes = Vec::new();
+    if let Some(tools) = preset.tools.take() {
+        let mut known: Vec<String> = Vec::new();
+        let mut skipped = Vec::new();
+        for tool in tools {
+            // MCP tools come from the backend's configuration and keep their exact name
+            let name = if tool.to_lowercase().starts_with("mcp__") {
+                tool
+            } else {
+                normalize_tool_name(&tool)
+            };
+            if name.starts_with("mcp__") || ALL_TOOLS.iter().any(|(n, _, _)| *n == name) {
+                if !known.contains(&name) {
+                    known.push(name);
+                }
+            } else {
+                skipped.push(name);
+            }
+        }
+        if !skipped.is_empty() {
+            notes.push(format!("Skipped unknown tools: {}", skipped.join(", ")));
+        }
+        preset.tools = Some(known);
+    }
+    if let Some(max) = preset.max_response {
+        let clamped = max.clamp(*MAX_RESPONSE_RANGE.start(), *MAX_RESPONSE_RANGE.end());
+        if clamped != max {
+            notes.push(format!("Response cap {max} adjusted to {clamped}"));
+            preset.max_response = Some(clamped);
+        }
+    }
+    Ok((preset, notes))
+}
+
+/// Apply a validated preset to the chat's settings
+fn apply_preset(data: &mut SharedData, chat_id: ChatId, preset: SharedPreset) {
+    let chat_key = chat_id.0.to_string();
+    let settings = &mut data.settings;
+    match preset.tools {
+        Some(tools) => settings.allowed_tools.insert(chat_key.clone(), tools),
+        None => settings.allowed_tools.remove(&chat_key),
+    };
+    match preset.prefix {
+        Some(prefix) => settings.prompt_prefixes.insert(chat_key.clone(), prefix),
+        None => settings.prompt_prefixes.remove(&chat_key),
+    };
+    match preset.suffix {
+        Some(suffix) => settings.prompt_suffixes.insert(chat_key.clone(), suffix),
+        None => settings.prompt_suffixes.remove(&chat_key),
+    };
+    match preset.max_response {
+        Some(max) => settings.max_response_chars.insert(chat_key, max),
+        None => settings.max_response_chars.remove(&chat_key),
+    };
+}
+
+/// Handle /preset export and /preset import <json> - share the chat's allowed tools, prompt
+/// prefix/suffix and response cap with another chat
+pub(super) async fn handle_preset_share(
+    bot: &Bot,
+    chat_id: ChatId,
+    export: bool,
+    json: &str,
+    state: &SharedState,
+    token: &str,
+) -> ResponseResult<()> {
+    if export {
+        let command = {
+            let data = state.lock().await;
+            let preset = export_preset(&data, chat_id);
+            format!(
+                "/preset import {}",
+                serde_json::to_string(&preset).unwrap_or_default()
+            )
+        };
+        let message = format!(
+            "This chat's preset. Send it in another chat to apply it there:\n\n<code>{}</code>",
+            html_escape(&command)
+        );
+        shared_rate_limit_wait(state, chat_id).await;
+        if message.len() <= TELEGRAM_MSG_LIMIT {
+            bot.send_message(chat_id, message)
+                .parse_mode(ParseMode::Html)
+                .await?;
+        } else {
+            bot.send_document(
+                chat_id,
+                InputFile::memory(command.into_bytes()).file_name("preset.txt"),
+            )
+            .caption("This chat's preset. Send the file's text in another chat to apply it.")
+            .await?;
+        }
+        return Ok(());
+    }
+
+    let response_msg = if json.is_empty() {
+        "Usage: <code>/preset import &lt;preset&gt;</code> (the text from <code>/preset export</code>)"
+            .to_string()
+    } else {
+        match parse_preset(json) {
+            Ok((preset, notes)) => {
+                let tools = preset.tools.as_ref().map(|t| t.len());
+                let mut data = state.lock().await;
+                apply_preset(&mut data, chat_id, preset);
+                save_bot_settings(token, &data.settings);
+                let mut lines = vec![format!(
+                    "Preset applied. {}",
+                    match tools {
+                        Some(count) => format!("{count} allowed tool(s)."),
+                        None => "Allowed tools reset to the defaults.".to_string(),
+                    }
+                )];
+                lines.extend(notes.iter().map(|note| html_escape(note)));
+                lines.join("\n")
+            }
+            Err(e) => html_escape(&e),
+        }
+    };
+
+    shared_rate_limit_wait(state, chat_id).await;
+    bot.send_message(chat_id, &response_msg)
+        .parse_mode(ParseMode::Html)
+        .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_preset() {
+        let (preset, notes) = parse_preset(
+            r#"{"v":1,"tools":["read","Bash","Browse","mcp__github__search","Read"],"prefix":"Be brief.","max_response":50}"#,
+        )
+        .unwrap_or_default();
+        assert_eq!(
+            preset,
+            SharedPreset {
+                v: 1,
+                tools: Some(vec![
+                    "Read".to_string(),
+                    "Bash".to_string(),
+                    "mcp__github__search".to_string()
+                ]),
+                prefix: Some("Be brief.".to_string()),
+                suffix: None,
+                max_response: Some(200),
+            }
+        );
+        assert_eq!(
+            notes,
+            vec![
+                "Skipped unknown tools: Browse".to_string(),
+                "Response cap 50 adjusted to 200".to_string()
+            ]
+        );
+
+        assert!(parse_preset(r#"{"v":2}"#).is_err());
+        assert!(parse_preset("not json").is_err());
+        let long = format!(r#"{{"v":1,"suffix":"{}"}}"#, "x".repeat(MAX_AFFIX_LEN + 1));
+        assert!(parse_preset(&long).is_err());
+    }
+
+    #[test]
+    fn test_preset_roundtrip() {
+        let preset = SharedPreset {
+            v: PRESET_VERSION,
+            tools: Some(vec!["Read".to_string(), "Grep".to_string()]),
+            suffix: Some("Answer in English.".to_string()),
+            ..SharedPreset::default()
+        };
+        let json = serde_json::to_string(&preset).unwrap_or_default();
+        assert_eq!(
+            json,
+            r#"{"v":1,"tools":["Read","Grep"],"suffix":"Answer in English."}"#
+        );
+        assert_eq!(parse_preset(&json).map(|(p, _)| p), Ok(preset));
+    }
+}
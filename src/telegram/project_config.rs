@@ -0,0 +1,129 @@
+use std::path::Path;
+
+use serde::Deserialize;
+
+/// File name looked up in the session directory on /start and /cd
+pub(super) const PROJECT_CONFIG_FILE: &str = ".opencodex.toml";
+
+/// Project-local defaults read from `.opencodex.toml`.
+/// These override bot defaults but yield to explicit per-chat commands (e.g. /allowed).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(super) struct ProjectConfig {
+    /// Model passed to the backend (`--model`)
+    pub model: Option<String>,
+    /// Allowed tools used when the chat has no /allowed list of its own
+    pub allowed_tools: Option<Vec<String>>,
+    /// Extra project instructions appended to the bot's system prompt
+    pub system_prompt: Option<String>,
+    /// Timeout for `!` shell commands, in seconds
+    pub shell_timeout: Option<u64>,
+    /// Maximum bytes of `!` command output kept in memory (stdout and stderr combined)
+    pub shell_output_limit: Option<u64>,
+}
+
+impl ProjectConfig {
+    /// Names of the fields set in this config (for the /start summary)
+    pub fn field_names(&self) -> Vec<&'static str> {
+        let mut names = Vec::new();
+        if self.model.is_some() {
+            names.push("model");
+        }
+        if self.allowed_tools.is_some() {
+            names.push("allowed_tools");
+        }
+        if self.system_prompt.is_some() {
+            names.push("system_prompt");
+        }
+        if self.shell_timeout.is_some() {
+            names.push("shell_timeout");
+        }
+        if self.shell_output_limit.is_some() {
+            names.push("shell_output_limit");
+        }
+        names
+    }
+}
+
+/// Parse the contents of a project config file
+pub(super) fn parse_project_config(content: &str) -> Result<ProjectConfig, String> {
+    let config: ProjectConfig = toml::from_str(content).map_err(|e| e.message().to_string())?;
+    if config.shell_timeout == Some(0) {
+        return Err("shell_timeout must be greater than 0".to_string());
+    }
+    if config.shell_output_limit == Some(0) {
+        return Err("shell_output_limit must be greater than 0".to_string());
+    }
+    if let Some(model) = &config.model {
+        if model.trim().is_empty() || model.starts_with('-') {
+            return Err(format!("invalid model name: {model}"));
+        }
+    }
+    Ok(config)
+}
+
+/// Load `.opencodex.toml` from `dir`.
+/// Returns Ok(None) when the file does not exist.
+pub(super) fn load_project_config(dir: &str) -> Result<Option<ProjectConfig>, String> {
+    let path = Path::new(dir).join(PROJECT_CONFIG_FILE);
+    if !path.is_file() {
+        return Ok(None);
+    }
+    let content = std::fs::read_to_string(&path).map_err(|e| e.to_string())?;
+    parse_project_config(&content).map(Some)
+}
+
+/// Load the project config for a session directory, turning errors into a user-facing note.
+/// Returns the config to apply (None on error) and an optional status line for the reply.
+pub(super) fn load_project_config_with_note(dir: &str) -> (Option<ProjectConfig>, Option<String>) {
+    match load_project_config(dir) {
+        Ok(Some(config)) => {
+            let fields = config.field_names();
+            let note = if fields.is_empty() {
+                format!("Loaded {PROJECT_CONFIG_FILE} (no settings).")
+            } else {
+                format!("Loaded {PROJECT_CONFIG_FILE}: {}", fields.join(", "))
+            };
+            (Some(config), Some(note))
+        }
+        Ok(None) => (None, None),
+        Err(e) => {
+            let ts = chrono::Local::now().format("%H:%M:%S");
+            logln!("  [{ts}]   ⚠ {PROJECT_CONFIG_FILE} in {dir}: {e}");
+            (
+                None,
+                Some(format!("Ignored {PROJECT_CONFIG_FILE} (parse error): {e}")),
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_project_config() {
+        let config = parse_project_config(
+            "model = \"o3\"\nallowed_tools = [\"Bash\", \"Read\"]\n\
+             system_prompt = \"Use tabs.\"\nshell_timeout = 120\nshell_output_limit = 4096\n",
+        )
+        .unwrap_or_default();
+        assert_eq!(config.model.as_deref(), Some("o3"));
+        assert_eq!(
+            config.allowed_tools,
+            Some(vec!["Bash".to_string(), "Read".to_string()])
+        );
+        assert_eq!(config.system_prompt.as_deref(), Some("Use tabs."));
+        assert_eq!(config.shell_timeout, Some(120));
+        assert_eq!(config.shell_output_limit, Some(4096));
+        assert_eq!(config.field_names().len(), 5);
+
+        assert_eq!(parse_project_config(""), Ok(ProjectConfig::default()));
+        assert!(parse_project_config("shell_timeout = 0").is_err());
+        assert!(parse_project_config("shell_output_limit = 0").is_err());
+        assert!(parse_project_config("model = \"--oss\"").is_err());
+        assert!(parse_project_config("unknown = 1").is_err());
+        assert!(parse_project_config("model = ").is_err());
+    }
+}
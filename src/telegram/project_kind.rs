@@ -0,0 +1,100 @@
+use std::path::Path;
+
+/// Marker files checked in the session directory: (file name, project type)
+const PROJECT_MARKERS: &[(&str, &str)] = &[
+    ("Cargo.toml", "Rust"),
+    ("go.mod", "Go"),
+    ("package.json", "Node.js"),
+    ("tsconfig.json", "TypeScript"),
+    ("deno.json", "Deno"),
+    ("pyproject.toml", "Python"),
+    ("setup.py", "Python"),
+    ("requirements.txt", "Python"),
+    ("Gemfile", "Ruby"),
+    ("composer.json", "PHP"),
+    ("pom.xml", "Java (Maven)"),
+    ("build.gradle", "JVM (Gradle)"),
+    ("build.gradle.kts", "JVM (Gradle)"),
+    ("CMakeLists.txt", "C/C++ (CMake)"),
+    ("mix.exs", "Elixir"),
+    ("Package.swift", "Swift"),
+    ("pubspec.yaml", "Dart/Flutter"),
+];
+
+/// Project types detected in a session directory, cached per session (/projecthint)
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(super) struct ProjectKind {
+    /// Directory the detection ran in; a different working directory is detected again
+    pub path: String,
+    /// (project type, marker file that revealed it), once per type
+    pub types: Vec<(&'static str, &'static str)>,
+}
+
+/// Look for well-known build and package files directly in `path`
+pub(super) fn detect_project_kind(path: &str) -> ProjectKind {
+    let dir = Path::new(path);
+    let mut types: Vec<(&'static str, &'static str)> = Vec::new();
+    for (marker, kind) in PROJECT_MARKERS {
+        if !types.iter().any(|(seen, _)| seen == kind) && dir.join(marker).is_file() {
+            types.push((kind, marker));
+        }
+    }
+    ProjectKind {
+        path: path.to_string(),
+        types,
+    }
+}
+
+impl ProjectKind {
+    /// System prompt section naming the project type; empty if nothing was recognized
+    pub(super) fn notice(&self) -> String {
+        if self.types.is_empty() {
+            return String::new();
+        }
+        let kinds: Vec<&str> = self.types.iter().map(|(kind, _)| *kind).collect();
+        let markers: Vec<&str> = self.types.iter().map(|(_, marker)| *marker).collect();
+        format!(
+            "\n\nPROJECT TYPE: The working directory looks like a {} project ({}). \
+             Use its usual tooling and conventions.",
+            kinds.join(" + "),
+            markers.join(", ")
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_detect_project_kind() {
+        let dir = std::env::temp_dir().join("opencodex_test_project_kind");
+        let _ = fs::remove_dir_all(&dir);
+        let _ = fs::create_dir_all(&dir);
+        let path = dir.to_string_lossy().to_string();
+        assert_eq!(detect_project_kind(&path).notice(), "");
+
+        for marker in ["Cargo.toml", "package.json", "requirements.txt", "setup.py"] {
+            let _ = fs::write(dir.join(marker), "");
+        }
+        // A directory with a marker's name is not a marker
+        let _ = fs::create_dir_all(dir.join("go.mod"));
+        let kind = detect_project_kind(&path);
+        assert_eq!(
+            kind.types,
+            vec![
+                ("Rust", "Cargo.toml"),
+                ("Node.js", "package.json"),
+                ("Python", "setup.py")
+            ]
+        );
+        assert_eq!(
+            kind.notice(),
+            "\n\nPROJECT TYPE: The working directory looks like a Rust + Node.js + Python \
+             project (Cargo.toml, package.json, setup.py). Use its usual tooling and conventions."
+        );
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,73 @@
+use std::sync::Arc;
+
+use teloxide::prelude::*;
+use teloxide::types::InputFile;
+
+use super::bot::SharedState;
+use super::streaming::shared_rate_limit_wait;
+
+/// Caption of the /rawstream file
+fn raw_stream_caption(lines: usize, dropped: usize) -> String {
+    if dropped > 0 {
+        format!("{lines} raw line(s) from the latest request (the first {dropped} were dropped)")
+    } else {
+        format!("{lines} raw line(s) from the latest request")
+    }
+}
+
+/// Handle /rawstream command - capture and show the backend's unparsed JSONL output (owner-only)
+/// Usage: /rawstream on|off
+///        /rawstream        (send the lines of the latest request as a file)
+pub(super) async fn handle_rawstream_command(
+    bot: &Bot,
+    chat_id: ChatId,
+    text: &str,
+    state: &SharedState,
+) -> ResponseResult<()> {
+    let arg = text.strip_prefix("/rawstream").unwrap_or("").trim();
+
+    let response_msg = match arg {
+        "on" => {
+            let mut data = state.lock().await;
+            data.raw_streams.entry(chat_id).or_insert_with(Arc::default);
+            "Raw output capture on. The backend's JSONL lines for each request are kept \
+             (latest request only, up to 200 lines).\n/rawstream — View\n/rawstream off — Stop capturing"
+                .to_string()
+        }
+        "off" => {
+            let mut data = state.lock().await;
+            if data.raw_streams.remove(&chat_id).is_some() {
+                "Raw output capture off.".to_string()
+            } else {
+                "Raw output capture is already off.".to_string()
+            }
+        }
+        "" => {
+            let log = state.lock().await.raw_streams.get(&chat_id).cloned();
+            match log.map(|log| log.snapshot()) {
+                None => "Raw output capture is off.\n/rawstream on — Keep the backend's JSONL \
+                         output of the next requests"
+                    .to_string(),
+                Some((lines, _)) if lines.is_empty() => {
+                    "No raw output captured yet. It is recorded from the next request.".to_string()
+                }
+                Some((lines, dropped)) => {
+                    let mut content = lines.join("\n");
+                    content.push('\n');
+                    let file = InputFile::memory(content.into_bytes()).file_name("rawstream.jsonl");
+                    shared_rate_limit_wait(state, chat_id).await;
+                    bot.send_document(chat_id, file)
+                        .caption(raw_stream_caption(lines.len(), dropped))
+                        .await?;
+                    return Ok(());
+                }
+            }
+        }
+        _ => "Usage: /rawstream on|off, or /rawstream to view the latest request's output"
+            .to_string(),
+    };
+
+    shared_rate_limit_wait(state, chat_id).await;
+    bot.send_message(chat_id, response_msg).await?;
+    Ok(())
+}
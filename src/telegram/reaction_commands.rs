@@ -0,0 +1,151 @@
+use teloxide::prelude::*;
+use teloxide::types::{MessageId, MessageReactionUpdated, ReactionType};
+
+use super::bot::{is_owner, ChatSession, SharedState};
+use super::commands::{handle_clear_command, handle_stop_command};
+use super::message::handle_text_message;
+
+/// Owner prompt messages remembered per session for reaction commands
+const MAX_TRACKED_PROMPTS: usize = 20;
+
+/// Action triggered by the owner reacting to one of their own prompt messages
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ReactionCommand {
+    /// Send the reacted prompt to the AI again
+    Rerun,
+    /// Stop the running request (same as /stop)
+    Stop,
+    /// Clear the session (same as /clear, including /clearconfirm)
+    Clear,
+}
+
+impl ReactionCommand {
+    /// Telegram only accepts reactions from a fixed emoji set, so the emoji are picked from it
+    fn from_emoji(emoji: &str) -> Option<Self> {
+        match emoji {
+            "⚡" => Some(ReactionCommand::Rerun),
+            "😴" => Some(ReactionCommand::Stop),
+            "🕊" => Some(ReactionCommand::Clear),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            ReactionCommand::Rerun => "rerun",
+            ReactionCommand::Stop => "stop",
+            ReactionCommand::Clear => "clear",
+        }
+    }
+}
+
+/// Command of an emoji this update added; reactions that were already there are ignored
+fn added_command(old: &[ReactionType], new: &[ReactionType]) -> Option<ReactionCommand> {
+    new.iter()
+        .filter(|r| !old.contains(r))
+        .filter_map(|r| r.emoji())
+        .find_map(|emoji| ReactionCommand::from_emoji(emoji))
+}
+
+/// Remember the prompt an owner message sent, keeping only the most recent entries
+pub(super) fn track_prompt(session: &mut ChatSession, msg_id: MessageId, prompt: &str) {
+    session.recent_prompts.push((msg_id, prompt.to_string()));
+    if session.recent_prompts.len() > MAX_TRACKED_PROMPTS {
+        let drain_count = session.recent_prompts.len() - MAX_TRACKED_PROMPTS;
+        session.recent_prompts.drain(..drain_count);
+    }
+}
+
+/// Handle ⚡ (rerun), 😴 (stop) and 🕊 (clear) reactions the owner puts on one of their own
+/// prompt messages. Returns false if the reaction is not such a command, so it can be treated
+/// as feedback instead.
+pub(super) async fn handle_reaction_command(
+    bot: &Bot,
+    reaction: &MessageReactionUpdated,
+    state: &SharedState,
+) -> ResponseResult<bool> {
+    let Some(command) = added_command(&reaction.old_reaction, &reaction.new_reaction) else {
+        return Ok(false);
+    };
+    let Some(user) = reaction.user() else {
+        return Ok(false);
+    };
+    let chat_id = reaction.chat.id;
+
+    let prompt = {
+        let data = state.lock().await;
+        if !is_owner(&data.settings, user.id.0) {
+            return Ok(false);
+        }
+        let Some(prompt) = data.sessions.get(&chat_id).and_then(|s| {
+            s.recent_prompts
+                .iter()
+                .find(|(id, _)| *id == reaction.message_id)
+                .map(|(_, prompt)| prompt.clone())
+        }) else {
+            // Not one of the owner's tracked prompts
+            return Ok(false);
+        };
+        prompt
+    };
+
+    let ts = chrono::Local::now().format("%H:%M:%S");
+    logln!(
+        "  [{ts}] ◀ [reaction] {} (msg:{})",
+        command.name(),
+        reaction.message_id.0
+    );
+    match command {
+        // handle_text_message refuses the rerun if a request is running
+        ReactionCommand::Rerun => handle_text_message(bot, chat_id, &prompt, state).await?,
+        ReactionCommand::Stop => handle_stop_command(bot, chat_id, state).await?,
+        ReactionCommand::Clear => {
+            if handle_clear_command(bot, chat_id, state).await? {
+                logln!("  [{ts}] ▶ [reaction] Session cleared");
+            }
+        }
+    }
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn emoji(e: &str) -> ReactionType {
+        ReactionType::Emoji {
+            emoji: e.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_added_command() {
+        assert_eq!(
+            added_command(&[], &[emoji("⚡")]),
+            Some(ReactionCommand::Rerun)
+        );
+        assert_eq!(
+            added_command(&[emoji("👍")], &[emoji("👍"), emoji("😴")]),
+            Some(ReactionCommand::Stop)
+        );
+        // Adding another emoji next to an existing command does not repeat it
+        assert_eq!(
+            added_command(&[emoji("🕊")], &[emoji("🕊"), emoji("👍")]),
+            None
+        );
+        assert_eq!(added_command(&[emoji("⚡")], &[]), None);
+    }
+
+    #[test]
+    fn test_track_prompt() {
+        let mut session = ChatSession::default();
+        for i in 0..MAX_TRACKED_PROMPTS as i32 + 5 {
+            track_prompt(&mut session, MessageId(i), &format!("prompt {i}"));
+        }
+        assert_eq!(session.recent_prompts.len(), MAX_TRACKED_PROMPTS);
+        assert_eq!(
+            session.recent_prompts[0],
+            (MessageId(5), "prompt 5".to_string())
+        );
+    }
+}
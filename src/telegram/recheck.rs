@@ -0,0 +1,87 @@
+use teloxide::prelude::*;
+use teloxide::types::{InputFile, ParseMode};
+
+use crate::session::HistoryType;
+
+use super::bot::SharedState;
+use super::streaming::{
+    markdown_to_telegram_html, send_long_message, shared_rate_limit_wait, truncate_str,
+};
+
+/// Text file with the exact converter input and outputs, for attaching to a bug report
+fn recheck_report(markdown: &str, html: &str, html_outcome: &str) -> String {
+    format!(
+        "=== Input: raw markdown of the last response ({} bytes) ===\n{markdown}\n\n\
+         === markdown_to_telegram_html output ({} bytes) ===\n{html}\n\n\
+         === Telegram: {html_outcome} ===\n\n\
+         === Plain-text fallback (sent without a parse mode when HTML is rejected) ===\n\
+         Identical to the input.\n",
+        markdown.len(),
+        html.len()
+    )
+}
+
+/// Handle /recheck command - re-run the last response's raw markdown through the HTML
+/// converter and the plain-text fallback, sending both plus a report file with the exact
+/// input and output, so rendering bugs can be reproduced and filed
+pub(super) async fn handle_recheck_command(
+    bot: &Bot,
+    chat_id: ChatId,
+    state: &SharedState,
+) -> ResponseResult<()> {
+    let last_response = {
+        let data = state.lock().await;
+        data.sessions.get(&chat_id).and_then(|s| {
+            s.history
+                .iter()
+                .rev()
+                .find(|item| matches!(item.item_type, HistoryType::Assistant))
+                .map(|item| item.content.clone())
+        })
+    };
+    let Some(markdown) = last_response else {
+        shared_rate_limit_wait(state, chat_id).await;
+        bot.send_message(chat_id, "No AI response in this session yet.")
+            .await?;
+        return Ok(());
+    };
+
+    let html = markdown_to_telegram_html(&markdown);
+    shared_rate_limit_wait(state, chat_id).await;
+    bot.send_message(chat_id, "▼ HTML rendering").await?;
+    let html_outcome =
+        match send_long_message(bot, chat_id, &html, Some(ParseMode::Html), state).await {
+            Ok(_) => "accepted the HTML".to_string(),
+            Err(e) => format!("rejected the HTML: {e}"),
+        };
+
+    shared_rate_limit_wait(state, chat_id).await;
+    bot.send_message(chat_id, "▼ Plain-text fallback").await?;
+    if let Err(e) = send_long_message(bot, chat_id, &markdown, None, state).await {
+        let ts = chrono::Local::now().format("%H:%M:%S");
+        logln!("  [{ts}]   ⚠ /recheck plain-text send failed: {e}");
+    }
+
+    let report = recheck_report(&markdown, &html, &html_outcome);
+    shared_rate_limit_wait(state, chat_id).await;
+    bot.send_document(
+        chat_id,
+        InputFile::memory(report.into_bytes()).file_name("recheck.txt"),
+    )
+    .caption(truncate_str(&format!("Telegram {html_outcome}"), 1000))
+    .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recheck_report() {
+        let report = recheck_report("**hi**", "<b>hi</b>", "accepted the HTML");
+        assert!(report.contains("raw markdown of the last response (6 bytes) ===\n**hi**\n"));
+        assert!(report.contains("output (9 bytes) ===\n<b>hi</b>\n"));
+        assert!(report.contains("=== Telegram: accepted the HTML ==="));
+    }
+}
@@ -0,0 +1,177 @@
+use std::sync::atomic::Ordering;
+
+use teloxide::prelude::*;
+use teloxide::types::{CallbackQuery, InlineKeyboardButton, InlineKeyboardMarkup, ParseMode};
+
+use crate::app;
+use crate::codex;
+
+use super::bot::{is_owner, SharedState};
+use super::storage::save_session_to_file;
+use super::streaming::shared_rate_limit_wait;
+
+/// Callback data prefix routed to this module
+pub(super) const RESTART_CALLBACK_PREFIX: &str = "restart:";
+
+/// Handle /restart command - ask for confirmation before restarting the bot process
+pub(super) async fn handle_restart_command(
+    bot: &Bot,
+    chat_id: ChatId,
+    state: &SharedState,
+) -> ResponseResult<()> {
+    let active = {
+        let data = state.lock().await;
+        data.cancel_tokens.len() + data.shell_pids.len()
+    };
+    let warning = if active > 0 {
+        format!("\n\n⚠ {active} running request(s) or shell command(s) will be cancelled.")
+    } else {
+        String::new()
+    };
+    let keyboard = InlineKeyboardMarkup::new(vec![vec![
+        InlineKeyboardButton::callback("Restart", format!("{RESTART_CALLBACK_PREFIX}confirm")),
+        InlineKeyboardButton::callback("Cancel", format!("{RESTART_CALLBACK_PREFIX}cancel")),
+    ]]);
+    shared_rate_limit_wait(state, chat_id).await;
+    let sent = bot
+        .send_message(
+            chat_id,
+            format!(
+                "<b>Restart the bot?</b>\n\nSessions are saved and the process is started again \
+                 with the same arguments (picking up a new binary if it was replaced).{warning}"
+            ),
+        )
+        .parse_mode(ParseMode::Html)
+        .reply_markup(keyboard)
+        .await?;
+
+    let mut data = state.lock().await;
+    data.pending_restarts.insert(chat_id, sent.id);
+    Ok(())
+}
+
+/// Handle a Restart/Cancel press (owner-only). `data` is `confirm` or `cancel`.
+pub(super) async fn handle_restart_callback(
+    bot: &Bot,
+    query: &CallbackQuery,
+    data: &str,
+    state: &SharedState,
+) -> ResponseResult<()> {
+    let Some(message) = query.message.as_ref() else {
+        bot.answer_callback_query(&query.id).await?;
+        return Ok(());
+    };
+    let chat_id = message.chat().id;
+
+    let outcome = {
+        let mut shared = state.lock().await;
+        if !is_owner(&shared.settings, query.from.id.0) {
+            Err("Only the bot owner can restart the bot.")
+        } else if shared.pending_restarts.get(&chat_id) == Some(&message.id()) {
+            shared.pending_restarts.remove(&chat_id);
+            Ok(data == "confirm")
+        } else {
+            Err("This confirmation has expired.")
+        }
+    };
+
+    let confirmed = match outcome {
+        Ok(confirmed) => confirmed,
+        Err(reason) => {
+            bot.answer_callback_query(&query.id).text(reason).await?;
+            return Ok(());
+        }
+    };
+
+    bot.answer_callback_query(&query.id).await?;
+    let ts = chrono::Local::now().format("%H:%M:%S");
+    logln!(
+        "  [{ts}] ◀ [button] /restart ({})",
+        if confirmed { "confirmed" } else { "cancelled" }
+    );
+    let result = if confirmed {
+        "Restarting…"
+    } else {
+        "Restart cancelled."
+    };
+    shared_rate_limit_wait(state, chat_id).await;
+    if let Err(e) = bot.edit_message_text(chat_id, message.id(), result).await {
+        logln!("  [{ts}]   ⚠ edit_message failed (restart): {e}");
+    }
+    if !confirmed {
+        return Ok(());
+    }
+
+    shut_down_work(state).await;
+    let error = restart_process();
+    logln!("  [{ts}]   ⚠ Restart failed: {error}");
+    shared_rate_limit_wait(state, chat_id).await;
+    bot.send_message(chat_id, format!("Restart failed: {error}"))
+        .await?;
+    Ok(())
+}
+
+/// Save every session and cancel running AI requests and shell commands
+async fn shut_down_work(state: &SharedState) {
+    let data = state.lock().await;
+    for session in data.sessions.values() {
+        if let Some(path) = &session.current_path {
+            save_session_to_file(session, path);
+        }
+    }
+    for token in data.cancel_tokens.values() {
+        token.cancelled.store(true, Ordering::Relaxed);
+        if let Ok(guard) = token.child_pid.lock() {
+            if let Some(pid) = *guard {
+                #[cfg(unix)]
+                // SAFETY: sending SIGTERM to cancel the child AI process before restarting
+                #[allow(unsafe_code)]
+                unsafe {
+                    libc::kill(pid as libc::pid_t, libc::SIGTERM);
+                }
+            }
+        }
+    }
+    for pid in data.shell_pids.values() {
+        #[cfg(unix)]
+        // SAFETY: sending SIGTERM to stop a running shell command before restarting
+        #[allow(unsafe_code)]
+        unsafe {
+            libc::kill(*pid as libc::pid_t, libc::SIGTERM);
+        }
+    }
+    let ts = chrono::Local::now().format("%H:%M:%S");
+    logln!(
+        "  [{ts}] ↻ Restarting: saved {} session(s), cancelled {} request(s)",
+        data.sessions.len(),
+        data.cancel_tokens.len() + data.shell_pids.len()
+    );
+}
+
+/// Replace this process with a fresh copy of the current executable and arguments.
+/// Only returns if that failed.
+fn restart_process() -> String {
+    let exe = match std::env::current_exe() {
+        Ok(exe) => exe,
+        Err(e) => return e.to_string(),
+    };
+    let mut command = std::process::Command::new(exe);
+    command.args(std::env::args_os().skip(1));
+    if codex::is_madmax() {
+        // Already confirmed when this process started; there may be no terminal to ask again
+        command.env(app::CONFIRM_MADMAX_ENV, "1");
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        command.exec().to_string()
+    }
+    #[cfg(not(unix))]
+    {
+        match command.spawn() {
+            Ok(_) => std::process::exit(0),
+            Err(e) => e.to_string(),
+        }
+    }
+}
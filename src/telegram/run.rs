@@ -0,0 +1,333 @@
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+use teloxide::prelude::*;
+use teloxide::types::{
+    CallbackQuery, InlineKeyboardButton, InlineKeyboardMarkup, MessageId, ParseMode,
+};
+
+use crate::i18n;
+use crate::session::HistoryType;
+
+use super::bot::{is_owner, SharedData, SharedState};
+use super::file_ops::run_shell_command;
+use super::settings::ChatToggle;
+use super::streaming::{html_escape, shared_rate_limit_wait};
+
+/// Callback data prefix routed to this module
+pub(super) const RUN_CALLBACK_PREFIX: &str = "run:";
+
+/// Longest script (bytes once HTML-escaped) /run offers. The confirmation always shows the
+/// whole script, so anything longer is refused rather than run partly unseen.
+const MAX_SCRIPT_PREVIEW: usize = 3000;
+
+/// A code block from the last AI response held until the owner confirms running it (/run)
+pub(super) struct PendingRun {
+    /// Message carrying the Run/Cancel buttons (older buttons are rejected)
+    pub message_id: MessageId,
+    pub interpreter: &'static str,
+    pub extension: &'static str,
+    pub code: String,
+}
+
+/// Language (first word of the info string, lowercase) and body of the first fenced code block
+fn first_code_block(md: &str) -> Option<(String, String)> {
+    let mut lines = md.lines();
+    while let Some(line) = lines.next() {
+        let Some(info) = line.trim_start().strip_prefix("```") else {
+            continue;
+        };
+        // `python:scripts/run.py` names a file after the language
+        let language = info
+            .split_whitespace()
+            .next()
+            .and_then(|word| word.split(':').next())
+            .unwrap_or("")
+            .to_lowercase();
+        let mut code_lines = Vec::new();
+        for inner in lines.by_ref() {
+            if inner.trim_start().starts_with("```") {
+                break;
+            }
+            code_lines.push(inner);
+        }
+        let mut code = code_lines.join("\n");
+        code.push('\n');
+        return Some((language, code));
+    }
+    None
+}
+
+/// Interpreter and script file extension for a code block language
+fn interpreter_for(language: &str) -> Option<(&'static str, &'static str)> {
+    match language {
+        "bash" | "sh" | "shell" => Some(("bash", "sh")),
+        "python" | "python3" | "py" => Some(("python3", "py")),
+        "node" | "js" | "javascript" => Some(("node", "js")),
+        _ => None,
+    }
+}
+
+/// Temporary script file prefix in the session directory; the file is removed after the run
+fn script_file_prefix() -> String {
+    format!(".{}-run-", env!("CARGO_BIN_NAME"))
+}
+
+/// Interpreter, extension and code of the first code block of the chat's last AI response
+fn script_to_run(
+    data: &SharedData,
+    chat_id: ChatId,
+) -> Result<(&'static str, &'static str, String), String> {
+    if !ChatToggle::Shell.is_enabled(&data.settings, chat_id) {
+        return Err("Direct shell is disabled in this chat, so /run is too. Use /shell on.".into());
+    }
+    let session = data
+        .sessions
+        .get(&chat_id)
+        .ok_or_else(|| i18n::MSG_NO_SESSION.to_string())?;
+    let last_response = session
+        .history
+        .iter()
+        .rev()
+        .find(|item| matches!(item.item_type, HistoryType::Assistant))
+        .ok_or_else(|| "No AI response in this session yet.".to_string())?;
+    let (language, code) = first_code_block(&last_response.content)
+        .ok_or_else(|| "The last AI response has no fenced code block.".to_string())?;
+    let (interpreter, extension) = interpreter_for(&language).ok_or_else(|| {
+        format!(
+            "Cannot run a code block tagged \"{}\". Supported: bash/sh, python/py, node/js.",
+            if language.is_empty() {
+                "(none)"
+            } else {
+                &language
+            }
+        )
+    })?;
+    if html_escape(&code).len() > MAX_SCRIPT_PREVIEW {
+        return Err(format!(
+            "The code block is too long to show for confirmation ({} bytes, limit {MAX_SCRIPT_PREVIEW}).",
+            code.len()
+        ));
+    }
+    Ok((interpreter, extension, code))
+}
+
+/// Handle /run command - offer to run the first code block of the last AI response (owner-only)
+pub(super) async fn handle_run_command(
+    bot: &Bot,
+    chat_id: ChatId,
+    is_owner: bool,
+    state: &SharedState,
+) -> ResponseResult<()> {
+    let script = if is_owner {
+        script_to_run(&*state.lock().await, chat_id)
+    } else {
+        Err("Only the bot owner can run scripts.".to_string())
+    };
+
+    match script {
+        Ok((interpreter, extension, code)) => {
+            offer_run(bot, chat_id, interpreter, extension, code, state).await
+        }
+        Err(message) => {
+            shared_rate_limit_wait(state, chat_id).await;
+            bot.send_message(chat_id, message).await?;
+            Ok(())
+        }
+    }
+}
+
+/// Show the script with Run/Cancel buttons and remember it until a button is pressed
+async fn offer_run(
+    bot: &Bot,
+    chat_id: ChatId,
+    interpreter: &'static str,
+    extension: &'static str,
+    code: String,
+    state: &SharedState,
+) -> ResponseResult<()> {
+    let keyboard = InlineKeyboardMarkup::new(vec![vec![
+        InlineKeyboardButton::callback("▶ Run", format!("{RUN_CALLBACK_PREFIX}run")),
+        InlineKeyboardButton::callback("Cancel", format!("{RUN_CALLBACK_PREFIX}cancel")),
+    ]]);
+
+    shared_rate_limit_wait(state, chat_id).await;
+    let sent = bot
+        .send_message(
+            chat_id,
+            format!(
+                "Run this script with <code>{interpreter}</code> in the session directory?\n<pre>{}</pre>",
+                html_escape(&code)
+            ),
+        )
+        .parse_mode(ParseMode::Html)
+        .reply_markup(keyboard)
+        .await?;
+
+    let mut data = state.lock().await;
+    data.pending_runs.insert(
+        chat_id,
+        PendingRun {
+            message_id: sent.id,
+            interpreter,
+            extension,
+            code,
+        },
+    );
+    Ok(())
+}
+
+/// Handle a Run/Cancel press (owner-only). `data` is `run` or `cancel`.
+pub(super) async fn handle_run_callback(
+    bot: &Bot,
+    query: &CallbackQuery,
+    data: &str,
+    state: &SharedState,
+) -> ResponseResult<()> {
+    let Some(message) = query.message.as_ref() else {
+        bot.answer_callback_query(&query.id).await?;
+        return Ok(());
+    };
+    let chat_id = message.chat().id;
+
+    let outcome = {
+        let mut shared = state.lock().await;
+        if !is_owner(&shared.settings, query.from.id.0) {
+            Err("Only the bot owner can run scripts.")
+        } else if !ChatToggle::Shell.is_enabled(&shared.settings, chat_id) {
+            // /shell off after the script was offered
+            shared.pending_runs.remove(&chat_id);
+            Err("Direct shell is disabled in this chat.")
+        } else if shared.cancel_tokens.contains_key(&chat_id)
+            || shared.shell_pids.contains_key(&chat_id)
+        {
+            Err(i18n::MSG_AI_BUSY)
+        } else {
+            let session_path = shared
+                .sessions
+                .get(&chat_id)
+                .and_then(|s| s.current_path.clone());
+            match (shared.pending_runs.remove(&chat_id), session_path) {
+                (Some(pending), Some(path)) if pending.message_id == message.id() => {
+                    Ok((pending, path))
+                }
+                (Some(pending), _) if pending.message_id != message.id() => {
+                    shared.pending_runs.insert(chat_id, pending);
+                    Err("This confirmation has expired.")
+                }
+                (Some(_), None) => Err(i18n::MSG_NO_SESSION),
+                _ => Err("This confirmation has expired."),
+            }
+        }
+    };
+
+    let (pending, session_path) = match outcome {
+        Ok(run) => run,
+        Err(reason) => {
+            bot.answer_callback_query(&query.id).text(reason).await?;
+            return Ok(());
+        }
+    };
+
+    bot.answer_callback_query(&query.id).await?;
+    let run = data == "run";
+    let status = if run { "▶ Running" } else { "✖ Cancelled" };
+    shared_rate_limit_wait(state, chat_id).await;
+    if let Err(e) = bot
+        .edit_message_text(
+            chat_id,
+            message.id(),
+            format!(
+                "{status} ({}):\n<pre>{}</pre>",
+                pending.interpreter,
+                html_escape(&pending.code)
+            ),
+        )
+        .parse_mode(ParseMode::Html)
+        .await
+    {
+        let ts = chrono::Local::now().format("%H:%M:%S");
+        logln!("  [{ts}]   ⚠ edit_message failed (run confirm): {e}");
+    }
+
+    let ts = chrono::Local::now().format("%H:%M:%S");
+    logln!(
+        "  [{ts}] ◀ [button] /run {} ({})",
+        pending.interpreter,
+        if run { "confirmed" } else { "cancelled" }
+    );
+    if !run {
+        return Ok(());
+    }
+
+    // A fresh, exclusively created file: a name pre-planted as a symlink is never written through
+    let written = crate::app::create_private_file(
+        Path::new(&session_path),
+        &script_file_prefix(),
+        pending.extension,
+    )
+    .and_then(
+        |(path, mut file)| match file.write_all(pending.code.as_bytes()) {
+            Ok(()) => Ok(path),
+            Err(e) => {
+                let _ = fs::remove_file(&path);
+                Err(e)
+            }
+        },
+    );
+    let script_path = match written {
+        Ok(path) => path,
+        Err(e) => {
+            shared_rate_limit_wait(state, chat_id).await;
+            bot.send_message(chat_id, format!("Could not write the script: {e}"))
+                .await?;
+            return Ok(());
+        }
+    };
+    let file_name = script_path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let result = run_shell_command(
+        bot,
+        chat_id,
+        &format!("{} {file_name}", pending.interpreter),
+        state,
+    )
+    .await;
+    let _ = fs::remove_file(&script_path);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_first_code_block() {
+        let md = "Try this:\n```Python title=x.py\nprint(1)\n```\n```bash\nls\n```";
+        assert_eq!(
+            first_code_block(md),
+            Some(("python".to_string(), "print(1)\n".to_string()))
+        );
+        assert_eq!(
+            first_code_block("```sh:run.sh\necho hi\n```"),
+            Some(("sh".to_string(), "echo hi\n".to_string()))
+        );
+        assert_eq!(
+            first_code_block("```\nplain\n```").map(|(lang, _)| lang),
+            Some(String::new())
+        );
+        assert_eq!(first_code_block("no code here"), None);
+    }
+
+    #[test]
+    fn test_interpreter_for() {
+        assert_eq!(interpreter_for("bash"), Some(("bash", "sh")));
+        assert_eq!(interpreter_for("py"), Some(("python3", "py")));
+        assert_eq!(interpreter_for("javascript"), Some(("node", "js")));
+        assert_eq!(interpreter_for("rust"), None);
+        assert_eq!(interpreter_for(""), None);
+    }
+}
@@ -0,0 +1,250 @@
+use teloxide::prelude::*;
+
+use crate::i18n;
+use crate::session::{HistoryItem, HistoryType};
+
+use super::bot::SharedState;
+use super::storage::load_sessions_for_path;
+use super::streaming::shared_rate_limit_wait;
+
+/// Matches listed per /search reply, across the current and earlier sessions
+const MAX_SEARCH_RESULTS: usize = 10;
+
+/// Characters of context shown on each side of a match
+const SNIPPET_CONTEXT_CHARS: usize = 60;
+
+/// Earlier session files of the same folder read by `/search all`
+const MAX_SEARCHED_SESSIONS: usize = 20;
+
+/// Longest query accepted
+const MAX_QUERY_CHARS: usize = 100;
+
+/// One history item containing the query
+#[derive(Debug, PartialEq)]
+struct SearchHit {
+    /// 1-based number of the user turn the item belongs to (0 before the first prompt)
+    turn: usize,
+    item_type: &'static str,
+    snippet: String,
+}
+
+fn item_type_label(item_type: &HistoryType) -> &'static str {
+    match item_type {
+        HistoryType::User => "you",
+        HistoryType::Assistant => "AI",
+        HistoryType::Error => "error",
+        HistoryType::System => "system",
+        HistoryType::ToolUse => "tool",
+        HistoryType::ToolResult => "tool result",
+    }
+}
+
+/// Lowercase a character, keeping one character per input character so positions line up
+fn fold(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+/// Character index of the first case-insensitive occurrence of `query` (already folded)
+fn find_folded(text: &[char], query: &[char]) -> Option<usize> {
+    if query.is_empty() || query.len() > text.len() {
+        return None;
+    }
+    (0..=text.len() - query.len()).find(|&start| {
+        query
+            .iter()
+            .zip(&text[start..])
+            .all(|(q, c)| *q == fold(*c))
+    })
+}
+
+/// The match with up to SNIPPET_CONTEXT_CHARS around it, on one line
+fn snippet(text: &[char], start: usize, len: usize) -> String {
+    let from = start.saturating_sub(SNIPPET_CONTEXT_CHARS);
+    let to = (start + len + SNIPPET_CONTEXT_CHARS).min(text.len());
+    let body: String = text[from..to]
+        .iter()
+        .map(|c| if c.is_whitespace() { ' ' } else { *c })
+        .collect();
+    format!(
+        "{}{}{}",
+        if from > 0 { "…" } else { "" },
+        body.trim(),
+        if to < text.len() { "…" } else { "" }
+    )
+}
+
+/// Items of `history` containing `query` (case-insensitive), newest first
+fn search_history(history: &[HistoryItem], query: &str) -> Vec<SearchHit> {
+    let query: Vec<char> = query.chars().map(fold).collect();
+    let mut turn = 0;
+    let mut hits = Vec::new();
+    for item in history {
+        if matches!(item.item_type, HistoryType::User) {
+            turn += 1;
+        }
+        let text: Vec<char> = item.content.chars().collect();
+        if let Some(start) = find_folded(&text, &query) {
+            hits.push(SearchHit {
+                turn,
+                item_type: item_type_label(&item.item_type),
+                snippet: snippet(&text, start, query.len()),
+            });
+        }
+    }
+    hits.reverse();
+    hits
+}
+
+fn hit_line(hit: &SearchHit) -> String {
+    format!("#{} {}: {}", hit.turn, hit.item_type, hit.snippet)
+}
+
+/// Handle /search command - find history items mentioning a phrase
+/// Usage: /search <query>      (current session)
+///        /search all <query>  (also earlier saved sessions of this folder)
+pub(super) async fn handle_search_command(
+    bot: &Bot,
+    chat_id: ChatId,
+    text: &str,
+    state: &SharedState,
+) -> ResponseResult<()> {
+    let arg = text.strip_prefix("/search").unwrap_or("").trim();
+    let (include_saved, query) = match arg.strip_prefix("all ") {
+        Some(rest) => (true, rest.trim()),
+        None => (false, arg),
+    };
+
+    let response_msg = if query.is_empty() {
+        "Usage:\n/search <text> — Find it in this session's history\n\
+         /search all <text> — Also search earlier sessions of this folder"
+            .to_string()
+    } else if query.chars().count() > MAX_QUERY_CHARS {
+        format!("Query too long (max {MAX_QUERY_CHARS} characters).")
+    } else {
+        let session = {
+            let data = state.lock().await;
+            data.sessions.get(&chat_id).and_then(|s| {
+                s.current_path
+                    .clone()
+                    .map(|path| (path, s.session_id.clone(), s.history.clone()))
+            })
+        };
+        match session {
+            None => i18n::MSG_NO_SESSION.to_string(),
+            Some((path, session_id, history)) => {
+                let saved = if include_saved {
+                    tokio::task::spawn_blocking(move || {
+                        load_sessions_for_path(&path, session_id.as_deref(), MAX_SEARCHED_SESSIONS)
+                    })
+                    .await
+                    .unwrap_or_default()
+                } else {
+                    Vec::new()
+                };
+                search_results(query, &history, &saved, include_saved)
+            }
+        }
+    };
+
+    shared_rate_limit_wait(state, chat_id).await;
+    bot.send_message(chat_id, response_msg).await?;
+    Ok(())
+}
+
+/// Reply listing up to MAX_SEARCH_RESULTS matches, current session first
+fn search_results(
+    query: &str,
+    history: &[HistoryItem],
+    saved: &[crate::session::SessionData],
+    include_saved: bool,
+) -> String {
+    let current = search_history(history, query);
+    let mut shown = 0;
+    let mut total = current.len();
+    let mut sections = Vec::new();
+
+    if !current.is_empty() {
+        let lines: Vec<String> = current
+            .iter()
+            .take(MAX_SEARCH_RESULTS)
+            .map(hit_line)
+            .collect();
+        shown += lines.len();
+        sections.push(format!("This session:\n{}", lines.join("\n")));
+    }
+    for session in saved {
+        let hits = search_history(&session.history, query);
+        total += hits.len();
+        let room = MAX_SEARCH_RESULTS - shown;
+        if hits.is_empty() || room == 0 {
+            continue;
+        }
+        let lines: Vec<String> = hits.iter().take(room).map(hit_line).collect();
+        shown += lines.len();
+        let title = match &session.label {
+            Some(label) => format!("{} · {label}", session.created_at),
+            None => session.created_at.clone(),
+        };
+        sections.push(format!("Session of {title}:\n{}", lines.join("\n")));
+    }
+
+    if total == 0 {
+        let scope = if include_saved {
+            "this folder's sessions"
+        } else {
+            "this session (/search all also checks earlier ones)"
+        };
+        return format!("No matches for \"{query}\" in {scope}.");
+    }
+    let more = if total > shown {
+        format!(
+            "\n\nShowing {shown} of {total} matches. Use a more specific query to narrow it down."
+        )
+    } else {
+        String::new()
+    };
+    format!(
+        "Matches for \"{query}\" (newest first):\n\n{}{more}",
+        sections.join("\n\n")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(item_type: HistoryType, content: &str) -> HistoryItem {
+        HistoryItem {
+            item_type,
+            content: content.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_search_history() {
+        let history = vec![
+            item(HistoryType::User, "Where is the Database schema?"),
+            item(HistoryType::Assistant, "It lives in db/schema.sql"),
+            item(HistoryType::User, "Thanks"),
+            item(
+                HistoryType::Assistant,
+                &format!("{} the DATABASE\nis fine", "x".repeat(80)),
+            ),
+        ];
+        let hits = search_history(&history, "database");
+        assert_eq!(hits.len(), 2);
+        assert_eq!(
+            hits[0],
+            SearchHit {
+                turn: 2,
+                item_type: "AI",
+                snippet: format!("…{} the DATABASE is fine", "x".repeat(55)),
+            }
+        );
+        assert_eq!(hits[1].turn, 1);
+        assert_eq!(hits[1].snippet, "Where is the Database schema?");
+        assert!(search_history(&history, "missing").is_empty());
+        assert_eq!(search_history(&history, "SCHÉMA").len(), 0);
+        assert_eq!(search_history(&history, "Schema").len(), 2);
+    }
+}
@@ -0,0 +1,104 @@
+use std::fs;
+use std::path::Path;
+use std::time::Instant;
+
+use teloxide::prelude::*;
+use teloxide::types::ParseMode;
+
+use crate::codex;
+use crate::session::ai_sessions_dir;
+
+use super::bot::SharedState;
+use super::storage::check_bot_settings_file;
+use super::streaming::{html_escape, shared_rate_limit_wait};
+
+/// Create `dir` if needed, then write and remove a probe file in it
+fn check_dir_writable(dir: &Path) -> Result<String, String> {
+    fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    let probe = dir.join(format!(".selftest-{}", std::process::id()));
+    fs::write(&probe, b"selftest").map_err(|e| e.to_string())?;
+    fs::remove_file(&probe).map_err(|e| e.to_string())?;
+    Ok(dir.display().to_string())
+}
+
+fn check_line(name: &str, result: &Result<String, String>) -> String {
+    match result {
+        Ok(detail) => format!("✅ {name} — {}", html_escape(detail)),
+        Err(e) => format!("❌ {name} — {}", html_escape(e)),
+    }
+}
+
+/// Checks that do not involve Telegram, run on a blocking thread
+fn run_local_checks() -> Vec<(&'static str, Result<String, String>)> {
+    let binary = codex::get_ai_binary_path().ok_or_else(|| "not found in PATH".to_string());
+    let version = match &binary {
+        Ok(_) => codex::resolve_ai_binary_version()
+            .ok_or_else(|| "`--version` failed or printed nothing".to_string()),
+        Err(_) => Err("skipped (no binary)".to_string()),
+    };
+    let sessions = ai_sessions_dir()
+        .ok_or_else(|| "no data directory".to_string())
+        .and_then(|dir| check_dir_writable(&dir));
+    vec![
+        ("Backend binary", binary),
+        ("Backend --version", version),
+        ("Session directory writable", sessions),
+        ("Settings file readable/writable", check_bot_settings_file()),
+    ]
+}
+
+/// Handle /selftest command - check the backend, storage and Telegram paths (owner-only)
+pub(super) async fn handle_selftest_command(
+    bot: &Bot,
+    chat_id: ChatId,
+    state: &SharedState,
+) -> ResponseResult<()> {
+    let mut checks = tokio::task::spawn_blocking(run_local_checks)
+        .await
+        .unwrap_or_else(|e| vec![("Local checks", Err(e.to_string()))]);
+
+    // Telegram: send a message and delete it again
+    let started = Instant::now();
+    shared_rate_limit_wait(state, chat_id).await;
+    let telegram = match bot.send_message(chat_id, "🧪 /selftest message").await {
+        Ok(msg) => {
+            let sent_ms = started.elapsed().as_millis();
+            shared_rate_limit_wait(state, chat_id).await;
+            match bot.delete_message(chat_id, msg.id).await {
+                Ok(_) => Ok(format!("sent in {sent_ms} ms and deleted")),
+                Err(e) => Err(format!("sent, but delete failed: {e}")),
+            }
+        }
+        Err(e) => Err(format!("send failed: {e}")),
+    };
+    checks.push(("Telegram send/delete", telegram));
+
+    let passed = checks.iter().filter(|(_, r)| r.is_ok()).count();
+    let mut lines = vec!["<b>Self-test</b>".to_string()];
+    lines.extend(checks.iter().map(|(name, result)| check_line(name, result)));
+    lines.push(format!("\n{passed}/{} checks passed", checks.len()));
+
+    shared_rate_limit_wait(state, chat_id).await;
+    bot.send_message(chat_id, lines.join("\n"))
+        .parse_mode(ParseMode::Html)
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_dir_writable() {
+        let dir = std::env::temp_dir().join(format!("opencodex_selftest_{}", std::process::id()));
+        let nested = dir.join("sessions");
+        assert!(check_dir_writable(&nested).is_ok());
+        assert_eq!(fs::read_dir(&nested).map(|d| d.count()).ok(), Some(0));
+        assert_eq!(
+            check_line("Probe", &Err("<denied>".to_string())),
+            "❌ Probe — &lt;denied&gt;"
+        );
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,429 @@
+use std::fs;
+use std::io::Read;
+use std::path::{Component, Path};
+
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use teloxide::prelude::*;
+use teloxide::types::{
+    CallbackQuery, InlineKeyboardButton, InlineKeyboardMarkup, InputFile, MessageId,
+};
+
+use crate::session::ai_sessions_dir;
+
+use super::bot::{is_owner, SharedState};
+use super::bot_api::{download_limit, upload_limit};
+use super::file_ops::download_telegram_file;
+use super::storage::{is_session_file, restore_session_files};
+use super::streaming::shared_rate_limit_wait;
+
+/// Callback data prefix routed to this module
+pub(super) const SESSION_RESTORE_CALLBACK_PREFIX: &str = "sessrestore:";
+
+/// Most uncompressed bytes a session archive may expand to (guards against gzip bombs)
+const MAX_RESTORE_BYTES: u64 = 512 * 1024 * 1024;
+
+/// Bytes left under the upload limit for tar headers and gzip framing
+const ARCHIVE_OVERHEAD_MARGIN: u64 = 1024 * 1024;
+
+/// An uploaded session archive awaiting confirmation (/restoresessions)
+pub(super) struct PendingSessionRestore {
+    /// Message carrying the Apply/Cancel buttons (older buttons are rejected)
+    pub message_id: MessageId,
+    /// Validated (file name, contents) pairs from the archive
+    pub files: Vec<(String, Vec<u8>)>,
+}
+
+/// Session files in `dir` as (file name, size), sorted by name
+fn session_files(dir: &Path) -> Vec<(String, u64)> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut files: Vec<(String, u64)> = entries
+        .flatten()
+        .filter(|entry| entry.file_type().is_ok_and(|t| t.is_file()))
+        .filter(|entry| is_session_file(&entry.path()))
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            Some((name, entry.metadata().ok()?.len()))
+        })
+        .collect();
+    files.sort();
+    files
+}
+
+/// Split files into archive parts whose uncompressed size stays within `budget`.
+/// Returns the parts (as indices into `files`) and the names of files too large for any part.
+fn plan_parts(files: &[(String, u64)], budget: u64) -> (Vec<Vec<usize>>, Vec<String>) {
+    let mut parts: Vec<Vec<usize>> = Vec::new();
+    let mut skipped = Vec::new();
+    let mut current: Vec<usize> = Vec::new();
+    let mut current_size = 0;
+    for (i, (name, size)) in files.iter().enumerate() {
+        if *size > budget {
+            skipped.push(name.clone());
+            continue;
+        }
+        if current_size + size > budget && !current.is_empty() {
+            parts.push(std::mem::take(&mut current));
+            current_size = 0;
+        }
+        current.push(i);
+        current_size += size;
+    }
+    if !current.is_empty() {
+        parts.push(current);
+    }
+    (parts, skipped)
+}
+
+/// gzip-compressed tar of the named files in `dir`
+fn build_archive(dir: &Path, names: &[&str]) -> std::io::Result<Vec<u8>> {
+    let mut builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+    for name in names {
+        builder.append_path_with_name(dir.join(name), name)?;
+    }
+    builder.into_inner()?.finish()
+}
+
+/// Read a session archive, accepting only plain session files at the top level.
+/// Any other entry (folders, links, `..` or absolute paths) rejects the whole archive.
+fn read_archive(bytes: &[u8]) -> Result<Vec<(String, Vec<u8>)>, String> {
+    let mut archive = tar::Archive::new(GzDecoder::new(bytes));
+    let mut files = Vec::new();
+    let mut total: u64 = 0;
+    for entry in archive.entries().map_err(|e| e.to_string())? {
+        let mut entry = entry.map_err(|e| e.to_string())?;
+        let path = entry.path().map_err(|e| e.to_string())?.into_owned();
+        let shown = path.display().to_string();
+        let name = match path.components().collect::<Vec<_>>().as_slice() {
+            [Component::Normal(name)] => name.to_str().map(String::from),
+            _ => None,
+        };
+        let Some(name) = name.filter(|n| !n.starts_with('.') && is_session_file(Path::new(n)))
+        else {
+            return Err(format!("unexpected entry: {shown}"));
+        };
+        if !entry.header().entry_type().is_file() {
+            return Err(format!("not a regular file: {shown}"));
+        }
+        total += entry.size();
+        if total > MAX_RESTORE_BYTES {
+            return Err(format!(
+                "archive expands beyond {} MB",
+                MAX_RESTORE_BYTES / 1024 / 1024
+            ));
+        }
+        let mut content = Vec::new();
+        entry.read_to_end(&mut content).map_err(|e| e.to_string())?;
+        files.push((name, content));
+    }
+    if files.is_empty() {
+        return Err("no session files in the archive".to_string());
+    }
+    Ok(files)
+}
+
+/// Handle /backupsessions command - send every saved session file as .tar.gz documents
+/// (owner-only). Split into several archives when the sessions exceed the upload limit.
+pub(super) async fn handle_backupsessions_command(
+    bot: &Bot,
+    chat_id: ChatId,
+    state: &SharedState,
+) -> ResponseResult<()> {
+    let Some(dir) = ai_sessions_dir() else {
+        shared_rate_limit_wait(state, chat_id).await;
+        bot.send_message(chat_id, "Session folder unavailable.")
+            .await?;
+        return Ok(());
+    };
+    let budget = upload_limit().saturating_sub(ARCHIVE_OVERHEAD_MARGIN);
+    let archives = tokio::task::spawn_blocking(move || {
+        let files = session_files(&dir);
+        let (parts, skipped) = plan_parts(&files, budget);
+        let archives: std::io::Result<Vec<(Vec<u8>, usize)>> = parts
+            .iter()
+            .map(|part| {
+                let names: Vec<&str> = part.iter().map(|&i| files[i].0.as_str()).collect();
+                build_archive(&dir, &names).map(|bytes| (bytes, names.len()))
+            })
+            .collect();
+        archives.map(|archives| (archives, skipped))
+    })
+    .await
+    .map_err(|e| std::io::Error::other(e.to_string()))
+    .and_then(|result| result);
+
+    let (archives, skipped) = match archives {
+        Ok(result) => result,
+        Err(e) => {
+            shared_rate_limit_wait(state, chat_id).await;
+            bot.send_message(chat_id, format!("Failed to archive sessions: {e}"))
+                .await?;
+            return Ok(());
+        }
+    };
+    if archives.is_empty() && skipped.is_empty() {
+        shared_rate_limit_wait(state, chat_id).await;
+        bot.send_message(chat_id, "No saved sessions to back up.")
+            .await?;
+        return Ok(());
+    }
+
+    let stamp = chrono::Local::now().format("%Y%m%d_%H%M%S");
+    let count = archives.len();
+    for (i, (bytes, files)) in archives.into_iter().enumerate() {
+        let file_name = if count == 1 {
+            format!("opencodex_sessions_{stamp}.tar.gz")
+        } else {
+            format!("opencodex_sessions_{stamp}_part{}of{count}.tar.gz", i + 1)
+        };
+        let part = if count == 1 {
+            String::new()
+        } else {
+            format!(" (part {}/{count})", i + 1)
+        };
+        shared_rate_limit_wait(state, chat_id).await;
+        bot.send_document(chat_id, InputFile::memory(bytes).file_name(file_name))
+            .caption(format!(
+                "Session backup{part}: {files} file(s). Send it back with the caption \
+                 /restoresessions to restore it."
+            ))
+            .await?;
+    }
+    if !skipped.is_empty() {
+        shared_rate_limit_wait(state, chat_id).await;
+        bot.send_message(
+            chat_id,
+            format!(
+                "Skipped {} session file(s) larger than the upload limit:\n{}",
+                skipped.len(),
+                skipped.join("\n")
+            ),
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+/// Handle /restoresessions typed as text: explain how to restore
+pub(super) async fn handle_restoresessions_command(
+    bot: &Bot,
+    chat_id: ChatId,
+    state: &SharedState,
+) -> ResponseResult<()> {
+    shared_rate_limit_wait(state, chat_id).await;
+    bot.send_message(
+        chat_id,
+        "Send a session archive (from /backupsessions) with the caption /restoresessions.\n\
+         The files are listed for confirmation before anything is written.",
+    )
+    .await?;
+    Ok(())
+}
+
+/// Handle an uploaded session archive (caption /restoresessions): validate it and ask for
+/// confirmation
+pub(super) async fn handle_restoresessions_upload(
+    bot: &Bot,
+    chat_id: ChatId,
+    msg: &Message,
+    state: &SharedState,
+) -> ResponseResult<()> {
+    let Some(doc) = msg.document() else {
+        return handle_restoresessions_command(bot, chat_id, state).await;
+    };
+
+    let parsed = if u64::from(doc.file.size) > download_limit() {
+        Err(format!(
+            "file too large (limit {} MB)",
+            download_limit() / 1024 / 1024
+        ))
+    } else {
+        shared_rate_limit_wait(state, chat_id).await;
+        match download_telegram_file(bot, &doc.file.id, download_limit()).await? {
+            Ok(bytes) => tokio::task::spawn_blocking(move || read_archive(&bytes))
+                .await
+                .unwrap_or_else(|e| Err(e.to_string())),
+            Err(e) => Err(e),
+        }
+    };
+    let files = match parsed {
+        Ok(files) => files,
+        Err(e) => {
+            shared_rate_limit_wait(state, chat_id).await;
+            bot.send_message(chat_id, format!("Invalid session archive: {e}"))
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let existing = ai_sessions_dir()
+        .map(|dir| {
+            files
+                .iter()
+                .filter(|(name, _)| dir.join(name).exists())
+                .count()
+        })
+        .unwrap_or(0);
+    let bytes: usize = files.iter().map(|(_, content)| content.len()).sum();
+    let keyboard = InlineKeyboardMarkup::new(vec![vec![
+        InlineKeyboardButton::callback("Apply", format!("{SESSION_RESTORE_CALLBACK_PREFIX}apply")),
+        InlineKeyboardButton::callback(
+            "Cancel",
+            format!("{SESSION_RESTORE_CALLBACK_PREFIX}cancel"),
+        ),
+    ]]);
+    shared_rate_limit_wait(state, chat_id).await;
+    let sent = bot
+        .send_message(
+            chat_id,
+            format!(
+                "Restore {} session file(s) ({} KB) into the session folder?\n\
+                 {existing} of them would replace existing files.",
+                files.len(),
+                bytes.div_ceil(1024)
+            ),
+        )
+        .reply_markup(keyboard)
+        .await?;
+
+    let mut data = state.lock().await;
+    data.pending_session_restores.insert(
+        chat_id,
+        PendingSessionRestore {
+            message_id: sent.id,
+            files,
+        },
+    );
+    Ok(())
+}
+
+/// Handle an Apply/Cancel press (owner-only). `data` is `apply` or `cancel`.
+pub(super) async fn handle_session_restore_callback(
+    bot: &Bot,
+    query: &CallbackQuery,
+    data: &str,
+    state: &SharedState,
+) -> ResponseResult<()> {
+    let Some(message) = query.message.as_ref() else {
+        bot.answer_callback_query(&query.id).await?;
+        return Ok(());
+    };
+    let chat_id = message.chat().id;
+
+    let pending = {
+        let mut shared = state.lock().await;
+        if !is_owner(&shared.settings, query.from.id.0) {
+            Err("Only the bot owner can restore sessions.")
+        } else {
+            match shared.pending_session_restores.remove(&chat_id) {
+                Some(pending) if pending.message_id == message.id() => Ok(pending),
+                Some(pending) => {
+                    shared.pending_session_restores.insert(chat_id, pending);
+                    Err("This restore request has expired.")
+                }
+                None => Err("This restore request has expired."),
+            }
+        }
+    };
+    let pending = match pending {
+        Ok(pending) => pending,
+        Err(reason) => {
+            bot.answer_callback_query(&query.id).text(reason).await?;
+            return Ok(());
+        }
+    };
+    bot.answer_callback_query(&query.id).await?;
+
+    let result = if data == "apply" {
+        let written = tokio::task::spawn_blocking(move || restore_session_files(&pending.files))
+            .await
+            .unwrap_or_else(|e| Err(std::io::Error::other(e.to_string())));
+        match written {
+            Ok(count) => format!(
+                "Restored {count} session file(s). They are picked up when /start opens \
+                 their folder."
+            ),
+            Err(e) => format!("Restore failed: {e}"),
+        }
+    } else {
+        "Session restore cancelled.".to_string()
+    };
+
+    let ts = chrono::Local::now().format("%H:%M:%S");
+    logln!("  [{ts}] ◀ [button] /restoresessions {data}");
+    shared_rate_limit_wait(state, chat_id).await;
+    if let Err(e) = bot.edit_message_text(chat_id, message.id(), result).await {
+        logln!("  [{ts}]   ⚠ edit_message failed (session restore): {e}");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+#[allow(clippy::expect_used)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plan_parts() {
+        let files = vec![
+            ("a.json".to_string(), 40),
+            ("b.json".to_string(), 50),
+            ("c.json".to_string(), 150),
+            ("d.json.gz".to_string(), 30),
+        ];
+        let (parts, skipped) = plan_parts(&files, 100);
+        assert_eq!(parts, vec![vec![0, 1], vec![3]]);
+        assert_eq!(skipped, vec!["c.json"]);
+        assert_eq!(plan_parts(&[], 100), (Vec::new(), Vec::new()));
+    }
+
+    #[test]
+    fn test_archive_roundtrip() {
+        let dir = std::env::temp_dir().join(format!("opencodex_sessbak_{}", std::process::id()));
+        fs::create_dir_all(dir.join("sub")).expect("create dir");
+        fs::write(dir.join("s1.json"), "{}").expect("write s1");
+        fs::write(dir.join("s2.json.gz"), [1, 2, 3]).expect("write s2");
+        fs::write(dir.join("notes.txt"), "x").expect("write notes");
+
+        let files = session_files(&dir);
+        let names: Vec<&str> = files.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, vec!["s1.json", "s2.json.gz"]);
+
+        let archive = build_archive(&dir, &names).expect("build archive");
+        let restored = read_archive(&archive).expect("read archive");
+        assert_eq!(
+            restored,
+            vec![
+                ("s1.json".to_string(), b"{}".to_vec()),
+                ("s2.json.gz".to_string(), vec![1, 2, 3]),
+            ]
+        );
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_read_archive_rejects_unsafe_entries() {
+        let archive_with = |path: &[u8], entry_type: tar::EntryType| {
+            let mut builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+            let mut header = tar::Header::new_gnu();
+            header.as_old_mut().name[..path.len()].copy_from_slice(path);
+            header.set_entry_type(entry_type);
+            header.set_size(2);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append(&header, &b"{}"[..]).expect("append");
+            builder.into_inner().expect("tar").finish().expect("gzip")
+        };
+        assert!(read_archive(&archive_with(b"ok.json", tar::EntryType::Regular)).is_ok());
+        assert!(read_archive(&archive_with(b"../evil.json", tar::EntryType::Regular)).is_err());
+        assert!(read_archive(&archive_with(b"/etc/evil.json", tar::EntryType::Regular)).is_err());
+        assert!(read_archive(&archive_with(b"dir/a.json", tar::EntryType::Regular)).is_err());
+        assert!(read_archive(&archive_with(b"run.sh", tar::EntryType::Regular)).is_err());
+        assert!(read_archive(&archive_with(b"link.json", tar::EntryType::Symlink)).is_err());
+        assert!(read_archive(b"not an archive").is_err());
+    }
+}
@@ -0,0 +1,276 @@
+use teloxide::prelude::*;
+use teloxide::types::{CallbackQuery, InlineKeyboardButton, InlineKeyboardMarkup};
+
+use crate::i18n;
+use crate::session::{HistoryItem, SessionData};
+
+use super::bot::{is_owner, SharedState};
+use super::storage::{load_existing_session, save_session_to_file, saveable_history};
+use super::streaming::shared_rate_limit_wait;
+
+/// Callback data prefix routed to this module
+pub(super) const SESSION_RELOAD_CALLBACK_PREFIX: &str = "sessreload:";
+
+/// What /reloadsession compares: the saveable part of the chat's in-memory session
+struct MemorySession {
+    path: String,
+    session_id: Option<String>,
+    label: Option<String>,
+    history: Vec<HistoryItem>,
+}
+
+/// Ways the in-memory session differs from the saved file, one line each
+fn session_differences(memory: &MemorySession, disk: &SessionData) -> Vec<String> {
+    let mut differences = Vec::new();
+    if memory.session_id.as_deref() != Some(disk.session_id.as_str()) {
+        differences.push(format!(
+            "session id: {} in memory, {} on disk",
+            memory.session_id.as_deref().unwrap_or("none"),
+            disk.session_id
+        ));
+    }
+    if memory.label != disk.label {
+        differences.push(format!(
+            "label: {} in memory, {} on disk",
+            memory.label.as_deref().unwrap_or("none"),
+            disk.label.as_deref().unwrap_or("none")
+        ));
+    }
+    if memory.history != disk.history {
+        let shared = memory
+            .history
+            .iter()
+            .zip(&disk.history)
+            .take_while(|(m, d)| m == d)
+            .count();
+        differences.push(format!(
+            "history: {} item(s) in memory, {} on disk, identical for the first {shared}",
+            memory.history.len(),
+            disk.history.len()
+        ));
+    }
+    if disk.in_progress {
+        differences.push("the file on disk is a checkpoint of an unfinished turn".to_string());
+    }
+    differences
+}
+
+async fn load_from_disk(path: &str) -> Option<SessionData> {
+    let path = path.to_string();
+    tokio::task::spawn_blocking(move || load_existing_session(&path).map(|(data, _)| data))
+        .await
+        .ok()
+        .flatten()
+}
+
+/// Handle /reloadsession command - compare the in-memory session with its file on disk and,
+/// if they differ, offer to load the file (dropping in-memory changes) or save over it
+pub(super) async fn handle_reloadsession_command(
+    bot: &Bot,
+    chat_id: ChatId,
+    state: &SharedState,
+) -> ResponseResult<()> {
+    let memory = {
+        let data = state.lock().await;
+        if data.cancel_tokens.contains_key(&chat_id) {
+            drop(data);
+            shared_rate_limit_wait(state, chat_id).await;
+            bot.send_message(chat_id, i18n::MSG_AI_BUSY).await?;
+            return Ok(());
+        }
+        data.sessions.get(&chat_id).and_then(|session| {
+            Some(MemorySession {
+                path: session.current_path.clone()?,
+                session_id: session.session_id.clone(),
+                label: session.label.clone(),
+                history: saveable_history(session),
+            })
+        })
+    };
+    let Some(memory) = memory else {
+        shared_rate_limit_wait(state, chat_id).await;
+        bot.send_message(chat_id, i18n::MSG_NO_SESSION).await?;
+        return Ok(());
+    };
+
+    let response_msg = match load_from_disk(&memory.path).await {
+        None if memory.session_id.is_none() || memory.history.is_empty() => {
+            "Nothing is saved for this folder yet, and the in-memory session is empty.".to_string()
+        }
+        None => {
+            let data = state.lock().await;
+            if let Some(session) = data.sessions.get(&chat_id) {
+                save_session_to_file(session, &memory.path);
+            }
+            "No session file for this folder yet. The in-memory session was saved to disk."
+                .to_string()
+        }
+        Some(disk) => {
+            let differences = session_differences(&memory, &disk);
+            if differences.is_empty() {
+                "The in-memory session matches the file on disk. Nothing changed.".to_string()
+            } else {
+                let keyboard = InlineKeyboardMarkup::new(vec![vec![
+                    InlineKeyboardButton::callback(
+                        "Load from disk",
+                        format!("{SESSION_RELOAD_CALLBACK_PREFIX}load"),
+                    ),
+                    InlineKeyboardButton::callback(
+                        "Save to disk",
+                        format!("{SESSION_RELOAD_CALLBACK_PREFIX}save"),
+                    ),
+                    InlineKeyboardButton::callback(
+                        "Cancel",
+                        format!("{SESSION_RELOAD_CALLBACK_PREFIX}cancel"),
+                    ),
+                ]]);
+                shared_rate_limit_wait(state, chat_id).await;
+                let sent = bot
+                    .send_message(
+                        chat_id,
+                        format!(
+                            "The in-memory session differs from the file on disk:\n• {}\n\n\
+                             Load from disk — Replace the in-memory session (its unsaved \
+                             changes are lost)\n\
+                             Save to disk — Write the in-memory session over the file",
+                            differences.join("\n• ")
+                        ),
+                    )
+                    .reply_markup(keyboard)
+                    .await?;
+                state
+                    .lock()
+                    .await
+                    .pending_session_reloads
+                    .insert(chat_id, sent.id);
+                return Ok(());
+            }
+        }
+    };
+
+    shared_rate_limit_wait(state, chat_id).await;
+    bot.send_message(chat_id, response_msg).await?;
+    Ok(())
+}
+
+/// Handle a Load/Save/Cancel press (owner-only). `data` is `load`, `save` or `cancel`.
+pub(super) async fn handle_session_reload_callback(
+    bot: &Bot,
+    query: &CallbackQuery,
+    data: &str,
+    state: &SharedState,
+) -> ResponseResult<()> {
+    let Some(message) = query.message.as_ref() else {
+        bot.answer_callback_query(&query.id).await?;
+        return Ok(());
+    };
+    let chat_id = message.chat().id;
+
+    let outcome = {
+        let mut shared = state.lock().await;
+        if !is_owner(&shared.settings, query.from.id.0) {
+            Err("Only the bot owner can reload the session.")
+        } else if shared.pending_session_reloads.get(&chat_id) != Some(&message.id()) {
+            Err("This confirmation has expired.")
+        } else if shared.cancel_tokens.contains_key(&chat_id) {
+            Err(i18n::MSG_AI_BUSY)
+        } else {
+            shared.pending_session_reloads.remove(&chat_id);
+            shared
+                .sessions
+                .get(&chat_id)
+                .and_then(|s| s.current_path.clone())
+                .ok_or(i18n::MSG_NO_SESSION)
+        }
+    };
+    let path = match outcome {
+        Ok(path) => path,
+        Err(reason) => {
+            bot.answer_callback_query(&query.id).text(reason).await?;
+            return Ok(());
+        }
+    };
+    bot.answer_callback_query(&query.id).await?;
+
+    let result = match data {
+        "load" => match load_from_disk(&path).await {
+            None => "The session file is gone; the in-memory session was kept.".to_string(),
+            Some(disk) => {
+                let mut shared = state.lock().await;
+                match shared.sessions.get_mut(&chat_id) {
+                    Some(session) if session.current_path.as_deref() == Some(path.as_str()) => {
+                        session.session_id = Some(disk.session_id);
+                        session.history = disk.history;
+                        session.label = disk.label;
+                        format!(
+                            "Session reloaded from disk ({} history items).",
+                            session.history.len()
+                        )
+                    }
+                    _ => "The session changed folder meanwhile; nothing was reloaded.".to_string(),
+                }
+            }
+        },
+        "save" => {
+            let shared = state.lock().await;
+            match shared.sessions.get(&chat_id) {
+                Some(session) => {
+                    save_session_to_file(session, &path);
+                    "In-memory session saved to disk.".to_string()
+                }
+                None => i18n::MSG_NO_SESSION.to_string(),
+            }
+        }
+        _ => "Reload cancelled. Nothing changed.".to_string(),
+    };
+
+    let ts = chrono::Local::now().format("%H:%M:%S");
+    logln!("  [{ts}] ◀ [button] /reloadsession ({data})");
+    shared_rate_limit_wait(state, chat_id).await;
+    if let Err(e) = bot.edit_message_text(chat_id, message.id(), result).await {
+        logln!("  [{ts}]   ⚠ edit_message failed (session reload): {e}");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session::HistoryType;
+
+    fn item(content: &str) -> HistoryItem {
+        HistoryItem {
+            item_type: HistoryType::User,
+            content: content.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_session_differences() {
+        let memory = MemorySession {
+            path: "/tmp/p".to_string(),
+            session_id: Some("s1".to_string()),
+            label: None,
+            history: vec![item("a"), item("b")],
+        };
+        let mut disk = SessionData {
+            session_id: "s1".to_string(),
+            history: vec![item("a"), item("b")],
+            current_path: "/tmp/p".to_string(),
+            created_at: String::new(),
+            label: None,
+            in_progress: false,
+        };
+        assert!(session_differences(&memory, &disk).is_empty());
+
+        disk.history.push(item("c"));
+        disk.label = Some("edited".to_string());
+        assert_eq!(
+            session_differences(&memory, &disk),
+            vec![
+                "label: none in memory, edited on disk".to_string(),
+                "history: 2 item(s) in memory, 3 on disk, identical for the first 2".to_string(),
+            ]
+        );
+    }
+}
@@ -0,0 +1,237 @@
+use std::sync::OnceLock;
+
+use regex::Regex;
+use teloxide::prelude::*;
+use teloxide::types::{
+    CallbackQuery, InlineKeyboardButton, InlineKeyboardMarkup, MessageId, ParseMode,
+};
+
+use crate::i18n;
+
+use super::bot::{is_owner, SharedState};
+use super::file_ops::run_shell_command;
+use super::settings::ChatToggle;
+use super::streaming::{html_escape, shared_rate_limit_wait, truncate_str};
+
+/// Callback data prefix routed to this module
+pub(super) const SHELL_CALLBACK_PREFIX: &str = "shell:";
+
+/// A `!` command held back until the owner confirms it (/shellconfirm on)
+pub(super) struct PendingShellCommand {
+    /// Message carrying the Run/Cancel buttons (older buttons are rejected)
+    pub message_id: MessageId,
+    pub command: String,
+}
+
+/// Destructive command patterns with a short description shown in the confirmation
+#[allow(clippy::expect_used)]
+fn dangerous_patterns() -> &'static [(Regex, &'static str)] {
+    static PATTERNS: OnceLock<Vec<(Regex, &'static str)>> = OnceLock::new();
+    PATTERNS.get_or_init(|| {
+        [
+            (
+                r"\brm\s+(-[a-zA-Z]*[rR][a-zA-Z]*|--recursive)\b",
+                "recursive delete (rm -r)",
+            ),
+            (r"\bdd\b.*\bof=", "raw disk write (dd)"),
+            (r"\bmkfs(\.\w+)?\b", "filesystem format (mkfs)"),
+            (
+                r"\b(wipefs|shred|fdisk|parted|sfdisk)\b",
+                "disk wipe/partition tool",
+            ),
+            (r">\s*/dev/(sd|nvme|hd|vd|disk)", "write to a block device"),
+            (
+                r"\bchmod\s+(-[a-zA-Z]*R[a-zA-Z]*\s+)?[0-7]*777\s+/",
+                "world-writable permissions on a system path",
+            ),
+            (
+                r"\bchown\s+-[a-zA-Z]*R[a-zA-Z]*\s+\S+\s+/(\s|$)",
+                "recursive chown of /",
+            ),
+            (r":\(\)\s*\{.*\};\s*:", "fork bomb"),
+            (
+                r"\bgit\s+reset\s+--hard\b",
+                "discard local changes (git reset --hard)",
+            ),
+            (
+                r"\bgit\s+clean\s+-[a-zA-Z]*f",
+                "delete untracked files (git clean -f)",
+            ),
+            (
+                r"\bgit\s+push\b.*(\s--force\b|\s-f\b)",
+                "force push (git push --force)",
+            ),
+            (
+                r"\b(shutdown|reboot|halt|poweroff)\b",
+                "shut down or reboot",
+            ),
+            (r"\bkill\s+-9\s+-1\b", "kill all processes"),
+        ]
+        .into_iter()
+        .map(|(pattern, reason)| {
+            (
+                Regex::new(pattern).expect("Invalid dangerous command regex"),
+                reason,
+            )
+        })
+        .collect()
+    })
+}
+
+/// Description of the first destructive pattern found in a shell command, if any
+pub(super) fn dangerous_shell_pattern(command: &str) -> Option<&'static str> {
+    dangerous_patterns()
+        .iter()
+        .find(|(regex, _)| regex.is_match(command))
+        .map(|(_, reason)| *reason)
+}
+
+/// Hold a destructive-looking `!` command and ask for confirmation
+pub(super) async fn offer_shell_confirmation(
+    bot: &Bot,
+    chat_id: ChatId,
+    command: &str,
+    reason: &str,
+    state: &SharedState,
+) -> ResponseResult<()> {
+    let keyboard = InlineKeyboardMarkup::new(vec![vec![
+        InlineKeyboardButton::callback("⚠ Run", format!("{SHELL_CALLBACK_PREFIX}run")),
+        InlineKeyboardButton::callback("Cancel", format!("{SHELL_CALLBACK_PREFIX}cancel")),
+    ]]);
+
+    shared_rate_limit_wait(state, chat_id).await;
+    let sent = bot
+        .send_message(
+            chat_id,
+            format!(
+                "⚠ <b>This command looks destructive</b> ({})\n<pre>{}</pre>\nRun it anyway?",
+                html_escape(reason),
+                html_escape(&truncate_str(command, 1000))
+            ),
+        )
+        .parse_mode(ParseMode::Html)
+        .reply_markup(keyboard)
+        .await?;
+
+    let mut data = state.lock().await;
+    data.pending_shell_commands.insert(
+        chat_id,
+        PendingShellCommand {
+            message_id: sent.id,
+            command: command.to_string(),
+        },
+    );
+    Ok(())
+}
+
+/// Handle a Run/Cancel press (owner-only). `data` is `run` or `cancel`.
+pub(super) async fn handle_shell_callback(
+    bot: &Bot,
+    query: &CallbackQuery,
+    data: &str,
+    state: &SharedState,
+) -> ResponseResult<()> {
+    let Some(message) = query.message.as_ref() else {
+        bot.answer_callback_query(&query.id).await?;
+        return Ok(());
+    };
+    let chat_id = message.chat().id;
+
+    let outcome = {
+        let mut shared = state.lock().await;
+        if !is_owner(&shared.settings, query.from.id.0) {
+            Err("Only the bot owner can run shell commands.")
+        } else if !ChatToggle::Shell.is_enabled(&shared.settings, chat_id) {
+            // /shell off after the confirmation was offered
+            shared.pending_shell_commands.remove(&chat_id);
+            Err("Direct shell (!) is disabled in this chat.")
+        } else if shared.cancel_tokens.contains_key(&chat_id)
+            || shared.shell_pids.contains_key(&chat_id)
+        {
+            Err(i18n::MSG_AI_BUSY)
+        } else {
+            match shared.pending_shell_commands.remove(&chat_id) {
+                Some(pending) if pending.message_id == message.id() => Ok(pending.command),
+                Some(pending) => {
+                    shared.pending_shell_commands.insert(chat_id, pending);
+                    Err("This confirmation has expired.")
+                }
+                None => Err("This confirmation has expired."),
+            }
+        }
+    };
+
+    let command = match outcome {
+        Ok(command) => command,
+        Err(reason) => {
+            bot.answer_callback_query(&query.id).text(reason).await?;
+            return Ok(());
+        }
+    };
+
+    bot.answer_callback_query(&query.id).await?;
+    let run = data == "run";
+    let status = if run { "▶ Running" } else { "✖ Cancelled" };
+    shared_rate_limit_wait(state, chat_id).await;
+    if let Err(e) = bot
+        .edit_message_text(
+            chat_id,
+            message.id(),
+            format!(
+                "{status}:\n<pre>{}</pre>",
+                html_escape(&truncate_str(&command, 1000))
+            ),
+        )
+        .parse_mode(ParseMode::Html)
+        .await
+    {
+        let ts = chrono::Local::now().format("%H:%M:%S");
+        logln!("  [{ts}]   ⚠ edit_message failed (shell confirm): {e}");
+    }
+
+    let ts = chrono::Local::now().format("%H:%M:%S");
+    logln!(
+        "  [{ts}] ◀ [button] !{command} ({})",
+        if run { "confirmed" } else { "cancelled" }
+    );
+    if run {
+        run_shell_command(bot, chat_id, &command, state).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dangerous_shell_pattern() {
+        for command in [
+            "rm -rf build",
+            "sudo rm -fr /",
+            "rm -r dir",
+            "rm --recursive x",
+            "dd if=/dev/zero of=/dev/sda bs=1M",
+            "mkfs.ext4 /dev/sdb1",
+            "echo x > /dev/sda",
+            "chmod -R 777 /",
+            "git reset --hard HEAD~3",
+            "git clean -fdx",
+            "git push origin main --force",
+            "cd /tmp && shred secret.txt",
+        ] {
+            assert!(dangerous_shell_pattern(command).is_some(), "{command}");
+        }
+        for command in [
+            "ls -la",
+            "rm file.txt",
+            "git status",
+            "git push origin main",
+            "cargo build --release",
+            "echo format done",
+            "grep -r dd src",
+        ] {
+            assert!(dangerous_shell_pattern(command).is_none(), "{command}");
+        }
+    }
+}
@@ -1,12 +1,27 @@
 use std::collections::HashMap;
 use std::fs;
-use std::time::{Duration, SystemTime};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock, PoisonError};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 
-use crate::session::{ai_sessions_dir, SessionData};
+use crate::codex;
+use crate::session::{
+    ai_sessions_dir, HistoryItem, HistoryType, PromptLimit, SessionData, PROMPT_LIMIT_RANGE,
+};
 
-use super::bot::{BotSettings, ChatSession};
+use super::bot::{BotSettings, ChatSession, ReplyStyle, StartPreview, ToolOutputLimits};
+use super::cron::CronJob;
+use super::file_ops::normalize_upload_subdir;
+use super::footer::MAX_FOOTER_LEN;
+use super::settings::parse_response_language;
+use super::streaming::ResponseFormat;
 
 /// Compute a short hash key from the bot token (first 16 chars of SHA-256 hex)
 pub fn token_hash(token: &str) -> String {
@@ -18,20 +33,66 @@ pub fn token_hash(token: &str) -> String {
 
 /// Bot settings path: ~/<app_dir>/bot_settings.json
 fn bot_settings_path() -> Option<std::path::PathBuf> {
-    dirs::home_dir().map(|h| h.join(crate::app::dir_name()).join("bot_settings.json"))
+    crate::app::data_dir().map(|d| d.join("bot_settings.json"))
 }
 
-pub(super) fn parse_bot_settings_entry(entry: &serde_json::Value) -> BotSettings {
-    let owner_user_id = entry.get("owner_user_id").and_then(|v| v.as_u64());
-    let last_sessions: HashMap<String, String> = entry
-        .get("last_sessions")
+/// Settings keys are the `BotSettings` field names. These (current, former) keys were
+/// renamed to follow that rule; the former name is still read from older files.
+const RENAMED_SETTINGS: &[(&str, &str)] = &[
+    ("start_previews", "start_preview"),
+    ("reply_styles", "reply_style"),
+    ("response_languages", "response_language"),
+    ("response_formats", "chat_response_format"),
+    ("upload_subdirs", "upload_subdir"),
+    ("start_dirs", "start_dir"),
+];
+
+/// Current name of a settings key that may use a former name
+fn current_setting_key(key: &str) -> &str {
+    RENAMED_SETTINGS
+        .iter()
+        .find(|(_, former)| *former == key)
+        .map_or(key, |(current, _)| current)
+}
+
+/// Value of a settings key, falling back to its former name
+fn get_setting<'a>(entry: &'a serde_json::Value, key: &str) -> Option<&'a serde_json::Value> {
+    entry.get(key).or_else(|| {
+        RENAMED_SETTINGS
+            .iter()
+            .find(|(current, _)| *current == key)
+            .and_then(|(_, former)| entry.get(former))
+    })
+}
+
+/// Parse a `{ chat_id: string }` object from a settings entry (missing -> empty)
+fn parse_string_map(entry: &serde_json::Value, key: &str) -> HashMap<String, String> {
+    get_setting(entry, key)
         .and_then(|v| v.as_object())
         .map(|obj| {
             obj.iter()
                 .filter_map(|(k, v)| v.as_str().map(|s| (k.clone(), s.to_string())))
                 .collect()
         })
-        .unwrap_or_default();
+        .unwrap_or_default()
+}
+
+/// Parse a `{ chat_id: bool }` object from a settings entry (missing -> empty)
+fn parse_bool_map(entry: &serde_json::Value, key: &str) -> HashMap<String, bool> {
+    entry
+        .get(key)
+        .and_then(|v| v.as_object())
+        .map(|obj| {
+            obj.iter()
+                .filter_map(|(k, v)| v.as_bool().map(|b| (k.clone(), b)))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+pub(super) fn parse_bot_settings_entry(entry: &serde_json::Value) -> BotSettings {
+    let owner_user_id = entry.get("owner_user_id").and_then(|v| v.as_u64());
+    let last_sessions = parse_string_map(entry, "last_sessions");
 
     let allowed_tools = match entry.get("allowed_tools") {
         Some(serde_json::Value::Array(arr)) => {
@@ -65,21 +126,128 @@ pub(super) fn parse_bot_settings_entry(entry: &serde_json::Value) -> BotSettings
         _ => HashMap::new(),
     };
 
-    let as_public_for_group_chat: HashMap<String, bool> = entry
-        .get("as_public_for_group_chat")
-        .and_then(|v| v.as_object())
-        .map(|obj| {
-            obj.iter()
-                .filter_map(|(k, v)| v.as_bool().map(|b| (k.clone(), b)))
-                .collect()
-        })
-        .unwrap_or_default();
+    let as_public_for_group_chat = parse_bool_map(entry, "as_public_for_group_chat");
 
     BotSettings {
         allowed_tools,
         last_sessions,
         owner_user_id,
+        owner_dm_chat_id: entry.get("owner_dm_chat_id").and_then(|v| v.as_i64()),
+        greeting_disabled: entry
+            .get("greeting_disabled")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false),
+        greeting_text: entry
+            .get("greeting_text")
+            .and_then(|v| v.as_str())
+            .map(String::from),
+        // A footer longer than /footer allows would crowd the response out of a message
+        footer: entry
+            .get("footer")
+            .and_then(|v| v.as_str())
+            .filter(|footer| footer.len() <= MAX_FOOTER_LEN)
+            .map(String::from),
+        error_notify: entry
+            .get("error_notify")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false),
         as_public_for_group_chat,
+        default_public: entry
+            .get("default_public")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false),
+        prompt_prefixes: parse_string_map(entry, "prompt_prefixes"),
+        prompt_suffixes: parse_string_map(entry, "prompt_suffixes"),
+        prompt_aliases: parse_string_map(entry, "prompt_aliases"),
+        command_aliases: entry
+            .get("command_aliases")
+            .and_then(|v| serde_json::from_value(v.clone()).ok())
+            .unwrap_or_default(),
+        extract_code_blocks: parse_bool_map(entry, "extract_code_blocks"),
+        verbose_output: parse_bool_map(entry, "verbose_output"),
+        input_filter: parse_bool_map(entry, "input_filter"),
+        auto_compact: parse_bool_map(entry, "auto_compact"),
+        shell_confirm: parse_bool_map(entry, "shell_confirm"),
+        progress_file: parse_bool_map(entry, "progress_file"),
+        clear_confirm: parse_bool_map(entry, "clear_confirm"),
+        audit_log: parse_bool_map(entry, "audit_log"),
+        madmax: parse_bool_map(entry, "madmax"),
+        forward_wait: parse_bool_map(entry, "forward_wait"),
+        shell_enabled: parse_bool_map(entry, "shell_enabled"),
+        media_hint: parse_bool_map(entry, "media_hint"),
+        short_paths: parse_bool_map(entry, "short_paths"),
+        stream_output: parse_bool_map(entry, "stream_output"),
+        history_recap: parse_bool_map(entry, "history_recap"),
+        stderr_notes: parse_bool_map(entry, "stderr_notes"),
+        uploads_enabled: parse_bool_map(entry, "uploads_enabled"),
+        auto_download: parse_bool_map(entry, "auto_download"),
+        strict_code: parse_bool_map(entry, "strict_code"),
+        prompt_approval: parse_bool_map(entry, "prompt_approval"),
+        typing_pause: parse_bool_map(entry, "typing_pause"),
+        project_hint: parse_bool_map(entry, "project_hint"),
+        muted_chats: parse_bool_map(entry, "muted_chats"),
+        mirror_chats: entry
+            .get("mirror_chats")
+            .and_then(|v| serde_json::from_value(v.clone()).ok())
+            .unwrap_or_default(),
+        start_previews: get_setting(entry, "start_previews")
+            .and_then(|v| serde_json::from_value(v.clone()).ok())
+            .unwrap_or_default(),
+        tool_output_limits: entry
+            .get("tool_output_limits")
+            .and_then(|v| serde_json::from_value(v.clone()).ok())
+            .unwrap_or_default(),
+        // Re-validated so a hand-edited file cannot inject disallowed flags
+        backend_args: entry
+            .get("backend_args")
+            .and_then(|v| serde_json::from_value::<HashMap<String, Vec<String>>>(v.clone()).ok())
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|(_, args)| codex::validate_backend_args(args).is_ok())
+            .collect(),
+        reply_styles: get_setting(entry, "reply_styles")
+            .and_then(|v| serde_json::from_value(v.clone()).ok())
+            .unwrap_or_default(),
+        max_response_chars: entry
+            .get("max_response_chars")
+            .and_then(|v| serde_json::from_value(v.clone()).ok())
+            .unwrap_or_default(),
+        request_cooldowns: entry
+            .get("request_cooldowns")
+            .and_then(|v| serde_json::from_value(v.clone()).ok())
+            .unwrap_or_default(),
+        command_cooldowns: entry
+            .get("command_cooldowns")
+            .and_then(|v| serde_json::from_value(v.clone()).ok())
+            .unwrap_or_default(),
+        command_cooldowns_include_owner: entry
+            .get("command_cooldowns_include_owner")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false),
+        response_languages: parse_string_map(entry, "response_languages")
+            .into_iter()
+            .filter(|(_, lang)| parse_response_language(lang).as_deref() == Ok(lang.as_str()))
+            .collect(),
+        response_formats: parse_string_map(entry, "response_formats")
+            .into_iter()
+            .filter_map(|(chat, name)| ResponseFormat::from_name(&name).map(|f| (chat, f)))
+            .collect(),
+        // Re-validated so a hand-edited file cannot point uploads outside the session
+        upload_subdirs: parse_string_map(entry, "upload_subdirs")
+            .into_iter()
+            .filter(|(_, dir)| normalize_upload_subdir(dir).as_deref() == Ok(dir.as_str()))
+            .collect(),
+        start_dirs: parse_string_map(entry, "start_dirs"),
+        response_format: entry
+            .get("response_format")
+            .and_then(|v| v.as_str())
+            .and_then(ResponseFormat::from_name)
+            .unwrap_or_default(),
+        prompt_limit: entry
+            .get("prompt_limit")
+            .and_then(|v| serde_json::from_value::<PromptLimit>(v.clone()).ok())
+            .filter(|limit| PROMPT_LIMIT_RANGE.contains(&limit.max_bytes))
+            .unwrap_or_default(),
     }
 }
 
@@ -101,29 +269,198 @@ pub(super) fn load_bot_settings(token: &str) -> BotSettings {
     parse_bot_settings_entry(entry)
 }
 
-fn write_bot_settings_file(path: &std::path::Path, token: &str, settings: &BotSettings) {
-    if let Some(parent) = path.parent() {
-        let _ = fs::create_dir_all(parent);
-    }
-
-    let mut json: serde_json::Value = if let Ok(content) = fs::read_to_string(path) {
-        serde_json::from_str(&content).unwrap_or_else(|_| serde_json::json!({}))
-    } else {
-        serde_json::json!({})
+/// Check that a settings entry (e.g. an uploaded /restore backup) has the expected shape:
+/// only known keys, each with the type `parse_bot_settings_entry` reads.
+pub(super) fn validate_bot_settings_entry(entry: &serde_json::Value) -> Result<(), String> {
+    let obj = entry
+        .as_object()
+        .ok_or("expected a JSON object of bot settings")?;
+    let is_map_of = |v: &serde_json::Value, check: fn(&serde_json::Value) -> bool| {
+        v.as_object().is_some_and(|m| m.values().all(check))
     };
+    for (key, value) in obj {
+        let valid = match current_setting_key(key) {
+            "token" => value.is_string(),
+            "owner_user_id" => value.is_u64(),
+            "owner_dm_chat_id" => value.is_i64(),
+            "error_notify"
+            | "greeting_disabled"
+            | "command_cooldowns_include_owner"
+            | "default_public" => value.is_boolean(),
+            "greeting_text" => value.is_string(),
+            "footer" => value.as_str().is_some_and(|f| f.len() <= MAX_FOOTER_LEN),
+            "response_format" => value.as_str().and_then(ResponseFormat::from_name).is_some(),
+            "prompt_limit" => serde_json::from_value::<PromptLimit>(value.clone())
+                .is_ok_and(|limit| PROMPT_LIMIT_RANGE.contains(&limit.max_bytes)),
+            "allowed_tools" => {
+                let tool_list = |v: &serde_json::Value| {
+                    v.as_array()
+                        .is_some_and(|arr| arr.iter().all(|t| t.is_string()))
+                };
+                tool_list(value) || is_map_of(value, tool_list)
+            }
+            "last_sessions" | "prompt_prefixes" | "prompt_suffixes" | "prompt_aliases"
+            | "start_dirs" => is_map_of(value, serde_json::Value::is_string),
+            "command_aliases" => is_map_of(value, |v| {
+                v.as_object()
+                    .is_some_and(|m| m.values().all(serde_json::Value::is_string))
+            }),
+            "as_public_for_group_chat"
+            | "extract_code_blocks"
+            | "verbose_output"
+            | "input_filter"
+            | "auto_compact"
+            | "shell_confirm"
+            | "progress_file"
+            | "clear_confirm"
+            | "audit_log"
+            | "madmax"
+            | "forward_wait"
+            | "shell_enabled"
+            | "media_hint"
+            | "short_paths"
+            | "stream_output"
+            | "history_recap"
+            | "stderr_notes"
+            | "uploads_enabled"
+            | "auto_download"
+            | "strict_code"
+            | "prompt_approval"
+            | "typing_pause"
+            | "project_hint"
+            | "muted_chats" => is_map_of(value, serde_json::Value::is_boolean),
+            "start_previews" => is_map_of(value, |v| {
+                serde_json::from_value::<StartPreview>(v.clone()).is_ok()
+            }),
+            "tool_output_limits" => is_map_of(value, |v| {
+                serde_json::from_value::<ToolOutputLimits>(v.clone()).is_ok()
+            }),
+            "backend_args" => is_map_of(value, |v| {
+                serde_json::from_value::<Vec<String>>(v.clone())
+                    .is_ok_and(|args| codex::validate_backend_args(&args).is_ok())
+            }),
+            "request_cooldowns" | "command_cooldowns" | "max_response_chars" => {
+                is_map_of(value, serde_json::Value::is_u64)
+            }
+            "mirror_chats" => is_map_of(value, serde_json::Value::is_i64),
+            "response_languages" => is_map_of(value, |v| {
+                v.as_str()
+                    .is_some_and(|lang| parse_response_language(lang).as_deref() == Ok(lang))
+            }),
+            "response_formats" => is_map_of(value, |v| {
+                v.as_str().and_then(ResponseFormat::from_name).is_some()
+            }),
+            "upload_subdirs" => is_map_of(value, |v| {
+                v.as_str()
+                    .is_some_and(|dir| normalize_upload_subdir(dir).as_deref() == Ok(dir))
+            }),
+            "reply_styles" => is_map_of(value, |v| {
+                v.as_str().and_then(ReplyStyle::from_name).is_some()
+            }),
+            _ => return Err(format!("unknown setting: {key}")),
+        };
+        if !valid {
+            return Err(format!("invalid value for {key}"));
+        }
+    }
+    Ok(())
+}
 
-    let key = token_hash(token);
+/// Serialize settings as a bot_settings.json entry (without the token)
+pub(super) fn bot_settings_entry(settings: &BotSettings) -> serde_json::Value {
     let mut entry = serde_json::json!({
-        "token": token,
         "allowed_tools": settings.allowed_tools,
         "last_sessions": settings.last_sessions,
         "as_public_for_group_chat": settings.as_public_for_group_chat,
+        "prompt_prefixes": settings.prompt_prefixes,
+        "prompt_suffixes": settings.prompt_suffixes,
+        "prompt_aliases": settings.prompt_aliases,
+        "command_aliases": settings.command_aliases,
+        "extract_code_blocks": settings.extract_code_blocks,
+        "verbose_output": settings.verbose_output,
+        "input_filter": settings.input_filter,
+        "auto_compact": settings.auto_compact,
+        "shell_confirm": settings.shell_confirm,
+        "progress_file": settings.progress_file,
+        "clear_confirm": settings.clear_confirm,
+        "audit_log": settings.audit_log,
+        "madmax": settings.madmax,
+        "forward_wait": settings.forward_wait,
+        "shell_enabled": settings.shell_enabled,
+        "media_hint": settings.media_hint,
+        "short_paths": settings.short_paths,
+        "stream_output": settings.stream_output,
+        "history_recap": settings.history_recap,
+        "stderr_notes": settings.stderr_notes,
+        "uploads_enabled": settings.uploads_enabled,
+        "auto_download": settings.auto_download,
+        "strict_code": settings.strict_code,
+        "muted_chats": settings.muted_chats,
+        "mirror_chats": settings.mirror_chats,
+        "start_previews": settings.start_previews,
+        "tool_output_limits": settings.tool_output_limits,
+        "backend_args": settings.backend_args,
+        "reply_styles": settings.reply_styles,
+        "max_response_chars": settings.max_response_chars,
+        "request_cooldowns": settings.request_cooldowns,
+        "command_cooldowns": settings.command_cooldowns,
+        "upload_subdirs": settings.upload_subdirs,
+        "start_dirs": settings.start_dirs,
+        "response_languages": settings.response_languages,
+        "response_formats": settings
+            .response_formats
+            .iter()
+            .map(|(chat, format)| (chat.clone(), format.name()))
+            .collect::<HashMap<_, _>>(),
+        "response_format": settings.response_format.name(),
+        "prompt_limit": settings.prompt_limit,
     });
+    // Added outside the literal, which is at serde_json's macro recursion limit
+    entry["prompt_approval"] = serde_json::json!(settings.prompt_approval);
+    entry["typing_pause"] = serde_json::json!(settings.typing_pause);
+    entry["project_hint"] = serde_json::json!(settings.project_hint);
 
     if let Some(owner_id) = settings.owner_user_id {
         entry["owner_user_id"] = serde_json::json!(owner_id);
     }
+    if let Some(dm_chat_id) = settings.owner_dm_chat_id {
+        entry["owner_dm_chat_id"] = serde_json::json!(dm_chat_id);
+    }
+    if settings.error_notify {
+        entry["error_notify"] = serde_json::json!(true);
+    }
+    if settings.greeting_disabled {
+        entry["greeting_disabled"] = serde_json::json!(true);
+    }
+    if settings.default_public {
+        entry["default_public"] = serde_json::json!(true);
+    }
+    if settings.command_cooldowns_include_owner {
+        entry["command_cooldowns_include_owner"] = serde_json::json!(true);
+    }
+    if let Some(text) = &settings.greeting_text {
+        entry["greeting_text"] = serde_json::json!(text);
+    }
+    if let Some(footer) = &settings.footer {
+        entry["footer"] = serde_json::json!(footer);
+    }
+    entry
+}
+
+fn write_bot_settings_file(path: &std::path::Path, token: &str, settings: &BotSettings) {
+    if let Some(parent) = path.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+
+    let mut json: serde_json::Value = if let Ok(content) = fs::read_to_string(path) {
+        serde_json::from_str(&content).unwrap_or_else(|_| serde_json::json!({}))
+    } else {
+        serde_json::json!({})
+    };
 
+    let key = token_hash(token);
+    let mut entry = bot_settings_entry(settings);
+    entry["token"] = serde_json::json!(token);
     json[key] = entry;
 
     if let Ok(s) = serde_json::to_string_pretty(&json) {
@@ -138,6 +475,27 @@ fn write_bot_settings_file(path: &std::path::Path, token: &str, settings: &BotSe
     }
 }
 
+/// Check that bot_settings.json parses and can be written, without changing it (/selftest).
+/// A missing file is fine as long as its directory accepts new files.
+pub(super) fn check_bot_settings_file() -> Result<String, String> {
+    let path = bot_settings_path().ok_or("no data directory")?;
+    if !path.exists() {
+        let parent = path.parent().ok_or("no data directory")?;
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        let probe = parent.join(format!(".selftest-{}", std::process::id()));
+        fs::write(&probe, b"").map_err(|e| e.to_string())?;
+        let _ = fs::remove_file(&probe);
+        return Ok(format!("{} (not created yet)", path.display()));
+    }
+    let content = fs::read_to_string(&path).map_err(|e| format!("read: {e}"))?;
+    serde_json::from_str::<serde_json::Value>(&content).map_err(|e| format!("parse: {e}"))?;
+    fs::OpenOptions::new()
+        .append(true)
+        .open(&path)
+        .map_err(|e| format!("write: {e}"))?;
+    Ok(path.display().to_string())
+}
+
 /// Save bot settings to the app-specific path.
 pub(super) fn save_bot_settings(token: &str, settings: &BotSettings) {
     if let Some(path) = bot_settings_path() {
@@ -145,6 +503,172 @@ pub(super) fn save_bot_settings(token: &str, settings: &BotSettings) {
     }
 }
 
+/// Feedback log path: ~/<app_dir>/feedback.jsonl
+fn feedback_log_path() -> Option<std::path::PathBuf> {
+    crate::app::data_dir().map(|d| d.join("feedback.jsonl"))
+}
+
+/// Append one JSON record (single line) to the reaction feedback log.
+pub(super) fn append_feedback_record(record: &serde_json::Value) {
+    let Some(path) = feedback_log_path() else {
+        return;
+    };
+    if let Some(parent) = path.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+    let Ok(line) = serde_json::to_string(record) else {
+        return;
+    };
+    if let Ok(mut file) = fs::OpenOptions::new().create(true).append(true).open(&path) {
+        let _ = writeln!(file, "{}", line);
+    }
+
+    // Feedback includes prompts: owner-only read/write (0o600)
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let _ = fs::set_permissions(&path, fs::Permissions::from_mode(0o600));
+    }
+}
+
+/// Recurring job store path: ~/<app_dir>/cron_jobs.json (jobs keyed by token hash)
+fn cron_jobs_path() -> Option<PathBuf> {
+    crate::app::data_dir().map(|d| d.join("cron_jobs.json"))
+}
+
+fn read_cron_jobs_file(path: &Path) -> HashMap<String, Vec<CronJob>> {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+/// Load this bot's /cron jobs
+pub(super) fn load_cron_jobs(token: &str) -> Vec<CronJob> {
+    let Some(path) = cron_jobs_path() else {
+        return Vec::new();
+    };
+    read_cron_jobs_file(&path)
+        .remove(&token_hash(token))
+        .unwrap_or_default()
+}
+
+/// Save this bot's /cron jobs, keeping entries of other bots intact
+pub(super) fn save_cron_jobs(token: &str, jobs: &[CronJob]) {
+    let Some(path) = cron_jobs_path() else {
+        return;
+    };
+    if let Some(parent) = path.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+    let mut all = read_cron_jobs_file(&path);
+    all.insert(token_hash(token), jobs.to_vec());
+    let Ok(json) = serde_json::to_string_pretty(&all) else {
+        return;
+    };
+    // Jobs include prompts: write atomically with owner-only read/write (0o600)
+    let tmp_path = path.with_extension("json.tmp");
+    if fs::write(&tmp_path, json).is_ok() {
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let _ = fs::set_permissions(&tmp_path, fs::Permissions::from_mode(0o600));
+        }
+        let _ = fs::rename(&tmp_path, &path);
+    }
+}
+
+/// Whether session files are written gzip-compressed (`--gzip-sessions`)
+static COMPRESS_SESSIONS: OnceLock<bool> = OnceLock::new();
+
+pub fn configure_session_compression(enabled: bool) {
+    let _ = COMPRESS_SESSIONS.set(enabled);
+}
+
+fn compress_sessions() -> bool {
+    COMPRESS_SESSIONS.get().copied().unwrap_or(false)
+}
+
+/// History item types written to session files (`--persist-history`); unset keeps all but
+/// system messages
+static PERSISTED_HISTORY: OnceLock<Vec<HistoryType>> = OnceLock::new();
+
+pub fn configure_persisted_history(types: Option<Vec<HistoryType>>) {
+    if let Some(types) = types {
+        let _ = PERSISTED_HISTORY.set(types);
+    }
+}
+
+fn is_persisted(item_type: &HistoryType) -> bool {
+    match PERSISTED_HISTORY.get() {
+        Some(types) => types.contains(item_type),
+        None => !matches!(item_type, HistoryType::System),
+    }
+}
+
+/// Parse a comma-separated list of history item types for `--persist-history`:
+/// `user`, `assistant`, `error`, `system`, `tool_use`, `tool_result`, or `all`
+pub fn parse_history_types(list: &str) -> Result<Vec<HistoryType>, String> {
+    let mut types = Vec::new();
+    for name in list.split(',').map(str::trim).filter(|n| !n.is_empty()) {
+        let parsed: &[HistoryType] = match name.to_lowercase().as_str() {
+            "user" => &[HistoryType::User],
+            "assistant" => &[HistoryType::Assistant],
+            "error" => &[HistoryType::Error],
+            "system" => &[HistoryType::System],
+            "tool_use" | "tooluse" => &[HistoryType::ToolUse],
+            "tool_result" | "toolresult" => &[HistoryType::ToolResult],
+            "tools" => &[HistoryType::ToolUse, HistoryType::ToolResult],
+            "all" => &[
+                HistoryType::User,
+                HistoryType::Assistant,
+                HistoryType::Error,
+                HistoryType::System,
+                HistoryType::ToolUse,
+                HistoryType::ToolResult,
+            ],
+            other => {
+                return Err(format!(
+                    "unknown history type \"{other}\" (expected user, assistant, error, \
+                     system, tool_use, tool_result, tools or all)"
+                ))
+            }
+        };
+        for item_type in parsed {
+            if !types.contains(item_type) {
+                types.push(item_type.clone());
+            }
+        }
+    }
+    if types.is_empty() {
+        return Err("no history types given".to_string());
+    }
+    Ok(types)
+}
+
+const SESSION_FILE_SUFFIX: &str = ".json";
+const GZIP_SESSION_FILE_SUFFIX: &str = ".json.gz";
+
+/// Plain (`.json`) or gzip-compressed (`.json.gz`) session file, detected by extension
+pub(super) fn is_session_file(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|n| n.to_str())
+        .is_some_and(|n| n.ends_with(SESSION_FILE_SUFFIX) || n.ends_with(GZIP_SESSION_FILE_SUFFIX))
+}
+
+fn read_session_file(path: &Path) -> Option<SessionData> {
+    let content = if path.to_string_lossy().ends_with(GZIP_SESSION_FILE_SUFFIX) {
+        let mut content = String::new();
+        GzDecoder::new(fs::File::open(path).ok()?)
+            .read_to_string(&mut content)
+            .ok()?;
+        content
+    } else {
+        fs::read_to_string(path).ok()?
+    };
+    serde_json::from_str(&content).ok()
+}
+
 pub fn cleanup_stale_sessions(max_age_days: u64) {
     let Some(sessions_dir) = ai_sessions_dir() else {
         return;
@@ -154,7 +678,7 @@ pub fn cleanup_stale_sessions(max_age_days: u64) {
     if let Ok(entries) = fs::read_dir(&sessions_dir) {
         for entry in entries.filter_map(|e| e.ok()) {
             let path = entry.path();
-            if path.extension().map(|e| e == "json").unwrap_or(false) {
+            if is_session_file(&path) {
                 if let Ok(meta) = path.metadata() {
                     if let Ok(modified) = meta.modified() {
                         if modified < cutoff {
@@ -180,65 +704,274 @@ pub fn resolve_token_by_hash(hash: &str) -> Option<String> {
         .map(String::from)
 }
 
-/// Load existing session from the session directory matching the given path
-pub(super) fn load_existing_session(
-    current_path: &str,
-) -> Option<(SessionData, std::time::SystemTime)> {
-    let mut matching_session: Option<(SessionData, std::time::SystemTime)> = None;
+/// Serializes session writes and index updates: each one reads the index, changes it and writes
+/// it back, so concurrent saves from different chats would otherwise drop each other's entries
+static SESSION_INDEX_LOCK: Mutex<()> = Mutex::new(());
 
-    let sessions_dir = ai_sessions_dir()?;
+/// Session index path: ~/<app_dir>/sessions_index.json
+fn session_index_path() -> Option<PathBuf> {
+    crate::app::data_dir().map(|d| d.join("sessions_index.json"))
+}
 
-    if !sessions_dir.exists() {
-        return None;
+/// Latest session file for one working directory
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct SessionIndexEntry {
+    /// File name inside the sessions directory
+    file: String,
+    /// Modification time of the file when indexed (ms since epoch)
+    mtime_ms: u64,
+}
+
+/// Index of `current_path` -> latest session file, so lookups avoid parsing every session.
+/// `dir_mtime_ms` records the sessions directory mtime the index is known to be complete for;
+/// files added or removed behind the index's back change it and force a rescan.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct SessionIndex {
+    #[serde(default)]
+    dir_mtime_ms: u64,
+    #[serde(default)]
+    paths: HashMap<String, SessionIndexEntry>,
+}
+
+fn mtime_ms(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+fn dir_mtime_ms(dir: &Path) -> u64 {
+    dir.metadata()
+        .and_then(|m| m.modified())
+        .map(mtime_ms)
+        .unwrap_or(0)
+}
+
+fn read_session_index(index_path: &Path) -> SessionIndex {
+    fs::read_to_string(index_path)
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+fn write_session_index(index_path: &Path, index: &SessionIndex) {
+    if let Some(parent) = index_path.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+    let Ok(json) = serde_json::to_string_pretty(index) else {
+        return;
+    };
+    // Write to a temp file and rename so concurrent readers never see a partial index
+    let tmp_path = index_path.with_extension("json.tmp");
+    if fs::write(&tmp_path, json).is_ok() {
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let _ = fs::set_permissions(&tmp_path, fs::Permissions::from_mode(0o600));
+        }
+        let _ = fs::rename(&tmp_path, index_path);
     }
+}
 
-    let Ok(entries) = fs::read_dir(&sessions_dir) else {
+/// Read the indexed session for `current_path`. None if the entry is missing or stale
+/// (file gone, modified since indexed, or belonging to another path).
+fn read_indexed_session(
+    sessions_dir: &Path,
+    index: &SessionIndex,
+    current_path: &str,
+) -> Option<(SessionData, SystemTime)> {
+    let entry = index.paths.get(current_path)?;
+    let file_path = sessions_dir.join(&entry.file);
+    if file_path.parent() != Some(sessions_dir) {
+        return None;
+    }
+    let modified = file_path.metadata().ok()?.modified().ok()?;
+    if mtime_ms(modified) != entry.mtime_ms {
         return None;
+    }
+    let session_data = read_session_file(&file_path)?;
+    (session_data.current_path == current_path).then_some((session_data, modified))
+}
+
+/// Rebuild the index by parsing every session file (newest file wins per path)
+fn scan_session_index(sessions_dir: &Path) -> SessionIndex {
+    let mut index = SessionIndex {
+        dir_mtime_ms: dir_mtime_ms(sessions_dir),
+        paths: HashMap::new(),
+    };
+
+    let Ok(entries) = fs::read_dir(sessions_dir) else {
+        return index;
     };
 
     for entry in entries.filter_map(|e| e.ok()) {
         let path = entry.path();
-        if path.extension().map(|e| e == "json").unwrap_or(false) {
-            if let Ok(content) = fs::read_to_string(&path) {
-                if let Ok(session_data) = serde_json::from_str::<SessionData>(&content) {
-                    if session_data.current_path == current_path {
-                        if let Ok(metadata) = path.metadata() {
-                            if let Ok(modified) = metadata.modified() {
-                                match &matching_session {
-                                    None => matching_session = Some((session_data, modified)),
-                                    Some((_, latest_time)) if modified > *latest_time => {
-                                        matching_session = Some((session_data, modified));
-                                    }
-                                    _ => {}
-                                }
-                            }
-                        }
-                    }
-                }
+        if !is_session_file(&path) {
+            continue;
+        }
+        let Some(file) = path.file_name().map(|n| n.to_string_lossy().to_string()) else {
+            continue;
+        };
+        let Some(session_data) = read_session_file(&path) else {
+            continue;
+        };
+        let Ok(modified) = path.metadata().and_then(|m| m.modified()) else {
+            continue;
+        };
+        let modified = mtime_ms(modified);
+        match index.paths.get(&session_data.current_path) {
+            Some(existing) if existing.mtime_ms >= modified => {}
+            _ => {
+                index.paths.insert(
+                    session_data.current_path,
+                    SessionIndexEntry {
+                        file,
+                        mtime_ms: modified,
+                    },
+                );
             }
         }
     }
 
-    matching_session
+    index
 }
 
-fn write_session_file(sessions_dir: &std::path::Path, session_data: &SessionData) {
-    if fs::create_dir_all(sessions_dir).is_err() {
+fn load_session_via_index(
+    sessions_dir: &Path,
+    index_path: &Path,
+    current_path: &str,
+) -> Option<(SessionData, SystemTime)> {
+    if !sessions_dir.exists() {
+        return None;
+    }
+
+    let index = read_session_index(index_path);
+    // Only trust the index while the directory is unchanged since it was last complete:
+    // a session written behind its back may be newer than an entry that still looks valid
+    if index.dir_mtime_ms != 0 && index.dir_mtime_ms == dir_mtime_ms(sessions_dir) {
+        if !index.paths.contains_key(current_path) {
+            // Index is complete for the current directory contents: no session for this path
+            return None;
+        }
+        if let Some(found) = read_indexed_session(sessions_dir, &index, current_path) {
+            return Some(found);
+        }
+    }
+
+    // Index missing or stale: fall back to a full scan and rebuild it
+    let index = scan_session_index(sessions_dir);
+    write_session_index(index_path, &index);
+    read_indexed_session(sessions_dir, &index, current_path)
+}
+
+/// Load existing session from the session directory matching the given path.
+/// Uses the session index when it is fresh, otherwise scans all session files.
+pub(super) fn load_existing_session(current_path: &str) -> Option<(SessionData, SystemTime)> {
+    let sessions_dir = ai_sessions_dir()?;
+    let index_path = session_index_path()?;
+    let _guard = SESSION_INDEX_LOCK
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+    load_session_via_index(&sessions_dir, &index_path, current_path)
+}
+
+/// Saved sessions for `current_path` other than `exclude_id`, newest first (at most `limit`)
+pub(super) fn load_sessions_for_path(
+    current_path: &str,
+    exclude_id: Option<&str>,
+    limit: usize,
+) -> Vec<SessionData> {
+    let Some(entries) = ai_sessions_dir().and_then(|dir| fs::read_dir(dir).ok()) else {
+        return Vec::new();
+    };
+    let mut files: Vec<(SystemTime, PathBuf)> = entries
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|path| is_session_file(path))
+        .filter_map(|path| Some((path.metadata().ok()?.modified().ok()?, path)))
+        .collect();
+    files.sort_by_key(|(modified, _)| std::cmp::Reverse(*modified));
+
+    files
+        .iter()
+        .filter_map(|(_, path)| read_session_file(path))
+        .filter(|s| s.current_path == current_path && Some(s.session_id.as_str()) != exclude_id)
+        .take(limit)
+        .collect()
+}
+
+/// Record a freshly written session file in the index. `dir_mtime_before` is the sessions
+/// directory mtime before the write; the index stays marked complete only if it was up to date.
+fn record_session_in_index(
+    sessions_dir: &Path,
+    index_path: &Path,
+    current_path: &str,
+    file_path: &Path,
+    dir_mtime_before: u64,
+) {
+    let Some(file) = file_path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+    else {
+        return;
+    };
+    let Ok(modified) = file_path.metadata().and_then(|m| m.modified()) else {
         return;
+    };
+
+    let mut index = read_session_index(index_path);
+    if index.dir_mtime_ms == dir_mtime_before {
+        index.dir_mtime_ms = dir_mtime_ms(sessions_dir);
     }
+    index.paths.insert(
+        current_path.to_string(),
+        SessionIndexEntry {
+            file,
+            mtime_ms: mtime_ms(modified),
+        },
+    );
+    write_session_index(index_path, &index);
+}
 
-    let file_path = sessions_dir.join(format!("{}.json", session_data.session_id));
+/// Write a session file as `<id>.json`, or `<id>.json.gz` when `compress` is set.
+/// A copy in the other format is removed, so existing files migrate on their next save.
+fn write_session_file(
+    sessions_dir: &Path,
+    session_data: &SessionData,
+    compress: bool,
+) -> Option<PathBuf> {
+    if fs::create_dir_all(sessions_dir).is_err() {
+        return None;
+    }
+
+    let (suffix, other_suffix) = if compress {
+        (GZIP_SESSION_FILE_SUFFIX, SESSION_FILE_SUFFIX)
+    } else {
+        (SESSION_FILE_SUFFIX, GZIP_SESSION_FILE_SUFFIX)
+    };
+    let file_path = sessions_dir.join(format!("{}{suffix}", session_data.session_id));
 
     // Security: Verify the path is within sessions directory
     if let Some(parent) = file_path.parent() {
         if parent != sessions_dir {
-            return;
+            return None;
         }
     }
 
-    if let Ok(json) = serde_json::to_string_pretty(session_data) {
-        let _ = fs::write(file_path, json);
+    if compress {
+        let json = serde_json::to_vec(session_data).ok()?;
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&json).ok()?;
+        fs::write(&file_path, encoder.finish().ok()?).ok()?;
+    } else {
+        let json = serde_json::to_string_pretty(session_data).ok()?;
+        fs::write(&file_path, json).ok()?;
     }
+
+    let other_path = sessions_dir.join(format!("{}{other_suffix}", session_data.session_id));
+    if other_path.exists() {
+        let _ = fs::remove_file(&other_path);
+    }
+    Some(file_path)
 }
 
 /// Save session to both primary and legacy session directories
@@ -251,26 +984,285 @@ pub(super) fn save_session_to_file(session: &ChatSession, current_path: &str) {
         return;
     }
 
-    // Filter out system messages
-    let saveable_history: Vec<crate::session::HistoryItem> = session
-        .history
-        .iter()
-        .filter(|item| !matches!(item.item_type, crate::session::HistoryType::System))
-        .cloned()
-        .collect();
-
+    let saveable_history = saveable_history(session);
     if saveable_history.is_empty() {
         return;
     }
 
-    let session_data = SessionData {
+    persist_session(&SessionData {
         session_id: session_id.clone(),
         history: saveable_history,
         current_path: current_path.to_string(),
         created_at: chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+        label: session.label.clone(),
+        in_progress: false,
+    });
+}
+
+/// Checkpoint a turn that is still running: the saved history plus the partial
+/// user/assistant exchange, marked `in_progress`. The save on completion overwrites it.
+pub(super) fn save_session_checkpoint(
+    session: &ChatSession,
+    current_path: &str,
+    session_id: &str,
+    user_text: &str,
+    partial_response: &str,
+) {
+    let mut history = saveable_history(session);
+    history.push(HistoryItem {
+        item_type: HistoryType::User,
+        content: user_text.to_string(),
+    });
+    history.push(HistoryItem {
+        item_type: HistoryType::Assistant,
+        content: format!("{partial_response}\n\n[In progress]"),
+    });
+
+    persist_session(&SessionData {
+        session_id: session_id.to_string(),
+        history,
+        current_path: current_path.to_string(),
+        created_at: chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+        label: session.label.clone(),
+        in_progress: true,
+    });
+}
+
+/// Session history limited to the item types written to session files (`--persist-history`)
+pub(super) fn saveable_history(session: &ChatSession) -> Vec<HistoryItem> {
+    session
+        .history
+        .iter()
+        .filter(|item| is_persisted(&item.item_type))
+        .cloned()
+        .collect()
+}
+
+/// Write session files from a backup into `sessions_dir` and index each one, so a folder that
+/// is already indexed loads the restored session instead of the file it replaced
+fn write_restored_sessions(
+    sessions_dir: &Path,
+    index_path: &Path,
+    files: &[(String, Vec<u8>)],
+) -> std::io::Result<usize> {
+    fs::create_dir_all(sessions_dir)?;
+    for (name, content) in files {
+        let dir_mtime_before = dir_mtime_ms(sessions_dir);
+        let file_path = sessions_dir.join(name);
+        fs::File::create(&file_path)?.write_all(content)?;
+        if let Some(session_data) = read_session_file(&file_path) {
+            record_session_in_index(
+                sessions_dir,
+                index_path,
+                &session_data.current_path,
+                &file_path,
+                dir_mtime_before,
+            );
+        }
+    }
+    Ok(files.len())
+}
+
+/// Write session files restored by /restoresessions. Returns the number written.
+pub(super) fn restore_session_files(files: &[(String, Vec<u8>)]) -> std::io::Result<usize> {
+    let (Some(sessions_dir), Some(index_path)) = (ai_sessions_dir(), session_index_path()) else {
+        return Err(std::io::Error::other("session folder unavailable"));
+    };
+    let _guard = SESSION_INDEX_LOCK
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+    write_restored_sessions(&sessions_dir, &index_path, files)
+}
+
+/// Write a session file and record it in the session index
+fn persist_session(session_data: &SessionData) {
+    let current_path = session_data.current_path.as_str();
+    let Some(sessions_dir) = ai_sessions_dir() else {
+        return;
     };
+    let _guard = SESSION_INDEX_LOCK
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+    let dir_mtime_before = dir_mtime_ms(&sessions_dir);
+    if let Some(file_path) = write_session_file(&sessions_dir, session_data, compress_sessions()) {
+        if let Some(index_path) = session_index_path() {
+            record_session_in_index(
+                &sessions_dir,
+                &index_path,
+                current_path,
+                &file_path,
+                dir_mtime_before,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_test_session(dir: &Path, id: &str, current_path: &str, age_secs: u64) {
+        let data = SessionData {
+            session_id: id.to_string(),
+            history: Vec::new(),
+            current_path: current_path.to_string(),
+            created_at: String::new(),
+            label: None,
+            in_progress: false,
+        };
+        let file_path = dir.join(format!("{id}.json"));
+        let _ = fs::write(&file_path, serde_json::to_string(&data).unwrap_or_default());
+        if let Ok(file) = fs::File::options().write(true).open(&file_path) {
+            let _ = file.set_modified(SystemTime::now() - Duration::from_secs(age_secs));
+        }
+    }
+
+    #[test]
+    fn test_parse_history_types() {
+        assert_eq!(
+            parse_history_types("user, Assistant,user"),
+            Ok(vec![HistoryType::User, HistoryType::Assistant])
+        );
+        assert_eq!(
+            parse_history_types("error,tools"),
+            Ok(vec![
+                HistoryType::Error,
+                HistoryType::ToolUse,
+                HistoryType::ToolResult
+            ])
+        );
+        assert_eq!(parse_history_types("all").map(|t| t.len()), Ok(6));
+        assert!(parse_history_types("users").is_err());
+        assert!(parse_history_types(" , ").is_err());
+    }
+
+    #[test]
+    fn test_session_index_lookup_and_stale_fallback() {
+        let root = std::env::temp_dir().join("opencodex_test_session_index");
+        let _ = fs::remove_dir_all(&root);
+        let sessions_dir = root.join("sessions");
+        let index_path = root.join("sessions_index.json");
+        let _ = fs::create_dir_all(&sessions_dir);
+        write_test_session(&sessions_dir, "old", "/proj", 200);
+        write_test_session(&sessions_dir, "new", "/proj", 100);
+        write_test_session(&sessions_dir, "other", "/other", 100);
+
+        // No index yet: full scan picks the newest session and writes the index
+        let found = load_session_via_index(&sessions_dir, &index_path, "/proj");
+        assert_eq!(found.map(|(s, _)| s.session_id), Some("new".to_string()));
+        let index = read_session_index(&index_path);
+        assert_eq!(index.paths.len(), 2);
+        assert_eq!(
+            index.paths.get("/proj").map(|e| e.file.as_str()),
+            Some("new.json")
+        );
+
+        // Indexed file removed: entry is stale, rescan falls back to the older session
+        let _ = fs::remove_file(sessions_dir.join("new.json"));
+        let found = load_session_via_index(&sessions_dir, &index_path, "/proj");
+        assert_eq!(found.map(|(s, _)| s.session_id), Some("old".to_string()));
+
+        assert!(load_session_via_index(&sessions_dir, &index_path, "/missing").is_none());
+
+        // A newer session written behind the index: the indexed entry is still readable, but
+        // the directory changed since the index was complete, so it is rescanned
+        write_test_session(&sessions_dir, "outside", "/proj", 0);
+        let mut index = read_session_index(&index_path);
+        index.dir_mtime_ms -= 1;
+        write_session_index(&index_path, &index);
+        let found = load_session_via_index(&sessions_dir, &index_path, "/proj");
+        assert_eq!(
+            found.map(|(s, _)| s.session_id),
+            Some("outside".to_string())
+        );
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_restored_session_replaces_indexed_one() {
+        let root = std::env::temp_dir().join("opencodex_test_session_restore");
+        let _ = fs::remove_dir_all(&root);
+        let sessions_dir = root.join("sessions");
+        let index_path = root.join("sessions_index.json");
+        let _ = fs::create_dir_all(&sessions_dir);
+        write_test_session(&sessions_dir, "current", "/proj", 0);
+        let found = load_session_via_index(&sessions_dir, &index_path, "/proj");
+        assert_eq!(
+            found.map(|(s, _)| s.session_id),
+            Some("current".to_string())
+        );
+
+        let restored = SessionData {
+            session_id: "restored".to_string(),
+            history: Vec::new(),
+            current_path: "/proj".to_string(),
+            created_at: String::new(),
+            label: None,
+            in_progress: false,
+        };
+        let files = vec![(
+            "restored.json".to_string(),
+            serde_json::to_vec(&restored).unwrap_or_default(),
+        )];
+        assert_eq!(
+            write_restored_sessions(&sessions_dir, &index_path, &files).ok(),
+            Some(1)
+        );
+        assert_eq!(
+            fs::read(sessions_dir.join("restored.json")).ok(),
+            Some(files[0].1.clone())
+        );
+        // The index itself points at the restored file, without waiting for a rescan
+        let index = read_session_index(&index_path);
+        assert_eq!(
+            index.paths.get("/proj").map(|e| e.file.as_str()),
+            Some("restored.json")
+        );
+        assert_eq!(index.dir_mtime_ms, dir_mtime_ms(&sessions_dir));
+        let found = load_session_via_index(&sessions_dir, &index_path, "/proj");
+        assert_eq!(
+            found.map(|(s, _)| s.session_id),
+            Some("restored".to_string())
+        );
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_gzip_session_file_roundtrip_and_migration() {
+        let dir = std::env::temp_dir().join("opencodex_test_gzip_sessions");
+        let _ = fs::remove_dir_all(&dir);
+        let data = SessionData {
+            session_id: "gz".to_string(),
+            history: vec![HistoryItem {
+                item_type: HistoryType::User,
+                content: "hello ".repeat(100),
+            }],
+            current_path: "/proj".to_string(),
+            created_at: String::new(),
+            label: None,
+            in_progress: false,
+        };
+
+        let plain = write_session_file(&dir, &data, false);
+        assert_eq!(plain, Some(dir.join("gz.json")));
+
+        // Next save with compression replaces the plain file
+        let gz = write_session_file(&dir, &data, true).unwrap_or_default();
+        assert_eq!(gz, dir.join("gz.json.gz"));
+        assert!(!dir.join("gz.json").exists());
+        assert!(is_session_file(&gz));
+        let loaded = read_session_file(&gz);
+        assert_eq!(loaded.map(|s| s.history.len()), Some(1));
+        assert_eq!(
+            scan_session_index(&dir)
+                .paths
+                .get("/proj")
+                .map(|e| e.file.as_str()),
+            Some("gz.json.gz")
+        );
 
-    if let Some(sessions_dir) = ai_sessions_dir() {
-        write_session_file(&sessions_dir, &session_data);
+        let _ = fs::remove_dir_all(&dir);
     }
 }
@@ -1,5 +1,5 @@
 use teloxide::prelude::*;
-use teloxide::types::ParseMode;
+use teloxide::types::{MessageId, ParseMode};
 
 use super::bot::{SharedState, TELEGRAM_MSG_LIMIT};
 
@@ -16,19 +16,32 @@ pub(super) fn floor_char_boundary(s: &str, index: usize) -> usize {
     }
 }
 
+/// Minimum gap between two Telegram API calls for the same chat
+pub(super) const MIN_SEND_GAP: tokio::time::Duration = tokio::time::Duration::from_millis(3000);
+
+/// How long after a chat's last incoming message streaming edits stay paused (/typingpause)
+pub(super) const TYPING_QUIET_PERIOD: tokio::time::Duration = tokio::time::Duration::from_secs(10);
+
+/// True if a message arrived in the chat less than TYPING_QUIET_PERIOD before `now`
+pub(super) fn chat_recently_active(
+    last_inbound: Option<tokio::time::Instant>,
+    now: tokio::time::Instant,
+) -> bool {
+    last_inbound.is_some_and(|last| now.saturating_duration_since(last) < TYPING_QUIET_PERIOD)
+}
+
 /// Shared per-chat rate limiter using reservation pattern.
 /// Acquires the lock briefly to calculate and reserve the next API call slot,
 /// then releases the lock and sleeps until the reserved time.
 /// This ensures that even concurrent tasks for the same chat maintain 3s gaps.
 pub(super) async fn shared_rate_limit_wait(state: &SharedState, chat_id: ChatId) {
-    let min_gap = tokio::time::Duration::from_millis(3000);
     let sleep_until = {
         let mut data = state.lock().await;
         let last = data
             .api_timestamps
             .entry(chat_id)
             .or_insert_with(|| tokio::time::Instant::now() - tokio::time::Duration::from_secs(10));
-        let earliest_next = *last + min_gap;
+        let earliest_next = *last + MIN_SEND_GAP;
         let now = tokio::time::Instant::now();
         let target = if earliest_next > now {
             earliest_next
@@ -41,90 +54,351 @@ pub(super) async fn shared_rate_limit_wait(state: &SharedState, chat_id: ChatId)
     tokio::time::sleep_until(sleep_until).await;
 }
 
-/// Send a message that may exceed Telegram's 4096 character limit
-/// by splitting it into multiple messages, handling UTF-8 boundaries
-/// and unclosed HTML tags (e.g. <pre>) across split points
-pub(super) async fn send_long_message(
-    bot: &Bot,
-    chat_id: ChatId,
-    text: &str,
-    parse_mode: Option<ParseMode>,
-    state: &SharedState,
-) -> ResponseResult<()> {
-    if text.len() <= TELEGRAM_MSG_LIMIT {
-        shared_rate_limit_wait(state, chat_id).await;
-        let mut req = bot.send_message(chat_id, text);
-        if let Some(mode) = parse_mode {
-            req = req.parse_mode(mode);
+/// Parse mode used for AI responses (bot-level setting, changed via /parsemode;
+/// a chat can override it with /format)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(super) enum ResponseFormat {
+    #[default]
+    Html,
+    MarkdownV2,
+    /// Sent as-is: no markdown conversion and no parse mode
+    Plain,
+}
+
+impl ResponseFormat {
+    /// Name used in settings and the /parsemode command
+    pub(super) fn name(self) -> &'static str {
+        match self {
+            ResponseFormat::Html => "html",
+            ResponseFormat::MarkdownV2 => "markdownv2",
+            ResponseFormat::Plain => "off",
+        }
+    }
+
+    pub(super) fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "html" => Some(ResponseFormat::Html),
+            "markdownv2" | "mdv2" => Some(ResponseFormat::MarkdownV2),
+            "off" | "plain" => Some(ResponseFormat::Plain),
+            _ => None,
         }
-        req.await?;
-        return Ok(());
     }
 
-    let is_html = parse_mode.is_some();
+    pub(super) fn parse_mode(self) -> Option<ParseMode> {
+        match self {
+            ResponseFormat::Html => Some(ParseMode::Html),
+            ResponseFormat::MarkdownV2 => Some(ParseMode::MarkdownV2),
+            ResponseFormat::Plain => None,
+        }
+    }
+
+    /// Render standard markdown for this parse mode
+    pub(super) fn render(self, md: &str) -> String {
+        match self {
+            ResponseFormat::Html => markdown_to_telegram_html(md),
+            ResponseFormat::MarkdownV2 => markdown_to_telegram_markdownv2(md),
+            ResponseFormat::Plain => md.to_string(),
+        }
+    }
+}
+
+/// Code block open/close markers for a parse mode, used to rebalance split chunks
+fn code_block_markers(parse_mode: Option<ParseMode>) -> Option<(&'static str, &'static str)> {
+    match parse_mode {
+        Some(ParseMode::Html) => Some(("<pre>", "</pre>")),
+        Some(ParseMode::MarkdownV2) => Some(("```\n", "\n```")),
+        _ => None,
+    }
+}
+
+/// Whether a code block is still open after `chunk`, given the state before it
+fn code_block_open_after(chunk: &str, parse_mode: Option<ParseMode>, in_pre: bool) -> bool {
+    match parse_mode {
+        Some(ParseMode::Html) => {
+            let last_open = chunk.rfind("<pre>");
+            let last_close = chunk.rfind("</pre>");
+            match (last_open, last_close) {
+                (Some(o), Some(c)) => o > c,
+                (Some(_), None) => true,
+                (None, Some(_)) => false,
+                (None, None) => in_pre,
+            }
+        }
+        // Fences open and close with the same marker; escaped backticks never form one
+        Some(ParseMode::MarkdownV2) => in_pre ^ (chunk.matches("```").count() % 2 == 1),
+        _ => false,
+    }
+}
+
+/// Telegram rejects messages carrying more formatting entities than this
+const TELEGRAM_ENTITY_LIMIT: usize = 100;
+
+/// Byte offset where an HTML chunk must end to stay within the entity limit, or None if
+/// `text` has few enough tags. `reserved` entities are already used by the chunk (a
+/// reopened `<pre>`). The cut is placed before an opening tag outside any other tag, so
+/// no element is left unclosed.
+fn html_entity_cut(text: &str, reserved: usize) -> Option<usize> {
+    let mut entities = reserved;
+    let mut depth = 0usize;
+    let mut last_top_level = None;
+    for (i, _) in text.match_indices('<') {
+        if text[i + 1..].starts_with('/') {
+            depth = depth.saturating_sub(1);
+            continue;
+        }
+        if depth == 0 && i > 0 {
+            last_top_level = Some(i);
+        }
+        entities += 1;
+        if entities > TELEGRAM_ENTITY_LIMIT {
+            return last_top_level.or((i > 0).then_some(i));
+        }
+        depth += 1;
+    }
+    None
+}
+
+/// Where to end an HTML chunk taken from the start of `text`: the last newline outside any
+/// inline tag (<b>, <code>, <a> ...), tag or entity, else the last such position. None if
+/// there is none. <pre> does not count, since split_message closes and reopens it.
+fn html_split_point(text: &str) -> Option<usize> {
+    let mut depth = 0usize;
+    let mut tag_start = None;
+    let mut in_entity = false;
+    let mut last_newline = None;
+    let mut last_safe = None;
+    for (i, c) in text.char_indices() {
+        if let Some(start) = tag_start {
+            if c == '>' {
+                tag_start = None;
+                let tag = &text[start + 1..i];
+                let (closing, tag) = match tag.strip_prefix('/') {
+                    Some(name) => (true, name),
+                    None => (false, tag),
+                };
+                let name = tag.split_whitespace().next().unwrap_or("");
+                if name != "pre" {
+                    depth = if closing {
+                        depth.saturating_sub(1)
+                    } else {
+                        depth + 1
+                    };
+                }
+            }
+            continue;
+        }
+        if in_entity {
+            in_entity = c.is_ascii_alphanumeric() || c == '#';
+            if c == ';' || in_entity {
+                continue;
+            }
+        }
+        if depth == 0 && i > 0 {
+            last_safe = Some(i);
+            if c == '\n' {
+                last_newline = Some(i);
+            }
+        }
+        match c {
+            '<' => tag_start = Some(i),
+            '&' => in_entity = true,
+            _ => {}
+        }
+    }
+    last_newline.or(last_safe)
+}
+
+/// Split a message into chunks that fit Telegram's 4096 character limit and (for HTML)
+/// its entity limit, handling UTF-8 boundaries and unclosed code blocks
+/// (HTML <pre> or MarkdownV2 fences) across split points
+fn split_message(text: &str, parse_mode: Option<ParseMode>) -> Vec<String> {
+    let markers = code_block_markers(parse_mode);
     let mut remaining = text;
     let mut in_pre = false;
+    let mut chunks = Vec::new();
 
     while !remaining.is_empty() {
-        // Reserve space for tags we may need to add (<pre> + </pre> = 11 bytes)
-        let tag_overhead = if is_html && in_pre { 11 } else { 0 };
+        // Reserve space for markers we may need to add (e.g. <pre> + </pre> = 11 bytes)
+        let tag_overhead = match markers {
+            Some((open, close)) if in_pre => open.len() + close.len(),
+            _ => 0,
+        };
         let effective_limit = TELEGRAM_MSG_LIMIT.saturating_sub(tag_overhead);
+        let entity_cut = match parse_mode {
+            Some(ParseMode::Html) => html_entity_cut(remaining, usize::from(in_pre)),
+            _ => None,
+        };
 
-        if remaining.len() <= effective_limit {
-            let mut chunk = String::new();
-            if is_html && in_pre {
-                chunk.push_str("<pre>");
-            }
-            chunk.push_str(remaining);
+        let mut chunk = String::new();
+        if let Some((open, _)) = markers.filter(|_| in_pre) {
+            chunk.push_str(open);
+        }
 
-            shared_rate_limit_wait(state, chat_id).await;
-            let mut req = bot.send_message(chat_id, &chunk);
-            if let Some(mode) = parse_mode {
-                req = req.parse_mode(mode);
-            }
-            req.await?;
+        if remaining.len() <= effective_limit && entity_cut.is_none() {
+            chunk.push_str(remaining);
+            chunks.push(chunk);
             break;
         }
 
         // Find a safe UTF-8 char boundary, then find a newline before it
-        let safe_end = floor_char_boundary(remaining, effective_limit);
-        let split_at = remaining[..safe_end].rfind('\n').unwrap_or(safe_end);
+        let limit = entity_cut.map_or(effective_limit, |cut| cut.min(effective_limit));
+        let safe_end = floor_char_boundary(remaining, limit);
+        let split_at = match parse_mode {
+            Some(ParseMode::Html) => html_split_point(&remaining[..safe_end]),
+            _ => None,
+        }
+        .or_else(|| remaining[..safe_end].rfind('\n'))
+        .unwrap_or(safe_end);
 
         let (raw_chunk, rest) = remaining.split_at(split_at);
-
-        let mut chunk = String::new();
-        if is_html && in_pre {
-            chunk.push_str("<pre>");
-        }
         chunk.push_str(raw_chunk);
 
-        // Track unclosed <pre> tags to close/reopen across chunks
-        if is_html {
-            let last_open = raw_chunk.rfind("<pre>");
-            let last_close = raw_chunk.rfind("</pre>");
-            in_pre = match (last_open, last_close) {
-                (Some(o), Some(c)) => o > c,
-                (Some(_), None) => true,
-                (None, Some(_)) => false,
-                (None, None) => in_pre,
-            };
-            if in_pre {
-                chunk.push_str("</pre>");
-            }
+        // Track unclosed code blocks to close/reopen across chunks
+        in_pre = code_block_open_after(raw_chunk, parse_mode, in_pre);
+        if let Some((_, close)) = markers.filter(|_| in_pre) {
+            chunk.push_str(close);
         }
+        chunks.push(chunk);
+
+        // Skip the newline character at the split point
+        remaining = rest.strip_prefix('\n').unwrap_or(rest);
+    }
 
+    chunks
+}
+
+/// Send a message that may exceed Telegram's 4096 character limit or entity limit
+/// by splitting it into multiple messages (see `split_message`).
+/// Returns the ID of the first message sent.
+pub(super) async fn send_long_message(
+    bot: &Bot,
+    chat_id: ChatId,
+    text: &str,
+    parse_mode: Option<ParseMode>,
+    state: &SharedState,
+) -> ResponseResult<Option<MessageId>> {
+    let mut first_id: Option<MessageId> = None;
+    for chunk in split_message(text, parse_mode) {
         shared_rate_limit_wait(state, chat_id).await;
         let mut req = bot.send_message(chat_id, &chunk);
         if let Some(mode) = parse_mode {
             req = req.parse_mode(mode);
         }
-        req.await?;
+        let sent = req.await?;
+        first_id.get_or_insert(sent.id);
+    }
 
-        // Skip the newline character at the split point
-        remaining = rest.strip_prefix('\n').unwrap_or(rest);
+    Ok(first_id)
+}
+
+/// A stretch of a response: prose rendered with the chat's parse mode, or the body of a
+/// fenced code block sent verbatim (/strictcode)
+#[derive(Debug, PartialEq, Eq)]
+pub(super) enum ResponseSegment {
+    Prose(String),
+    Code(String),
+}
+
+/// Split markdown into prose and fenced code block bodies, in order. Blank prose between
+/// blocks is dropped; an unterminated fence runs to the end.
+pub(super) fn split_code_segments(md: &str) -> Vec<ResponseSegment> {
+    let mut segments = Vec::new();
+    let mut prose: Vec<&str> = Vec::new();
+    let mut code: Option<Vec<&str>> = None;
+    for line in md.lines() {
+        let is_fence = line.trim_start().starts_with("```");
+        match code.as_mut() {
+            Some(body) if is_fence => {
+                segments.push(ResponseSegment::Code(body.join("\n")));
+                code = None;
+            }
+            Some(body) => body.push(line),
+            None if is_fence => {
+                let text = prose.join("\n");
+                if !text.trim().is_empty() {
+                    segments.push(ResponseSegment::Prose(text.trim().to_string()));
+                }
+                prose.clear();
+                code = Some(Vec::new());
+            }
+            None => prose.push(line),
+        }
+    }
+    if let Some(body) = code {
+        segments.push(ResponseSegment::Code(body.join("\n")));
+    }
+    let text = prose.join("\n");
+    if !text.trim().is_empty() {
+        segments.push(ResponseSegment::Prose(text.trim().to_string()));
+    }
+    segments
+}
+
+/// Send a response as alternating messages: prose in `format` (plain text if Telegram
+/// rejects it) and code blocks as plain text, so code always arrives verbatim (/strictcode).
+/// Returns the ID of the first message sent.
+pub(super) async fn send_code_segments(
+    bot: &Bot,
+    chat_id: ChatId,
+    segments: &[ResponseSegment],
+    format: ResponseFormat,
+    state: &SharedState,
+) -> ResponseResult<Option<MessageId>> {
+    let mut first_id: Option<MessageId> = None;
+    for segment in segments {
+        let sent = match segment {
+            // Telegram rejects empty messages
+            ResponseSegment::Code(code) if code.trim().is_empty() => continue,
+            ResponseSegment::Code(code) => {
+                send_long_message(bot, chat_id, code, None, state).await?
+            }
+            ResponseSegment::Prose(prose) => {
+                match send_long_message(
+                    bot,
+                    chat_id,
+                    &format.render(prose),
+                    format.parse_mode(),
+                    state,
+                )
+                .await
+                {
+                    Ok(id) => id,
+                    Err(_) => send_long_message(bot, chat_id, prose, None, state).await?,
+                }
+            }
+        };
+        if first_id.is_none() {
+            first_id = sent;
+        }
     }
+    Ok(first_id)
+}
+
+/// Frames of the "Processing.." indicator appended to a placeholder while a backend runs
+pub(super) const PROGRESS_SPINNER: &[&str] = &[
+    "P",
+    "Pr",
+    "Pro",
+    "Proc",
+    "Proce",
+    "Proces",
+    "Process",
+    "Processi",
+    "Processin",
+    "Processing",
+    "Processing.",
+    "Processing..",
+];
 
-    Ok(())
+/// Placeholder text while a response streams in: the text so far, cut to fit one message,
+/// followed by the progress indicator
+pub(super) fn progress_text(response: &str, indicator: &str) -> String {
+    if response.is_empty() {
+        return indicator.to_string();
+    }
+    let normalized = normalize_empty_lines(response);
+    let truncated = truncate_str(&normalized, TELEGRAM_MSG_LIMIT - 20 - indicator.len());
+    format!("{}\n\n{}", truncated, indicator)
 }
 
 /// Normalize consecutive empty lines to maximum of one
@@ -151,6 +425,54 @@ pub(super) fn normalize_empty_lines(s: &str) -> String {
     result
 }
 
+/// Remove ANSI escape sequences (colors, cursor movement, terminal titles) from CLI output
+pub(super) fn strip_ansi(s: &str) -> String {
+    if !s.contains(['\x1b', '\u{9b}']) {
+        return s.to_string();
+    }
+    let mut result = String::with_capacity(s.len());
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        let csi = match c {
+            '\u{9b}' => true,
+            '\x1b' => match chars.peek() {
+                Some('[') => {
+                    chars.next();
+                    true
+                }
+                Some(']') => {
+                    // OSC: runs until BEL or ESC \
+                    chars.next();
+                    while let Some(c) = chars.next() {
+                        if c == '\x07' || (c == '\x1b' && chars.next_if_eq(&'\\').is_some()) {
+                            break;
+                        }
+                    }
+                    continue;
+                }
+                _ => {
+                    // Other escapes: intermediate bytes, then one final byte
+                    while chars.next_if(|c| ('\x20'..='\x2f').contains(c)).is_some() {}
+                    chars.next();
+                    continue;
+                }
+            },
+            _ => false,
+        };
+        if csi {
+            // CSI: parameter/intermediate bytes until a final byte in @..~
+            for c in chars.by_ref() {
+                if ('\x40'..='\x7e').contains(&c) {
+                    break;
+                }
+            }
+        } else {
+            result.push(c);
+        }
+    }
+    result
+}
+
 /// Escape special HTML characters for Telegram HTML parse mode
 pub(super) fn html_escape(s: &str) -> String {
     s.replace('&', "&amp;")
@@ -341,6 +663,157 @@ fn find_closing_single(chars: &[char], start: usize, marker: char) -> Option<usi
     None
 }
 
+/// Characters that must be escaped everywhere outside code in MarkdownV2
+const MARKDOWNV2_SPECIAL: &[char] = &[
+    '_', '*', '[', ']', '(', ')', '~', '`', '>', '#', '+', '-', '=', '|', '{', '}', '.', '!', '\\',
+];
+
+/// Escape text for Telegram MarkdownV2 (outside code entities)
+pub(super) fn markdownv2_escape(s: &str) -> String {
+    let mut result = String::with_capacity(s.len());
+    for c in s.chars() {
+        if MARKDOWNV2_SPECIAL.contains(&c) {
+            result.push('\\');
+        }
+        result.push(c);
+    }
+    result
+}
+
+/// Escape text inside MarkdownV2 `code` and ```pre``` entities (only backtick and backslash)
+fn markdownv2_escape_code(s: &str) -> String {
+    let mut result = String::with_capacity(s.len());
+    for c in s.chars() {
+        if c == '`' || c == '\\' {
+            result.push('\\');
+        }
+        result.push(c);
+    }
+    result
+}
+
+/// Convert standard markdown to Telegram MarkdownV2
+pub(super) fn markdown_to_telegram_markdownv2(md: &str) -> String {
+    let lines: Vec<&str> = md.lines().collect();
+    let mut result = String::new();
+    let mut i = 0;
+
+    while i < lines.len() {
+        let trimmed = lines[i].trim_start();
+
+        // Fenced code block
+        if trimmed.starts_with("```") {
+            let mut code_lines = Vec::new();
+            i += 1; // skip opening ```
+            while i < lines.len() {
+                if lines[i].trim_start().starts_with("```") {
+                    break;
+                }
+                code_lines.push(lines[i]);
+                i += 1;
+            }
+            let code = code_lines.join("\n");
+            if !code.is_empty() {
+                result.push_str(&format!(
+                    "```\n{}\n```",
+                    markdownv2_escape_code(code.trim_end())
+                ));
+            }
+            result.push('\n');
+            i += 1; // skip closing ```
+            continue;
+        }
+
+        // Heading (# ~ ######)
+        if let Some(rest) = strip_heading(trimmed) {
+            result.push_str(&format!("*{}*", markdownv2_escape(rest)));
+            result.push('\n');
+            i += 1;
+            continue;
+        }
+
+        // Unordered list (- or *)
+        if let Some(stripped) = trimmed.strip_prefix("- ") {
+            result.push_str(&format!("• {}", convert_inline_markdownv2(stripped)));
+            result.push('\n');
+            i += 1;
+            continue;
+        }
+        if trimmed.starts_with("* ") && !trimmed.starts_with("**") {
+            if let Some(stripped) = trimmed.strip_prefix("* ") {
+                result.push_str(&format!("• {}", convert_inline_markdownv2(stripped)));
+            }
+            result.push('\n');
+            i += 1;
+            continue;
+        }
+
+        // Regular line
+        result.push_str(&convert_inline_markdownv2(lines[i]));
+        result.push('\n');
+        i += 1;
+    }
+
+    result.trim_end().to_string()
+}
+
+/// Convert inline markdown (code, bold, italic) in raw text to escaped MarkdownV2
+fn convert_inline_markdownv2(text: &str) -> String {
+    let mut result = String::new();
+    let mut remaining = text;
+
+    loop {
+        if let Some(start) = remaining.find('`') {
+            let after_start = &remaining[start + 1..];
+            if let Some(end) = after_start.find('`') {
+                let before = &remaining[..start];
+                let code_content = &after_start[..end];
+                result.push_str(&convert_bold_italic_markdownv2(before));
+                result.push_str(&format!("`{}`", markdownv2_escape_code(code_content)));
+                remaining = &after_start[end + 1..];
+                continue;
+            }
+        }
+        result.push_str(&convert_bold_italic_markdownv2(remaining));
+        break;
+    }
+
+    result
+}
+
+/// Convert bold (**...**) to *...* and italic (*...*) to _..._, escaping everything else
+fn convert_bold_italic_markdownv2(text: &str) -> String {
+    let mut result = String::new();
+    let chars: Vec<char> = text.chars().collect();
+    let len = chars.len();
+    let mut i = 0;
+
+    while i < len {
+        // Bold: **...**
+        if i + 1 < len && chars[i] == '*' && chars[i + 1] == '*' {
+            if let Some(end) = find_closing_marker(&chars, i + 2, &['*', '*']) {
+                let inner: String = chars[i + 2..end].iter().collect();
+                result.push_str(&format!("*{}*", markdownv2_escape(&inner)));
+                i = end + 2;
+                continue;
+            }
+        }
+        // Italic: *...*
+        if chars[i] == '*' {
+            if let Some(end) = find_closing_single(&chars, i + 1, '*') {
+                let inner: String = chars[i + 1..end].iter().collect();
+                result.push_str(&format!("_{}_", markdownv2_escape(&inner)));
+                i = end + 1;
+                continue;
+            }
+        }
+        result.push_str(&markdownv2_escape(&chars[i].to_string()));
+        i += 1;
+    }
+
+    result
+}
+
 /// Format tool input JSON into a human-readable summary
 pub(super) fn format_tool_input(name: &str, input: &str) -> String {
     let Ok(v) = serde_json::from_str::<serde_json::Value>(input) else {
@@ -383,6 +856,23 @@ pub(super) fn format_tool_input(name: &str, input: &str) -> String {
                 format!("Edit {}", fp)
             }
         }
+        "apply_patch" => {
+            let paths: Vec<&str> = v
+                .get("changes")
+                .and_then(|c| c.as_array())
+                .map(|changes| {
+                    changes
+                        .iter()
+                        .filter_map(|c| c.get("path").and_then(|p| p.as_str()))
+                        .collect()
+                })
+                .unwrap_or_default();
+            if paths.is_empty() {
+                format!("{} {}", name, truncate_str(input, 200))
+            } else {
+                format!("Patch {}", truncate_str(&paths.join(", "), 200))
+            }
+        }
         "Glob" => {
             let pattern = v.get("pattern").and_then(|v| v.as_str()).unwrap_or("");
             let path = v.get("path").and_then(|v| v.as_str()).unwrap_or("");
@@ -509,3 +999,168 @@ pub(super) fn format_tool_input(name: &str, input: &str) -> String {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chat_recently_active() {
+        let now = tokio::time::Instant::now();
+        let second = tokio::time::Duration::from_secs(1);
+        assert!(!chat_recently_active(None, now));
+        assert!(chat_recently_active(Some(now), now));
+        assert!(chat_recently_active(Some(now - second), now));
+        assert!(!chat_recently_active(Some(now - TYPING_QUIET_PERIOD), now));
+        // A message stamped after `now` (taken earlier) still counts as recent
+        assert!(chat_recently_active(Some(now + second), now));
+    }
+
+    #[test]
+    fn test_progress_text() {
+        assert_eq!(progress_text("", "Pro"), "Pro");
+        assert_eq!(progress_text("a\n\n\nb", "Pro"), "a\n\nb\n\nPro");
+        let long = "x".repeat(TELEGRAM_MSG_LIMIT * 2);
+        assert!(progress_text(&long, "Pro").len() <= TELEGRAM_MSG_LIMIT);
+    }
+
+    #[test]
+    fn test_split_code_segments() {
+        let md = "Run this:\n\n```bash\necho <b> & done\n```\n\nThen:\n```\nx\n```";
+        assert_eq!(
+            split_code_segments(md),
+            vec![
+                ResponseSegment::Prose("Run this:".to_string()),
+                ResponseSegment::Code("echo <b> & done".to_string()),
+                ResponseSegment::Prose("Then:".to_string()),
+                ResponseSegment::Code("x".to_string()),
+            ]
+        );
+        assert_eq!(
+            split_code_segments("text\n```rust\nfn a() {}"),
+            vec![
+                ResponseSegment::Prose("text".to_string()),
+                ResponseSegment::Code("fn a() {}".to_string()),
+            ]
+        );
+        assert_eq!(
+            split_code_segments("no code"),
+            vec![ResponseSegment::Prose("no code".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_plain_response_format_skips_conversion() {
+        let format = ResponseFormat::from_name("off");
+        assert_eq!(format, Some(ResponseFormat::Plain));
+        assert_eq!(ResponseFormat::Plain.render("**a** * b_c_"), "**a** * b_c_");
+        assert_eq!(ResponseFormat::Plain.parse_mode(), None);
+        assert_eq!(ResponseFormat::Html.parse_mode(), Some(ParseMode::Html));
+    }
+
+    #[test]
+    fn test_markdownv2_escape_special_chars() {
+        assert_eq!(
+            markdownv2_escape("a_b*c[d](e)~f`g>h#i+j-k=l|m{n}o.p!q\\r"),
+            "a\\_b\\*c\\[d\\]\\(e\\)\\~f\\`g\\>h\\#i\\+j\\-k\\=l\\|m\\{n\\}o\\.p\\!q\\\\r"
+        );
+        assert_eq!(markdownv2_escape("plain 한글"), "plain 한글");
+    }
+
+    #[test]
+    fn test_strip_ansi() {
+        assert_eq!(strip_ansi("plain text"), "plain text");
+        assert_eq!(
+            strip_ansi("\x1b[1;32m   Compiling\x1b[0m foo v0.1.0"),
+            "   Compiling foo v0.1.0"
+        );
+        assert_eq!(
+            strip_ansi("\x1b[01;34msrc\x1b[0m\n\x1b[Kmain.rs"),
+            "src\nmain.rs"
+        );
+        assert_eq!(strip_ansi("\x1b]0;title\x07after"), "after");
+        assert_eq!(
+            strip_ansi("\x1b]8;;http://x\x1b\\link\x1b]8;;\x1b\\"),
+            "link"
+        );
+        assert_eq!(strip_ansi("\x1b(Bok\x1b="), "ok");
+        assert_eq!(strip_ansi("한글 \x1b[31m빨강\x1b[m"), "한글 빨강");
+    }
+
+    #[test]
+    fn test_markdownv2_inline_formatting() {
+        assert_eq!(
+            markdown_to_telegram_markdownv2("**Done.** see *file_a.rs* or `a_b.c`"),
+            "*Done\\.* see _file\\_a\\.rs_ or `a_b.c`"
+        );
+        assert_eq!(
+            markdown_to_telegram_markdownv2("# Title (v1.0)\n- item 1.\n"),
+            "*Title \\(v1\\.0\\)*\n• item 1\\."
+        );
+    }
+
+    #[test]
+    fn test_markdownv2_code_block_escapes_only_backtick_and_backslash() {
+        assert_eq!(
+            markdown_to_telegram_markdownv2("```rust\nlet s = \"a.b\\n\"; // `x`\n```"),
+            "```\nlet s = \"a.b\\\\n\"; // \\`x\\`\n```"
+        );
+    }
+
+    #[test]
+    fn test_split_message_respects_entity_limit() {
+        let text: String = (0..150)
+            .map(|i| format!("value <code>x{i}</code> "))
+            .collect();
+        assert!(text.len() < TELEGRAM_MSG_LIMIT);
+
+        let chunks = split_message(&text, Some(ParseMode::Html));
+        assert_eq!(chunks.len(), 2);
+        for chunk in &chunks {
+            let opened = chunk.matches("<code>").count();
+            assert!(opened <= TELEGRAM_ENTITY_LIMIT);
+            assert_eq!(opened, chunk.matches("</code>").count());
+        }
+        assert_eq!(chunks.concat(), text);
+
+        // Plain text is only split by length
+        assert_eq!(split_message(&text, None).len(), 1);
+        // Nested tags are not split apart
+        let nested = "<b><code>a</code></b> ".repeat(60);
+        for chunk in split_message(&nested, Some(ParseMode::Html)) {
+            assert_eq!(chunk.matches("<b>").count(), chunk.matches("</b>").count());
+        }
+    }
+
+    #[test]
+    fn test_split_message_keeps_inline_tags_whole() {
+        // The last newline before the length limit falls inside <b>...</b>
+        let text = format!(
+            "yyyy\n{}",
+            "intro <b>bold\nstill bold</b> end\n".repeat(200)
+        );
+        let chunks = split_message(&text, Some(ParseMode::Html));
+        assert!(chunks.len() > 1);
+        for chunk in &chunks {
+            assert!(chunk.len() <= TELEGRAM_MSG_LIMIT);
+            assert_eq!(chunk.matches("<b>").count(), chunk.matches("</b>").count());
+        }
+
+        // Without a usable newline, the cut still avoids tags and entities
+        assert_eq!(html_split_point("a &amp; <code>b\nc</code>"), Some(8));
+        assert_eq!(html_split_point("<pre>a\nb"), Some(6));
+        assert_eq!(html_split_point("<b>a\nb"), None);
+    }
+
+    #[test]
+    fn test_code_block_open_after_split() {
+        let mdv2 = Some(ParseMode::MarkdownV2);
+        assert!(code_block_open_after("text\n```\ncode", mdv2, false));
+        assert!(!code_block_open_after("more code\n```\ntext", mdv2, true));
+        assert!(code_block_open_after("still code", mdv2, true));
+
+        let html = Some(ParseMode::Html);
+        assert!(code_block_open_after("<pre>code", html, false));
+        assert!(!code_block_open_after("code</pre>", html, true));
+    }
+}
@@ -0,0 +1,733 @@
+use std::sync::atomic::Ordering;
+use std::sync::{mpsc, Arc};
+
+use teloxide::prelude::*;
+use teloxide::types::{
+    CallbackQuery, ChatAction, InlineKeyboardButton, InlineKeyboardMarkup, MessageId,
+};
+
+use crate::codex::{self, BackendOverrides, CancelToken, StreamMessage};
+use crate::i18n;
+use crate::session::{enforce_history_cap, HistoryItem, HistoryType, MAX_HISTORY_ITEMS};
+
+use super::bot::{
+    get_allowed_tools, is_current_request, is_owner, SharedData, SharedState, TELEGRAM_MSG_LIMIT,
+};
+use super::settings::ChatToggle;
+use super::storage::save_session_to_file;
+use super::streaming::{
+    progress_text, shared_rate_limit_wait, truncate_str, MIN_SEND_GAP, PROGRESS_SPINNER,
+};
+
+/// Callback data prefix routed to this module
+pub(super) const SUMMARY_CALLBACK_PREFIX: &str = "summary:";
+
+/// Marks a history item that stands in for summarized earlier conversation
+pub(super) const SUMMARY_MARKER: &str = "[Conversation summary]";
+
+/// Instruction sent to the backend ahead of the transcript
+const SUMMARY_PROMPT: &str = "Summarize the conversation transcript below so it can replace \
+     the transcript as context for continuing the work. Keep decisions made, files and commands \
+     involved, open questions and the current state of the task. Be concise, use plain text, \
+     and do not use any tools.";
+
+/// Tools the summarizer may use. The transcript can contain untrusted text, so the call is
+/// limited to read-only tools.
+const SUMMARY_TOOLS: &[&str] = &["Read", "Glob", "Grep"];
+
+/// Tool list and backend overrides for a summary call in this chat, built like a normal
+/// prompt's: the read-only SUMMARY_TOOLS the chat also allows, the chat's /madmax mode,
+/// /backendargs and the project's model
+fn summary_backend(data: &SharedData, chat_id: ChatId) -> (Vec<String>, BackendOverrides) {
+    let allowed = get_allowed_tools(data, chat_id);
+    let tools = SUMMARY_TOOLS
+        .iter()
+        .filter(|tool| allowed.iter().any(|a| a == *tool))
+        .map(|tool| tool.to_string())
+        .collect();
+    let overrides = BackendOverrides {
+        model: data
+            .sessions
+            .get(&chat_id)
+            .and_then(|s| s.project_config.as_ref())
+            .and_then(|c| c.model.clone()),
+        madmax: Some(ChatToggle::Madmax.is_enabled(&data.settings, chat_id)),
+        extra_args: data
+            .settings
+            .backend_args
+            .get(&chat_id.0.to_string())
+            .cloned()
+            .unwrap_or_default(),
+        ..BackendOverrides::default()
+    };
+    (tools, overrides)
+}
+
+/// Transcripts longer than this keep only their most recent part
+const MAX_TRANSCRIPT_LEN: usize = 60_000;
+
+/// History length at which /compact on folds the oldest items into a summary.
+/// Kept below the cap so nothing is dropped while the summary is generated.
+const AUTO_COMPACT_THRESHOLD: usize = MAX_HISTORY_ITEMS - 10;
+
+/// Number of oldest history items replaced by one summary item per compaction
+const AUTO_COMPACT_CHUNK: usize = MAX_HISTORY_ITEMS / 2;
+
+/// A summary awaiting confirmation before it replaces the chat's history
+pub(super) struct PendingSummary {
+    /// Message carrying the Replace/Keep buttons (older buttons are rejected)
+    pub message_id: MessageId,
+    /// Leading history items the summary covers, checked again before they are replaced
+    pub covered: Vec<HistoryItem>,
+    pub summary: String,
+}
+
+/// Recaps prepended to a fresh session (/recap) keep only this much of the latest history
+const MAX_RECAP_LEN: usize = 8_000;
+
+/// Render history items as a plain transcript, dropping the oldest lines beyond `max_len`
+fn build_transcript(history: &[HistoryItem], max_len: usize) -> String {
+    let mut lines: Vec<String> = Vec::new();
+    let mut len = 0;
+    for item in history.iter().rev() {
+        let role = match item.item_type {
+            HistoryType::User => "User",
+            HistoryType::Assistant => "Assistant",
+            HistoryType::Error => "Error",
+            HistoryType::System => "System",
+            HistoryType::ToolUse => "Tool call",
+            HistoryType::ToolResult => "Tool result",
+        };
+        let line = format!("{role}: {}", item.content.trim());
+        len += line.len() + 2;
+        if len > max_len && !lines.is_empty() {
+            break;
+        }
+        lines.push(line);
+    }
+    lines.reverse();
+    lines.join("\n\n")
+}
+
+/// Backend prompt asking for a summary of history items
+fn summary_prompt(history: &[HistoryItem]) -> String {
+    format!(
+        "{SUMMARY_PROMPT}\n\n--- TRANSCRIPT ---\n{}\n--- END TRANSCRIPT ---",
+        build_transcript(history, MAX_TRANSCRIPT_LEN)
+    )
+}
+
+fn non_empty_summary(summary: &str) -> Result<String, String> {
+    match summary.trim() {
+        "" => Err("The backend returned an empty summary".to_string()),
+        summary => Ok(summary.to_string()),
+    }
+}
+
+/// Summarize history items with a one-off (non-resumed) backend call. Blocking.
+/// `tools` and `overrides` come from summary_backend.
+fn summarize_history(
+    history: &[HistoryItem],
+    working_dir: &str,
+    tools: &[String],
+    overrides: &BackendOverrides,
+) -> Result<String, String> {
+    let response = codex::execute_command(
+        &summary_prompt(history),
+        None,
+        working_dir,
+        Some(tools),
+        overrides,
+    );
+    if !response.success {
+        return Err(response
+            .error
+            .unwrap_or_else(|| "Unknown backend error".to_string()));
+    }
+    non_empty_summary(response.response.as_deref().unwrap_or(""))
+}
+
+/// The backend call behind /summary
+struct SummaryRequest {
+    history: Vec<HistoryItem>,
+    working_dir: String,
+    tools: Vec<String>,
+    overrides: BackendOverrides,
+    /// Registered in `cancel_tokens`, so /stop ends the call and the busy guard sees it
+    cancel_token: Arc<CancelToken>,
+}
+
+/// Summarize history items with a streaming backend call, showing the summary as it is
+/// written in the placeholder message (same progress display as a normal prompt).
+/// Err(None) means the call was stopped.
+async fn stream_summary(
+    bot: &Bot,
+    chat_id: ChatId,
+    placeholder_id: MessageId,
+    request: SummaryRequest,
+    state: &SharedState,
+) -> Result<String, Option<String>> {
+    let prompt = summary_prompt(&request.history);
+    let cancel_token = request.cancel_token.clone();
+    let (tx, rx) = mpsc::sync_channel(codex::STREAM_CHANNEL_CAPACITY);
+    let backend = tokio::task::spawn_blocking(move || {
+        codex::execute_command_streaming(
+            &prompt,
+            None,
+            &request.working_dir,
+            tx,
+            None,
+            Some(&request.tools),
+            &request.overrides,
+            Some(request.cancel_token),
+        )
+    });
+
+    let mut summary = String::new();
+    let mut result: Option<String> = None;
+    let mut error: Option<String> = None;
+    let mut last_edit_text = String::new();
+    let mut spin_idx: usize = 0;
+    let mut done = false;
+    while !done {
+        // Polling interval (without reserving a rate limit slot)
+        tokio::time::sleep(MIN_SEND_GAP).await;
+        loop {
+            match rx.try_recv() {
+                Ok(StreamMessage::Text { content }) => summary.push_str(&content),
+                Ok(StreamMessage::Done { result: text, .. }) => result = Some(text),
+                Ok(StreamMessage::Error { message }) => error = Some(message),
+                Ok(_) => {}
+                Err(mpsc::TryRecvError::Empty) => break,
+                Err(mpsc::TryRecvError::Disconnected) => {
+                    done = true;
+                    break;
+                }
+            }
+        }
+        if done || cancel_token.cancelled.load(Ordering::Relaxed) {
+            break;
+        }
+
+        let spinner = PROGRESS_SPINNER[spin_idx % PROGRESS_SPINNER.len()];
+        spin_idx += 1;
+        let display_text = progress_text(&summary, spinner);
+        if display_text != last_edit_text {
+            shared_rate_limit_wait(state, chat_id).await;
+            if let Err(e) = bot
+                .edit_message_text(chat_id, placeholder_id, &display_text)
+                .await
+            {
+                let ts = chrono::Local::now().format("%H:%M:%S");
+                logln!("  [{ts}]   ⚠ edit_message failed (summary): {e}");
+            }
+            last_edit_text = display_text;
+        } else {
+            shared_rate_limit_wait(state, chat_id).await;
+            let _ = bot.send_chat_action(chat_id, ChatAction::Typing).await;
+        }
+    }
+
+    let backend_result = backend.await;
+    if cancel_token.cancelled.load(Ordering::Relaxed) {
+        return Err(None);
+    }
+    match backend_result {
+        Ok(Err(e)) => return Err(Some(e)),
+        Err(e) => return Err(Some(format!("Summary task failed: {e}"))),
+        Ok(Ok(())) => {}
+    }
+    if let Some(e) = error {
+        return Err(Some(e));
+    }
+    if summary.trim().is_empty() {
+        summary = result.unwrap_or_default();
+    }
+    non_empty_summary(&summary).map_err(Some)
+}
+
+/// Condensed recent conversation for a backend session that starts without the earlier
+/// context (/recap): prompts, answers and summaries only, tool traffic and errors left out.
+/// None if there is nothing to recap.
+pub(super) fn history_recap(history: &[HistoryItem]) -> Option<String> {
+    let turns: Vec<HistoryItem> = history
+        .iter()
+        .filter(|item| {
+            matches!(
+                item.item_type,
+                HistoryType::User | HistoryType::Assistant | HistoryType::System
+            )
+        })
+        .cloned()
+        .collect();
+    if turns.is_empty() {
+        return None;
+    }
+    Some(format!(
+        "[Earlier conversation] The previous session could not be continued. \
+         These are the latest turns of the conversation for context:\n\
+         --- BEGIN CONVERSATION ---\n{}\n--- END CONVERSATION ---",
+        build_transcript(&turns, MAX_RECAP_LEN)
+    ))
+}
+
+/// History item holding a summary of earlier conversation
+pub(super) fn summary_item(summary: &str) -> HistoryItem {
+    HistoryItem {
+        item_type: HistoryType::System,
+        content: format!("{SUMMARY_MARKER}\n{summary}"),
+    }
+}
+
+/// Oldest items to compact, or None while the history is below the threshold
+fn auto_compact_chunk(history: &[HistoryItem]) -> Option<&[HistoryItem]> {
+    (history.len() >= AUTO_COMPACT_THRESHOLD).then(|| &history[..AUTO_COMPACT_CHUNK])
+}
+
+/// True if `history` still starts with `chunk` (content-wise)
+fn starts_with_items(history: &[HistoryItem], chunk: &[HistoryItem]) -> bool {
+    history.len() >= chunk.len()
+        && history
+            .iter()
+            .zip(chunk)
+            .all(|(a, b)| a.content == b.content)
+}
+
+/// Replace the leading `covered` items with one summary item, keeping anything added since.
+/// Returns false (and leaves the history alone) if it no longer starts with those items.
+fn apply_summary(history: &mut Vec<HistoryItem>, covered: &[HistoryItem], summary: &str) -> bool {
+    if !starts_with_items(history, covered) {
+        return false;
+    }
+    history.splice(..covered.len(), [summary_item(summary)]);
+    true
+}
+
+/// With /compact on, summarize the oldest history items in the background once the history
+/// nears the cap, instead of letting enforce_history_cap drop them. The caller leaves the cap
+/// to this function, which applies it once the compaction has finished (or failed).
+/// At most one compaction runs per chat; the summary call itself never touches the history,
+/// so compaction cannot trigger itself.
+pub(super) async fn maybe_auto_compact(bot: &Bot, chat_id: ChatId, state: &SharedState) {
+    let job = {
+        let mut data = state.lock().await;
+        if !ChatToggle::Compact.is_enabled(&data.settings, chat_id)
+            || data.compacting.contains(&chat_id)
+        {
+            return;
+        }
+        let job = data.sessions.get(&chat_id).and_then(|s| {
+            let path = s.current_path.clone()?;
+            auto_compact_chunk(&s.history).map(|chunk| (path, chunk.to_vec()))
+        });
+        let job = job.map(|(path, chunk)| (path, chunk, summary_backend(&data, chat_id)));
+        if job.is_some() {
+            data.compacting.insert(chat_id);
+        } else if let Some(session) = data.sessions.get_mut(&chat_id) {
+            enforce_history_cap(&mut session.history);
+        }
+        job
+    };
+    let Some((current_path, chunk, (tools, overrides))) = job else {
+        return;
+    };
+
+    let bot = bot.clone();
+    let state = state.clone();
+    tokio::spawn(async move {
+        let ts = chrono::Local::now().format("%H:%M:%S");
+        logln!("  [{ts}] 🗜 Compacting {} history items", chunk.len());
+        let for_backend = chunk.clone();
+        let result = tokio::task::spawn_blocking(move || {
+            summarize_history(&for_backend, &current_path, &tools, &overrides)
+        })
+        .await
+        .unwrap_or_else(|e| Err(format!("Summary task failed: {e}")));
+
+        let applied = {
+            let mut data = state.lock().await;
+            data.compacting.remove(&chat_id);
+            match (&result, data.sessions.get_mut(&chat_id)) {
+                (Ok(summary), Some(session)) if starts_with_items(&session.history, &chunk) => {
+                    session
+                        .history
+                        .splice(..chunk.len(), [summary_item(summary)]);
+                    enforce_history_cap(&mut session.history);
+                    if let Some(path) = session.current_path.clone() {
+                        save_session_to_file(session, &path);
+                    }
+                    true
+                }
+                // Turns added while the compaction ran are capped now that it is over
+                (_, Some(session)) => {
+                    if session.history.len() > MAX_HISTORY_ITEMS {
+                        enforce_history_cap(&mut session.history);
+                        if let Some(path) = session.current_path.clone() {
+                            save_session_to_file(session, &path);
+                        }
+                    }
+                    false
+                }
+                _ => false,
+            }
+        };
+
+        let ts = chrono::Local::now().format("%H:%M:%S");
+        match result {
+            Ok(_) if applied => {
+                logln!("  [{ts}] 🗜 Compacted {} history items", chunk.len());
+                shared_rate_limit_wait(&state, chat_id).await;
+                let _ = bot
+                    .send_message(
+                        chat_id,
+                        format!(
+                            "🗜 Compacted the {} oldest history items into a summary.",
+                            chunk.len()
+                        ),
+                    )
+                    .await;
+            }
+            // History was cleared or trimmed meanwhile; a later turn retries
+            Ok(_) => logln!("  [{ts}]   ⚠ Compaction skipped (history changed)"),
+            Err(e) => logln!("  [{ts}]   ⚠ Compaction failed: {e}"),
+        }
+    });
+}
+
+/// Handle /summary command - summarize the session history via the backend and offer
+/// to replace the history with the summary. The backend call runs as the chat's request:
+/// it is refused while another one runs, and /stop ends it.
+pub(super) async fn handle_summary_command(
+    bot: &Bot,
+    chat_id: ChatId,
+    state: &SharedState,
+) -> ResponseResult<()> {
+    // Check for a running request and register this one under the same lock
+    let request = {
+        let mut data = state.lock().await;
+        let snapshot = data.sessions.get(&chat_id).and_then(|s| {
+            s.current_path
+                .clone()
+                .filter(|_| !s.history.is_empty())
+                .map(|path| (path, s.history.clone()))
+        });
+        match snapshot {
+            _ if data.cancel_tokens.contains_key(&chat_id) => Err(i18n::MSG_AI_BUSY),
+            Some((working_dir, history)) => {
+                let (tools, overrides) = summary_backend(&data, chat_id);
+                let cancel_token = Arc::new(CancelToken::new());
+                data.cancel_tokens.insert(chat_id, cancel_token.clone());
+                data.stopping.remove(&chat_id);
+                Ok(SummaryRequest {
+                    history,
+                    working_dir,
+                    tools,
+                    overrides,
+                    cancel_token,
+                })
+            }
+            None => Err("No conversation history to summarize."),
+        }
+    };
+    let request = match request {
+        Ok(request) => request,
+        Err(message) => {
+            shared_rate_limit_wait(state, chat_id).await;
+            bot.send_message(chat_id, message).await?;
+            return Ok(());
+        }
+    };
+    let cancel_token = request.cancel_token.clone();
+    let covered = request.history.clone();
+
+    shared_rate_limit_wait(state, chat_id).await;
+    let placeholder = bot
+        .send_message(
+            chat_id,
+            format!("Summarizing {} history items...", covered.len()),
+        )
+        .await;
+    let placeholder = match placeholder {
+        Ok(placeholder) => placeholder,
+        Err(e) => {
+            finish_summary_request(state, chat_id, &cancel_token).await;
+            return Err(e);
+        }
+    };
+
+    // Run in the background so the chat's worker stays free for /stop
+    let bot = bot.clone();
+    let state = state.clone();
+    tokio::spawn(async move {
+        let result = stream_summary(&bot, chat_id, placeholder.id, request, &state).await;
+        if let Some(stop_msg_id) = finish_summary_request(&state, chat_id, &cancel_token).await {
+            shared_rate_limit_wait(&state, chat_id).await;
+            let _ = bot.delete_message(chat_id, stop_msg_id).await;
+        }
+        if let Err(e) = offer_summary(&bot, chat_id, placeholder.id, covered, result, &state).await
+        {
+            let ts = chrono::Local::now().format("%H:%M:%S");
+            logln!("  [{ts}]   ⚠ /summary failed to send: {e}");
+        }
+    });
+
+    Ok(())
+}
+
+/// Unregister a finished /summary call (unless a newer request replaced it) and take the
+/// "Stopping..." message /stop left for it
+async fn finish_summary_request(
+    state: &SharedState,
+    chat_id: ChatId,
+    cancel_token: &Arc<CancelToken>,
+) -> Option<MessageId> {
+    let mut data = state.lock().await;
+    if !is_current_request(&data, chat_id, cancel_token) {
+        return None;
+    }
+    data.cancel_tokens.remove(&chat_id);
+    if !data.shell_pids.contains_key(&chat_id) {
+        data.stopping.remove(&chat_id);
+    }
+    data.stop_message_ids.remove(&chat_id)
+}
+
+/// Show the finished summary with Replace/Keep buttons, or why there is none
+async fn offer_summary(
+    bot: &Bot,
+    chat_id: ChatId,
+    placeholder_id: MessageId,
+    covered: Vec<HistoryItem>,
+    result: Result<String, Option<String>>,
+    state: &SharedState,
+) -> ResponseResult<()> {
+    let summary = match result {
+        Ok(summary) => summary,
+        Err(e) => {
+            let ts = chrono::Local::now().format("%H:%M:%S");
+            let text = match e {
+                Some(e) => {
+                    logln!("  [{ts}]   ⚠ /summary failed: {e}");
+                    format!("Summary failed: {}", truncate_str(&e, 1000))
+                }
+                None => {
+                    logln!("  [{ts}] ■ /summary stopped");
+                    "Summary stopped.".to_string()
+                }
+            };
+            shared_rate_limit_wait(state, chat_id).await;
+            bot.edit_message_text(chat_id, placeholder_id, text).await?;
+            return Ok(());
+        }
+    };
+
+    let header = format!("Summary of {} history items:\n\n", covered.len());
+    let footer = "\n\nReplace the history with this summary?";
+    let shown = truncate_str(
+        &summary,
+        TELEGRAM_MSG_LIMIT - header.len() - footer.len() - 16,
+    );
+    let keyboard = InlineKeyboardMarkup::new(vec![vec![
+        InlineKeyboardButton::callback(
+            "Replace history",
+            format!("{SUMMARY_CALLBACK_PREFIX}apply"),
+        ),
+        InlineKeyboardButton::callback("Keep history", format!("{SUMMARY_CALLBACK_PREFIX}discard")),
+    ]]);
+
+    {
+        let mut data = state.lock().await;
+        data.pending_summaries.insert(
+            chat_id,
+            PendingSummary {
+                message_id: placeholder_id,
+                covered,
+                summary,
+            },
+        );
+    }
+
+    shared_rate_limit_wait(state, chat_id).await;
+    bot.edit_message_text(chat_id, placeholder_id, format!("{header}{shown}{footer}"))
+        .reply_markup(keyboard)
+        .await?;
+
+    Ok(())
+}
+
+/// Handle a Replace/Keep press (owner-only). `data` is `apply` or `discard`.
+pub(super) async fn handle_summary_callback(
+    bot: &Bot,
+    query: &CallbackQuery,
+    data: &str,
+    state: &SharedState,
+) -> ResponseResult<()> {
+    let Some(message) = query.message.as_ref() else {
+        bot.answer_callback_query(&query.id).await?;
+        return Ok(());
+    };
+    let chat_id = message.chat().id;
+
+    let outcome = {
+        let mut shared = state.lock().await;
+        if !is_owner(&shared.settings, query.from.id.0) {
+            Err("Only the bot owner can replace the history.")
+        } else if shared.cancel_tokens.contains_key(&chat_id) {
+            Err(i18n::MSG_AI_BUSY)
+        } else {
+            match shared.pending_summaries.remove(&chat_id) {
+                Some(pending) if pending.message_id == message.id() => {
+                    if data != "apply" {
+                        Ok("History kept.".to_string())
+                    } else {
+                        // Items added while summarizing are kept after the summary
+                        match shared.sessions.get_mut(&chat_id) {
+                            Some(session)
+                                if starts_with_items(&session.history, &pending.covered) =>
+                            {
+                                apply_summary(
+                                    &mut session.history,
+                                    &pending.covered,
+                                    &pending.summary,
+                                );
+                                if let Some(path) = session.current_path.clone() {
+                                    save_session_to_file(session, &path);
+                                }
+                                Ok(format!(
+                                    "History replaced: {} items condensed into a summary.",
+                                    pending.covered.len()
+                                ))
+                            }
+                            _ => Err("The history changed; summary discarded."),
+                        }
+                    }
+                }
+                Some(pending) => {
+                    shared.pending_summaries.insert(chat_id, pending);
+                    Err("This summary has expired.")
+                }
+                None => Err("This summary has expired."),
+            }
+        }
+    };
+
+    match outcome {
+        Ok(result) => {
+            bot.answer_callback_query(&query.id).await?;
+            let ts = chrono::Local::now().format("%H:%M:%S");
+            logln!("  [{ts}] ◀ [button] /summary {data}");
+            // Drop the buttons but keep the summary text visible
+            let text = message
+                .regular_message()
+                .and_then(|m| m.text())
+                .and_then(|t| t.strip_suffix("Replace the history with this summary?"))
+                .map(|t| format!("{t}{result}"))
+                .unwrap_or(result);
+            shared_rate_limit_wait(state, chat_id).await;
+            if let Err(e) = bot.edit_message_text(chat_id, message.id(), text).await {
+                logln!("  [{ts}]   ⚠ edit_message failed (summary): {e}");
+            }
+        }
+        Err(reason) => {
+            bot.answer_callback_query(&query.id).text(reason).await?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(item_type: HistoryType, content: &str) -> HistoryItem {
+        HistoryItem {
+            item_type,
+            content: content.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_build_transcript_keeps_latest_items() {
+        let history = vec![
+            item(HistoryType::User, "hi"),
+            item(HistoryType::Assistant, " hello \n"),
+        ];
+        assert_eq!(
+            build_transcript(&history, MAX_TRANSCRIPT_LEN),
+            "User: hi\n\nAssistant: hello"
+        );
+
+        let long = "x".repeat(MAX_TRANSCRIPT_LEN / 2);
+        let history = vec![
+            item(HistoryType::User, "first"),
+            item(HistoryType::User, &long),
+            item(HistoryType::Assistant, &long),
+        ];
+        let transcript = build_transcript(&history, MAX_TRANSCRIPT_LEN);
+        assert!(!transcript.contains("first"));
+        assert!(transcript.starts_with("Assistant: x"));
+
+        assert!(summary_item("s").content.starts_with(SUMMARY_MARKER));
+    }
+
+    #[test]
+    fn test_history_recap() {
+        assert_eq!(history_recap(&[]), None);
+        assert_eq!(history_recap(&[item(HistoryType::ToolUse, "ls")]), None);
+
+        let long = "x".repeat(MAX_RECAP_LEN);
+        let history = vec![
+            item(HistoryType::User, &long),
+            item(HistoryType::User, "fix the parser"),
+            item(HistoryType::ToolUse, "cat parser.rs"),
+            item(HistoryType::Error, "[10:00:00] timeout"),
+            item(HistoryType::Assistant, "Done."),
+        ];
+        let recap = history_recap(&history).unwrap_or_default();
+        assert!(recap.contains("User: fix the parser\n\nAssistant: Done.\n--- END"));
+        assert!(!recap.contains("cat parser.rs"));
+        assert!(!recap.contains("timeout"));
+        assert!(!recap.contains(&long));
+    }
+
+    #[test]
+    fn test_auto_compact_chunk() {
+        let history: Vec<HistoryItem> = (0..AUTO_COMPACT_THRESHOLD)
+            .map(|i| item(HistoryType::User, &i.to_string()))
+            .collect();
+        assert!(auto_compact_chunk(&history[..AUTO_COMPACT_THRESHOLD - 1]).is_none());
+        let chunk = auto_compact_chunk(&history).unwrap_or_default();
+        assert_eq!(chunk.len(), AUTO_COMPACT_CHUNK);
+        assert!(starts_with_items(&history, chunk));
+
+        // After compaction the history is far below the threshold again
+        let mut compacted = history.clone();
+        compacted.splice(..chunk.len(), [summary_item("s")]);
+        assert!(auto_compact_chunk(&compacted).is_none());
+        assert!(!starts_with_items(&compacted, chunk));
+    }
+
+    #[test]
+    fn test_apply_summary_checks_covered_items() {
+        let covered = vec![
+            item(HistoryType::User, "a"),
+            item(HistoryType::Assistant, "b"),
+        ];
+        // New turns after the proposal are kept behind the summary
+        let mut history = covered.clone();
+        history.push(item(HistoryType::User, "c"));
+        assert!(apply_summary(&mut history, &covered, "s"));
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[1].content, "c");
+
+        // The history cap dropped the oldest item and a new turn arrived: same length,
+        // different items, so nothing is replaced
+        let mut shifted = vec![
+            item(HistoryType::Assistant, "b"),
+            item(HistoryType::User, "c"),
+        ];
+        assert!(!apply_summary(&mut shifted, &covered, "s"));
+        assert_eq!(shifted[0].content, "b");
+    }
+}
@@ -3,7 +3,9 @@ use teloxide::types::ParseMode;
 
 use crate::codex::DEFAULT_ALLOWED_TOOLS;
 
-use super::bot::SharedState;
+use super::bot::{get_allowed_tools, is_public_chat, SharedState};
+use super::paginate::{send_paged_list, ListContent, PagedList};
+use super::preset_share::handle_preset_share;
 use super::storage::save_bot_settings;
 use super::streaming::{html_escape, send_long_message, shared_rate_limit_wait};
 
@@ -57,6 +59,41 @@ pub(super) const ALL_TOOLS: &[(&str, &str, bool)] = &[
     ("ExitPlanMode", "Exit planning mode (interactive)", false),
 ];
 
+/// Named tool sets applied with /preset: (name, description, tools)
+pub(super) const TOOL_PRESETS: &[(&str, &str, &[&str])] = &[
+    (
+        "readonly",
+        "Read-only review: read and search files only",
+        &["Read", "Grep", "Glob"],
+    ),
+    (
+        "dev",
+        "Full development: the default tool list",
+        DEFAULT_ALLOWED_TOOLS,
+    ),
+    (
+        "safe",
+        "Defaults without Bash and Write",
+        &[
+            "Read",
+            "Edit",
+            "Glob",
+            "Grep",
+            "Task",
+            "TaskOutput",
+            "TaskStop",
+            "WebFetch",
+            "WebSearch",
+            "NotebookEdit",
+            "Skill",
+            "TaskCreate",
+            "TaskGet",
+            "TaskUpdate",
+            "TaskList",
+        ],
+    ),
+];
+
 /// Tool info: (description, is_destructive)
 pub(super) fn tool_info(name: &str) -> (&'static str, bool) {
     ALL_TOOLS
@@ -75,37 +112,72 @@ pub(super) fn risk_badge(destructive: bool) -> &'static str {
     }
 }
 
+/// Format one tool as an HTML list line: name, risk badge and description
+fn tool_line(name: &str, desc: &str, destructive: bool) -> String {
+    let badge = risk_badge(destructive);
+    if badge.is_empty() {
+        format!("<code>{}</code> — {}", html_escape(name), html_escape(desc))
+    } else {
+        format!(
+            "<code>{}</code> {} — {}",
+            html_escape(name),
+            badge,
+            html_escape(desc)
+        )
+    }
+}
+
+fn tools_footer(total: usize) -> String {
+    format!("{} = destructive\nTotal: {}", risk_badge(true), total)
+}
+
+/// Content of the /availabletools list
+pub(super) fn available_tools_content() -> ListContent {
+    ListContent {
+        title: "Available Tools".to_string(),
+        items: ALL_TOOLS
+            .iter()
+            .map(|&(name, desc, destructive)| tool_line(name, desc, destructive))
+            .collect(),
+        footer: tools_footer(ALL_TOOLS.len()),
+    }
+}
+
+/// Content of the /allowedtools list
+pub(super) fn allowed_tools_content(tools: &[String]) -> ListContent {
+    ListContent {
+        title: "Allowed Tools".to_string(),
+        items: tools
+            .iter()
+            .map(|tool| {
+                let (desc, destructive) = tool_info(tool);
+                tool_line(tool, desc, destructive)
+            })
+            .collect(),
+        footer: tools_footer(tools.len()),
+    }
+}
+
 /// Handle /availabletools command - show all available tools
+/// Usage: /availabletools [all]  (paginated by default, `all` sends the full list at once)
 pub(super) async fn handle_availabletools_command(
     bot: &Bot,
     chat_id: ChatId,
+    text: &str,
     state: &SharedState,
 ) -> ResponseResult<()> {
-    let mut msg = String::from("<b>Available Tools</b>\n\n");
-
-    for &(name, desc, destructive) in ALL_TOOLS {
-        let badge = risk_badge(destructive);
-        if badge.is_empty() {
-            msg.push_str(&format!(
-                "<code>{}</code> — {}\n",
-                html_escape(name),
-                html_escape(desc)
-            ));
-        } else {
-            msg.push_str(&format!(
-                "<code>{}</code> {} — {}\n",
-                html_escape(name),
-                badge,
-                html_escape(desc)
-            ));
-        }
+    let arg = text.strip_prefix("/availabletools").unwrap_or("").trim();
+    if arg != "all" {
+        return send_paged_list(bot, chat_id, PagedList::AvailableTools, state).await;
     }
-    msg.push_str(&format!(
-        "\n{} = destructive\nTotal: {}",
-        risk_badge(true),
-        ALL_TOOLS.len()
-    ));
 
+    let content = available_tools_content();
+    let msg = format!(
+        "<b>{}</b>\n\n{}\n\n{}",
+        content.title,
+        content.items.join("\n"),
+        content.footer
+    );
     send_long_message(bot, chat_id, &msg, Some(ParseMode::Html), state).await?;
 
     Ok(())
@@ -117,42 +189,7 @@ pub(super) async fn handle_allowedtools_command(
     chat_id: ChatId,
     state: &SharedState,
 ) -> ResponseResult<()> {
-    let tools = {
-        let data = state.lock().await;
-        super::bot::get_allowed_tools(&data.settings, chat_id)
-    };
-
-    let mut msg = String::from("<b>Allowed Tools</b>\n\n");
-    for tool in &tools {
-        let (desc, destructive) = tool_info(tool);
-        let badge = risk_badge(destructive);
-        if badge.is_empty() {
-            msg.push_str(&format!(
-                "<code>{}</code> — {}\n",
-                html_escape(tool),
-                html_escape(desc)
-            ));
-        } else {
-            msg.push_str(&format!(
-                "<code>{}</code> {} — {}\n",
-                html_escape(tool),
-                badge,
-                html_escape(desc)
-            ));
-        }
-    }
-    msg.push_str(&format!(
-        "\n{} = destructive\nTotal: {}",
-        risk_badge(true),
-        tools.len()
-    ));
-
-    shared_rate_limit_wait(state, chat_id).await;
-    bot.send_message(chat_id, &msg)
-        .parse_mode(ParseMode::Html)
-        .await?;
-
-    Ok(())
+    send_paged_list(bot, chat_id, PagedList::AllowedTools, state).await
 }
 
 /// Handle /allowed command - add/remove tools
@@ -206,12 +243,9 @@ pub(super) async fn handle_allowed_command(
     let response_msg = {
         let mut data = state.lock().await;
         let chat_key = chat_id.0.to_string();
-        // Ensure this chat has its own tool list (initialize from defaults if missing)
+        // Ensure this chat has its own tool list (initialize from the effective list if missing)
         if !data.settings.allowed_tools.contains_key(&chat_key) {
-            let defaults: Vec<String> = DEFAULT_ALLOWED_TOOLS
-                .iter()
-                .map(|s| s.to_string())
-                .collect();
+            let defaults = get_allowed_tools(&data, chat_id);
             data.settings
                 .allowed_tools
                 .insert(chat_key.clone(), defaults);
@@ -255,3 +289,215 @@ pub(super) async fn handle_allowed_command(
 
     Ok(())
 }
+
+/// Handle /preset command - replace the chat's allowed tools with a named preset, or share
+/// the chat's settings with another chat
+/// Usage: /preset <name>  |  /preset list  |  /preset export  |  /preset import <preset>
+pub(super) async fn handle_preset_command(
+    bot: &Bot,
+    chat_id: ChatId,
+    text: &str,
+    state: &SharedState,
+    token: &str,
+) -> ResponseResult<()> {
+    let raw_arg = text.strip_prefix("/preset").unwrap_or("").trim();
+    let (sub, rest) = raw_arg
+        .split_once(char::is_whitespace)
+        .unwrap_or((raw_arg, ""));
+    if sub == "export" || sub == "import" {
+        return handle_preset_share(bot, chat_id, sub == "export", rest.trim(), state, token).await;
+    }
+    let arg = raw_arg.to_lowercase();
+
+    let preset = TOOL_PRESETS.iter().find(|(name, _, _)| *name == arg);
+    let response_msg = match preset {
+        Some((name, _, tools)) => {
+            let tools: Vec<String> = tools.iter().map(|t| t.to_string()).collect();
+            let list = tools
+                .iter()
+                .map(|t| format!("<code>{}</code>", html_escape(t)))
+                .collect::<Vec<_>>()
+                .join(", ");
+            let mut data = state.lock().await;
+            data.settings
+                .allowed_tools
+                .insert(chat_id.0.to_string(), tools);
+            save_bot_settings(token, &data.settings);
+            format!("Applied preset <b>{name}</b>. Allowed tools:\n{list}")
+        }
+        None => {
+            let mut lines = Vec::new();
+            if !arg.is_empty() && arg != "list" {
+                lines.push(format!(
+                    "Unknown preset: <code>{}</code>\n",
+                    html_escape(&arg)
+                ));
+            }
+            lines.push("<b>Tool presets</b>".to_string());
+            for (name, desc, tools) in TOOL_PRESETS {
+                lines.push(format!(
+                    "\n<code>/preset {name}</code> — {}\n{}",
+                    html_escape(desc),
+                    html_escape(&tools.join(", "))
+                ));
+            }
+            lines.push(
+                "\n<code>/preset export</code> — Share this chat's tools, prefix/suffix and \
+                 response cap\n<code>/preset import &lt;preset&gt;</code> — Apply a shared preset"
+                    .to_string(),
+            );
+            lines.join("\n")
+        }
+    };
+
+    shared_rate_limit_wait(state, chat_id).await;
+    bot.send_message(chat_id, &response_msg)
+        .parse_mode(ParseMode::Html)
+        .await?;
+
+    Ok(())
+}
+
+/// Allowed tools sorted for /toolscheck: names not in ALL_TOOLS, and enabled destructive ones.
+/// MCP tools (`mcp__server__tool`) come from the backend's configuration and are not unknown.
+fn audit_tools(tools: &[String]) -> (Vec<String>, Vec<String>) {
+    let mut unknown = Vec::new();
+    let mut destructive = Vec::new();
+    for tool in tools {
+        match ALL_TOOLS.iter().find(|(name, _, _)| name == tool) {
+            Some((_, _, true)) => destructive.push(tool.clone()),
+            Some(_) => {}
+            None if tool.to_lowercase().starts_with("mcp__") => {}
+            None => unknown.push(tool.clone()),
+        }
+    }
+    (unknown, destructive)
+}
+
+/// Handle /toolscheck command - audit the chat's allowed tools
+/// Usage: /toolscheck        (report unknown and destructive tools)
+///        /toolscheck prune  (remove the unknown entries)
+pub(super) async fn handle_toolscheck_command(
+    bot: &Bot,
+    chat_id: ChatId,
+    text: &str,
+    state: &SharedState,
+    token: &str,
+) -> ResponseResult<()> {
+    let arg = text.strip_prefix("/toolscheck").unwrap_or("").trim();
+    let code_list = |tools: &[String]| {
+        tools
+            .iter()
+            .map(|t| format!("<code>{}</code>", html_escape(t)))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+
+    let response_msg = {
+        let mut data = state.lock().await;
+        let chat_key = chat_id.0.to_string();
+        let own_list = data.settings.allowed_tools.contains_key(&chat_key);
+        let tools = get_allowed_tools(&data, chat_id);
+        let (unknown, destructive) = audit_tools(&tools);
+
+        if arg == "prune" {
+            if unknown.is_empty() {
+                "No unknown tools to remove.".to_string()
+            } else if !own_list {
+                // The list comes from .opencodex.toml (defaults never contain unknown tools)
+                format!(
+                    "The unknown tools come from the project's <code>allowed_tools</code>; \
+                     edit <code>.opencodex.toml</code> to remove them: {}",
+                    code_list(&unknown)
+                )
+            } else {
+                if let Some(list) = data.settings.allowed_tools.get_mut(&chat_key) {
+                    list.retain(|t| !unknown.contains(t));
+                }
+                save_bot_settings(token, &data.settings);
+                format!("Removed {}", code_list(&unknown))
+            }
+        } else {
+            let source = if own_list {
+                "this chat's list"
+            } else if data
+                .sessions
+                .get(&chat_id)
+                .and_then(|s| s.project_config.as_ref())
+                .is_some_and(|c| c.allowed_tools.is_some())
+            {
+                "the project's .opencodex.toml"
+            } else {
+                "the defaults"
+            };
+            let is_public = is_public_chat(&data.settings, chat_id);
+            let mut lines = vec![format!(
+                "<b>Tools check</b> — {} allowed tool(s) from {source}",
+                tools.len()
+            )];
+            if unknown.is_empty() {
+                lines.push("✅ No unknown tools".to_string());
+            } else {
+                lines.push(format!(
+                    "⚠ Unknown (removed or renamed?): {}\n<code>/toolscheck prune</code> — Remove them",
+                    code_list(&unknown)
+                ));
+            }
+            if destructive.is_empty() {
+                lines.push("✅ No destructive tools enabled".to_string());
+            } else {
+                lines.push(format!(
+                    "{} Destructive: {}{}",
+                    risk_badge(true),
+                    code_list(&destructive),
+                    if is_public {
+                        "\nThis chat is public: every group member can use them."
+                    } else {
+                        ""
+                    }
+                ));
+            }
+            lines.join("\n\n")
+        }
+    };
+
+    shared_rate_limit_wait(state, chat_id).await;
+    bot.send_message(chat_id, &response_msg)
+        .parse_mode(ParseMode::Html)
+        .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_audit_tools() {
+        let tools: Vec<String> = ["Read", "Bash", "Browse", "mcp__github__search", "Write"]
+            .iter()
+            .map(|t| t.to_string())
+            .collect();
+        let (unknown, destructive) = audit_tools(&tools);
+        assert_eq!(unknown, vec!["Browse".to_string()]);
+        assert_eq!(destructive, vec!["Bash".to_string(), "Write".to_string()]);
+    }
+
+    #[test]
+    fn test_tool_presets_use_known_tools() {
+        for (name, _, tools) in TOOL_PRESETS {
+            assert!(!tools.is_empty(), "{name}");
+            for tool in *tools {
+                assert!(
+                    ALL_TOOLS.iter().any(|(n, _, _)| n == tool),
+                    "{name}: {tool}"
+                );
+            }
+        }
+        let safe = TOOL_PRESETS.iter().find(|(n, _, _)| *n == "safe");
+        assert!(
+            safe.is_some_and(|(_, _, tools)| !tools.contains(&"Bash") && !tools.contains(&"Write"))
+        );
+    }
+}
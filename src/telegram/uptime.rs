@@ -0,0 +1,56 @@
+use std::time::Duration;
+
+use teloxide::prelude::*;
+
+use super::bot::SharedState;
+use super::streaming::shared_rate_limit_wait;
+
+/// Uptime as days, hours and minutes, leaving out leading zero units
+fn format_uptime(uptime: Duration) -> String {
+    let minutes = uptime.as_secs() / 60;
+    let (days, hours, minutes) = (minutes / 1440, minutes / 60 % 24, minutes % 60);
+    if days > 0 {
+        format!("{days}d {hours}h {minutes}m")
+    } else if hours > 0 {
+        format!("{hours}h {minutes}m")
+    } else {
+        format!("{minutes}m")
+    }
+}
+
+/// Handle /uptime command - how long the bot process has been running
+pub(super) async fn handle_uptime_command(
+    bot: &Bot,
+    chat_id: ChatId,
+    state: &SharedState,
+) -> ResponseResult<()> {
+    let uptime = state.lock().await.started_at.elapsed();
+    let since = chrono::Local::now()
+        - chrono::Duration::from_std(uptime).unwrap_or_else(|_| chrono::Duration::zero());
+    let message = format!(
+        "Uptime: {} (since {})",
+        format_uptime(uptime),
+        since.format("%Y-%m-%d %H:%M:%S")
+    );
+
+    shared_rate_limit_wait(state, chat_id).await;
+    bot.send_message(chat_id, message).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_uptime() {
+        assert_eq!(format_uptime(Duration::from_secs(59)), "0m");
+        assert_eq!(format_uptime(Duration::from_secs(3 * 60 + 5)), "3m");
+        assert_eq!(format_uptime(Duration::from_secs(2 * 3600 + 60)), "2h 1m");
+        assert_eq!(
+            format_uptime(Duration::from_secs(3 * 86400 + 5 * 3600 + 7 * 60)),
+            "3d 5h 7m"
+        );
+        assert_eq!(format_uptime(Duration::from_secs(86400)), "1d 0h 0m");
+    }
+}
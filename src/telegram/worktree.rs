@@ -0,0 +1,298 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use teloxide::prelude::*;
+use teloxide::types::ParseMode;
+
+use crate::i18n;
+
+use super::bot::SharedState;
+use super::project_config::load_project_config_with_note;
+use super::storage::save_bot_settings;
+use super::streaming::{html_escape, shared_rate_limit_wait};
+
+/// Directory holding worktrees created via /worktree: ~/<app_dir>/worktrees/<repo>/<branch>
+fn worktrees_root() -> Option<PathBuf> {
+    crate::app::data_dir().map(|d| d.join("worktrees"))
+}
+
+/// Run git in `dir` and return its trimmed stdout, or stderr as the error
+fn git(dir: &Path, args: &[&str]) -> Result<String, String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .map_err(|e| format!("failed to run git: {e}"))?;
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr).trim().to_string())
+    }
+}
+
+/// Directory name for a branch (`feature/x` → `feature-x`)
+fn branch_dir_name(branch: &str) -> String {
+    branch
+        .chars()
+        .map(|c| {
+            if c == '/' || c.is_whitespace() {
+                '-'
+            } else {
+                c
+            }
+        })
+        .collect()
+}
+
+/// Path of the main worktree: the first entry of `git worktree list --porcelain`
+fn main_worktree_from_porcelain(porcelain: &str) -> Option<&str> {
+    porcelain.lines().next()?.strip_prefix("worktree ")
+}
+
+/// Branch checked out in the worktree at `path`, from `git worktree list --porcelain`
+fn branch_from_porcelain<'a>(porcelain: &'a str, path: &str) -> Option<&'a str> {
+    porcelain
+        .split("\n\n")
+        .find(|entry| entry.lines().next() == Some(&format!("worktree {path}")))?
+        .lines()
+        .find_map(|l| l.strip_prefix("branch "))
+        .map(|b| b.strip_prefix("refs/heads/").unwrap_or(b))
+}
+
+fn canonical(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Keep the session's position inside the repository when switching between worktrees
+fn same_subdir(current: &Path, from_root: &Path, to_root: &Path) -> PathBuf {
+    let relative = current.strip_prefix(from_root).unwrap_or(Path::new(""));
+    let target = to_root.join(relative);
+    if target.is_dir() {
+        target
+    } else {
+        to_root.to_path_buf()
+    }
+}
+
+/// Create (or reuse) a worktree for `branch` off the repository containing `current_path`.
+/// Returns the directory to switch to and whether a new branch was created. Blocking.
+fn create_worktree(current_path: &str, branch: &str) -> Result<(PathBuf, bool), String> {
+    let current = canonical(Path::new(current_path));
+    if branch.starts_with('-') {
+        return Err(format!("invalid branch name: {branch}"));
+    }
+    git(&current, &["check-ref-format", "--branch", branch])
+        .map_err(|_| format!("invalid branch name: {branch}"))?;
+
+    let toplevel = PathBuf::from(git(&current, &["rev-parse", "--show-toplevel"])?);
+    let porcelain = git(&current, &["worktree", "list", "--porcelain"])?;
+    let main = main_worktree_from_porcelain(&porcelain)
+        .map(PathBuf::from)
+        .unwrap_or_else(|| toplevel.clone());
+
+    let repo_name = main
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| "repo".to_string());
+    let root = worktrees_root().ok_or("cannot determine home directory")?;
+    let target = root.join(repo_name).join(branch_dir_name(branch));
+
+    // Already created earlier: switch to it, but only if git lists it as this repository's
+    // worktree for the branch (another repository, or `feature/x` vs `feature-x`, share the path)
+    if target.exists() {
+        let listed = [target.clone(), canonical(&target)].iter().any(|path| {
+            branch_from_porcelain(&porcelain, &path.display().to_string()) == Some(branch)
+        });
+        if !listed {
+            return Err(format!(
+                "{} already exists but is not this repository's worktree for {branch}",
+                target.display()
+            ));
+        }
+        return Ok((same_subdir(&current, &toplevel, &target), false));
+    }
+    if let Some(parent) = target.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+
+    let target_str = target.display().to_string();
+    let branch_exists = git(
+        &main,
+        &[
+            "rev-parse",
+            "--verify",
+            "--quiet",
+            &format!("refs/heads/{branch}"),
+        ],
+    )
+    .is_ok();
+    if branch_exists {
+        git(&main, &["worktree", "add", &target_str, branch])?;
+    } else {
+        git(&main, &["worktree", "add", "-b", branch, &target_str])?;
+    }
+    Ok((same_subdir(&current, &toplevel, &target), !branch_exists))
+}
+
+/// Remove the /worktree-created worktree containing `current_path`.
+/// Returns the directory to switch back to and the worktree's branch. Blocking.
+fn remove_worktree(current_path: &str, force: bool) -> Result<(PathBuf, String), String> {
+    let current = canonical(Path::new(current_path));
+    let toplevel = canonical(Path::new(&git(
+        &current,
+        &["rev-parse", "--show-toplevel"],
+    )?));
+    let root = worktrees_root().ok_or("cannot determine home directory")?;
+    if !toplevel.starts_with(canonical(&root)) {
+        return Err("the session is not in a worktree created by /worktree".to_string());
+    }
+
+    let porcelain = git(&current, &["worktree", "list", "--porcelain"])?;
+    let main = main_worktree_from_porcelain(&porcelain)
+        .map(PathBuf::from)
+        .ok_or("cannot find the main worktree")?;
+    let branch = branch_from_porcelain(&porcelain, &toplevel.display().to_string())
+        .unwrap_or("(detached)")
+        .to_string();
+
+    let toplevel_str = toplevel.display().to_string();
+    let mut args = vec!["worktree", "remove"];
+    if force {
+        args.push("--force");
+    }
+    args.push(&toplevel_str);
+    git(&main, &args).map_err(|e| {
+        if force {
+            e
+        } else {
+            format!("{e}\n\nUse /worktree remove force to discard uncommitted changes.")
+        }
+    })?;
+    Ok((same_subdir(&current, &toplevel, &main), branch))
+}
+
+/// Handle /worktree command - move the session into a git worktree on its own branch,
+/// so AI edits do not touch the main working tree
+/// Usage: /worktree <branch>           (create or reuse, then switch to it)
+///        /worktree remove [force]     (delete the worktree, switch back; the branch is kept)
+pub(super) async fn handle_worktree_command(
+    bot: &Bot,
+    chat_id: ChatId,
+    text: &str,
+    state: &SharedState,
+    token: &str,
+) -> ResponseResult<()> {
+    let arg = text
+        .strip_prefix("/worktree")
+        .unwrap_or("")
+        .trim()
+        .to_string();
+
+    let current_path = {
+        let data = state.lock().await;
+        data.sessions
+            .get(&chat_id)
+            .and_then(|s| s.current_path.clone())
+    };
+    let Some(current_path) = current_path else {
+        shared_rate_limit_wait(state, chat_id).await;
+        bot.send_message(chat_id, i18n::MSG_NO_SESSION).await?;
+        return Ok(());
+    };
+
+    if arg.is_empty() {
+        shared_rate_limit_wait(state, chat_id).await;
+        bot.send_message(
+            chat_id,
+            "Usage:\n\
+             <code>/worktree &lt;branch&gt;</code> — Work in a git worktree on that branch \
+             (created from the current HEAD if it does not exist)\n\
+             <code>/worktree remove</code> — Delete the worktree and switch back (the branch is kept)",
+        )
+        .parse_mode(ParseMode::Html)
+        .await?;
+        return Ok(());
+    }
+
+    let (sub, rest) = arg.split_once(char::is_whitespace).unwrap_or((&arg, ""));
+    let removing = sub == "remove";
+    let force = rest.trim() == "force";
+    let arg_owned = arg.clone();
+    let path_owned = current_path.clone();
+    let result = tokio::task::spawn_blocking(move || {
+        if removing {
+            remove_worktree(&path_owned, force)
+                .map(|(path, branch)| (path, format!("Removed worktree (branch {branch} kept).")))
+        } else {
+            create_worktree(&path_owned, &arg_owned).map(|(path, created)| {
+                let verb = if created { "Created" } else { "Using" };
+                (path, format!("{verb} worktree for branch {arg_owned}."))
+            })
+        }
+    })
+    .await
+    .unwrap_or_else(|e| Err(format!("worktree task failed: {e}")));
+
+    let (new_path, summary) = match result {
+        Ok(ok) => ok,
+        Err(e) => {
+            shared_rate_limit_wait(state, chat_id).await;
+            bot.send_message(
+                chat_id,
+                format!("<b>Worktree error</b>\n<pre>{}</pre>", html_escape(&e)),
+            )
+            .parse_mode(ParseMode::Html)
+            .await?;
+            return Ok(());
+        }
+    };
+
+    let new_path = new_path.display().to_string();
+    let (project_config, project_note) = load_project_config_with_note(&new_path);
+    {
+        let mut data = state.lock().await;
+        if let Some(session) = data.sessions.get_mut(&chat_id) {
+            session.current_path = Some(new_path.clone());
+            session.project_config = project_config;
+        }
+        data.settings
+            .last_sessions
+            .insert(chat_id.0.to_string(), new_path.clone());
+        save_bot_settings(token, &data.settings);
+    }
+
+    let ts = chrono::Local::now().format("%H:%M:%S");
+    logln!("  [{ts}]   🌿 {summary} → {new_path}");
+
+    let mut response_msg = format!("{summary}\nChanged to: {new_path}");
+    if let Some(note) = project_note {
+        response_msg.push('\n');
+        response_msg.push_str(&note);
+    }
+    shared_rate_limit_wait(state, chat_id).await;
+    bot.send_message(chat_id, response_msg).await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_worktree_porcelain_parsing() {
+        let porcelain = "worktree /src/app\nHEAD abc\nbranch refs/heads/main\n\n\
+                         worktree /home/u/.opencodex/worktrees/app/feature-x\nHEAD def\n\
+                         branch refs/heads/feature/x\n\n\
+                         worktree /tmp/detached\nHEAD 123\ndetached";
+        assert_eq!(main_worktree_from_porcelain(porcelain), Some("/src/app"));
+        assert_eq!(
+            branch_from_porcelain(porcelain, "/home/u/.opencodex/worktrees/app/feature-x"),
+            Some("feature/x")
+        );
+        assert_eq!(branch_from_porcelain(porcelain, "/tmp/detached"), None);
+        assert_eq!(branch_from_porcelain(porcelain, "/missing"), None);
+        assert_eq!(branch_dir_name("feature/x"), "feature-x");
+    }
+}